@@ -0,0 +1,170 @@
+use crate::gui_host::GuiWindow;
+use chromabridge::state::MAX_SPECTRUM_FOLDER_DEPTH;
+use crossbeam_channel::{Receiver, Sender};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// How long the folders have to stay unchanged before a change is reported, so copying many
+/// files or a sync client catching up is picked up once.
+const QUIET_PERIOD: Duration = Duration::from_secs(1);
+
+/// Name, size and modification time of every file under the watched folders.
+type Snapshot = Vec<(PathBuf, u64, Option<SystemTime>)>;
+
+/// Polls the spectrum and noise folders and tells the settings window to relist them once a
+/// change has settled.
+///
+/// Only compares directory listings and file metadata, so no folder or file stays open between
+/// polls. Idle while the window is hidden, which relists on showing anyway. Stops when dropped.
+pub struct AssetWatcher {
+    shutdown: Arc<AtomicBool>,
+    thread: Option<thread::JoinHandle<()>>,
+    changed: Receiver<()>,
+}
+
+impl AssetWatcher {
+    pub fn spawn(spectrums_dir: PathBuf, noise_dir: PathBuf, window: Arc<GuiWindow>) -> Self {
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let shutdown_flag = Arc::clone(&shutdown);
+        let (tx, rx) = crossbeam_channel::bounded(1);
+
+        let thread = thread::spawn(move || {
+            let mut watched = WatchedFolders::default();
+            while !shutdown_flag.load(Ordering::Acquire) {
+                thread::sleep(POLL_INTERVAL);
+                if !window.is_visible() {
+                    watched = WatchedFolders::default();
+                    continue;
+                }
+                let snapshot = [
+                    snapshot(&spectrums_dir, MAX_SPECTRUM_FOLDER_DEPTH),
+                    snapshot(&noise_dir, 0),
+                ]
+                .concat();
+                if watched.poll(snapshot, Instant::now()) {
+                    notify(&tx, &window);
+                }
+            }
+        });
+
+        Self {
+            shutdown,
+            thread: Some(thread),
+            changed: rx,
+        }
+    }
+
+    /// True once after the folders changed.
+    pub fn take_changed(&self) -> bool {
+        self.changed.try_recv().is_ok()
+    }
+}
+
+impl Drop for AssetWatcher {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::Release);
+        if let Some(handle) = self.thread.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+#[derive(Default)]
+struct WatchedFolders {
+    /// `None` until the first poll after the window was shown, which takes the baseline.
+    last: Option<Snapshot>,
+    /// When the folders last changed, while that change hasn't been reported.
+    changed_at: Option<Instant>,
+}
+
+impl WatchedFolders {
+    /// True once the folders changed and then stayed put for `QUIET_PERIOD`.
+    fn poll(&mut self, snapshot: Snapshot, now: Instant) -> bool {
+        let Some(ref last) = self.last else {
+            self.last = Some(snapshot);
+            return false;
+        };
+        if *last != snapshot {
+            self.last = Some(snapshot);
+            self.changed_at = Some(now);
+            return false;
+        }
+        match self.changed_at {
+            Some(changed_at) if now.duration_since(changed_at) >= QUIET_PERIOD => {
+                self.changed_at = None;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+fn notify(tx: &Sender<()>, window: &GuiWindow) {
+    // A change not yet picked up covers this one too
+    let _ = tx.try_send(());
+    window.request_repaint();
+}
+
+/// Every file under `dir`, descending at most `depth` folders, sorted by path.
+fn snapshot(dir: &Path, depth: usize) -> Snapshot {
+    let mut files = Vec::new();
+    collect(dir, depth, &mut files);
+    files.sort();
+    files
+}
+
+fn collect(dir: &Path, depth: usize, files: &mut Snapshot) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        if metadata.is_dir() {
+            if depth > 0 {
+                collect(&entry.path(), depth - 1, files);
+            }
+            continue;
+        }
+        files.push((entry.path(), metadata.len(), metadata.modified().ok()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn files(names: &[&str]) -> Snapshot {
+        names.iter().map(|name| (PathBuf::from(name), 1, None)).collect()
+    }
+
+    #[test]
+    fn first_poll_only_takes_the_baseline() {
+        let mut watched = WatchedFolders::default();
+        let start = Instant::now();
+        assert!(!watched.poll(files(&["a.json"]), start));
+        assert!(!watched.poll(files(&["a.json"]), start + QUIET_PERIOD * 2));
+    }
+
+    #[test]
+    fn a_burst_of_changes_is_reported_once_after_it_settles() {
+        let mut watched = WatchedFolders::default();
+        let start = Instant::now();
+        let step = QUIET_PERIOD / 2;
+        watched.poll(files(&["a.json"]), start);
+
+        // Each change restarts the quiet period
+        assert!(!watched.poll(files(&["a.json", "b.json"]), start + step));
+        assert!(!watched.poll(files(&["a.json", "b.json", "c.json"]), start + step * 2));
+        assert!(!watched.poll(files(&["a.json", "b.json", "c.json"]), start + step * 3));
+        assert!(watched.poll(files(&["a.json", "b.json", "c.json"]), start + step * 4));
+
+        assert!(!watched.poll(files(&["a.json", "b.json", "c.json"]), start + step * 5));
+        assert!(!watched.poll(files(&["a.json", "b.json", "c.json"]), start + QUIET_PERIOD * 10));
+    }
+}
@@ -0,0 +1,139 @@
+//! Writing asset files so readers never see them half written.
+
+use anyhow::{Context, Result};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Tries at moving the new file into place; on Windows a reader holding the old one open for a
+/// moment makes it fail.
+const REPLACE_ATTEMPTS: u32 = 5;
+const REPLACE_RETRY_DELAY: Duration = Duration::from_millis(20);
+
+/// Writes `contents` to `path` through a temporary file in the same folder that then replaces
+/// it, so a reader sees either the old or the new file, and a crash mid-write leaves the old one.
+///
+/// The temporary file starts with a dot and ends in `.tmp`, so asset listings skip it.
+pub fn write<P: AsRef<Path>>(path: P, contents: impl AsRef<[u8]>) -> Result<()> {
+    let path = path.as_ref();
+    let temp = temp_path(path)?;
+
+    let result = write_temp(&temp, contents.as_ref()).and_then(|_| replace_retrying(&temp, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result.with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Whether `path` was modified less than `window` ago, e.g. because something is still
+/// writing it. False when its modification time can't be read.
+pub fn modified_within(path: &Path, window: Duration) -> bool {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+        .is_some_and(|age| age < window)
+}
+
+/// `.<name>.<pid>.tmp` next to `path`; the process id keeps two instances from sharing one.
+fn temp_path(path: &Path) -> Result<PathBuf> {
+    let name = path.file_name().with_context(|| format!("{} is not a file path", path.display()))?;
+    let temp_name = format!(".{}.{}.tmp", name.to_string_lossy(), std::process::id());
+    Ok(path.with_file_name(temp_name))
+}
+
+fn write_temp(temp: &Path, contents: &[u8]) -> Result<()> {
+    let mut file = fs::File::create(temp)?;
+    file.write_all(contents)?;
+    // On disk before the rename, so a crash can't leave an empty file under the real name
+    file.sync_all()?;
+    Ok(())
+}
+
+fn replace_retrying(temp: &Path, path: &Path) -> Result<()> {
+    let mut attempt = 1;
+    loop {
+        match replace(temp, path) {
+            Err(_) if attempt < REPLACE_ATTEMPTS => {
+                std::thread::sleep(REPLACE_RETRY_DELAY);
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Moves `temp` over `path` in one step. An existing file is replaced with `ReplaceFileW`, which
+/// keeps its attributes, falling back to a plain rename when that isn't possible.
+#[cfg(windows)]
+fn replace(temp: &Path, path: &Path) -> Result<()> {
+    use windows::core::HSTRING;
+    use windows::Win32::Storage::FileSystem::{ReplaceFileW, REPLACEFILE_IGNORE_MERGE_ERRORS};
+
+    if path.exists() {
+        let replaced = unsafe { ReplaceFileW(&HSTRING::from(path), &HSTRING::from(temp), None, REPLACEFILE_IGNORE_MERGE_ERRORS, None, None) };
+        if replaced.is_ok() {
+            return Ok(());
+        }
+    }
+    // MoveFileExW with MOVEFILE_REPLACE_EXISTING
+    fs::rename(temp, path)?;
+    Ok(())
+}
+
+#[cfg(not(windows))]
+fn replace(temp: &Path, path: &Path) -> Result<()> {
+    fs::rename(temp, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+
+    #[test]
+    fn replaces_the_file_and_leaves_no_temporary_file() {
+        let dir = temp_dir("atomic-replace");
+        let path = dir.join("tritan.json");
+
+        write(&path, "first").unwrap();
+        write(&path, "second").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "second");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        assert!(modified_within(&path, Duration::from_secs(60)));
+        assert!(!modified_within(&dir.join("missing.json"), Duration::from_secs(60)));
+
+        // A folder that doesn't exist fails without leaving anything behind
+        assert!(write(dir.join("missing").join("tritan.json"), "third").is_err());
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn readers_never_see_a_partial_file() {
+        let dir = temp_dir("atomic-readers");
+        let path = dir.join("tritan.json");
+        let short = "a".repeat(1000);
+        let long = "b".repeat(200_000);
+        write(&path, &short).unwrap();
+
+        let writer = {
+            let (path, short, long) = (path.clone(), short.clone(), long.clone());
+            std::thread::spawn(move || {
+                for i in 0..100 {
+                    write(&path, if i % 2 == 0 { &long } else { &short }).unwrap();
+                }
+            })
+        };
+        while !writer.is_finished() {
+            let content = fs::read_to_string(&path).unwrap();
+            assert!(content == short || content == long, "read {} bytes", content.len());
+        }
+        writer.join().unwrap();
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
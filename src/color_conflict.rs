@@ -0,0 +1,111 @@
+use chromabridge::tr;
+use std::time::Duration;
+#[cfg(windows)]
+use chromabridge::log_debug;
+#[cfg(windows)]
+use windows::{
+    Win32::Foundation::CloseHandle,
+    Win32::Graphics::Gdi::{GetDC, ReleaseDC},
+    Win32::System::Diagnostics::ToolHelp::{CreateToolhelp32Snapshot, Process32FirstW, Process32NextW, PROCESSENTRY32W, TH32CS_SNAPPROCESS},
+    Win32::UI::ColorSystem::GetDeviceGammaRamp,
+};
+
+/// How often the check repeats while the overlay runs, after the one at start.
+pub const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Strength multiplier while a color filter is detected and `ConflictBehavior::Reduce` is selected.
+pub const REDUCED_STRENGTH: f32 = 0.5;
+
+/// Largest distance from the identity gamma ramp, as a fraction of full scale, still taken for a
+/// calibration profile rather than a color filter.
+#[cfg(windows)]
+const GAMMA_TOLERANCE: f32 = 0.1;
+
+/// Another app changing the screen's colors, which the correction then stacks on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ColorConflict {
+    /// The display gamma ramp is far from identity, as with Night Light or a vendor eye saver mode.
+    GammaRamp,
+    /// A process from the configured list, named as listed.
+    Process(String),
+}
+
+impl ColorConflict {
+    /// E.g. "Night Light detected — colors may differ", for the banner and the tray tooltip.
+    pub fn message(&self) -> String {
+        match self {
+            ColorConflict::GammaRamp => tr!("conflict.gamma"),
+            ColorConflict::Process(name) => tr!("conflict.process", app = name),
+        }
+    }
+}
+
+/// Looks for a running process from `processes` first, since that names the culprit, then at
+/// the gamma ramp of the primary display.
+#[cfg(windows)]
+pub fn detect(processes: &[String]) -> Option<ColorConflict> {
+    running_process(processes)
+        .map(ColorConflict::Process)
+        .or_else(|| unsafe { gamma_ramp_altered() }.then_some(ColorConflict::GammaRamp))
+}
+
+#[cfg(not(windows))]
+pub fn detect(_processes: &[String]) -> Option<ColorConflict> {
+    None
+}
+
+#[cfg(windows)]
+unsafe fn gamma_ramp_altered() -> bool {
+    let hdc = GetDC(None);
+    if hdc.is_invalid() {
+        return false;
+    }
+
+    let mut ramp = [[0u16; 256]; 3];
+    let read = GetDeviceGammaRamp(hdc, ramp.as_mut_ptr() as *mut _).as_bool();
+    ReleaseDC(None, hdc);
+    if !read {
+        return false;
+    }
+
+    let deviation = ramp_deviation(&ramp);
+    log_debug!("Gamma ramp deviates {:.1}% from identity", deviation * 100.0);
+    deviation > GAMMA_TOLERANCE
+}
+
+/// Largest distance of any channel entry from the identity ramp, 0 to 1.
+#[cfg(windows)]
+fn ramp_deviation(ramp: &[[u16; 256]; 3]) -> f32 {
+    ramp.iter()
+        .flat_map(|channel| channel.iter().enumerate())
+        .map(|(i, &value)| (value as f32 - i as f32 * 257.0).abs() / u16::MAX as f32)
+        .fold(0.0, f32::max)
+}
+
+/// The first entry of `names` running as a process, compared to executable names ignoring case.
+#[cfg(windows)]
+fn running_process(names: &[String]) -> Option<String> {
+    if names.is_empty() {
+        return None;
+    }
+
+    unsafe {
+        let snapshot = CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0).ok()?;
+        let mut entry = PROCESSENTRY32W {
+            dwSize: std::mem::size_of::<PROCESSENTRY32W>() as u32,
+            ..Default::default()
+        };
+
+        let mut found = None;
+        let mut more = Process32FirstW(snapshot, &mut entry).is_ok();
+        while more && found.is_none() {
+            let len = entry.szExeFile.iter().position(|&c| c == 0).unwrap_or(entry.szExeFile.len());
+            let exe = String::from_utf16_lossy(&entry.szExeFile[..len]);
+            found = names.iter().find(|name| name.eq_ignore_ascii_case(&exe)).cloned();
+            more = Process32NextW(snapshot, &mut entry).is_ok();
+        }
+
+        let _ = CloseHandle(snapshot);
+        found
+    }
+}
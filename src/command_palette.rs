@@ -0,0 +1,161 @@
+use crate::gui::SettingsGui;
+use crate::theme;
+use crate::tr;
+
+const MAX_VISIBLE_ROWS: f32 = 300.0;
+
+/// A command reachable from the Ctrl+K palette.
+pub struct Action {
+    pub name: String,
+    /// Extra space separated search terms, e.g. "start stop enable" for toggling the overlay.
+    pub keywords: &'static str,
+    pub handler: Box<dyn Fn(&mut SettingsGui)>,
+}
+
+impl Action {
+    pub fn new(name: impl Into<String>, keywords: &'static str, handler: impl Fn(&mut SettingsGui) + 'static) -> Self {
+        Self {
+            name: name.into(),
+            keywords,
+            handler: Box::new(handler),
+        }
+    }
+
+    fn score(&self, query: &str) -> Option<i32> {
+        let by_name = fuzzy_score(query, &self.name);
+        // Keyword hits rank below equally good name hits
+        let by_keywords = fuzzy_score(query, self.keywords).map(|s| s - 2);
+        by_name.max(by_keywords)
+    }
+}
+
+pub enum PaletteOutcome {
+    Open,
+    Closed,
+    Run(usize),
+}
+
+#[derive(Default)]
+pub struct CommandPalette {
+    query: String,
+    selected: usize,
+    focused: bool,
+}
+
+impl CommandPalette {
+    /// Indices into `actions` matching the query, best first; registry order when the query is empty.
+    fn matches(&self, actions: &[Action]) -> Vec<usize> {
+        let mut scored: Vec<(usize, i32)> = actions
+            .iter()
+            .enumerate()
+            .filter_map(|(index, action)| action.score(&self.query).map(|score| (index, score)))
+            .collect();
+        scored.sort_by(|a, b| b.1.cmp(&a.1));
+        scored.into_iter().map(|(index, _)| index).collect()
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context, actions: &[Action]) -> PaletteOutcome {
+        let matches = self.matches(actions);
+
+        // Consumed before the text field sees them, which would otherwise move the cursor
+        let (up, down, enter, escape) = ctx.input_mut(|i| {
+            (
+                i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowUp),
+                i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowDown),
+                i.consume_key(egui::Modifiers::NONE, egui::Key::Enter),
+                i.consume_key(egui::Modifiers::NONE, egui::Key::Escape),
+            )
+        });
+
+        if escape {
+            return PaletteOutcome::Closed;
+        }
+        if down && self.selected + 1 < matches.len() {
+            self.selected += 1;
+        }
+        if up {
+            self.selected = self.selected.saturating_sub(1);
+        }
+        self.selected = self.selected.min(matches.len().saturating_sub(1));
+
+        if enter {
+            return match matches.get(self.selected) {
+                Some(&index) => PaletteOutcome::Run(index),
+                None => PaletteOutcome::Open,
+            };
+        }
+
+        let mut clicked = None;
+        let mut open = true;
+
+        egui::Window::new(tr!("palette.title"))
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_TOP, [0.0, 60.0])
+            .show(ctx, |ui| {
+                let response = ui.add(
+                    egui::TextEdit::singleline(&mut self.query)
+                        .hint_text(tr!("palette.hint"))
+                        .desired_width(320.0)
+                );
+                if !self.focused {
+                    response.request_focus();
+                    self.focused = true;
+                }
+                if response.changed() {
+                    self.selected = 0;
+                }
+
+                ui.add_space(5.0);
+                egui::ScrollArea::vertical().max_height(MAX_VISIBLE_ROWS).show(ui, |ui| {
+                    for (row, &index) in matches.iter().enumerate() {
+                        let response = theme::selectable_label(ui, row == self.selected, &actions[index].name);
+                        if row == self.selected && (up || down) {
+                            response.scroll_to_me(None);
+                        }
+                        if response.clicked() {
+                            clicked = Some(index);
+                        }
+                    }
+                    if matches.is_empty() {
+                        ui.label(tr!("palette.no_matches"));
+                    }
+                });
+            });
+
+        match clicked {
+            Some(index) => PaletteOutcome::Run(index),
+            None if !open => PaletteOutcome::Closed,
+            None => PaletteOutcome::Open,
+        }
+    }
+}
+
+/// Case-insensitive subsequence match, higher is better. Consecutive characters and matches at
+/// word starts score extra, so "tog ov" ranks "Toggle overlay" above names that merely contain the letters.
+pub fn fuzzy_score(query: &str, text: &str) -> Option<i32> {
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut next = 0;
+    let mut previous: Option<usize> = None;
+
+    for q in query.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+        let found = (next..text.len()).find(|&i| text[i] == q)?;
+
+        score += 1;
+        if found == 0 || !text[found - 1].is_alphanumeric() {
+            score += 3;
+        }
+        match previous {
+            Some(p) if p + 1 == found => score += 5,
+            Some(p) => score -= (found - p - 1).min(5) as i32,
+            None => {}
+        }
+
+        previous = Some(found);
+        next = found + 1;
+    }
+
+    Some(score)
+}
@@ -0,0 +1,337 @@
+use crate::StateManager;
+use crate::overlay::OverlayManager;
+use chromabridge::{log_info, log_warn, log_error};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(2);
+const MAX_BODY_SIZE: usize = 4096;
+/// Longest request or header line read, with its line break.
+const MAX_LINE_LENGTH: u64 = 8192;
+
+/// Endpoints shown in the settings panel so users can wire up Stream Deck / Home Assistant.
+pub const ENDPOINTS: &[(&str, &str)] = &[
+    ("GET", "/status"),
+    ("POST", "/overlay {\"enabled\": true}"),
+    ("POST", "/spectrum {\"name\": \"...\"}"),
+    ("POST", "/strength {\"value\": 0.7}"),
+];
+
+/// Optional localhost HTTP API for driving the overlay from external tools.
+///
+/// The server thread follows `control_server_enabled` / `control_server_port` in the app state,
+/// so toggling it in the GUI takes effect without any extra plumbing.
+pub struct ControlServer {
+    shutdown: Arc<AtomicBool>,
+    thread: parking_lot::Mutex<Option<thread::JoinHandle<()>>>,
+}
+
+impl ControlServer {
+    pub fn spawn(state: Arc<StateManager>, overlay_manager: Arc<OverlayManager>) -> Self {
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let shutdown_flag = Arc::clone(&shutdown);
+
+        let thread = thread::spawn(move || {
+            Self::run(state, overlay_manager, shutdown_flag);
+        });
+
+        Self {
+            shutdown,
+            thread: parking_lot::Mutex::new(Some(thread)),
+        }
+    }
+
+    pub fn stop(&self) {
+        self.shutdown.store(true, Ordering::Release);
+        if let Some(handle) = self.thread.lock().take() {
+            let _ = handle.join();
+        }
+    }
+
+    fn run(state: Arc<StateManager>, overlay_manager: Arc<OverlayManager>, shutdown: Arc<AtomicBool>) {
+        let mut listener: Option<(TcpListener, u16)> = None;
+
+        while !shutdown.load(Ordering::Acquire) {
+            let (enabled, port) = state.read(|s| (s.control_server_enabled, s.control_server_port));
+
+            let wanted_port = if enabled { Some(port) } else { None };
+            if listener.as_ref().map(|(_, p)| *p) != wanted_port {
+                if let Some((_, old_port)) = listener.take() {
+                    log_info!("Control server stopped (port {})", old_port);
+                }
+                if let Some(port) = wanted_port {
+                    listener = Self::bind(port).map(|l| (l, port));
+                    if listener.is_none() {
+                        // Don't retry every poll; wait for the user to change the port or toggle
+                        Self::wait_for_settings_change(&state, &shutdown, enabled, port);
+                        continue;
+                    }
+                }
+            }
+
+            match listener {
+                Some((ref l, _)) => match l.accept() {
+                    Ok((stream, addr)) => {
+                        Self::handle_connection(stream, addr, &state, &overlay_manager);
+                    }
+                    Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                        thread::sleep(POLL_INTERVAL);
+                    }
+                    Err(e) => {
+                        log_warn!("Control server accept failed: {}", e);
+                        thread::sleep(POLL_INTERVAL);
+                    }
+                },
+                None => thread::sleep(POLL_INTERVAL),
+            }
+        }
+
+        if let Some((_, port)) = listener {
+            log_info!("Control server stopped (port {})", port);
+        }
+    }
+
+    fn bind(port: u16) -> Option<TcpListener> {
+        let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, port));
+        match TcpListener::bind(addr).and_then(|l| l.set_nonblocking(true).map(|_| l)) {
+            Ok(l) => {
+                log_info!("Control server listening on http://{}", addr);
+                Some(l)
+            }
+            Err(e) => {
+                log_error!("Failed to start control server on {}: {}", addr, e);
+                None
+            }
+        }
+    }
+
+    fn wait_for_settings_change(state: &StateManager, shutdown: &AtomicBool, enabled: bool, port: u16) {
+        while !shutdown.load(Ordering::Acquire) {
+            if state.read(|s| (s.control_server_enabled, s.control_server_port)) != (enabled, port) {
+                return;
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    fn handle_connection(stream: TcpStream, addr: SocketAddr, state: &StateManager, overlay_manager: &OverlayManager) {
+        // Listener is bound to 127.0.0.1, but double-check in case that ever changes
+        if !addr.ip().is_loopback() {
+            log_warn!("Control server rejected non-loopback connection from {}", addr);
+            let _ = Self::respond(&stream, 403, &serde_json::json!({ "error": "forbidden" }));
+            return;
+        }
+
+        let _ = stream.set_nonblocking(false);
+        let _ = stream.set_read_timeout(Some(CLIENT_TIMEOUT));
+        let _ = stream.set_write_timeout(Some(CLIENT_TIMEOUT));
+
+        let (status, body) = match Self::read_request(&stream) {
+            Ok(request) => {
+                let token = state.read(|s| s.control_server_token.clone());
+                if token.is_empty() || !request.authorized(&token) {
+                    log_warn!("Control server rejected unauthorized {} {}", request.method, request.path);
+                    (401, serde_json::json!({ "error": "unauthorized" }))
+                } else {
+                    Self::route(&request, state, overlay_manager)
+                }
+            }
+            Err(e) => (400, serde_json::json!({ "error": e })),
+        };
+
+        let _ = Self::respond(&stream, status, &body);
+    }
+
+    fn route(request: &Request, state: &StateManager, overlay_manager: &OverlayManager) -> (u16, serde_json::Value) {
+        match (request.method.as_str(), request.path.as_str()) {
+            ("GET", "/status") => (200, Self::status(state, overlay_manager)),
+            ("POST", "/overlay") => {
+                let Some(enabled) = request.json_field("enabled").and_then(|v| v.as_bool()) else {
+                    return (400, serde_json::json!({ "error": "expected {\"enabled\": bool}" }));
+                };
+                if enabled != overlay_manager.is_running() {
+                    log_info!("Overlay toggled from control server: {}", if enabled { "ON" } else { "OFF" });
+                    // Not a toggle, so a concurrent change can't flip it the other way
+                    if enabled {
+                        overlay_manager.start();
+                    } else {
+                        overlay_manager.stop();
+                    }
+                }
+                (200, Self::status(state, overlay_manager))
+            }
+            ("POST", "/spectrum") => {
+                let Some(name) = request.json_field("name").and_then(|v| v.as_str().map(str::to_string)) else {
+                    return (400, serde_json::json!({ "error": "expected {\"name\": string}" }));
+                };
+                if !state.list_spectrum_files().unwrap_or_default().contains(&name) {
+                    return (404, serde_json::json!({ "error": format!("unknown spectrum '{}'", name) }));
+                }
+                log_info!("Spectrum changed from control server: {}", name);
+                state.update(|s| s.select_spectrum(name));
+                overlay_manager.request_restart();
+                (200, Self::status(state, overlay_manager))
+            }
+            ("POST", "/strength") => {
+                let Some(value) = request.json_field("value").and_then(|v| v.as_f64()) else {
+                    return (400, serde_json::json!({ "error": "expected {\"value\": number}" }));
+                };
+                let strength = (value as f32).clamp(0.0, 1.0);
+                state.update(|s| {
+                    s.strength = strength;
+                    s.remember_spectrum_settings();
+                });
+                (200, Self::status(state, overlay_manager))
+            }
+            ("GET", _) | ("POST", _) => (404, serde_json::json!({ "error": "not found" })),
+            _ => (405, serde_json::json!({ "error": "method not allowed" })),
+        }
+    }
+
+    fn status(state: &StateManager, overlay_manager: &OverlayManager) -> serde_json::Value {
+        let (spectrum, strength) = state.read(|s| (s.spectrum_name.clone(), s.strength));
+        serde_json::json!({
+            "overlay": overlay_manager.is_running(),
+            "spectrum": spectrum,
+            "strength": strength,
+            "fps": overlay_manager.get_frame_stats().map(|stats| stats.fps),
+            "latency_ms": overlay_manager.get_frame_stats().and_then(|stats| stats.latency_ms),
+            "dropped_presents_per_second": overlay_manager.get_frame_stats().and_then(|stats| stats.dropped_presents_per_second),
+        })
+    }
+
+    fn read_request(stream: &TcpStream) -> std::result::Result<Request, String> {
+        let mut reader = BufReader::new(stream);
+
+        let mut request_line = String::new();
+        Self::read_line(&mut reader, &mut request_line)?;
+        let mut parts = request_line.split_whitespace();
+        let method = parts.next().ok_or("missing method")?.to_string();
+        let path = parts.next().ok_or("missing path")?.to_string();
+
+        let mut authorization = None;
+        let mut content_length = 0usize;
+        loop {
+            let mut line = String::new();
+            if Self::read_line(&mut reader, &mut line)? == 0 {
+                break;
+            }
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                let value = value.trim();
+                if name.eq_ignore_ascii_case("authorization") {
+                    authorization = Some(value.to_string());
+                } else if name.eq_ignore_ascii_case("content-length") {
+                    content_length = value.parse().map_err(|_| "invalid Content-Length")?;
+                }
+            }
+        }
+
+        if content_length > MAX_BODY_SIZE {
+            return Err("request body too large".to_string());
+        }
+
+        let mut body = vec![0u8; content_length];
+        reader.read_exact(&mut body).map_err(|e| e.to_string())?;
+
+        Ok(Request {
+            method,
+            path,
+            authorization,
+            body,
+        })
+    }
+
+    /// `BufRead::read_line` reading at most `MAX_LINE_LENGTH` bytes, so a client can't make the
+    /// server buffer an endless line.
+    fn read_line(reader: &mut impl BufRead, line: &mut String) -> std::result::Result<usize, String> {
+        let read = reader.take(MAX_LINE_LENGTH).read_line(line).map_err(|e| e.to_string())?;
+        if read as u64 == MAX_LINE_LENGTH && !line.ends_with('\n') {
+            return Err("line too long".to_string());
+        }
+        Ok(read)
+    }
+
+    fn respond(mut stream: &TcpStream, status: u16, body: &serde_json::Value) -> std::io::Result<()> {
+        let reason = match status {
+            200 => "OK",
+            400 => "Bad Request",
+            401 => "Unauthorized",
+            403 => "Forbidden",
+            404 => "Not Found",
+            _ => "Method Not Allowed",
+        };
+        let body = body.to_string();
+        write!(
+            stream,
+            "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status, reason, body.len(), body
+        )?;
+        stream.flush()
+    }
+}
+
+impl Drop for ControlServer {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+struct Request {
+    method: String,
+    path: String,
+    authorization: Option<String>,
+    body: Vec<u8>,
+}
+
+impl Request {
+    fn authorized(&self, token: &str) -> bool {
+        self.authorization
+            .as_deref()
+            .and_then(|h| h.strip_prefix("Bearer "))
+            .is_some_and(|t| constant_time_eq(t.trim().as_bytes(), token.as_bytes()))
+    }
+
+    fn json_field(&self, name: &str) -> Option<serde_json::Value> {
+        let value: serde_json::Value = serde_json::from_slice(&self.body).ok()?;
+        value.get(name).cloned()
+    }
+}
+
+/// Compares without returning early at the first differing byte, so response times don't tell
+/// how much of a guessed token was right.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// Generates a random bearer token for the control server, 128 bits from the system's
+/// cryptographic random number generator. Empty if that failed, which the server rejects every
+/// request with.
+pub fn generate_token() -> String {
+    let mut bytes = [0u8; 16];
+    if let Err(e) = random_bytes(&mut bytes) {
+        log_error!("Failed to generate a control server token: {}", e);
+        return String::new();
+    }
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(windows)]
+fn random_bytes(buffer: &mut [u8]) -> std::io::Result<()> {
+    use windows::Win32::Security::Cryptography::{BCryptGenRandom, BCRYPT_USE_SYSTEM_PREFERRED_RNG};
+
+    unsafe { BCryptGenRandom(None, buffer, BCRYPT_USE_SYSTEM_PREFERRED_RNG) }.ok().map_err(std::io::Error::other)
+}
+
+#[cfg(not(windows))]
+fn random_bytes(buffer: &mut [u8]) -> std::io::Result<()> {
+    std::fs::File::open("/dev/urandom")?.read_exact(buffer)
+}
@@ -0,0 +1,81 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const PENDING_MARKER: &str = "pending.marker";
+
+pub fn crash_dir(app_data_dir: &Path) -> PathBuf {
+    app_data_dir.join("crashes")
+}
+
+/// Installs a panic hook that logs the panic, flushes buffered logs and writes a crash report.
+pub fn install_panic_hook(crash_dir: PathBuf) {
+    let previous_hook = std::panic::take_hook();
+
+    std::panic::set_hook(Box::new(move |info| {
+        let thread = std::thread::current();
+        let thread_name = thread.name().unwrap_or("<unnamed>");
+
+        let message = if let Some(s) = info.payload().downcast_ref::<&str>() {
+            s.to_string()
+        } else if let Some(s) = info.payload().downcast_ref::<String>() {
+            s.clone()
+        } else {
+            "<non-string panic payload>".to_string()
+        };
+
+        let location = info
+            .location()
+            .map(|l| format!("{}:{}:{}", l.file(), l.line(), l.column()))
+            .unwrap_or_else(|| "<unknown>".to_string());
+
+        let backtrace = std::backtrace::Backtrace::force_capture();
+
+        crate::log_error!("PANIC in thread '{}' at {}: {}", thread_name, location, message);
+        crate::log_error!("Backtrace:\n{}", backtrace);
+        let _ = crate::logger::flush_logs();
+
+        let report = format!(
+            "ChromaBridge {}\nTime: {}\nThread: {}\nLocation: {}\nMessage: {}\n\nBacktrace:\n{}\n",
+            env!("CARGO_PKG_VERSION"),
+            chrono::Local::now().format("%Y-%m-%d %H:%M:%S%.3f"),
+            thread_name,
+            location,
+            message,
+            backtrace,
+        );
+
+        if let Err(e) = write_crash_report(&crash_dir, &report) {
+            crate::log_error!("Failed to write crash report: {}", e);
+            let _ = crate::logger::flush_logs();
+        }
+
+        previous_hook(info);
+    }));
+}
+
+fn write_crash_report(crash_dir: &Path, report: &str) -> Result<()> {
+    fs::create_dir_all(crash_dir).context("Failed to create crash directory")?;
+
+    let timestamp = chrono::Local::now().format("%Y%m%d_%H%M%S");
+    let filename = format!("crash_{}.txt", timestamp);
+    fs::write(crash_dir.join(&filename), report).context("Failed to write crash report")?;
+    fs::write(crash_dir.join(PENDING_MARKER), &filename).context("Failed to write crash marker")?;
+
+    Ok(())
+}
+
+/// Returns the report left by a crash in the previous session and clears the marker,
+/// so each crash is only surfaced once.
+pub fn take_pending_crash(crash_dir: &Path) -> Option<PathBuf> {
+    let marker = crash_dir.join(PENDING_MARKER);
+    let filename = fs::read_to_string(&marker).ok()?;
+    let _ = fs::remove_file(&marker);
+
+    let report = crash_dir.join(filename.trim());
+    if report.exists() {
+        Some(report)
+    } else {
+        Some(crash_dir.to_path_buf())
+    }
+}
@@ -0,0 +1,197 @@
+/// Pointer shape formats reported by desktop duplication (DXGI_OUTDUPL_POINTER_SHAPE_TYPE_*).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CursorShapeKind {
+    /// 1bpp AND mask followed by a 1bpp XOR mask of the same size.
+    Monochrome,
+    /// 32bpp BGRA drawn with alpha blending.
+    Color,
+    /// 32bpp BGRA; alpha 0 replaces the screen pixel, alpha 0xFF XORs it.
+    MaskedColor,
+}
+
+/// How the frame a cursor is drawn into stores its pixels.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PixelLayout {
+    /// B8G8R8A8, what duplication delivers for 8-bit desktops.
+    #[default]
+    Bgra8,
+    /// R10G10B10A2 of 10-bit desktops: a little-endian u32 per pixel, red in the lowest bits and
+    /// alpha in the top two.
+    Rgb10A2,
+}
+
+/// Bit offsets of blue, green and red in an R10G10B10A2 pixel, in the order `apply` expects.
+const RGB10A2_BGR_SHIFTS: [u32; 3] = [20, 10, 0];
+
+/// A pointer shape as returned by `GetFramePointerShape`, drawn into captured frames when the
+/// hardware cursor should be corrected too (duplicated frames never contain it).
+#[derive(Debug, Clone)]
+pub struct CursorShape {
+    pub kind: CursorShapeKind,
+    pub width: u32,
+    /// Height of the drawn image; half the reported height for monochrome shapes.
+    pub height: u32,
+    /// Bytes per row of `data`.
+    pub pitch: u32,
+    pub data: Vec<u8>,
+}
+
+impl CursorShape {
+    /// `reported_height` is the height from the shape info, which covers both masks of a monochrome cursor.
+    pub fn new(kind: CursorShapeKind, width: u32, reported_height: u32, pitch: u32, data: Vec<u8>) -> Self {
+        let height = match kind {
+            CursorShapeKind::Monochrome => reported_height / 2,
+            _ => reported_height,
+        };
+        Self { kind, width, height, pitch, data }
+    }
+
+    /// Draws the cursor onto `target` rows with its top-left corner at `origin`, which may lie
+    /// partly outside the target; anything outside is clipped.
+    ///
+    /// Shapes are 8 bits per channel, so 10-bit pixels are blended at 8 bits; pixels the cursor
+    /// leaves as they were keep all 10.
+    pub fn composite_onto(&self, target: &mut [u8], target_pitch: usize, target_size: (u32, u32), origin: (i32, i32), layout: PixelLayout) {
+        for y in 0..self.height {
+            let ty = origin.1 + y as i32;
+            if ty < 0 || ty >= target_size.1 as i32 {
+                continue;
+            }
+            for x in 0..self.width {
+                let tx = origin.0 + x as i32;
+                if tx < 0 || tx >= target_size.0 as i32 {
+                    continue;
+                }
+                let idx = ty as usize * target_pitch + tx as usize * 4;
+                let Some(pixel) = target.get_mut(idx..idx + 4) else {
+                    continue;
+                };
+                match layout {
+                    PixelLayout::Bgra8 => self.apply(x, y, &mut pixel[..3]),
+                    PixelLayout::Rgb10A2 => {
+                        let packed = u32::from_le_bytes([pixel[0], pixel[1], pixel[2], pixel[3]]);
+                        let before = RGB10A2_BGR_SHIFTS.map(|shift| (packed >> (shift + 2)) as u8);
+                        let mut bgr = before;
+                        self.apply(x, y, &mut bgr);
+                        if bgr != before {
+                            let packed = bgr
+                                .iter()
+                                .zip(RGB10A2_BGR_SHIFTS)
+                                .fold(packed & 0xC000_0000, |packed, (&value, shift)| packed | widen_to_10_bits(value) << shift);
+                            pixel.copy_from_slice(&packed.to_le_bytes());
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn apply(&self, x: u32, y: u32, pixel: &mut [u8]) {
+        match self.kind {
+            CursorShapeKind::Monochrome => {
+                let and = self.mask_bit(x, y);
+                let xor = self.mask_bit(x, y + self.height);
+                for channel in pixel.iter_mut() {
+                    *channel = (*channel & if and { 0xFF } else { 0 }) ^ if xor { 0xFF } else { 0 };
+                }
+            }
+            CursorShapeKind::Color => {
+                let Some(src) = self.texel(x, y) else { return };
+                let alpha = src[3] as u32;
+                for (channel, &value) in pixel.iter_mut().zip(src) {
+                    *channel = ((value as u32 * alpha + *channel as u32 * (255 - alpha) + 127) / 255) as u8;
+                }
+            }
+            CursorShapeKind::MaskedColor => {
+                let Some(src) = self.texel(x, y) else { return };
+                let xor = src[3] != 0;
+                for (channel, &value) in pixel.iter_mut().zip(src) {
+                    *channel = if xor { *channel ^ value } else { value };
+                }
+            }
+        }
+    }
+
+    fn mask_bit(&self, x: u32, row: u32) -> bool {
+        let byte = self.data.get((row * self.pitch + x / 8) as usize).copied().unwrap_or(0);
+        byte & (0x80 >> (x % 8)) != 0
+    }
+
+    fn texel(&self, x: u32, y: u32) -> Option<&[u8]> {
+        let idx = (y * self.pitch + x * 4) as usize;
+        self.data.get(idx..idx + 4)
+    }
+}
+
+/// Spreads an 8-bit channel over 10 bits, keeping 0 and full scale exact.
+fn widen_to_10_bits(value: u8) -> u32 {
+    (value as u32) << 2 | (value as u32) >> 6
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grey_target(width: u32, height: u32, grey: u8) -> Vec<u8> {
+        [grey, grey, grey, 255].repeat((width * height) as usize)
+    }
+
+    #[test]
+    fn monochrome_applies_and_then_xor() {
+        // 4x1 cursor: AND row 1100, XOR row 1010
+        let shape = CursorShape::new(CursorShapeKind::Monochrome, 4, 2, 1, vec![0b1100_0000, 0b1010_0000]);
+        let mut target = grey_target(4, 1, 0x40);
+        shape.composite_onto(&mut target, 16, (4, 1), (0, 0), PixelLayout::Bgra8);
+
+        let reds: Vec<u8> = target.chunks(4).map(|p| p[2]).collect();
+        // inverted, unchanged, white, black
+        assert_eq!(reds, vec![0x40 ^ 0xFF, 0x40, 0xFF, 0x00]);
+        assert!(target.chunks(4).all(|p| p[3] == 255));
+    }
+
+    #[test]
+    fn masked_color_replaces_or_xors() {
+        let data = vec![
+            0x10, 0x20, 0x30, 0x00, // replace
+            0xFF, 0xFF, 0xFF, 0xFF, // invert
+        ];
+        let shape = CursorShape::new(CursorShapeKind::MaskedColor, 2, 1, 8, data);
+        let mut target = grey_target(2, 1, 0x40);
+        shape.composite_onto(&mut target, 8, (2, 1), (0, 0), PixelLayout::Bgra8);
+
+        assert_eq!(&target[0..4], &[0x10, 0x20, 0x30, 255]);
+        assert_eq!(&target[4..8], &[0xBF, 0xBF, 0xBF, 255]);
+    }
+
+    #[test]
+    fn color_blends_and_clips_at_edges() {
+        let shape = CursorShape::new(CursorShapeKind::Color, 2, 2, 8, [0, 0, 255, 255].repeat(4));
+        let mut target = grey_target(2, 2, 0);
+        // Only the top-left cursor pixel lands on the bottom-right target pixel
+        shape.composite_onto(&mut target, 8, (2, 2), (1, 1), PixelLayout::Bgra8);
+
+        assert_eq!(&target[12..16], &[0, 0, 255, 255]);
+        assert!(target[..12].chunks(4).all(|p| p == [0, 0, 0, 255]));
+    }
+
+    #[test]
+    fn ten_bit_frames_keep_their_precision_outside_the_cursor() {
+        // Red 1023, green 513, blue 2, opaque; 513 and 2 fall between two 8-bit values
+        let pixel = (0b11 << 30 | 2 << 20 | 513 << 10 | 1023u32).to_le_bytes();
+        let mut target = pixel.repeat(3);
+        // Replaces the first pixel with red, leaves the second, inverts the third
+        let data = vec![
+            0x00, 0x00, 0xFF, 0x00,
+            0x00, 0x00, 0x00, 0xFF,
+            0xFF, 0xFF, 0xFF, 0xFF,
+        ];
+        let shape = CursorShape::new(CursorShapeKind::MaskedColor, 3, 1, 12, data);
+        shape.composite_onto(&mut target, 12, (3, 1), (0, 0), PixelLayout::Rgb10A2);
+
+        let pixels: Vec<u32> = target.chunks(4).map(|p| u32::from_le_bytes([p[0], p[1], p[2], p[3]])).collect();
+        assert_eq!(pixels[0], 0b11 << 30 | 1023);
+        assert_eq!(pixels[1], u32::from_le_bytes(pixel));
+        // Inverted at 8 bits (red 255 -> 0, green 128 -> 127, blue 0 -> 255), then widened again
+        assert_eq!(pixels[2], 0b11 << 30 | 1023 << 20 | 509 << 10);
+    }
+}
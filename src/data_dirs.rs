@@ -0,0 +1,294 @@
+use crate::migration;
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+
+/// Folder of the app in the profile folders.
+pub const APP_DIR_NAME: &str = "ChromaBridge";
+/// Environment variable that moves the spectrum and noise files, e.g. to a shared drive.
+pub const DATA_DIR_VAR: &str = "CHROMABRIDGE_DATA_DIR";
+/// File next to the executable that switches to portable mode.
+pub const PORTABLE_MARKER: &str = "portable.txt";
+/// Folder beside the executable holding everything in portable mode.
+const PORTABLE_DIR_NAME: &str = "data";
+
+/// The database files, copied from the roaming folder of older versions.
+const DATABASE_FILES: [&str; 3] = ["state.db", "state.db-wal", "state.db-shm"];
+
+/// How the data folders were chosen, for the log and Advanced Settings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataDirLayout {
+    /// Database and logs in the local profile, which roaming profiles don't sync to a network
+    /// share; spectrum and noise files in the roaming profile.
+    Profile,
+    /// Like `Profile`, with the spectrum and noise files in `DATA_DIR_VAR`.
+    AssetsFromEnvironment,
+    /// `PORTABLE_MARKER` is next to the executable: everything in one folder beside it.
+    Portable,
+    /// No profile folder is set, as for some services and scheduled tasks: everything in one
+    /// folder beside the executable.
+    BesideExecutable,
+}
+
+impl DataDirLayout {
+    pub fn label(&self) -> &'static str {
+        match self {
+            DataDirLayout::Profile => "user profile",
+            DataDirLayout::AssetsFromEnvironment => DATA_DIR_VAR,
+            DataDirLayout::Portable => "portable mode",
+            DataDirLayout::BesideExecutable => "no profile folder, beside the executable",
+        }
+    }
+}
+
+/// Where the app keeps its files.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DataDirs {
+    /// The database, logs, crash reports and debug output, which belong to this machine.
+    pub local: PathBuf,
+    /// Spectrum and noise files with their trash, in `spectrums` and `noise`.
+    pub assets: PathBuf,
+    /// `%APPDATA%\ChromaBridge`, where older versions kept everything.
+    pub roaming: Option<PathBuf>,
+    pub layout: DataDirLayout,
+}
+
+/// What `resolve` reads, taken from the process by `current` and made up in tests.
+#[derive(Debug, Clone, Default)]
+pub struct Environment {
+    pub local_app_data: Option<PathBuf>,
+    pub app_data: Option<PathBuf>,
+    pub user_profile: Option<PathBuf>,
+    /// `DATA_DIR_VAR`.
+    pub data_dir: Option<PathBuf>,
+    pub exe_dir: Option<PathBuf>,
+    /// Whether `PORTABLE_MARKER` is in `exe_dir`.
+    pub portable: bool,
+}
+
+impl Environment {
+    /// Empty variables count as unset.
+    pub fn current() -> Self {
+        let var = |name: &str| std::env::var_os(name).filter(|value| !value.is_empty()).map(PathBuf::from);
+        let exe_dir = std::env::current_exe().ok().and_then(|exe| exe.parent().map(Path::to_path_buf));
+        Self {
+            local_app_data: var("LOCALAPPDATA"),
+            app_data: var("APPDATA"),
+            user_profile: var("USERPROFILE"),
+            data_dir: var(DATA_DIR_VAR),
+            portable: exe_dir.as_ref().is_some_and(|dir| dir.join(PORTABLE_MARKER).is_file()),
+            exe_dir,
+        }
+    }
+}
+
+impl DataDirs {
+    /// Everything in `dir`, as in portable mode.
+    pub fn single(dir: PathBuf, layout: DataDirLayout) -> Self {
+        Self {
+            assets: dir.join("assets"),
+            local: dir,
+            roaming: None,
+            layout,
+        }
+    }
+
+    /// Picks the folders for `env`, first match wins:
+    ///
+    /// 1. Portable mode: everything in `data` beside the executable.
+    /// 2. The local folder: `%LOCALAPPDATA%`, `%USERPROFILE%\AppData\Local` or `%APPDATA%`, each
+    ///    with `APP_DIR_NAME`; without any, `data` beside the executable.
+    /// 3. The assets: `DATA_DIR_VAR`, then `%APPDATA%\ChromaBridge`, then the local folder, each
+    ///    with `assets`.
+    pub fn resolve(env: &Environment) -> Result<Self> {
+        let beside_exe = || {
+            env.exe_dir
+                .as_ref()
+                .map(|dir| dir.join(PORTABLE_DIR_NAME))
+                .context("Failed to find a folder for the app data: LOCALAPPDATA, APPDATA and USERPROFILE are unset and the executable's folder is unknown")
+        };
+        if env.portable {
+            return Ok(Self::single(beside_exe()?, DataDirLayout::Portable));
+        }
+
+        let roaming = env.app_data.as_ref().map(|dir| dir.join(APP_DIR_NAME));
+        let profile_local = env
+            .local_app_data
+            .clone()
+            .or_else(|| env.user_profile.as_ref().map(|profile| profile.join("AppData").join("Local")))
+            .map(|dir| dir.join(APP_DIR_NAME))
+            .or_else(|| roaming.clone());
+        let (local, layout) = match profile_local {
+            Some(dir) => (dir, DataDirLayout::Profile),
+            None => (beside_exe()?, DataDirLayout::BesideExecutable),
+        };
+
+        let (assets, layout) = match env.data_dir {
+            Some(ref dir) => (dir.join("assets"), DataDirLayout::AssetsFromEnvironment),
+            None => (roaming.as_ref().unwrap_or(&local).join("assets"), layout),
+        };
+        Ok(Self {
+            local,
+            assets,
+            roaming,
+            layout,
+        })
+    }
+
+    /// Copies what an older version left in the roaming folder to where it goes now: the
+    /// database when the local folder has none yet, and the assets when they were moved and
+    /// their folder doesn't exist yet. Call before creating either folder.
+    ///
+    /// The roaming folder is left as it was, so the other machines of a roaming profile still
+    /// find it. Returns `None` when there was nothing to copy.
+    pub fn copy_from_roaming(&self) -> Option<RoamingCopy> {
+        let roaming = self.roaming.as_ref().filter(|roaming| **roaming != self.local && roaming.is_dir())?;
+        let mut copy = RoamingCopy {
+            from: roaming.clone(),
+            ..Default::default()
+        };
+
+        let roaming_assets = roaming.join("assets");
+        if self.assets != roaming_assets && !self.assets.exists() {
+            migration::copy_missing(&roaming_assets, &self.assets, &mut copy.copied_files, &mut copy.skipped);
+        }
+
+        if !self.local.join(DATABASE_FILES[0]).exists() && roaming.join(DATABASE_FILES[0]).exists() {
+            if let Err(e) = std::fs::create_dir_all(&self.local) {
+                copy.skipped.push(format!("{}: {}", self.local.display(), e));
+                return Some(copy);
+            }
+            for name in DATABASE_FILES.iter().chain([&migration::MARKER_FILE]) {
+                let source = roaming.join(name);
+                if !source.exists() {
+                    continue;
+                }
+                match std::fs::copy(&source, self.local.join(name)) {
+                    Ok(_) => copy.copied_files += 1,
+                    Err(e) => copy.skipped.push(format!("{}: {}", source.display(), e)),
+                }
+            }
+        }
+
+        (copy.copied_files > 0 || !copy.skipped.is_empty()).then_some(copy)
+    }
+}
+
+/// What `DataDirs::copy_from_roaming` brought over.
+#[derive(Debug, Clone, Default)]
+pub struct RoamingCopy {
+    pub from: PathBuf,
+    pub copied_files: usize,
+    /// Files that couldn't be copied, with the reason.
+    pub skipped: Vec<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env() -> Environment {
+        Environment {
+            local_app_data: Some(PathBuf::from(r"C:\Users\a\AppData\Local")),
+            app_data: Some(PathBuf::from(r"C:\Users\a\AppData\Roaming")),
+            user_profile: Some(PathBuf::from(r"C:\Users\a")),
+            data_dir: None,
+            exe_dir: Some(PathBuf::from(r"D:\Tools\ChromaBridge")),
+            portable: false,
+        }
+    }
+
+    fn resolve(env: &Environment) -> DataDirs {
+        DataDirs::resolve(env).unwrap()
+    }
+
+    #[test]
+    fn database_is_local_and_assets_roam() {
+        let dirs = resolve(&env());
+        assert_eq!(dirs.layout, DataDirLayout::Profile);
+        assert_eq!(dirs.local, Path::new(r"C:\Users\a\AppData\Local").join("ChromaBridge"));
+        assert_eq!(dirs.assets, Path::new(r"C:\Users\a\AppData\Roaming").join("ChromaBridge").join("assets"));
+        assert_eq!(dirs.roaming, Some(Path::new(r"C:\Users\a\AppData\Roaming").join("ChromaBridge")));
+    }
+
+    #[test]
+    fn portable_mode_keeps_everything_beside_the_executable() {
+        let dirs = resolve(&Environment {
+            portable: true,
+            data_dir: Some(PathBuf::from(r"\\server\share")),
+            ..env()
+        });
+        let data = Path::new(r"D:\Tools\ChromaBridge").join("data");
+        assert_eq!(dirs, DataDirs::single(data.clone(), DataDirLayout::Portable));
+        assert_eq!(dirs.assets, data.join("assets"));
+    }
+
+    #[test]
+    fn environment_variable_moves_only_the_assets() {
+        let dirs = resolve(&Environment {
+            data_dir: Some(PathBuf::from(r"\\server\share")),
+            ..env()
+        });
+        assert_eq!(dirs.layout, DataDirLayout::AssetsFromEnvironment);
+        assert_eq!(dirs.local, Path::new(r"C:\Users\a\AppData\Local").join("ChromaBridge"));
+        assert_eq!(dirs.assets, Path::new(r"\\server\share").join("assets"));
+    }
+
+    #[test]
+    fn missing_profile_variables_fall_back_in_order() {
+        // No LOCALAPPDATA: the local folder of the user profile
+        let dirs = resolve(&Environment { local_app_data: None, ..env() });
+        assert_eq!(dirs.local, Path::new(r"C:\Users\a").join("AppData").join("Local").join("ChromaBridge"));
+
+        // Only APPDATA: everything in the roaming folder, as before
+        let dirs = resolve(&Environment {
+            local_app_data: None,
+            user_profile: None,
+            ..env()
+        });
+        let roaming = Path::new(r"C:\Users\a\AppData\Roaming").join("ChromaBridge");
+        assert_eq!(dirs.local, roaming);
+        assert_eq!(dirs.assets, roaming.join("assets"));
+        assert_eq!(dirs.layout, DataDirLayout::Profile);
+
+        // No APPDATA, as for some services: assets with the database
+        let dirs = resolve(&Environment { app_data: None, ..env() });
+        assert_eq!(dirs.assets, Path::new(r"C:\Users\a\AppData\Local").join("ChromaBridge").join("assets"));
+        assert_eq!(dirs.roaming, None);
+
+        // No profile at all
+        let bare = Environment {
+            exe_dir: Some(PathBuf::from(r"D:\Tools\ChromaBridge")),
+            ..Default::default()
+        };
+        let dirs = resolve(&bare);
+        assert_eq!(dirs.layout, DataDirLayout::BesideExecutable);
+        assert_eq!(dirs.local, Path::new(r"D:\Tools\ChromaBridge").join("data"));
+        assert!(DataDirs::resolve(&Environment::default()).is_err());
+    }
+
+    #[test]
+    fn roaming_data_is_copied_once() {
+        let root = crate::test_util::temp_dir("dirs");
+        let roaming = root.join("Roaming").join(APP_DIR_NAME);
+        std::fs::create_dir_all(roaming.join("assets").join("spectrums")).unwrap();
+        std::fs::write(roaming.join("state.db"), "db").unwrap();
+        std::fs::write(roaming.join("assets").join("spectrums").join("deutan.json"), "{}").unwrap();
+
+        let dirs = resolve(&Environment {
+            local_app_data: Some(root.join("Local")),
+            app_data: Some(root.join("Roaming")),
+            data_dir: Some(root.join("Shared")),
+            ..env()
+        });
+        let copy = dirs.copy_from_roaming().unwrap();
+        assert_eq!(copy.copied_files, 2);
+        assert!(copy.skipped.is_empty());
+        assert_eq!(std::fs::read_to_string(dirs.local.join("state.db")).unwrap(), "db");
+        assert!(dirs.assets.join("spectrums").join("deutan.json").exists());
+        // The roaming folder is left for the profile's other machines
+        assert!(roaming.join("state.db").exists());
+
+        assert!(dirs.copy_from_roaming().is_none());
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,116 @@
+use crate::overlay::OverlayManager;
+use anyhow::Result;
+use once_cell::sync::OnceCell;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use windows::{
+    core::w,
+    Win32::Foundation::{HANDLE, HINSTANCE, HWND, LPARAM, LRESULT, WPARAM},
+    Win32::System::LibraryLoader::GetModuleHandleW,
+    Win32::System::Power::{GetSystemPowerStatus, POWERBROADCAST_SETTING, SYSTEM_POWER_STATUS},
+    Win32::System::SystemServices::GUID_CONSOLE_DISPLAY_STATE,
+    Win32::UI::WindowsAndMessaging::{
+        CreateWindowExW, DefWindowProcW, DestroyWindow, RegisterClassW, RegisterPowerSettingNotification,
+        UnregisterPowerSettingNotification, DEVICE_NOTIFY_WINDOW_HANDLE, HPOWERNOTIFY, PBT_POWERSETTINGCHANGE,
+        WINDOW_EX_STYLE, WINDOW_STYLE, WM_DISPLAYCHANGE, WM_POWERBROADCAST, WNDCLASSW,
+    },
+};
+
+/// Whether the system runs on battery, or `None` if Windows doesn't know (e.g. desktops without a UPS driver).
+pub fn on_battery() -> Option<bool> {
+    let mut status = SYSTEM_POWER_STATUS::default();
+    unsafe { GetSystemPowerStatus(&mut status) }.ok()?;
+    match status.ACLineStatus {
+        0 => Some(true),
+        1 => Some(false),
+        _ => None,
+    }
+}
+
+// The window procedure has no context pointer of its own, and there is only ever one overlay manager
+static OVERLAY_MANAGER: OnceCell<Arc<OverlayManager>> = OnceCell::new();
+static DISPLAY_CHANGED: AtomicBool = AtomicBool::new(false);
+
+/// True once after monitors were added, removed or changed resolution since the last call.
+pub fn take_display_changed() -> bool {
+    DISPLAY_CHANGED.swap(false, Ordering::AcqRel)
+}
+
+/// Hidden window that receives console display on/off notifications and suspends the overlay
+/// render loop while the display is off. Also notes display topology changes for `take_display_changed`.
+///
+/// Lives on the main thread, so its messages are dispatched by the main loop or by the settings window's event loop.
+pub struct DisplayPowerMonitor {
+    hwnd: HWND,
+    notify: HPOWERNOTIFY,
+}
+
+impl DisplayPowerMonitor {
+    pub fn register(overlay_manager: Arc<OverlayManager>) -> Result<Self> {
+        let _ = OVERLAY_MANAGER.set(overlay_manager);
+
+        unsafe {
+            let class_name = w!("ChromaBridgeDisplayPower");
+            let hinstance = GetModuleHandleW(None)?;
+
+            let wc = WNDCLASSW {
+                lpfnWndProc: Some(window_proc),
+                hInstance: hinstance.into(),
+                lpszClassName: class_name,
+                ..Default::default()
+            };
+            RegisterClassW(&wc);
+
+            // Never shown; a message-only window would not receive WM_POWERBROADCAST
+            let hwnd = CreateWindowExW(
+                WINDOW_EX_STYLE::default(),
+                class_name,
+                w!("ChromaBridge Display Power"),
+                WINDOW_STYLE::default(),
+                0, 0, 0, 0,
+                None, None,
+                Some(HINSTANCE(hinstance.0)),
+                None,
+            )?;
+
+            // Windows immediately sends the current display state after registering
+            let notify = match RegisterPowerSettingNotification(HANDLE(hwnd.0), &GUID_CONSOLE_DISPLAY_STATE, DEVICE_NOTIFY_WINDOW_HANDLE) {
+                Ok(notify) => notify,
+                Err(e) => {
+                    let _ = DestroyWindow(hwnd);
+                    return Err(e.into());
+                }
+            };
+
+            Ok(Self { hwnd, notify })
+        }
+    }
+}
+
+impl Drop for DisplayPowerMonitor {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = UnregisterPowerSettingNotification(self.notify);
+            let _ = DestroyWindow(self.hwnd);
+        }
+    }
+}
+
+unsafe extern "system" fn window_proc(hwnd: HWND, msg: u32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    if msg == WM_POWERBROADCAST && wparam.0 as u32 == PBT_POWERSETTINGCHANGE && lparam.0 != 0 {
+        let setting = &*(lparam.0 as *const POWERBROADCAST_SETTING);
+        if setting.PowerSetting == GUID_CONSOLE_DISPLAY_STATE && setting.DataLength as usize >= std::mem::size_of::<u32>() {
+            // 0 = off, 1 = on, 2 = dimmed (still visible, keep rendering)
+            let display_state = std::ptr::read_unaligned(setting.Data.as_ptr() as *const u32);
+            if let Some(overlay_manager) = OVERLAY_MANAGER.get() {
+                overlay_manager.set_display_on(display_state != 0);
+            }
+        }
+        return LRESULT(1);
+    }
+    if msg == WM_DISPLAYCHANGE {
+        DISPLAY_CHANGED.store(true, Ordering::Release);
+    }
+
+    DefWindowProcW(hwnd, msg, wparam, lparam)
+}
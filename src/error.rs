@@ -0,0 +1,106 @@
+use std::path::PathBuf;
+use thiserror::Error;
+
+/// Why a spectrum file or spectrum couldn't be loaded.
+#[derive(Debug, Error)]
+pub enum SpectrumError {
+    /// The file couldn't be read, e.g. because it doesn't exist.
+    #[error("Failed to read spectrum file {}", .path.display())]
+    Io { path: PathBuf, source: std::io::Error },
+    /// The file isn't JSON in the spectrum file format.
+    #[error("Failed to parse spectrum file {}", .path.display())]
+    Parse { path: PathBuf, source: serde_json::Error },
+    /// The spectra parsed but break a rule. Indices are 0-based and missing where the rule isn't
+    /// about a single spectrum or node; `field` is the JSON key at fault, e.g. `"hue"`.
+    #[error("{}", describe_validation(.spectrum_index, .node_index, .reason))]
+    Validation {
+        spectrum_index: Option<usize>,
+        node_index: Option<usize>,
+        field: &'static str,
+        reason: String,
+    },
+}
+
+impl SpectrumError {
+    pub(crate) fn invalid(field: &'static str, reason: impl Into<String>) -> Self {
+        SpectrumError::Validation {
+            spectrum_index: None,
+            node_index: None,
+            field,
+            reason: reason.into(),
+        }
+    }
+
+    /// Places a node's validation error at `index` in its spectrum.
+    pub(crate) fn at_node(self, index: usize) -> Self {
+        match self {
+            SpectrumError::Validation { spectrum_index, field, reason, .. } => SpectrumError::Validation {
+                spectrum_index,
+                node_index: Some(index),
+                field,
+                reason,
+            },
+            other => other,
+        }
+    }
+
+    /// Places a spectrum's validation error at `index` in its file.
+    pub(crate) fn in_spectrum(self, index: usize) -> Self {
+        match self {
+            SpectrumError::Validation { node_index, field, reason, .. } => SpectrumError::Validation {
+                spectrum_index: Some(index),
+                node_index,
+                field,
+                reason,
+            },
+            other => other,
+        }
+    }
+}
+
+fn describe_validation(spectrum_index: &Option<usize>, node_index: &Option<usize>, reason: &str) -> String {
+    match (spectrum_index, node_index) {
+        (Some(spectrum), Some(node)) => format!("Spectrum {}, node {}: {}", spectrum + 1, node + 1, reason),
+        (Some(spectrum), None) => format!("Spectrum {}: {}", spectrum + 1, reason),
+        (None, Some(node)) => format!("Node {}: {}", node + 1, reason),
+        (None, None) => reason.to_string(),
+    }
+}
+
+/// Why a noise texture couldn't be loaded.
+#[derive(Debug, Error)]
+pub enum NoiseError {
+    #[error("Failed to open noise texture {}", .path.display())]
+    Io { path: PathBuf, source: std::io::Error },
+    /// The file isn't an image the noise loader understands.
+    #[error("Failed to decode noise texture {}", .path.display())]
+    Decode {
+        path: PathBuf,
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+    /// Refused before decoding, see `NoiseTexture::load_with_max_size`.
+    #[error("Noise texture is {width}x{height}, larger than the maximum of {max_size}x{max_size}")]
+    TooLarge { width: u32, height: u32, max_size: u32 },
+}
+
+/// Why the settings database couldn't be opened or its settings loaded.
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("{} does not exist", .0.display())]
+    Missing(PathBuf),
+    /// SQLite failed; `action` says at what, e.g. `"open database"`.
+    #[error("Failed to {action}")]
+    Database { action: &'static str, source: rusqlite::Error },
+    /// SQLite's consistency check found damage, described by SQLite.
+    #[error("Database is damaged: {0}")]
+    Damaged(String),
+    /// The saved settings are in the database but not readable as settings.
+    #[error("Failed to parse state JSON")]
+    Parse { source: serde_json::Error },
+}
+
+impl ConfigError {
+    pub(crate) fn database(action: &'static str) -> impl FnOnce(rusqlite::Error) -> Self {
+        move |source| ConfigError::Database { action, source }
+    }
+}
@@ -0,0 +1,100 @@
+use anyhow::{Context, Result};
+use chromabridge::{log_info, log_warn, OverlaySource};
+use image::RgbaImage;
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Folder in the app data folder that holds the dumps, one timestamped folder each.
+pub const DUMP_DIR_NAME: &str = "debug_frames";
+
+/// Dumps kept; the oldest are deleted when a new one is written.
+const KEEP_DUMPS: usize = 20;
+
+/// A debug frame dump asked for in the settings window and taken by the overlay thread on the
+/// next frame it renders.
+#[derive(Default)]
+pub struct FrameDump {
+    armed: AtomicBool,
+    /// The folder of the last finished dump, or why it failed.
+    result: Mutex<Option<Result<PathBuf, String>>>,
+}
+
+impl FrameDump {
+    pub fn arm(&self) {
+        self.armed.store(true, Ordering::Release);
+    }
+
+    pub fn is_armed(&self) -> bool {
+        self.armed.load(Ordering::Acquire)
+    }
+
+    /// True once per `arm`. Only a load while not armed, so checking every frame costs nothing.
+    pub fn take_armed(&self) -> bool {
+        self.armed.load(Ordering::Relaxed) && self.armed.swap(false, Ordering::AcqRel)
+    }
+
+    pub fn finish(&self, result: Result<PathBuf>) {
+        let result = match result {
+            Ok(folder) => {
+                log_info!("Debug frames saved to {}", folder.display());
+                Ok(folder)
+            }
+            Err(e) => {
+                log_warn!("Failed to dump debug frames: {:#}", e);
+                Err(format!("{:#}", e))
+            }
+        };
+        *self.result.lock() = Some(result);
+    }
+
+    pub fn take_result(&self) -> Option<Result<PathBuf, String>> {
+        self.result.lock().take()
+    }
+}
+
+/// The sidecar written next to the images.
+#[derive(Debug, Serialize)]
+pub struct FrameDumpInfo {
+    pub timestamp: String,
+    pub monitor: usize,
+    pub source: OverlaySource,
+    pub spectrum: Option<String>,
+    /// The strength setting; the blend weight actually used is in `constant_buffer`.
+    pub strength: f32,
+    pub noise: Option<String>,
+    /// The `SpectrumParams` the frame was drawn with, named as in shader_params.rs.
+    pub constant_buffer: serde_json::Value,
+}
+
+/// Writes `captured` (what the shader sampled), `corrected` (what it rendered) and `info` into a
+/// new folder in `dir`. Returns that folder.
+pub fn save(dir: &Path, captured: &RgbaImage, corrected: &RgbaImage, info: &FrameDumpInfo) -> Result<PathBuf> {
+    let folder = dir.join(chrono::Local::now().format("%Y%m%d-%H%M%S-%3f").to_string());
+    std::fs::create_dir_all(&folder).with_context(|| format!("Failed to create {}", folder.display()))?;
+
+    captured.save(folder.join("captured.png")).context("Failed to write the captured frame")?;
+    corrected.save(folder.join("corrected.png")).context("Failed to write the corrected frame")?;
+    let json = serde_json::to_string_pretty(info)?;
+    std::fs::write(folder.join("frame.json"), json).context("Failed to write the frame info")?;
+
+    prune(dir);
+    Ok(folder)
+}
+
+/// Deletes all but the newest `KEEP_DUMPS` dumps; their timestamped names sort by age.
+fn prune(dir: &Path) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    let mut dumps: Vec<PathBuf> = entries.flatten().map(|e| e.path()).filter(|path| path.is_dir()).collect();
+    dumps.sort();
+
+    let excess = dumps.len().saturating_sub(KEEP_DUMPS);
+    for old in &dumps[..excess] {
+        if let Err(e) = std::fs::remove_dir_all(old) {
+            log_warn!("Failed to delete old debug frames {}: {}", old.display(), e);
+        }
+    }
+}
@@ -0,0 +1,120 @@
+#[cfg(windows)]
+use windows::{
+    core::PWSTR,
+    Win32::Foundation::{CloseHandle, RECT},
+    Win32::Graphics::Gdi::{GetMonitorInfoW, MonitorFromWindow, MONITORINFO, MONITOR_DEFAULTTONEAREST},
+    Win32::System::Threading::{
+        GetCurrentProcessId, OpenProcess, QueryFullProcessImageNameW, PROCESS_NAME_WIN32,
+        PROCESS_QUERY_LIMITED_INFORMATION,
+    },
+    Win32::UI::WindowsAndMessaging::{
+        GetClassNameW, GetDesktopWindow, GetForegroundWindow, GetShellWindow, GetWindowRect, GetWindowThreadProcessId,
+    },
+};
+
+/// The desktop wallpaper windows. They cover the monitor and take the foreground when the desktop
+/// is clicked, but nothing there runs fullscreen.
+#[cfg(windows)]
+const DESKTOP_CLASSES: [&str; 2] = ["WorkerW", "Progman"];
+
+/// A foreground window covering its entire monitor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FullscreenApp {
+    pub process_name: String,
+}
+
+/// Whether a window at `window` fills `overlay_monitor`, the monitor the overlay runs on.
+/// `window_monitor` is the monitor Windows puts the window on, which has to be that one, so a
+/// window spanning several monitors only counts for its main one. Rects are (left, top, right, bottom).
+#[cfg(any(windows, test))]
+fn fills_overlay_monitor(window: [i32; 4], window_monitor: [i32; 4], overlay_monitor: [i32; 4]) -> bool {
+    let [left, top, right, bottom] = overlay_monitor;
+    window_monitor == overlay_monitor && window[0] <= left && window[1] <= top && window[2] >= right && window[3] >= bottom
+}
+
+/// Returns the foreground app if it covers the whole monitor at `overlay_monitor` (exclusive or
+/// borderless fullscreen), the overlay's monitor as (left, top, right, bottom). Apps fullscreen on
+/// another monitor don't affect the overlay.
+#[cfg(windows)]
+pub fn detect_fullscreen_app(overlay_monitor: [i32; 4]) -> Option<FullscreenApp> {
+    unsafe {
+        let hwnd = GetForegroundWindow();
+        if hwnd.is_invalid() || hwnd == GetDesktopWindow() || hwnd == GetShellWindow() {
+            return None;
+        }
+
+        let mut class = [0u16; 64];
+        let len = GetClassNameW(hwnd, &mut class) as usize;
+        if DESKTOP_CLASSES.contains(&String::from_utf16_lossy(&class[..len]).as_str()) {
+            return None;
+        }
+
+        let mut pid = 0u32;
+        GetWindowThreadProcessId(hwnd, Some(&mut pid));
+        if pid == 0 || pid == GetCurrentProcessId() {
+            return None;
+        }
+
+        let mut window_rect = RECT::default();
+        GetWindowRect(hwnd, &mut window_rect).ok()?;
+
+        let monitor = MonitorFromWindow(hwnd, MONITOR_DEFAULTTONEAREST);
+        let mut info = MONITORINFO {
+            cbSize: std::mem::size_of::<MONITORINFO>() as u32,
+            ..Default::default()
+        };
+        if !GetMonitorInfoW(monitor, &mut info).as_bool() {
+            return None;
+        }
+
+        let edges = |rect: RECT| [rect.left, rect.top, rect.right, rect.bottom];
+        if !fills_overlay_monitor(edges(window_rect), edges(info.rcMonitor), overlay_monitor) {
+            return None;
+        }
+
+        Some(FullscreenApp {
+            process_name: process_name(pid).unwrap_or_else(|| format!("pid {}", pid)),
+        })
+    }
+}
+
+#[cfg(windows)]
+pub(crate) unsafe fn process_name(pid: u32) -> Option<String> {
+    let handle = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, pid).ok()?;
+
+    let mut buffer = [0u16; 260];
+    let mut size = buffer.len() as u32;
+    let result = QueryFullProcessImageNameW(handle, PROCESS_NAME_WIN32, PWSTR(buffer.as_mut_ptr()), &mut size);
+    let _ = CloseHandle(handle);
+    result.ok()?;
+
+    let path = String::from_utf16_lossy(&buffer[..size as usize]);
+    path.rsplit(['\\', '/']).next().map(|name| name.to_string())
+}
+
+#[cfg(not(windows))]
+pub fn detect_fullscreen_app(_overlay_monitor: [i32; 4]) -> Option<FullscreenApp> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_windows_filling_the_overlay_monitor_count() {
+        let primary = [0, 0, 1920, 1080];
+        let secondary = [1920, 0, 4480, 1440];
+
+        assert!(fills_overlay_monitor(primary, primary, primary));
+        // Exclusive fullscreen windows often hang over the edges
+        assert!(fills_overlay_monitor([-8, -8, 1928, 1088], primary, primary));
+        assert!(!fills_overlay_monitor([0, 0, 1920, 1040], primary, primary));
+
+        // Fullscreen on the other monitor
+        assert!(!fills_overlay_monitor(secondary, secondary, primary));
+        // Stretched over both, but Windows puts it on the larger one
+        assert!(!fills_overlay_monitor([0, 0, 4480, 1440], secondary, primary));
+        assert!(fills_overlay_monitor([0, 0, 4480, 1440], secondary, secondary));
+    }
+}
@@ -0,0 +1,124 @@
+use anyhow::Result;
+use chromabridge::log_info;
+use windows::{
+    core::{factory, Interface},
+    Graphics::Capture::{Direct3D11CaptureFrame, Direct3D11CaptureFramePool, GraphicsCaptureItem, GraphicsCaptureSession},
+    Graphics::DirectX::{Direct3D11::IDirect3DDevice, DirectXPixelFormat},
+    Graphics::SizeInt32,
+    Win32::Graphics::Direct3D11::{ID3D11Device, ID3D11Texture2D},
+    Win32::Graphics::Dxgi::IDXGIDevice,
+    Win32::System::WinRT::Direct3D11::{CreateDirect3D11DeviceFromDXGIDevice, IDirect3DDxgiInterfaceAccess},
+    Win32::System::WinRT::Graphics::Capture::IGraphicsCaptureItemInterop,
+};
+
+/// Frames the pool keeps; the overlay only ever holds the latest one.
+const FRAME_BUFFERS: i32 = 2;
+
+/// A captured frame; the pool reuses its texture once this is dropped.
+pub struct CapturedFrame {
+    pub texture: ID3D11Texture2D,
+    _frame: Direct3D11CaptureFrame,
+}
+
+/// Monitor capture through Windows.Graphics.Capture.
+///
+/// Used when desktop duplication is held by another app (OBS, Discord, ...), since any number of
+/// Windows.Graphics.Capture sessions can share an output. Frames arrive already in the monitor's
+/// orientation and carry no separate pointer information.
+pub struct GraphicsCapturer {
+    device: IDirect3DDevice,
+    // Kept alive for the session
+    _item: GraphicsCaptureItem,
+    frame_pool: Direct3D11CaptureFramePool,
+    session: GraphicsCaptureSession,
+    size: SizeInt32,
+    cursor_captured: bool,
+}
+
+impl GraphicsCapturer {
+    /// Requires Windows 10 1903 or later.
+    pub fn is_supported() -> bool {
+        GraphicsCaptureSession::IsSupported().unwrap_or(false)
+    }
+
+    /// Starts capturing the monitor of `d3d_device`'s adapter output `monitor_index`, the same
+    /// output desktop duplication would use.
+    pub unsafe fn new(d3d_device: &ID3D11Device, monitor_index: usize, capture_cursor: bool) -> Result<Self> {
+        let dxgi_device: IDXGIDevice = d3d_device.cast()?;
+        let monitor = dxgi_device.GetAdapter()?.EnumOutputs(monitor_index as u32)?.GetDesc()?.Monitor;
+        let device: IDirect3DDevice = CreateDirect3D11DeviceFromDXGIDevice(&dxgi_device)?.cast()?;
+
+        let interop = factory::<GraphicsCaptureItem, IGraphicsCaptureItemInterop>()?;
+        let item: GraphicsCaptureItem = interop.CreateForMonitor(monitor)?;
+        let size = item.Size()?;
+
+        // Free threaded, so frames can be polled from the render loop without a dispatcher queue
+        let frame_pool = Direct3D11CaptureFramePool::CreateFreeThreaded(&device, DirectXPixelFormat::B8G8R8A8UIntNormalized, FRAME_BUFFERS, size)?;
+        let session = frame_pool.CreateCaptureSession(&item)?;
+        // Both need newer Windows builds; older ones draw the cursor and the yellow border regardless
+        let _ = session.SetIsCursorCaptureEnabled(capture_cursor);
+        let _ = session.SetIsBorderRequired(false);
+        session.StartCapture()?;
+
+        log_info!("Windows.Graphics.Capture initialized for monitor {} ({}x{})", monitor_index, size.Width, size.Height);
+
+        Ok(Self {
+            device,
+            _item: item,
+            frame_pool,
+            session,
+            size,
+            cursor_captured: capture_cursor,
+        })
+    }
+
+    /// Creates what `new` would for `monitor_index` short of the capture session, which is what
+    /// brings up the capture border, and drops it again.
+    pub unsafe fn probe(d3d_device: &ID3D11Device, monitor_index: usize) -> Result<()> {
+        let dxgi_device: IDXGIDevice = d3d_device.cast()?;
+        let monitor = dxgi_device.GetAdapter()?.EnumOutputs(monitor_index as u32)?.GetDesc()?.Monitor;
+        let _device: IDirect3DDevice = CreateDirect3D11DeviceFromDXGIDevice(&dxgi_device)?.cast()?;
+
+        let interop = factory::<GraphicsCaptureItem, IGraphicsCaptureItemInterop>()?;
+        let item: GraphicsCaptureItem = interop.CreateForMonitor(monitor)?;
+        item.Size()?;
+        Ok(())
+    }
+
+    /// The newest frame since the last call, if any. Older queued frames are dropped.
+    pub unsafe fn latest_frame(&mut self) -> Result<Option<CapturedFrame>> {
+        let mut latest = None;
+        while let Ok(frame) = self.frame_pool.TryGetNextFrame() {
+            latest = Some(frame);
+        }
+        let Some(frame) = latest else {
+            return Ok(None);
+        };
+
+        // Frames keep the pool's size until it is recreated, so the next ones match the monitor again
+        let content_size = frame.ContentSize()?;
+        if (content_size.Width, content_size.Height) != (self.size.Width, self.size.Height) {
+            log_info!("Captured monitor size changed to {}x{}", content_size.Width, content_size.Height);
+            self.size = content_size;
+            self.frame_pool.Recreate(&self.device, DirectXPixelFormat::B8G8R8A8UIntNormalized, FRAME_BUFFERS, content_size)?;
+            return Ok(None);
+        }
+
+        let texture = frame.Surface()?.cast::<IDirect3DDxgiInterfaceAccess>()?.GetInterface::<ID3D11Texture2D>()?;
+        Ok(Some(CapturedFrame { texture, _frame: frame }))
+    }
+
+    /// Draws the pointer into captured frames, where the correction pass recolors it.
+    pub fn set_cursor_capture(&mut self, enabled: bool) {
+        if enabled != self.cursor_captured && self.session.SetIsCursorCaptureEnabled(enabled).is_ok() {
+            self.cursor_captured = enabled;
+        }
+    }
+}
+
+impl Drop for GraphicsCapturer {
+    fn drop(&mut self) {
+        let _ = self.session.Close();
+        let _ = self.frame_pool.Close();
+    }
+}
@@ -1,8 +1,24 @@
-use crate::StateManager;
-use anyhow::Result;
+use crate::{tr, StateManager};
+use chromabridge::state::AppState;
+use chromabridge::{SessionSummary, StateChanges};
+use crate::command_palette::{Action, CommandPalette, PaletteOutcome};
+use crate::asset_watcher::AssetWatcher;
+use crate::gui_host::GuiWindow;
+use crate::overlay::OverlayStatus;
+use crate::noise_preview::NoisePreview;
+use crate::output_preview::OutputPreviewWindow;
+use crate::render_sweep::RenderSweep;
+use crate::screenshot_preview::ScreenshotPreview;
+use crate::settings_search::{self, Setting, SettingsSection};
+use crate::theme;
+use chromabridge::number_format::NumberFormat;
+use chromabridge::update_check;
+use chromabridge::trash::{AssetKind, TrashedAsset};
+use anyhow::{Context, Result};
+use std::collections::HashSet;
+use std::rc::Rc;
 use std::sync::Arc;
 use std::time::Instant;
-use std::path::Path;
 
 #[cfg(windows)]
 use windows::{
@@ -11,115 +27,9 @@ use windows::{
         EnumDisplayMonitors, EnumDisplaySettingsW, GetMonitorInfoW, HDC, HMONITOR, MONITORINFOEXW,
         DEVMODEW, ENUM_CURRENT_SETTINGS,
     },
-    Win32::System::Registry::{
-        RegOpenKeyExW, RegSetValueExW, RegDeleteValueW, RegCloseKey,
-        HKEY_CURRENT_USER, HKEY, KEY_READ, KEY_WRITE, REG_VALUE_TYPE,
-    },
-    Win32::Foundation::ERROR_FILE_NOT_FOUND,
 };
 
-#[cfg(windows)]
-fn check_startup_registry_exists() -> Result<bool> {
-    use windows::core::HSTRING;
-    use windows::Win32::System::Registry::RegQueryValueExW;
-
-    unsafe {
-        let subkey = HSTRING::from("Software\\Microsoft\\Windows\\CurrentVersion\\Run");
-        let value_name = HSTRING::from("ChromaBridge");
-        let mut hkey = HKEY::default();
-
-        let open_result = RegOpenKeyExW(HKEY_CURRENT_USER, &subkey, None, KEY_READ, &mut hkey);
-
-        if open_result == ERROR_FILE_NOT_FOUND {
-            return Ok(false);
-        }
-
-        if open_result.is_err() {
-            return Err(anyhow::anyhow!("Failed to open registry key: {:?}", open_result));
-        }
-
-        let mut buffer = [0u16; 512];
-        let mut buffer_size = (buffer.len() * 2) as u32;
-        let mut value_type = REG_VALUE_TYPE::default();
-
-        let query_result = RegQueryValueExW(
-            hkey,
-            &value_name,
-            None,
-            Some(&mut value_type),
-            Some(buffer.as_mut_ptr() as *mut u8),
-            Some(&mut buffer_size),
-        );
-
-        let _ = RegCloseKey(hkey);
-        Ok(query_result.is_ok())
-    }
-}
-
-#[cfg(windows)]
-fn set_startup_registry(enabled: bool, exe_path: &Path) -> Result<()> {
-    use windows::core::HSTRING;
-    use windows::Win32::System::Registry::REG_SZ;
-
-    unsafe {
-        let subkey = HSTRING::from("Software\\Microsoft\\Windows\\CurrentVersion\\Run");
-        let value_name = HSTRING::from("ChromaBridge");
-        let mut hkey = HKEY::default();
-
-        let open_result = RegOpenKeyExW(HKEY_CURRENT_USER, &subkey, None, KEY_WRITE, &mut hkey);
-
-        if open_result.is_err() {
-            return Err(anyhow::anyhow!("Failed to open registry key for write: {:?}", open_result));
-        }
-
-        let result = if enabled {
-            let path_str = exe_path.to_string_lossy();
-            let path_wide: Vec<u16> = path_str.encode_utf16().chain(std::iter::once(0)).collect();
-            let bytes: &[u8] = std::slice::from_raw_parts(
-                path_wide.as_ptr() as *const u8,
-                path_wide.len() * 2
-            );
-
-            RegSetValueExW(
-                hkey,
-                &value_name,
-                None,
-                REG_SZ,
-                Some(bytes),
-            )
-        } else {
-            RegDeleteValueW(hkey, &value_name)
-        };
-
-        let _ = RegCloseKey(hkey);
-
-        if result.is_err() {
-            return Err(anyhow::anyhow!("Failed to set/delete registry value: {:?}", result));
-        }
-
-        Ok(())
-    }
-}
-
-#[cfg(not(windows))]
-fn check_startup_registry_exists() -> Result<bool> {
-    Ok(false)
-}
-
-#[cfg(not(windows))]
-fn set_startup_registry(_enabled: bool, _exe_path: &Path) -> Result<()> {
-    Ok(())
-}
-
-#[derive(Debug, Clone)]
-pub struct MonitorInfo {
-    pub index: usize,
-    pub name: String,
-    pub is_primary: bool,
-    pub width: i32,
-    pub height: i32,
-    pub refresh_rate: u32,
-}
+pub use chromabridge::monitor::MonitorInfo;
 
 #[cfg(windows)]
 pub fn enumerate_monitors() -> Result<Vec<MonitorInfo>> {
@@ -166,32 +76,40 @@ unsafe extern "system" fn monitor_enum_proc(
         let name = String::from_utf16_lossy(
             &info.szDevice.iter().take_while(|&&c| c != 0).copied().collect::<Vec<_>>(),
         );
+        let device_string = chromabridge::monitor::device_string(&name).unwrap_or_default();
+        let is_virtual = chromabridge::monitor::is_virtual_display(&device_string);
 
         let refresh_rate = {
             let mut dev_mode: DEVMODEW = std::mem::zeroed();
             dev_mode.dmSize = std::mem::size_of::<DEVMODEW>() as u16;
 
-            if EnumDisplaySettingsW(
+            let reported = EnumDisplaySettingsW(
                 windows::core::PCWSTR(info.szDevice.as_ptr()),
                 ENUM_CURRENT_SETTINGS,
                 &mut dev_mode,
-            ).as_bool() {
-                dev_mode.dmDisplayFrequency
-            } else {
-                60
-            }
+            ).as_bool().then_some(dev_mode.dmDisplayFrequency);
+            chromabridge::monitor::refresh_rate_or_default(&name, reported, is_virtual)
         };
 
         let mut monitors = monitors.lock().unwrap();
         let index = monitors.len();
+        if let Err(unusable) = chromabridge::monitor::check_size(width, height) {
+            crate::log_warn!("Monitor {} ({}) {}, listed as unusable", index + 1, name, unusable);
+        }
+        if is_virtual {
+            crate::log_info!("Monitor {} ({}) is a virtual display: {}", index + 1, name, device_string);
+        }
 
         monitors.push(MonitorInfo {
             index,
             name,
             is_primary,
+            left: rect.left,
+            top: rect.top,
             width,
             height,
             refresh_rate,
+            device_string,
         });
     }
 
@@ -204,27 +122,223 @@ pub fn enumerate_monitors() -> Result<Vec<MonitorInfo>> {
         index: 0,
         name: "Primary Monitor".to_string(),
         is_primary: true,
+        left: 0,
+        top: 0,
         width: 1920,
         height: 1080,
         refresh_rate: 60,
+        device_string: String::new(),
     }])
 }
 
+pub struct CrashNotice {
+    pub report_path: std::path::PathBuf,
+    pub crash_dir: std::path::PathBuf,
+    pub overlay_restore_skipped: bool,
+}
+
+struct MatrixImportDialog {
+    matrix_text: String,
+    file_path: String,
+    name: String,
+    node_count: usize,
+    preview: Option<chromabridge::MatrixImport>,
+    error: Option<String>,
+}
+
+impl Default for MatrixImportDialog {
+    fn default() -> Self {
+        Self {
+            matrix_text: "1 0 0\n0 1 0\n0 0 1".to_string(),
+            file_path: String::new(),
+            name: "imported-matrix".to_string(),
+            node_count: 24,
+            preview: None,
+            error: None,
+        }
+    }
+}
+
+struct ShareCodeImportDialog {
+    code: String,
+    name: String,
+    preview: Option<chromabridge::spectrum::SpectrumFile>,
+    error: Option<String>,
+}
+
+impl Default for ShareCodeImportDialog {
+    fn default() -> Self {
+        Self {
+            code: String::new(),
+            name: "shared-spectrum".to_string(),
+            preview: None,
+            error: None,
+        }
+    }
+}
+
+/// Creates the subfolder an imported spectrum named like "gaming/deutan-strong" is saved into.
+fn create_spectrum_folder(path: &std::path::Path) -> Result<()> {
+    if let Some(folder) = path.parent() {
+        std::fs::create_dir_all(folder).with_context(|| format!("Failed to create folder {}", folder.display()))?;
+    }
+    Ok(())
+}
+
+/// What the user is told about a spectrum that failed to load or save: where the file or the
+/// spectrum is wrong when that is known, the error chain otherwise.
+fn spectrum_error_message(error: &anyhow::Error) -> String {
+    use chromabridge::SpectrumError;
+
+    match error.downcast_ref::<SpectrumError>() {
+        Some(SpectrumError::Io { path, source }) if source.kind() == std::io::ErrorKind::NotFound => {
+            tr!("spectrum.error_not_found", path = path.display())
+        }
+        Some(SpectrumError::Parse { source, .. }) => {
+            tr!("spectrum.error_parse", line = source.line(), column = source.column())
+        }
+        Some(SpectrumError::Validation { spectrum_index: Some(spectrum), node_index: Some(node), reason, .. }) => {
+            tr!("spectrum.error_node", spectrum = spectrum + 1, node = node + 1, reason = reason)
+        }
+        Some(SpectrumError::Validation { spectrum_index: Some(spectrum), node_index: None, reason, .. }) => {
+            tr!("spectrum.error_spectrum", spectrum = spectrum + 1, reason = reason)
+        }
+        _ => format!("{:#}", error),
+    }
+}
+
+fn spectrum_deficiencies(state: &StateManager, spectrum_files: &[String]) -> Vec<Option<chromabridge::Deficiency>> {
+    spectrum_files
+        .iter()
+        .map(|name| {
+            let file = state.get_spectrum_path(name).ok().and_then(|path| chromabridge::SpectrumFile::load(path).ok());
+            file.and_then(|file| file.metadata.deficiency)
+        })
+        .collect()
+}
+
+fn deficiency_label(deficiency: chromabridge::Deficiency) -> String {
+    use chromabridge::Deficiency;
+
+    match deficiency {
+        Deficiency::Protan => tr!("deficiency.protan"),
+        Deficiency::Deutan => tr!("deficiency.deutan"),
+        Deficiency::RedGreen => tr!("deficiency.red_green"),
+        Deficiency::Tritan => tr!("deficiency.tritan"),
+        Deficiency::Achromat => tr!("deficiency.achromat"),
+        Deficiency::Other => tr!("deficiency.other"),
+    }
+}
+
+/// Opens the release page in the default browser; only https addresses get this far, see
+/// `Release::from_manifest`.
+fn open_release_page(release: &update_check::Release) {
+    #[cfg(windows)]
+    {
+        let _ = std::process::Command::new("explorer").arg(&release.url).spawn();
+    }
+    crate::log_info!("Opened release page of {} ({})", release.version, release.url);
+}
+
+/// A 0–1 slider shown as a whole percentage in the user's number format. Typing accepts either
+/// form, e.g. `70 %`, `70` or `0,7`.
+fn percent_slider(value: &mut f32) -> egui::Slider<'_> {
+    let format = NumberFormat::current();
+    egui::Slider::new(value, 0.0..=1.0)
+        .custom_formatter(move |v, _| format.percent(v, 0))
+        .custom_parser(move |text| format.parse_fraction(text))
+}
+
+fn paint_spectrum_preview(ui: &mut egui::Ui, spectrum: &chromabridge::Spectrum, size: egui::Vec2) {
+    let (rect, _) = ui.allocate_exact_size(size, egui::Sense::hover());
+    let Ok(table) = spectrum.get_rgb_lookup_table(360) else {
+        ui.painter().rect_filled(rect, 0.0, theme::palette(ui).surface);
+        return;
+    };
+
+    let step = rect.width() / 360.0;
+    for (i, rgb) in table.chunks_exact(3).enumerate() {
+        let x = rect.left() + i as f32 * step;
+        let color = egui::Color32::from_rgb(
+            (rgb[0] * 255.0).round() as u8,
+            (rgb[1] * 255.0).round() as u8,
+            (rgb[2] * 255.0).round() as u8,
+        );
+        ui.painter().rect_filled(
+            egui::Rect::from_min_max(egui::pos2(x, rect.top()), egui::pos2(x + step + 0.5, rect.bottom())),
+            0.0,
+            color,
+        );
+    }
+}
+
+#[derive(Default)]
+struct VisionSetupWizard {
+    /// 0 is the intro, 1..=TEST_SCREENS.len() are the tests, anything after is the result.
+    step: usize,
+    answers: Vec<Option<usize>>,
+    start_overlay: bool,
+}
+
+/// Paints an Ishihara-style dot plate: an inner disc of the first color surrounded by the second.
+fn paint_test_panel(ui: &mut egui::Ui, colors: [(u8, u8, u8); 2], size: f32) -> egui::Response {
+    let (rect, response) = ui.allocate_exact_size(egui::vec2(size, size), egui::Sense::click());
+    let painter = ui.painter();
+    let center = rect.center();
+    let radius = size * 0.5 - 2.0;
+    let dot = size / 16.0;
+
+    // Fixed seed so the plates don't flicker between frames
+    let mut seed = 0x9e37_79b9u32;
+    let mut y = rect.top() + dot * 0.5;
+    while y < rect.bottom() {
+        let mut x = rect.left() + dot * 0.5;
+        while x < rect.right() {
+            let pos = egui::pos2(x, y);
+            let distance = pos.distance(center);
+            if distance + dot * 0.5 <= radius {
+                seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                let jitter = ((seed >> 24) % 21) as i32 - 10;
+                let (r, g, b) = if distance < radius * 0.55 { colors[0] } else { colors[1] };
+                let shade = |c: u8| (c as i32 + jitter).clamp(0, 255) as u8;
+                painter.circle_filled(pos, dot * 0.45, egui::Color32::from_rgb(shade(r), shade(g), shade(b)));
+            }
+            x += dot;
+        }
+        y += dot;
+    }
+
+    let palette = theme::palette(ui);
+    let stroke = if response.hovered() {
+        egui::Stroke::new(2.0, palette.strong_text)
+    } else {
+        egui::Stroke::new(1.0, palette.border)
+    };
+    painter.circle_stroke(center, radius, stroke);
+
+    response
+}
+
 pub struct SettingsGui {
     state: Arc<StateManager>,
     overlay_manager: Arc<crate::overlay::OverlayManager>,
-
-    tray_icon: Option<tray_icon::TrayIcon>,
-    overlay_menu_item: Option<tray_icon::menu::CheckMenuItem>,
+    window: Arc<GuiWindow>,
 
     monitors: Vec<MonitorInfo>,
     selected_monitor: usize,
 
     spectrum_files: Vec<String>,
-    selected_spectrum: Option<usize>,
+    /// The deficiency each of `spectrum_files` is made for, by which the list is sorted.
+    spectrum_deficiencies: Vec<Option<chromabridge::Deficiency>>,
+    /// Selections are kept by name, which stays put when files are added or removed; see
+    /// `resolve_selections`.
+    selected_spectrum: Option<String>,
+    /// The selected spectrum file and its load warnings, keyed by file name; `None` inside if it
+    /// failed to load.
+    spectrum_file: Option<(String, Option<(chromabridge::SpectrumFile, Vec<String>)>)>,
 
     noise_files: Vec<String>,
-    selected_noise: Option<usize>,
+    selected_noise: Option<String>,
 
     strength: f32,
 
@@ -235,84 +349,167 @@ pub struct SettingsGui {
     icon_click_times: Vec<Instant>,
 
     overlay_toggle_callback: Option<Box<dyn Fn() + Send>>,
-    overlay_restart_callback: Option<Box<dyn Fn() + Send>>,
+    /// Quits the app when the window is closed with "keep running in tray" off.
+    exit_callback: Option<Box<dyn Fn() + Send>>,
 
     first_frame: bool,
-    close_receiver: Option<crossbeam_channel::Receiver<()>>,
-    toggle_receiver: Option<crossbeam_channel::Receiver<()>>,
-    app_ctx_storage: Option<Arc<parking_lot::Mutex<Option<egui::Context>>>>,
     dragging: bool,
     icon_texture: Option<egui::TextureHandle>,
+    matrix_import: Option<MatrixImportDialog>,
+    share_import: Option<ShareCodeImportDialog>,
+    crash_notice: Option<CrashNotice>,
+    vision_setup: Option<VisionSetupWizard>,
+    reset_notice: Option<std::path::PathBuf>,
+    command_palette: Option<CommandPalette>,
+    /// Palette actions; rebuilt by `refresh_assets` since some are per spectrum file.
+    actions: Rc<Vec<Action>>,
+    /// Rows of the settings window; rebuilt when their labels change (language, monitor).
+    settings: Rc<Vec<Setting>>,
+    settings_query: String,
+    confirm_reset_all: bool,
+    /// Last sessions read from the database; loaded when the history is first shown.
+    session_history: Option<Vec<SessionSummary>>,
+    /// Last startup and overlay start timings read from the database; loaded when first shown.
+    startup_timings: Option<[Option<String>; 2]>,
+    /// Last average render time seen per `ColorMath` mode, indexed by the mode, for comparing them.
+    render_ms_by_color_math: [Option<f32>; 2],
+    /// The color filter app list while it is being edited; committed when the field loses focus.
+    conflict_processes_text: Option<String>,
+    /// The target window's title pattern while it is being edited; committed when the field loses focus.
+    target_title_text: Option<String>,
+    /// The update manifest address while it is being edited; committed when the field loses focus.
+    update_manifest_text: Option<String>,
+    /// Windows offered by the target window picker; listed when it opens.
+    window_choices: Option<Vec<crate::target_window::WindowChoice>>,
+    /// Set from asking for a debug frame dump until its result is shown.
+    awaiting_frame_dump: bool,
+    /// What the current visuals were built from; `None` until the first frame applies them.
+    applied_theme: Option<theme::ThemeSettings>,
+    /// Spectrum or noise texture waiting for the delete confirmation.
+    pending_delete: Option<(AssetKind, String)>,
+    /// Contents of the trash; reloaded when `None`.
+    trash_items: Option<Vec<TrashedAsset>>,
+    confirm_purge: bool,
+    /// Results of the last self-check run from this window.
+    self_check: Option<Vec<crate::self_check::CheckResult>>,
+    /// Set while a self-check runs in the background.
+    self_check_pending: Option<crossbeam_channel::Receiver<Vec<crate::self_check::CheckResult>>>,
+    /// Runs in which the last focus check saw the foreground move, or why it failed.
+    focus_check: Option<Result<usize, String>>,
+    /// Set while a focus check runs in the background.
+    focus_check_pending: Option<crossbeam_channel::Receiver<Result<usize, String>>>,
+    screenshot_preview: Option<ScreenshotPreview>,
+    noise_preview: NoisePreview,
+    render_sweep: Option<RenderSweep>,
+    output_preview: Option<OutputPreviewWindow>,
+    state_changes: crossbeam_channel::Receiver<StateChanges>,
+    asset_watcher: AssetWatcher,
 }
 
 impl SettingsGui {
-    pub fn new(state: Arc<StateManager>, overlay_manager: Arc<crate::overlay::OverlayManager>, ctx_storage: Arc<parking_lot::Mutex<Option<egui::Context>>>) -> Self {
-        use crate::log_info;
+    pub fn new(state: Arc<StateManager>, overlay_manager: Arc<crate::overlay::OverlayManager>, window: Arc<GuiWindow>) -> Self {
+        use crate::{log_debug, log_info};
 
         log_info!("Initializing SettingsGui");
         let monitors = enumerate_monitors().unwrap_or_default();
-        log_info!("Found {} monitors", monitors.len());
+        log_debug!("Found {} monitors", monitors.len());
+
+        let reset_notice = state.take_reset_notice();
+        let migration_report = state.take_migration_report();
+        let settings = state.snapshot();
+        let first_run = settings.spectrum_name.is_none() && !settings.vision_setup_complete;
+        if first_run {
+            log_info!("No spectrum selected yet - starting vision setup");
+        }
 
-        let (selected_monitor, selected_spectrum, selected_noise, strength, show_advanced, show_developer) = state.read(|s| {
+        let (selected_monitor, selected_spectrum, selected_noise, strength, show_advanced, show_developer) = {
+            let s = &settings;
             let monitor = s.last_monitor.unwrap_or(0).min(monitors.len().saturating_sub(1));
-            let spectrum = s.spectrum_name.as_ref().and_then(|name| {
-                state.list_spectrum_files().ok()?.into_iter().position(|s| s == *name)
-            });
-            let noise = s.noise_texture.as_ref().and_then(|name| {
-                state.list_noise_files().ok()?.into_iter().position(|n| n == *name)
-            });
-            (monitor, spectrum, noise, s.strength, s.show_advanced_settings, false)
-        });
+            // Checked against the listed files below, so last session's picks that are gone get reported
+            (monitor, s.spectrum_name.clone(), s.noise_texture.clone(), s.strength, s.show_advanced_settings, false)
+        };
 
         let spectrum_files = state.list_spectrum_files().unwrap_or_default();
-        log_info!("Loaded {} spectrum files", spectrum_files.len());
+        let spectrum_deficiencies = spectrum_deficiencies(&state, &spectrum_files);
+        log_debug!("Loaded {} spectrum files", spectrum_files.len());
 
         let noise_files = state.list_noise_files().unwrap_or_default();
-        log_info!("Loaded {} noise textures", noise_files.len());
+        log_debug!("Loaded {} noise textures", noise_files.len());
 
-        // Sync startup registry with actual state - registry is source of truth
-        let registry_enabled = check_startup_registry_exists().unwrap_or(false);
-        state.update(|s| s.run_at_startup = registry_enabled);
-        log_info!("Startup registry check: {}", registry_enabled);
+        let state_changes = state.subscribe();
+        let asset_watcher = AssetWatcher::spawn(state.spectrums_dir(), state.noise_dir(), Arc::clone(&window));
 
-        Self {
+        let mut gui = Self {
             state,
             overlay_manager,
-            tray_icon: None,
-            overlay_menu_item: None,
+            window,
             monitors,
             selected_monitor,
             spectrum_files,
+            spectrum_deficiencies,
             selected_spectrum,
+            spectrum_file: None,
             noise_files,
             selected_noise,
             strength,
             show_advanced,
             show_developer,
-            status_message: None,
+            // Old ColorInterlacer data imported on this launch
+            status_message: migration_report.map(|report| match report.skipped.len() {
+                0 => tr!("status.legacy_imported", files = report.copied_files),
+                skipped => tr!("status.legacy_imported_partial", files = report.copied_files, skipped = skipped),
+            }),
             icon_click_times: Vec::new(),
             overlay_toggle_callback: None,
-            overlay_restart_callback: None,
+            exit_callback: None,
             first_frame: true,
-            close_receiver: None,
-            toggle_receiver: None,
-            app_ctx_storage: Some(ctx_storage),
             dragging: false,
             icon_texture: None,
-        }
-    }
-
-    pub fn set_close_receiver(&mut self, receiver: crossbeam_channel::Receiver<()>) {
-        self.close_receiver = Some(receiver);
+            matrix_import: None,
+            share_import: None,
+            crash_notice: None,
+            vision_setup: first_run.then(VisionSetupWizard::default),
+            reset_notice,
+            command_palette: None,
+            actions: Rc::new(Vec::new()),
+            settings: Rc::new(Vec::new()),
+            settings_query: String::new(),
+            confirm_reset_all: false,
+            session_history: None,
+            startup_timings: None,
+            render_ms_by_color_math: [None; 2],
+            conflict_processes_text: None,
+            target_title_text: None,
+            update_manifest_text: None,
+            window_choices: None,
+            awaiting_frame_dump: false,
+            applied_theme: None,
+            pending_delete: None,
+            trash_items: None,
+            confirm_purge: false,
+            self_check: None,
+            self_check_pending: None,
+            focus_check: None,
+            focus_check_pending: None,
+            screenshot_preview: None,
+            noise_preview: NoisePreview::default(),
+            render_sweep: None,
+            output_preview: None,
+            state_changes,
+            asset_watcher,
+        };
+        gui.resolve_selections();
+        gui.actions = Rc::new(gui.build_actions());
+        gui.settings = Rc::new(gui.build_settings());
+        gui
     }
 
-    pub fn set_toggle_receiver(&mut self, receiver: crossbeam_channel::Receiver<()>) {
-        self.toggle_receiver = Some(receiver);
+    pub fn set_crash_notice(&mut self, notice: CrashNotice) {
+        self.crash_notice = Some(notice);
     }
 
-    pub fn set_tray_items(&mut self, tray_icon: tray_icon::TrayIcon, overlay_item: tray_icon::menu::CheckMenuItem) {
-        self.tray_icon = Some(tray_icon);
-        self.overlay_menu_item = Some(overlay_item);
+    pub fn set_startup_repaired(&mut self, previous_path: std::path::PathBuf) {
+        self.status_message = Some(tr!("status.startup_repaired", path = previous_path.display()));
     }
 
     pub fn set_overlay_toggle_callback<F>(&mut self, callback: F)
@@ -322,11 +519,11 @@ impl SettingsGui {
         self.overlay_toggle_callback = Some(Box::new(callback));
     }
 
-    pub fn set_overlay_restart_callback<F>(&mut self, callback: F)
+    pub fn set_exit_callback<F>(&mut self, callback: F)
     where
         F: Fn() + Send + 'static,
     {
-        self.overlay_restart_callback = Some(Box::new(callback));
+        self.exit_callback = Some(Box::new(callback));
     }
 
     fn truncate_with_ellipsis(text: &str, max_chars: usize) -> String {
@@ -340,392 +537,3091 @@ impl SettingsGui {
     }
 
     fn refresh_assets(&mut self) {
+        // Naming a selection that disappeared matters more than the counts
+        if self.reload_assets() {
+            self.status_message = Some(tr!(
+                "status.refreshed",
+                spectra = self.spectrum_files.len(),
+                noise = self.noise_files.len()
+            ));
+        }
+    }
+
+    /// Relists the asset folders after the watcher saw them change, naming files that appeared.
+    fn on_assets_changed(&mut self) {
+        let known_spectra: HashSet<String> = self.spectrum_files.iter().cloned().collect();
+        let known_noise: HashSet<String> = self.noise_files.iter().cloned().collect();
+        // A selection that disappeared was named already
+        if !self.reload_assets() {
+            return;
+        }
+
+        let added: Vec<&str> = self.spectrum_files.iter().filter(|name| !known_spectra.contains(*name))
+            .chain(self.noise_files.iter().filter(|name| !known_noise.contains(*name)))
+            .map(String::as_str)
+            .collect();
+        if !added.is_empty() {
+            crate::log_info!("New assets found: {}", added.join(", "));
+            self.status_message = Some(tr!("status.assets_added", names = added.join(", ")));
+        }
+    }
+
+    /// Relists the asset folders. False when a selected file was gone, see `resolve_selections`.
+    fn reload_assets(&mut self) -> bool {
         self.spectrum_files = self.state.list_spectrum_files().unwrap_or_default();
+        self.spectrum_deficiencies = spectrum_deficiencies(&self.state, &self.spectrum_files);
         self.noise_files = self.state.list_noise_files().unwrap_or_default();
+        self.spectrum_file = None;
+        self.trash_items = None;
+        self.actions = Rc::new(self.build_actions());
+        self.resolve_selections()
+    }
 
-        if let Some(idx) = self.selected_spectrum {
-            if idx >= self.spectrum_files.len() {
-                self.selected_spectrum = None;
-            }
+    /// Looks the spectrum and noise texture of the state up in the listed files. A shown
+    /// selection whose file is gone is cleared and named in the status message; the state keeps
+    /// it, so a running overlay goes on with what it loaded. False when that happened.
+    fn resolve_selections(&mut self) -> bool {
+        use chromabridge::state::resolve_asset;
+
+        let (spectrum, noise) = self.state.read(|s| (s.spectrum_name.clone(), s.noise_texture.clone()));
+        let spectrum = resolve_asset(&self.spectrum_files, spectrum.as_deref());
+        let noise = resolve_asset(&self.noise_files, noise.as_deref());
+
+        // Names the state was switched to without a relist aren't news to the user
+        let missing: Vec<&str> = [(&spectrum, &self.selected_spectrum), (&noise, &self.selected_noise)]
+            .into_iter()
+            .filter_map(|(resolved, shown)| resolved.missing().filter(|name| shown.as_deref() == Some(*name)))
+            .collect();
+        let kept = missing.is_empty();
+        if !kept {
+            crate::log_warn!("Selected assets no longer exist: {}", missing.join(", "));
+            self.status_message = Some(tr!("status.asset_missing", names = missing.join(", ")));
         }
 
-        if let Some(idx) = self.selected_noise {
-            if idx >= self.noise_files.len() {
-                self.selected_noise = None;
-            }
+        self.selected_spectrum = spectrum.index().map(|i| self.spectrum_files[i].clone());
+        self.selected_noise = noise.index().map(|i| self.noise_files[i].clone());
+        kept
+    }
+
+    /// Hides the window, or quits the app when it isn't meant to keep running in the tray.
+    fn close_window(&mut self) {
+        use crate::log_info;
+
+        if self.state.read(|s| s.keep_running_in_tray) {
+            self.command_palette = None;
+            self.window.hide();
+        } else if let Some(ref callback) = self.exit_callback {
+            log_info!("Keep in tray disabled - exiting application");
+            callback();
         }
+    }
 
-        self.status_message = Some(format!(
-            "Refreshed: {} spectrums, {} noise textures",
-            self.spectrum_files.len(),
-            self.noise_files.len()
-        ));
+    /// The window was hidden since the last frame, so files and monitors may have changed.
+    fn on_reshown(&mut self) {
+        self.reload_assets();
+        self.monitors = enumerate_monitors().unwrap_or_default();
+        self.selected_monitor = self.state.read(|s| s.last_monitor.unwrap_or(0)).min(self.monitors.len().saturating_sub(1));
+        self.settings = Rc::new(self.build_settings());
     }
 
     fn open_asset_folder(&self) {
         #[cfg(windows)]
         {
             use std::process::Command;
-            let assets_dir = self.state.app_data_dir().join("assets");
+            let assets_dir = self.state.assets_dir();
             let _ = Command::new("explorer").arg(assets_dir.to_str().unwrap_or("")).spawn();
         }
     }
 
-    fn restart_overlay_if_needed(&mut self) {
-        if let Some(ref callback) = self.overlay_restart_callback {
-            callback();
+    fn open_logs_folder(&self) {
+        #[cfg(windows)]
+        {
+            use std::process::Command;
+            let logs_dir = self.state.app_data_dir().join("logs");
+            let _ = Command::new("explorer").arg(logs_dir.to_str().unwrap_or("")).spawn();
         }
     }
 
-    fn update_tray_state(&self) {
-        if let (Some(ref tray_icon), Some(ref overlay_item)) = (&self.tray_icon, &self.overlay_menu_item) {
-            let overlay_running = self.overlay_manager.is_running();
-            overlay_item.set_checked(overlay_running);
+    /// Everything the command palette can do. New settings should register an action here
+    /// that calls the same method as their control.
+    fn build_actions(&self) -> Vec<Action> {
+        let mut actions = vec![
+            Action::new(tr!("palette.toggle_overlay"), "start stop enable disable on off", |gui| gui.toggle_overlay()),
+            Action::new(tr!("palette.open_asset_folder"), "spectra spectrums noise files explorer", |gui| gui.open_asset_folder()),
+            Action::new(tr!("palette.refresh_assets"), "reload rescan spectra noise", |gui| gui.refresh_assets()),
+            Action::new(tr!("palette.open_logs_folder"), "log files explorer debug", |gui| gui.open_logs_folder()),
+            Action::new(tr!("palette.toggle_debug_overlay"), "hud border developer click-through", |gui| {
+                let enabled = gui.state.read(|s| s.debug_overlay);
+                gui.set_debug_overlay(!enabled);
+            }),
+            Action::new(tr!("palette.toggle_cursor_correction"), "pointer mouse recolor", |gui| {
+                let enabled = !gui.state.read(|s| s.correct_cursor);
+                gui.state.update(|s| s.correct_cursor = enabled);
+            }),
+            Action::new(tr!("palette.import_matrix"), "new create", |gui| {
+                gui.matrix_import = Some(MatrixImportDialog::default());
+            }),
+            Action::new(tr!("palette.import_share_code"), "paste new", |gui| {
+                gui.share_import = Some(ShareCodeImportDialog::default());
+            }),
+            Action::new(tr!("palette.vision_setup"), "test wizard recommend", |gui| {
+                gui.vision_setup = Some(VisionSetupWizard::default());
+            }),
+            Action::new(tr!("palette.screenshot_preview"), "before after compare", |gui| gui.open_screenshot_preview()),
+            Action::new(tr!("palette.render_sweep"), "developer documentation comparison strength images png", |gui| {
+                gui.render_sweep = Some(RenderSweep::default());
+            }),
+        ];
+
+        for percent in [25, 50, 75, 100] {
+            actions.push(Action::new(tr!("palette.set_strength", percent = percent), "correction intensity", move |gui| {
+                gui.set_strength(percent as f32 / 100.0);
+            }));
+        }
 
-            let spectrum_name = self.state.read(|s| s.spectrum_name.clone());
-            let tooltip = if overlay_running {
-                if let Some(name) = spectrum_name {
-                    format!("ChromaBridge\nOverlay: {} (Active)", name)
-                } else {
-                    "ChromaBridge\nOverlay: Active".to_string()
-                }
-            } else {
-                "ChromaBridge\nOverlay: Inactive".to_string()
-            };
-            let _ = tray_icon.set_tooltip(Some(&tooltip));
+        // Favorites first, in their order
+        let favorites = self.state.read(|s| s.favorite_spectrums.clone());
+        let mut spectra: Vec<(usize, &String)> = self.spectrum_files.iter().enumerate().collect();
+        spectra.sort_by_key(|(_, name)| favorites.iter().position(|f| f == *name).unwrap_or(usize::MAX));
+
+        for (index, name) in spectra {
+            actions.push(Action::new(tr!("palette.select_spectrum", name = name), "color blind type folder favorite", move |gui| {
+                gui.select_spectrum_index(index);
+            }));
         }
+
+        actions
     }
-}
 
-impl eframe::App for SettingsGui {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        use crate::log_info;
+    /// Every row of the settings window, in display order; the search box filters this list.
+    /// New settings are added here, with a `show_*` method drawing the row.
+    fn build_settings(&self) -> Vec<Setting> {
+        use SettingsSection::{Advanced, Correction, Developer};
+
+        let monitor_hz = self.monitors.get(self.selected_monitor).map_or(60, |m| m.refresh_rate);
+        let system = tr!("advanced.system");
+        let notifications = tr!("notifications.heading");
+        let rendering = tr!("developer.rendering");
+
+        vec![
+            Setting::new(Correction, tr!("monitor.label"), "display screen", Self::show_monitor_select),
+            Setting::new(Correction, tr!("correction.color_blind_type"), "spectrum file", Self::show_spectrum_select),
+            Setting::new(Correction, tr!("spectrum.primary"), "secondary spectrum", Self::show_spectrum_selection),
+            Setting::new(Correction, tr!("correction.interlace_pattern"), "noise texture target dither original", Self::show_noise_select),
+            Setting::new(Correction, tr!("noise_preview.label"), "noise pattern tiling zoom pixel lines", Self::show_noise_preview),
+            Setting::new(Correction, tr!("correction.pattern_intensity"), "noise", Self::show_noise_intensity),
+            Setting::new(Correction, tr!("correction.pattern_mapping"), "noise stretch tile", Self::show_noise_mapping),
+            Setting::new(Correction, tr!("correction.blend_ratio"), "secondary spectrum mix blend", Self::show_blend_ratio),
+            Setting::new(Correction, tr!("correction.strength"), "intensity per spectrum", Self::show_strength),
+            Setting::new(Correction, tr!("correction.temporal_smoothing"), "flicker shimmer", Self::show_temporal_smoothing),
+            Setting::new(Correction, tr!("preview.label"), "screenshot before after compare", Self::show_screenshot_preview_button),
+
+            Setting::new(Advanced, tr!("advanced.assets"), "folder refresh import matrix share code vision setup", Self::show_asset_settings),
+            Setting::new(Advanced, tr!("data_dirs.heading"), "folders paths location appdata localappdata roaming portable database logs", Self::show_data_folders),
+            Setting::new(Advanced, tr!("trash.heading"), "deleted trash restore undo purge spectrum noise", Self::show_recently_deleted),
+            Setting::new(Advanced, tr!("hue_gate.heading"), "hue gate range", Self::show_hue_gate_settings),
+            Setting::new(Advanced, tr!("advanced.strength_curve"), "slider response perceptual gamma linear", Self::show_strength_curve),
+            Setting::new(Advanced, tr!("advanced.run_at_startup"), "autostart boot", Self::show_run_at_startup).in_group(system.clone()),
+            Setting::new(Advanced, tr!("advanced.open_gui_on_launch"), "window", Self::show_open_gui_on_launch).in_group(system.clone()),
+            Setting::new(Advanced, tr!("advanced.keep_running_in_tray"), "close minimize", Self::show_keep_running_in_tray).in_group(system.clone()),
+            Setting::new(Advanced, tr!("advanced.language"), "translation", Self::show_language_select).in_group(system),
+            Setting::new(Advanced, tr!("notifications.start_stop"), "toast balloon alert notify started stopped", Self::show_notify_start_stop).in_group(notifications.clone()),
+            Setting::new(Advanced, tr!("notifications.pause"), "toast balloon alert notify paused resumed fullscreen", Self::show_notify_pause).in_group(notifications.clone()),
+            Setting::new(Advanced, tr!("notifications.errors"), "toast balloon alert notify failed capture error recovered", Self::show_notify_errors).in_group(notifications),
+            Setting::new(Advanced, tr!("update.heading"), "updates new version release download github", Self::show_update_settings),
+            Setting::new(Advanced, tr!("advanced.theme"), "colors appearance dark light high contrast accent", Self::show_theme_settings),
+            Setting::new(Advanced, tr!("active_time.today"), "uptime usage hours eye strain statistics reset", Self::show_active_today),
+            Setting::new(Advanced, tr!("performance.heading"), "battery saver fps gpu latency", Self::show_performance_settings),
+            Setting::new(Advanced, tr!("advanced.fullscreen"), "games fps pause", Self::show_fullscreen_settings),
+            Setting::new(Advanced, tr!("advanced.conflict_detection"), "night light flux blue light filter gamma", Self::show_conflict_detection),
+            Setting::new(Advanced, tr!("advanced.topmost"), "topmost z-order", Self::show_topmost_settings),
+            Setting::new(Advanced, tr!("advanced.correct_cursor"), "pointer mouse", Self::show_cursor_correction),
+            Setting::new(Advanced, tr!("advanced.exclude_shell"), "taskbar start menu notifications toasts", Self::show_shell_exclusion),
+            Setting::new(Advanced, tr!("advanced.hide_from_capture"), "screenshot screen share sharing stream discord obs record capture", Self::show_hide_from_capture),
+            Setting::new(Advanced, tr!("advanced.target_window"), "window game app only region windowed", Self::show_target_window),
+            Setting::new(Advanced, tr!("control_server.heading"), "api http stream deck home assistant token port", Self::show_control_server_settings),
+            Setting::new(Advanced, tr!("self_check.heading"), "diagnose troubleshoot problem support issue report gpu", Self::show_self_check),
+
+            Setting::new(Developer, tr!("developer.cap_refresh", hz = monitor_hz), "fps vsync", Self::show_refresh_cap).in_group(rendering.clone()),
+            Setting::new(Developer, tr!("developer.debug_overlay"), "hud border click-through", Self::show_debug_overlay).in_group(rendering.clone()),
+            Setting::new(Developer, tr!("developer.debug_visibility"), "opacity transparent alpha magenta tint coverage layering", Self::show_debug_visibility).in_group(rendering.clone()),
+            Setting::new(Developer, tr!("developer.watch_spectrum"), "hot reload", Self::show_watch_spectrum).in_group(rendering.clone()),
+            Setting::new(Developer, tr!("developer.posterize"), "levels", Self::show_posterize).in_group(rendering.clone()),
+            Setting::new(Developer, tr!("developer.color_math"), "lut shader baked accurate", Self::show_color_math).in_group(rendering.clone()),
+            Setting::new(Developer, tr!("developer.max_noise_size"), "noise texture limit memory pixels", Self::show_max_noise_size).in_group(rendering.clone()),
+            Setting::new(Developer, tr!("developer.render_priority"), "thread priority mmcss stutter scheduling", Self::show_render_priority).in_group(rendering.clone()),
+            Setting::new(Developer, tr!("overlay_source.label"), "test pattern solid color", Self::show_overlay_source).in_group(rendering),
+            Setting::new(Developer, tr!("developer.frame_dump"), "debug frames screenshot png capture dump wrong colors", Self::show_frame_dump),
+            Setting::new(Developer, tr!("sweep.open"), "render documentation comparison strength images png contact sheet", Self::show_render_sweep_button),
+            Setting::new(Developer, tr!("output_preview.open"), "corrected output live mirror virtual headless display stream", Self::show_output_preview_button),
+            Setting::new(Developer, tr!("developer.log_level"), "logging debug", Self::show_log_level).in_group(tr!("developer.logging")),
+            Setting::new(Developer, tr!("developer.log_format"), "logging json structured text scripts parse", Self::show_log_format).in_group(tr!("developer.logging")),
+            Setting::new(Developer, tr!("diagnostics.heading"), "gpu adapter device capture", Self::show_diagnostics),
+            Setting::new(Developer, tr!("focus_check.heading"), "focus steal activate keystrokes typing noactivate", Self::show_focus_check),
+            Setting::new(Developer, tr!("startup_timing.heading"), "slow launch startup overlay start profile timing", Self::show_startup_timing),
+            Setting::new(Developer, tr!("history.heading"), "performance fps stutter sessions", Self::show_performance_history),
+        ]
+    }
 
-        ctx.style_mut(|style| {
-            style.interaction.selectable_labels = false;
-        });
+    fn show_command_palette(&mut self, ctx: &egui::Context) {
+        let Some(palette) = self.command_palette.as_mut() else {
+            return;
+        };
 
-        if let Some(ref rx) = self.close_receiver {
-            if rx.try_recv().is_ok() {
-                log_info!("Close signal received - closing GUI window");
-                ctx.send_viewport_cmd(egui::ViewportCommand::Close);
-                return;
+        match palette.show(ctx, &self.actions) {
+            PaletteOutcome::Open => {}
+            PaletteOutcome::Closed => self.command_palette = None,
+            PaletteOutcome::Run(index) => {
+                self.command_palette = None;
+                // Handlers may rebuild the registry (e.g. refreshing assets), so hold our own reference
+                let actions = Rc::clone(&self.actions);
+                (actions[index].handler)(self);
             }
         }
+    }
 
-        if let Some(ref rx) = self.toggle_receiver {
-            if rx.try_recv().is_ok() {
-                log_info!("Toggle signal received from tray menu");
-                if let Some(ref callback) = self.overlay_toggle_callback {
-                    callback();
-                }
-                self.update_tray_state();
-                ctx.request_repaint();
-            }
+    fn toggle_overlay(&mut self) {
+        if let Some(ref callback) = self.overlay_toggle_callback {
+            callback();
         }
+    }
 
-        if self.first_frame {
-            ctx.send_viewport_cmd(egui::ViewportCommand::Focus);
+    fn select_spectrum_index(&mut self, index: usize) {
+        let Some(spectrum) = self.spectrum_files.get(index).cloned() else {
+            return;
+        };
+        self.selected_spectrum = Some(spectrum.clone());
+        // Selecting a spectrum can also change strength and noise; take them from the same update
+        let mut applied = (self.strength, None);
+        self.state.update(|s| {
+            s.select_spectrum(spectrum);
+            applied = (s.strength, s.noise_texture.clone());
+        });
+        let (strength, noise) = applied;
+        self.strength = strength;
+        self.selected_noise = noise.filter(|name| self.noise_files.contains(name));
+        self.restart_overlay_if_needed();
+    }
 
-            if let Some(ref storage) = self.app_ctx_storage {
-                *storage.lock() = Some(ctx.clone());
-                log_info!("GUI context stored for exit signaling");
-            }
+    fn set_strength(&mut self, strength: f32) {
+        self.strength = strength;
+        self.state.update(|s| {
+            s.strength = strength;
+            s.remember_spectrum_settings();
+        });
+    }
 
-            if let Ok(icon_path) = std::env::current_exe() {
-                if let Some(parent) = icon_path.parent() {
-                    let icon_file = parent.join("icon.ico");
-                    if icon_file.exists() {
-                        if let Ok(img) = image::open(&icon_file) {
-                            let rgba = img.to_rgba8();
-                            let size = [rgba.width() as usize, rgba.height() as usize];
-                            let pixels = rgba.as_flat_samples();
-                            let color_image = egui::ColorImage::from_rgba_unmultiplied(size, pixels.as_slice());
-                            self.icon_texture = Some(ctx.load_texture("app_icon", color_image, Default::default()));
-                        }
-                    }
-                }
-            }
+    fn set_language(&mut self, language: chromabridge::Language) {
+        // Palette actions and the tray menu are relabeled once the change notification arrives
+        chromabridge::i18n::set_language(language);
+        self.state.update(|s| s.language = language);
+    }
 
-            self.first_frame = false;
-        }
+    fn set_debug_overlay(&mut self, enabled: bool) {
+        self.state.update(|s| s.debug_overlay = enabled);
+        self.restart_overlay_if_needed();
+    }
 
-        let title_bar_height = 32.0;
-        let close_button_size = egui::vec2(46.0, title_bar_height);
+    fn show_matrix_import_dialog(&mut self, ctx: &egui::Context) {
+        use crate::{log_info, log_warn};
+        use chromabridge::matrix_import::{parse_matrix, spectrum_from_matrix};
 
-        egui::TopBottomPanel::top("title_bar").exact_height(title_bar_height).show(ctx, |ui| {
-            ui.horizontal_centered(|ui| {
-                ui.add_space(8.0);
+        let Some(dialog) = self.matrix_import.as_mut() else {
+            return;
+        };
 
-                if let Some(ref texture) = self.icon_texture {
-                    let icon_size = 20.0;
-                    let icon_response = ui.add(
-                        egui::Image::new(texture)
-                            .max_size(egui::vec2(icon_size, icon_size))
-                            .sense(egui::Sense::click())
-                    );
+        let mut open = true;
+        let mut saved = None;
 
-                    if icon_response.clicked() {
-                        let now = Instant::now();
-                        self.icon_click_times.retain(|&time| now.duration_since(time).as_secs_f32() < 1.0);
-                        self.icon_click_times.push(now);
+        egui::Window::new(tr!("matrix_import.title"))
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label(tr!("matrix_import.matrix"));
+                ui.add(egui::TextEdit::multiline(&mut dialog.matrix_text).desired_rows(3).code_editor());
 
-                        if self.icon_click_times.len() >= 5 {
-                            log_info!("Developer mode toggled via 5 rapid clicks");
-                            self.show_developer = !self.show_developer;
-                            self.icon_click_times.clear();
+                ui.horizontal(|ui| {
+                    ui.label(tr!("common.file"));
+                    ui.text_edit_singleline(&mut dialog.file_path);
+                    if ui.button(tr!("common.load")).clicked() {
+                        match std::fs::read_to_string(dialog.file_path.trim()) {
+                            Ok(content) => {
+                                dialog.matrix_text = content;
+                                dialog.error = None;
+                            }
+                            Err(e) => dialog.error = Some(tr!("matrix_import.read_failed", error = e)),
                         }
                     }
+                });
 
-                    ui.add_space(8.0);
-                }
-
-                let title_response = ui.interact(
-                    egui::Rect::from_min_size(ui.cursor().min, egui::vec2(ui.available_width() - close_button_size.x, title_bar_height)),
-                    ui.id().with("title_bar_drag"),
-                    egui::Sense::click_and_drag(),
-                );
+                ui.horizontal(|ui| {
+                    ui.label(tr!("matrix_import.nodes"));
+                    ui.add(egui::Slider::new(&mut dialog.node_count, 12..=36));
+                });
 
-                let primary_down = ctx.input(|i| i.pointer.primary_down());
-                if title_response.is_pointer_button_down_on() && primary_down {
-                    if !self.dragging {
-                        log_info!("Title bar drag started");
-                        self.dragging = true;
+                if ui.button(tr!("common.preview")).clicked() {
+                    match parse_matrix(&dialog.matrix_text) {
+                        Ok(matrix) => {
+                            dialog.preview = Some(spectrum_from_matrix(&matrix, dialog.node_count, 1.0 / 255.0));
+                            dialog.error = None;
+                        }
+                        Err(e) => {
+                            dialog.preview = None;
+                            dialog.error = Some(format!("{:#}", e));
+                        }
                     }
-                    ctx.send_viewport_cmd(egui::ViewportCommand::StartDrag);
-                } else if self.dragging {
-                    self.dragging = false;
                 }
 
-                ui.label(
-                    egui::RichText::new("ChromaBridge - Settings")
-                        .size(14.0)
-                        .strong()
-                        .color(egui::Color32::from_rgb(220, 220, 220))
-                );
+                if let Some(ref preview) = dialog.preview {
+                    ui.add_space(5.0);
+                    paint_spectrum_preview(ui, &preview.spectrum, egui::vec2(ui.available_width(), 20.0));
+                    ui.label(tr!(
+                        "matrix_import.summary",
+                        nodes = preview.spectrum.nodes.len(),
+                        error = format!("{:.3}", preview.max_error)
+                    ));
+                    if preview.clipped_samples > 0 {
+                        ui.colored_label(
+                            theme::palette(ui).warning,
+                            tr!("matrix_import.clipped", count = preview.clipped_samples),
+                        );
+                    }
 
-                ui.add_space(6.0);
-                let version = env!("CARGO_PKG_VERSION").strip_prefix("0.").unwrap_or(env!("CARGO_PKG_VERSION"));
-                ui.label(egui::RichText::new(version).size(10.0).color(egui::Color32::from_rgb(140, 140, 140)));
+                    ui.add_space(5.0);
+                    ui.horizontal(|ui| {
+                        ui.label(tr!("common.name"));
+                        ui.text_edit_singleline(&mut dialog.name).on_hover_text(tr!("import.name_hint"));
+                        if ui.button(tr!("common.save")).clicked() {
+                            saved = Some(dialog.name.trim().to_string());
+                        }
+                    });
+                }
 
-                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
-                    let close_response = ui.add_sized(
-                        close_button_size,
-                        egui::Button::new(egui::RichText::new("X").size(16.0))
-                            .frame(false)
-                    );
-                    if close_response.clicked() {
-                        log_info!("Close button clicked");
-                        ctx.send_viewport_cmd(egui::ViewportCommand::Close);
-                    }
-                });
+                if let Some(ref error) = dialog.error {
+                    ui.colored_label(theme::palette(ui).error, error);
+                }
             });
-        });
 
-        egui::CentralPanel::default().show(ctx, |ui| {
-            egui::ScrollArea::vertical().show(ui, |ui| {
-                ui.add_space(10.0);
+        if let Some(name) = saved {
+            let dialog = self.matrix_import.as_mut().unwrap();
+            let preview = dialog.preview.as_ref().unwrap();
 
-                let overlay_running = self.overlay_manager.is_running();
+            let Ok(path) = self.state.get_spectrum_path(&name) else {
+                dialog.error = Some(tr!("import.invalid_name"));
+                return;
+            };
+            // Checked on disk too, the list may predate a file added since
+            if self.spectrum_files.contains(&name) || path.exists() {
+                dialog.error = Some(tr!("import.exists", name = name));
+                return;
+            }
 
-                ui.horizontal(|ui| {
-                    let button_text = if overlay_running { "Stop Overlay" } else { "Start Overlay" };
-                    let button = egui::Button::new(button_text).min_size(egui::vec2(120.0, 30.0));
-                    if ui.add(button).clicked() {
-                        if let Some(ref callback) = self.overlay_toggle_callback {
-                            callback();
+            let result = create_spectrum_folder(&path)
+                .and_then(|_| preview.to_spectrum_file().save_to_file(&path))
+                .and_then(|_| chromabridge::SpectrumPair::load_from_file(&path).map(|_| ()).map_err(Into::into));
+
+            match result {
+                Ok(()) => {
+                    if preview.clipped_samples > 0 {
+                        log_warn!("Matrix import '{}': {} samples clipped to gamut", name, preview.clipped_samples);
+                    }
+                    log_info!("Imported spectrum '{}' from matrix ({} nodes)", name, preview.spectrum.nodes.len());
+                    self.matrix_import = None;
+                    self.refresh_assets();
+                    self.status_message = Some(tr!("import.imported", name = name));
+                }
+                Err(e) => {
+                    // Only ever a file this save created, see the check above
+                    let _ = std::fs::remove_file(&path);
+                    dialog.error = Some(tr!("import.save_failed", error = spectrum_error_message(&e)));
+                }
+            }
+            return;
+        }
+
+        if !open {
+            self.matrix_import = None;
+        }
+    }
+
+    fn copy_share_code(&mut self, ctx: &egui::Context, name: &str) {
+        use crate::{log_info, log_warn};
+
+        let result = self.state
+            .get_spectrum_path(name)
+            .and_then(|path| std::fs::read_to_string(path).map_err(anyhow::Error::from))
+            .and_then(|content| serde_json::from_str::<chromabridge::spectrum::SpectrumFile>(&content).map_err(anyhow::Error::from))
+            .and_then(|spectrum_file| chromabridge::share_code::encode(&spectrum_file));
+
+        match result {
+            Ok(code) => {
+                log_info!("Copied share code for spectrum '{}' ({} characters)", name, code.len());
+                ctx.copy_text(code);
+                self.status_message = Some(tr!("share_code.copied", name = name));
+            }
+            Err(e) => {
+                log_warn!("Failed to create share code for '{}': {:#}", name, e);
+                self.status_message = Some(tr!("share_code.copy_failed", error = format!("{:#}", e)));
+            }
+        }
+    }
+
+    fn show_share_import_dialog(&mut self, ctx: &egui::Context) {
+        use crate::log_info;
+
+        let Some(dialog) = self.share_import.as_mut() else {
+            return;
+        };
+
+        let mut open = true;
+        let mut saved = None;
+
+        egui::Window::new(tr!("share_import.title"))
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label(tr!("share_import.paste", prefix = chromabridge::share_code::SHARE_CODE_PREFIX));
+                ui.add(egui::TextEdit::multiline(&mut dialog.code).desired_rows(3).code_editor());
+
+                if ui.button(tr!("common.preview")).clicked() {
+                    match chromabridge::share_code::decode(&dialog.code) {
+                        Ok(spectrum_file) => {
+                            dialog.preview = Some(spectrum_file);
+                            dialog.error = None;
+                        }
+                        Err(e) => {
+                            dialog.preview = None;
+                            dialog.error = Some(format!("{:#}", e));
+                        }
+                    }
+                }
+
+                if let Some(ref preview) = dialog.preview {
+                    ui.add_space(5.0);
+                    for spectrum in &preview.spectra {
+                        let mut spectrum = spectrum.clone();
+                        spectrum.interpolation = preview.interpolation;
+                        paint_spectrum_preview(ui, &spectrum, egui::vec2(ui.available_width(), 20.0));
+                    }
+                    ui.label(tr!("share_import.spectrum_count", count = preview.spectra.len()));
+
+                    ui.add_space(5.0);
+                    ui.horizontal(|ui| {
+                        ui.label(tr!("common.name"));
+                        ui.text_edit_singleline(&mut dialog.name).on_hover_text(tr!("import.name_hint"));
+                        if ui.button(tr!("common.save")).clicked() {
+                            saved = Some(dialog.name.trim().to_string());
+                        }
+                    });
+                }
+
+                if let Some(ref error) = dialog.error {
+                    ui.colored_label(theme::palette(ui).error, error);
+                }
+            });
+
+        if let Some(name) = saved {
+            let dialog = self.share_import.as_mut().unwrap();
+            let preview = dialog.preview.as_ref().unwrap();
+
+            let Ok(path) = self.state.get_spectrum_path(&name) else {
+                dialog.error = Some(tr!("import.invalid_name"));
+                return;
+            };
+            if self.spectrum_files.contains(&name) {
+                dialog.error = Some(tr!("import.exists", name = name));
+                return;
+            }
+
+            let result = create_spectrum_folder(&path)
+                .and_then(|_| preview.save_to_file(&path))
+                .and_then(|_| chromabridge::SpectrumPair::load_from_file(&path).map(|_| ()).map_err(Into::into));
+
+            match result {
+                Ok(()) => {
+                    log_info!("Imported spectrum '{}' from share code", name);
+                    self.share_import = None;
+                    self.refresh_assets();
+                    self.status_message = Some(tr!("import.imported", name = name));
+                }
+                Err(e) => {
+                    let _ = std::fs::remove_file(&path);
+                    dialog.error = Some(tr!("import.save_failed", error = spectrum_error_message(&e)));
+                }
+            }
+            return;
+        }
+
+        if !open {
+            self.share_import = None;
+        }
+    }
+
+    fn show_vision_setup(&mut self, ctx: &egui::Context) {
+        use chromabridge::vision_test::{recommend, TEST_SCREENS};
+
+        let Some(wizard) = self.vision_setup.as_mut() else {
+            return;
+        };
+
+        let mut open = true;
+        let mut finished = false;
+        let mut apply = None;
+
+        egui::Window::new(tr!("vision_setup.title"))
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+            .show(ctx, |ui| {
+                if wizard.step == 0 {
+                    ui.label(tr!("vision_setup.intro"));
+                    ui.label(tr!("vision_setup.instructions"));
+                    ui.add_space(10.0);
+                    ui.horizontal(|ui| {
+                        if ui.button(tr!("vision_setup.start")).clicked() {
+                            wizard.answers.clear();
+                            wizard.step = 1;
+                        }
+                        if ui.button(tr!("vision_setup.skip")).clicked() {
+                            finished = true;
+                        }
+                    });
+                } else if let Some(screen) = TEST_SCREENS.get(wizard.step - 1) {
+                    ui.label(tr!("vision_setup.step", step = wizard.step, total = TEST_SCREENS.len()));
+                    ui.label(egui::RichText::new(screen.prompt).strong());
+                    ui.add_space(10.0);
+
+                    let mut answer = None;
+                    ui.horizontal(|ui| {
+                        for (idx, panel) in screen.panels.iter().enumerate() {
+                            if paint_test_panel(ui, panel.colors, 120.0).clicked() {
+                                answer = Some(Some(idx));
+                            }
+                        }
+                    });
+
+                    ui.add_space(10.0);
+                    ui.horizontal(|ui| {
+                        if ui.button(tr!("vision_setup.back")).clicked() {
+                            wizard.answers.pop();
+                            wizard.step -= 1;
+                        }
+                        if ui.button(tr!("vision_setup.all_different")).clicked() {
+                            answer = Some(None);
+                        }
+                    });
+
+                    if let Some(answer) = answer {
+                        wizard.answers.push(answer);
+                        wizard.step += 1;
+                    }
+                } else {
+                    let recommendation = recommend(&wizard.answers);
+                    let installed = self.spectrum_files.iter().any(|s| s == recommendation.spectrum);
+
+                    ui.label(egui::RichText::new(recommendation.description).strong());
+                    ui.add_space(5.0);
+                    ui.label(tr!("vision_setup.recommended", spectrum = recommendation.spectrum, percent = format!("{:.0}", recommendation.strength * 100.0)));
+                    if let Some(pair) = self.state.get_spectrum_path(recommendation.spectrum).ok().and_then(|path| chromabridge::SpectrumPair::load_from_file(path).ok()) {
+                        paint_spectrum_preview(ui, pair.spectrum1(), egui::vec2(ui.available_width(), 20.0));
+                    }
+                    if !installed {
+                        ui.colored_label(
+                            theme::palette(ui).warning,
+                            tr!("vision_setup.not_installed"),
+                        );
+                    }
+
+                    ui.add_space(5.0);
+                    ui.checkbox(&mut wizard.start_overlay, tr!("vision_setup.start_overlay"));
+                    ui.add_space(10.0);
+                    ui.horizontal(|ui| {
+                        if ui.add_enabled(installed, egui::Button::new(tr!("vision_setup.apply"))).clicked() {
+                            apply = Some((recommendation, wizard.start_overlay));
+                        }
+                        if ui.button(tr!("vision_setup.retake")).clicked() {
+                            wizard.answers.clear();
+                            wizard.step = 1;
+                        }
+                        if ui.button(tr!("vision_setup.cancel")).clicked() {
+                            finished = true;
+                        }
+                    });
+                }
+            });
+
+        if let Some((recommendation, start_overlay)) = apply {
+            self.apply_vision_recommendation(recommendation, start_overlay);
+            finished = true;
+        }
+
+        if finished || !open {
+            self.state.update(|s| s.vision_setup_complete = true);
+            self.vision_setup = None;
+        }
+    }
+
+    fn apply_vision_recommendation(&mut self, recommendation: &chromabridge::vision_test::Recommendation, start_overlay: bool) {
+        use crate::log_info;
+
+        log_info!(
+            "Vision setup result: {} (spectrum: {}, strength: {:.2})",
+            recommendation.description,
+            recommendation.spectrum,
+            recommendation.strength
+        );
+
+        let noise = recommendation
+            .noise
+            .filter(|n| self.noise_files.iter().any(|f| f == n))
+            .map(str::to_string);
+        self.state.update(|s| {
+            s.select_spectrum(recommendation.spectrum.to_string());
+            s.strength = recommendation.strength;
+            s.select_noise(noise.clone());
+        });
+
+        self.selected_spectrum = Some(recommendation.spectrum.to_string()).filter(|name| self.spectrum_files.contains(name));
+        self.selected_noise = noise;
+        self.strength = recommendation.strength;
+        self.status_message = Some(tr!("vision_setup.applied", spectrum = recommendation.spectrum));
+
+        if self.overlay_manager.is_running() {
+            self.restart_overlay_if_needed();
+        } else if start_overlay {
+            self.toggle_overlay();
+        }
+    }
+
+    fn show_reset_banner(&mut self, ui: &mut egui::Ui) {
+        let Some(ref backup) = self.reset_notice else {
+            return;
+        };
+
+        let mut dismiss = false;
+        egui::Frame::none()
+            .fill(theme::palette(ui).warning_fill)
+            .rounding(4.0)
+            .inner_margin(8.0)
+            .show(ui, |ui| {
+                ui.label(egui::RichText::new(tr!("reset_banner.title")).strong());
+                ui.label(tr!("reset_banner.backup", file = backup.file_name().unwrap_or_default().to_string_lossy()));
+                if ui.button(tr!("common.dismiss")).clicked() {
+                    dismiss = true;
+                }
+            });
+
+        if dismiss {
+            self.reset_notice = None;
+        }
+
+        ui.add_space(10.0);
+    }
+
+    fn show_update_banner(&mut self, ui: &mut egui::Ui) {
+        let Some(release) = self.state.read(|s| s.update_banner().cloned()) else {
+            return;
+        };
+
+        let mut dismiss = false;
+        let palette = theme::palette(ui);
+        egui::Frame::none()
+            .fill(palette.surface)
+            .stroke(egui::Stroke::new(palette.stroke_width, palette.accent))
+            .rounding(4.0)
+            .inner_margin(8.0)
+            .show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(tr!("update.available", version = release.version, current = update_check::CURRENT_VERSION));
+                    if ui.button(tr!("update.download")).on_hover_text(&release.url).clicked() {
+                        open_release_page(&release);
+                    }
+                    if ui.button(tr!("common.dismiss")).clicked() {
+                        dismiss = true;
+                    }
+                });
+            });
+
+        if dismiss {
+            self.state.update(|s| s.dismissed_update = Some(release.version.clone()));
+        }
+
+        ui.add_space(10.0);
+    }
+
+    fn show_crash_banner(&mut self, ui: &mut egui::Ui) {
+        use crate::log_info;
+
+        let Some(ref notice) = self.crash_notice else {
+            return;
+        };
+
+        let mut dismiss = false;
+        let mut restore_overlay = false;
+
+        egui::Frame::none()
+            .fill(theme::palette(ui).error_fill)
+            .rounding(4.0)
+            .inner_margin(8.0)
+            .show(ui, |ui| {
+                ui.label(egui::RichText::new(tr!("crash_banner.title")).strong());
+                if notice.overlay_restore_skipped {
+                    ui.label(tr!("crash_banner.overlay_not_restored"));
+                }
+                ui.horizontal(|ui| {
+                    if ui.button(tr!("crash_banner.open_folder")).clicked() {
+                        #[cfg(windows)]
+                        {
+                            let _ = std::process::Command::new("explorer")
+                                .arg(notice.crash_dir.to_str().unwrap_or(""))
+                                .spawn();
+                        }
+                        log_info!("Opened crash folder ({})", notice.report_path.display());
+                    }
+                    if notice.overlay_restore_skipped && ui.button(tr!("crash_banner.restore_overlay")).clicked() {
+                        restore_overlay = true;
+                    }
+                    if ui.button(tr!("common.dismiss")).clicked() {
+                        dismiss = true;
+                    }
+                });
+            });
+
+        if restore_overlay {
+            log_info!("Restoring overlay after crash notice");
+            if !self.overlay_manager.is_running() {
+                if let Some(ref callback) = self.overlay_toggle_callback {
+                    callback();
+                }
+            }
+            dismiss = true;
+        }
+
+        if dismiss {
+            self.crash_notice = None;
+        }
+
+        ui.add_space(10.0);
+    }
+
+    fn show_hue_gate_settings(&mut self, ui: &mut egui::Ui, label: &str) {
+        ui.label(label);
+        let (mut enabled, mut start, mut end) = self.state.read(|s| (s.hue_gate_enabled, s.hue_gate_start, s.hue_gate_end));
+
+        if ui.checkbox(&mut enabled, tr!("hue_gate.enable"))
+            .on_hover_text(tr!("hue_gate.enable_hint"))
+            .changed()
+        {
+            self.state.update(|s| {
+                s.hue_gate_enabled = enabled;
+                s.remember_spectrum_settings();
+            });
+        }
+        if !enabled {
+            return;
+        }
+
+        let slider = |ui: &mut egui::Ui, label: String, value: &mut f32| {
+            ui.horizontal(|ui| {
+                ui.label(label);
+                ui.add(egui::Slider::new(value, 0.0..=360.0).suffix("°").fixed_decimals(0))
+            })
+            .inner
+        };
+        let start_response = slider(ui, tr!("hue_gate.start"), &mut start);
+        let end_response = slider(ui, tr!("hue_gate.end"), &mut end);
+
+        if start_response.changed() || end_response.changed() {
+            // Persisted once the drag ends to avoid a write per frame
+            self.state.update_ephemeral(|s| {
+                s.hue_gate_start = start;
+                s.hue_gate_end = end;
+                s.remember_spectrum_settings();
+            });
+        }
+        for response in [start_response, end_response] {
+            if response.drag_stopped() || (response.changed() && !response.dragged()) {
+                self.state.flush();
+            }
+        }
+    }
+
+    fn show_performance_settings(&mut self, ui: &mut egui::Ui, label: &str) {
+        ui.label(label);
+
+        match self.overlay_manager.get_frame_stats() {
+            Some(stats) if self.overlay_manager.is_running() => {
+                // Fraction of each second spent rendering, a rough stand-in for GPU load
+                let busy_percent = stats.frame_time_ms * stats.fps / 10.0;
+                ui.label(tr!("performance.cost", render_ms = format!("{:.2}", stats.frame_time_ms), fps = format!("{:.0}", stats.fps), busy = format!("{:.1}", busy_percent)));
+                if let Some(latency_ms) = stats.latency_ms {
+                    ui.label(tr!("performance.latency", ms = format!("{:.1}", latency_ms)))
+                        .on_hover_text(tr!("performance.latency_hint"));
+                }
+                if let Some(dropped) = stats.dropped_presents_per_second {
+                    ui.label(tr!("performance.dropped", count = format!("{:.1}", dropped)))
+                        .on_hover_text(tr!("performance.dropped_hint"));
+                }
+            }
+            _ => {
+                ui.weak(tr!("performance.not_running"));
+            }
+        }
+
+        let (mut battery_saver, mut auto_battery_saver) = self.state.read(|s| (s.battery_saver_active(), s.auto_battery_saver));
+        if ui.checkbox(&mut battery_saver, tr!("performance.battery_saver"))
+            .on_hover_text(tr!("performance.battery_saver_hint", fps = chromabridge::BATTERY_SAVER_FPS))
+            .changed()
+        {
+            self.state.update(|s| s.set_battery_saver(battery_saver));
+        }
+        if ui.checkbox(&mut auto_battery_saver, tr!("performance.auto_battery_saver")).changed() {
+            self.state.update(|s| s.auto_battery_saver = auto_battery_saver);
+        }
+    }
+
+    fn show_control_server_settings(&mut self, ui: &mut egui::Ui, label: &str) {
+        use crate::control_server::{generate_token, ENDPOINTS};
+
+        ui.label(label);
+        let (mut enabled, mut port, token) = self.state.read(|s| {
+            (s.control_server_enabled, s.control_server_port, s.control_server_token.clone())
+        });
+
+        ui.horizontal(|ui| {
+            if ui.checkbox(&mut enabled, tr!("control_server.enable")).changed() {
+                self.state.update(|s| {
+                    s.control_server_enabled = enabled;
+                    if s.control_server_token.is_empty() {
+                        s.control_server_token = generate_token();
+                    }
+                });
+            }
+            ui.label(tr!("control_server.port"));
+            if ui.add(egui::DragValue::new(&mut port).range(1024..=65535)).changed() {
+                self.state.update(|s| s.control_server_port = port);
+            }
+        });
+
+        if !enabled {
+            return;
+        }
+
+        let base_url = format!("http://127.0.0.1:{}", port);
+        ui.horizontal(|ui| {
+            ui.monospace(&base_url);
+            if ui.small_button(tr!("control_server.copy_url")).clicked() {
+                ui.ctx().copy_text(base_url.clone());
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label(tr!("control_server.token"));
+            ui.monospace(Self::truncate_with_ellipsis(&token, 12));
+            if ui.small_button(tr!("control_server.copy")).clicked() {
+                ui.ctx().copy_text(token.clone());
+            }
+            if ui.small_button(tr!("control_server.regenerate")).clicked() {
+                self.state.update(|s| s.control_server_token = generate_token());
+            }
+        });
+
+        let hint_color = theme::palette(ui).weak_text;
+        ui.label(
+            egui::RichText::new(tr!("control_server.token_hint"))
+                .size(11.0)
+                .color(hint_color)
+        );
+        for (method, endpoint) in ENDPOINTS {
+            ui.label(egui::RichText::new(format!("{} {}", method, endpoint)).size(11.0).monospace().color(hint_color));
+        }
+    }
+
+    /// The selected spectrum file, loaded once per file.
+    fn selected_spectrum_file(&mut self) -> Option<&chromabridge::SpectrumFile> {
+        self.selected_spectrum_load().map(|(file, _)| file)
+    }
+
+    /// What loading the selected spectrum file corrected, e.g. nodes it sorted.
+    fn selected_spectrum_warnings(&mut self) -> &[String] {
+        self.selected_spectrum_load().map(|(_, warnings)| warnings.as_slice()).unwrap_or_default()
+    }
+
+    fn selected_spectrum_load(&mut self) -> Option<&(chromabridge::SpectrumFile, Vec<String>)> {
+        let name = self.selected_spectrum.clone();
+        let cached = matches!((&self.spectrum_file, &name), (Some((cached, _)), Some(name)) if cached == name);
+
+        if !cached {
+            self.spectrum_file = name.map(|name| {
+                let file = self.state.get_spectrum_path(&name).ok().and_then(|path| chromabridge::SpectrumFile::load_with_warnings(path).ok());
+                (name, file)
+            });
+        }
+
+        self.spectrum_file.as_ref().and_then(|(_, file)| file.as_ref())
+    }
+
+    /// Primary and secondary spectrum rows, for files with more than one spectrum or a derived
+    /// secondary one.
+    fn show_spectrum_selection(&mut self, ui: &mut egui::Ui, label: &str) {
+        let Some(file) = self.selected_spectrum_file().cloned() else {
+            return;
+        };
+        let entries: Vec<(String, Option<String>)> = file.spectra
+            .iter()
+            .enumerate()
+            .map(|(i, spectrum)| (spectrum.label(i), spectrum.description.clone()))
+            .collect();
+        let mode = file.spectrum2_mode;
+        if entries.len() < 2 && mode.is_explicit() {
+            return;
+        }
+
+        let current = self.state.read(|s| s.spectrum_selection).resolve(entries.len());
+        let mut selection = current;
+        let entry_text = |i: usize| Self::truncate_with_ellipsis(&entries[i].0, 30);
+
+        if entries.len() >= 2 {
+            ui.label(label);
+            egui::ComboBox::from_id_salt("spectrum_primary")
+                .selected_text(entry_text(selection.primary))
+                .show_ui(ui, |ui| {
+                    for (idx, (label, description)) in entries.iter().enumerate() {
+                        let response = theme::selectable_value(ui, &mut selection.primary, idx, label);
+                        if let Some(description) = description {
+                            response.on_hover_text(description);
+                        }
+                    }
+                });
+            ui.end_row();
+        }
+
+        ui.label(tr!("spectrum.secondary"));
+        if !mode.is_explicit() {
+            // Set by the file, so only shown
+            use chromabridge::Spectrum2Mode;
+            let derived_label = match mode {
+                Spectrum2Mode::Complement => tr!("spectrum.derived_complement"),
+                Spectrum2Mode::ValueInvert => tr!("spectrum.derived_value_invert"),
+                Spectrum2Mode::Explicit => String::new(),
+            };
+            ui.horizontal(|ui| {
+                if let Ok(Some(derived)) = mode.derive(&file.spectra[selection.primary]) {
+                    paint_spectrum_preview(ui, &derived, egui::vec2(120.0, 14.0));
+                }
+                ui.label(derived_label);
+            })
+            .response
+            .on_hover_text(tr!("spectrum.derived_hint"));
+            ui.end_row();
+        } else {
+            egui::ComboBox::from_id_salt("spectrum_secondary")
+                .selected_text(selection.secondary.map(entry_text).unwrap_or_else(|| tr!("common.none")))
+                .show_ui(ui, |ui| {
+                    theme::selectable_value(ui, &mut selection.secondary, None, tr!("common.none"));
+                    for (idx, (label, description)) in entries.iter().enumerate() {
+                        if idx == selection.primary {
+                            continue;
+                        }
+                        let response = theme::selectable_value(ui, &mut selection.secondary, Some(idx), label);
+                        if let Some(description) = description {
+                            response.on_hover_text(description);
+                        }
+                    }
+                })
+                .response
+                .on_hover_text(tr!("spectrum.secondary_hint"));
+            ui.end_row();
+        }
+
+        if selection != current {
+            let selection = selection.resolve(entries.len());
+            self.state.update(|s| s.spectrum_selection = selection);
+
+            if self.overlay_manager.is_running() {
+                let Some(name) = self.state.read(|s| s.spectrum_name.clone()) else {
+                    return;
+                };
+                let loaded = self.state
+                    .get_spectrum_path(&name)
+                    .and_then(|path| chromabridge::SpectrumPair::load_with_selection(path, selection).map_err(Into::into));
+                match loaded {
+                    Ok(pair) => self.overlay_manager.update_spectrum(pair),
+                    Err(e) => self.status_message = Some(tr!("spectrum.load_failed", error = spectrum_error_message(&e))),
+                }
+            }
+        }
+    }
+
+    fn show_overlay_source(&mut self, ui: &mut egui::Ui, label: &str) {
+        use chromabridge::{OverlaySource, TestPattern};
+
+        let (mut source, mut pattern, mut color) = self.state.read(|s| (s.overlay_source, s.test_pattern, s.solid_color));
+        let mut source_changed = false;
+        let mut image_changed = false;
+
+        ui.horizontal(|ui| {
+            ui.label(label);
+            egui::ComboBox::from_id_salt("overlay_source")
+                .selected_text(source.label())
+                .show_ui(ui, |ui| {
+                    for s in OverlaySource::ALL {
+                        source_changed |= theme::selectable_value(ui, &mut source, s, s.label()).changed();
+                    }
+                });
+
+            match source {
+                OverlaySource::TestPattern => {
+                    egui::ComboBox::from_id_salt("test_pattern")
+                        .selected_text(pattern.label())
+                        .show_ui(ui, |ui| {
+                            for p in TestPattern::ALL {
+                                image_changed |= theme::selectable_value(ui, &mut pattern, p, p.label()).changed();
+                            }
+                        });
+                }
+                OverlaySource::SolidColor => {
+                    image_changed |= ui.color_edit_button_srgb(&mut color).changed();
+                }
+                OverlaySource::DesktopCapture => {}
+            }
+        });
+
+        if source != OverlaySource::DesktopCapture {
+            ui.label(
+                egui::RichText::new(tr!("overlay_source.generated_warning"))
+                    .size(11.0)
+                    .color(theme::palette(ui).warning)
+            );
+        }
+
+        if source == OverlaySource::TestPattern && pattern == TestPattern::Coverage {
+            ui.label(egui::RichText::new(tr!("overlay_source.coverage_steps")).size(11.0));
+        }
+
+        if source_changed {
+            self.state.update(|s| s.overlay_source = source);
+            self.restart_overlay_if_needed();
+        } else if image_changed {
+            self.state.update(|s| {
+                s.test_pattern = pattern;
+                s.solid_color = color;
+            });
+        }
+    }
+
+    fn show_diagnostics(&mut self, ui: &mut egui::Ui, label: &str) {
+        const MB: u64 = 1024 * 1024;
+
+        ui.label(label);
+
+        let diagnostics = self.overlay_manager.get_diagnostics();
+        egui::Grid::new("gpu_diagnostics")
+            .num_columns(2)
+            .spacing([20.0, 4.0])
+            .show(ui, |ui| {
+                match diagnostics.adapter {
+                    Some(ref adapter) => {
+                        ui.label(tr!("diagnostics.adapter"));
+                        ui.label(&adapter.description);
+                        ui.end_row();
+
+                        ui.label(tr!("diagnostics.dedicated_video_memory"));
+                        ui.label(tr!("diagnostics.megabytes", size = adapter.dedicated_video_memory / MB));
+                        ui.end_row();
+
+                        ui.label(tr!("diagnostics.dedicated_system_memory"));
+                        ui.label(tr!("diagnostics.megabytes", size = adapter.dedicated_system_memory / MB));
+                        ui.end_row();
+
+                        ui.label(tr!("diagnostics.shared_system_memory"));
+                        ui.label(tr!("diagnostics.megabytes", size = adapter.shared_system_memory / MB));
+                        ui.end_row();
+
+                        ui.label(tr!("diagnostics.feature_level"));
+                        ui.label(&adapter.feature_level);
+                        ui.end_row();
+
+                        ui.label(tr!("diagnostics.swap_chain_format"));
+                        ui.label(&adapter.swap_chain_format);
+                        ui.end_row();
+
+                        ui.label(tr!("diagnostics.spectrum_format"));
+                        ui.label(&adapter.spectrum_format);
+                        ui.end_row();
+                    }
+                    None => {
+                        ui.label(tr!("diagnostics.adapter"));
+                        ui.label(tr!("diagnostics.no_adapter"));
+                        ui.end_row();
+                    }
+                }
+
+                ui.label(tr!("diagnostics.capture_backend"));
+                match diagnostics.capture_backend {
+                    Some(backend) => ui.label(backend.label()),
+                    None => ui.label(tr!("common.none")),
+                };
+                ui.end_row();
+
+                if diagnostics.capture_backend.is_some() {
+                    ui.label(tr!("diagnostics.capture_rotation"));
+                    ui.label(tr!("diagnostics.degrees", angle = diagnostics.capture_rotation));
+                    ui.end_row();
+                }
+
+                if let Some(ref format) = diagnostics.desktop_format {
+                    ui.label(tr!("diagnostics.desktop_format"));
+                    ui.label(format);
+                    ui.end_row();
+                }
+
+                if let Some(ref reason) = diagnostics.last_capture_fallback {
+                    ui.label(tr!("diagnostics.capture_fallback"));
+                    ui.label(reason);
+                    ui.end_row();
+                }
+
+                if diagnostics.occluded {
+                    ui.label(tr!("diagnostics.occluded"));
+                    ui.label(tr!("diagnostics.occluded_value"));
+                    ui.end_row();
+                }
+
+                if let Some(priority) = diagnostics.render_priority {
+                    ui.label(tr!("diagnostics.render_priority"));
+                    ui.label(priority.label());
+                    ui.end_row();
+                }
+
+                ui.label(tr!("diagnostics.recoveries"));
+                ui.label(diagnostics.device_removed_recoveries.to_string());
+                ui.end_row();
+
+                if let Some(ref reason) = diagnostics.last_device_removed_reason {
+                    ui.label(tr!("diagnostics.last_removal_reason"));
+                    ui.label(reason);
+                    ui.end_row();
+                }
+
+                if let Some(handles) = crate::overlay::process_handle_count() {
+                    ui.label(tr!("diagnostics.handle_count")).on_hover_text(tr!("diagnostics.handle_count_hint"));
+                    ui.label(handles.to_string());
+                    ui.end_row();
+                }
+            });
+
+        let mut max_device_recoveries = self.state.read(|s| s.max_device_recoveries);
+        ui.horizontal(|ui| {
+            ui.label(tr!("diagnostics.rebuild_attempts"));
+            if ui.add(egui::DragValue::new(&mut max_device_recoveries).range(0..=10))
+                .on_hover_text(tr!("diagnostics.rebuild_attempts_hint"))
+                .changed()
+            {
+                self.state.update(|s| s.max_device_recoveries = max_device_recoveries);
+            }
+        });
+    }
+
+    fn show_active_today(&mut self, ui: &mut egui::Ui, label: &str) {
+        ui.label(label);
+        ui.horizontal(|ui| {
+            ui.label(crate::format_active_time(self.overlay_manager.active_today()));
+            if ui.small_button(tr!("active_time.reset")).on_hover_text(tr!("active_time.reset_hint")).clicked() {
+                self.overlay_manager.reset_active_today();
+            }
+        });
+        ui.end_row();
+    }
+
+    fn show_startup_timing(&mut self, ui: &mut egui::Ui, label: &str) {
+        use crate::log_error;
+
+        ui.horizontal(|ui| {
+            ui.label(label);
+            if ui.small_button("↻").on_hover_text(tr!("startup_timing.refresh")).clicked() {
+                self.startup_timings = None;
+            }
+        });
+
+        let timings = self.startup_timings.get_or_insert_with(|| {
+            ["startup", "overlay"].map(|name| {
+                self.state.last_timing(name).unwrap_or_else(|e| {
+                    log_error!("{:#}", e);
+                    None
+                })
+            })
+        });
+
+        ui.weak(tr!("startup_timing.hint"));
+        for timing in timings.iter() {
+            match timing {
+                Some(summary) => ui.monospace(summary),
+                None => ui.weak(tr!("startup_timing.none")),
+            };
+        }
+    }
+
+    fn show_focus_check(&mut self, ui: &mut egui::Ui, label: &str) {
+        use crate::overlay::{self, FOCUS_CHECK_RUNS};
+        use crossbeam_channel::TryRecvError;
+
+        if let Some(pending) = self.focus_check_pending.as_ref() {
+            match pending.try_recv() {
+                Ok(result) => {
+                    self.focus_check = Some(result);
+                    self.focus_check_pending = None;
+                }
+                Err(TryRecvError::Disconnected) => self.focus_check_pending = None,
+                Err(TryRecvError::Empty) => ui.ctx().request_repaint_after(std::time::Duration::from_millis(100)),
+            }
+        }
+
+        let running = self.focus_check_pending.is_some();
+        ui.horizontal(|ui| {
+            ui.label(label);
+            if ui.add_enabled(!running, egui::Button::new(tr!("focus_check.run")))
+                .on_hover_text(tr!("focus_check.hint", runs = FOCUS_CHECK_RUNS))
+                .clicked()
+            {
+                let (tx, rx) = crossbeam_channel::bounded(1);
+                let monitor_index = self.selected_monitor;
+                std::thread::spawn(move || {
+                    let _ = tx.send(overlay::check_focus(monitor_index).map_err(|e| format!("{:#}", e)));
+                });
+                self.focus_check_pending = Some(rx);
+            }
+            if running {
+                ui.spinner();
+            }
+        });
+
+        match self.focus_check {
+            Some(Ok(0)) => {
+                ui.colored_label(theme::palette(ui).success, tr!("focus_check.kept", runs = FOCUS_CHECK_RUNS));
+            }
+            Some(Ok(moved)) => {
+                ui.colored_label(theme::palette(ui).error, tr!("focus_check.moved", moved = moved, runs = FOCUS_CHECK_RUNS));
+            }
+            Some(Err(ref error)) => {
+                ui.colored_label(theme::palette(ui).error, tr!("focus_check.failed", error = error));
+            }
+            None => {}
+        }
+    }
+
+    fn show_performance_history(&mut self, ui: &mut egui::Ui, label: &str) {
+        use crate::log_error;
+        const SHOWN: usize = 50;
+
+        ui.horizontal(|ui| {
+            ui.label(label);
+            if ui.small_button("↻").on_hover_text(tr!("history.refresh")).clicked() {
+                self.session_history = None;
+            }
+        });
+
+        let sessions = self.session_history.get_or_insert_with(|| {
+            self.state.recent_sessions(SHOWN).unwrap_or_else(|e| {
+                log_error!("{:#}", e);
+                Vec::new()
+            })
+        });
+
+        if sessions.is_empty() {
+            ui.weak(tr!("history.empty"));
+        } else {
+            egui::ScrollArea::both().id_salt("performance_history").max_height(200.0).show(ui, |ui| {
+                egui::Grid::new("performance_history_grid")
+                    .num_columns(9)
+                    .spacing([12.0, 4.0])
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.strong(tr!("history.started"));
+                        ui.strong(tr!("history.duration"));
+                        ui.strong(tr!("history.monitor"));
+                        ui.strong(tr!("history.spectrum"));
+                        ui.strong(tr!("history.average_fps"));
+                        ui.strong(tr!("history.low_fps"));
+                        ui.strong(tr!("history.max_frame_time"));
+                        ui.strong(tr!("history.errors"));
+                        ui.strong(tr!("history.recoveries"));
+                        ui.end_row();
+
+                        for session in sessions.iter() {
+                            let minutes = session.duration().as_secs() / 60;
+                            ui.label(session.started_at.format("%Y-%m-%d %H:%M").to_string());
+                            ui.label(format!("{}:{:02}", minutes / 60, minutes % 60));
+                            ui.label((session.monitor + 1).to_string());
+                            ui.label(session.spectrum.as_deref().unwrap_or("-"));
+                            ui.label(format!("{:.0}", session.average_fps));
+                            ui.label(format!("{:.0}", session.low_fps));
+                            ui.label(format!("{:.1}", session.max_frame_time_ms));
+                            ui.label(session.error_count.to_string());
+                            ui.label(session.recovery_count.to_string());
+                            ui.end_row();
+                        }
+                    });
+            });
+        }
+
+        let mut limit = self.state.read(|s| s.session_history_limit);
+        ui.horizontal(|ui| {
+            ui.label(tr!("history.limit"));
+            if ui.add(egui::DragValue::new(&mut limit).range(10..=10000))
+                .on_hover_text(tr!("history.limit_hint"))
+                .changed()
+            {
+                self.state.update(|s| s.session_history_limit = limit);
+            }
+        });
+    }
+
+    fn show_monitor_select(&mut self, ui: &mut egui::Ui, label: &str) {
+        if self.monitors.len() <= 1 {
+            return;
+        }
+
+        ui.label(label);
+        let mut monitor_changed = false;
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_id_salt("monitor_select")
+                .selected_text(format!("{}. {} ({}x{})",
+                    self.selected_monitor + 1,
+                    self.monitors[self.selected_monitor].name,
+                    self.monitors[self.selected_monitor].width,
+                    self.monitors[self.selected_monitor].height))
+                .show_ui(ui, |ui| {
+                    for (idx, monitor) in self.monitors.iter().enumerate() {
+                        let label = format!("{}. {} ({}x{} @ {}Hz){}{}",
+                            idx + 1, monitor.name, monitor.width, monitor.height,
+                            monitor.refresh_rate,
+                            if monitor.is_primary { tr!("monitor.primary_suffix") } else { String::new() },
+                            if monitor.is_virtual() { tr!("monitor.virtual_suffix") } else { String::new() });
+
+                        match monitor.unusable() {
+                            Some(unusable) => {
+                                // Listed so the numbers match Identify, but the overlay can't run there
+                                ui.add_enabled(false, egui::SelectableLabel::new(false, format!("{} {}", label, tr!("monitor.unusable_suffix"))))
+                                    .on_disabled_hover_text(tr!("monitor.unusable_hint", reason = unusable));
+                            }
+                            None => {
+                                let mut response = theme::selectable_value(ui, &mut self.selected_monitor, idx, label);
+                                if monitor.is_virtual() {
+                                    response = response.on_hover_text(tr!("monitor.virtual_hint", device = monitor.device_string));
+                                }
+                                if response.clicked() {
+                                    monitor_changed = true;
+                                }
+                            }
+                        }
+                    }
+                });
+            if ui.button(tr!("monitor.identify")).on_hover_text(tr!("monitor.identify_tooltip")).clicked() {
+                crate::monitor_identify::flash_all(&self.monitors);
+            }
+        });
+        if monitor_changed {
+            crate::monitor_identify::flash_one(&self.monitors, self.selected_monitor);
+            self.state.update(|s| {
+                s.last_monitor = Some(self.selected_monitor);
+            });
+            // The refresh rate cap is labeled with the monitor's rate
+            self.settings = Rc::new(self.build_settings());
+            self.restart_overlay_if_needed();
+        }
+        ui.end_row();
+    }
+
+    fn show_spectrum_select(&mut self, ui: &mut egui::Ui, label: &str) {
+        use chromabridge::state::split_spectrum_name;
+
+        ui.label(label);
+        let spectrum_text = self.selected_spectrum
+            .as_deref()
+            .map(|name| Self::truncate_with_ellipsis(name, 30))
+            .unwrap_or_else(|| tr!("common.none"));
+        let favorites = self.state.read(|s| s.favorite_spectrums.clone());
+        let metadata = self.selected_spectrum_file().map(|file| file.metadata.clone()).filter(|metadata| !metadata.is_empty());
+        let warnings = self.selected_spectrum_warnings().to_vec();
+        let mut spectrum_changed = None;
+        let mut favorite_toggled = None;
+        let mut favorite_moved = None;
+        let mut apply_strength = None;
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_id_salt("spectrum_select")
+                .selected_text(spectrum_text)
+                // Starring and dragging favorites keeps the list open; picking a spectrum closes it below
+                .close_behavior(egui::PopupCloseBehavior::CloseOnClickOutside)
+                .height(400.0)
+                .show_ui(ui, |ui| {
+                    let pinned: Vec<(usize, usize)> = favorites
+                        .iter()
+                        .enumerate()
+                        .filter_map(|(pos, name)| Some((pos, self.spectrum_files.iter().position(|f| f == name)?)))
+                        .collect();
+                    if !pinned.is_empty() {
+                        ui.weak(tr!("spectrum.favorites"));
+                        for (pos, idx) in pinned {
+                            let row = ui.dnd_drag_source(egui::Id::new(("favorite_spectrum", pos)), pos, |ui| {
+                                let spectrum = &self.spectrum_files[idx];
+                                if theme::selectable_label(ui, self.selected_spectrum.as_ref() == Some(spectrum), format!("★ {}", spectrum)).clicked() {
+                                    spectrum_changed = Some(idx);
+                                }
+                            });
+                            let row = row.response.on_hover_text(tr!("spectrum.reorder_hint"));
+                            if let Some(from) = row.dnd_release_payload::<usize>() {
+                                favorite_moved = Some((*from, pos));
+                            }
+                        }
+                        ui.separator();
+                    }
+
+                    // Within a folder, spectra for the same deficiency are listed together, undeclared ones last
+                    let deficiency = |idx: usize| self.spectrum_deficiencies.get(idx).copied().flatten();
+                    let mut order: Vec<usize> = (0..self.spectrum_files.len()).collect();
+                    order.sort_by_key(|&idx| {
+                        (split_spectrum_name(&self.spectrum_files[idx]).0, deficiency(idx).is_none(), deficiency(idx), idx)
+                    });
+
+                    let mut folder = "";
+                    for idx in order {
+                        let spectrum = &self.spectrum_files[idx];
+                        let (spectrum_folder, file_name) = split_spectrum_name(spectrum);
+                        if spectrum_folder != folder {
+                            folder = spectrum_folder;
+                            ui.add_space(4.0);
+                            ui.weak(format!("📁 {}", folder));
+                        }
+                        ui.horizontal(|ui| {
+                            let favorite = favorites.contains(spectrum);
+                            let star = ui.small_button(if favorite { "★" } else { "☆" }).on_hover_text(tr!("spectrum.favorite_hint"));
+                            if star.clicked() {
+                                favorite_toggled = Some(spectrum.clone());
+                            }
+                            if theme::selectable_label(ui, self.selected_spectrum.as_ref() == Some(spectrum), file_name).clicked() {
+                                spectrum_changed = Some(idx);
+                            }
+                            if let Some(deficiency) = deficiency(idx) {
+                                ui.weak(deficiency_label(deficiency));
+                            }
+                        });
+                    }
+
+                    if spectrum_changed.is_some() {
+                        ui.memory_mut(|m| m.close_popup());
+                    }
+                });
+            if let Some(metadata) = metadata {
+                let info = ui.small_button("ℹ").on_hover_text(tr!("spectrum.info_hint"));
+                let popup_id = ui.make_persistent_id("spectrum_info");
+                if info.clicked() {
+                    ui.memory_mut(|m| m.toggle_popup(popup_id));
+                }
+                egui::popup_below_widget(ui, popup_id, &info, egui::PopupCloseBehavior::CloseOnClickOutside, |ui| {
+                    ui.set_min_width(240.0);
+                    if let Some(ref name) = metadata.name {
+                        ui.strong(name);
+                    }
+                    if let Some(ref author) = metadata.author {
+                        ui.label(tr!("spectrum.info_author", author = author));
+                    }
+                    if let Some(deficiency) = metadata.deficiency {
+                        ui.label(tr!("spectrum.info_deficiency", deficiency = deficiency_label(deficiency)));
+                    }
+                    if let Some(ref description) = metadata.description {
+                        ui.label(description);
+                    }
+                    if let Some(strength) = metadata.recommended_strength() {
+                        ui.horizontal(|ui| {
+                            ui.label(tr!("spectrum.info_strength", percent = format!("{:.0}", strength * 100.0)));
+                            let applied = (self.strength - strength).abs() < 0.005;
+                            if ui.add_enabled(!applied, egui::Button::new(tr!("spectrum.info_apply"))).clicked() {
+                                apply_strength = Some(strength);
+                            }
+                        });
+                    }
+                });
+            }
+            if !warnings.is_empty() {
+                ui.label(
+                    egui::RichText::new(tr!("spectrum.load_warnings", count = warnings.len()))
+                        .size(11.0)
+                        .color(theme::palette(ui).warning)
+                )
+                .on_hover_text(format!("{}\n\n{}", warnings.join("\n"), tr!("spectrum.load_warnings_hint")));
+            }
+            let deletable = self.selected_spectrum.clone();
+            if ui.add_enabled(deletable.is_some(), egui::Button::new("🗑").small()).on_hover_text(tr!("trash.delete_spectrum")).clicked() {
+                self.pending_delete = deletable.map(|name| (AssetKind::Spectrum, name));
+            }
+        });
+        if let Some(name) = favorite_toggled {
+            self.state.update(|s| s.toggle_favorite_spectrum(&name));
+            self.actions = Rc::new(self.build_actions());
+        }
+        if let Some((from, to)) = favorite_moved {
+            self.state.update(|s| s.move_favorite_spectrum(from, to));
+            self.actions = Rc::new(self.build_actions());
+        }
+        if let Some(idx) = spectrum_changed {
+            self.select_spectrum_index(idx);
+        }
+        if let Some(strength) = apply_strength {
+            self.set_strength(strength);
+        }
+        ui.end_row();
+    }
+
+    fn show_noise_select(&mut self, ui: &mut egui::Ui, label: &str) {
+        ui.label(label);
+        let noise_text = self.selected_noise
+            .as_deref()
+            .map(|name| Self::truncate_with_ellipsis(name, 30))
+            .unwrap_or_else(|| tr!("common.none"));
+        let mut noise_changed: Option<Option<String>> = None;
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_id_salt("noise_select")
+                .selected_text(noise_text)
+                .show_ui(ui, |ui| {
+                    if theme::selectable_label(ui, self.selected_noise.is_none(), tr!("common.none")).clicked() {
+                        self.selected_noise = None;
+                        noise_changed = Some(None);
+                    }
+
+                    for noise in &self.noise_files {
+                        if theme::selectable_label(ui, self.selected_noise.as_ref() == Some(noise), noise).clicked() {
+                            self.selected_noise = Some(noise.clone());
+                            noise_changed = Some(Some(noise.clone()));
+                        }
+                    }
+                });
+            let deletable = self.selected_noise.clone();
+            if ui.add_enabled(deletable.is_some(), egui::Button::new("🗑").small()).on_hover_text(tr!("trash.delete_noise")).clicked() {
+                self.pending_delete = deletable.map(|name| (AssetKind::Noise, name));
+            }
+
+            let mut noise_target = self.state.read(|s| s.noise_target);
+            let mut target_changed = false;
+            ui.add_enabled_ui(self.selected_noise.is_some(), |ui| {
+                egui::ComboBox::from_id_salt("noise_target")
+                    .selected_text(noise_target.label())
+                    .show_ui(ui, |ui| {
+                        for target in chromabridge::NoiseTarget::ALL {
+                            target_changed |= theme::selectable_value(ui, &mut noise_target, target, target.label()).changed();
+                        }
+                    })
+                    .response
+                    .on_hover_text(tr!("correction.noise_target_hint"));
+            });
+            if target_changed {
+                self.state.update(|s| s.noise_target = noise_target);
+            }
+        });
+        if let Some(noise) = noise_changed {
+            self.state.update(|s| s.select_noise(noise));
+            self.restart_overlay_if_needed();
+        }
+        ui.end_row();
+    }
+
+    fn show_noise_preview(&mut self, ui: &mut egui::Ui, label: &str) {
+        if self.selected_noise.is_none() {
+            return;
+        }
+        let Some(monitor) = self.monitors.get(self.selected_monitor) else {
+            return;
+        };
+
+        ui.label(label);
+        let screen = (monitor.width.max(1) as u32, monitor.height.max(1) as u32);
+        self.noise_preview.show(ui, &self.state, screen);
+        ui.end_row();
+    }
+
+    fn show_noise_intensity(&mut self, ui: &mut egui::Ui, label: &str) {
+        ui.label(label);
+        let mut noise_intensity = self.state.read(|s| s.noise_intensity);
+        let intensity_response = ui.add_enabled(
+            self.selected_noise.is_some(),
+            percent_slider(&mut noise_intensity)
+        )
+        .on_hover_text(tr!("correction.pattern_intensity_hint"));
+        if intensity_response.changed() {
+            self.state.update_ephemeral(|s| {
+                s.noise_intensity = noise_intensity;
+                s.remember_noise_settings();
+                s.remember_spectrum_settings();
+            });
+        }
+        if intensity_response.drag_stopped() || (intensity_response.changed() && !intensity_response.dragged()) {
+            self.state.flush();
+        }
+        ui.end_row();
+    }
+
+    fn show_noise_mapping(&mut self, ui: &mut egui::Ui, label: &str) {
+        if self.selected_noise.is_none() {
+            return;
+        }
+
+        ui.label(label);
+        let (mut noise_mapping, noise_name) = self.state.read(|s| (s.noise_mapping, s.noise_texture.clone()));
+        let mut mapping_changed = false;
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_id_salt("noise_mapping")
+                .selected_text(noise_mapping.label())
+                .show_ui(ui, |ui| {
+                    for mapping in chromabridge::NoiseMapping::ALL {
+                        mapping_changed |= theme::selectable_value(ui, &mut noise_mapping, mapping, mapping.label()).changed();
+                    }
+                });
+            if let Some(name) = noise_name {
+                ui.weak("📌").on_hover_text(tr!("correction.noise_remembered", name = name));
+            }
+        });
+        if mapping_changed {
+            self.state.update(|s| {
+                s.noise_mapping = noise_mapping;
+                s.remember_noise_settings();
+            });
+        }
+        ui.end_row();
+    }
+
+    /// Only for a dual-spectrum file without a noise texture, which picks per pixel instead.
+    fn show_blend_ratio(&mut self, ui: &mut egui::Ui, label: &str) {
+        if self.selected_noise.is_some() {
+            return;
+        }
+        let selection = self.state.read(|s| s.spectrum_selection);
+        let Some(file) = self.selected_spectrum_file() else {
+            return;
+        };
+        if file.spectrum2_mode.is_explicit() && selection.resolve(file.spectra.len()).secondary.is_none() {
+            return;
+        }
+
+        ui.label(label);
+        let mut blend_ratio = self.state.read(|s| s.blend_ratio);
+        let response = ui.add(percent_slider(&mut blend_ratio)).on_hover_text(tr!("correction.blend_ratio_hint"));
+        if response.changed() {
+            self.state.update_ephemeral(|s| {
+                s.blend_ratio = blend_ratio;
+                s.remember_spectrum_settings();
+            });
+        }
+        if response.drag_stopped() || (response.changed() && !response.dragged()) {
+            self.state.flush();
+        }
+        ui.end_row();
+    }
+
+    fn show_strength(&mut self, ui: &mut egui::Ui, label: &str) {
+        ui.label(label);
+        ui.horizontal(|ui| {
+            let slider_response = ui.add(percent_slider(&mut self.strength).text(""));
+            if self.state.read(|s| s.strength_curve) == chromabridge::StrengthCurve::Perceptual {
+                Self::paint_strength_ticks(ui, slider_response.rect);
+            }
+            if slider_response.changed() {
+                // Persisted once the drag ends to avoid a write per frame
+                self.state.update_ephemeral(|s| {
+                    s.strength = self.strength;
+                    s.remember_spectrum_settings();
+                });
+            }
+            if slider_response.drag_stopped() || (slider_response.changed() && !slider_response.dragged()) {
+                self.state.flush();
+            }
+
+            let per_spectrum = self.state.read(|s| s.per_spectrum_settings);
+            let link_response = theme::selectable_label(ui, !per_spectrum, "🔗")
+                .on_hover_text(if per_spectrum {
+                    tr!("correction.per_spectrum_hint")
+                } else {
+                    tr!("correction.shared_hint")
+                });
+            if link_response.clicked() {
+                self.state.update(|s| {
+                    s.per_spectrum_settings = !per_spectrum;
+                    s.remember_spectrum_settings();
+                });
+            }
+        });
+        ui.end_row();
+    }
+
+    /// Marks every tenth of the slider's travel under its rail. On the perceptual curve each step
+    /// changes the picture about as much as the next, which the ticks make visible.
+    fn paint_strength_ticks(ui: &egui::Ui, slider_rect: egui::Rect) {
+        // Same geometry as egui's slider: the handle stops its radius short of each end
+        let height = ui.spacing().interact_size.y;
+        let inset = height / 2.5;
+        let left = slider_rect.left() + inset;
+        let right = slider_rect.left() + ui.spacing().slider_width - inset;
+        let bottom = slider_rect.top() + height;
+        let stroke = egui::Stroke::new(1.0, ui.visuals().widgets.noninteractive.fg_stroke.color.gamma_multiply(0.5));
+        for step in 0..=10 {
+            let x = egui::lerp(left..=right, step as f32 / 10.0);
+            let length = if step % 5 == 0 { 4.0 } else { 2.0 };
+            ui.painter().line_segment([egui::pos2(x, bottom - length), egui::pos2(x, bottom)], stroke);
+        }
+    }
+
+    fn show_temporal_smoothing(&mut self, ui: &mut egui::Ui, label: &str) {
+        ui.label(label);
+        let (mut temporal_smoothing, battery_saver) = self.state.read(|s| (s.temporal_smoothing, s.battery_saver_active()));
+        let smoothing_response = ui.add_enabled(
+            !battery_saver,
+            egui::Slider::new(&mut temporal_smoothing, 0.0..=0.9)
+                .custom_formatter(|v, _| if v <= 0.0 { tr!("correction.off") } else { NumberFormat::current().decimal(v, 2) })
+        )
+        .on_hover_text(tr!("correction.temporal_smoothing_hint"))
+        .on_disabled_hover_text(tr!("performance.managed_by_battery_saver"));
+        if smoothing_response.changed() {
+            self.state.update_ephemeral(|s| s.temporal_smoothing = temporal_smoothing);
+        }
+        if smoothing_response.drag_stopped() || (smoothing_response.changed() && !smoothing_response.dragged()) {
+            self.state.flush();
+        }
+        ui.end_row();
+    }
+
+    fn show_screenshot_preview_button(&mut self, ui: &mut egui::Ui, label: &str) {
+        ui.label(label);
+        if ui.button(tr!("preview.open")).on_hover_text(tr!("preview.open_hint")).clicked() {
+            self.open_screenshot_preview();
+        }
+        ui.end_row();
+    }
+
+    fn open_screenshot_preview(&mut self) {
+        if let Some(monitor) = self.monitors.get(self.selected_monitor).cloned() {
+            self.screenshot_preview = Some(ScreenshotPreview::new(monitor));
+        }
+    }
+
+    fn show_screenshot_preview(&mut self, ctx: &egui::Context) {
+        if let Some(preview) = self.screenshot_preview.as_mut() {
+            if !preview.show(ctx, &self.state) {
+                self.screenshot_preview = None;
+            }
+        }
+    }
+
+    fn show_asset_settings(&mut self, ui: &mut egui::Ui, label: &str) {
+        ui.label(label);
+        ui.horizontal(|ui| {
+            if ui.button(tr!("advanced.open_asset_folder")).clicked() {
+                self.open_asset_folder();
+            }
+
+            if ui.button("↻").clicked() {
+                self.refresh_assets();
+            }
+
+            if ui.button(tr!("advanced.import_matrix")).clicked() {
+                self.matrix_import = Some(MatrixImportDialog::default());
+            }
+        });
+
+        ui.horizontal(|ui| {
+            let selected_name = self.selected_spectrum.clone();
+            if ui.add_enabled(selected_name.is_some(), egui::Button::new(tr!("advanced.copy_share_code"))).clicked() {
+                if let Some(name) = selected_name {
+                    self.copy_share_code(ui.ctx(), &name);
+                }
+            }
+
+            if ui.button(tr!("advanced.import_share_code")).clicked() {
+                self.share_import = Some(ShareCodeImportDialog::default());
+            }
+        });
+
+        if ui.button(tr!("advanced.vision_setup")).clicked() {
+            self.vision_setup = Some(VisionSetupWizard::default());
+        }
+    }
+
+    fn show_data_folders(&mut self, ui: &mut egui::Ui, label: &str) {
+        let dirs = self.state.data_dirs();
+        ui.label(label);
+        egui::Grid::new("data_folders").num_columns(2).spacing([12.0, 4.0]).show(ui, |ui| {
+            ui.label(tr!("data_dirs.local"));
+            ui.label(dirs.local.display().to_string());
+            ui.end_row();
+            ui.label(tr!("data_dirs.assets"));
+            ui.label(dirs.assets.display().to_string());
+            ui.end_row();
+        });
+        ui.weak(tr!("data_dirs.layout", layout = dirs.layout.label())).on_hover_text(tr!("data_dirs.hint"));
+    }
+
+    fn show_run_at_startup(&mut self, ui: &mut egui::Ui, label: &str) {
+        let mut run_at_startup = self.state.read(|s| s.run_at_startup);
+        if ui.checkbox(&mut run_at_startup, label).changed()
+            && crate::startup::set_startup_enabled(run_at_startup).is_ok()
+        {
+            self.state.update(|s| s.run_at_startup = run_at_startup);
+        }
+    }
+
+    fn show_open_gui_on_launch(&mut self, ui: &mut egui::Ui, label: &str) {
+        let mut open_gui_on_launch = self.state.read(|s| s.open_gui_on_launch);
+        if ui.checkbox(&mut open_gui_on_launch, label).changed() {
+            self.state.update(|s| s.open_gui_on_launch = open_gui_on_launch);
+        }
+    }
+
+    fn show_keep_running_in_tray(&mut self, ui: &mut egui::Ui, label: &str) {
+        let mut keep_running_in_tray = self.state.read(|s| s.keep_running_in_tray);
+        if ui.checkbox(&mut keep_running_in_tray, label).changed() {
+            self.state.update(|s| s.keep_running_in_tray = keep_running_in_tray);
+        }
+    }
+
+    fn show_notify_start_stop(&mut self, ui: &mut egui::Ui, label: &str) {
+        let mut notify = self.state.read(|s| s.notify_start_stop);
+        if ui.checkbox(&mut notify, label).on_hover_text(tr!("notifications.hint")).changed() {
+            self.state.update(|s| s.notify_start_stop = notify);
+        }
+    }
+
+    fn show_notify_pause(&mut self, ui: &mut egui::Ui, label: &str) {
+        let mut notify = self.state.read(|s| s.notify_pause);
+        if ui.checkbox(&mut notify, label).on_hover_text(tr!("notifications.hint")).changed() {
+            self.state.update(|s| s.notify_pause = notify);
+        }
+    }
+
+    fn show_notify_errors(&mut self, ui: &mut egui::Ui, label: &str) {
+        let mut notify = self.state.read(|s| s.notify_errors);
+        if ui.checkbox(&mut notify, label).on_hover_text(tr!("notifications.hint")).changed() {
+            self.state.update(|s| s.notify_errors = notify);
+        }
+    }
+
+    fn show_update_settings(&mut self, ui: &mut egui::Ui, label: &str) {
+        ui.label(label);
+        let (mut enabled, manifest_url, last_check) =
+            self.state.read(|s| (s.check_for_updates, s.update_manifest_url.clone(), s.last_update_check.clone()));
+        if ui.checkbox(&mut enabled, tr!("update.enable")).on_hover_text(tr!("update.enable_hint")).changed() {
+            self.state.update(|s| s.check_for_updates = enabled);
+        }
+        if !enabled {
+            return;
+        }
+
+        ui.horizontal(|ui| {
+            ui.label(tr!("update.manifest_url"));
+            let mut text = self.update_manifest_text.take().unwrap_or(manifest_url);
+            let response = ui.add(egui::TextEdit::singleline(&mut text).hint_text(update_check::DEFAULT_MANIFEST_URL).desired_width(260.0))
+                .on_hover_text(tr!("update.manifest_url_hint"));
+            if response.lost_focus() {
+                let url = match text.trim() {
+                    "" => update_check::DEFAULT_MANIFEST_URL.to_string(),
+                    url => url.to_string(),
+                };
+                self.state.update(|s| s.update_manifest_url = url);
+            } else if response.has_focus() {
+                self.update_manifest_text = Some(text);
+            }
+        });
+
+        let status = match last_check {
+            Some(check) => {
+                let checked = chrono::DateTime::from_timestamp(check.checked_at as i64, 0)
+                    .map(|time| time.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M").to_string())
+                    .unwrap_or_default();
+                match check.update() {
+                    Some(release) => tr!("update.status_available", version = release.version, checked = checked),
+                    None => tr!("update.status_current", version = update_check::CURRENT_VERSION, checked = checked),
+                }
+            }
+            None => tr!("update.status_not_checked"),
+        };
+        ui.label(egui::RichText::new(status).size(11.0).color(theme::palette(ui).weak_text));
+    }
+
+    fn show_language_select(&mut self, ui: &mut egui::Ui, label: &str) {
+        let mut language = self.state.read(|s| s.language);
+        let mut language_changed = false;
+        ui.horizontal(|ui| {
+            ui.label(label);
+            egui::ComboBox::from_id_salt("language")
+                .selected_text(language.label())
+                .show_ui(ui, |ui| {
+                    for l in chromabridge::Language::ALL {
+                        language_changed |= theme::selectable_value(ui, &mut language, l, l.label()).changed();
+                    }
+                });
+        });
+        if language_changed {
+            self.set_language(language);
+        }
+    }
+
+    fn show_theme_settings(&mut self, ui: &mut egui::Ui, label: &str) {
+        ui.label(label);
+        let (mut gui_theme, mut spectrum_accent) = self.state.read(|s| (s.gui_theme, s.spectrum_accent));
+        let mut theme_changed = false;
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_id_salt("gui_theme")
+                .selected_text(gui_theme.label())
+                .show_ui(ui, |ui| {
+                    for t in chromabridge::GuiTheme::ALL {
+                        theme_changed |= theme::selectable_value(ui, &mut gui_theme, t, t.label()).changed();
+                    }
+                });
+            theme_changed |= ui.checkbox(&mut spectrum_accent, tr!("advanced.spectrum_accent"))
+                .on_hover_text(tr!("advanced.spectrum_accent_hint"))
+                .changed();
+        });
+        if theme_changed {
+            self.state.update(|s| {
+                s.gui_theme = gui_theme;
+                s.spectrum_accent = spectrum_accent;
+            });
+        }
+    }
+
+    fn show_fullscreen_settings(&mut self, ui: &mut egui::Ui, label: &str) {
+        ui.label(label);
+        let (mut fullscreen_behavior, mut fullscreen_fps, battery_saver) =
+            self.state.read(|s| (s.fullscreen_behavior, s.fullscreen_target_fps, s.battery_saver_active()));
+        ui.add_enabled_ui(!battery_saver, |ui| ui.horizontal(|ui| {
+            use chromabridge::FullscreenBehavior;
+
+            let label = |b: FullscreenBehavior| match b {
+                FullscreenBehavior::Ignore => tr!("advanced.fullscreen_ignore"),
+                FullscreenBehavior::Reduce => tr!("advanced.fullscreen_reduce"),
+                FullscreenBehavior::Pause => tr!("advanced.fullscreen_pause"),
+            };
+            let mut behavior_changed = false;
+            egui::ComboBox::from_id_salt("fullscreen_behavior")
+                .selected_text(label(fullscreen_behavior))
+                .show_ui(ui, |ui| {
+                    for b in [FullscreenBehavior::Ignore, FullscreenBehavior::Reduce, FullscreenBehavior::Pause] {
+                        behavior_changed |= theme::selectable_value(ui, &mut fullscreen_behavior, b, label(b)).changed();
+                    }
+                });
+            if behavior_changed {
+                self.state.update(|s| s.fullscreen_behavior = fullscreen_behavior);
+            }
+
+            if fullscreen_behavior == FullscreenBehavior::Reduce
+                && ui.add(egui::DragValue::new(&mut fullscreen_fps).range(5..=240).suffix(tr!("advanced.fps_suffix"))).changed()
+            {
+                self.state.update(|s| s.fullscreen_target_fps = fullscreen_fps);
+            }
+        }))
+        .response
+        .on_disabled_hover_text(tr!("performance.managed_by_battery_saver"));
+    }
+
+    fn show_conflict_detection(&mut self, ui: &mut egui::Ui, label: &str) {
+        use chromabridge::ConflictBehavior;
+
+        let (mut enabled, mut behavior, processes) = self.state.read(|s| (s.conflict_detection, s.conflict_behavior, s.conflict_processes.clone()));
+        if ui.checkbox(&mut enabled, label).on_hover_text(tr!("advanced.conflict_detection_hint")).changed() {
+            self.state.update(|s| s.conflict_detection = enabled);
+        }
+        if !enabled {
+            return;
+        }
+
+        ui.horizontal(|ui| {
+            ui.label(tr!("advanced.conflict_behavior"));
+            let label = |b: ConflictBehavior| match b {
+                ConflictBehavior::Notify => tr!("advanced.conflict_notify"),
+                ConflictBehavior::Reduce => tr!("advanced.conflict_reduce"),
+                ConflictBehavior::Pause => tr!("advanced.conflict_pause"),
+            };
+            let mut behavior_changed = false;
+            egui::ComboBox::from_id_salt("conflict_behavior")
+                .selected_text(label(behavior))
+                .show_ui(ui, |ui| {
+                    for b in [ConflictBehavior::Notify, ConflictBehavior::Reduce, ConflictBehavior::Pause] {
+                        behavior_changed |= theme::selectable_value(ui, &mut behavior, b, label(b)).changed();
+                    }
+                });
+            if behavior_changed {
+                self.state.update(|s| s.conflict_behavior = behavior);
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.label(tr!("advanced.conflict_processes"));
+            let mut text = self.conflict_processes_text.take().unwrap_or_else(|| processes.join(", "));
+            let response = ui.add(egui::TextEdit::singleline(&mut text).hint_text("flux.exe").desired_width(260.0))
+                .on_hover_text(tr!("advanced.conflict_processes_hint"));
+            if response.lost_focus() {
+                let processes: Vec<String> = text.split(',').map(str::trim).filter(|name| !name.is_empty()).map(String::from).collect();
+                self.state.update(|s| s.conflict_processes = processes);
+            } else if response.has_focus() {
+                self.conflict_processes_text = Some(text);
+            }
+        });
+    }
+
+    fn show_strength_curve(&mut self, ui: &mut egui::Ui, label: &str) {
+        use chromabridge::StrengthCurve;
+
+        ui.label(label);
+        let mut curve = self.state.read(|s| s.strength_curve);
+        let curve_label = |c: StrengthCurve| match c {
+            StrengthCurve::Linear => tr!("advanced.strength_curve_linear"),
+            StrengthCurve::Perceptual => tr!("advanced.strength_curve_perceptual"),
+        };
+        let mut curve_changed = false;
+        egui::ComboBox::from_id_salt("strength_curve")
+            .selected_text(curve_label(curve))
+            .show_ui(ui, |ui| {
+                for c in [StrengthCurve::Linear, StrengthCurve::Perceptual] {
+                    curve_changed |= theme::selectable_value(ui, &mut curve, c, curve_label(c)).changed();
+                }
+            })
+            .response
+            .on_hover_text(tr!("advanced.strength_curve_hint"));
+        if curve_changed {
+            self.state.update(|s| s.strength_curve = curve);
+        }
+    }
+
+    fn show_topmost_settings(&mut self, ui: &mut egui::Ui, label: &str) {
+        ui.label(label);
+        let mut topmost_mode = self.state.read(|s| s.topmost_mode);
+        let mut topmost_changed = false;
+        egui::ComboBox::from_id_salt("topmost_mode")
+            .selected_text(topmost_mode.label())
+            .show_ui(ui, |ui| {
+                for mode in chromabridge::TopmostMode::ALL {
+                    topmost_changed |= theme::selectable_value(ui, &mut topmost_mode, mode, mode.label()).changed();
+                }
+            })
+            .response
+            .on_hover_text(tr!("advanced.topmost_hint"));
+        if topmost_changed {
+            self.state.update(|s| s.topmost_mode = topmost_mode);
+        }
+    }
+
+    fn show_shell_exclusion(&mut self, ui: &mut egui::Ui, label: &str) {
+        let mut exclude_shell = self.state.read(|s| s.exclude_shell);
+        if ui.checkbox(&mut exclude_shell, label)
+            .on_hover_text(tr!("advanced.exclude_shell_hint"))
+            .changed()
+        {
+            self.state.update(|s| s.exclude_shell = exclude_shell);
+        }
+    }
+
+    fn show_hide_from_capture(&mut self, ui: &mut egui::Ui, label: &str) {
+        let mut hide_from_capture = self.state.read(|s| s.hide_from_capture);
+        if ui.checkbox(&mut hide_from_capture, label)
+            .on_hover_text(tr!("advanced.hide_from_capture_hint"))
+            .changed()
+        {
+            self.state.update(|s| s.hide_from_capture = hide_from_capture);
+        }
+        if !hide_from_capture {
+            ui.colored_label(theme::palette(ui).warning, tr!("overlay.capture_feedback"));
+        }
+    }
+
+    fn show_target_window(&mut self, ui: &mut egui::Ui, label: &str) {
+        let (mut only, target) = self.state.read(|s| (s.target_window_only, s.target_window.clone()));
+        ui.horizontal(|ui| {
+            if ui.add_enabled(target.is_some(), egui::Checkbox::new(&mut only, label))
+                .on_hover_text(tr!("advanced.target_window_hint"))
+                .changed()
+            {
+                self.state.update(|s| s.target_window_only = only);
+            }
+
+            let mut picked = None;
+            let selected_text = match target {
+                Some(ref target) => target.process_name.clone(),
+                None => tr!("target_window.select"),
+            };
+            let combo = egui::ComboBox::from_id_salt("target_window")
+                .selected_text(selected_text)
+                .show_ui(ui, |ui| {
+                    let choices = self.window_choices.get_or_insert_with(crate::target_window::list_windows);
+                    if choices.is_empty() {
+                        ui.label(tr!("target_window.no_windows"));
+                    }
+                    for choice in choices.iter() {
+                        if theme::selectable_label(ui, false, choice.label()).clicked() {
+                            picked = Some(choice.target());
                         }
-                        self.update_tray_state();
                     }
+                });
+            if combo.inner.is_none() {
+                // Listed again on the next opening, windows come and go meanwhile
+                self.window_choices = None;
+            }
+            if let Some(picked) = picked {
+                self.target_title_text = None;
+                self.state.update(|s| {
+                    s.target_window = Some(picked);
+                    s.target_window_only = true;
+                });
+            }
+        });
+
+        let Some(target) = target else {
+            return;
+        };
+        ui.horizontal(|ui| {
+            ui.label(tr!("target_window.title_pattern"));
+            let mut text = self.target_title_text.take().unwrap_or(target.title_pattern);
+            let response = ui.add(egui::TextEdit::singleline(&mut text).hint_text(tr!("target_window.any_title")).desired_width(260.0))
+                .on_hover_text(tr!("target_window.title_pattern_hint"));
+            if response.lost_focus() {
+                self.state.update(|s| {
+                    if let Some(ref mut target) = s.target_window {
+                        target.title_pattern = text.trim().to_string();
+                    }
+                });
+            } else if response.has_focus() {
+                self.target_title_text = Some(text);
+            }
+        });
+    }
+
+    fn show_cursor_correction(&mut self, ui: &mut egui::Ui, label: &str) {
+        let mut correct_cursor = self.state.read(|s| s.correct_cursor);
+        if ui.checkbox(&mut correct_cursor, label)
+            .on_hover_text(tr!("advanced.correct_cursor_hint"))
+            .changed()
+        {
+            self.state.update(|s| s.correct_cursor = correct_cursor);
+        }
+    }
+
+    fn show_refresh_cap(&mut self, ui: &mut egui::Ui, label: &str) {
+        let mut cap_to_monitor_refresh = self.state.read(|s| s.cap_to_monitor_refresh);
+        if ui.checkbox(&mut cap_to_monitor_refresh, label).changed() {
+            self.state.update(|s| s.cap_to_monitor_refresh = cap_to_monitor_refresh);
+            self.restart_overlay_if_needed();
+        }
+    }
+
+    fn show_debug_overlay(&mut self, ui: &mut egui::Ui, label: &str) {
+        let mut debug_overlay = self.state.read(|s| s.debug_overlay);
+        if ui.checkbox(&mut debug_overlay, label).changed() {
+            self.set_debug_overlay(debug_overlay);
+        }
+        if debug_overlay {
+            ui.label(
+                egui::RichText::new(tr!("developer.debug_overlay_hint"))
+                    .size(11.0)
+                    .color(theme::DEBUG_OVERLAY)
+            );
+        }
+    }
+
+    fn show_debug_visibility(&mut self, ui: &mut egui::Ui, label: &str) {
+        use crate::overlay::DebugVisibility;
+
+        let current = self.overlay_manager.debug_visibility();
+        let mut mode = current;
+        ui.horizontal(|ui| {
+            ui.label(label);
+            egui::ComboBox::from_id_salt("debug_visibility")
+                .selected_text(mode.label())
+                .show_ui(ui, |ui| {
+                    for m in DebugVisibility::ALL {
+                        theme::selectable_value(ui, &mut mode, m, m.label());
+                    }
+                })
+                .response
+                .on_hover_text(tr!("developer.debug_visibility_hint"));
+        });
+        if mode != current {
+            self.overlay_manager.set_debug_visibility(mode);
+        }
+    }
+
+    fn show_watch_spectrum(&mut self, ui: &mut egui::Ui, label: &str) {
+        let mut watch_spectrum_file = self.state.read(|s| s.watch_spectrum_file);
+        if ui.checkbox(&mut watch_spectrum_file, label).changed() {
+            self.state.update(|s| s.watch_spectrum_file = watch_spectrum_file);
+        }
+    }
+
+    fn show_posterize(&mut self, ui: &mut egui::Ui, label: &str) {
+        let posterize_levels = self.state.read(|s| s.posterize_levels);
+        let mut posterize = posterize_levels.is_some();
+        let mut levels = posterize_levels.unwrap_or(12);
+        ui.horizontal(|ui| {
+            let mut changed = ui.checkbox(&mut posterize, label).changed();
+            if posterize {
+                changed |= ui.add(egui::DragValue::new(&mut levels).range(2..=64).suffix(tr!("developer.levels_suffix"))).changed();
+            }
+            if changed {
+                let levels = posterize.then_some(levels);
+                self.state.update(|s| s.posterize_levels = levels);
+            }
+        });
+    }
+
+    fn show_max_noise_size(&mut self, ui: &mut egui::Ui, label: &str) {
+        let mut max_noise_size = self.state.read(|s| s.max_noise_size);
+        ui.horizontal(|ui| {
+            ui.label(label);
+            let response = ui.add(egui::DragValue::new(&mut max_noise_size).range(256..=16384).suffix(" px"))
+                .on_hover_text(tr!("developer.max_noise_size_hint"));
+            if response.changed() {
+                self.state.update_ephemeral(|s| s.max_noise_size = max_noise_size);
+            }
+            // Relisting decodes every noise texture, so only once the value settled
+            if response.drag_stopped() || (response.changed() && !response.dragged()) {
+                self.state.flush();
+                self.refresh_assets();
+            }
+        });
+    }
+
+    fn show_frame_dump(&mut self, ui: &mut egui::Ui, label: &str) {
+        let can_dump = self.overlay_manager.is_running() && !self.overlay_manager.frame_dump_pending();
+        if ui.add_enabled(can_dump, egui::Button::new(label))
+            .on_hover_text(tr!("developer.frame_dump_hint"))
+            .on_disabled_hover_text(tr!("developer.frame_dump_needs_overlay"))
+            .clicked()
+        {
+            self.overlay_manager.request_frame_dump();
+            self.awaiting_frame_dump = true;
+        }
+    }
+
+    fn show_render_sweep_button(&mut self, ui: &mut egui::Ui, label: &str) {
+        if ui.button(label).on_hover_text(tr!("sweep.open_hint")).clicked() {
+            self.render_sweep = Some(RenderSweep::default());
+        }
+    }
+
+    fn show_render_sweep(&mut self, ctx: &egui::Context) {
+        if let Some(sweep) = self.render_sweep.as_mut() {
+            if !sweep.show(ctx, &self.state) {
+                self.render_sweep = None;
+            }
+        }
+    }
+
+    fn show_output_preview_button(&mut self, ui: &mut egui::Ui, label: &str) {
+        if ui.add_enabled(self.output_preview.is_none(), egui::Button::new(label)).on_hover_text(tr!("output_preview.open_hint")).clicked() {
+            let monitor = self.monitors.get(self.selected_monitor).cloned();
+            self.output_preview = Some(OutputPreviewWindow::open(Arc::clone(&self.overlay_manager), monitor));
+        }
+    }
+
+    fn show_output_preview(&mut self, ctx: &egui::Context) {
+        if let Some(preview) = self.output_preview.as_mut() {
+            if !preview.show(ctx) {
+                self.output_preview = None;
+            }
+        }
+    }
+
+    fn show_render_priority(&mut self, ui: &mut egui::Ui, label: &str) {
+        use chromabridge::RenderThreadPriority;
+
+        let mut priority = self.state.read(|s| s.render_thread_priority);
+        let mut priority_changed = false;
+        ui.horizontal(|ui| {
+            ui.label(label);
+            egui::ComboBox::from_id_salt("render_thread_priority")
+                .selected_text(priority.label())
+                .show_ui(ui, |ui| {
+                    for p in RenderThreadPriority::ALL {
+                        priority_changed |= theme::selectable_value(ui, &mut priority, p, p.label()).changed();
+                    }
+                })
+                .response
+                .on_hover_text(tr!("developer.render_priority_hint"));
+        });
+        if priority_changed {
+            self.state.update(|s| s.render_thread_priority = priority);
+            self.restart_overlay_if_needed();
+        }
+    }
+
+    fn show_color_math(&mut self, ui: &mut egui::Ui, label: &str) {
+        use chromabridge::ColorMath;
+
+        if let Some(stats) = self.overlay_manager.get_frame_stats().filter(|_| self.overlay_manager.is_running()) {
+            self.render_ms_by_color_math[stats.color_math as usize] = Some(stats.frame_time_ms);
+        }
+
+        let current = self.state.read(|s| s.color_math);
+        let mut color_math = current;
+        ui.horizontal(|ui| {
+            ui.label(label);
+            egui::ComboBox::from_id_salt("color_math")
+                .selected_text(color_math.label())
+                .show_ui(ui, |ui| {
+                    for mode in ColorMath::ALL {
+                        theme::selectable_value(ui, &mut color_math, mode, mode.label());
+                    }
+                })
+                .response
+                .on_hover_text(tr!("developer.color_math_hint"));
+        });
+        if color_math != current {
+            self.state.update(|s| s.color_math = color_math);
+        }
+
+        // Both modes once each has run, so switching back and forth compares their cost
+        let timings: Vec<String> = ColorMath::ALL
+            .into_iter()
+            .filter_map(|mode| {
+                let ms = self.render_ms_by_color_math[mode as usize]?;
+                Some(tr!("developer.render_time", mode = mode.label(), ms = format!("{:.2}", ms)))
+            })
+            .collect();
+        if !timings.is_empty() {
+            ui.weak(timings.join("  ·  "));
+        }
+    }
+
+    fn show_log_level(&mut self, ui: &mut egui::Ui, label: &str) {
+        use crate::log_info;
+
+        let mut log_level = chromabridge::logger::max_level();
+        ui.horizontal(|ui| {
+            ui.label(label);
+            egui::ComboBox::from_id_salt("log_level")
+                .selected_text(log_level.label())
+                .show_ui(ui, |ui| {
+                    for level in chromabridge::LogLevel::ALL {
+                        theme::selectable_value(ui, &mut log_level, level, level.label());
+                    }
+                });
+        });
+        if log_level != chromabridge::logger::max_level() {
+            chromabridge::logger::set_max_level(log_level);
+            self.state.update(|s| s.log_level = log_level);
+            log_info!("Log level set to {}", log_level.label());
+        }
+    }
+
+    fn show_log_format(&mut self, ui: &mut egui::Ui, label: &str) {
+        let mut log_format = self.state.read(|s| s.log_format);
+        ui.horizontal(|ui| {
+            ui.label(label);
+            egui::ComboBox::from_id_salt("log_format")
+                .selected_text(log_format.label())
+                .show_ui(ui, |ui| {
+                    for format in chromabridge::LogFormat::ALL {
+                        theme::selectable_value(ui, &mut log_format, format, format.label());
+                    }
+                })
+                .response
+                .on_hover_text(tr!("developer.log_format_hint"));
+        });
+        if log_format != self.state.read(|s| s.log_format) {
+            self.state.update(|s| s.log_format = log_format);
+        }
+        // The open log file keeps the format it started with
+        if log_format != chromabridge::logger::log_format() {
+            ui.weak(tr!("developer.log_format_next_launch"));
+        }
+    }
+
+    /// The matching rows of a collapsible section, then its reset buttons.
+    fn show_section(&mut self, ui: &mut egui::Ui, settings: &[Setting], section: SettingsSection, query: &str) {
+        let spacing = if section == SettingsSection::Developer { 10.0 } else { 15.0 };
+
+        ui.add_space(10.0);
+        settings_search::show_rows(self, ui, settings, section, query, spacing);
+
+        ui.add_space(spacing);
+        ui.horizontal(|ui| {
+            if ui.button(tr!("settings.reset_section")).clicked() {
+                match section {
+                    SettingsSection::Advanced => self.reset_settings(AppState::reset_advanced_settings),
+                    SettingsSection::Developer => self.reset_settings(AppState::reset_developer_settings),
+                    SettingsSection::Correction => {}
+                }
+            }
+            if section == SettingsSection::Advanced && ui.button(tr!("settings.reset_all")).clicked() {
+                self.confirm_reset_all = true;
+            }
+        });
+        ui.add_space(10.0);
+    }
+
+    /// Applies a reset through `StateManager::update` like the individual controls, along with what
+    /// they do outside the app state: the startup entry, language, log level and overlay restarts.
+    fn reset_settings(&mut self, reset: impl FnOnce(&mut AppState)) {
+        use crate::{log_info, log_warn};
+
+        // Both sides of the diff come from the one update, not from reads around it
+        let mut states = None;
+        self.state.update(|s| {
+            let before = s.clone();
+            reset(s);
+            states = Some((before, s.clone()));
+        });
+        let Some((before, after)) = states else {
+            return;
+        };
+
+        if after.run_at_startup != before.run_at_startup {
+            if let Err(e) = crate::startup::set_startup_enabled(after.run_at_startup) {
+                log_warn!("Failed to update the startup entry: {}", e);
+            }
+        }
+        if after.language != before.language {
+            chromabridge::i18n::set_language(after.language);
+        }
+        if after.log_level != before.log_level {
+            chromabridge::logger::set_max_level(after.log_level);
+        }
+        self.selected_monitor = after.last_monitor.unwrap_or(0).min(self.monitors.len().saturating_sub(1));
+        self.settings = Rc::new(self.build_settings());
+
+        let needs_restart = after.last_monitor != before.last_monitor
+            || after.spectrum_name != before.spectrum_name
+            || after.noise_texture != before.noise_texture
+            || after.cap_to_monitor_refresh != before.cap_to_monitor_refresh
+            || after.debug_overlay != before.debug_overlay
+            || after.overlay_source != before.overlay_source
+            || after.render_thread_priority != before.render_thread_priority;
+        if after.spectrum_name.is_none() && self.overlay_manager.is_running() {
+            // Nothing left to correct with
+            self.toggle_overlay();
+        } else if needs_restart {
+            self.restart_overlay_if_needed();
+        }
+
+        log_info!("Settings reset");
+        self.status_message = Some(tr!("settings.reset_done"));
+    }
+
+    fn show_reset_all_confirmation(&mut self, ctx: &egui::Context) {
+        if !self.confirm_reset_all {
+            return;
+        }
+
+        let mut open = true;
+        let mut confirmed = false;
+        let mut cancelled = false;
+        egui::Window::new(tr!("settings.reset_all"))
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                ui.label(tr!("settings.reset_all_confirm"));
+                ui.add_space(10.0);
+                ui.horizontal(|ui| {
+                    confirmed = ui.button(tr!("settings.reset_all")).clicked();
+                    cancelled = ui.button(tr!("common.cancel")).clicked();
+                });
+            });
+
+        if confirmed {
+            self.reset_settings(|s| *s = AppState::default());
+        }
+        if confirmed || cancelled || !open {
+            self.confirm_reset_all = false;
+        }
+    }
+
+    fn show_delete_confirmation(&mut self, ctx: &egui::Context) {
+        let Some((kind, name)) = self.pending_delete.clone() else {
+            return;
+        };
+
+        let mut open = true;
+        let mut confirmed = false;
+        let mut cancelled = false;
+        egui::Window::new(tr!("trash.delete_title"))
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                ui.label(match kind {
+                    AssetKind::Spectrum => tr!("trash.delete_spectrum_confirm", name = name),
+                    AssetKind::Noise => tr!("trash.delete_noise_confirm", name = name),
+                });
+                ui.add_space(10.0);
+                ui.horizontal(|ui| {
+                    confirmed = ui.button(tr!("trash.delete")).clicked();
+                    cancelled = ui.button(tr!("common.cancel")).clicked();
+                });
+            });
 
-                    if overlay_running {
-                        if let Some((fps, frame_time_ms)) = self.overlay_manager.get_frame_stats() {
-                            ui.add_space(10.0);
-                            ui.label(format!("{:.1} FPS | {:.2}ms", fps, frame_time_ms));
+        if confirmed {
+            self.delete_asset(kind, &name);
+        }
+        if confirmed || cancelled || !open {
+            self.pending_delete = None;
+        }
+    }
+
+    /// Moves the asset to the trash. Deleting the active spectrum stops the overlay, which can't
+    /// run without one; deleting the active noise texture restarts it without.
+    fn delete_asset(&mut self, kind: AssetKind, name: &str) {
+        use crate::{log_info, log_warn};
+
+        if let Err(e) = chromabridge::trash::move_to_trash(&self.state.assets_dir(), kind, name, chrono::Local::now()) {
+            log_warn!("Failed to delete '{}': {:#}", name, e);
+            self.status_message = Some(tr!("trash.delete_failed", error = format!("{:#}", e)));
+            return;
+        }
+        log_info!("Moved {:?} '{}' to the trash", kind, name);
+        self.status_message = Some(tr!("trash.deleted", name = name));
+
+        match kind {
+            AssetKind::Spectrum if self.state.read(|s| s.spectrum_name.as_deref() == Some(name)) => {
+                self.state.update(|s| s.spectrum_name = None);
+                if self.overlay_manager.is_running() {
+                    self.toggle_overlay();
+                    self.status_message = Some(tr!("trash.deleted_active_spectrum", name = name));
+                }
+            }
+            AssetKind::Noise => {
+                // Spectra remembering it would otherwise bring it back, failing to load
+                let active = self.state.read(|s| s.noise_texture.as_deref() == Some(name));
+                self.state.update(|s| {
+                    if s.noise_texture.as_deref() == Some(name) {
+                        s.noise_texture = None;
+                    }
+                    s.noise_settings.remove(name);
+                    for settings in s.spectrum_settings.values_mut() {
+                        if settings.noise_texture.as_deref() == Some(name) {
+                            settings.noise_texture = None;
                         }
                     }
                 });
+                if active {
+                    self.restart_overlay_if_needed();
+                }
+            }
+            AssetKind::Spectrum => {}
+        }
+        self.refresh_assets();
+    }
 
-                ui.add_space(20.0);
-                ui.separator();
-                ui.add_space(15.0);
+    fn restore_asset(&mut self, item: &TrashedAsset) {
+        use crate::{log_info, log_warn};
 
-                egui::Grid::new("correction_grid")
-                    .num_columns(2)
-                    .spacing([20.0, 10.0])
-                    .show(ui, |ui| {
-                        if self.monitors.len() > 1 {
-                            ui.label("Monitor:");
-                            let mut monitor_changed = false;
-                            egui::ComboBox::from_id_salt("monitor_select")
-                                .selected_text(format!("{} ({}x{})",
-                                    self.monitors[self.selected_monitor].name,
-                                    self.monitors[self.selected_monitor].width,
-                                    self.monitors[self.selected_monitor].height))
-                                .show_ui(ui, |ui| {
-                                    for (idx, monitor) in self.monitors.iter().enumerate() {
-                                        let label = format!("{} ({}x{} @ {}Hz){}",
-                                            monitor.name, monitor.width, monitor.height,
-                                            monitor.refresh_rate,
-                                            if monitor.is_primary { " [Primary]" } else { "" });
-
-                                        if ui.selectable_value(&mut self.selected_monitor, idx, label).clicked() {
-                                            monitor_changed = true;
-                                        }
-                                    }
-                                });
-                            if monitor_changed {
-                                self.state.update(|s| {
-                                    s.last_monitor = Some(self.selected_monitor);
-                                });
-                                self.restart_overlay_if_needed();
-                            }
-                            ui.end_row();
+        match chromabridge::trash::restore(&self.state.assets_dir(), item) {
+            Ok(name) => {
+                log_info!("Restored {:?} '{}' from the trash as '{}'", item.kind, item.name, name);
+                self.refresh_assets();
+                self.status_message = Some(tr!("trash.restored", name = name));
+            }
+            Err(e) => {
+                log_warn!("Failed to restore '{}': {:#}", item.name, e);
+                self.trash_items = None;
+                self.status_message = Some(tr!("trash.restore_failed", error = format!("{:#}", e)));
+            }
+        }
+    }
+
+    fn show_recently_deleted(&mut self, ui: &mut egui::Ui, label: &str) {
+        use crate::log_warn;
+
+        ui.label(label);
+        let items = self
+            .trash_items
+            .get_or_insert_with(|| chromabridge::trash::list(&self.state.assets_dir()))
+            .clone();
+        let now = chrono::Local::now();
+
+        if items.is_empty() {
+            ui.weak(tr!("trash.empty"));
+            return;
+        }
+
+        let mut restore = None;
+        egui::ScrollArea::vertical().id_salt("recently_deleted").max_height(150.0).show(ui, |ui| {
+            egui::Grid::new("recently_deleted_grid").num_columns(3).spacing([12.0, 4.0]).striped(true).show(ui, |ui| {
+                for (idx, item) in items.iter().enumerate() {
+                    ui.label(match item.kind {
+                        AssetKind::Spectrum => tr!("trash.spectrum", name = item.name),
+                        AssetKind::Noise => tr!("trash.noise", name = item.name),
+                    });
+                    ui.label(item.deleted_at.format("%Y-%m-%d %H:%M").to_string());
+                    if item.restorable(now) {
+                        if ui.small_button(tr!("trash.restore")).clicked() {
+                            restore = Some(idx);
                         }
+                    } else {
+                        ui.weak(tr!("trash.expired"));
+                    }
+                    ui.end_row();
+                }
+            });
+        });
 
-                        ui.label("Color Blind Type:");
-                        let spectrum_text = self.selected_spectrum
-                            .map(|i| self.spectrum_files.get(i).map(|s| Self::truncate_with_ellipsis(s, 30)).unwrap_or_else(|| "Invalid".to_string()))
-                            .unwrap_or_else(|| "None".to_string());
-                        let mut spectrum_changed = None;
-                        egui::ComboBox::from_id_salt("spectrum_select")
-                            .selected_text(spectrum_text)
-                            .show_ui(ui, |ui| {
-                                for (idx, spectrum) in self.spectrum_files.iter().enumerate() {
-                                    if ui.selectable_label(self.selected_spectrum == Some(idx), spectrum).clicked() {
-                                        self.selected_spectrum = Some(idx);
-                                        spectrum_changed = Some(spectrum.clone());
-                                    }
-                                }
-                            });
-                        if let Some(spectrum) = spectrum_changed {
-                            self.state.update(|s| s.spectrum_name = Some(spectrum));
-                            self.restart_overlay_if_needed();
+        if self.confirm_purge {
+            ui.horizontal(|ui| {
+                ui.label(tr!("trash.purge_confirm"));
+                if ui.button(tr!("trash.purge")).clicked() {
+                    if let Err(e) = chromabridge::trash::purge_all(&self.state.assets_dir()) {
+                        log_warn!("{:#}", e);
+                        self.status_message = Some(format!("{:#}", e));
+                    }
+                    self.trash_items = None;
+                    self.confirm_purge = false;
+                }
+                if ui.button(tr!("common.cancel")).clicked() {
+                    self.confirm_purge = false;
+                }
+            });
+        } else if ui.button(tr!("trash.purge")).clicked() {
+            self.confirm_purge = true;
+        }
+        if let Some(idx) = restore {
+            self.restore_asset(&items[idx]);
+        }
+    }
+
+    fn show_self_check(&mut self, ui: &mut egui::Ui, label: &str) {
+        use crate::self_check;
+        use crossbeam_channel::TryRecvError;
+
+        if let Some(pending) = self.self_check_pending.as_ref() {
+            match pending.try_recv() {
+                Ok(results) => {
+                    self.self_check = Some(results);
+                    self.self_check_pending = None;
+                }
+                Err(TryRecvError::Disconnected) => self.self_check_pending = None,
+                Err(TryRecvError::Empty) => ui.ctx().request_repaint_after(std::time::Duration::from_millis(100)),
+            }
+        }
+
+        let running = self.self_check_pending.is_some();
+        ui.horizontal(|ui| {
+            ui.label(label);
+            if ui.add_enabled(!running, egui::Button::new(tr!("self_check.run")))
+                .on_hover_text(tr!("self_check.hint"))
+                .clicked()
+            {
+                let (tx, rx) = crossbeam_channel::bounded(1);
+                let dirs = self.state.data_dirs().clone();
+                std::thread::spawn(move || {
+                    let _ = tx.send(self_check::run(&dirs));
+                });
+                self.self_check_pending = Some(rx);
+            }
+            if running {
+                ui.spinner();
+            }
+        });
+
+        let Some(results) = self.self_check.as_ref() else {
+            return;
+        };
+        egui::ScrollArea::vertical().id_salt("self_check").max_height(200.0).show(ui, |ui| {
+            egui::Grid::new("self_check_grid").num_columns(3).spacing([12.0, 4.0]).striped(true).show(ui, |ui| {
+                for result in results {
+                    match result.outcome {
+                        Ok(ref detail) => {
+                            ui.colored_label(theme::palette(ui).success, "✔");
+                            ui.label(&result.name);
+                            ui.weak(detail);
                         }
-                        ui.end_row();
+                        Err(ref error) => {
+                            ui.colored_label(theme::palette(ui).error, "✖");
+                            ui.label(&result.name);
+                            ui.label(error);
+                        }
+                    }
+                    ui.end_row();
+                }
+            });
+        });
 
-                        ui.label("Interlace Pattern:");
-                        let noise_text = self.selected_noise
-                            .map(|i| self.noise_files.get(i).map(|n| Self::truncate_with_ellipsis(n, 30)).unwrap_or_else(|| "Invalid".to_string()))
-                            .unwrap_or_else(|| "None".to_string());
-                        let mut noise_changed: Option<Option<String>> = None;
-                        egui::ComboBox::from_id_salt("noise_select")
-                            .selected_text(noise_text)
-                            .show_ui(ui, |ui| {
-                                if ui.selectable_label(self.selected_noise.is_none(), "None").clicked() {
-                                    self.selected_noise = None;
-                                    noise_changed = Some(None);
-                                }
+        let passed = results.iter().filter(|result| result.passed()).count();
+        ui.horizontal(|ui| {
+            ui.label(tr!("self_check.summary", passed = passed, total = results.len()));
+            if ui.button(tr!("self_check.copy")).clicked() {
+                ui.ctx().copy_text(self_check::report(results));
+                self.status_message = Some(tr!("self_check.copied"));
+            }
+        });
+    }
 
-                                for (idx, noise) in self.noise_files.iter().enumerate() {
-                                    if ui.selectable_label(self.selected_noise == Some(idx), noise).clicked() {
-                                        self.selected_noise = Some(idx);
-                                        noise_changed = Some(Some(noise.clone()));
-                                    }
-                                }
-                            });
-                        if let Some(noise) = noise_changed {
-                            self.state.update(|s| s.noise_texture = noise);
-                            self.restart_overlay_if_needed();
+    /// Coalesced by the overlay manager, so several changes in a row restart the overlay once.
+    fn restart_overlay_if_needed(&mut self) {
+        self.overlay_manager.request_restart();
+    }
+
+    /// Rebuilds the visuals when the theme, or the spectrum the accent follows, changed.
+    fn apply_theme(&mut self, ctx: &egui::Context) {
+        let settings = theme::ThemeSettings::read(&self.state);
+        if self.applied_theme.as_ref() == Some(&settings) {
+            return;
+        }
+
+        let correction = settings.accent.as_ref().and_then(|&(_, selection, blend_weight)| {
+            let pair = chromabridge::SpectrumPair::from_file(self.selected_spectrum_file()?.clone(), selection);
+            chromabridge::preview::CpuCorrection::new(&pair, blend_weight, None, None).ok()
+        });
+        theme::apply(ctx, settings.theme, correction.as_ref());
+        self.applied_theme = Some(settings);
+    }
+
+    /// Catches up with updates made outside this window (tray, control server, vision setup, palette),
+    /// so the cached selections never show stale values.
+    fn apply_state_changes(&mut self) {
+        let mut changes = StateChanges::default();
+        while let Ok(more) = self.state_changes.try_recv() {
+            changes |= more;
+        }
+
+        if changes.language {
+            self.actions = Rc::new(self.build_actions());
+            self.settings = Rc::new(self.build_settings());
+        }
+        if changes.spectrum || changes.strength {
+            self.resolve_selections();
+            self.strength = self.state.read(|s| s.strength);
+        }
+    }
+}
+
+impl eframe::App for SettingsGui {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        use crate::{log_debug, log_info};
+
+        ctx.style_mut(|style| {
+            style.interaction.selectable_labels = false;
+        });
+
+        // Alt+F4 and the taskbar's close ask for a close; only the app exit really closes the window
+        if ctx.input(|i| i.viewport().close_requested()) && self.window.is_visible() {
+            ctx.send_viewport_cmd(egui::ViewportCommand::CancelClose);
+            self.close_window();
+        }
+
+        if self.window.take_reshown() {
+            self.on_reshown();
+        }
+        if self.asset_watcher.take_changed() {
+            self.on_assets_changed();
+        }
+
+        self.apply_state_changes();
+        self.apply_theme(ctx);
+
+        if ctx.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, egui::Key::K)) {
+            self.command_palette = match self.command_palette {
+                Some(_) => None,
+                None => Some(CommandPalette::default()),
+            };
+        }
+
+        if let Some(status) = self.overlay_manager.take_spectrum_status() {
+            self.status_message = Some(status);
+        }
+        if let Some(result) = self.overlay_manager.take_frame_dump_result() {
+            self.awaiting_frame_dump = false;
+            self.status_message = Some(match result {
+                Ok(folder) => tr!("developer.frame_dump_saved", path = folder.display()),
+                Err(error) => tr!("developer.frame_dump_failed", error = error),
+            });
+        }
+        if self.awaiting_frame_dump {
+            ctx.request_repaint_after(std::time::Duration::from_millis(250));
+        }
+        if self.overlay_manager.is_running() && self.state.read(|s| s.watch_spectrum_file) {
+            // Pick up hot-reload results even while the window is idle
+            ctx.request_repaint_after(std::time::Duration::from_secs(1));
+        }
+        if self.overlay_manager.is_running() {
+            // Keeps the uptime and today's active time current while the window is idle
+            ctx.request_repaint_after(std::time::Duration::from_secs(30));
+        }
+
+        if self.first_frame {
+            ctx.send_viewport_cmd(egui::ViewportCommand::Focus);
+
+            if let Ok(icon_path) = std::env::current_exe() {
+                if let Some(parent) = icon_path.parent() {
+                    let icon_file = parent.join("icon.ico");
+                    if icon_file.exists() {
+                        if let Ok(img) = image::open(&icon_file) {
+                            let rgba = img.to_rgba8();
+                            let size = [rgba.width() as usize, rgba.height() as usize];
+                            let pixels = rgba.as_flat_samples();
+                            let color_image = egui::ColorImage::from_rgba_unmultiplied(size, pixels.as_slice());
+                            self.icon_texture = Some(ctx.load_texture("app_icon", color_image, Default::default()));
                         }
-                        ui.end_row();
+                    }
+                }
+            }
+
+            self.first_frame = false;
+        }
 
-                        ui.label("Correction Strength:");
-                        if ui.add(egui::Slider::new(&mut self.strength, 0.0..=1.0).text("")).changed() {
-                            self.state.update(|s| s.strength = self.strength);
-                            // Update strength in running overlay without restart
-                            self.overlay_manager.update_strength(self.strength);
+        let title_bar_height = 32.0;
+        let close_button_size = egui::vec2(46.0, title_bar_height);
+
+        egui::TopBottomPanel::top("title_bar").exact_height(title_bar_height).show(ctx, |ui| {
+            ui.horizontal_centered(|ui| {
+                ui.add_space(8.0);
+
+                if let Some(ref texture) = self.icon_texture {
+                    let icon_size = 20.0;
+                    let icon_response = ui.add(
+                        egui::Image::new(texture)
+                            .max_size(egui::vec2(icon_size, icon_size))
+                            .sense(egui::Sense::click())
+                    );
+
+                    if icon_response.clicked() {
+                        let now = Instant::now();
+                        self.icon_click_times.retain(|&time| now.duration_since(time).as_secs_f32() < 1.0);
+                        self.icon_click_times.push(now);
+
+                        if self.icon_click_times.len() >= 5 {
+                            log_info!("Developer mode toggled via 5 rapid clicks");
+                            self.show_developer = !self.show_developer;
+                            self.icon_click_times.clear();
+                            if !self.show_developer {
+                                // Nobody should be left with a tinted screen they can't switch back
+                                self.overlay_manager.set_debug_visibility(crate::overlay::DebugVisibility::Normal);
+                            }
                         }
-                        ui.end_row();
-                    });
+                    }
 
-                ui.add_space(20.0);
-                ui.separator();
+                    ui.add_space(8.0);
+                }
+
+                let title_response = ui.interact(
+                    egui::Rect::from_min_size(ui.cursor().min, egui::vec2(ui.available_width() - close_button_size.x, title_bar_height)),
+                    ui.id().with("title_bar_drag"),
+                    egui::Sense::click_and_drag(),
+                );
+
+                let primary_down = ctx.input(|i| i.pointer.primary_down());
+                if title_response.is_pointer_button_down_on() && primary_down {
+                    if !self.dragging {
+                        log_debug!("Title bar drag started");
+                        self.dragging = true;
+                    }
+                    ctx.send_viewport_cmd(egui::ViewportCommand::StartDrag);
+                } else if self.dragging {
+                    self.dragging = false;
+                }
+
+                ui.label(
+                    egui::RichText::new(tr!("window.title"))
+                        .size(14.0)
+                        .strong()
+                        .color(theme::palette(ui).strong_text)
+                );
+
+                ui.add_space(6.0);
+                let version = env!("CARGO_PKG_VERSION").strip_prefix("0.").unwrap_or(env!("CARGO_PKG_VERSION"));
+                ui.label(egui::RichText::new(version).size(10.0).color(theme::palette(ui).weak_text));
+
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    let close_response = ui.add_sized(
+                        close_button_size,
+                        egui::Button::new(egui::RichText::new("X").size(16.0))
+                            .frame(false)
+                    );
+                    if close_response.clicked() {
+                        log_info!("Close button clicked");
+                        self.close_window();
+                    }
+                });
+            });
+        });
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            egui::ScrollArea::vertical().show(ui, |ui| {
                 ui.add_space(10.0);
 
-                let header_response = egui::CollapsingHeader::new("Advanced Settings")
-                    .default_open(self.show_advanced)
-                    .show(ui, |ui| {
-                        ui.add_space(10.0);
+                self.show_crash_banner(ui);
+                self.show_reset_banner(ui);
+                self.show_update_banner(ui);
 
-                        ui.label("Asset Management:");
-                        ui.horizontal(|ui| {
-                            if ui.button("Open Asset Folder").clicked() {
-                                self.open_asset_folder();
-                            }
+                ui.horizontal(|ui| {
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.settings_query)
+                            .hint_text(tr!("settings.search_hint"))
+                            .desired_width(260.0)
+                    );
+                    if !self.settings_query.is_empty() && ui.small_button("✕").clicked() {
+                        self.settings_query.clear();
+                    }
+                });
+                ui.add_space(10.0);
 
-                            if ui.button("↻").clicked() {
-                                self.refresh_assets();
-                            }
-                        });
+                let overlay_status = self.overlay_manager.state();
+                if matches!(overlay_status, OverlayStatus::Starting | OverlayStatus::Restarting) {
+                    // Nothing in the app state changes once the overlay thread is up
+                    ctx.request_repaint_after(std::time::Duration::from_millis(100));
+                }
 
-                        ui.add_space(15.0);
+                ui.horizontal(|ui| {
+                    let button_text = match overlay_status {
+                        OverlayStatus::Starting => tr!("overlay.starting"),
+                        OverlayStatus::Restarting => tr!("overlay.restarting"),
+                        OverlayStatus::Running => tr!("overlay.stop"),
+                        OverlayStatus::Stopped | OverlayStatus::Failed | OverlayStatus::WaitingForDisplay => tr!("overlay.start"),
+                    };
+                    let button = egui::Button::new(button_text).min_size(egui::vec2(120.0, 30.0));
+                    // Stopping joins the overlay thread, which would block on the asset loading
+                    if ui.add_enabled(!matches!(overlay_status, OverlayStatus::Starting | OverlayStatus::Restarting), button).clicked() {
+                        self.toggle_overlay();
+                    }
+                    let restart = egui::Button::new("🔄").min_size(egui::vec2(30.0, 30.0));
+                    if ui.add_enabled(overlay_status == OverlayStatus::Running, restart).on_hover_text(tr!("overlay.restart_hint")).clicked() {
+                        self.overlay_manager.restart("settings window");
+                    }
 
-                        ui.label("System Options:");
-                        let mut run_at_startup = self.state.read(|s| s.run_at_startup);
-                        if ui.checkbox(&mut run_at_startup, "Run at Windows startup").changed() {
-                            if let Ok(exe_path) = std::env::current_exe() {
-                                if set_startup_registry(run_at_startup, &exe_path).is_ok() {
-                                    self.state.update(|s| s.run_at_startup = run_at_startup);
-                                }
+                    if overlay_status == OverlayStatus::Running {
+                        if self.overlay_manager.is_occluded() {
+                            ui.add_space(10.0);
+                            ui.weak(tr!("overlay.occluded")).on_hover_text(tr!("overlay.occluded_hint"));
+                        } else if let Some(stats) = self.overlay_manager.get_frame_stats() {
+                            ui.add_space(10.0);
+                            let mut text = format!("{:.1} FPS | {:.2}ms", stats.fps, stats.frame_time_ms);
+                            if let Some(latency_ms) = stats.latency_ms {
+                                text += &format!(" | {}", tr!("overlay.latency", ms = format!("{:.1}", latency_ms)));
                             }
+                            if let Some(dropped) = stats.dropped_presents_per_second {
+                                text += &format!(" | {}", tr!("overlay.dropped", count = format!("{:.0}", dropped)));
+                            }
+                            ui.label(text);
                         }
-
-                        let mut open_gui_on_launch = self.state.read(|s| s.open_gui_on_launch);
-                        if ui.checkbox(&mut open_gui_on_launch, "Open settings on launch").changed() {
-                            self.state.update(|s| s.open_gui_on_launch = open_gui_on_launch);
+                        if let Some(uptime) = self.overlay_manager.uptime() {
+                            ui.add_space(10.0);
+                            ui.label(tr!("active_time.uptime", time = crate::format_active_time(uptime)));
                         }
+                    }
+                });
 
-                        let mut keep_running_in_tray = self.state.read(|s| s.keep_running_in_tray);
-                        if ui.checkbox(&mut keep_running_in_tray, "Keep running in Tray").changed() {
-                            self.state.update(|s| s.keep_running_in_tray = keep_running_in_tray);
-                        }
+                if overlay_status == OverlayStatus::Running && self.overlay_manager.get_diagnostics().capture_conflict {
+                    ui.add_space(5.0);
+                    ui.colored_label(theme::palette(ui).warning, tr!("overlay.capture_conflict"));
+                    // The overlay retries on its own; keep polling so the warning clears when it does
+                    ctx.request_repaint_after(std::time::Duration::from_secs(1));
+                }
 
-                        ui.add_space(10.0);
-                    });
+                if overlay_status == OverlayStatus::Running && !self.state.read(|s| s.hide_from_capture) {
+                    ui.add_space(5.0);
+                    ui.colored_label(theme::palette(ui).warning, tr!("overlay.capture_feedback"))
+                        .on_hover_text(tr!("advanced.hide_from_capture_hint"));
+                }
 
-                let is_open = header_response.openness > 0.5;
-                if is_open != self.show_advanced {
-                    self.show_advanced = is_open;
-                    self.state.update(|s| s.show_advanced_settings = is_open);
+                if let Some(conflict) = self.overlay_manager.color_conflict() {
+                    ui.add_space(5.0);
+                    ui.horizontal(|ui| {
+                        ui.colored_label(theme::palette(ui).warning, conflict.message())
+                            .on_hover_text(tr!("conflict.hint"));
+                        if ui.small_button(tr!("conflict.dismiss")).clicked() {
+                            self.overlay_manager.dismiss_color_conflict();
+                        }
+                        if ui.small_button(tr!("conflict.stop_checking")).clicked() {
+                            self.state.update(|s| s.conflict_detection = false);
+                        }
+                    });
                 }
 
+                ui.add_space(20.0);
+                ui.separator();
                 ui.add_space(15.0);
 
-                // Developer Settings (unlocked by clicking app icon 5 times)
-                if self.show_developer {
+                let query = self.settings_query.clone();
+                let searching = !query.trim().is_empty();
+                // Rows may rebuild the registry (e.g. changing the monitor), so hold our own reference
+                let settings = Rc::clone(&self.settings);
+                let mut any_match = false;
+
+                if settings_search::matching(&settings, SettingsSection::Correction, &query).next().is_some() {
+                    any_match = true;
+                    egui::Grid::new("correction_grid")
+                        .num_columns(2)
+                        .spacing([20.0, 10.0])
+                        .show(ui, |ui| {
+                            for setting in settings_search::matching(&settings, SettingsSection::Correction, &query) {
+                                (setting.show)(self, ui, &setting.label);
+                            }
+                        });
+
+                    ui.add_space(20.0);
                     ui.separator();
                     ui.add_space(10.0);
+                }
 
-                    let _dev_header_response = egui::CollapsingHeader::new("Developer Settings")
-                        .default_open(true)
-                        .show(ui, |ui| {
-                            ui.add_space(10.0);
+                if settings_search::matching(&settings, SettingsSection::Advanced, &query).next().is_some() {
+                    any_match = true;
+                    // Results are listed open without touching the remembered collapsed state
+                    if searching {
+                        ui.strong(tr!("advanced.heading"));
+                        self.show_section(ui, &settings, SettingsSection::Advanced, &query);
+                    } else {
+                        let header_response = egui::CollapsingHeader::new(tr!("advanced.heading"))
+                            .id_salt("advanced_settings")
+                            .default_open(self.show_advanced)
+                            .show(ui, |ui| self.show_section(ui, &settings, SettingsSection::Advanced, &query));
+
+                        let is_open = header_response.openness > 0.5;
+                        if is_open != self.show_advanced {
+                            self.show_advanced = is_open;
+                            self.state.update(|s| s.show_advanced_settings = is_open);
+                        }
+                    }
 
-                            ui.label("Rendering Options:");
-                            let mut cap_to_monitor_refresh = self.state.read(|s| s.cap_to_monitor_refresh);
-                            let monitor_hz = if self.selected_monitor < self.monitors.len() {
-                                self.monitors[self.selected_monitor].refresh_rate
-                            } else {
-                                60
-                            };
-
-                            if ui.checkbox(&mut cap_to_monitor_refresh, format!("Cap to Monitor Refresh Rate ({}Hz)", monitor_hz)).changed() {
-                                self.state.update(|s| s.cap_to_monitor_refresh = cap_to_monitor_refresh);
-                                self.restart_overlay_if_needed();
-                            }
+                    ui.add_space(15.0);
+                }
 
-                            ui.add_space(10.0);
-                        });
+                // Developer Settings (unlocked by clicking app icon 5 times)
+                if self.show_developer && settings_search::matching(&settings, SettingsSection::Developer, &query).next().is_some() {
+                    any_match = true;
+                    ui.separator();
+                    ui.add_space(10.0);
+
+                    if searching {
+                        ui.strong(tr!("developer.heading"));
+                        self.show_section(ui, &settings, SettingsSection::Developer, &query);
+                    } else {
+                        egui::CollapsingHeader::new(tr!("developer.heading"))
+                            .id_salt("developer_settings")
+                            .default_open(true)
+                            .show(ui, |ui| self.show_section(ui, &settings, SettingsSection::Developer, &query));
+                    }
 
                     ui.add_space(15.0);
                 }
 
+                if !any_match {
+                    ui.weak(tr!("settings.no_matches"));
+                    ui.add_space(15.0);
+                }
+
                 if let Some(ref msg) = self.status_message {
                     ui.label(msg);
                 }
             });
         });
+
+        self.show_matrix_import_dialog(ctx);
+        self.show_share_import_dialog(ctx);
+        self.show_vision_setup(ctx);
+        self.show_screenshot_preview(ctx);
+        self.show_render_sweep(ctx);
+        self.show_output_preview(ctx);
+        self.show_reset_all_confirmation(ctx);
+        self.show_delete_confirmation(ctx);
+        self.show_command_palette(ctx);
     }
 }
 
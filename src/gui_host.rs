@@ -0,0 +1,232 @@
+use crate::gui::SettingsGui;
+use chromabridge::{log_debug, log_error, log_info, log_warn, tr};
+use parking_lot::Mutex;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicIsize, Ordering};
+use std::thread::JoinHandle;
+use windows::{
+    core::HSTRING,
+    Win32::Foundation::HWND,
+    Win32::UI::WindowsAndMessaging::{IsIconic, MessageBoxW, SetForegroundWindow, ShowWindow, MB_ICONWARNING, MB_OK, SW_HIDE, SW_RESTORE, SW_SHOW},
+};
+use winit::platform::windows::EventLoopBuilderExtWindows;
+use winit::raw_window_handle::{HasWindowHandle, RawWindowHandle};
+
+/// The settings window's side of `GuiHost`, shared with the window itself.
+pub struct GuiWindow {
+    ctx: Mutex<Option<egui::Context>>,
+    /// The window's HWND, 0 until it was created.
+    hwnd: AtomicIsize,
+    visible: AtomicBool,
+    /// Set when the hidden window is shown again, taken by its next frame.
+    reshown: AtomicBool,
+}
+
+impl GuiWindow {
+    fn new() -> Self {
+        Self {
+            ctx: Mutex::new(None),
+            hwnd: AtomicIsize::new(0),
+            visible: AtomicBool::new(true),
+            reshown: AtomicBool::new(false),
+        }
+    }
+
+    /// Called by the window once eframe created it.
+    pub fn attach(&self, cc: &eframe::CreationContext) {
+        *self.ctx.lock() = Some(cc.egui_ctx.clone());
+        match cc.window_handle().map(|handle| handle.as_raw()) {
+            Ok(RawWindowHandle::Win32(handle)) => self.hwnd.store(handle.hwnd.get(), Ordering::Release),
+            Ok(_) | Err(_) => log_warn!("Settings window has no Win32 handle, it can't be hidden and shown again"),
+        }
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible.load(Ordering::Acquire)
+    }
+
+    /// True once after the window was shown again, so it can reload what may have changed meanwhile.
+    pub fn take_reshown(&self) -> bool {
+        self.reshown.swap(false, Ordering::AcqRel)
+    }
+
+    pub fn request_repaint(&self) {
+        if let Some(ctx) = self.ctx.lock().as_ref() {
+            ctx.request_repaint();
+        }
+    }
+
+    /// Hides the window instead of closing it; the next `GuiHost::open` shows it again.
+    pub fn hide(&self) {
+        let Some(hwnd) = self.hwnd() else {
+            return;
+        };
+        if self.visible.swap(false, Ordering::AcqRel) {
+            log_info!("Hiding GUI window");
+            unsafe {
+                let _ = ShowWindow(hwnd, SW_HIDE);
+            }
+        }
+    }
+
+    /// Shows the window and brings it to the front. Works from any thread: a hidden window gets
+    /// no frames, so egui's own viewport commands would wait until something else shows it.
+    fn show(&self) {
+        let Some(hwnd) = self.hwnd() else {
+            // Still being created; it appears by itself
+            return;
+        };
+        if !self.visible.swap(true, Ordering::AcqRel) {
+            log_info!("Showing GUI window");
+            self.reshown.store(true, Ordering::Release);
+        } else {
+            log_debug!("GUI already open - bringing to front");
+        }
+        unsafe {
+            let _ = ShowWindow(hwnd, if IsIconic(hwnd).as_bool() { SW_RESTORE } else { SW_SHOW });
+            let _ = SetForegroundWindow(hwnd);
+        }
+        self.request_repaint();
+    }
+
+    fn hwnd(&self) -> Option<HWND> {
+        match self.hwnd.load(Ordering::Acquire) {
+            0 => None,
+            hwnd => Some(HWND(hwnd as *mut _)),
+        }
+    }
+}
+
+/// Hosts the settings window on a thread of its own for the rest of the app's life.
+///
+/// Creating an eframe window with its rendering context takes most of a second, so the window is
+/// only created on the first open; closing it hides it and later opens show it again. Its event
+/// loop runs on that thread, so the main thread keeps pumping the tray and processing commands
+/// while it is open.
+pub struct GuiHost {
+    window: Arc<GuiWindow>,
+    thread: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl GuiHost {
+    pub fn new() -> Self {
+        Self {
+            window: Arc::new(GuiWindow::new()),
+            thread: Mutex::new(None),
+        }
+    }
+
+    pub fn window(&self) -> &Arc<GuiWindow> {
+        &self.window
+    }
+
+    /// Shows the window, creating it with `create` on its first open.
+    pub fn open<F>(&self, create: F)
+    where
+        F: FnOnce(Arc<GuiWindow>) -> SettingsGui + Send + 'static,
+    {
+        let mut thread = self.thread.lock();
+        if thread.as_ref().is_some_and(|handle| handle.is_finished()) {
+            // winit allows one event loop per process, and it ended with that thread
+            log_error!("GUI thread has exited, the settings window can't be opened again");
+            // The message box pumps the tray meanwhile, and another click would find the lock held
+            drop(thread);
+            show_unavailable();
+            return;
+        }
+        match thread.as_ref() {
+            Some(_) => self.window.show(),
+            None => {
+                log_info!("Opening GUI window");
+                let window = Arc::clone(&self.window);
+                let spawned = std::thread::Builder::new().name("gui".into()).spawn(move || run(window, create));
+                match spawned {
+                    Ok(handle) => *thread = Some(handle),
+                    Err(e) => log_error!("Failed to start GUI thread: {}", e),
+                }
+            }
+        }
+    }
+
+    /// Closes the window for good on app exit.
+    pub fn close(&self) {
+        self.window.hide();
+        if let Some(ctx) = self.window.ctx.lock().as_ref() {
+            ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+            ctx.request_repaint();
+        }
+    }
+}
+
+fn run<F>(window: Arc<GuiWindow>, create: F)
+where
+    F: FnOnce(Arc<GuiWindow>) -> SettingsGui + Send + 'static,
+{
+    let options = eframe::NativeOptions {
+        viewport: egui::ViewportBuilder::default()
+            .with_inner_size([500.0, 600.0])
+            .with_resizable(false)
+            .with_decorations(false)
+            .with_icon(load_window_icon()),
+        run_and_return: true,
+        // winit insists on the main thread unless told otherwise
+        event_loop_builder: Some(Box::new(|builder| {
+            builder.with_any_thread(true);
+        })),
+        ..Default::default()
+    };
+
+    let result = eframe::run_native(
+        "ChromaBridge",
+        options,
+        Box::new(move |cc| {
+            window.attach(cc);
+            Ok(Box::new(create(window)))
+        }),
+    );
+    if let Err(e) = result {
+        log_warn!("GUI window error: {:?}", e);
+    }
+    log_info!("GUI window closed");
+}
+
+/// Tells the user the settings window is gone until a restart, rather than ignoring their click.
+fn show_unavailable() {
+    let text = HSTRING::from(tr!("window.unavailable"));
+    let caption = HSTRING::from(tr!("window.title"));
+    unsafe {
+        MessageBoxW(None, &text, &caption, MB_OK | MB_ICONWARNING);
+    }
+}
+
+fn load_window_icon() -> egui::IconData {
+    let icon_path = std::env::current_exe()
+        .ok()
+        .and_then(|p| p.parent().map(|p| p.to_path_buf()))
+        .map(|p| p.join("icon.ico"));
+
+    if let Some(path) = icon_path {
+        if path.exists() {
+            if let Ok(image) = image::open(&path) {
+                let rgba = image.to_rgba8();
+                let (width, height) = rgba.dimensions();
+                return egui::IconData {
+                    rgba: rgba.into_raw(),
+                    width,
+                    height,
+                };
+            }
+        }
+    }
+
+    let mut rgba = Vec::with_capacity(32 * 32 * 4);
+    for _ in 0..32 * 32 {
+        rgba.extend_from_slice(&[100, 150, 255, 255]);
+    }
+
+    egui::IconData {
+        rgba,
+        width: 32,
+        height: 32,
+    }
+}
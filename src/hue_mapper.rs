@@ -1,3 +1,36 @@
+/// Width in degrees over which the correction fades in at each edge of a hue gate.
+pub const HUE_GATE_FEATHER: f32 = 10.0;
+
+/// Restricts correction to hues from `start` to `end` in degrees, clockwise, so ranges such as
+/// 330-30 wrap through red. Equal bounds cover the whole circle.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HueGate {
+    pub start: f32,
+    pub end: f32,
+}
+
+impl HueGate {
+    /// How much of the correction applies to hue `h`: 1 inside the range, 0 outside, blended
+    /// across `HUE_GATE_FEATHER` centered on each edge. Matches `hue_gate_weight` in shaders.hlsl.
+    pub fn weight(&self, h: f32) -> f32 {
+        let width = (self.end - self.start).rem_euclid(360.0);
+        if width == 0.0 {
+            return 1.0;
+        }
+
+        let offset = (h - self.start).rem_euclid(360.0);
+        // Degrees inside the range from the nearest edge, negative outside it
+        let distance = if offset <= width {
+            offset.min(width - offset)
+        } else {
+            -(offset - width).min(360.0 - offset)
+        };
+
+        let t = (distance / HUE_GATE_FEATHER + 0.5).clamp(0.0, 1.0);
+        t * t * (3.0 - 2.0 * t)
+    }
+}
+
 pub struct HueMapper {
     pub strength: f32,
 }
@@ -70,4 +103,107 @@ impl HueMapper {
 
         (r, g, b)
     }
+
+    /// Snaps `h` to the nearest of `levels` evenly spaced hues, matching `posterize` in shaders.hlsl.
+    pub fn posterize_hue(h: f32, levels: u32) -> f32 {
+        if levels == 0 {
+            return h;
+        }
+        let step = 360.0 / levels as f32;
+        ((h / step).round() * step) % 360.0
+    }
+
+    pub fn srgb_to_linear(c: f32) -> f32 {
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    }
+
+    pub fn linear_to_srgb(c: f32) -> f32 {
+        let c = c.clamp(0.0, 1.0);
+        if c <= 0.0031308 {
+            c * 12.92
+        } else {
+            1.055 * c.powf(1.0 / 2.4) - 0.055
+        }
+    }
+
+    pub fn linear_to_oklab(r: f32, g: f32, b: f32) -> (f32, f32, f32) {
+        let l = 0.41222146 * r + 0.53633255 * g + 0.051445995 * b;
+        let m = 0.2119035 * r + 0.6806995 * g + 0.10739696 * b;
+        let s = 0.08830246 * r + 0.28171885 * g + 0.6299787 * b;
+
+        let l = l.cbrt();
+        let m = m.cbrt();
+        let s = s.cbrt();
+
+        (
+            0.21045426 * l + 0.7936178 * m - 0.004072047 * s,
+            1.9779985 * l - 2.4285922 * m + 0.4505937 * s,
+            0.025904037 * l + 0.78277177 * m - 0.80867577 * s,
+        )
+    }
+
+    pub fn oklab_to_linear(l: f32, a: f32, b: f32) -> (f32, f32, f32) {
+        let l_ = l + 0.39633778 * a + 0.21580376 * b;
+        let m_ = l - 0.105561346 * a - 0.06385417 * b;
+        let s_ = l - 0.08948418 * a - 1.2914855 * b;
+
+        let l = l_ * l_ * l_;
+        let m = m_ * m_ * m_;
+        let s = s_ * s_ * s_;
+
+        (
+            4.0767417 * l - 3.3077116 * m + 0.23096994 * s,
+            -1.268438 * l + 2.6097574 * m - 0.34131938 * s,
+            -0.0041960864 * l - 0.7034186 * m + 1.7076147 * s,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hue_gate_feathers_edges_and_wraps() {
+        let gate = HueGate { start: 60.0, end: 180.0 };
+        assert_eq!(gate.weight(120.0), 1.0);
+        assert_eq!(gate.weight(240.0), 0.0);
+        assert!((gate.weight(60.0) - 0.5).abs() < 1e-6);
+        assert!(gate.weight(58.0) > 0.0 && gate.weight(58.0) < gate.weight(62.0));
+
+        let wrapping = HueGate { start: 330.0, end: 30.0 };
+        assert_eq!(wrapping.weight(0.0), 1.0);
+        assert_eq!(wrapping.weight(350.0), 1.0);
+        assert_eq!(wrapping.weight(180.0), 0.0);
+
+        assert_eq!(HueGate { start: 90.0, end: 90.0 }.weight(270.0), 1.0);
+    }
+
+    #[test]
+    fn transfer_functions_round_trip() {
+        for value in 0..=255u8 {
+            let c = value as f32 / 255.0;
+            let linear = HueMapper::srgb_to_linear(c);
+            assert!((0.0..=1.0).contains(&linear));
+            assert!((HueMapper::linear_to_srgb(linear) - c).abs() < 1e-5, "{}", value);
+        }
+        assert!((HueMapper::srgb_to_linear(0.5) - 0.214).abs() < 1e-3);
+        assert!((HueMapper::linear_to_srgb(0.5) - 0.735).abs() < 1e-3);
+    }
+
+    #[test]
+    fn oklab_round_trips_and_white_is_neutral() {
+        let (l, a, b) = HueMapper::linear_to_oklab(1.0, 1.0, 1.0);
+        assert!((l - 1.0).abs() < 1e-4 && a.abs() < 1e-4 && b.abs() < 1e-4);
+
+        for rgb in [(1.0, 0.0, 0.0), (0.0, 1.0, 0.0), (0.0, 0.0, 1.0), (0.2, 0.5, 0.8), (0.0, 0.0, 0.0)] {
+            let (l, a, b) = HueMapper::linear_to_oklab(rgb.0, rgb.1, rgb.2);
+            let back = HueMapper::oklab_to_linear(l, a, b);
+            assert!((back.0 - rgb.0).abs() < 1e-4 && (back.1 - rgb.1).abs() < 1e-4 && (back.2 - rgb.2).abs() < 1e-4, "{:?} -> {:?}", rgb, back);
+        }
+    }
 }
@@ -0,0 +1,221 @@
+use once_cell::sync::Lazy;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Embedded string tables, English first. Other tables may be partial; missing keys fall back to English.
+const LOCALES: [(&str, &str); 5] = [
+    ("en", include_str!("../locales/en.json")),
+    ("de", include_str!("../locales/de.json")),
+    ("fr", include_str!("../locales/fr.json")),
+    ("es", include_str!("../locales/es.json")),
+    ("ja", include_str!("../locales/ja.json")),
+];
+
+const FALLBACK_LOCALE: &str = "en";
+
+/// User interface language setting.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Language {
+    /// Follows the Windows display language, English if it has no translation.
+    #[default]
+    Auto,
+    English,
+    German,
+    French,
+    Spanish,
+    Japanese,
+}
+
+impl Language {
+    pub const ALL: [Language; 6] = [
+        Language::Auto,
+        Language::English,
+        Language::German,
+        Language::French,
+        Language::Spanish,
+        Language::Japanese,
+    ];
+
+    /// Shown in the language's own name so it can be found without reading the current language.
+    pub fn label(&self) -> String {
+        match self {
+            Language::Auto => crate::tr!("language.auto"),
+            Language::English => "English".to_string(),
+            Language::German => "Deutsch".to_string(),
+            Language::French => "Français".to_string(),
+            Language::Spanish => "Español".to_string(),
+            Language::Japanese => "日本語".to_string(),
+        }
+    }
+
+    fn locale(&self) -> &'static str {
+        match self {
+            Language::Auto => system_locale(),
+            Language::English => "en",
+            Language::German => "de",
+            Language::French => "fr",
+            Language::Spanish => "es",
+            Language::Japanese => "ja",
+        }
+    }
+}
+
+type Table = HashMap<String, String>;
+
+static TABLES: Lazy<HashMap<&'static str, Table>> = Lazy::new(|| {
+    LOCALES
+        .iter()
+        .map(|(locale, json)| {
+            let table = serde_json::from_str(json).unwrap_or_else(|e| {
+                crate::log_error!("Invalid string table '{}': {}", locale, e);
+                Table::new()
+            });
+            (*locale, table)
+        })
+        .collect()
+});
+
+static CURRENT_LOCALE: RwLock<&'static str> = RwLock::new(FALLBACK_LOCALE);
+static CURRENT_LANGUAGE: RwLock<Language> = RwLock::new(Language::Auto);
+
+/// Switches all subsequent lookups to `language`.
+pub fn set_language(language: Language) {
+    let locale = language.locale();
+    *CURRENT_LOCALE.write() = locale;
+    *CURRENT_LANGUAGE.write() = language;
+    crate::log_info!("UI language: {} ({:?})", locale, language);
+}
+
+/// The locale lookups currently resolve against, e.g. "de".
+pub fn current_locale() -> &'static str {
+    *CURRENT_LOCALE.read()
+}
+
+/// The language setting last applied, `Language::Auto` included.
+pub fn current_language() -> Language {
+    *CURRENT_LANGUAGE.read()
+}
+
+/// Looks up `key` in the current language, then English. Prefer the `tr!` macro.
+pub fn tr(key: &str) -> String {
+    lookup(current_locale(), key)
+}
+
+/// Looks up `key` and replaces each `{name}` placeholder with its value.
+pub fn tr_args(key: &str, args: &[(&str, String)]) -> String {
+    let mut text = tr(key);
+    for (name, value) in args {
+        text = text.replace(&format!("{{{}}}", name), value);
+    }
+    text
+}
+
+fn lookup(locale: &str, key: &str) -> String {
+    let found = TABLES
+        .get(locale)
+        .and_then(|table| table.get(key))
+        .or_else(|| TABLES.get(FALLBACK_LOCALE).and_then(|table| table.get(key)));
+
+    match found {
+        Some(text) => text.clone(),
+        None => {
+            // English is complete (see the tests), so this is a typo in a key
+            crate::log_warn!("Missing UI string '{}'", key);
+            key.rsplit('.').next().unwrap_or(key).replace('_', " ")
+        }
+    }
+}
+
+#[cfg(windows)]
+fn system_locale() -> &'static str {
+    // Primary language identifiers from winnt.h (LANG_*)
+    const LANG_GERMAN: u16 = 0x07;
+    const LANG_SPANISH: u16 = 0x0a;
+    const LANG_FRENCH: u16 = 0x0c;
+    const LANG_JAPANESE: u16 = 0x11;
+
+    let langid = unsafe { windows::Win32::Globalization::GetUserDefaultUILanguage() };
+    match langid & 0x3ff {
+        LANG_GERMAN => "de",
+        LANG_SPANISH => "es",
+        LANG_FRENCH => "fr",
+        LANG_JAPANESE => "ja",
+        _ => FALLBACK_LOCALE,
+    }
+}
+
+#[cfg(not(windows))]
+fn system_locale() -> &'static str {
+    FALLBACK_LOCALE
+}
+
+/// Translated UI string: `tr!("tray.exit")`, or with placeholders `tr!("status.refreshed", spectra = 3, noise = 1)`.
+#[macro_export]
+macro_rules! tr {
+    ($key:literal) => {
+        $crate::i18n::tr($key)
+    };
+    ($key:literal, $($name:ident = $value:expr),+ $(,)?) => {
+        $crate::i18n::tr_args($key, &[$((stringify!($name), $value.to_string())),+])
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn placeholders(text: &str) -> Vec<&str> {
+        let mut names: Vec<&str> = text
+            .split('{')
+            .skip(1)
+            .filter_map(|rest| rest.split_once('}').map(|(name, _)| name))
+            .collect();
+        names.sort_unstable();
+        names
+    }
+
+    #[test]
+    fn translations_only_use_english_keys_and_placeholders() {
+        let english = &TABLES[FALLBACK_LOCALE];
+        assert!(!english.is_empty());
+
+        for (locale, _) in LOCALES {
+            for (key, text) in &TABLES[locale] {
+                let source = english.get(key).unwrap_or_else(|| panic!("{}: unknown key '{}'", locale, key));
+                assert_eq!(placeholders(text), placeholders(source), "{}: placeholders of '{}'", locale, key);
+            }
+        }
+    }
+
+    #[test]
+    fn every_key_used_in_the_sources_exists_in_english() {
+        let english = &TABLES[FALLBACK_LOCALE];
+        let src = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("src");
+
+        for entry in std::fs::read_dir(src).unwrap().flatten() {
+            let source = std::fs::read_to_string(entry.path()).unwrap_or_default();
+            for (start, pattern) in source.match_indices("tr!(\"") {
+                // Skip other macros ending in "tr", such as include_str!
+                if source[..start].ends_with(|c: char| c.is_alphanumeric() || c == '_') {
+                    continue;
+                }
+                let key = source[start + pattern.len()..].split('"').next().unwrap();
+                assert!(english.contains_key(key), "{}: '{}' missing from en.json", entry.path().display(), key);
+            }
+        }
+    }
+
+    #[test]
+    fn missing_strings_fall_back_to_english_then_the_key() {
+        assert_eq!(lookup("xx", "tray.exit"), "Exit");
+        assert_eq!(lookup("en", "no.such_key"), "such key");
+    }
+
+    #[test]
+    fn placeholders_are_replaced() {
+        let text = tr_args("status.refreshed", &[("spectra", "3".into()), ("noise", "1".into())]);
+        assert_eq!(text, "Refreshed: 3 spectrums, 1 noise textures");
+    }
+}
@@ -1,11 +1,65 @@
+//! Color correction core of ChromaBridge: spectrum files, hue mapping, noise textures and settings.
+//!
+//! The types re-exported at the crate root are the stable API for third-party tools that write
+//! or check spectrum files. Versions are `0.YEAR.COMMIT_COUNT`, so by Cargo's semver rules they
+//! only break within a year change: until then existing functions and fields keep their meaning
+//! and saved files stay loadable. Everything reached only through the modules (e.g.
+//! `chromabridge::state`) is the app's own plumbing and may change in any release.
+//!
+//! ```
+//! use chromabridge::{Interpolation, Spectrum, SpectrumFile, SpectrumNode};
+//!
+//! let deuteranopia = Spectrum::builder()
+//!     .name("Deuteranopia")
+//!     .node(0.0, "#FF0000")
+//!     .push(SpectrumNode::new(0.5, "#0000FF").with_value(0.8))
+//!     .node(1.0, "#FF0000")
+//!     .build()?;
+//! let file = SpectrumFile::new(vec![deuteranopia], Interpolation::Oklab)?;
+//!
+//! let json = file.to_json()?;
+//! assert!(json.contains("\"Deuteranopia\""));
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
 pub mod logger;
+pub mod crash;
+pub mod error;
+pub mod atomic_file;
+pub mod cursor;
+pub mod i18n;
+pub mod number_format;
 pub mod spectrum;
 pub mod hue_mapper;
 pub mod noise;
 pub mod state;
+pub mod data_dirs;
+pub mod matrix_import;
+pub mod migration;
+pub mod session_history;
+pub mod startup_timer;
+pub mod lifecycle;
+pub mod shader_params;
+pub mod share_code;
+pub mod trash;
+pub mod update_check;
+pub mod test_pattern;
+pub mod vision_test;
+pub mod preview;
+pub mod monitor;
+pub mod sweep;
+#[cfg(test)]
+mod test_util;
 
 pub use logger::*;
-pub use spectrum::{Spectrum, SpectrumPair};
-pub use hue_mapper::HueMapper;
-pub use noise::NoiseTexture;
-pub use state::StateManager;
+pub use error::{ConfigError, NoiseError, SpectrumError};
+pub use spectrum::{Deficiency, Interpolation, Spectrum, Spectrum2Mode, SpectrumBuilder, SpectrumFile, SpectrumMetadata, SpectrumNode, SpectrumPair, SpectrumSelection};
+pub use hue_mapper::{HueGate, HueMapper};
+pub use i18n::Language;
+pub use data_dirs::{DataDirLayout, DataDirs};
+pub use noise::{NoiseHeader, NoiseKind, NoiseTexture, MAX_NOISE_INDICES};
+pub use state::{BATTERY_SAVER_FPS, ColorMath, ConflictBehavior, FullscreenBehavior, GuiTheme, NoiseMapping, NoiseSettings, NoiseTarget, OverlaySource, RenderThreadPriority, SpectrumSettings, StateChanges, StateManager, StrengthCurve, TargetWindow, TopmostMode};
+pub use session_history::{ActiveTime, SessionRecorder, SessionSummary};
+pub use test_pattern::TestPattern;
+pub use matrix_import::MatrixImport;
+pub use sweep::{SweepJob, SweepReport, SWEEP_STRENGTHS};
@@ -0,0 +1,280 @@
+//! Starting and stopping a worker thread, such as the overlay's, from several threads at once.
+
+use parking_lot::{Condvar, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+
+/// Where the worker is between being asked to start and having stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    Stopped,
+    /// The thread is spawned and setting up; it calls `set_running` once it is.
+    Starting,
+    Running,
+    /// A stop is waiting for the thread to end.
+    Stopping,
+}
+
+impl Phase {
+    /// Starting or running: the worker is on, or about to be.
+    pub fn is_on(self) -> bool {
+        matches!(self, Phase::Starting | Phase::Running)
+    }
+}
+
+/// What one start, stop or toggle did.
+pub struct Outcome<T> {
+    /// Whether the caller asked for the worker to run; for a toggle, decided against the
+    /// transition in flight when it was called.
+    pub run: bool,
+    pub started: bool,
+    pub stopped: bool,
+    /// What a thread joined by this call returned, `Err` if it panicked: the one it stopped, or
+    /// one that had ended by itself.
+    pub joined: Option<thread::Result<T>>,
+}
+
+impl<T> Outcome<T> {
+    fn unchanged(run: bool) -> Self {
+        Self { run, started: false, stopped: false, joined: None }
+    }
+}
+
+struct State<T> {
+    phase: Phase,
+    /// The worker's thread, kept after it ended by itself until a start or stop joins it.
+    thread: Option<JoinHandle<T>>,
+    /// Counts calls, so a call that had to wait for a stop can tell a newer one came in meanwhile.
+    latest: u64,
+    /// What the latest call asked for.
+    wanted: bool,
+}
+
+/// Serializes starting, stopping and toggling one worker thread.
+///
+/// Each call settles what it wants under a single lock, against the phase it finds: a toggle
+/// while the worker is still starting means stop, and the worker is told to end as soon as it
+/// checks `keep_running`. Calls that find a stop in progress wait for it; of those, only the
+/// newest acts, so the worker ends up the way the last caller asked and never runs twice.
+pub struct Lifecycle<T> {
+    state: Mutex<State<T>>,
+    stopped: Condvar,
+}
+
+impl<T> Default for Lifecycle<T> {
+    fn default() -> Self {
+        Self {
+            state: Mutex::new(State { phase: Phase::Stopped, thread: None, latest: 0, wanted: false }),
+            stopped: Condvar::new(),
+        }
+    }
+}
+
+impl<T> Lifecycle<T> {
+    pub fn phase(&self) -> Phase {
+        self.state.lock().phase
+    }
+
+    /// What the latest start, stop or toggle asked for.
+    pub fn wanted(&self) -> bool {
+        self.state.lock().wanted
+    }
+
+    /// Spawns the worker with `spawn` unless it is on already. `spawn` runs under the lock, so it
+    /// must not call this lifecycle itself; the thread it spawns may. Returning `None` leaves the
+    /// worker stopped, e.g. when there is nothing to run.
+    pub fn start(&self, spawn: impl FnOnce() -> Option<JoinHandle<T>>) -> Outcome<T> {
+        self.request(Some(true), spawn)
+    }
+
+    /// Stops the worker and waits for its thread, including one still starting.
+    pub fn stop(&self) -> Outcome<T> {
+        self.request(Some(false), || None)
+    }
+
+    /// Stops the worker if it is starting or running, starts it otherwise.
+    pub fn toggle(&self, spawn: impl FnOnce() -> Option<JoinHandle<T>>) -> Outcome<T> {
+        self.request(None, spawn)
+    }
+
+    /// For the worker: false once a stop was asked for, also while it is still starting.
+    pub fn keep_running(&self) -> bool {
+        self.phase().is_on()
+    }
+
+    /// For the worker, once it is set up.
+    pub fn set_running(&self) {
+        let mut state = self.state.lock();
+        if state.phase == Phase::Starting {
+            state.phase = Phase::Running;
+        }
+    }
+
+    /// For the worker, right before its thread returns. Ending without being asked to, e.g. on an
+    /// error, stops the lifecycle; a stop in progress finishes once it joined the thread.
+    pub fn ended(&self) {
+        let mut state = self.state.lock();
+        if state.phase.is_on() {
+            state.phase = Phase::Stopped;
+            self.stopped.notify_all();
+        }
+    }
+
+    /// `run` of `None` toggles.
+    fn request(&self, run: Option<bool>, spawn: impl FnOnce() -> Option<JoinHandle<T>>) -> Outcome<T> {
+        let mut state = self.state.lock();
+        state.latest += 1;
+        let ticket = state.latest;
+        // A stop in progress counts as off, so toggling during it starts the worker again
+        let run = run.unwrap_or(!state.phase.is_on());
+        state.wanted = run;
+
+        while state.phase == Phase::Stopping {
+            self.stopped.wait(&mut state);
+            if state.latest != ticket {
+                return Outcome::unchanged(run);
+            }
+        }
+
+        match state.phase {
+            Phase::Stopped if run => {
+                // A thread that ended by itself is past its last use of the lifecycle
+                let joined = state.thread.take().map(JoinHandle::join);
+                let Some(handle) = spawn() else {
+                    return Outcome { joined, ..Outcome::unchanged(run) };
+                };
+                state.thread = Some(handle);
+                state.phase = Phase::Starting;
+                Outcome { started: true, joined, ..Outcome::unchanged(run) }
+            }
+            Phase::Stopped => {
+                let joined = state.thread.take().map(JoinHandle::join);
+                Outcome { joined, ..Outcome::unchanged(run) }
+            }
+            Phase::Starting | Phase::Running if !run => {
+                state.phase = Phase::Stopping;
+                let handle = state.thread.take();
+                let joined = MutexGuard::unlocked(&mut state, || handle.map(JoinHandle::join));
+                state.phase = Phase::Stopped;
+                self.stopped.notify_all();
+                Outcome { stopped: true, joined, ..Outcome::unchanged(run) }
+            }
+            _ => Outcome::unchanged(run),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    /// Stands in for the overlay: counts how many bodies run at once and spins until stopped.
+    fn spawn_body(lifecycle: &Arc<Lifecycle<()>>, active: &Arc<AtomicUsize>, most_active: &Arc<AtomicUsize>) -> Option<JoinHandle<()>> {
+        let (lifecycle, active, most_active) = (Arc::clone(lifecycle), Arc::clone(active), Arc::clone(most_active));
+        Some(thread::spawn(move || {
+            let now_active = active.fetch_add(1, Ordering::SeqCst) + 1;
+            most_active.fetch_max(now_active, Ordering::SeqCst);
+            // Setting up, during which toggles land on `Starting`
+            thread::sleep(Duration::from_micros(200));
+            lifecycle.set_running();
+            while lifecycle.keep_running() {
+                thread::sleep(Duration::from_micros(100));
+            }
+            active.fetch_sub(1, Ordering::SeqCst);
+            lifecycle.ended();
+        }))
+    }
+
+    #[test]
+    fn toggling_while_starting_stops_once_started() {
+        let lifecycle = Arc::new(Lifecycle::default());
+        let (started_tx, started_rx) = crossbeam_channel::bounded(0);
+        let (release_tx, release_rx) = crossbeam_channel::bounded::<()>(0);
+        let body = Arc::clone(&lifecycle);
+        let outcome = lifecycle.start(|| {
+            Some(thread::spawn(move || {
+                started_tx.send(()).unwrap();
+                let _ = release_rx.recv();
+                // Told to stop before it got going
+                let cancelled = !body.keep_running();
+                body.ended();
+                cancelled
+            }))
+        });
+        assert!(outcome.started);
+        started_rx.recv().unwrap();
+        assert_eq!(lifecycle.phase(), Phase::Starting);
+
+        let toggler = {
+            let lifecycle = Arc::clone(&lifecycle);
+            thread::spawn(move || lifecycle.toggle(|| unreachable!("toggling a starting worker stops it")))
+        };
+        while lifecycle.phase() != Phase::Stopping {
+            thread::yield_now();
+        }
+        // A start while the stop waits gives way to the newer stop below
+        let starter = {
+            let lifecycle = Arc::clone(&lifecycle);
+            thread::spawn(move || lifecycle.start(|| unreachable!("a newer stop came in")))
+        };
+        while lifecycle.state.lock().latest < 3 {
+            thread::yield_now();
+        }
+        let stopper = {
+            let lifecycle = Arc::clone(&lifecycle);
+            thread::spawn(move || lifecycle.stop())
+        };
+        while lifecycle.state.lock().latest < 4 {
+            thread::yield_now();
+        }
+        drop(release_tx);
+
+        let toggled = toggler.join().unwrap();
+        assert!(!toggled.run && toggled.stopped);
+        assert!(matches!(toggled.joined, Some(Ok(true))));
+        assert!(!starter.join().unwrap().started);
+        assert!(!stopper.join().unwrap().stopped);
+        assert_eq!(lifecycle.phase(), Phase::Stopped);
+    }
+
+    #[test]
+    fn concurrent_toggles_never_run_two_bodies() {
+        const THREADS: usize = 16;
+        const TOGGLES: usize = 50;
+
+        let lifecycle = Arc::new(Lifecycle::default());
+        let (active, most_active) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+        let togglers: Vec<_> = (0..THREADS)
+            .map(|i| {
+                let (lifecycle, active, most_active) = (Arc::clone(&lifecycle), Arc::clone(&active), Arc::clone(&most_active));
+                thread::spawn(move || {
+                    for j in 0..TOGGLES {
+                        let outcome = match (i + j) % 5 {
+                            0 => lifecycle.start(|| spawn_body(&lifecycle, &active, &most_active)),
+                            1 => lifecycle.stop(),
+                            _ => lifecycle.toggle(|| spawn_body(&lifecycle, &active, &most_active)),
+                        };
+                        assert!(!(outcome.started && outcome.stopped));
+                    }
+                })
+            })
+            .collect();
+        for toggler in togglers {
+            toggler.join().unwrap();
+        }
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while lifecycle.phase() == Phase::Starting && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(most_active.load(Ordering::SeqCst), 1);
+        let expected = if lifecycle.wanted() { Phase::Running } else { Phase::Stopped };
+        assert_eq!(lifecycle.phase(), expected);
+        assert_eq!(active.load(Ordering::SeqCst), usize::from(lifecycle.wanted()));
+
+        lifecycle.stop();
+        assert_eq!(active.load(Ordering::SeqCst), 0);
+    }
+}
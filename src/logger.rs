@@ -1,33 +1,216 @@
 use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Local, SecondsFormat};
+use crossbeam_channel::{bounded, unbounded, Receiver, RecvTimeoutError, Sender};
 use std::path::PathBuf;
-use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
 use std::fs;
 use std::io::Write;
+use std::thread;
+use std::time::Duration;
+
+/// Ordered from least to most verbose; a message is kept if its level is <= the global maximum.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Error,
+    Warn,
+    #[default]
+    Info,
+    Debug,
+    Trace,
+}
+
+impl LogLevel {
+    pub const ALL: [LogLevel; 5] = [LogLevel::Error, LogLevel::Warn, LogLevel::Info, LogLevel::Debug, LogLevel::Trace];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            LogLevel::Error => "Error",
+            LogLevel::Warn => "Warn",
+            LogLevel::Info => "Info",
+            LogLevel::Debug => "Debug",
+            LogLevel::Trace => "Trace",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|level| level.label().eq_ignore_ascii_case(value))
+    }
+
+    fn from_u8(value: u8) -> Self {
+        Self::ALL.get(value as usize).copied().unwrap_or(LogLevel::Trace)
+    }
+
+    /// Leads the message in text lines; info lines have none.
+    fn text_prefix(&self) -> &'static str {
+        match self {
+            LogLevel::Error => "ERROR: ",
+            LogLevel::Warn => "WARN: ",
+            LogLevel::Info => "",
+            LogLevel::Debug => "DEBUG: ",
+            LogLevel::Trace => "TRACE: ",
+        }
+    }
+}
+
+/// How lines are written to the log file. Fixed for a session, so one file never mixes both.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// `[2024-05-01 12:00:00.000] WARN: message (key=value)`, for reading.
+    #[default]
+    Text,
+    /// One JSON object per line with `time`, `level`, `target`, `message` and `fields`, for scripts.
+    Json,
+}
+
+impl LogFormat {
+    pub const ALL: [LogFormat; 2] = [LogFormat::Text, LogFormat::Json];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            LogFormat::Text => "Text",
+            LogFormat::Json => "JSON",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|format| format.label().eq_ignore_ascii_case(value))
+    }
+}
+
+/// Key-value pairs logged next to the message, from the `fields: { .. }` form of the log macros.
+pub type LogFields = Vec<(&'static str, serde_json::Value)>;
+
+/// Turns a field of the log macros into JSON; values that can't be serialized log the reason.
+pub fn field_value<T: Serialize + ?Sized>(value: &T) -> serde_json::Value {
+    serde_json::to_value(value).unwrap_or_else(|e| serde_json::Value::String(format!("<{}>", e)))
+}
+
+static MAX_LEVEL: AtomicU8 = AtomicU8::new(LogLevel::Info as u8);
+
+/// Sets the most verbose level that gets recorded. Takes effect immediately on all threads.
+pub fn set_max_level(level: LogLevel) {
+    MAX_LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+pub fn max_level() -> LogLevel {
+    LogLevel::from_u8(MAX_LEVEL.load(Ordering::Relaxed))
+}
+
+/// Checked by the log macros before formatting so filtered messages cost a single atomic load.
+#[inline]
+pub fn log_enabled(level: LogLevel) -> bool {
+    level as u8 <= MAX_LEVEL.load(Ordering::Relaxed)
+}
+
+/// How long `flush_to_disk` and `finalize` wait for the logger thread to catch up.
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// One logged message, timestamped when logged.
+struct LogLine {
+    time: DateTime<Local>,
+    level: LogLevel,
+    /// Module the message was logged from, e.g. `chromabridge::overlay`; empty for the logger's own lines.
+    target: &'static str,
+    message: String,
+    fields: LogFields,
+}
+
+impl LogLine {
+    fn new(level: LogLevel, target: &'static str, message: String, fields: LogFields) -> Self {
+        Self { time: chrono::Local::now(), level, target, message, fields }
+    }
+
+    /// `[time] WARN: message (key=value, key=value)`; fields only add the suffix.
+    fn text(&self) -> String {
+        let mut line = format!("[{}] {}{}", self.time.format(TIMESTAMP_FORMAT), self.level.text_prefix(), self.message);
+        if !self.fields.is_empty() {
+            let fields: Vec<String> = self
+                .fields
+                .iter()
+                .map(|(key, value)| match value {
+                    serde_json::Value::String(text) => format!("{}={}", key, text),
+                    value => format!("{}={}", key, value),
+                })
+                .collect();
+            line.push_str(&format!(" ({})", fields.join(", ")));
+        }
+        line
+    }
+
+    fn json(&self) -> String {
+        let mut object = serde_json::Map::new();
+        object.insert("time".into(), self.time.to_rfc3339_opts(SecondsFormat::Millis, false).into());
+        object.insert("level".into(), field_value(&self.level));
+        if !self.target.is_empty() {
+            object.insert("target".into(), self.target.into());
+        }
+        object.insert("message".into(), self.message.clone().into());
+        if !self.fields.is_empty() {
+            let fields = self.fields.iter().map(|(key, value)| (key.to_string(), value.clone())).collect();
+            object.insert("fields".into(), serde_json::Value::Object(fields));
+        }
+        serde_json::Value::Object(object).to_string()
+    }
+
+    fn format(&self, format: LogFormat) -> String {
+        match format {
+            LogFormat::Text => self.text(),
+            LogFormat::Json => self.json(),
+        }
+    }
+}
 
+/// Work for the logger thread.
+enum LogCommand {
+    /// The thread formats and stores the line.
+    Line(LogLine),
+    /// Writes the buffered lines to the file, then replies.
+    Flush(Sender<Result<(), String>>),
+}
+
+/// Logging only formats the message and queues it; a dedicated thread keeps the buffer and does
+/// all file I/O, so a slow disk never stalls the render loop or IPC threads that log.
 pub struct SessionLogger {
-    log_buffer: Arc<Mutex<Vec<String>>>,
+    sender: Sender<LogCommand>,
     log_path: PathBuf,
     log_dir: PathBuf,
     retention_count: usize,
     app_name: String,
-    stream_to_stdout: bool,
+    format: LogFormat,
+    /// Lines still queued when `finalize` gave up waiting for the logger thread.
+    dropped_lines: AtomicUsize,
 }
 
 impl SessionLogger {
-    pub fn new(log_dir: PathBuf, app_name: &str, retention_count: usize, stream_to_stdout: bool) -> Result<Self> {
+    /// Streaming also prints each line to the console, always as text.
+    pub fn new(log_dir: PathBuf, app_name: &str, retention_count: usize, stream_to_stdout: bool, format: LogFormat) -> Result<Self> {
         fs::create_dir_all(&log_dir)?;
 
         let timestamp = chrono::Local::now().format("%Y%m%d_%H%M%S");
         let log_filename = format!("{}_{}.log", app_name, timestamp);
         let log_path = log_dir.join(&log_filename);
 
+        let (sender, receiver) = unbounded();
+        let writer = LogWriter {
+            log_path: log_path.clone(),
+            stream_to_stdout,
+            format,
+            buffer: Vec::new(),
+            file: None,
+        };
+        thread::Builder::new().name("logger".into()).spawn(move || writer.run(receiver))?;
+
         let logger = Self {
-            log_buffer: Arc::new(Mutex::new(Vec::new())),
+            sender,
             log_path,
             log_dir,
             retention_count,
             app_name: app_name.to_string(),
-            stream_to_stdout,
+            format,
+            dropped_lines: AtomicUsize::new(0),
         };
 
         logger.clean_old_logs()?;
@@ -37,44 +220,42 @@ impl SessionLogger {
     }
 
     pub fn log(&self, message: impl AsRef<str>) {
-        let timestamp = chrono::Local::now().format("%Y-%m-%d %H:%M:%S%.3f");
-        let log_line = format!("[{}] {}", timestamp, message.as_ref());
-
-        // Only print to stdout when streaming mode is enabled
-        if self.stream_to_stdout {
-            println!("{}", log_line);
-            // In streaming mode, write to file immediately
-            let _ = self.write_line_to_file(&log_line);
-        } else {
-            // In buffered mode, add to buffer
-            if let Ok(mut buffer) = self.log_buffer.lock() {
-                buffer.push(log_line);
-            }
+        self.queue(LogLevel::Info, message);
+    }
+
+    /// Never blocks: the channel is unbounded and only fails once the logger thread is gone.
+    /// Drops messages above `max_level`, like the macros do before formatting.
+    pub fn record(&self, level: LogLevel, target: &'static str, message: String, fields: LogFields) {
+        if !log_enabled(level) {
+            return;
         }
+        let _ = self.sender.send(LogCommand::Line(LogLine::new(level, target, message, fields)));
     }
 
-    fn write_line_to_file(&self, line: &str) -> Result<()> {
-        let mut file = fs::OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(&self.log_path)?;
-        writeln!(file, "{}", line)?;
-        file.flush()?;
-        Ok(())
+    fn queue(&self, level: LogLevel, message: impl AsRef<str>) {
+        self.record(level, "", message.as_ref().to_string(), Vec::new());
     }
 
     pub fn error(&self, message: impl AsRef<str>) {
-        self.log(format!("ERROR: {}", message.as_ref()));
+        self.queue(LogLevel::Error, message);
     }
 
     pub fn warn(&self, message: impl AsRef<str>) {
-        self.log(format!("WARN: {}", message.as_ref()));
+        self.queue(LogLevel::Warn, message);
     }
 
     pub fn info(&self, message: impl AsRef<str>) {
         self.log(message);
     }
 
+    pub fn debug(&self, message: impl AsRef<str>) {
+        self.queue(LogLevel::Debug, message);
+    }
+
+    pub fn trace(&self, message: impl AsRef<str>) {
+        self.queue(LogLevel::Trace, message);
+    }
+
     fn clean_old_logs(&self) -> Result<()> {
         let mut log_files: Vec<(PathBuf, std::time::SystemTime)> = Vec::new();
         let prefix = format!("{}_", self.app_name);
@@ -105,32 +286,41 @@ impl SessionLogger {
         Ok(())
     }
 
+    /// Waits up to `FLUSH_TIMEOUT` for the logger thread to write everything logged so far.
     pub fn flush_to_disk(&self) -> Result<()> {
-        if let Ok(mut buffer) = self.log_buffer.lock() {
-            if buffer.is_empty() {
-                return Ok(());
-            }
-
-            let mut file = fs::OpenOptions::new()
-                .create(true)
-                .append(true)
-                .open(&self.log_path)?;
-
-            for line in buffer.iter() {
-                writeln!(file, "{}", line)?;
-            }
-
-            file.flush()?;
-            buffer.clear();
+        let (reply, done) = bounded(1);
+        self.sender
+            .send(LogCommand::Flush(reply))
+            .map_err(|_| anyhow::anyhow!("Logger thread stopped"))?;
+        match done.recv_timeout(FLUSH_TIMEOUT) {
+            Ok(result) => result.map_err(|e| anyhow::anyhow!("Failed to write {}: {}", self.log_path.display(), e)),
+            Err(RecvTimeoutError::Timeout) => anyhow::bail!("Logger thread didn't flush within {:?}", FLUSH_TIMEOUT),
+            Err(RecvTimeoutError::Disconnected) => anyhow::bail!("Logger thread stopped"),
         }
-
-        Ok(())
     }
 
+    /// Logs the end of the session and flushes. Lines the thread didn't get to in time are
+    /// counted, and the count is appended to the file directly.
     pub fn finalize(&self) -> Result<()> {
         self.log(format!("=== {} Session Ended ===", self.app_name));
-        self.flush_to_disk()?;
-        Ok(())
+        let flushed = self.flush_to_disk();
+        if flushed.is_err() {
+            let dropped = self.sender.len();
+            self.dropped_lines.fetch_add(dropped, Ordering::Relaxed);
+            let note = LogLine::new(LogLevel::Warn, "", format!("{} log lines were still queued at exit and are missing", dropped), Vec::new());
+            let mut file = fs::OpenOptions::new().create(true).append(true).open(&self.log_path)?;
+            writeln!(file, "{}", note.format(self.format))?;
+        }
+        flushed
+    }
+
+    /// Lines lost because the logger thread was backed up when the session ended.
+    pub fn dropped_lines(&self) -> usize {
+        self.dropped_lines.load(Ordering::Relaxed)
+    }
+
+    pub fn format(&self) -> LogFormat {
+        self.format
     }
 }
 
@@ -140,10 +330,66 @@ impl Drop for SessionLogger {
     }
 }
 
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S%.3f";
+
+/// The logger thread's side: the buffered lines, or the open file in streaming mode.
+struct LogWriter {
+    log_path: PathBuf,
+    stream_to_stdout: bool,
+    format: LogFormat,
+    /// Formatted for the file.
+    buffer: Vec<String>,
+    file: Option<fs::File>,
+}
+
+impl LogWriter {
+    /// Runs until every `SessionLogger` sender is gone.
+    fn run(mut self, receiver: Receiver<LogCommand>) {
+        for command in receiver {
+            match command {
+                LogCommand::Line(line) => {
+                    let log_line = line.format(self.format);
+                    if self.stream_to_stdout {
+                        // The console is for reading, whatever the file's format
+                        match self.format {
+                            LogFormat::Text => println!("{}", log_line),
+                            LogFormat::Json => println!("{}", line.text()),
+                        }
+                        // In streaming mode, write to file immediately
+                        let _ = self.write_lines(&[log_line]);
+                    } else {
+                        self.buffer.push(log_line);
+                    }
+                }
+                LogCommand::Flush(reply) => {
+                    let lines = std::mem::take(&mut self.buffer);
+                    let _ = reply.send(self.write_lines(&lines).map_err(|e| e.to_string()));
+                }
+            }
+        }
+    }
+
+    fn write_lines(&mut self, lines: &[String]) -> std::io::Result<()> {
+        if lines.is_empty() {
+            return Ok(());
+        }
+        // Opened once, on the first write
+        let file = match self.file.take() {
+            Some(file) => file,
+            None => fs::OpenOptions::new().create(true).append(true).open(&self.log_path)?,
+        };
+        let file = self.file.insert(file);
+        for line in lines {
+            writeln!(file, "{}", line)?;
+        }
+        file.flush()
+    }
+}
+
 static LOGGER: once_cell::sync::OnceCell<SessionLogger> = once_cell::sync::OnceCell::new();
 
-pub fn init_logger(log_dir: PathBuf, app_name: &str, retention_count: usize, stream_to_stdout: bool) -> Result<()> {
-    let logger = SessionLogger::new(log_dir, app_name, retention_count, stream_to_stdout)?;
+pub fn init_logger(log_dir: PathBuf, app_name: &str, retention_count: usize, stream_to_stdout: bool, format: LogFormat) -> Result<()> {
+    let logger = SessionLogger::new(log_dir, app_name, retention_count, stream_to_stdout, format)?;
     LOGGER.set(logger).map_err(|_| anyhow::anyhow!("Logger already initialized"))?;
     Ok(())
 }
@@ -172,6 +418,32 @@ pub fn log_info(message: impl AsRef<str>) {
     }
 }
 
+pub fn log_debug(message: impl AsRef<str>) {
+    if let Some(logger) = LOGGER.get() {
+        logger.debug(message);
+    }
+}
+
+pub fn log_trace(message: impl AsRef<str>) {
+    if let Some(logger) = LOGGER.get() {
+        logger.trace(message);
+    }
+}
+
+/// What the log macros call.
+pub fn record(level: LogLevel, target: &'static str, message: String, fields: LogFields) {
+    if let Some(logger) = LOGGER.get() {
+        logger.record(level, target, message, fields);
+    }
+}
+
+pub fn flush_logs() -> Result<()> {
+    if let Some(logger) = LOGGER.get() {
+        logger.flush_to_disk()?;
+    }
+    Ok(())
+}
+
 pub fn finalize_logs() -> Result<()> {
     if let Some(logger) = LOGGER.get() {
         logger.finalize()?;
@@ -183,23 +455,190 @@ pub fn get_log_path() -> Option<PathBuf> {
     LOGGER.get().map(|logger| logger.log_path.clone())
 }
 
+/// The format of this session's log file.
+pub fn log_format() -> LogFormat {
+    LOGGER.get().map(SessionLogger::format).unwrap_or_default()
+}
+
+/// Shared by the log macros: checks the level, then formats and queues the message with the
+/// calling module as its target.
+#[doc(hidden)]
 #[macro_export]
-macro_rules! log_info {
-    ($($arg:tt)*) => {
-        $crate::logger::log_info(format!($($arg)*))
+macro_rules! log_record {
+    ($level:ident, { $($key:ident = $value:expr),* }, $($arg:tt)+) => {
+        if $crate::logger::log_enabled($crate::logger::LogLevel::$level) {
+            $crate::logger::record(
+                $crate::logger::LogLevel::$level,
+                module_path!(),
+                format!($($arg)+),
+                vec![$((stringify!($key), $crate::logger::field_value(&$value))),*],
+            )
+        }
+    };
+}
+
+#[macro_export]
+macro_rules! log_error {
+    (fields: { $($key:ident = $value:expr),* $(,)? }, $($arg:tt)+) => {
+        $crate::log_record!(Error, { $($key = $value),* }, $($arg)+)
+    };
+    ($($arg:tt)+) => {
+        $crate::log_record!(Error, {}, $($arg)+)
     };
 }
 
 #[macro_export]
 macro_rules! log_warn {
-    ($($arg:tt)*) => {
-        $crate::logger::log_warn(format!($($arg)*))
+    (fields: { $($key:ident = $value:expr),* $(,)? }, $($arg:tt)+) => {
+        $crate::log_record!(Warn, { $($key = $value),* }, $($arg)+)
+    };
+    ($($arg:tt)+) => {
+        $crate::log_record!(Warn, {}, $($arg)+)
     };
 }
 
+/// `log_info!(fields: { monitor = index, spectrum = name }, "Overlay started")` logs the
+/// fields next to the message: under `fields` in JSON lines, appended as
+/// `(monitor=0, spectrum=tritan)` to text lines. Values have to implement `Serialize`.
 #[macro_export]
-macro_rules! log_error {
-    ($($arg:tt)*) => {
-        $crate::logger::log_error(format!($($arg)*))
+macro_rules! log_info {
+    (fields: { $($key:ident = $value:expr),* $(,)? }, $($arg:tt)+) => {
+        $crate::log_record!(Info, { $($key = $value),* }, $($arg)+)
+    };
+    ($($arg:tt)+) => {
+        $crate::log_record!(Info, {}, $($arg)+)
+    };
+}
+
+#[macro_export]
+macro_rules! log_debug {
+    (fields: { $($key:ident = $value:expr),* $(,)? }, $($arg:tt)+) => {
+        $crate::log_record!(Debug, { $($key = $value),* }, $($arg)+)
     };
+    ($($arg:tt)+) => {
+        $crate::log_record!(Debug, {}, $($arg)+)
+    };
+}
+
+#[macro_export]
+macro_rules! log_trace {
+    (fields: { $($key:ident = $value:expr),* $(,)? }, $($arg:tt)+) => {
+        $crate::log_record!(Trace, { $($key = $value),* }, $($arg)+)
+    };
+    ($($arg:tt)+) => {
+        $crate::log_record!(Trace, {}, $($arg)+)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+    use std::time::Instant;
+
+    #[test]
+    fn buffered_lines_reach_the_file_on_flush() {
+        let dir = temp_dir("logs-buffered");
+        let logger = SessionLogger::new(dir.clone(), "test", 5, false, LogFormat::Text).unwrap();
+        logger.warn("disk is slow");
+        logger.record(LogLevel::Info, "chromabridge::overlay", "Overlay started".into(), vec![("monitor", field_value(&1)), ("spectrum", field_value("tritan"))]);
+        assert!(!logger.log_path.exists());
+
+        logger.flush_to_disk().unwrap();
+        let written = fs::read_to_string(&logger.log_path).unwrap();
+        assert!(written.contains("=== test Session Started ==="));
+        assert!(written.contains("] WARN: disk is slow"));
+        assert!(written.contains("] Overlay started (monitor=1, spectrum=tritan)\n"));
+
+        logger.finalize().unwrap();
+        assert!(fs::read_to_string(&logger.log_path).unwrap().ends_with("=== test Session Ended ===\n"));
+        assert_eq!(logger.dropped_lines(), 0);
+        drop(logger);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn logging_never_waits_for_the_logger_thread() {
+        const LINES: u32 = 10_000;
+        // Far above the cost of formatting and queueing a line, far below a file write
+        const BUDGET_PER_LINE: Duration = Duration::from_micros(50);
+
+        let dir = temp_dir("logs-stalled");
+        let logger = SessionLogger::new(dir.clone(), "test", 5, true, LogFormat::Text).unwrap();
+        // The thread blocks handing over this reply until it is received, like on a stuck disk
+        let (reply, done) = bounded(0);
+        logger.sender.send(LogCommand::Flush(reply)).unwrap();
+
+        let start = Instant::now();
+        for i in 0..LINES {
+            logger.info(format!("frame {} failed: {}", i, "device removed"));
+        }
+        let elapsed = start.elapsed();
+        assert!(elapsed < BUDGET_PER_LINE * LINES, "{} lines took {:?}", LINES, elapsed);
+        assert!(logger.sender.len() >= LINES as usize);
+
+        // Once the thread moves again everything is written
+        done.recv().unwrap().unwrap();
+        logger.flush_to_disk().unwrap();
+        let written = fs::read_to_string(&logger.log_path).unwrap();
+        assert!(written.contains(&format!("frame {} failed", LINES - 1)));
+        drop(logger);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn json_lines_parse_and_carry_their_fields() {
+        let dir = temp_dir("logs-json");
+        let logger = SessionLogger::new(dir.clone(), "test", 5, false, LogFormat::Json).unwrap();
+        logger.record(LogLevel::Warn, "chromabridge::overlay", "Overlay started".into(), vec![("monitor", field_value(&1)), ("spectrum", field_value("tritan \"v2\""))]);
+        logger.info("line one\nline two");
+        logger.finalize().unwrap();
+
+        let written = fs::read_to_string(&logger.log_path).unwrap();
+        let lines: Vec<serde_json::Value> = written.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0]["message"], "=== test Session Started ===");
+        assert_eq!(lines[0]["level"], "info");
+        assert!(lines[0].get("target").is_none() && lines[0].get("fields").is_none());
+
+        let started = &lines[1];
+        assert_eq!(started["level"], "warn");
+        assert_eq!(started["target"], "chromabridge::overlay");
+        assert_eq!(started["message"], "Overlay started");
+        assert_eq!(started["fields"]["monitor"], 1);
+        assert_eq!(started["fields"]["spectrum"], "tritan \"v2\"");
+        assert!(DateTime::parse_from_rfc3339(started["time"].as_str().unwrap()).is_ok());
+
+        assert_eq!(lines[2]["message"], "line one\nline two");
+        assert_eq!(lines[3]["message"], "=== test Session Ended ===");
+        drop(logger);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn levels_above_the_max_are_dropped() {
+        let dir = temp_dir("logs-levels");
+        let logger = SessionLogger::new(dir.clone(), "test", 5, false, LogFormat::Text).unwrap();
+        assert_eq!(max_level(), LogLevel::Info);
+        logger.debug("debug line");
+        logger.trace("trace line");
+        logger.record(LogLevel::Debug, "chromabridge::overlay", "debug record".into(), Vec::new());
+        logger.warn("warn line");
+        logger.finalize().unwrap();
+
+        let written = fs::read_to_string(&logger.log_path).unwrap();
+        assert!(written.contains("warn line"));
+        assert!(!written.contains("debug line") && !written.contains("debug record") && !written.contains("trace line"), "{}", written);
+        drop(logger);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn macros_accept_fields() {
+        // Without an initialized logger these only have to compile
+        let (monitor, spectrum) = (0, "tritan");
+        crate::log_info!(fields: { monitor = monitor, spectrum = spectrum }, "Overlay started on {}", monitor);
+        crate::log_warn!(fields: { retries = 3, }, "Capture lost");
+        crate::log_error!("Plain {}", "message");
+    }
 }
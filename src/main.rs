@@ -1,190 +1,636 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod asset_watcher;
+mod color_conflict;
+mod command_palette;
+mod control_server;
+mod display_power;
+mod frame_dump;
+mod fullscreen;
+mod graphics_capture;
 mod gui;
+mod gui_host;
+mod monitor_identify;
+mod noise_preview;
+mod notifications;
+mod output_preview;
 mod overlay;
+mod platform_check;
+mod present_stats;
+mod render_priority;
+mod render_sweep;
+mod screenshot_preview;
+mod self_check;
+mod settings_search;
+mod shell_exclusion;
+mod spectrum_watcher;
+mod startup;
+mod target_window;
+mod theme;
+mod topmost;
+mod tray;
+mod update_checker;
 
 use anyhow::Result;
-use chromabridge::{StateManager, log_info, log_warn};
+use chromabridge::{AppState, BATTERY_SAVER_FPS, ConflictBehavior, FullscreenBehavior, LogFormat, LogLevel, StateManager, log_debug, log_info, log_warn, log_error, tr};
+use chromabridge::startup_timer::StartupTimer;
+use std::process::ExitCode;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
 use crossbeam_channel::{Sender, Receiver, bounded};
-use tray_icon::{TrayIconBuilder, TrayIconEvent, MouseButton, Icon};
-use tray_icon::menu::{Menu, MenuItem, MenuEvent, CheckMenuItem};
+use tray_icon::{TrayIconEvent, MouseButton};
+use tray_icon::menu::{Menu, MenuEvent};
+
+/// Overlay status outside the app state (capture errors, fullscreen throttling) is still polled, at this interval.
+const TRAY_STATUS_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+/// How often the power source is checked for the automatic battery saver.
+const POWER_SOURCE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+/// Delays between timed retries of a restored overlay whose display wasn't there yet, e.g. a
+/// docked monitor still connecting at logon. A display change retries early without using one up.
+const OVERLAY_RETRY_DELAYS: [std::time::Duration; 4] = [
+    std::time::Duration::from_secs(2),
+    std::time::Duration::from_secs(5),
+    std::time::Duration::from_secs(15),
+    std::time::Duration::from_secs(30),
+];
 
 #[derive(Debug)]
 enum AppCommand {
-    OpenGui,
     ToggleOverlay,
     Exit,
 }
 
+/// Progress of retrying the overlay restore at startup.
+#[derive(Default)]
+struct OverlayRetry {
+    timer_attempts: usize,
+    /// When the next attempt is due, `None` while an attempt is in flight.
+    due: Option<std::time::Instant>,
+}
+
 struct App {
     state: Arc<StateManager>,
     overlay_manager: Arc<overlay::OverlayManager>,
-    gui_visible: Arc<AtomicBool>,
+    gui: gui_host::GuiHost,
     exit_requested: Arc<AtomicBool>,
     command_tx: Sender<AppCommand>,
-    gui_close_tx: parking_lot::Mutex<Option<Sender<()>>>,
-    gui_toggle_tx: parking_lot::Mutex<Option<Sender<()>>>,
-    gui_ctx: Arc<parking_lot::Mutex<Option<egui::Context>>>,
     wakeup: Arc<(parking_lot::Mutex<()>, parking_lot::Condvar)>,
+    /// Set by the state notifier when a change affects the tray icon, tooltip or checkbox.
+    tray_dirty: Arc<AtomicBool>,
+    pending_crash: parking_lot::Mutex<Option<gui::CrashNotice>>,
+    startup_repaired: parking_lot::Mutex<Option<std::path::PathBuf>>,
+    control_server: control_server::ControlServer,
+    spectrum_watcher: spectrum_watcher::SpectrumWatcher,
+    update_checker: update_checker::UpdateChecker,
 }
 
 impl App {
     fn new() -> Result<(Self, Receiver<AppCommand>)> {
         let state = Arc::new(StateManager::new()?);
-        let overlay_manager = Arc::new(overlay::OverlayManager::new(Arc::clone(&state)));
+        let overlay_manager = overlay::OverlayManager::new(Arc::clone(&state));
+        let control_server = control_server::ControlServer::spawn(Arc::clone(&state), Arc::clone(&overlay_manager));
+        let spectrum_watcher = spectrum_watcher::SpectrumWatcher::spawn(Arc::clone(&state), Arc::clone(&overlay_manager));
+        let update_checker = update_checker::UpdateChecker::spawn(Arc::clone(&state));
         let (command_tx, command_rx) = bounded(10);
 
         Ok((Self {
             state,
             overlay_manager,
-            gui_visible: Arc::new(AtomicBool::new(false)),
+            gui: gui_host::GuiHost::new(),
             exit_requested: Arc::new(AtomicBool::new(false)),
             command_tx,
-            gui_close_tx: parking_lot::Mutex::new(None),
-            gui_toggle_tx: parking_lot::Mutex::new(None),
-            gui_ctx: Arc::new(parking_lot::Mutex::new(None)),
             wakeup: Arc::new((parking_lot::Mutex::new(()), parking_lot::Condvar::new())),
+            tray_dirty: Arc::new(AtomicBool::new(true)),
+            pending_crash: parking_lot::Mutex::new(None),
+            startup_repaired: parking_lot::Mutex::new(None),
+            control_server,
+            spectrum_watcher,
+            update_checker,
         }, command_rx))
     }
 
+    /// Shows the settings window, creating it on the first call. Never blocks the caller.
     fn request_open_gui(&self) {
-        if !self.gui_visible.load(Ordering::Acquire) {
-            let _ = self.command_tx.try_send(AppCommand::OpenGui);
-            self.wakeup.1.notify_one();
-        } else {
-            log_info!("GUI already open - bringing to front");
-            if let Some(ctx) = self.gui_ctx.lock().as_ref() {
-                ctx.send_viewport_cmd(egui::ViewportCommand::Focus);
-                ctx.request_repaint();
-            }
+        if self.exit_requested.load(Ordering::Acquire) {
+            log_info!("Exit requested, ignoring GUI open request");
+            return;
         }
-    }
 
-    fn request_toggle_overlay(&self) {
-        if self.gui_visible.load(Ordering::Acquire) {
-            if let Some(toggle_tx) = self.gui_toggle_tx.lock().as_ref() {
-                let _ = toggle_tx.try_send(());
+        let state = Arc::clone(&self.state);
+        let overlay_manager = Arc::clone(&self.overlay_manager);
+        let wakeup = Arc::clone(&self.wakeup);
+        let exit_requested = Arc::clone(&self.exit_requested);
+        let command_tx = self.command_tx.clone();
+        let crash_notice = self.pending_crash.lock().take();
+        let startup_repaired = self.startup_repaired.lock().take();
+
+        self.gui.open(move |window| {
+            let mut settings_gui = gui::SettingsGui::new(state, Arc::clone(&overlay_manager), window);
+            if let Some(notice) = crash_notice {
+                settings_gui.set_crash_notice(notice);
             }
-
-            if let Some(ctx) = self.gui_ctx.lock().as_ref() {
-                ctx.request_repaint();
+            if let Some(previous_path) = startup_repaired {
+                settings_gui.set_startup_repaired(previous_path);
             }
+            let wakeup_for_toggle = Arc::clone(&wakeup);
+            settings_gui.set_overlay_toggle_callback(move || {
+                let on = overlay_manager.toggle();
+                log_info!("Overlay toggled from GUI: {}", if on { "ON" } else { "OFF" });
+                wakeup_for_toggle.1.notify_one();
+            });
+            settings_gui.set_exit_callback(move || {
+                exit_requested.store(true, Ordering::Release);
+                let _ = command_tx.try_send(AppCommand::Exit);
+                wakeup.1.notify_one();
+            });
+            settings_gui
+        });
+    }
 
-        } else {
-            let _ = self.command_tx.try_send(AppCommand::ToggleOverlay);
-            self.wakeup.1.notify_one();
-        }
+    fn request_toggle_overlay(&self) {
+        let _ = self.command_tx.try_send(AppCommand::ToggleOverlay);
+        self.wakeup.1.notify_one();
     }
 
     fn request_exit(&self) {
         self.exit_requested.store(true, Ordering::Release);
+        let _ = self.command_tx.try_send(AppCommand::Exit);
+        self.wakeup.1.notify_one();
+    }
+
+    fn toggle_overlay(&self) {
+        self.overlay_manager.toggle();
+    }
+
+    /// Applies a strength entry of the tray menu. A running overlay picks the new strength up
+    /// through its state listener, and the state notifier refreshes the menu and tooltip.
+    fn change_strength(&self, change: tray::StrengthChange) {
+        let strength = change.apply(self.state.read(|s| s.strength));
+        log_info!("Strength set to {}% from the tray menu", tray::strength_percent(strength));
+        self.state.update(|s| {
+            s.strength = strength;
+            s.remember_spectrum_settings();
+        });
+    }
+
+    /// Wakes the main loop when the tray needs refreshing and repaints the settings window on
+    /// any state change, wherever it came from.
+    fn spawn_state_notifier(&self) {
+        let changes = self.state.subscribe();
+        let tray_dirty = Arc::clone(&self.tray_dirty);
+        let wakeup = Arc::clone(&self.wakeup);
+        let gui_window = Arc::clone(self.gui.window());
+
+        let spawned = std::thread::Builder::new().name("state-notifier".into()).spawn(move || {
+            for change in changes.iter() {
+                if change.overlay || change.spectrum || change.strength || change.language {
+                    tray_dirty.store(true, Ordering::Release);
+                    wakeup.1.notify_one();
+                }
+                gui_window.request_repaint();
+            }
+        });
 
-        // Force GUI to repaint so it checks the close signal
-        if let Some(ctx) = self.gui_ctx.lock().as_ref() {
-            ctx.request_repaint();
+        if let Err(e) = spawned {
+            log_warn!("Failed to start state notifier, tray updates fall back to polling: {}", e);
         }
+    }
 
-        if let Some(close_tx) = self.gui_close_tx.lock().as_ref() {
-            let _ = close_tx.try_send(());
+    /// Follows the power source with battery saver when the automatic mode is on. Only acts on
+    /// transitions, so turning battery saver off by hand while on battery sticks until the next one.
+    fn update_battery_saver(&self, last_on_battery: &mut Option<bool>) {
+        let Some(on_battery) = display_power::on_battery() else {
+            return;
+        };
+        if last_on_battery.replace(on_battery) == Some(on_battery) || !self.state.read(|s| s.auto_battery_saver) {
+            return;
         }
 
-        let _ = self.command_tx.try_send(AppCommand::Exit);
-        self.wakeup.1.notify_one();
+        log_info!("Power source changed to {} - battery saver {}", if on_battery { "battery" } else { "AC" }, if on_battery { "on" } else { "off" });
+        self.state.update(|s| s.set_battery_saver(on_battery));
     }
 
-    fn toggle_overlay(&self) {
-        self.overlay_manager.toggle();
+    /// Retries the restored overlay while its start fails for lack of a display, on a timer and
+    /// whenever the display topology changes. Clears `retry` once the overlay runs, the retries are
+    /// used up, or the user starts or stops the overlay themselves.
+    fn update_overlay_retry(&self, retry: &mut Option<OverlayRetry>, display_changed: bool) {
+        let Some(progress) = retry else {
+            return;
+        };
+        let overlay = &self.overlay_manager;
+
+        match progress.due {
+            None if overlay.state() == overlay::OverlayStatus::Starting => {}
+            None if overlay.failed_for_display() => {
+                match OVERLAY_RETRY_DELAYS.get(progress.timer_attempts) {
+                    Some(&delay) => {
+                        log_info!("Overlay display not available, retrying in {}s", delay.as_secs());
+                        progress.due = Some(std::time::Instant::now() + delay);
+                        overlay.set_waiting_for_display(true);
+                    }
+                    None => {
+                        log_warn!("Overlay display still not available after {} retries, giving up", OVERLAY_RETRY_DELAYS.len());
+                        overlay.set_waiting_for_display(false);
+                        *retry = None;
+                    }
+                }
+            }
+            None => {
+                if overlay.is_running() && overlay.waiting_for_display() {
+                    log_info!("Overlay restored after retrying");
+                }
+                overlay.set_waiting_for_display(false);
+                *retry = None;
+            }
+            Some(_) if !overlay.waiting_for_display() => {
+                log_info!("Overlay started or stopped manually, cancelling restore retries");
+                *retry = None;
+            }
+            Some(due) => {
+                let trigger = if display_changed {
+                    "display change"
+                } else if std::time::Instant::now() >= due {
+                    progress.timer_attempts += 1;
+                    "timer"
+                } else {
+                    return;
+                };
+                log_info!("Retrying overlay start (trigger: {})", trigger);
+                progress.due = None;
+                overlay.retry_start();
+            }
+        }
     }
 
     fn get_tooltip(&self) -> String {
-        let overlay_running = self.overlay_manager.is_running();
-        let spectrum_name = self.state.read(|s| s.spectrum_name.clone());
-
-        if overlay_running {
-            if let Some(name) = spectrum_name {
-                format!("ChromaBridge\nOverlay: {} (Active)", name)
-            } else {
-                "ChromaBridge\nOverlay: Active".to_string()
+        tray_tooltip(&self.state.snapshot(), &self.overlay_manager)
+    }
+
+    /// Looks for other color filters when the overlay starts and every `CHECK_INTERVAL` after,
+    /// and lowers the strength while one is found if so configured. Returns true if what is
+    /// shown about it changed.
+    fn update_color_conflict(&self, last_check: &mut Option<std::time::Instant>) -> bool {
+        let (enabled, behavior, processes) = self.state.read(|s| (s.conflict_detection, s.conflict_behavior, s.conflict_processes.clone()));
+
+        let mut changed = false;
+        if !enabled || !self.overlay_manager.is_running() {
+            *last_check = None;
+            changed = self.overlay_manager.set_color_conflict(None);
+        } else if last_check.is_none_or(|check| check.elapsed() >= color_conflict::CHECK_INTERVAL) {
+            *last_check = Some(std::time::Instant::now());
+            let conflict = color_conflict::detect(&processes);
+            changed = self.overlay_manager.set_color_conflict(conflict.clone());
+            if changed {
+                match conflict {
+                    Some(conflict) => log_info!("Color filter detected: {:?}", conflict),
+                    None => log_info!("Color filter no longer detected"),
+                }
             }
-        } else {
-            "ChromaBridge\nOverlay: Inactive".to_string()
         }
+
+        let scale = match self.overlay_manager.color_conflict() {
+            Some(_) if behavior == ConflictBehavior::Reduce => color_conflict::REDUCED_STRENGTH,
+            _ => 1.0,
+        };
+        if scale != self.overlay_manager.strength_scale() {
+            log_info!("Correction strength scaled to {}x while another color filter is active", scale);
+            self.overlay_manager.set_strength_scale(scale);
+        }
+
+        changed
+    }
+
+    /// Throttles the overlay while a fullscreen app is in the foreground, battery saver is on, or
+    /// another color filter is active with `ConflictBehavior::Pause`. Returns true if the throttle changed.
+    fn update_throttle(&self) -> bool {
+        use overlay::OverlayThrottle;
+
+        let (behavior, target_fps, battery_saver, conflict_behavior) = self.state.read(|s| (s.fullscreen_behavior, s.fullscreen_target_fps, s.battery_saver_active(), s.conflict_behavior));
+        let overlay_monitor = self.overlay_manager.get_diagnostics().monitor_rect;
+        let fullscreen_app = match overlay_monitor {
+            Some(monitor) if behavior != FullscreenBehavior::Ignore && self.overlay_manager.is_running() => fullscreen::detect_fullscreen_app(monitor),
+            _ => None,
+        };
+        let conflict_pause = conflict_behavior == ConflictBehavior::Pause && self.overlay_manager.color_conflict().is_some();
+
+        let throttle = match (&fullscreen_app, behavior) {
+            _ if conflict_pause => OverlayThrottle::Paused,
+            (Some(_), FullscreenBehavior::Reduce) => OverlayThrottle::Reduced(target_fps as f32),
+            (Some(_), FullscreenBehavior::Pause) => OverlayThrottle::Paused,
+            _ if battery_saver => OverlayThrottle::Reduced(BATTERY_SAVER_FPS as f32),
+            _ => OverlayThrottle::Full,
+        };
+
+        if throttle == self.overlay_manager.throttle() {
+            return false;
+        }
+
+        match (throttle, fullscreen_app) {
+            (OverlayThrottle::Paused, _) if conflict_pause => log_info!("Another color filter is active - overlay paused"),
+            (OverlayThrottle::Reduced(fps), Some(app)) => {
+                log_info!("Fullscreen app detected ({}) - overlay reduced to {} FPS", app.process_name, fps);
+            }
+            (OverlayThrottle::Paused, Some(app)) => {
+                log_info!("Fullscreen app detected ({}) - overlay paused", app.process_name);
+            }
+            (OverlayThrottle::Reduced(fps), None) => log_info!("Battery saver - overlay limited to {} FPS", fps),
+            _ => log_info!("Overlay back to full rate"),
+        }
+
+        self.overlay_manager.set_throttle(throttle);
+        true
+    }
+}
+
+/// The overlay's status as the tray tooltip shows it, e.g. "Paused"; notifications carry it as
+/// their reason.
+pub fn overlay_status_text(status: overlay::OverlayStatus, overlay_manager: &overlay::OverlayManager) -> String {
+    use overlay::{OverlayStatus, OverlayThrottle};
+
+    match status {
+        OverlayStatus::Stopped => tr!("tooltip.inactive"),
+        OverlayStatus::Failed => tr!("tooltip.failed"),
+        OverlayStatus::Starting => tr!("tooltip.starting"),
+        OverlayStatus::Restarting => tr!("tooltip.restarting"),
+        OverlayStatus::WaitingForDisplay => tr!("tooltip.waiting_for_display"),
+        OverlayStatus::Running => match overlay_manager.throttle() {
+            _ if overlay_manager.has_error() => tr!("tooltip.capture_error"),
+            _ if overlay_manager.is_occluded() => tr!("tooltip.occluded"),
+            OverlayThrottle::Full => tr!("tooltip.active"),
+            OverlayThrottle::Reduced(_) => tr!("tooltip.active_reduced"),
+            OverlayThrottle::Paused => tr!("tooltip.paused"),
+        },
+    }
+}
+
+/// Built from one snapshot of the settings, so the strength and spectrum shown belong together.
+pub fn tray_tooltip(settings: &AppState, overlay_manager: &overlay::OverlayManager) -> String {
+    use overlay::OverlayStatus;
+
+    let mut strength = tr!("tooltip.strength", percent = tray::strength_percent(settings.strength));
+    if let Some(release) = settings.available_update() {
+        strength = format!("{} | {}", strength, tr!("tooltip.update", version = release.version));
+    }
+    let overlay_status = overlay_manager.state();
+    let status = overlay_status_text(overlay_status, overlay_manager);
+    match overlay_status {
+        OverlayStatus::Running => {
+            let mut tooltip = match &settings.spectrum_name {
+                Some(name) => tr!("tooltip.overlay_spectrum", spectrum = name, status = status),
+                None => tr!("tooltip.overlay", status = status),
+            };
+            // On the strength line, as the shell cuts tooltips off after 127 characters
+            let strength = match overlay_manager.uptime() {
+                Some(uptime) => format!("{} | {}", strength, tr!("tooltip.uptime", time = format_active_time(uptime))),
+                None => strength,
+            };
+            if overlay_manager.debug_visibility() != overlay::DebugVisibility::Normal {
+                tooltip = format!("{} {}", tooltip, tr!("tooltip.debug_visibility"));
+            }
+            match overlay_manager.color_conflict() {
+                Some(conflict) => format!("{}\n{}\n{}", tooltip, strength, conflict.message()),
+                None => format!("{}\n{}", tooltip, strength),
+            }
+        }
+        _ => format!("{}\n{}", tr!("tooltip.overlay", status = status), strength),
+    }
+}
+
+/// `time` in hours and minutes, e.g. "2h 05m", or just minutes under an hour.
+pub fn format_active_time(time: std::time::Duration) -> String {
+    let minutes = time.as_secs() / 60;
+    match minutes / 60 {
+        0 => tr!("active_time.minutes", minutes = minutes),
+        hours => tr!("active_time.hours_minutes", hours = hours, minutes = format!("{:02}", minutes % 60)),
     }
 }
 
-fn main() -> Result<()> {
+fn main() -> Result<ExitCode> {
+    if std::env::args().any(|a| a == "--self-check") {
+        // Runs on its own, without the tray, overlay or control server of the app
+        std::process::exit(self_check::run_from_command_line());
+    }
+
     let result = run_app();
-    let _ = chromabridge::logger::finalize_logs();
+    if let Err(ref e) = result {
+        // Clean exits finalize logs in shutdown(); error paths bail out before reaching it
+        log_error!("Fatal error: {:#}", e);
+        let _ = chromabridge::logger::finalize_logs();
+    }
     result
 }
 
-fn run_app() -> Result<()> {
-    let args: Vec<String> = std::env::args().collect();
-    let enable_file_logging = args.contains(&"--stream-logs".to_string());
+/// Tears the app down in dependency order: external control first, then the overlay,
+/// then persisted state, the tray icon, and finally the log file. `tray` is `None` when startup
+/// stops before creating it.
+fn shutdown(app: &App, tray: Option<tray::TrayIndicator>) {
+    log_info!("Shutdown: closing GUI window");
+    app.gui.close();
 
+    log_info!("Shutdown: stopping control server, spectrum watcher and update checker");
+    app.control_server.stop();
+    app.spectrum_watcher.stop();
+    app.update_checker.stop();
+
+    log_info!("Shutdown: stopping overlay");
+    app.overlay_manager.shutdown();
+
+    log_info!("Shutdown: flushing state");
+    if !app.state.flush_and_join(std::time::Duration::from_secs(2)) {
+        log_warn!("Shutdown: state writer did not finish in time, last changes may be lost");
+    }
+
+    if let Some(tray) = tray {
+        log_info!("Shutdown: removing tray icon");
+        drop(tray);
+    }
+
+    log_info!("Shutdown complete");
+    let _ = chromabridge::logger::finalize_logs();
+}
+
+fn run_app() -> Result<ExitCode> {
+    let args: Vec<String> = std::env::args().collect();
+    // --stream-logs or --stream-logs=<level>; the level overrides the saved one for this session only
+    let stream_arg = args.iter().find(|a| *a == "--stream-logs" || a.starts_with("--stream-logs="));
+    let enable_file_logging = stream_arg.is_some();
+    let stream_level = stream_arg.and_then(|a| a.strip_prefix("--stream-logs="));
+    // --log-format=<text|json> overrides the saved format for this session only
+    let format_arg = args.iter().find_map(|a| a.strip_prefix("--log-format="));
+
+    let mut startup_timer = StartupTimer::start("startup");
     let (app, command_rx) = App::new()?;
     let app = Arc::new(app);
+    startup_timer.checkpoint("state");
 
     let log_dir = app.state.app_data_dir().join("logs");
-    let log_retention = app.state.read(|s| s.log_retention_count);
-    chromabridge::logger::init_logger(log_dir, "chromabridge", log_retention, enable_file_logging)?;
+    let (log_retention, saved_level, saved_format) = app.state.read(|s| (s.log_retention_count, s.log_level, s.log_format));
+    let log_level = stream_level.and_then(LogLevel::parse).unwrap_or(saved_level);
+    let log_format = format_arg.and_then(LogFormat::parse).unwrap_or(saved_format);
+    chromabridge::logger::set_max_level(log_level);
+    chromabridge::logger::init_logger(log_dir, "chromabridge", log_retention, enable_file_logging, log_format)?;
+    startup_timer.checkpoint("logger");
 
     log_info!("ChromaBridge main() started");
     if let Some(log_path) = chromabridge::logger::get_log_path() {
         log_info!("Log file: {}", log_path.display());
     }
     if enable_file_logging {
-        log_info!("Streaming mode enabled via --stream-logs");
+        log_info!("Streaming mode enabled via --stream-logs (level: {})", log_level.label());
+        if let Some(value) = stream_level.filter(|v| LogLevel::parse(v).is_none()) {
+            log_warn!("Unknown log level '{}' - expected error, warn, info, debug or trace", value);
+        }
     } else {
         log_info!("Buffered mode - logs will be written to file on exit");
     }
+    if let Some(value) = format_arg.filter(|v| LogFormat::parse(v).is_none()) {
+        log_warn!("Unknown log format '{}' - expected text or json", value);
+    }
+
+    let dirs = app.state.data_dirs();
+    log_info!("Data folders ({}): settings and logs in {}, assets in {}", dirs.layout.label(), dirs.local.display(), dirs.assets.display());
+    if let Some(copy) = app.state.roaming_copy() {
+        log_info!("Copied {} file(s) from {}", copy.copied_files, copy.from.display());
+        for skipped in &copy.skipped {
+            log_warn!("Not copied - {}", skipped);
+        }
+    }
+
+    chromabridge::i18n::set_language(app.state.read(|s| s.language));
+
+    match platform_check::detect() {
+        Some(unsupported) if !args.iter().any(|a| a == platform_check::FORCE_FLAG) => {
+            log_error!("Unsupported platform ({}) - exiting; start with {} to run anyway", unsupported, platform_check::FORCE_FLAG);
+            platform_check::show_message(unsupported);
+            shutdown(&app, None);
+            return Ok(ExitCode::from(platform_check::UNSUPPORTED_PLATFORM_EXIT_CODE));
+        }
+        Some(unsupported) => log_warn!("Unsupported platform ({}) - continuing because of {}", unsupported, platform_check::FORCE_FLAG),
+        None => log_debug!("Platform check passed"),
+    }
+
+    let database_reset = app.state.corrupt_database_backup();
+    if let Some(ref backup) = database_reset {
+        log_error!("!!! State database was corrupt - settings reset to defaults (old database: {}) !!!", backup.display());
+    }
+    if let Some(report) = app.state.migration_report() {
+        log_info!(
+            "Imported {} asset file(s) and {} from {}",
+            report.copied_files,
+            report.settings_source.as_deref().map_or("no settings".to_string(), |source| format!("settings ({})", source)),
+            report.legacy_dir.display()
+        );
+        for skipped in &report.skipped {
+            log_warn!("Not imported - {}", skipped);
+        }
+    }
+
+    let purged = chromabridge::trash::purge_expired(&app.state.assets_dir(), chrono::Local::now());
+    if purged > 0 {
+        log_info!("Purged {} deleted asset(s) older than 30 days", purged);
+    }
+
+    app.spawn_state_notifier();
+
+    let crash_dir = chromabridge::crash::crash_dir(app.state.app_data_dir());
+    chromabridge::crash::install_panic_hook(crash_dir.clone());
 
     log_info!("=== ChromaBridge Starting ===");
 
+    // The registry is the source of truth for run-at-startup; fix entries left behind by a moved install
+    match startup::verify_startup_entry() {
+        Ok(check) => {
+            app.state.update(|s| s.run_at_startup = check != startup::StartupCheck::Disabled);
+            if let startup::StartupCheck::Repaired { previous_path } = check {
+                *app.startup_repaired.lock() = Some(previous_path);
+            }
+        }
+        Err(e) => log_warn!("Failed to verify startup entry: {:#}", e),
+    }
+    startup_timer.checkpoint("setup");
+
+    let pending_crash = chromabridge::crash::take_pending_crash(&crash_dir);
     let last_overlay_enabled = app.state.read(|s| s.last_overlay_enabled);
-    if last_overlay_enabled {
+
+    if let Some(ref report) = pending_crash {
+        log_warn!("Previous session crashed (report: {})", report.display());
+        if last_overlay_enabled {
+            log_warn!("Skipping overlay restore for this session because of the previous crash");
+        }
+        *app.pending_crash.lock() = Some(gui::CrashNotice {
+            report_path: report.clone(),
+            crash_dir: crash_dir.clone(),
+            overlay_restore_skipped: last_overlay_enabled,
+        });
+    } else if last_overlay_enabled {
         log_info!("Restoring overlay (was enabled on last shutdown)");
         app.overlay_manager.start();
     }
-
-    let open_gui = app.state.read(|s| s.open_gui_on_launch);
-    if open_gui {
-        log_info!("Auto-opening GUI (open_gui_on_launch=true)");
+    let mut overlay_retry = (pending_crash.is_none() && last_overlay_enabled).then(OverlayRetry::default);
+    startup_timer.checkpoint("overlay");
+
+    let open_gui_reason = if pending_crash.is_some() {
+        Some("previous session crashed")
+    } else if database_reset.is_some() {
+        Some("settings database was reset")
+    } else if app.state.read(|s| s.open_gui_on_launch) {
+        Some("open_gui_on_launch=true")
+    } else {
+        None
+    };
+    if let Some(reason) = open_gui_reason {
+        log_info!("Auto-opening GUI ({})", reason);
         app.request_open_gui();
     }
 
+    // Kept alive for the rest of run_app; dropping it unregisters the notification
+    let _display_power = match display_power::DisplayPowerMonitor::register(Arc::clone(&app.overlay_manager)) {
+        Ok(monitor) => Some(monitor),
+        Err(e) => {
+            log_warn!("Display power notifications unavailable, overlay will keep rendering while the display is off: {}", e);
+            None
+        }
+    };
+
     log_info!("Loading tray icon");
-    let icon = load_icon()?;
+    let icon_rgba = tray::load_icon_rgba();
 
     let initial_overlay_state = app.overlay_manager.is_running();
 
     let menu = Menu::new();
-    let open_settings_item = MenuItem::new("Open Settings", true, None);
-    let overlay_item = CheckMenuItem::new("Enable Overlay", true, initial_overlay_state, None);
+    let menu_items = tray::TrayMenuItems::new(initial_overlay_state, app.state.read(|s| s.strength))?;
     let separator = tray_icon::menu::PredefinedMenuItem::separator();
-    let exit_item = MenuItem::new("Exit", true, None);
 
-    menu.append(&open_settings_item)?;
-    menu.append(&overlay_item)?;
+    menu.append(&menu_items.open_settings)?;
+    menu.append(&menu_items.overlay)?;
+    menu.append(&menu_items.restart)?;
+    menu.append(&menu_items.strength)?;
     menu.append(&separator)?;
-    menu.append(&exit_item)?;
+    menu.append(&menu_items.exit)?;
 
-    let open_settings_id = open_settings_item.id().clone();
-    let overlay_id = overlay_item.id().clone();
-    let exit_id = exit_item.id().clone();
+    let open_settings_id = menu_items.open_settings.id().clone();
+    let overlay_id = menu_items.overlay.id().clone();
+    let restart_id = menu_items.restart.id().clone();
+    let exit_id = menu_items.exit.id().clone();
+    let strength_ids = menu_items.strength_ids();
 
     let tooltip = app.get_tooltip();
-    let tray_icon = TrayIconBuilder::new()
-        .with_menu(Box::new(menu.clone()))
-        .with_menu_on_left_click(false)
-        .with_tooltip(&tooltip)
-        .with_icon(icon.clone())
-        .build()?;
+    let mut tray = tray::TrayIndicator::new(menu, menu_items, icon_rgba, &tooltip)?;
+    tray.refresh(&app.state, &app.overlay_manager);
 
     log_info!("Tray icon created on main thread");
 
+    // Kept alive for the rest of run_app, like the display power monitor
+    let taskbar_watcher = match tray::TaskbarWatcher::register() {
+        Ok(watcher) => Some(watcher),
+        Err(e) => {
+            log_warn!("Taskbar restart notifications unavailable, the tray icon won't come back if Explorer restarts: {:#}", e);
+            None
+        }
+    };
+    let mut notifier = notifications::Notifier::new();
+    startup_timer.checkpoint("tray");
+
     let app_clone = Arc::clone(&app);
     let exit_requested_for_click = Arc::clone(&app.exit_requested);
     TrayIconEvent::set_event_handler(Some(move |event| {
@@ -194,7 +640,7 @@ fn run_app() -> Result<()> {
                     if exit_requested_for_click.load(Ordering::Acquire) {
                         return;
                     }
-                    log_info!("Tray icon clicked");
+                    log_debug!("Tray icon clicked");
                     app_clone.request_open_gui();
                 }
             }
@@ -216,17 +662,29 @@ fn run_app() -> Result<()> {
             let state = if was_running { "OFF" } else { "ON" };
             log_info!("Toggle Overlay clicked (turning {})", state);
             app_clone.request_toggle_overlay();
+        } else if event.id == restart_id {
+            app_clone.overlay_manager.restart("tray menu");
         } else if event.id == exit_id {
             log_info!("Exit clicked");
             app_clone.request_exit();
+        } else if let Some(&(_, change)) = strength_ids.iter().find(|(id, _)| *id == event.id) {
+            app_clone.change_strength(change);
         }
     }));
 
     log_info!("Entering main event loop");
+    startup_timer.checkpoint("loop");
+    log_info!("{}", startup_timer.summary());
+    app.state.record_timing(&startup_timer);
 
     use windows::Win32::UI::WindowsAndMessaging::{PeekMessageW, TranslateMessage, DispatchMessageW, MSG, PM_REMOVE, WM_QUIT};
 
     let mut last_tray_update = std::time::Instant::now();
+    let mut last_fullscreen_check = std::time::Instant::now();
+    let mut last_power_check = std::time::Instant::now();
+    let mut last_conflict_check = None;
+    let mut last_on_battery = None;
+    app.update_battery_saver(&mut last_on_battery);
     let wakeup = Arc::clone(&app.wakeup);
 
     loop {
@@ -235,137 +693,56 @@ fn run_app() -> Result<()> {
             while PeekMessageW(&mut msg, None, 0, 0, PM_REMOVE).as_bool() {
                 if msg.message == WM_QUIT {
                     log_info!("WM_QUIT received, exiting");
-                    return Ok(());
+                    shutdown(&app, Some(tray));
+                    return Ok(ExitCode::SUCCESS);
                 }
                 let _ = TranslateMessage(&msg);
                 DispatchMessageW(&msg);
             }
         }
 
-        // Check if we should update tray (either timer elapsed or explicitly requested)
-        let should_update_tray = last_tray_update.elapsed() >= std::time::Duration::from_millis(100);
+        app.update_overlay_retry(&mut overlay_retry, display_power::take_display_changed());
+        app.overlay_manager.count_active_time();
 
-        if should_update_tray {
-            let tooltip = app.get_tooltip();
-            tray_icon.set_tooltip(Some(&tooltip)).ok();
+        if tray::take_taskbar_created() {
+            match tray.recreate(&app.state, &app.overlay_manager) {
+                Ok(()) => log_info!("Taskbar restarted, tray icon recreated"),
+                Err(e) => log_warn!("Taskbar restarted, failed to recreate tray icon: {:#}", e),
+            }
+            last_tray_update = std::time::Instant::now();
+        }
+
+        if last_power_check.elapsed() >= POWER_SOURCE_INTERVAL {
+            app.update_battery_saver(&mut last_on_battery);
+            last_power_check = std::time::Instant::now();
+        }
 
-            let overlay_running = app.overlay_manager.is_running();
-            overlay_item.set_checked(overlay_running);
+        let mut fullscreen_changed = false;
+        let mut conflict_changed = false;
+        if last_fullscreen_check.elapsed() >= std::time::Duration::from_millis(500) {
+            conflict_changed = app.update_color_conflict(&mut last_conflict_check);
+            fullscreen_changed = app.update_throttle();
+            last_fullscreen_check = std::time::Instant::now();
+        }
+        if conflict_changed {
+            // Shows or hides the banner even while the settings window is idle
+            app.gui.window().request_repaint();
+        }
 
+        let should_update_tray = fullscreen_changed
+            || conflict_changed
+            || app.tray_dirty.swap(false, Ordering::AcqRel)
+            || last_tray_update.elapsed() >= TRAY_STATUS_INTERVAL;
+
+        if should_update_tray {
+            tray.refresh(&app.state, &app.overlay_manager);
             last_tray_update = std::time::Instant::now();
         }
+        notifier.update(&app.state, &app.overlay_manager, taskbar_watcher.as_ref());
 
         let mut processed_toggle = false;
         while let Ok(cmd) = command_rx.try_recv() {
             match cmd {
-            AppCommand::OpenGui => {
-                if app.exit_requested.load(Ordering::Acquire) {
-                    log_info!("Exit requested, ignoring GUI open request");
-                    continue;
-                }
-
-                if app.gui_visible.swap(true, Ordering::AcqRel) {
-                    log_info!("GUI already open, ignoring duplicate open request");
-                    continue;
-                }
-
-                log_info!("Opening GUI window");
-
-                let state = Arc::clone(&app.state);
-                let overlay_manager = Arc::clone(&app.overlay_manager);
-                let gui_visible = Arc::clone(&app.gui_visible);
-                let command_tx_for_requeue = app.command_tx.clone();
-                let gui_ctx_storage = Arc::clone(&app.gui_ctx);
-                let wakeup = Arc::clone(&app.wakeup);
-
-                let (close_tx, close_rx) = bounded(1);
-                *app.gui_close_tx.lock() = Some(close_tx);
-
-                let (toggle_tx, toggle_rx) = bounded(1);
-                *app.gui_toggle_tx.lock() = Some(toggle_tx);
-
-                let native_options = eframe::NativeOptions {
-                    viewport: egui::ViewportBuilder::default()
-                        .with_inner_size([500.0, 600.0])
-                        .with_resizable(false)
-                        .with_decorations(false)
-                        .with_icon(load_window_icon()),
-                    run_and_return: true,
-                    ..Default::default()
-                };
-
-                let overlay_manager_for_gui = Arc::clone(&overlay_manager);
-                let overlay_manager_for_toggle = Arc::clone(&overlay_manager);
-                let overlay_manager_for_restart = Arc::clone(&overlay_manager);
-                let wakeup_for_toggle = Arc::clone(&wakeup);
-                let gui_ctx_storage_for_gui = Arc::clone(&gui_ctx_storage);
-                let state_for_gui = Arc::clone(&state);
-                let tray_icon_for_gui = tray_icon.clone();
-                let overlay_item_for_gui = overlay_item.clone();
-
-                let result = eframe::run_native(
-                    "ChromaBridge",
-                    native_options,
-                    Box::new(move |_cc| {
-                        let mut settings_gui = gui::SettingsGui::new(state_for_gui, overlay_manager_for_gui, gui_ctx_storage_for_gui);
-                        settings_gui.set_tray_items(tray_icon_for_gui, overlay_item_for_gui);
-                        settings_gui.set_close_receiver(close_rx);
-                        settings_gui.set_toggle_receiver(toggle_rx);
-                        settings_gui.set_overlay_toggle_callback(move || {
-                            let was_running = overlay_manager_for_toggle.is_running();
-                            overlay_manager_for_toggle.toggle();
-                            let state = if was_running { "OFF" } else { "ON" };
-                            log_info!("Overlay toggled from GUI: {}", state);
-                            wakeup_for_toggle.1.notify_one();
-                        });
-
-                        settings_gui.set_overlay_restart_callback(move || {
-                            if overlay_manager_for_restart.is_running() {
-                                log_info!("Restarting overlay (settings changed)");
-                                overlay_manager_for_restart.stop();
-                                overlay_manager_for_restart.start();
-                            }
-                        });
-
-                        Ok(Box::new(settings_gui))
-                    })
-                );
-
-                if let Err(e) = result {
-                    log_warn!("GUI window error: {:?}", e);
-                }
-                *app.gui_close_tx.lock() = None;
-                *app.gui_toggle_tx.lock() = None;
-                *gui_ctx_storage.lock() = None;
-                gui_visible.store(false, Ordering::Release);
-                log_info!("GUI window closed");
-
-                // Drain any buffered OpenGui commands to prevent immediate reopening
-                let mut drained = 0;
-                let mut other_commands = Vec::new();
-                while let Ok(cmd) = command_rx.try_recv() {
-                    match cmd {
-                        AppCommand::OpenGui => {
-                            drained += 1;
-                        }
-                        other => {
-                            other_commands.push(other);
-                        }
-                    }
-                }
-                for cmd in other_commands {
-                    let _ = command_tx_for_requeue.try_send(cmd);
-                }
-                if drained > 0 {
-                    log_info!("Drained {} buffered OpenGui commands", drained);
-                }
-
-                let keep_in_tray = state.read(|s| s.keep_running_in_tray);
-                if !keep_in_tray {
-                    log_info!("Keep in tray disabled - exiting application");
-                    return Ok(());
-                }
-            }
             AppCommand::ToggleOverlay => {
                 app.toggle_overlay();
                 processed_toggle = true;
@@ -373,98 +750,23 @@ fn run_app() -> Result<()> {
             AppCommand::Exit => {
                 log_info!("Exit command - shutting down application");
                 app.exit_requested.store(true, Ordering::Release);
-                return Ok(());
+                shutdown(&app, Some(tray));
+                return Ok(ExitCode::SUCCESS);
             }
             }
         }
 
         // Update tray immediately if we processed a toggle command
         if processed_toggle {
-            let tooltip = app.get_tooltip();
-            tray_icon.set_tooltip(Some(&tooltip)).ok();
-            let overlay_running = app.overlay_manager.is_running();
-            overlay_item.set_checked(overlay_running);
+            tray.refresh(&app.state, &app.overlay_manager);
             last_tray_update = std::time::Instant::now();
         }
 
+        // Also bounds how long tray menu messages wait to be pumped; state changes wake us early
         let mut guard = wakeup.0.lock();
-        let result = wakeup.1.wait_for(&mut guard, std::time::Duration::from_millis(100));
-
-        // If woken by notification (not timeout), update tray immediately
-        // This handles GUI button toggle which wakes us via condvar
-        if !result.timed_out() && !processed_toggle {
-            let tooltip = app.get_tooltip();
-            tray_icon.set_tooltip(Some(&tooltip)).ok();
-            let overlay_running = app.overlay_manager.is_running();
-            overlay_item.set_checked(overlay_running);
-            last_tray_update = std::time::Instant::now();
-        }
+        wakeup.1.wait_for(&mut guard, std::time::Duration::from_millis(100));
     }
 
     #[allow(unreachable_code)]
-    Ok(())
-}
-
-fn load_icon() -> Result<Icon> {
-    let icon_path = std::env::current_exe()?
-        .parent()
-        .ok_or_else(|| anyhow::anyhow!("Failed to get parent directory"))?
-        .join("icon.ico");
-
-    if icon_path.exists() {
-        match Icon::from_path(&icon_path, Some((32, 32))) {
-            Ok(icon) => {
-                log_info!("Loaded icon from {:?}", icon_path);
-                return Ok(icon);
-            }
-            Err(e) => {
-                log_warn!("Failed to load icon from {:?}: {}. Using fallback.", icon_path, e);
-            }
-        }
-    } else {
-        log_warn!("Icon file not found at {:?}. Using fallback.", icon_path);
-    }
-
-    let icon_rgba = {
-        let mut rgba = Vec::with_capacity(16 * 16 * 4);
-        for _ in 0..16 * 16 {
-            rgba.extend_from_slice(&[100, 150, 255, 255]);
-        }
-        rgba
-    };
-
-    Icon::from_rgba(icon_rgba, 16, 16)
-        .map_err(|e| anyhow::anyhow!("Failed to create fallback icon: {}", e))
-}
-
-fn load_window_icon() -> egui::IconData {
-    let icon_path = std::env::current_exe()
-        .ok()
-        .and_then(|p| p.parent().map(|p| p.to_path_buf()))
-        .map(|p| p.join("icon.ico"));
-
-    if let Some(path) = icon_path {
-        if path.exists() {
-            if let Ok(image) = image::open(&path) {
-                let rgba = image.to_rgba8();
-                let (width, height) = rgba.dimensions();
-                return egui::IconData {
-                    rgba: rgba.into_raw(),
-                    width,
-                    height,
-                };
-            }
-        }
-    }
-
-    let mut rgba = Vec::with_capacity(32 * 32 * 4);
-    for _ in 0..32 * 32 {
-        rgba.extend_from_slice(&[100, 150, 255, 255]);
-    }
-
-    egui::IconData {
-        rgba,
-        width: 32,
-        height: 32,
-    }
+    Ok(ExitCode::SUCCESS)
 }
@@ -0,0 +1,209 @@
+use anyhow::{Context, Result};
+use crate::hue_mapper::HueMapper;
+use crate::spectrum::{Interpolation, Spectrum, Spectrum2Mode, SpectrumFile, SpectrumMetadata, SpectrumNode};
+
+const SAMPLE_COUNT: usize = 360;
+
+pub type ColorMatrix = [[f32; 3]; 3];
+
+pub struct MatrixImport {
+    pub spectrum: Spectrum,
+    pub clipped_samples: usize,
+    pub max_error: f32,
+}
+
+impl MatrixImport {
+    pub fn to_spectrum_file(&self) -> SpectrumFile {
+        SpectrumFile {
+            metadata: SpectrumMetadata::default(),
+            spectra: vec![self.spectrum.clone()],
+            interpolation: Interpolation::Srgb,
+            spectrum2_mode: Spectrum2Mode::Explicit,
+        }
+    }
+}
+
+/// Parses a 3x3 matrix from JSON (`[[a, b, c], ...]`) or from nine comma/whitespace separated numbers.
+pub fn parse_matrix(text: &str) -> Result<ColorMatrix> {
+    let trimmed = text.trim();
+
+    if trimmed.starts_with('[') {
+        let rows: Vec<Vec<f32>> = serde_json::from_str(trimmed)
+            .context("Failed to parse matrix JSON")?;
+        if rows.len() != 3 || rows.iter().any(|r| r.len() != 3) {
+            anyhow::bail!("Matrix JSON must be a 3x3 array");
+        }
+        return Ok([
+            [rows[0][0], rows[0][1], rows[0][2]],
+            [rows[1][0], rows[1][1], rows[1][2]],
+            [rows[2][0], rows[2][1], rows[2][2]],
+        ]);
+    }
+
+    let values: Vec<f32> = trimmed
+        .split(|c: char| c == ',' || c == ';' || c.is_whitespace())
+        .filter(|v| !v.is_empty())
+        .map(|v| v.parse::<f32>().with_context(|| format!("Invalid matrix value: {}", v)))
+        .collect::<Result<_>>()?;
+
+    if values.len() != 9 {
+        anyhow::bail!("Matrix must contain exactly 9 values (found {})", values.len());
+    }
+
+    Ok([
+        [values[0], values[1], values[2]],
+        [values[3], values[4], values[5]],
+        [values[6], values[7], values[8]],
+    ])
+}
+
+/// Samples every hue through `matrix` and decimates the result down to at most `max_nodes` nodes.
+pub fn spectrum_from_matrix(matrix: &ColorMatrix, max_nodes: usize, tolerance: f32) -> MatrixImport {
+    let mut samples = Vec::with_capacity(SAMPLE_COUNT + 1);
+    let mut clipped_samples = 0;
+
+    for i in 0..SAMPLE_COUNT {
+        let (r, g, b) = HueMapper::hsv_to_rgb(i as f32, 1.0, 1.0);
+        let input = [r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0];
+
+        let mut output = [0.0f32; 3];
+        for (row, out) in matrix.iter().zip(output.iter_mut()) {
+            *out = row[0] * input[0] + row[1] * input[1] + row[2] * input[2];
+        }
+
+        if output.iter().any(|c| !(0.0..=1.0).contains(c)) {
+            clipped_samples += 1;
+        }
+
+        samples.push((
+            output[0].clamp(0.0, 1.0),
+            output[1].clamp(0.0, 1.0),
+            output[2].clamp(0.0, 1.0),
+        ));
+    }
+    // Close the loop so hue 360 maps back onto hue 0
+    samples.push(samples[0]);
+
+    let (kept, max_error) = decimate(&samples, max_nodes.max(2), tolerance);
+
+    let nodes = kept
+        .into_iter()
+        .map(|i| {
+            let (r, g, b) = samples[i];
+            SpectrumNode::new(
+                i as f32 / SAMPLE_COUNT as f32,
+                format!(
+                    "#{:02X}{:02X}{:02X}",
+                    (r * 255.0).round() as u8,
+                    (g * 255.0).round() as u8,
+                    (b * 255.0).round() as u8,
+                ),
+            )
+        })
+        .collect();
+
+    MatrixImport {
+        spectrum: Spectrum {
+            name: None,
+            description: None,
+            nodes,
+            interpolation: Interpolation::Srgb,
+        },
+        clipped_samples,
+        max_error,
+    }
+}
+
+/// Greedy insertion: start from the endpoints and keep adding the sample with the largest
+/// reconstruction error until the error drops below `tolerance` or `max_nodes` is reached.
+fn decimate(samples: &[(f32, f32, f32)], max_nodes: usize, tolerance: f32) -> (Vec<usize>, f32) {
+    let last = samples.len() - 1;
+    let mut kept = vec![0, last];
+
+    loop {
+        let mut worst = (0usize, 0.0f32);
+
+        for pair in kept.windows(2) {
+            let (a, b) = (pair[0], pair[1]);
+            for i in a + 1..b {
+                let t = (i - a) as f32 / (b - a) as f32;
+                let expected = samples[i];
+                let (ra, ga, ba) = samples[a];
+                let (rb, gb, bb) = samples[b];
+                let error = (ra + t * (rb - ra) - expected.0).abs()
+                    .max((ga + t * (gb - ga) - expected.1).abs())
+                    .max((ba + t * (bb - ba) - expected.2).abs());
+                if error > worst.1 {
+                    worst = (i, error);
+                }
+            }
+        }
+
+        if worst.1 <= tolerance || kept.len() >= max_nodes {
+            return (kept, worst.1);
+        }
+
+        let insert_at = kept.partition_point(|&k| k < worst.0);
+        kept.insert(insert_at, worst.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IDENTITY: ColorMatrix = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+
+    #[test]
+    fn parses_json_and_separated_values() {
+        let expected = [[0.5, 0.5, 0.0], [0.25, 0.75, 0.0], [0.0, 0.1, 0.9]];
+        assert_eq!(parse_matrix("[[0.5, 0.5, 0], [0.25, 0.75, 0], [0, 0.1, 0.9]]").unwrap(), expected);
+        assert_eq!(parse_matrix("0.5,0.5,0\n0.25,0.75,0\n0,0.1,0.9\n").unwrap(), expected);
+        assert_eq!(parse_matrix(" 0.5 0.5 0; 0.25 0.75 0; 0 0.1 0.9 ").unwrap(), expected);
+    }
+
+    #[test]
+    fn rejects_malformed_matrices() {
+        for text in [
+            "",
+            "[[1, 0, 0], [0, 1, 0]]",
+            "[[1, 0], [0, 1], [0, 0]]",
+            "[[1, 0, 0], [0, 1, 0], [0, 0, 1]",
+            "1, 0, 0, 0, 1, 0, 0, 0",
+            "1, 0, 0, 0, 1, 0, 0, 0, 1, 0",
+            "1, 0, 0, 0, one, 0, 0, 0, 1",
+        ] {
+            assert!(parse_matrix(text).is_err(), "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn counts_samples_outside_the_gamut() {
+        assert_eq!(spectrum_from_matrix(&IDENTITY, 36, 1.0 / 255.0).clipped_samples, 0);
+        // Every fully saturated hue has a channel at 1.0, which doubles past the gamut
+        let doubled = IDENTITY.map(|row| row.map(|v| v * 2.0));
+        assert_eq!(spectrum_from_matrix(&doubled, 36, 1.0 / 255.0).clipped_samples, SAMPLE_COUNT);
+        // Only hues with more than half red go past it
+        let red_doubled = [[2.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+        let clipped = spectrum_from_matrix(&red_doubled, 36, 1.0 / 255.0).clipped_samples;
+        assert!(clipped > 0 && clipped < SAMPLE_COUNT, "{}", clipped);
+    }
+
+    #[test]
+    fn decimation_respects_the_node_limit_and_error_bound() {
+        let tolerance = 1.0 / 255.0;
+        // The hue wheel is linear between multiples of 60°, so a few nodes reproduce it
+        let import = spectrum_from_matrix(&IDENTITY, 36, tolerance);
+        assert!(import.max_error <= tolerance, "{}", import.max_error);
+        assert!(import.spectrum.nodes.len() <= 12, "{}", import.spectrum.nodes.len());
+        assert_eq!(import.spectrum.nodes.first().unwrap().position, 0.0);
+        assert_eq!(import.spectrum.nodes.last().unwrap().position, 1.0);
+        assert!(import.spectrum.validate_strict().is_ok());
+
+        // Too few nodes for the bound: the limit wins and the error says by how much
+        let limited = spectrum_from_matrix(&IDENTITY, 4, tolerance);
+        assert_eq!(limited.spectrum.nodes.len(), 4);
+        assert!(limited.max_error > tolerance);
+        assert!(limited.spectrum.validate_strict().is_ok());
+    }
+}
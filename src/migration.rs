@@ -0,0 +1,207 @@
+use anyhow::{Context, Result};
+use rusqlite::{Connection, OpenFlags};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use crate::data_dirs::DataDirs;
+use crate::state::{AppState, StateManager};
+
+/// The data folder name from before the rename to ChromaBridge.
+pub const LEGACY_DIR_NAME: &str = "ColorInterlacer";
+
+/// Left in the new data folder once an import ran, so a deleted or reset database doesn't trigger another.
+pub(crate) const MARKER_FILE: &str = "imported-from-colorinterlacer";
+
+/// Settings files of older builds, newest first. The databases hold the same JSON as `state.db` now.
+const LEGACY_DATABASES: [&str; 2] = ["state.db", "config.db"];
+const LEGACY_CONFIG_JSON: &str = "config.json";
+
+/// Old setting names in config.json and their current names.
+const RENAMED_SETTINGS: [(&str, &str); 3] = [
+    ("colorblind_type", "spectrum_name"),
+    ("noise_pattern", "noise_texture"),
+    ("monitor", "last_monitor"),
+];
+
+/// What a one-time import from the ColorInterlacer folder brought over.
+#[derive(Debug, Clone, Default)]
+pub struct MigrationReport {
+    pub legacy_dir: PathBuf,
+    /// Spectrum, noise and other asset files copied.
+    pub copied_files: usize,
+    /// The file the settings were taken from; `None` when the defaults were kept.
+    pub settings_source: Option<String>,
+    /// Whatever could not be imported, with the reason, e.g. "setting 'strength': invalid type".
+    pub skipped: Vec<String>,
+}
+
+/// True when `app_data_dir` was just created and an old ColorInterlacer folder is waiting to be imported.
+pub fn should_import(legacy_dir: &Path, app_data_dir: &Path, fresh: bool) -> bool {
+    fresh && legacy_dir.is_dir() && !app_data_dir.join(MARKER_FILE).exists()
+}
+
+/// Copies the assets of `legacy_dir` into `dirs` and reads its settings, then leaves the marker
+/// file. Never fails as a whole: assets stay copied even if the settings can't be read, and
+/// everything left out is listed in the report. The old folder is not modified.
+pub fn import_legacy(legacy_dir: &Path, dirs: &DataDirs) -> (MigrationReport, Option<AppState>) {
+    let mut report = MigrationReport {
+        legacy_dir: legacy_dir.to_path_buf(),
+        ..Default::default()
+    };
+
+    copy_missing(&legacy_dir.join("assets"), &dirs.assets, &mut report.copied_files, &mut report.skipped);
+
+    let state = read_legacy_settings(legacy_dir, &mut report).map(|mut state| {
+        drop_missing_assets(&mut state, &dirs.assets, &mut report);
+        state
+    });
+
+    if let Err(e) = std::fs::write(dirs.local.join(MARKER_FILE), legacy_dir.display().to_string()) {
+        report.skipped.push(format!("import marker: {}", e));
+    }
+
+    (report, state)
+}
+
+/// Recursively copies files that don't exist in `to` yet, counting them in `copied_files` and
+/// listing what failed in `skipped`.
+pub(crate) fn copy_missing(from: &Path, to: &Path, copied_files: &mut usize, skipped: &mut Vec<String>) {
+    let entries = match std::fs::read_dir(from) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return,
+        Err(e) => {
+            skipped.push(format!("{}: {}", from.display(), e));
+            return;
+        }
+    };
+
+    for entry in entries.flatten() {
+        let source = entry.path();
+        let target = to.join(entry.file_name());
+        if source.is_dir() {
+            match std::fs::create_dir_all(&target) {
+                Ok(()) => copy_missing(&source, &target, copied_files, skipped),
+                Err(e) => skipped.push(format!("{}: {}", source.display(), e)),
+            }
+        } else if !target.exists() {
+            match std::fs::copy(&source, &target) {
+                Ok(_) => *copied_files += 1,
+                Err(e) => skipped.push(format!("{}: {}", source.display(), e)),
+            }
+        }
+    }
+}
+
+fn read_legacy_settings(legacy_dir: &Path, report: &mut MigrationReport) -> Option<AppState> {
+    for name in LEGACY_DATABASES {
+        let path = legacy_dir.join(name);
+        if !path.exists() {
+            continue;
+        }
+        match read_legacy_database(&path) {
+            Ok(state) => {
+                report.settings_source = Some(name.to_string());
+                return Some(state);
+            }
+            Err(e) => report.skipped.push(format!("settings ({}): {:#}", name, e)),
+        }
+    }
+
+    let path = legacy_dir.join(LEGACY_CONFIG_JSON);
+    if !path.exists() {
+        return None;
+    }
+    match std::fs::read_to_string(&path).context("Failed to read file").and_then(|json| parse_legacy_config(&json)) {
+        Ok((state, skipped)) => {
+            report.settings_source = Some(LEGACY_CONFIG_JSON.to_string());
+            report.skipped.extend(skipped);
+            Some(state)
+        }
+        Err(e) => {
+            report.skipped.push(format!("settings ({}): {:#}", LEGACY_CONFIG_JSON, e));
+            None
+        }
+    }
+}
+
+fn read_legacy_database(path: &Path) -> Result<AppState> {
+    // Read-only, so the old install keeps working if it is started again
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY).context("Failed to open database")?;
+    Ok(StateManager::load_state(&conn)?)
+}
+
+/// Lays the recognized settings of an old config.json over the defaults, one at a time, so a
+/// value of the wrong type only skips that setting. Unknown settings are ignored.
+fn parse_legacy_config(json: &str) -> Result<(AppState, Vec<String>)> {
+    let legacy: serde_json::Map<String, Value> = serde_json::from_str(json).context("Failed to parse JSON")?;
+    let mut merged = serde_json::to_value(AppState::default())?;
+    let mut skipped = Vec::new();
+
+    for (key, value) in legacy {
+        let key = RENAMED_SETTINGS
+            .iter()
+            .find(|(old, _)| *old == key)
+            .map_or(key.as_str(), |(_, new)| new)
+            .to_string();
+        let Some(slot) = merged.get_mut(&key) else {
+            continue;
+        };
+
+        let previous = std::mem::replace(slot, value);
+        if let Err(e) = serde_json::from_value::<AppState>(merged.clone()) {
+            skipped.push(format!("setting '{}': {}", key, e));
+            merged[&key] = previous;
+        }
+    }
+
+    Ok((serde_json::from_value(merged)?, skipped))
+}
+
+/// Clears a selected spectrum or noise texture whose file didn't come along.
+fn drop_missing_assets(state: &mut AppState, assets: &Path, report: &mut MigrationReport) {
+    if let Some(name) = state.spectrum_name.take_if(|name| !assets.join("spectrums").join(format!("{}.json", name)).exists()) {
+        report.skipped.push(format!("spectrum '{}': file not found", name));
+    }
+    if let Some(name) = state.noise_texture.take_if(|name| !assets.join("noise").join(format!("{}.png", name)).exists()) {
+        report.skipped.push(format!("noise texture '{}': file not found", name));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_dirs::DataDirLayout;
+    use crate::test_util::temp_dir;
+
+    #[test]
+    fn legacy_config_is_mapped_setting_by_setting() {
+        let json = r#"{"colorblind_type": "deutan", "strength": "strong", "debug_overlay": true, "window_x": 40}"#;
+        let (state, skipped) = parse_legacy_config(json).unwrap();
+
+        assert_eq!(state.spectrum_name.as_deref(), Some("deutan"));
+        assert!(state.debug_overlay);
+        assert_eq!(state.strength, AppState::default().strength);
+        assert_eq!(skipped.len(), 1);
+        assert!(skipped[0].starts_with("setting 'strength'"), "{}", skipped[0]);
+    }
+
+    #[test]
+    fn assets_are_copied_when_settings_are_unreadable() {
+        let legacy = temp_dir("legacy");
+        let app = temp_dir("imported");
+        std::fs::create_dir_all(legacy.join("assets").join("spectrums")).unwrap();
+        std::fs::write(legacy.join("assets").join("spectrums").join("deutan.json"), "{}").unwrap();
+        std::fs::write(legacy.join(LEGACY_CONFIG_JSON), "not json").unwrap();
+
+        assert!(should_import(&legacy, &app, true));
+        let (report, state) = import_legacy(&legacy, &DataDirs::single(app.clone(), DataDirLayout::Portable));
+        assert!(state.is_none());
+        assert_eq!(report.copied_files, 1);
+        assert!(app.join("assets").join("spectrums").join("deutan.json").exists());
+        assert!(report.skipped.iter().any(|s| s.starts_with("settings (config.json)")));
+        // Never twice
+        assert!(!should_import(&legacy, &app, true));
+
+        let _ = std::fs::remove_dir_all(&legacy);
+        let _ = std::fs::remove_dir_all(&app);
+    }
+}
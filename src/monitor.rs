@@ -0,0 +1,189 @@
+use crate::log_warn;
+use thiserror::Error;
+
+/// Parts of the adapter description of display drivers that make up virtual monitors, matched
+/// without case. Streamers use them as a canvas nobody looks at directly.
+const VIRTUAL_DISPLAY_DRIVERS: &[&str] = &[
+    "iddsampledriver",
+    "indirect display",
+    "virtual display",
+    "virtual monitor",
+    "parsec virtual",
+    "usbmmidd",
+    "spacedesk",
+];
+
+/// Smallest monitor the overlay runs on. Remote desktop sessions, virtual display adapters and
+/// some docks report outputs of 0x0 or a few pixels, which no swap chain can cover.
+pub const MIN_MONITOR_SIZE: (i32, i32) = (160, 120);
+/// Largest monitor side the overlay runs on, the Direct3D 11 texture size limit.
+pub const MAX_MONITOR_SIZE: i32 = 16384;
+
+/// Assumed when the display mode reports no usable refresh rate.
+pub const DEFAULT_REFRESH_RATE: u32 = 60;
+/// Highest refresh rate taken as reported; above it the driver is assumed to be wrong.
+const MAX_REFRESH_RATE: u32 = 1000;
+
+/// A monitor as the settings window lists it.
+#[derive(Debug, Clone)]
+pub struct MonitorInfo {
+    pub index: usize,
+    pub name: String,
+    pub is_primary: bool,
+    /// Top-left corner on the virtual desktop.
+    pub left: i32,
+    pub top: i32,
+    pub width: i32,
+    pub height: i32,
+    pub refresh_rate: u32,
+    /// The description of the adapter driving the monitor, e.g. "IddSampleDriver Device";
+    /// empty if Windows didn't give one.
+    pub device_string: String,
+}
+
+impl MonitorInfo {
+    /// Why the overlay can't run on this monitor, `None` if it can.
+    pub fn unusable(&self) -> Option<UnusableMonitor> {
+        check_size(self.width, self.height).err()
+    }
+
+    /// Whether the monitor only exists in software, see [`is_virtual_display`].
+    pub fn is_virtual(&self) -> bool {
+        is_virtual_display(&self.device_string)
+    }
+}
+
+/// Whether `device_string`, an adapter description from [`device_string`], belongs to a virtual
+/// display driver. These are corrected like any other monitor.
+pub fn is_virtual_display(device_string: &str) -> bool {
+    let device_string = device_string.to_lowercase();
+    VIRTUAL_DISPLAY_DRIVERS.iter().any(|driver| device_string.contains(driver))
+}
+
+/// The description of the adapter driving `device` (e.g. `\\.\DISPLAY3`) as
+/// `EnumDisplayDevicesW` reports it, `None` if no adapter drives it.
+#[cfg(windows)]
+pub fn device_string(device: &str) -> Option<String> {
+    use windows::core::PCWSTR;
+    use windows::Win32::Graphics::Gdi::{EnumDisplayDevicesW, DISPLAY_DEVICEW};
+
+    let text = |wide: &[u16]| String::from_utf16_lossy(&wide[..wide.iter().position(|&c| c == 0).unwrap_or(wide.len())]);
+    for index in 0.. {
+        let mut display_device: DISPLAY_DEVICEW = unsafe { std::mem::zeroed() };
+        display_device.cb = std::mem::size_of::<DISPLAY_DEVICEW>() as u32;
+        // A null device enumerates the adapters, each named after the display it drives
+        if !unsafe { EnumDisplayDevicesW(PCWSTR::null(), index, &mut display_device, 0) }.as_bool() {
+            return None;
+        }
+        if text(&display_device.DeviceName) == device {
+            return Some(text(&display_device.DeviceString).trim().to_string());
+        }
+    }
+    None
+}
+
+#[cfg(not(windows))]
+pub fn device_string(_device: &str) -> Option<String> {
+    None
+}
+
+/// A monitor whose reported size the overlay can't work with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum UnusableMonitor {
+    #[error("reports {width}x{height}, smaller than the minimum of {}x{}", MIN_MONITOR_SIZE.0, MIN_MONITOR_SIZE.1)]
+    TooSmall { width: i32, height: i32 },
+    #[error("reports {width}x{height}, larger than the maximum of {}x{}", MAX_MONITOR_SIZE, MAX_MONITOR_SIZE)]
+    TooLarge { width: i32, height: i32 },
+}
+
+/// Checks a monitor size from enumeration before anything is sized to it.
+pub fn check_size(width: i32, height: i32) -> Result<(), UnusableMonitor> {
+    if width < MIN_MONITOR_SIZE.0 || height < MIN_MONITOR_SIZE.1 {
+        return Err(UnusableMonitor::TooSmall { width, height });
+    }
+    if width > MAX_MONITOR_SIZE || height > MAX_MONITOR_SIZE {
+        return Err(UnusableMonitor::TooLarge { width, height });
+    }
+    Ok(())
+}
+
+/// The refresh rate `EnumDisplaySettingsW` reported for `device`, `None` if the call failed.
+/// 0 and 1 mean the hardware default and, like rates no display runs at, are replaced by
+/// `DEFAULT_REFRESH_RATE`.
+///
+/// Virtual displays often report 1Hz; they are still used, the warning just says why.
+pub fn refresh_rate_or_default(device: &str, reported: Option<u32>, virtual_display: bool) -> u32 {
+    match reported {
+        Some(rate @ 2..=MAX_REFRESH_RATE) => rate,
+        Some(rate) if virtual_display => {
+            log_warn!("Virtual display {} reports a refresh rate of {}Hz, as virtual displays often do; assuming {}Hz", device, rate, DEFAULT_REFRESH_RATE);
+            DEFAULT_REFRESH_RATE
+        }
+        Some(rate) => {
+            log_warn!("{} reports a refresh rate of {}Hz, assuming {}Hz", device, rate, DEFAULT_REFRESH_RATE);
+            DEFAULT_REFRESH_RATE
+        }
+        None => {
+            log_warn!("Failed to read the display mode of {}, assuming {}Hz", device, DEFAULT_REFRESH_RATE);
+            DEFAULT_REFRESH_RATE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn monitor(width: i32, height: i32) -> MonitorInfo {
+        MonitorInfo {
+            index: 0,
+            name: r"\\.\DISPLAY1".to_string(),
+            is_primary: true,
+            left: 0,
+            top: 0,
+            width,
+            height,
+            refresh_rate: 60,
+            device_string: "NVIDIA GeForce RTX 3070".to_string(),
+        }
+    }
+
+    #[test]
+    fn odd_monitor_sizes_are_unusable() {
+        assert_eq!(monitor(1920, 1080).unusable(), None);
+        assert_eq!(monitor(160, 120).unusable(), None);
+        assert_eq!(monitor(16384, 2160).unusable(), None);
+        // Portrait monitors are narrower than tall
+        assert_eq!(monitor(1080, 1920).unusable(), None);
+
+        assert_eq!(monitor(0, 0).unusable(), Some(UnusableMonitor::TooSmall { width: 0, height: 0 }));
+        assert_eq!(monitor(1024, 1).unusable(), Some(UnusableMonitor::TooSmall { width: 1024, height: 1 }));
+        assert_eq!(monitor(-1920, 1080).unusable(), Some(UnusableMonitor::TooSmall { width: -1920, height: 1080 }));
+        assert_eq!(monitor(32768, 1080).unusable(), Some(UnusableMonitor::TooLarge { width: 32768, height: 1080 }));
+    }
+
+    #[test]
+    fn unusable_refresh_rates_fall_back_to_60hz() {
+        assert_eq!(refresh_rate_or_default("test", Some(144), false), 144);
+        assert_eq!(refresh_rate_or_default("test", Some(0), false), DEFAULT_REFRESH_RATE);
+        assert_eq!(refresh_rate_or_default("test", Some(1), false), DEFAULT_REFRESH_RATE);
+        assert_eq!(refresh_rate_or_default("test", Some(100_000), false), DEFAULT_REFRESH_RATE);
+        assert_eq!(refresh_rate_or_default("test", None, false), DEFAULT_REFRESH_RATE);
+        assert_eq!(refresh_rate_or_default("test", Some(1), true), DEFAULT_REFRESH_RATE);
+        assert_eq!(refresh_rate_or_default("test", Some(30), true), 30);
+    }
+
+    #[test]
+    fn virtual_displays_are_told_apart_by_their_driver() {
+        assert!(!monitor(1920, 1080).is_virtual());
+        assert!(is_virtual_display("IddSampleDriver Device"));
+        assert!(is_virtual_display("Parsec Virtual Display Adapter"));
+        assert!(is_virtual_display("Virtual Display Driver"));
+        assert!(!is_virtual_display("AMD Radeon RX 6800"));
+        assert!(!is_virtual_display(""));
+        // Virtual or not, the same size limits apply
+        let headless = MonitorInfo { device_string: "IddSampleDriver Device".to_string(), ..monitor(3840, 2160) };
+        assert!(headless.is_virtual());
+        assert_eq!(headless.unusable(), None);
+    }
+}
@@ -0,0 +1,194 @@
+use crate::gui::MonitorInfo;
+use chromabridge::{log_error, log_warn};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use windows::{
+    core::w,
+    Win32::Foundation::{COLORREF, HINSTANCE, HWND, LPARAM, LRESULT, RECT, WPARAM},
+    Win32::Graphics::Gdi::{
+        BeginPaint, CreateFontW, CreateSolidBrush, DeleteObject, DrawTextW, EndPaint, FillRect, SelectObject, SetBkMode,
+        SetTextColor, CLEARTYPE_QUALITY, CLIP_DEFAULT_PRECIS, DEFAULT_CHARSET, DT_CENTER, DT_SINGLELINE, DT_VCENTER,
+        FF_DONTCARE, FW_BOLD, OUT_DEFAULT_PRECIS, PAINTSTRUCT, TRANSPARENT,
+    },
+    Win32::System::LibraryLoader::GetModuleHandleW,
+    Win32::UI::WindowsAndMessaging::{
+        CreateWindowExW, DefWindowProcW, DestroyWindow, DispatchMessageW, GetClientRect, GetMessageW, GetWindowLongPtrW,
+        IsWindow, RegisterClassW, SetLayeredWindowAttributes, SetTimer, SetWindowLongPtrW, ShowWindow, TranslateMessage,
+        GWLP_USERDATA, HTTRANSPARENT, LWA_ALPHA, MSG, SW_SHOWNOACTIVATE, WM_NCHITTEST, WM_PAINT, WM_TIMER, WNDCLASSW,
+        WS_EX_LAYERED, WS_EX_NOACTIVATE, WS_EX_TOOLWINDOW, WS_EX_TOPMOST, WS_EX_TRANSPARENT, WS_POPUP,
+    },
+};
+
+/// How long the numbers stay on screen.
+const FLASH_DURATION: Duration = Duration::from_secs(2);
+
+/// Badge size as a fraction of the monitor's height.
+const BADGE_FRACTION: f32 = 0.3;
+
+const BADGE_OPACITY: u8 = 220;
+const BACKGROUND: COLORREF = COLORREF(0x00302820);
+const FOREGROUND: COLORREF = COLORREF(0x00FFFFFF);
+
+const CLOSE_TIMER_ID: usize = 1;
+
+// A second flash while one is showing would only stack identical windows on top of it
+static FLASHING: AtomicBool = AtomicBool::new(false);
+
+/// A monitor to flash and the number shown on it.
+struct Badge {
+    number: usize,
+    rect: RECT,
+}
+
+/// Flashes a large number on each of `monitors` for two seconds: its position in the monitor
+/// dropdown, counting from 1.
+///
+/// The numbers are drawn by click-through topmost windows of their own class on a helper thread,
+/// which also destroys them, so they go away even when the settings window closes first. Sizes
+/// are taken from the monitor rectangles in physical pixels, so each badge scales with its own
+/// monitor under mixed DPI.
+pub fn flash_all(monitors: &[MonitorInfo]) {
+    flash(monitors.iter().enumerate().map(|(position, monitor)| badge(position, monitor)).collect());
+}
+
+/// Flashes only the monitor at `position` in `monitors`, as confirmation after picking it.
+pub fn flash_one(monitors: &[MonitorInfo], position: usize) {
+    if let Some(monitor) = monitors.get(position) {
+        flash(vec![badge(position, monitor)]);
+    }
+}
+
+fn badge(position: usize, monitor: &MonitorInfo) -> Badge {
+    let size = (monitor.height as f32 * BADGE_FRACTION) as i32;
+    let left = monitor.left + (monitor.width - size) / 2;
+    let top = monitor.top + (monitor.height - size) / 2;
+    Badge {
+        number: position + 1,
+        rect: RECT { left, top, right: left + size, bottom: top + size },
+    }
+}
+
+fn flash(badges: Vec<Badge>) {
+    if badges.is_empty() || FLASHING.swap(true, Ordering::AcqRel) {
+        return;
+    }
+
+    let spawned = std::thread::Builder::new().name("monitor-identify".into()).spawn(move || {
+        unsafe { show_badges(&badges) };
+        FLASHING.store(false, Ordering::Release);
+    });
+    if let Err(e) = spawned {
+        log_error!("Failed to spawn monitor identification thread: {}", e);
+        FLASHING.store(false, Ordering::Release);
+    }
+}
+
+/// Creates one window per badge and pumps messages until their timers destroyed all of them.
+unsafe fn show_badges(badges: &[Badge]) {
+    let class_name = w!("ChromaBridgeMonitorIdentify");
+    let hinstance = match GetModuleHandleW(None) {
+        Ok(hinstance) => hinstance,
+        Err(e) => {
+            log_error!("Failed to get module handle for monitor identification: {:?}", e);
+            return;
+        }
+    };
+
+    let wc = WNDCLASSW {
+        lpfnWndProc: Some(window_proc),
+        hInstance: hinstance.into(),
+        lpszClassName: class_name,
+        ..Default::default()
+    };
+    // Fails harmlessly when an earlier flash already registered the class
+    RegisterClassW(&wc);
+
+    let mut windows = Vec::with_capacity(badges.len());
+    for badge in badges {
+        let rect = badge.rect;
+        let created = CreateWindowExW(
+            WS_EX_TOPMOST | WS_EX_LAYERED | WS_EX_TRANSPARENT | WS_EX_TOOLWINDOW | WS_EX_NOACTIVATE,
+            class_name,
+            w!("ChromaBridge Monitor"),
+            WS_POPUP,
+            rect.left, rect.top, rect.right - rect.left, rect.bottom - rect.top,
+            None, None,
+            Some(HINSTANCE(hinstance.0)),
+            None,
+        );
+        let hwnd = match created {
+            Ok(hwnd) => hwnd,
+            Err(e) => {
+                log_warn!("Failed to create identification window for monitor {}: {:?}", badge.number, e);
+                continue;
+            }
+        };
+
+        // Read by window_proc when painting
+        SetWindowLongPtrW(hwnd, GWLP_USERDATA, badge.number as isize);
+        let _ = SetLayeredWindowAttributes(hwnd, COLORREF(0), BADGE_OPACITY, LWA_ALPHA);
+        SetTimer(Some(hwnd), CLOSE_TIMER_ID, FLASH_DURATION.as_millis() as u32, None);
+        let _ = ShowWindow(hwnd, SW_SHOWNOACTIVATE);
+        windows.push(hwnd);
+    }
+
+    // Each window destroys itself on its timer; the loop ends with the last of them
+    let mut msg = MSG::default();
+    while windows.iter().any(|&hwnd| IsWindow(Some(hwnd)).as_bool()) {
+        if !GetMessageW(&mut msg, None, 0, 0).as_bool() {
+            break;
+        }
+        let _ = TranslateMessage(&msg);
+        DispatchMessageW(&msg);
+    }
+}
+
+unsafe extern "system" fn window_proc(hwnd: HWND, msg: u32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    match msg {
+        WM_PAINT => {
+            paint(hwnd);
+            LRESULT(0)
+        }
+        WM_TIMER if wparam.0 == CLOSE_TIMER_ID => {
+            let _ = DestroyWindow(hwnd);
+            LRESULT(0)
+        }
+        WM_NCHITTEST => LRESULT(HTTRANSPARENT as isize),
+        _ => DefWindowProcW(hwnd, msg, wparam, lparam),
+    }
+}
+
+unsafe fn paint(hwnd: HWND) {
+    let number = GetWindowLongPtrW(hwnd, GWLP_USERDATA);
+    let mut ps = PAINTSTRUCT::default();
+    let hdc = BeginPaint(hwnd, &mut ps);
+
+    let mut rect = RECT::default();
+    let _ = GetClientRect(hwnd, &mut rect);
+    let brush = CreateSolidBrush(BACKGROUND);
+    FillRect(hdc, &rect, brush);
+    let _ = DeleteObject(brush.into());
+
+    // The window is sized in physical pixels of its monitor, so the font follows it
+    let font = CreateFontW(
+        (rect.bottom - rect.top) * 3 / 4,
+        0, 0, 0,
+        FW_BOLD.0 as i32,
+        0, 0, 0,
+        DEFAULT_CHARSET,
+        OUT_DEFAULT_PRECIS,
+        CLIP_DEFAULT_PRECIS,
+        CLEARTYPE_QUALITY,
+        FF_DONTCARE.0 as u32,
+        w!("Segoe UI"),
+    );
+    let previous = SelectObject(hdc, font.into());
+    SetBkMode(hdc, TRANSPARENT);
+    SetTextColor(hdc, FOREGROUND);
+    let mut text: Vec<u16> = number.to_string().encode_utf16().collect();
+    DrawTextW(hdc, &mut text, &mut rect, DT_CENTER | DT_VCENTER | DT_SINGLELINE);
+    SelectObject(hdc, previous);
+    let _ = DeleteObject(font.into());
+
+    let _ = EndPaint(hwnd, &ps);
+}
@@ -1,30 +1,160 @@
-use anyhow::{Context, Result};
-use image::ImageReader;
+use crate::error::NoiseError;
+use image::{DynamicImage, ImageReader};
+use std::fs::File;
+use std::io::{BufReader, ErrorKind, Read};
 use std::path::Path;
 
+/// Most spectra a noise texture can select between.
+pub const MAX_NOISE_INDICES: usize = 4;
+
+/// Default for the largest width and height of a noise texture, see `load_with_max_size`.
+pub const DEFAULT_MAX_NOISE_SIZE: u32 = 4096;
+
+/// The PNG signature followed by the IHDR chunk's length, type, 13 data bytes and CRC.
+const PNG_HEADER_LEN: usize = 33;
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+
+/// A noise file's PNG header, as read by `NoiseTexture::probe`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NoiseHeader {
+    pub width: u32,
+    pub height: u32,
+    pub bit_depth: u8,
+    /// 0 greyscale, 2 RGB, 3 indexed, 4 greyscale with alpha, 6 RGBA.
+    pub color_type: u8,
+}
+
+/// How a noise texture's pixels select a spectrum.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NoiseKind {
+    /// Greyscale: bright pixels select the primary spectrum, dark ones the secondary.
+    Mask,
+    /// Color: the strongest of red, green and blue selects spectrum 1, 2 or 3, black the fourth.
+    Channels,
+    /// Indexed PNG: the palette index selects the spectrum.
+    Palette,
+}
+
 pub struct NoiseTexture {
     width: u32,
     height: u32,
-    data: Vec<bool>,
+    kind: NoiseKind,
+    /// Spectrum index per pixel, row by row.
+    indices: Vec<u8>,
+    index_count: usize,
 }
 
 impl NoiseTexture {
-    pub fn load_from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
-        let img = ImageReader::open(path.as_ref())
-            .context("Failed to open noise texture file")?
+    pub fn load_from_file<P: AsRef<Path>>(path: P) -> Result<Self, NoiseError> {
+        Self::load_with_max_size(path, DEFAULT_MAX_NOISE_SIZE)
+    }
+
+    /// Like `load_from_file`, refusing files wider or taller than `max_size` pixels. The size is
+    /// read from the file header, so oversized files are never decoded.
+    pub fn load_with_max_size<P: AsRef<Path>>(path: P, max_size: u32) -> Result<Self, NoiseError> {
+        let path = path.as_ref();
+        if let Some(texture) = Self::load_palette(path, max_size)? {
+            return Ok(texture);
+        }
+
+        let (width, height) = image::image_dimensions(path).map_err(decode_error(path))?;
+        check_size(width, height, max_size)?;
+
+        let img = ImageReader::open(path)
+            .map_err(|source| NoiseError::Io { path: path.to_path_buf(), source })?
             .decode()
-            .context("Failed to decode noise texture")?;
+            .map_err(decode_error(path))?;
 
-        let gray = img.to_luma8();
-        let (width, height) = gray.dimensions();
+        Ok(Self::from_image(&img))
+    }
 
-        let data: Vec<bool> = gray.pixels().map(|p| p.0[0] > 128).collect();
+    /// Checks `path` the way `load_from_file` would, from its PNG header alone.
+    pub fn probe<P: AsRef<Path>>(path: P) -> Result<NoiseHeader, NoiseError> {
+        Self::probe_with_max_size(path, DEFAULT_MAX_NOISE_SIZE)
+    }
 
-        Ok(Self {
-            width,
-            height,
-            data,
-        })
+    /// Reads only the signature and IHDR chunk of `path` and applies the format and size rules
+    /// of `load_with_max_size`, without decoding any pixels. Cheap enough for listing files; one
+    /// that passes can still fail to load if its image data is damaged.
+    pub fn probe_with_max_size<P: AsRef<Path>>(path: P, max_size: u32) -> Result<NoiseHeader, NoiseError> {
+        let path = path.as_ref();
+        let mut bytes = [0u8; PNG_HEADER_LEN];
+        File::open(path).and_then(|mut file| file.read_exact(&mut bytes)).map_err(|source| match source.kind() {
+            ErrorKind::UnexpectedEof => decode_error(path)("File is shorter than a PNG header"),
+            _ => NoiseError::Io { path: path.to_path_buf(), source },
+        })?;
+
+        let header = parse_png_header(&bytes).map_err(decode_error(path))?;
+        check_size(header.width, header.height, max_size)?;
+        Ok(header)
+    }
+
+    /// Indexed PNGs with a colored palette; `None` for anything else, including palettes of greys,
+    /// which are masks saved with a palette.
+    fn load_palette(path: &Path, max_size: u32) -> Result<Option<Self>, NoiseError> {
+        let file = File::open(path).map_err(|source| NoiseError::Io { path: path.to_path_buf(), source })?;
+        let mut decoder = png::Decoder::new(BufReader::new(file));
+        decoder.set_transformations(png::Transformations::IDENTITY);
+        let Ok(mut reader) = decoder.read_info() else {
+            return Ok(None);
+        };
+
+        let info = reader.info();
+        if info.color_type != png::ColorType::Indexed {
+            return Ok(None);
+        }
+        let greys = info.palette.as_deref().unwrap_or_default().chunks(3).all(|c| c[0] == c[1] && c[1] == c[2]);
+        if greys {
+            return Ok(None);
+        }
+        check_size(info.width, info.height, max_size)?;
+        let bit_depth = info.bit_depth as u8;
+
+        let Some(buffer_size) = reader.output_buffer_size() else {
+            return Err(decode_error(path)("Noise texture too large"));
+        };
+        let mut buffer = vec![0; buffer_size];
+        let frame = reader.next_frame(&mut buffer).map_err(decode_error(path))?;
+
+        let indices: Vec<u8> = buffer[..frame.buffer_size()]
+            .chunks(frame.line_size)
+            .flat_map(|row| unpack_row(row, bit_depth, frame.width))
+            .collect();
+        Ok(Some(Self::new(frame.width, frame.height, NoiseKind::Palette, indices)))
+    }
+
+    /// Classifies a decoded image; indexed PNGs must go through `load_from_file` to keep their palette indices.
+    pub fn from_image(img: &DynamicImage) -> Self {
+        let mask_index = |value: u8| -> u8 { if value > 128 { 0 } else { 1 } };
+        if !img.color().has_color() {
+            let luma = img.to_luma8();
+            let (width, height) = luma.dimensions();
+            let indices = luma.pixels().map(|p| mask_index(p.0[0])).collect();
+            return Self::new(width, height, NoiseKind::Mask, indices);
+        }
+
+        let rgb = img.to_rgb8();
+        let (width, height) = rgb.dimensions();
+
+        // Greyscale files saved as RGB are still masks
+        if rgb.pixels().all(|p| p.0[0] == p.0[1] && p.0[1] == p.0[2]) {
+            let indices = rgb.pixels().map(|p| mask_index(p.0[0])).collect();
+            return Self::new(width, height, NoiseKind::Mask, indices);
+        }
+
+        let indices = rgb
+            .pixels()
+            .map(|p| {
+                let strongest = (0..3).max_by_key(|&c| (p.0[c], std::cmp::Reverse(c))).unwrap_or(0);
+                if p.0[strongest] > 128 { strongest as u8 } else { 3 }
+            })
+            .collect();
+        Self::new(width, height, NoiseKind::Channels, indices)
+    }
+
+    fn new(width: u32, height: u32, kind: NoiseKind, indices: Vec<u8>) -> Self {
+        let index_count = indices.iter().max().map_or(1, |&max| max as usize + 1);
+        Self { width, height, kind, indices, index_count }
     }
 
     pub fn width(&self) -> u32 {
@@ -35,6 +165,21 @@ impl NoiseTexture {
         self.height
     }
 
+    pub fn kind(&self) -> NoiseKind {
+        self.kind
+    }
+
+    /// Spectrum index per pixel, row by row. Not clamped to `MAX_NOISE_INDICES`.
+    pub fn indices(&self) -> &[u8] {
+        &self.indices
+    }
+
+    /// Number of spectra the texture refers to, i.e. its highest index plus one.
+    pub fn index_count(&self) -> usize {
+        self.index_count
+    }
+
+    /// True where the texture selects the primary spectrum.
     pub fn sample(&self, display_x: u32, display_y: u32, display_width: u32, display_height: u32) -> bool {
         let texture_aspect = self.width as f32 / self.height as f32;
         let display_aspect = display_width as f32 / display_height as f32;
@@ -62,6 +207,199 @@ impl NoiseTexture {
         let tex_y = ((adjusted_y as f32 * scale_y) as u32).min(self.height - 1);
 
         let idx = (tex_y * self.width + tex_x) as usize;
-        self.data.get(idx).copied().unwrap_or(false)
+        self.indices.get(idx).is_some_and(|&index| index == 0)
+    }
+}
+
+fn check_size(width: u32, height: u32, max_size: u32) -> Result<(), NoiseError> {
+    if width > max_size || height > max_size {
+        return Err(NoiseError::TooLarge { width, height, max_size });
+    }
+    Ok(())
+}
+
+/// Validates the PNG signature and IHDR chunk at the start of a file.
+fn parse_png_header(bytes: &[u8; PNG_HEADER_LEN]) -> Result<NoiseHeader, String> {
+    if bytes[..8] != PNG_SIGNATURE {
+        return Err("Not a PNG file".into());
+    }
+    let be_u32 = |at: usize| u32::from_be_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]]);
+    if be_u32(8) != 13 || &bytes[12..16] != b"IHDR" {
+        return Err("PNG doesn't start with an IHDR chunk".into());
+    }
+    let mut crc = flate2::Crc::new();
+    crc.update(&bytes[12..29]);
+    if crc.sum() != be_u32(29) {
+        return Err("PNG header checksum mismatch".into());
+    }
+
+    let header = NoiseHeader {
+        width: be_u32(16),
+        height: be_u32(20),
+        bit_depth: bytes[24],
+        color_type: bytes[25],
+    };
+    if header.width == 0 || header.height == 0 {
+        return Err(format!("PNG header reports a {}x{} image", header.width, header.height));
+    }
+    let valid_depth = match header.color_type {
+        0 => matches!(header.bit_depth, 1 | 2 | 4 | 8 | 16),
+        3 => matches!(header.bit_depth, 1 | 2 | 4 | 8),
+        2 | 4 | 6 => matches!(header.bit_depth, 8 | 16),
+        _ => false,
+    };
+    if !valid_depth {
+        return Err(format!("PNG color type {} with bit depth {} is invalid", header.color_type, header.bit_depth));
+    }
+    Ok(header)
+}
+
+fn decode_error<E: Into<Box<dyn std::error::Error + Send + Sync>>>(path: &Path) -> impl FnOnce(E) -> NoiseError + '_ {
+    move |source| NoiseError::Decode { path: path.to_path_buf(), source: source.into() }
+}
+
+/// Splits a row of 1, 2, 4 or 8 bit palette indices, most significant bits first, into one byte each.
+fn unpack_row(row: &[u8], bit_depth: u8, width: u32) -> Vec<u8> {
+    let bits = bit_depth as usize;
+    let mask = ((1u16 << bits) - 1) as u8;
+    (0..width as usize)
+        .map(|x| {
+            let bit = x * bits;
+            let shift = 8 - bits - bit % 8;
+            (row.get(bit / 8).copied().unwrap_or(0) >> shift) & mask
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+    use image::{GrayImage, Luma, Rgb, RgbImage};
+
+    #[test]
+    fn greyscale_keeps_mask_semantics() {
+        let grey = GrayImage::from_fn(4, 1, |x, _| Luma([[0, 128, 129, 255][x as usize]]));
+        let texture = NoiseTexture::from_image(&DynamicImage::ImageLuma8(grey));
+        assert_eq!(texture.kind(), NoiseKind::Mask);
+        assert_eq!(texture.indices(), &[1, 1, 0, 0]);
+        assert_eq!(texture.index_count(), 2);
+
+        // The same mask saved as RGB
+        let rgb = RgbImage::from_fn(2, 1, |x, _| Rgb([255 * x as u8; 3]));
+        let texture = NoiseTexture::from_image(&DynamicImage::ImageRgb8(rgb));
+        assert_eq!(texture.kind(), NoiseKind::Mask);
+        assert_eq!(texture.indices(), &[1, 0]);
+    }
+
+    #[test]
+    fn color_selects_by_strongest_channel() {
+        let colors = [[200, 10, 10], [10, 200, 10], [10, 10, 200], [20, 20, 20], [255, 255, 0]];
+        let rgb = RgbImage::from_fn(5, 1, |x, _| Rgb(colors[x as usize]));
+        let texture = NoiseTexture::from_image(&DynamicImage::ImageRgb8(rgb));
+        assert_eq!(texture.kind(), NoiseKind::Channels);
+        assert_eq!(texture.indices(), &[0, 1, 2, 3, 0]);
+        assert_eq!(texture.index_count(), 4);
+    }
+
+    #[test]
+    fn packed_palette_rows_unpack() {
+        assert_eq!(unpack_row(&[0b1010_0000], 1, 4), vec![1, 0, 1, 0]);
+        assert_eq!(unpack_row(&[0b0001_1011, 0b1100_0000], 2, 5), vec![0, 1, 2, 3, 3]);
+        assert_eq!(unpack_row(&[0x3F], 4, 2), vec![3, 15]);
+        assert_eq!(unpack_row(&[7, 9], 8, 2), vec![7, 9]);
+    }
+
+    #[test]
+    fn oversized_files_are_refused_with_their_size() {
+        let dir = temp_dir("noise-size");
+        let path = dir.join("oversized.png");
+        GrayImage::from_pixel(6, 3, Luma([255])).save(&path).unwrap();
+
+        let error = NoiseTexture::load_with_max_size(&path, 4).err().unwrap();
+        assert!(matches!(error, NoiseError::TooLarge { width: 6, height: 3, max_size: 4 }), "{:?}", error);
+        assert!(error.to_string().contains("6x3"), "{}", error);
+        let texture = NoiseTexture::load_with_max_size(&path, 6).unwrap();
+        assert_eq!((texture.width(), texture.height()), (6, 3));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    /// A PNG signature and IHDR chunk with a correct checksum, and no image data.
+    fn png_header(width: u32, height: u32, bit_depth: u8, color_type: u8) -> Vec<u8> {
+        let mut chunk = b"IHDR".to_vec();
+        chunk.extend_from_slice(&width.to_be_bytes());
+        chunk.extend_from_slice(&height.to_be_bytes());
+        chunk.extend_from_slice(&[bit_depth, color_type, 0, 0, 0]);
+        let mut crc = flate2::Crc::new();
+        crc.update(&chunk);
+
+        let mut bytes = PNG_SIGNATURE.to_vec();
+        bytes.extend_from_slice(&13u32.to_be_bytes());
+        bytes.extend_from_slice(&chunk);
+        bytes.extend_from_slice(&crc.sum().to_be_bytes());
+        bytes
+    }
+
+    fn parse(bytes: &[u8]) -> Result<NoiseHeader, String> {
+        parse_png_header(bytes.try_into().unwrap())
+    }
+
+    #[test]
+    fn png_headers_are_checked_without_decoding() {
+        let header = parse(&png_header(640, 480, 8, 3)).unwrap();
+        assert_eq!(header, NoiseHeader { width: 640, height: 480, bit_depth: 8, color_type: 3 });
+        assert!(parse(&png_header(2, 2, 16, 6)).is_ok());
+
+        let mut jpeg = png_header(2, 2, 8, 2);
+        jpeg[..4].copy_from_slice(&[0xFF, 0xD8, 0xFF, 0xE0]);
+        assert_eq!(parse(&jpeg).unwrap_err(), "Not a PNG file");
+
+        let mut damaged = png_header(2, 2, 8, 2);
+        damaged[17] ^= 0x01;
+        assert_eq!(parse(&damaged).unwrap_err(), "PNG header checksum mismatch");
+
+        assert!(parse(&png_header(0, 2, 8, 0)).is_err());
+        assert!(parse(&png_header(2, 2, 4, 2)).is_err());
+        assert!(parse(&png_header(2, 2, 16, 3)).is_err());
+        assert!(parse(&png_header(2, 2, 8, 5)).is_err());
+    }
+
+    #[test]
+    fn probing_refuses_truncated_and_oversized_files() {
+        let dir = temp_dir("noise-probe");
+        let path = dir.join("probe.png");
+
+        std::fs::write(&path, &png_header(8192, 8192, 8, 0)[..20]).unwrap();
+        assert!(matches!(NoiseTexture::probe(&path), Err(NoiseError::Decode { .. })));
+
+        std::fs::write(&path, png_header(8192, 64, 8, 0)).unwrap();
+        let error = NoiseTexture::probe(&path).err().unwrap();
+        assert!(matches!(error, NoiseError::TooLarge { width: 8192, height: 64, max_size: DEFAULT_MAX_NOISE_SIZE }), "{:?}", error);
+        assert_eq!(NoiseTexture::probe_with_max_size(&path, 8192).unwrap().width, 8192);
+
+        // A plausible header with no pixels behind it is listed, and fails when loaded
+        std::fs::write(&path, png_header(64, 64, 8, 0)).unwrap();
+        assert!(NoiseTexture::probe(&path).is_ok());
+        assert!(matches!(NoiseTexture::load_from_file(&path), Err(NoiseError::Decode { .. })));
+
+        GrayImage::from_pixel(6, 3, Luma([255])).save(&path).unwrap();
+        assert_eq!(NoiseTexture::probe(&path).unwrap(), NoiseHeader { width: 6, height: 3, bit_depth: 8, color_type: 0 });
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn missing_and_broken_files_are_told_apart() {
+        let dir = temp_dir("noise-broken");
+        let path = dir.join("broken.png");
+        match NoiseTexture::load_from_file(&path) {
+            Err(NoiseError::Io { source, .. }) => assert_eq!(source.kind(), std::io::ErrorKind::NotFound),
+            other => panic!("expected an I/O error, got {:?}", other.err()),
+        }
+
+        std::fs::write(&path, b"not an image").unwrap();
+        assert!(matches!(NoiseTexture::load_from_file(&path), Err(NoiseError::Decode { .. })));
+        let _ = std::fs::remove_dir_all(&dir);
     }
 }
@@ -0,0 +1,203 @@
+use crate::screenshot_preview::{self, PreviewSettings};
+use crate::theme;
+use anyhow::Result;
+use chromabridge::{tr, StateManager, TestPattern};
+use crossbeam_channel::{Receiver, TryRecvError};
+use image::imageops::FilterType;
+use image::RgbaImage;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+/// Width of the preview; its height follows the monitor's aspect ratio.
+const PREVIEW_WIDTH: u32 = 320;
+/// Magnification of the 1:1 crop, so single pixel lines are visible.
+const ZOOM: u32 = 4;
+/// How long the settings have to stay put before a new preview is rendered.
+const DEBOUNCE: Duration = Duration::from_millis(200);
+/// Finished previews kept, so switching back to a recent combination is instant.
+const CACHE_SIZE: usize = 8;
+/// The sample image the pattern is laid over; colorful everywhere but the center.
+const SAMPLE: TestPattern = TestPattern::HueWheel;
+
+/// What a preview depends on.
+#[derive(Debug, Clone, PartialEq)]
+struct PreviewKey {
+    settings: PreviewSettings,
+    screen: (u32, u32),
+}
+
+struct RenderedPreview {
+    /// The whole screen scaled down to `PREVIEW_WIDTH`.
+    scaled: egui::TextureHandle,
+    /// A crop of the screen at 1:1, drawn `ZOOM` times as large.
+    zoomed: egui::TextureHandle,
+    /// False when the spectrum file has no secondary spectrum, which the pattern needs.
+    patterned: bool,
+}
+
+struct PreviewImages {
+    scaled: RgbaImage,
+    zoomed: RgbaImage,
+    patterned: bool,
+}
+
+/// Small preview under the interlace pattern selector showing how the selected noise texture
+/// lands on a sample image at the selected monitor's resolution, with its mapping, intensity and
+/// both spectra.
+///
+/// Rendered on a worker thread with `CpuCorrection` once the settings stopped changing for
+/// `DEBOUNCE`, and cached per combination of settings.
+#[derive(Default)]
+pub struct NoisePreview {
+    /// Oldest first; failures hold the message to show.
+    cache: Vec<(PreviewKey, Result<RenderedPreview, String>)>,
+    /// The key as last seen and when it changed to that.
+    seen: Option<(PreviewKey, Instant)>,
+    pending: Option<(PreviewKey, Receiver<Result<PreviewImages>>)>,
+    /// Show the 1:1 crop instead of the whole screen.
+    zoom: bool,
+}
+
+impl NoisePreview {
+    /// Draws the preview of the current settings on a `screen` sized monitor.
+    pub fn show(&mut self, ui: &mut egui::Ui, state: &StateManager, screen: (u32, u32)) {
+        let key = PreviewKey {
+            settings: PreviewSettings::read(state),
+            screen: (screen.0.max(1), screen.1.max(1)),
+        };
+        self.update(ui.ctx(), state, &key);
+
+        ui.vertical(|ui| {
+            let current = self.cache.iter().find(|(cached, _)| *cached == key);
+            // While the new one renders the last preview stays up, so it doesn't flicker
+            let shown = current.or(self.cache.last());
+            match shown.map(|(_, preview)| preview) {
+                Some(Ok(preview)) => {
+                    let texture = if self.zoom { &preview.zoomed } else { &preview.scaled };
+                    let size = preview.scaled.size_vec2();
+                    ui.add(egui::Image::new(texture).fit_to_exact_size(size));
+                    if current.is_some() && !preview.patterned {
+                        ui.weak(tr!("noise_preview.single_spectrum"));
+                    }
+                }
+                Some(Err(error)) if current.is_some() => {
+                    ui.colored_label(theme::palette(ui).error, error);
+                }
+                _ => {}
+            }
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut self.zoom, tr!("noise_preview.zoom", zoom = ZOOM)).on_hover_text(tr!("noise_preview.zoom_hint"));
+                if current.is_none() {
+                    ui.spinner();
+                }
+            });
+        });
+    }
+
+    /// Collects a finished preview and starts the next one once the settings stopped changing.
+    fn update(&mut self, ctx: &egui::Context, state: &StateManager, key: &PreviewKey) {
+        if let Some((rendered, receiver)) = self.pending.take() {
+            match receiver.try_recv() {
+                Ok(result) => {
+                    let preview = result
+                        .map(|images| load_preview(ctx, images))
+                        .map_err(|e| tr!("preview.correction_failed", error = format!("{:#}", e)));
+                    self.remember(rendered, preview);
+                }
+                Err(TryRecvError::Disconnected) => {}
+                Err(TryRecvError::Empty) => {
+                    self.pending = Some((rendered, receiver));
+                    ctx.request_repaint_after(Duration::from_millis(50));
+                    return;
+                }
+            }
+        }
+
+        if self.seen.as_ref().is_none_or(|(seen, _)| seen != key) {
+            self.seen = Some((key.clone(), Instant::now()));
+        }
+        if self.cache.iter().any(|(cached, _)| cached == key) {
+            return;
+        }
+        let since_change = self.seen.as_ref().map_or(DEBOUNCE, |(_, changed_at)| changed_at.elapsed());
+        if since_change < DEBOUNCE {
+            ctx.request_repaint_after(DEBOUNCE - since_change);
+            return;
+        }
+
+        let (spectrum_path, noise_path) = match key.settings.files(state) {
+            Ok(Some(files)) => files,
+            Ok(None) => {
+                self.remember(key.clone(), Err(tr!("preview.no_spectrum")));
+                return;
+            }
+            Err(e) => {
+                self.remember(key.clone(), Err(tr!("preview.correction_failed", error = format!("{:#}", e))));
+                return;
+            }
+        };
+
+        let (tx, rx) = crossbeam_channel::bounded(1);
+        let job = key.clone();
+        let repaint = ctx.clone();
+        std::thread::spawn(move || {
+            let _ = tx.send(render(&job, spectrum_path, noise_path));
+            repaint.request_repaint();
+        });
+        self.pending = Some((key.clone(), rx));
+    }
+
+    fn remember(&mut self, key: PreviewKey, preview: Result<RenderedPreview, String>) {
+        self.cache.retain(|(cached, _)| *cached != key);
+        if self.cache.len() >= CACHE_SIZE {
+            self.cache.remove(0);
+        }
+        self.cache.push((key, preview));
+    }
+}
+
+fn render(key: &PreviewKey, spectrum_path: PathBuf, noise_path: Option<PathBuf>) -> Result<PreviewImages> {
+    let correction = screenshot_preview::load_correction(&key.settings, spectrum_path, noise_path)?;
+    let (screen_width, screen_height) = key.screen;
+    let height = (PREVIEW_WIDTH as u64 * screen_height as u64 / screen_width as u64).max(1) as u32;
+    let sample = SAMPLE.generate_rgba(PREVIEW_WIDTH, height);
+
+    let mut scaled = sample.clone();
+    correction.apply(&mut scaled, screen_width);
+
+    // Centered halfway between the left edge and the middle, where the sample is saturated; its
+    // pixels there are blown up to screen size
+    let (crop_width, crop_height) = ((PREVIEW_WIDTH / ZOOM).min(screen_width), (height / ZOOM).max(1).min(screen_height));
+    let (left, top) = ((screen_width / 4).saturating_sub(crop_width / 2), (screen_height - crop_height) / 2);
+    let to_sample = |screen: u32, screen_size: u32, sample_size: u32| (screen as u64 * sample_size as u64 / screen_size as u64) as u32;
+    let (sample_left, sample_top) = (to_sample(left, screen_width, PREVIEW_WIDTH), to_sample(top, screen_height, height));
+    let sample_crop = image::imageops::crop_imm(
+        &sample,
+        sample_left,
+        sample_top,
+        to_sample(crop_width, screen_width, PREVIEW_WIDTH).max(1),
+        to_sample(crop_height, screen_height, height).max(1),
+    )
+    .to_image();
+    let mut zoomed = image::imageops::resize(&sample_crop, crop_width, crop_height, FilterType::Triangle);
+    correction.apply_crop(&mut zoomed, left, top, screen_width, screen_height);
+
+    Ok(PreviewImages {
+        scaled,
+        zoomed,
+        patterned: correction.has_noise(),
+    })
+}
+
+fn load_preview(ctx: &egui::Context, images: PreviewImages) -> RenderedPreview {
+    let load = |name: &str, image: &RgbaImage, options| {
+        let size = [image.width() as usize, image.height() as usize];
+        ctx.load_texture(name, egui::ColorImage::from_rgba_unmultiplied(size, image.as_raw()), options)
+    };
+    RenderedPreview {
+        scaled: load("noise_preview", &images.scaled, egui::TextureOptions::LINEAR),
+        // Magnified without smoothing, so each screen pixel is a sharp square
+        zoomed: load("noise_preview_zoomed", &images.zoomed, egui::TextureOptions::NEAREST),
+        patterned: images.patterned,
+    }
+}
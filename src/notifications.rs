@@ -0,0 +1,215 @@
+//! Optional notifications when the overlay starts, stops, pauses on its own or runs into an error.
+
+use crate::overlay::{OverlayManager, OverlayStatus, OverlayThrottle};
+use crate::tray::TaskbarWatcher;
+use anyhow::{Context, Result};
+use chromabridge::{StateManager, log_debug, log_info, log_warn, tr};
+use std::time::{Duration, Instant};
+use windows::{
+    core::{h, HSTRING, PCWSTR},
+    Data::Xml::Dom::XmlDocument,
+    UI::Notifications::{NotificationSetting, ToastNotification, ToastNotificationManager, ToastNotifier, ToastTemplateType},
+    Win32::System::Registry::{RegCloseKey, RegCreateKeyExW, RegSetValueExW, HKEY, HKEY_CURRENT_USER, KEY_WRITE, REG_OPTION_NON_VOLATILE, REG_SZ},
+    Win32::UI::Shell::SetCurrentProcessExplicitAppUserModelID,
+};
+
+/// Toasts are attributed to this id, registered under the current user with the app's name and icon.
+const APP_USER_MODEL_ID: &str = "ChromaBridge.ChromaBridge";
+/// At most one notification per category this often, so an error storm shows one.
+const RATE_LIMIT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Category {
+    StartStop,
+    Pause,
+    Error,
+}
+
+impl Category {
+    const COUNT: usize = 3;
+
+    fn enabled(self, state: &StateManager) -> bool {
+        state.read(|s| match self {
+            Category::StartStop => s.notify_start_stop,
+            Category::Pause => s.notify_pause,
+            Category::Error => s.notify_errors,
+        })
+    }
+}
+
+/// What notifications are about, as last seen by the main loop.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Observed {
+    on: bool,
+    paused: bool,
+    error: bool,
+}
+
+impl Observed {
+    /// `None` while the overlay is between states; it is reported once it settles.
+    fn of(status: OverlayStatus, overlay_manager: &OverlayManager) -> Option<Self> {
+        let on = match status {
+            OverlayStatus::Running => true,
+            OverlayStatus::Stopped | OverlayStatus::Failed => false,
+            OverlayStatus::Starting | OverlayStatus::Restarting | OverlayStatus::WaitingForDisplay => return None,
+        };
+        Some(Self {
+            on,
+            paused: on && overlay_manager.throttle() == OverlayThrottle::Paused,
+            error: overlay_manager.has_error(),
+        })
+    }
+
+    /// Category and title of each change from `before`.
+    fn changes_from(self, before: Self) -> Vec<(Category, String)> {
+        let mut changes = Vec::new();
+        if self.on != before.on {
+            let title = if self.on { tr!("notifications.started") } else { tr!("notifications.stopped") };
+            changes.push((Category::StartStop, title));
+        } else if self.on && self.paused != before.paused {
+            let title = if self.paused { tr!("notifications.paused") } else { tr!("notifications.resumed") };
+            changes.push((Category::Pause, title));
+        }
+        if self.error && !before.error {
+            changes.push((Category::Error, tr!("notifications.error")));
+        } else if !self.error && before.error && self.on {
+            changes.push((Category::Error, tr!("notifications.recovered")));
+        }
+        changes
+    }
+}
+
+/// Whether toasts are set up. Setting them up registers the app id under the current user and
+/// gives the process that id, so it waits for the first notification actually shown.
+enum Toasts {
+    NotSetUp,
+    Ready(ToastNotifier),
+    /// Setting them up or showing one failed; balloons are used then.
+    Unavailable,
+}
+
+/// Shows a notification for each overlay change whose category is switched on, as a toast or,
+/// where toasts aren't available, as a tray balloon. Neither takes focus.
+///
+/// Polled by the main loop, which also picks up changes made by the overlay thread itself.
+pub struct Notifier {
+    toasts: Toasts,
+    seen: Option<Observed>,
+    last_shown: [Option<Instant>; Category::COUNT],
+}
+
+impl Notifier {
+    pub fn new() -> Self {
+        Self { toasts: Toasts::NotSetUp, seen: None, last_shown: [None; Category::COUNT] }
+    }
+
+    /// Notifies about what changed since the last call. The first call only takes note of the state.
+    pub fn update(&mut self, state: &StateManager, overlay_manager: &OverlayManager, balloons: Option<&TaskbarWatcher>) {
+        let status = overlay_manager.state();
+        let Some(observed) = Observed::of(status, overlay_manager) else {
+            return;
+        };
+        let Some(before) = self.seen.replace(observed) else {
+            return;
+        };
+
+        for (category, title) in observed.changes_from(before) {
+            if !category.enabled(state) {
+                continue;
+            }
+            let now = Instant::now();
+            let last_shown = &mut self.last_shown[category as usize];
+            if last_shown.is_some_and(|shown| now.duration_since(shown) < RATE_LIMIT) {
+                log_debug!("Notification \"{}\" skipped, another {:?} notification was shown less than {}s ago", title, category, RATE_LIMIT.as_secs());
+                continue;
+            }
+            *last_shown = Some(now);
+
+            let reason = crate::overlay_status_text(status, overlay_manager);
+            self.show(&title, &reason, balloons);
+        }
+    }
+
+    fn show(&mut self, title: &str, reason: &str, balloons: Option<&TaskbarWatcher>) {
+        if let Toasts::NotSetUp = self.toasts {
+            self.toasts = match toast_notifier() {
+                Ok(notifier) => Toasts::Ready(notifier),
+                Err(e) => {
+                    log_info!("Toast notifications unavailable, falling back to tray balloons: {:#}", e);
+                    Toasts::Unavailable
+                }
+            };
+        }
+        if let Toasts::Ready(ref notifier) = self.toasts {
+            match show_toast(notifier, title, reason) {
+                Ok(()) => return,
+                Err(e) => {
+                    log_warn!("Failed to show toast, falling back to tray balloons: {:#}", e);
+                    self.toasts = Toasts::Unavailable;
+                }
+            }
+        }
+        let Some(balloons) = balloons else {
+            log_debug!("Notification \"{}\" not shown, no tray balloons without the taskbar watcher", title);
+            return;
+        };
+        if let Err(e) = balloons.show_balloon(title, reason) {
+            log_warn!("Failed to show tray balloon: {:#}", e);
+        }
+    }
+}
+
+fn toast_notifier() -> Result<ToastNotifier> {
+    register_app_user_model_id()?;
+    let notifier = ToastNotificationManager::CreateToastNotifierWithId(&HSTRING::from(APP_USER_MODEL_ID))?;
+    // Fails where the toast platform isn't there, e.g. on Server Core
+    let setting = notifier.Setting().context("Failed to read the notification setting")?;
+    if setting != NotificationSetting::Enabled {
+        // Turned off in Windows settings; balloons would be held back the same way
+        log_info!("Toast notifications are turned off for ChromaBridge ({:?})", setting);
+    }
+    Ok(notifier)
+}
+
+/// A title line and a text line, shown by the shell without activating any window.
+fn show_toast(notifier: &ToastNotifier, title: &str, text: &str) -> Result<()> {
+    let xml: XmlDocument = ToastNotificationManager::GetTemplateContent(ToastTemplateType::ToastText02)?;
+    let lines = xml.GetElementsByTagName(h!("text"))?;
+    lines.Item(0)?.AppendChild(&xml.CreateTextNode(&HSTRING::from(title))?)?;
+    lines.Item(1)?.AppendChild(&xml.CreateTextNode(&HSTRING::from(text))?)?;
+
+    let toast = ToastNotification::CreateToastNotification(&xml)?;
+    notifier.Show(&toast)?;
+    Ok(())
+}
+
+/// Unpackaged apps need their id registered with a display name for their toasts to show, and
+/// the process has to use it too.
+fn register_app_user_model_id() -> Result<()> {
+    unsafe {
+        let subkey = HSTRING::from(format!("Software\\Classes\\AppUserModelId\\{}", APP_USER_MODEL_ID));
+        let mut hkey = HKEY::default();
+        RegCreateKeyExW(HKEY_CURRENT_USER, &subkey, None, PCWSTR::null(), REG_OPTION_NON_VOLATILE, KEY_WRITE, None, &mut hkey, None)
+            .ok()
+            .context("Failed to create the app id registry key")?;
+
+        let icon = std::env::current_exe().ok().and_then(|p| p.parent().map(|p| p.join("icon.ico"))).filter(|p| p.exists());
+        let mut result = set_string_value(hkey, "DisplayName", "ChromaBridge");
+        if let (Ok(()), Some(icon)) = (&result, icon) {
+            result = set_string_value(hkey, "IconUri", &icon.to_string_lossy());
+        }
+        let _ = RegCloseKey(hkey);
+        result?;
+
+        SetCurrentProcessExplicitAppUserModelID(&HSTRING::from(APP_USER_MODEL_ID)).context("Failed to set the app id")?;
+        Ok(())
+    }
+}
+
+unsafe fn set_string_value(hkey: HKEY, name: &str, value: &str) -> Result<()> {
+    let wide: Vec<u16> = value.encode_utf16().chain(std::iter::once(0)).collect();
+    let bytes = std::slice::from_raw_parts(wide.as_ptr() as *const u8, wide.len() * 2);
+    RegSetValueExW(hkey, &HSTRING::from(name), None, REG_SZ, Some(bytes))
+        .ok()
+        .with_context(|| format!("Failed to write {} of the app id", name))
+}
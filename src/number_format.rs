@@ -0,0 +1,172 @@
+//! Numbers and percentages shown in the settings window, in the user's number format.
+
+use crate::i18n::{self, Language};
+use once_cell::sync::Lazy;
+
+/// Where the percent sign goes, as in Windows' `LOCALE_IPOSITIVEPERCENT`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PercentPattern {
+    /// `70 %`
+    Spaced,
+    /// `70%`
+    Attached,
+    /// `%70`
+    Leading,
+    /// `% 70`
+    LeadingSpaced,
+}
+
+/// Decimal separator and percent placement of one locale.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NumberFormat {
+    pub decimal_separator: char,
+    pub percent: PercentPattern,
+}
+
+impl NumberFormat {
+    pub const ENGLISH: NumberFormat = NumberFormat { decimal_separator: '.', percent: PercentPattern::Attached };
+
+    /// The format of the UI language, or of the Windows user locale while the language follows Windows.
+    pub fn current() -> Self {
+        match i18n::current_language() {
+            Language::Auto => *USER_LOCALE,
+            language => Self::of_language(language),
+        }
+    }
+
+    fn of_language(language: Language) -> Self {
+        match language {
+            Language::German | Language::French | Language::Spanish => NumberFormat { decimal_separator: ',', percent: PercentPattern::Spaced },
+            Language::Auto | Language::English | Language::Japanese => Self::ENGLISH,
+        }
+    }
+
+    /// `value` with `decimals` digits after the separator, e.g. `0,50`.
+    pub fn decimal(&self, value: f64, decimals: usize) -> String {
+        let text = format!("{:.*}", decimals, value);
+        if self.decimal_separator == '.' {
+            text
+        } else {
+            text.replace('.', &self.decimal_separator.to_string())
+        }
+    }
+
+    /// `fraction` (1 = 100 %) as a percentage with `decimals` digits, e.g. `70 %`.
+    pub fn percent(&self, fraction: f64, decimals: usize) -> String {
+        let number = self.decimal(fraction * 100.0, decimals);
+        match self.percent {
+            PercentPattern::Spaced => format!("{} %", number),
+            PercentPattern::Attached => format!("{}%", number),
+            PercentPattern::Leading => format!("%{}", number),
+            PercentPattern::LeadingSpaced => format!("% {}", number),
+        }
+    }
+
+    /// Reads a typed percentage or fraction back as a fraction: `70 %`, `70` and `0,7` are all 0.7.
+    ///
+    /// A number with a percent sign, or without a decimal separator, is a percentage; one with a
+    /// separator up to 1 is a fraction, so `1` means 1 % and `1.0` means 100 %. Both `.` and `,`
+    /// are taken as the separator whatever the locale, as there are no thousands to group.
+    pub fn parse_fraction(&self, text: &str) -> Option<f64> {
+        let text = text.trim();
+        let (number, percent_sign) = match text.strip_suffix('%').or_else(|| text.strip_prefix('%')) {
+            Some(number) => (number.trim(), true),
+            None => (text, false),
+        };
+        let has_separator = number.contains([',', '.']);
+        let value: f64 = number.replace(',', ".").parse().ok()?;
+        if !value.is_finite() {
+            return None;
+        }
+        if percent_sign || !has_separator || value > 1.0 {
+            Some(value / 100.0)
+        } else {
+            Some(value)
+        }
+    }
+}
+
+impl Default for NumberFormat {
+    fn default() -> Self {
+        Self::ENGLISH
+    }
+}
+
+/// Read once; Windows doesn't tell running apps when the format settings change.
+static USER_LOCALE: Lazy<NumberFormat> = Lazy::new(user_locale);
+
+#[cfg(windows)]
+fn user_locale() -> NumberFormat {
+    use windows::core::PCWSTR;
+    use windows::Win32::Globalization::{GetLocaleInfoEx, LOCALE_IPOSITIVEPERCENT, LOCALE_SDECIMAL};
+
+    let read = |info: u32| -> Option<String> {
+        let mut buffer = [0u16; 8];
+        // A null name is the user default locale
+        let len = unsafe { GetLocaleInfoEx(PCWSTR::null(), info, Some(&mut buffer)) };
+        // The length includes the terminating nul; 0 is a failure
+        (len > 1).then(|| String::from_utf16_lossy(&buffer[..len as usize - 1]))
+    };
+
+    let decimal_separator = read(LOCALE_SDECIMAL).and_then(|s| s.chars().next()).unwrap_or('.');
+    let percent = match read(LOCALE_IPOSITIVEPERCENT).as_deref() {
+        Some("0") => PercentPattern::Spaced,
+        Some("2") => PercentPattern::Leading,
+        Some("3") => PercentPattern::LeadingSpaced,
+        _ => PercentPattern::Attached,
+    };
+    NumberFormat { decimal_separator, percent }
+}
+
+#[cfg(not(windows))]
+fn user_locale() -> NumberFormat {
+    NumberFormat::ENGLISH
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GERMAN: NumberFormat = NumberFormat { decimal_separator: ',', percent: PercentPattern::Spaced };
+
+    #[test]
+    fn formats_in_the_locale() {
+        assert_eq!(NumberFormat::ENGLISH.percent(0.7, 0), "70%");
+        assert_eq!(GERMAN.percent(0.7, 0), "70 %");
+        assert_eq!(GERMAN.percent(0.125, 1), "12,5 %");
+        assert_eq!(GERMAN.decimal(0.5, 2), "0,50");
+        assert_eq!(NumberFormat { decimal_separator: '.', percent: PercentPattern::Leading }.percent(1.0, 0), "%100");
+    }
+
+    #[test]
+    fn parses_percentages_and_fractions() {
+        for format in [NumberFormat::ENGLISH, GERMAN] {
+            assert_eq!(format.parse_fraction("70 %"), Some(0.7));
+            assert_eq!(format.parse_fraction("70%"), Some(0.7));
+            assert_eq!(format.parse_fraction("% 70"), Some(0.7));
+            assert_eq!(format.parse_fraction("70"), Some(0.7));
+            assert_eq!(format.parse_fraction("0,7"), Some(0.7));
+            assert_eq!(format.parse_fraction("0.7"), Some(0.7));
+            assert_eq!(format.parse_fraction(" 12,5 % "), Some(0.125));
+            assert_eq!(format.parse_fraction("1"), Some(0.01));
+            assert_eq!(format.parse_fraction("1.0"), Some(1.0));
+            assert_eq!(format.parse_fraction("0"), Some(0.0));
+            assert_eq!(format.parse_fraction("seventy"), None);
+            assert_eq!(format.parse_fraction(""), None);
+            assert_eq!(format.parse_fraction("inf"), None);
+        }
+    }
+
+    #[test]
+    fn formatted_values_parse_back() {
+        for format in [NumberFormat::ENGLISH, GERMAN, NumberFormat { decimal_separator: ',', percent: PercentPattern::LeadingSpaced }] {
+            for percent in [0, 1, 5, 33, 70, 99, 100] {
+                let fraction = percent as f64 / 100.0;
+                let parsed = format.parse_fraction(&format.percent(fraction, 0)).unwrap();
+                assert!((parsed - fraction).abs() < 1e-9, "{:?}: {} came back as {}", format, percent, parsed);
+            }
+            let parsed = format.parse_fraction(&format.percent(0.125, 1)).unwrap();
+            assert!((parsed - 0.125).abs() < 1e-9);
+        }
+    }
+}
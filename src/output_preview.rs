@@ -0,0 +1,148 @@
+use crate::gui::MonitorInfo;
+use crate::overlay::OverlayManager;
+use crate::theme;
+use chromabridge::{log_debug, tr};
+use image::RgbaImage;
+use parking_lot::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// How often the overlay reads back a frame while the preview is open, about 10 FPS.
+pub const PREVIEW_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Frames are scaled down to at most this width, so reading one back doesn't hold up the overlay.
+pub const PREVIEW_WIDTH: u32 = 480;
+
+/// A scaled-down live copy of what the overlay presents, for the developer preview window.
+/// The overlay thread only reads frames back while the window has it enabled.
+#[derive(Default)]
+pub struct OutputPreview {
+    enabled: AtomicBool,
+    last_read: Mutex<Option<Instant>>,
+    /// The newest frame not yet shown, or why reading it back failed.
+    frame: Mutex<Option<Result<RgbaImage, String>>>,
+}
+
+impl OutputPreview {
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Release);
+        if !enabled {
+            *self.frame.lock() = None;
+        }
+    }
+
+    /// True when the overlay should read back a frame now. Only a load while disabled, so
+    /// checking every frame costs nothing.
+    pub fn is_due(&self) -> bool {
+        if !self.enabled.load(Ordering::Relaxed) {
+            return false;
+        }
+        let mut last_read = self.last_read.lock();
+        if last_read.is_some_and(|at| at.elapsed() < PREVIEW_INTERVAL) {
+            return false;
+        }
+        *last_read = Some(Instant::now());
+        true
+    }
+
+    /// Hands a frame to the window. Dropped once the window closed, as the last read can finish
+    /// after that.
+    pub fn publish(&self, frame: anyhow::Result<RgbaImage>) {
+        if !self.enabled.load(Ordering::Acquire) {
+            return;
+        }
+        let frame = frame.map_err(|e| {
+            log_debug!("Failed to read back a preview frame: {:#}", e);
+            format!("{:#}", e)
+        });
+        *self.frame.lock() = Some(frame);
+    }
+
+    pub fn take_frame(&self) -> Option<Result<RgbaImage, String>> {
+        self.frame.lock().take()
+    }
+}
+
+/// Developer window showing what the overlay presents, for monitors nobody looks at, like the
+/// virtual display a stream is captured from. It mirrors that display into the settings window,
+/// so it says it is for checking the correction only.
+pub struct OutputPreviewWindow {
+    overlay_manager: Arc<OverlayManager>,
+    /// The monitor the overlay runs on, for the caption.
+    monitor: Option<MonitorInfo>,
+    frame: Option<egui::TextureHandle>,
+    error: Option<String>,
+}
+
+impl OutputPreviewWindow {
+    /// Starts the overlay reading back frames until the window is dropped.
+    pub fn open(overlay_manager: Arc<OverlayManager>, monitor: Option<MonitorInfo>) -> Self {
+        overlay_manager.set_output_preview(true);
+        Self { overlay_manager, monitor, frame: None, error: None }
+    }
+
+    /// Draws the window; false once it was closed.
+    pub fn show(&mut self, ctx: &egui::Context) -> bool {
+        let overlay_manager = Arc::clone(&self.overlay_manager);
+        match overlay_manager.take_output_preview() {
+            Some(Ok(image)) => {
+                let size = [image.width() as usize, image.height() as usize];
+                let image = egui::ColorImage::from_rgba_unmultiplied(size, image.as_raw());
+                match self.frame {
+                    Some(ref mut frame) => frame.set(image, egui::TextureOptions::LINEAR),
+                    None => self.frame = Some(ctx.load_texture("output_preview", image, egui::TextureOptions::LINEAR)),
+                }
+                self.error = None;
+            }
+            Some(Err(error)) => self.error = Some(error),
+            None => {}
+        }
+        let running = overlay_manager.is_running();
+        if running {
+            ctx.request_repaint_after(PREVIEW_INTERVAL);
+        }
+
+        let mut open = true;
+        egui::Window::new(tr!("output_preview.title"))
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.colored_label(theme::palette(ui).warning, tr!("output_preview.developer_only"));
+                if let Some(ref monitor) = self.monitor {
+                    let device = if monitor.device_string.is_empty() { monitor.name.clone() } else { format!("{}, {}", monitor.name, monitor.device_string) };
+                    ui.weak(tr!("output_preview.monitor", device = device, width = monitor.width, height = monitor.height));
+                }
+                ui.add_space(5.0);
+
+                if !running {
+                    ui.label(tr!("output_preview.needs_overlay"));
+                } else if overlay_manager.is_occluded() {
+                    ui.label(tr!("overlay.occluded")).on_hover_text(tr!("overlay.occluded_hint"));
+                }
+                match self.frame {
+                    Some(ref frame) if running => {
+                        let size = frame.size_vec2();
+                        let width = ui.available_width().min(size.x).max(1.0);
+                        ui.add(egui::Image::new(frame).fit_to_exact_size(egui::vec2(width, width * size.y / size.x)));
+                    }
+                    None if running => {
+                        ui.spinner();
+                    }
+                    _ => {}
+                }
+                if let Some(ref error) = self.error {
+                    ui.colored_label(theme::palette(ui).error, tr!("output_preview.failed", error = error));
+                }
+            });
+
+        open
+    }
+}
+
+impl Drop for OutputPreviewWindow {
+    fn drop(&mut self) {
+        self.overlay_manager.set_output_preview(false);
+    }
+}
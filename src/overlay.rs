@@ -1,9 +1,29 @@
 use crate::StateManager;
-use chromabridge::{log_info, log_error, log_warn, SpectrumPair, NoiseTexture, HueMapper};
+use chromabridge::cursor::{CursorShape, CursorShapeKind, PixelLayout};
+use chromabridge::lifecycle::{Lifecycle, Outcome, Phase};
+use chromabridge::startup_timer::StartupTimer;
+use chromabridge::state::AppState;
+use chromabridge::{log_debug, log_info, log_error, log_warn, tr, ActiveTime, ColorMath, SessionRecorder, SessionSummary, SpectrumPair, SpectrumSelection, NoiseKind, NoiseMapping, NoiseTarget, NoiseTexture, MAX_NOISE_INDICES, HueGate, HueMapper, OverlaySource, RenderThreadPriority, StrengthCurve, TargetWindow, TestPattern, TopmostMode};
 use anyhow::Result;
-use std::sync::Arc;
+use std::sync::{Arc, Weak};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::thread;
-use parking_lot::{Mutex, RwLock};
+use std::time::{Duration, Instant};
+use parking_lot::{Condvar, Mutex, RwLock};
+
+#[cfg(windows)]
+use crate::graphics_capture::GraphicsCapturer;
+#[cfg(windows)]
+use crate::shell_exclusion::{ShellExclusion, MAX_EXCLUSION_RECTS};
+#[cfg(windows)]
+use chromabridge::shader_params::{self, SpectrumParams};
+#[cfg(windows)]
+use crate::present_stats::PresentStats;
+#[cfg(windows)]
+use crate::target_window::{CorrectionArea, TargetWindowTracker};
+use crate::color_conflict::ColorConflict;
+use crate::frame_dump::FrameDump;
+use crate::output_preview::OutputPreview;
 
 #[cfg(windows)]
 use windows::{
@@ -19,826 +39,3563 @@ use windows::{
             Gdi::*,
         },
         UI::WindowsAndMessaging::*,
-        System::{Com::*, Threading::*},
+        System::{Com::*, Performance::*, Threading::*},
     },
 };
 
+/// Consecutive failed frames before the capture is reported as unhealthy (~0.5s at 60Hz).
+const CAPTURE_UNHEALTHY_AFTER: u32 = 30;
+/// Hue entries in each spectrum lookup texture.
+#[cfg(windows)]
+const SPECTRUM_RESOLUTION: usize = 360;
+/// Lattice points per channel of the baked correction tables. Keep in sync with LUT_SIZE in shaders.hlsl.
+#[cfg(windows)]
+const LUT_SIZE: u32 = 33;
+/// Rows of the noise texture converted and uploaded at a time.
+#[cfg(windows)]
+const NOISE_UPLOAD_ROWS: u32 = 64;
+/// How often a missing screen capture is retried, e.g. while a secure desktop is shown or
+/// another app holds the desktop duplication.
+#[cfg(windows)]
+const DUPLICATION_RETRY_INTERVAL: std::time::Duration = std::time::Duration::from_secs(2);
+/// Upper bound on waiting for the swap chain, so a removed device can't stall the render loop.
+#[cfg(windows)]
+const FRAME_WAIT_TIMEOUT_MS: u32 = 1000;
+/// Time left for rendering and presenting when waiting for desktop content runs up to the next refresh.
+#[cfg(windows)]
+const CAPTURE_WAIT_MARGIN: std::time::Duration = std::time::Duration::from_millis(1);
+/// Formats desktop duplication is asked to deliver frames in: 10-bit desktops as they are, the
+/// rest converted to 8 bits like plain `DuplicateOutput` does.
+#[cfg(windows)]
+const DUPLICATION_FORMATS: [DXGI_FORMAT; 2] = [DXGI_FORMAT_R10G10B10A2_UNORM, DXGI_FORMAT_B8G8R8A8_UNORM];
+/// Swap chain format until captured frames call for another, see `output_format_for`.
+#[cfg(windows)]
+const DEFAULT_OUTPUT_FORMAT: DXGI_FORMAT = DXGI_FORMAT_B8G8R8A8_UNORM;
+/// How often an occluded overlay tests whether it is shown again, instead of rendering.
+#[cfg(windows)]
+const OCCLUSION_PROBE_INTERVAL: std::time::Duration = std::time::Duration::from_millis(500);
+/// Occluded this long, the swap chain is recreated once; some drivers never show it again otherwise.
+#[cfg(windows)]
+const OCCLUSION_RECREATE_AFTER: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Quiet period after the last restart request before the overlay actually restarts, so changing
+/// monitor, spectrum and noise in a row costs one restart instead of three.
+const RESTART_DELAY: std::time::Duration = std::time::Duration::from_millis(300);
+
+/// Long overlay runs are stored in the performance history in slices of this length, so a crash
+/// or power loss costs at most one slice.
+const SESSION_SLICE: std::time::Duration = std::time::Duration::from_secs(10 * 60);
+
+// Set from the overlay window procedure on WM_DISPLAYCHANGE; there is only ever one overlay window
+static DISPLAY_CHANGED: AtomicBool = AtomicBool::new(false);
+
+/// Live frame pacing override, e.g. while a fullscreen game is in the foreground.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum OverlayThrottle {
+    #[default]
+    Full,
+    Reduced(f32),
+    Paused,
+}
+
+/// Developer aid for telling where the overlay covers the screen from what it does to the colors.
+/// Never saved, so every start is back to normal.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DebugVisibility {
+    #[default]
+    Normal,
+    /// Half of the overlay's output alpha, so the uncorrected screen shows through.
+    HalfAlpha,
+    /// Magenta over everything the overlay corrects.
+    MagentaTint,
+}
+
+impl DebugVisibility {
+    pub const ALL: [DebugVisibility; 3] = [DebugVisibility::Normal, DebugVisibility::HalfAlpha, DebugVisibility::MagentaTint];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            DebugVisibility::Normal => "Normal",
+            DebugVisibility::HalfAlpha => "50% opacity",
+            DebugVisibility::MagentaTint => "Magenta tint",
+        }
+    }
+
+    /// Must match the DEBUG_* constants in shaders.hlsl.
+    fn shader_mode(self) -> i32 {
+        match self {
+            DebugVisibility::Normal => 0,
+            DebugVisibility::HalfAlpha => 1,
+            DebugVisibility::MagentaTint => 2,
+        }
+    }
+}
+
+/// Lifecycle of the overlay thread as shown in the GUI and tray.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverlayStatus {
+    Stopped,
+    /// The thread is loading assets and creating the window; counts as on for toggles.
+    Starting,
+    /// `restart` was asked for and the new overlay hasn't presented a frame yet; counts as on.
+    Restarting,
+    Running,
+    /// The last start attempt failed or the overlay thread died with an error.
+    Failed,
+    /// A start failed because its display wasn't available, and the main loop will retry it.
+    WaitingForDisplay,
+}
+
+/// Whether the console display is on. The render loop blocks on `changed` while it is off.
+#[derive(Default)]
+struct DisplayPower {
+    off: Mutex<bool>,
+    changed: Condvar,
+}
+
+impl DisplayPower {
+    fn is_off(&self) -> bool {
+        *self.off.lock()
+    }
+
+    fn wait_while_off(&self, timeout: std::time::Duration) {
+        let mut off = self.off.lock();
+        if *off {
+            self.changed.wait_for(&mut off, timeout);
+        }
+    }
+}
+
+/// Rolling averages over the last 60 rendered frames, published by the overlay thread.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FrameStats {
+    pub fps: f32,
+    /// Time spent preparing each frame, excluding waits.
+    pub frame_time_ms: f32,
+    /// From the desktop presenting new content to the overlay presenting its correction;
+    /// `None` until a duplicated frame has been shown, and for the other capture backends and sources.
+    pub latency_ms: Option<f32>,
+    /// How the frames measured here were corrected.
+    pub color_math: ColorMath,
+    /// Presents Windows discarded without showing them, per second, from the swap chain's own
+    /// statistics; `None` until measured and where the swap chain keeps none.
+    pub dropped_presents_per_second: Option<f32>,
+}
+
+/// GPU details for bug reports, published by the overlay thread.
+#[derive(Debug, Clone, Default)]
+pub struct OverlayDiagnostics {
+    /// The adapter the overlay last rendered on; `None` until the overlay has started once.
+    pub adapter: Option<AdapterDiagnostics>,
+    /// Successful device rebuilds after the GPU device was removed, this session.
+    pub device_removed_recoveries: u32,
+    pub last_device_removed_reason: Option<String>,
+    /// The screen capture API in use; `None` while capture is unavailable or the source is generated.
+    pub capture_backend: Option<CaptureBackend>,
+    /// Why the overlay last fell back to Windows.Graphics.Capture, e.g. "DXGI_ERROR_NOT_CURRENTLY_AVAILABLE".
+    pub last_capture_fallback: Option<String>,
+    /// Set while no capture works because another app holds the desktop duplication.
+    pub capture_conflict: bool,
+    /// Clockwise rotation, in degrees, applied to captured frames to match the desktop; non-zero
+    /// for portrait or flipped monitors captured through desktop duplication.
+    pub capture_rotation: u32,
+    /// The priority the render thread got, see `RenderThreadPriority`.
+    pub render_priority: Option<crate::render_priority::AchievedPriority>,
+    /// The desktop format desktop duplication reports, e.g. "R10G10B10A2_UNORM" for 10-bit
+    /// desktops; `None` while another backend or no capture is in use.
+    pub desktop_format: Option<String>,
+    /// Set while presents report the overlay occluded, e.g. behind a UAC prompt's secure desktop;
+    /// nothing is rendered then.
+    pub occluded: bool,
+    /// The monitor the overlay last started on as (left, top, right, bottom) on the virtual
+    /// desktop; `None` until the overlay has started once.
+    pub monitor_rect: Option<[i32; 4]>,
+}
+
+/// How the overlay reads the screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureBackend {
+    /// DXGI desktop duplication, the default; only one app at a time can duplicate an output.
+    DesktopDuplication,
+    /// Windows.Graphics.Capture, the fallback while another app holds the duplication.
+    GraphicsCapture,
+}
+
+impl CaptureBackend {
+    pub fn label(&self) -> &'static str {
+        match self {
+            CaptureBackend::DesktopDuplication => "Desktop Duplication",
+            CaptureBackend::GraphicsCapture => "Windows.Graphics.Capture",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AdapterDiagnostics {
+    pub description: String,
+    pub dedicated_video_memory: u64,
+    pub dedicated_system_memory: u64,
+    pub shared_system_memory: u64,
+    pub feature_level: String,
+    pub swap_chain_format: String,
+    pub spectrum_format: String,
+}
+
 pub struct OverlayState {
     pub spectrum_pair: SpectrumPair,
+    /// Bumped whenever `spectrum_pair` is replaced so the renderer re-uploads the lookup textures.
+    pub spectrum_generation: u64,
     pub noise_texture: Option<NoiseTexture>,
+    pub noise_mapping: NoiseMapping,
+    pub noise_intensity: f32,
+    pub noise_target: NoiseTarget,
+    pub blend_ratio: f32,
     pub hue_mapper: HueMapper,
+    pub strength_curve: StrengthCurve,
     pub monitor_refresh_rate: f32,
+    pub throttle: OverlayThrottle,
+    pub posterize_levels: Option<u32>,
+    pub temporal_smoothing: f32,
+    pub hue_gate: Option<HueGate>,
+    pub topmost_mode: TopmostMode,
+    pub test_pattern: TestPattern,
+    pub solid_color: [u8; 3],
+    pub correct_cursor: bool,
+    /// Leave the taskbars, Start menu and notifications uncorrected.
+    pub exclude_shell: bool,
+    /// Keep the overlay window out of screenshots, screen sharing and the overlay's own capture.
+    pub hide_from_capture: bool,
+    /// Correct only inside this window instead of the whole monitor.
+    pub target_window: Option<TargetWindow>,
+    pub color_math: ColorMath,
+    pub debug_visibility: DebugVisibility,
+}
+
+impl OverlayState {
+    /// How many spectra the noise texture can select between: up to `MAX_NOISE_INDICES` for color
+    /// and palette textures, the primary and secondary for masks.
+    fn noise_spectrum_limit(&self) -> usize {
+        match self.noise_texture {
+            Some(ref noise) if noise.kind() != NoiseKind::Mask => MAX_NOISE_INDICES,
+            _ => 2,
+        }
+    }
+
+    /// The share of the correction blended in, from the strength through the strength curve.
+    fn blend_weight(&self) -> f32 {
+        self.strength_curve.apply(self.hue_mapper.strength)
+    }
+}
+
+/// The `AppState` fields mirrored into `OverlayState` by the state listener.
+struct LiveSettings {
+    strength: f32,
+    strength_curve: StrengthCurve,
+    noise_mapping: NoiseMapping,
+    noise_intensity: f32,
+    noise_target: NoiseTarget,
+    blend_ratio: f32,
+    temporal_smoothing: f32,
+    hue_gate: Option<HueGate>,
+    posterize_levels: Option<u32>,
+    topmost_mode: TopmostMode,
+    test_pattern: TestPattern,
+    solid_color: [u8; 3],
+    correct_cursor: bool,
+    exclude_shell: bool,
+    hide_from_capture: bool,
+    target_window: Option<TargetWindow>,
+    color_math: ColorMath,
+}
+
+/// The `AppState` fields the overlay thread only reads on start. Changing any of them needs a
+/// restart, except for the spectrum, which a running overlay can swap in place.
+#[derive(Debug, Clone, PartialEq)]
+struct LaunchSettings {
+    spectrum_name: Option<String>,
+    spectrum_selection: SpectrumSelection,
+    noise_texture: Option<String>,
+    monitor: usize,
+    cap_to_monitor_refresh: bool,
+    debug_overlay: bool,
+    overlay_source: OverlaySource,
+    render_thread_priority: RenderThreadPriority,
+}
+
+impl LaunchSettings {
+    fn of(s: &AppState) -> Self {
+        Self {
+            spectrum_name: s.spectrum_name.clone(),
+            spectrum_selection: s.spectrum_selection,
+            noise_texture: s.noise_texture.clone(),
+            monitor: s.last_monitor.unwrap_or(0),
+            cap_to_monitor_refresh: s.cap_to_monitor_refresh,
+            debug_overlay: s.debug_overlay,
+            overlay_source: s.overlay_source,
+            render_thread_priority: s.render_thread_priority,
+        }
+    }
+
+    /// True if `self` is `other` with another spectrum file or selection.
+    fn only_spectrum_differs(&self, other: &Self) -> bool {
+        let with_other_spectrum = Self {
+            spectrum_name: other.spectrum_name.clone(),
+            spectrum_selection: other.spectrum_selection,
+            ..self.clone()
+        };
+        with_other_spectrum == *other && self.spectrum_name.is_some()
+    }
+}
+
+/// The last color filter check and whether the user dismissed its result.
+#[derive(Default)]
+struct ConflictStatus {
+    detected: Option<ColorConflict>,
+    dismissed: bool,
+}
+
+/// Restart requests waiting out `RESTART_DELAY`, served by the overlay-restart thread.
+#[derive(Default)]
+struct RestartQueue {
+    /// When the pending restart is due; every new request pushes it back.
+    due: Mutex<Option<Instant>>,
+    wakeup: Condvar,
+    /// Set when the manager is dropped, or if the thread couldn't be started; requests then apply at once.
+    closed: AtomicBool,
+    /// Set by `restart`: the pending restart happens even if no setting changed.
+    manual: AtomicBool,
 }
 
+/// The overlay thread hands back the summary of the session not yet stored in the performance history.
+type OverlayLifecycle = Lifecycle<Option<SessionSummary>>;
+
 pub struct OverlayManager {
     app_state: Arc<StateManager>,
-    running: Arc<Mutex<bool>>,
-    overlay_thread: Mutex<Option<thread::JoinHandle<()>>>,
+    /// Starts, stops and toggles one overlay thread at a time, whichever threads ask.
+    lifecycle: Arc<OverlayLifecycle>,
     last_monitor: Mutex<Option<usize>>,
-    frame_stats: Arc<Mutex<Option<(f32, f32)>>>, // (fps, frame_time_ms)
+    frame_stats: Arc<Mutex<Option<FrameStats>>>,
     overlay_state: Arc<Mutex<Option<Arc<RwLock<OverlayState>>>>>,
+    throttle: Mutex<OverlayThrottle>,
+    spectrum_status: Arc<Mutex<Option<String>>>,
+    /// Set when the last start attempt failed or the overlay thread died with an error.
+    start_failed: Arc<AtomicBool>,
+    /// Set with `start_failed` when the failure was finding the monitor or creating the overlay on it.
+    display_unavailable: Arc<AtomicBool>,
+    /// Set while the main loop retries a start that failed for lack of a display; manual starts and stops clear it.
+    waiting_for_display: AtomicBool,
+    /// Cleared by the render loop while screen capture is unavailable or repeatedly failing.
+    capture_healthy: Arc<AtomicBool>,
+    display_power: Arc<DisplayPower>,
+    diagnostics: Arc<Mutex<OverlayDiagnostics>>,
+    /// What the running overlay was started with, to tell which restart requests need one.
+    launched: Mutex<Option<LaunchSettings>>,
+    restart_queue: Arc<RestartQueue>,
+    color_conflict: Mutex<ConflictStatus>,
+    /// Applied on top of the strength setting, e.g. while another color filter is active.
+    strength_scale: Arc<Mutex<f32>>,
+    frame_dump: Arc<FrameDump>,
+    output_preview: Arc<OutputPreview>,
+    /// Kept here rather than in the app state so it is never saved.
+    debug_visibility: Mutex<DebugVisibility>,
+    /// Uptime of the running overlay and today's total, stored through the app state.
+    active_time: Mutex<ActiveTime>,
+    /// Set while a manual restart stops and starts the overlay, so the uptime runs on.
+    restarting: AtomicBool,
+    /// Set from `restart` until the restarted overlay presented its first frame.
+    restart_pending: Arc<AtomicBool>,
 }
 
 impl OverlayManager {
-    pub fn new(state: Arc<StateManager>) -> Self {
+    pub fn new(state: Arc<StateManager>) -> Arc<Self> {
+        let overlay_state = Arc::new(Mutex::new(None));
+        let strength_scale = Arc::new(Mutex::new(1.0));
+        Self::spawn_state_listener(&state, Arc::clone(&overlay_state), Arc::clone(&strength_scale));
+        let restart_queue = Arc::new(RestartQueue::default());
+
+        Arc::new_cyclic(|manager| {
+            Self::spawn_restart_worker(manager.clone(), Arc::clone(&restart_queue));
+            Self::with_state(state, overlay_state, restart_queue, strength_scale)
+        })
+    }
+
+    fn with_state(state: Arc<StateManager>, overlay_state: Arc<Mutex<Option<Arc<RwLock<OverlayState>>>>>, restart_queue: Arc<RestartQueue>, strength_scale: Arc<Mutex<f32>>) -> Self {
+        let today = chrono::Local::now().date_naive();
+        let active_today = state.active_time(today).unwrap_or_else(|e| {
+            log_warn!("Failed to load today's active time: {:#}", e);
+            Duration::ZERO
+        });
         Self {
             app_state: state,
-            running: Arc::new(Mutex::new(false)),
-            overlay_thread: Mutex::new(None),
+            lifecycle: Arc::new(OverlayLifecycle::default()),
             last_monitor: Mutex::new(None),
             frame_stats: Arc::new(Mutex::new(None)),
-            overlay_state: Arc::new(Mutex::new(None)),
+            overlay_state,
+            throttle: Mutex::new(OverlayThrottle::Full),
+            spectrum_status: Arc::new(Mutex::new(None)),
+            start_failed: Arc::new(AtomicBool::new(false)),
+            display_unavailable: Arc::new(AtomicBool::new(false)),
+            waiting_for_display: AtomicBool::new(false),
+            capture_healthy: Arc::new(AtomicBool::new(true)),
+            display_power: Arc::new(DisplayPower::default()),
+            diagnostics: Arc::new(Mutex::new(OverlayDiagnostics::default())),
+            launched: Mutex::new(None),
+            restart_queue,
+            color_conflict: Mutex::new(ConflictStatus::default()),
+            strength_scale,
+            frame_dump: Arc::new(FrameDump::default()),
+            output_preview: Arc::new(OutputPreview::default()),
+            debug_visibility: Mutex::new(DebugVisibility::Normal),
+            active_time: Mutex::new(ActiveTime::new(today, active_today, Instant::now())),
+            restarting: AtomicBool::new(false),
+            restart_pending: Arc::new(AtomicBool::new(false)),
         }
     }
 
+    /// True from `start` until the overlay stops, including while it is still starting.
     pub fn is_running(&self) -> bool {
-        *self.running.lock()
-    }
-
-    pub fn get_frame_stats(&self) -> Option<(f32, f32)> {
-        *self.frame_stats.lock()
+        self.lifecycle.phase().is_on()
     }
 
-    pub fn toggle(&self) {
-        let running = self.is_running();
-        if running {
-            self.stop();
+    pub fn state(&self) -> OverlayStatus {
+        let phase = self.lifecycle.phase();
+        if phase.is_on() {
+            if self.restart_pending.load(Ordering::Acquire) {
+                OverlayStatus::Restarting
+            } else if phase == Phase::Starting {
+                OverlayStatus::Starting
+            } else {
+                OverlayStatus::Running
+            }
+        } else if self.start_failed.load(Ordering::Acquire) {
+            if self.waiting_for_display.load(Ordering::Acquire) {
+                OverlayStatus::WaitingForDisplay
+            } else {
+                OverlayStatus::Failed
+            }
         } else {
-            self.start();
+            OverlayStatus::Stopped
         }
     }
 
-    pub fn update_strength(&self, strength: f32) {
-        if let Some(ref state) = *self.overlay_state.lock() {
-            state.write().hue_mapper.set_strength(strength);
-        }
+    /// True if the last start failed because the monitor wasn't there or the overlay couldn't be
+    /// created on it, which may resolve itself once the display topology settles.
+    pub fn failed_for_display(&self) -> bool {
+        !self.is_running() && self.start_failed.load(Ordering::Acquire) && self.display_unavailable.load(Ordering::Acquire)
     }
 
-    pub fn start(&self) {
-        let mut running = self.running.lock();
-        if *running {
-            return;
-        }
+    pub fn waiting_for_display(&self) -> bool {
+        self.waiting_for_display.load(Ordering::Acquire)
+    }
 
-        let (spectrum_name, noise_name, strength, monitor_index, cap_to_monitor_refresh) = self.app_state.read(|s| {
-            (
-                s.spectrum_name.clone(),
-                s.noise_texture.clone(),
-                s.strength,
-                s.last_monitor.unwrap_or(0),
-                s.cap_to_monitor_refresh,
-            )
-        });
+    pub fn set_waiting_for_display(&self, waiting: bool) {
+        self.waiting_for_display.store(waiting, Ordering::Release);
+    }
 
-        let spectrum_name = match spectrum_name {
-            Some(name) => name,
-            None => {
-                log_error!("No spectrum selected");
-                return;
-            }
-        };
+    /// How long the overlay has been running, `None` while it is stopped.
+    pub fn uptime(&self) -> Option<Duration> {
+        self.active_time.lock().uptime(Instant::now()).filter(|_| self.is_running())
+    }
 
-        let spectrum_path = self.app_state.get_spectrum_path(&spectrum_name);
-        let spectrum_pair = match SpectrumPair::load_from_file(spectrum_path) {
-            Ok(sp) => {
-                log_info!("Loaded spectrum: {}", spectrum_name);
-                sp
-            }
-            Err(e) => {
-                log_error!("Failed to load spectrum '{}': {}", spectrum_name, e);
-                return;
-            }
-        };
+    /// How long the overlay ran today, including the current run.
+    pub fn active_today(&self) -> Duration {
+        self.active_time.lock().total(chrono::Local::now().date_naive())
+    }
 
-        let noise_texture = if let Some(ref name) = noise_name {
-            let noise_path = self.app_state.get_noise_path(name);
-            match NoiseTexture::load_from_file(noise_path) {
-                Ok(nt) => {
-                    log_info!("Loaded noise texture: {}", name);
-                    Some(nt)
-                }
-                Err(e) => {
-                    log_error!("Failed to load noise texture '{}': {}", name, e);
-                    None
-                }
+    pub fn reset_active_today(&self) {
+        let today = chrono::Local::now().date_naive();
+        self.active_time.lock().reset(today);
+        self.app_state.reset_active_time(today);
+    }
+
+    /// Adds the time since the last call to today's active time, storing it every
+    /// `ACTIVE_TIME_SAVE_INTERVAL`. Called every loop of the main thread, which also notices
+    /// an overlay thread that ended by itself.
+    pub fn count_active_time(&self) {
+        let mut active_time = self.active_time.lock();
+        let (now, wall) = (Instant::now(), chrono::Local::now());
+        if self.is_running() || self.restarting.load(Ordering::Acquire) {
+            active_time.start(now, wall);
+            if let Some((day, time)) = active_time.count(now, wall) {
+                self.app_state.record_active_time(day, time);
             }
         } else {
-            None
-        };
+            for (day, time) in active_time.stop(now, wall) {
+                self.app_state.record_active_time(day, time);
+            }
+        }
+    }
 
-        let hue_mapper = HueMapper::new(strength);
+    pub fn get_frame_stats(&self) -> Option<FrameStats> {
+        *self.frame_stats.lock()
+    }
 
-        let running_flag = Arc::clone(&self.running);
-        let frame_stats = Arc::clone(&self.frame_stats);
-        let overlay_state_ref = Arc::clone(&self.overlay_state);
-        *running = true;
-        *self.last_monitor.lock() = Some(monitor_index);
+    pub fn get_diagnostics(&self) -> OverlayDiagnostics {
+        self.diagnostics.lock().clone()
+    }
 
-        let handle = thread::spawn(move || {
-            log_info!("Overlay thread started (Monitor {})", monitor_index);
+    /// True while the running overlay is occluded and only checks whether it is shown again.
+    pub fn is_occluded(&self) -> bool {
+        self.is_running() && self.diagnostics.lock().occluded
+    }
 
-            #[cfg(windows)]
-            unsafe {
-                let _ = CoInitializeEx(None, COINIT_MULTITHREADED);
+    /// True if the overlay failed to start or is running without a working screen capture.
+    pub fn has_error(&self) -> bool {
+        self.start_failed.load(Ordering::Acquire)
+            || (self.is_running() && !self.capture_healthy.load(Ordering::Acquire))
+    }
 
-                let monitor_info = match get_monitor_info(monitor_index) {
-                    Ok(info) => info,
-                    Err(e) => {
-                        log_error!("Failed to get monitor info: {}", e);
-                        *running_flag.lock() = false;
-                        return;
-                    }
-                };
+    /// Called on display power notifications; rendering is suspended while the display is off.
+    pub fn set_display_on(&self, on: bool) {
+        let mut off = self.display_power.off.lock();
+        if *off == !on {
+            return;
+        }
+        *off = !on;
+        log_debug!("Console display turned {}", if on { "on" } else { "off" });
+        self.display_power.changed.notify_all();
+    }
 
-                let overlay_state = OverlayState {
-                    spectrum_pair,
-                    noise_texture,
-                    hue_mapper,
-                    monitor_refresh_rate: monitor_info.refresh_rate as f32,
-                };
+    /// Starts the overlay if it is stopped or stopping, stops it otherwise; toggling while it is
+    /// still starting stops it as soon as it is up. Returns whether it is meant to run now.
+    pub fn toggle(&self) -> bool {
+        self.set_waiting_for_display(false);
+        let outcome = self.lifecycle.toggle(|| self.spawn_thread());
+        let run = outcome.run;
+        self.finish_transition(outcome, true);
+        run
+    }
+
+    /// Restarts the running overlay once no further request arrived for `RESTART_DELAY`, so a burst
+    /// of setting changes costs one restart. Requests made while a restart runs are absorbed by it
+    /// unless they changed something it didn't pick up. A changed spectrum alone is swapped in live.
+    pub fn request_restart(&self) {
+        if !self.is_running() {
+            return;
+        }
+        if self.restart_queue.closed.load(Ordering::Acquire) {
+            self.apply_restart();
+            return;
+        }
 
-                let overlay_state = Arc::new(RwLock::new(overlay_state));
-                *overlay_state_ref.lock() = Some(Arc::clone(&overlay_state));
+        *self.restart_queue.due.lock() = Some(Instant::now() + RESTART_DELAY);
+        self.restart_queue.wakeup.notify_one();
+    }
 
-                let result = (|| -> Result<()> {
-                    let mut overlay = DCompOverlay::new(overlay_state, monitor_info, monitor_index, cap_to_monitor_refresh)?;
-                    overlay.run_message_loop(&running_flag, &frame_stats)
-                })();
+    /// Stops and starts the running overlay, e.g. to pick up the desktop again after a driver
+    /// update, as one run: the uptime goes on. Goes through the restart queue, so a restart
+    /// already pending or in progress absorbs the request. `source` is logged.
+    pub fn restart(&self, source: &str) {
+        if !self.is_running() {
+            return;
+        }
+        if self.restart_pending.swap(true, Ordering::AcqRel) {
+            log_debug!("Overlay restart from {} absorbed by the one in progress", source);
+            return;
+        }
+        log_info!("Overlay restart requested from {}", source);
 
-                *overlay_state_ref.lock() = None;
+        self.restart_queue.manual.store(true, Ordering::Release);
+        if self.restart_queue.closed.load(Ordering::Acquire) {
+            self.apply_restart();
+            return;
+        }
+        *self.restart_queue.due.lock() = Some(Instant::now() + RESTART_DELAY);
+        self.restart_queue.wakeup.notify_one();
+    }
 
-                if let Err(e) = result {
-                    log_error!("Overlay error: {}", e);
+    fn spawn_restart_worker(manager: Weak<OverlayManager>, queue: Arc<RestartQueue>) {
+        let worker_queue = Arc::clone(&queue);
+        let spawned = thread::Builder::new().name("overlay-restart".into()).spawn(move || {
+            let queue = worker_queue;
+            loop {
+                let mut due = queue.due.lock();
+                loop {
+                    if queue.closed.load(Ordering::Acquire) {
+                        return;
+                    }
+                    match *due {
+                        Some(at) if Instant::now() >= at => break,
+                        Some(at) => {
+                            queue.wakeup.wait_until(&mut due, at);
+                        }
+                        None => queue.wakeup.wait(&mut due),
+                    }
                 }
+                *due = None;
+                drop(due);
 
-                *running_flag.lock() = false;
-                log_info!("Overlay thread ended");
-            }
-
-            #[cfg(not(windows))]
-            {
-                log_error!("Overlay is only supported on Windows");
-                *running_flag.lock() = false;
+                let Some(manager) = manager.upgrade() else {
+                    return;
+                };
+                manager.apply_restart();
             }
         });
 
-        *self.overlay_thread.lock() = Some(handle);
-        self.app_state.update(|s| {
-            s.overlay_enabled = true;
-            s.last_overlay_enabled = true;
-        });
-        log_info!("Overlay started (Monitor {}, Spectrum: {})", monitor_index, spectrum_name);
+        if let Err(e) = spawned {
+            log_error!("Failed to start overlay restart thread, setting changes restart the overlay immediately: {}", e);
+            queue.closed.store(true, Ordering::Release);
+        }
     }
 
-    pub fn stop(&self) {
-        let mut running = self.running.lock();
-        if !*running {
+    /// Brings the running overlay in line with the start-only settings: nothing if they are
+    /// unchanged, a live spectrum swap if only the spectrum changed, a restart otherwise. Always
+    /// restarts after `restart`.
+    fn apply_restart(&self) {
+        let manual = self.restart_queue.manual.swap(false, Ordering::AcqRel);
+        if !self.is_running() {
+            return;
+        }
+        if manual {
+            log_info!("Restarting overlay (requested)");
+            self.restarting.store(true, Ordering::Release);
+            self.stop_thread(false);
+            self.start_thread();
+            self.restarting.store(false, Ordering::Release);
             return;
         }
 
-        *running = false;
-        drop(running);
+        let wanted = self.app_state.read(LaunchSettings::of);
+        let Some(launched) = self.launched.lock().clone() else {
+            return;
+        };
+        if wanted == launched {
+            log_debug!("Restart requested but the overlay already runs with these settings");
+            return;
+        }
 
-        if let Some(handle) = self.overlay_thread.lock().take() {
-            let _ = handle.join();
+        if wanted.only_spectrum_differs(&launched) {
+            let name = wanted.spectrum_name.as_deref().unwrap_or_default();
+            let loaded = self.app_state
+                .get_spectrum_path(name)
+                .and_then(|path| SpectrumPair::load_with_selection(path, wanted.spectrum_selection).map_err(Into::into));
+            match loaded {
+                Ok(spectrum_pair) => {
+                    log_info!("Spectrum switched without restarting the overlay: {}", name);
+                    self.update_spectrum(spectrum_pair);
+                    *self.launched.lock() = Some(wanted);
+                    return;
+                }
+                // The restart reports the error in the GUI
+                Err(e) => log_warn!("Failed to switch spectrum live, restarting the overlay: {:#}", e),
+            }
         }
 
-        *self.frame_stats.lock() = None;
+        log_info!("Restarting overlay (settings changed)");
+        self.stop();
+        self.start();
+    }
 
-        let monitor_idx = self.last_monitor.lock().take();
-        self.app_state.update(|s| {
-            s.overlay_enabled = false;
-            s.last_overlay_enabled = false;
+    /// Pushes settings the render thread can apply live into the running overlay whenever they
+    /// change, so callers only have to update the app state.
+    fn spawn_state_listener(app_state: &Arc<StateManager>, overlay_state: Arc<Mutex<Option<Arc<RwLock<OverlayState>>>>>, strength_scale: Arc<Mutex<f32>>) {
+        let changes = app_state.subscribe();
+        // Weak so the listener doesn't keep the state manager alive
+        let app_state = Arc::downgrade(app_state);
+
+        let spawned = thread::Builder::new().name("overlay-state-listener".into()).spawn(move || {
+            while let Ok(mut pending) = changes.recv() {
+                // Coalesce a burst such as a slider drag into one update
+                while let Ok(more) = changes.try_recv() {
+                    pending |= more;
+                }
+                if !pending.strength && !pending.live_rendering {
+                    continue;
+                }
+
+                let Some(app_state) = app_state.upgrade() else {
+                    break;
+                };
+                let Some(overlay_state) = overlay_state.lock().clone() else {
+                    continue;
+                };
+
+                let settings = app_state.read(|s| LiveSettings {
+                    strength: s.strength,
+                    strength_curve: s.strength_curve,
+                    noise_mapping: s.noise_mapping,
+                    noise_intensity: s.noise_intensity,
+                    noise_target: s.noise_target,
+                    blend_ratio: s.blend_ratio,
+                    temporal_smoothing: s.temporal_smoothing,
+                    hue_gate: s.hue_gate(),
+                    posterize_levels: s.posterize_levels,
+                    topmost_mode: s.topmost_mode,
+                    test_pattern: s.test_pattern,
+                    solid_color: s.solid_color,
+                    correct_cursor: s.correct_cursor,
+                    exclude_shell: s.exclude_shell,
+                    hide_from_capture: s.hide_from_capture,
+                    target_window: s.correction_target().cloned(),
+                    color_math: s.color_math,
+                });
+
+                let mut state = overlay_state.write();
+                state.hue_mapper.set_strength(settings.strength * *strength_scale.lock());
+                state.strength_curve = settings.strength_curve;
+                state.noise_mapping = settings.noise_mapping;
+                state.noise_intensity = settings.noise_intensity;
+                state.noise_target = settings.noise_target;
+                state.blend_ratio = settings.blend_ratio;
+                state.temporal_smoothing = settings.temporal_smoothing;
+                state.hue_gate = settings.hue_gate;
+                state.posterize_levels = settings.posterize_levels;
+                state.topmost_mode = settings.topmost_mode;
+                state.test_pattern = settings.test_pattern;
+                state.solid_color = settings.solid_color;
+                state.correct_cursor = settings.correct_cursor;
+                state.exclude_shell = settings.exclude_shell;
+                state.hide_from_capture = settings.hide_from_capture;
+                state.target_window = settings.target_window;
+                state.color_math = settings.color_math;
+            }
         });
 
-        if let Some(idx) = monitor_idx {
-            log_info!("Overlay stopped (Monitor {})", idx);
-        } else {
-            log_info!("Overlay stopped");
+        if let Err(e) = spawned {
+            log_error!("Failed to start overlay state listener, settings changes will need an overlay restart: {}", e);
         }
     }
-}
 
-impl Drop for OverlayManager {
-    fn drop(&mut self) {
-        self.stop();
+    /// Swaps the spectrum of the running overlay without restarting it.
+    pub fn update_spectrum(&self, spectrum_pair: SpectrumPair) {
+        if let Some(ref state) = *self.overlay_state.lock() {
+            let mut state = state.write();
+            if let Some(ref noise) = state.noise_texture {
+                warn_if_noise_exceeds_spectra(noise, &spectrum_pair);
+            }
+            state.spectrum_pair = spectrum_pair;
+            state.spectrum_generation += 1;
+        }
     }
-}
 
-#[cfg(windows)]
-#[derive(Clone)]
-struct MonitorInfo {
-    pos: (i32, i32),
-    size: (i32, i32),
-    refresh_rate: u32,
-}
+    /// Reports a hot-reload or asset loading result for the GUI status area.
+    pub fn set_spectrum_status(&self, message: String) {
+        *self.spectrum_status.lock() = Some(message);
+    }
 
-#[cfg(windows)]
-unsafe fn get_monitor_info(monitor_index: usize) -> Result<MonitorInfo> {
-    use std::sync::Mutex;
+    pub fn take_spectrum_status(&self) -> Option<String> {
+        self.spectrum_status.lock().take()
+    }
 
-    let monitors = Mutex::new(Vec::<MonitorInfo>::new());
+    /// Saves the next frame the overlay renders, captured and corrected, for debugging a
+    /// spectrum; see `frame_dump`. The outcome is picked up with `take_frame_dump_result`.
+    pub fn request_frame_dump(&self) {
+        self.frame_dump.arm();
+    }
 
-    let _ = EnumDisplayMonitors(
-        None,
-        None,
-        Some(monitor_enum_proc),
-        LPARAM(&monitors as *const _ as isize),
-    );
+    /// True from `request_frame_dump` until the overlay rendered the frame to dump.
+    pub fn frame_dump_pending(&self) -> bool {
+        self.frame_dump.is_armed()
+    }
 
-    let monitors = monitors.into_inner().unwrap();
+    /// The folder of the last finished dump, or why it failed.
+    pub fn take_frame_dump_result(&self) -> Option<std::result::Result<std::path::PathBuf, String>> {
+        self.frame_dump.take_result()
+    }
 
-    if monitor_index >= monitors.len() {
-        anyhow::bail!("Monitor index {} out of range (found {} monitors)", monitor_index, monitors.len());
+    /// Has the overlay read back a scaled-down copy of what it presents about every
+    /// `PREVIEW_INTERVAL`, for the developer preview window; see `output_preview`.
+    pub fn set_output_preview(&self, enabled: bool) {
+        self.output_preview.set_enabled(enabled);
     }
 
-    Ok(monitors[monitor_index].clone())
-}
+    /// The newest preview frame since the last call, or why reading it back failed.
+    pub fn take_output_preview(&self) -> Option<std::result::Result<image::RgbaImage, String>> {
+        self.output_preview.take_frame()
+    }
 
-#[cfg(windows)]
-unsafe extern "system" fn monitor_enum_proc(
-    hmonitor: HMONITOR,
-    _hdc: HDC,
-    _rect: *mut RECT,
-    lparam: LPARAM,
-) -> BOOL {
-    use std::sync::Mutex;
+    pub fn throttle(&self) -> OverlayThrottle {
+        *self.throttle.lock()
+    }
 
-    let monitors = &*(lparam.0 as *const Mutex<Vec<MonitorInfo>>);
+    pub fn strength_scale(&self) -> f32 {
+        *self.strength_scale.lock()
+    }
 
-    let mut info: MONITORINFOEXW = std::mem::zeroed();
-    info.monitorInfo.cbSize = std::mem::size_of::<MONITORINFOEXW>() as u32;
+    /// Scales the correction strength of the running and later overlays without changing the
+    /// setting; 1.0 restores it.
+    pub fn set_strength_scale(&self, scale: f32) {
+        *self.strength_scale.lock() = scale;
+        if let Some(ref state) = *self.overlay_state.lock() {
+            let strength = self.app_state.read(|s| s.strength);
+            state.write().hue_mapper.set_strength(strength * scale);
+        }
+    }
 
-    if GetMonitorInfoW(hmonitor, &mut info as *mut _ as *mut _).as_bool() {
-        let rect = info.monitorInfo.rcMonitor;
-        let pos = (rect.left, rect.top);
-        let size = (rect.right - rect.left, rect.bottom - rect.top);
+    pub fn debug_visibility(&self) -> DebugVisibility {
+        *self.debug_visibility.lock()
+    }
 
-        let refresh_rate = {
-            let mut dev_mode: DEVMODEW = std::mem::zeroed();
-            dev_mode.dmSize = std::mem::size_of::<DEVMODEW>() as u16;
+    /// Applies to the running overlay right away and to later starts of this session.
+    pub fn set_debug_visibility(&self, mode: DebugVisibility) {
+        *self.debug_visibility.lock() = mode;
+        if let Some(ref state) = *self.overlay_state.lock() {
+            state.write().debug_visibility = mode;
+        }
+    }
 
-            if EnumDisplaySettingsW(
-                PCWSTR(info.szDevice.as_ptr()),
-                ENUM_CURRENT_SETTINGS,
-                &mut dev_mode,
-            ).as_bool() {
-                dev_mode.dmDisplayFrequency
-            } else {
-                60
-            }
-        };
+    /// The color filter found by the last check, unless the user dismissed it.
+    pub fn color_conflict(&self) -> Option<ColorConflict> {
+        let status = self.color_conflict.lock();
+        status.detected.clone().filter(|_| !status.dismissed)
+    }
 
-        monitors.lock().unwrap().push(MonitorInfo {
-            pos,
-            size,
-            refresh_rate,
+    /// Stores the result of a color filter check; true if `color_conflict` changed. A dismissed
+    /// conflict stays dismissed until it clears or another one is found.
+    pub fn set_color_conflict(&self, conflict: Option<ColorConflict>) -> bool {
+        let mut status = self.color_conflict.lock();
+        if status.detected == conflict {
+            return false;
+        }
+        let was_shown = status.detected.is_some() && !status.dismissed;
+        *status = ConflictStatus { detected: conflict, dismissed: false };
+        was_shown || status.detected.is_some()
+    }
+
+    /// Hides the current conflict and lifts its automatic pause or strength reduction.
+    pub fn dismiss_color_conflict(&self) {
+        self.color_conflict.lock().dismissed = true;
+    }
+
+    pub fn set_throttle(&self, throttle: OverlayThrottle) {
+        *self.throttle.lock() = throttle;
+        if let Some(ref state) = *self.overlay_state.lock() {
+            state.write().throttle = throttle;
+        }
+    }
+
+    pub fn start(&self) {
+        self.set_waiting_for_display(false);
+        self.start_thread();
+    }
+
+    /// Starts again after a display failure without cancelling the pending retries.
+    pub fn retry_start(&self) {
+        self.start_thread();
+    }
+
+    fn start_thread(&self) {
+        let outcome = self.lifecycle.start(|| self.spawn_thread());
+        self.finish_transition(outcome, true);
+    }
+
+    /// Spawns the overlay thread for `lifecycle`, which holds its lock meanwhile; `None` if there
+    /// is no spectrum to run with.
+    fn spawn_thread(&self) -> Option<thread::JoinHandle<Option<SessionSummary>>> {
+        // Left over from a restart that found the overlay already stopped
+        if !self.restarting.load(Ordering::Acquire) {
+            self.restart_pending.store(false, Ordering::Release);
+        }
+
+        self.start_failed.store(false, Ordering::Release);
+        self.display_unavailable.store(false, Ordering::Release);
+        self.capture_healthy.store(true, Ordering::Release);
+        // One copy for everything below, so a change saved mid-start can't mix old and new settings
+        let s = self.app_state.snapshot();
+        *self.launched.lock() = Some(LaunchSettings::of(&s));
+
+        let (spectrum_name, spectrum_selection, noise_name, strength, strength_curve, monitor_index, cap_to_monitor_refresh, interactive_debug, posterize_levels, noise_mapping, noise_intensity, noise_target, blend_ratio, temporal_smoothing, hue_gate, max_device_recoveries, topmost_mode, overlay_source, test_pattern, solid_color, correct_cursor, exclude_shell, hide_from_capture, target_window, color_math, render_thread_priority) = (
+            s.spectrum_name.clone(),
+            s.spectrum_selection,
+            s.noise_texture.clone(),
+            s.strength,
+            s.strength_curve,
+            s.last_monitor.unwrap_or(0),
+            s.cap_to_monitor_refresh,
+            s.debug_overlay,
+            s.posterize_levels,
+            s.noise_mapping,
+            s.noise_intensity,
+            s.noise_target,
+            s.blend_ratio,
+            s.temporal_smoothing,
+            s.hue_gate(),
+            s.max_device_recoveries,
+            s.topmost_mode,
+            s.overlay_source,
+            s.test_pattern,
+            s.solid_color,
+            s.correct_cursor,
+            s.exclude_shell,
+            s.hide_from_capture,
+            s.correction_target().cloned(),
+            s.color_math,
+            s.render_thread_priority,
+        );
+
+        let spectrum_name = match spectrum_name {
+            Some(name) => name,
+            None => {
+                log_error!("No spectrum selected");
+                self.start_failed.store(true, Ordering::Release);
+                return None;
+            }
+        };
+
+        let hue_mapper = HueMapper::new(strength * self.strength_scale());
+        let throttle = self.throttle();
+        let debug_visibility = self.debug_visibility();
+
+        let app_state = Arc::clone(&self.app_state);
+        let lifecycle = Arc::clone(&self.lifecycle);
+        let frame_stats = Arc::clone(&self.frame_stats);
+        let spectrum_status = Arc::clone(&self.spectrum_status);
+        let start_failed = Arc::clone(&self.start_failed);
+        let display_unavailable = Arc::clone(&self.display_unavailable);
+        let capture_healthy = Arc::clone(&self.capture_healthy);
+        let display_power = Arc::clone(&self.display_power);
+        let diagnostics = Arc::clone(&self.diagnostics);
+        let frame_dump = Arc::clone(&self.frame_dump);
+        let output_preview = Arc::clone(&self.output_preview);
+        let restart_pending = Arc::clone(&self.restart_pending);
+        let overlay_state_ref = Arc::clone(&self.overlay_state);
+        *self.last_monitor.lock() = Some(monitor_index);
+        log_info!(fields: { monitor = monitor_index, spectrum = spectrum_name }, "Overlay starting");
+
+        let handle = thread::spawn(move || {
+            // The panic hook writes the crash report; catching here keeps the manager state consistent
+            let lifecycle_for_panic = Arc::clone(&lifecycle);
+            let overlay_state_for_panic = Arc::clone(&overlay_state_ref);
+            let start_failed_for_panic = Arc::clone(&start_failed);
+            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(move || {
+                log_info!("Overlay thread started (Monitor {})", monitor_index);
+                let mut startup_timer = StartupTimer::start("overlay");
+
+                let fail = |error: String| {
+                    log_error!("{}", error);
+                    *spectrum_status.lock() = Some(error);
+                    start_failed.store(true, Ordering::Release);
+                    lifecycle.ended();
+                };
+
+                // Loaded here rather than in start() so large noise textures don't block the caller
+                let (spectrum_pair, noise_texture) = match load_assets(&app_state, &spectrum_name, spectrum_selection, noise_name.as_deref()) {
+                    Ok(assets) => assets,
+                    Err(e) => {
+                        fail(tr!("overlay.load_failed", spectrum = spectrum_name, error = format!("{:#}", e)));
+                        return None;
+                    }
+                };
+                startup_timer.checkpoint("assets");
+                // Toggled off while loading; the window isn't there yet to tear down
+                if !lifecycle.keep_running() {
+                    log_info!("Overlay start cancelled");
+                    lifecycle.ended();
+                    return None;
+                }
+
+                #[cfg(windows)]
+                unsafe {
+                    let _ = CoInitializeEx(None, COINIT_MULTITHREADED);
+
+                    // Only reached with --force. One clear error instead of failing device and
+                    // capture setup, which would be taken for a missing display and retried
+                    if let Some(unsupported) = crate::platform_check::detect() {
+                        fail(unsupported.message());
+                        return None;
+                    }
+
+                    // Reverted when the guard drops at the end of the thread
+                    let priority = crate::render_priority::PriorityGuard::raise(render_thread_priority);
+                    diagnostics.lock().render_priority = Some(priority.achieved());
+
+                    let monitor_info = match get_monitor_info(monitor_index) {
+                        Ok(info) => info,
+                        Err(e) => {
+                            display_unavailable.store(true, Ordering::Release);
+                            fail(format!("Failed to get monitor info: {}", e));
+                            return None;
+                        }
+                    };
+                    // A swap chain can't be sized to what remote desktop or a virtual display
+                    // sometimes reports; not retried, the monitor is there
+                    if let Err(unusable) = chromabridge::monitor::check_size(monitor_info.size.0, monitor_info.size.1) {
+                        fail(tr!("overlay.monitor_unusable", monitor = monitor_index + 1, reason = unusable));
+                        return None;
+                    }
+                    let (left, top) = monitor_info.pos;
+                    diagnostics.lock().monitor_rect = Some([left, top, left + monitor_info.size.0, top + monitor_info.size.1]);
+
+                    let overlay_state = OverlayState {
+                        spectrum_pair,
+                        spectrum_generation: 0,
+                        noise_texture,
+                        noise_mapping,
+                        noise_intensity,
+                        noise_target,
+                        blend_ratio,
+                        hue_mapper,
+                        strength_curve,
+                        monitor_refresh_rate: monitor_info.refresh_rate as f32,
+                        throttle,
+                        posterize_levels,
+                        temporal_smoothing,
+                        hue_gate,
+                        topmost_mode,
+                        test_pattern,
+                        solid_color,
+                        correct_cursor,
+                        exclude_shell,
+                        hide_from_capture,
+                        target_window,
+                        color_math,
+                        debug_visibility,
+                    };
+
+                    let overlay_state = Arc::new(RwLock::new(overlay_state));
+                    *overlay_state_ref.lock() = Some(Arc::clone(&overlay_state));
+
+                    let mut session = SessionRecorder::new(monitor_index);
+                    let result = (|| -> Result<()> {
+                        let mut overlay = DCompOverlay::new(overlay_state, monitor_info, monitor_index, overlay_source, cap_to_monitor_refresh, interactive_debug, &mut startup_timer)
+                            .inspect_err(|_| display_unavailable.store(true, Ordering::Release))?;
+                        lifecycle.set_running();
+                        overlay.run_message_loop(&lifecycle, &frame_stats, &capture_healthy, &display_power, &diagnostics, &app_state, &frame_dump, &output_preview, &mut session, max_device_recoveries, Some(startup_timer), &restart_pending)
+                    })();
+
+                    *overlay_state_ref.lock() = None;
+
+                    if let Err(e) = result {
+                        log_error!("Overlay error: {}", e);
+                        start_failed.store(true, Ordering::Release);
+                    }
+
+                    lifecycle.ended();
+                    log_info!("Overlay thread ended");
+                    session.take_summary(app_state.read(|s| s.spectrum_name.clone()))
+                }
+
+                #[cfg(not(windows))]
+                {
+                    let _ = (spectrum_pair, noise_texture, startup_timer);
+                    fail("Overlay is only supported on Windows".to_string());
+                    None
+                }
+            }));
+
+            if result.is_err() {
+                log_error!("Overlay thread panicked - overlay stopped");
+                *overlay_state_for_panic.lock() = None;
+                start_failed_for_panic.store(true, Ordering::Release);
+                lifecycle_for_panic.ended();
+            }
+            result.ok().flatten()
         });
+        Some(handle)
     }
 
-    true.into()
+    pub fn stop(&self) {
+        self.set_waiting_for_display(false);
+        self.restart_pending.store(false, Ordering::Release);
+        self.stop_thread(true);
+    }
+
+    /// Stops the overlay on app exit, keeping `last_overlay_enabled` so it is restored next launch.
+    pub fn shutdown(&self) {
+        self.stop_thread(false);
+    }
+
+    fn stop_thread(&self, persist: bool) {
+        let outcome = self.lifecycle.stop();
+        self.finish_transition(outcome, persist);
+    }
+
+    /// Does what goes with a start or stop once `lifecycle` finished it. `persist` records
+    /// whether the overlay is on in the app state.
+    fn finish_transition(&self, outcome: Outcome<Option<SessionSummary>>, persist: bool) {
+        if let Some(Ok(Some(summary))) = outcome.joined {
+            self.app_state.record_session(summary);
+        }
+        if outcome.started {
+            self.active_time.lock().start(Instant::now(), chrono::Local::now());
+            self.app_state.update(|s| {
+                s.overlay_enabled = true;
+                s.last_overlay_enabled = true;
+            });
+        }
+        if !outcome.stopped {
+            return;
+        }
+
+        self.count_active_time();
+        if persist {
+            self.restart_pending.store(false, Ordering::Release);
+        }
+        *self.frame_stats.lock() = None;
+
+        let monitor_idx = self.last_monitor.lock().take();
+        if persist {
+            self.app_state.update(|s| {
+                s.overlay_enabled = false;
+                s.last_overlay_enabled = false;
+            });
+        }
+
+        if let Some(idx) = monitor_idx {
+            log_info!(fields: { monitor = idx }, "Overlay stopped");
+        } else {
+            log_info!("Overlay stopped");
+        }
+    }
+}
+
+impl Drop for OverlayManager {
+    fn drop(&mut self) {
+        {
+            // Under the lock, so the restart thread can't miss the wakeup between its check and wait
+            let _due = self.restart_queue.due.lock();
+            self.restart_queue.closed.store(true, Ordering::Release);
+            self.restart_queue.wakeup.notify_all();
+        }
+        self.stop();
+    }
+}
+
+/// Loads the spectrum and the optional noise texture on the overlay thread. A noise texture
+/// that fails to load is skipped, a spectrum that fails to load is an error.
+fn load_assets(
+    app_state: &StateManager,
+    spectrum_name: &str,
+    selection: chromabridge::SpectrumSelection,
+    noise_name: Option<&str>,
+) -> Result<(SpectrumPair, Option<NoiseTexture>)> {
+    let spectrum_pair = SpectrumPair::load_with_selection(app_state.get_spectrum_path(spectrum_name)?, selection)?;
+    log_info!("Loaded spectrum: {}", spectrum_name);
+
+    let max_noise_size = app_state.read(|s| s.max_noise_size);
+    let noise_texture = noise_name.and_then(|name| match NoiseTexture::load_with_max_size(app_state.get_noise_path(name), max_noise_size) {
+        Ok(nt) => {
+            log_info!("Loaded noise texture: {}", name);
+            Some(nt)
+        }
+        Err(e) => {
+            log_error!("Failed to load noise texture '{}': {}", name, e);
+            None
+        }
+    });
+    if let Some(ref noise) = noise_texture {
+        warn_if_noise_exceeds_spectra(noise, &spectrum_pair);
+    }
+
+    Ok((spectrum_pair, noise_texture))
+}
+
+/// The shader clamps indices past the last available spectrum, which silently merges regions
+/// the texture meant to tell apart.
+fn warn_if_noise_exceeds_spectra(noise: &NoiseTexture, spectrum_pair: &SpectrumPair) {
+    let available = spectrum_pair.noise_spectrum_count(MAX_NOISE_INDICES);
+    if spectrum_pair.has_dual_spectrum() && noise.index_count() > available {
+        log_warn!(
+            "Noise texture selects between {} spectra but only {} are available; higher indices use the last one",
+            noise.index_count(),
+            available
+        );
+    }
 }
 
 #[cfg(windows)]
-struct DesktopDuplicator {
-    output_duplication: IDXGIOutputDuplication,
-    _d3d_device: ID3D11Device,
-    _d3d_context: ID3D11DeviceContext,
+#[derive(Clone)]
+struct MonitorInfo {
+    pos: (i32, i32),
+    size: (i32, i32),
+    refresh_rate: u32,
+    /// E.g. `\\.\DISPLAY3`.
+    device: String,
+    /// The adapter description, see `chromabridge::monitor::device_string`.
+    device_string: String,
 }
 
 #[cfg(windows)]
-impl DesktopDuplicator {
-    unsafe fn new(d3d_device: ID3D11Device, d3d_context: ID3D11DeviceContext, monitor_index: usize) -> Result<Self> {
-        let dxgi_device: IDXGIDevice = d3d_device.cast()?;
-        let dxgi_adapter = dxgi_device.GetAdapter()?;
+unsafe fn get_monitor_info(monitor_index: usize) -> Result<MonitorInfo> {
+    use std::sync::Mutex;
 
-        let output: IDXGIOutput = dxgi_adapter.EnumOutputs(monitor_index as u32)?;
-        let output1: IDXGIOutput1 = output.cast()?;
+    let monitors = Mutex::new(Vec::<MonitorInfo>::new());
 
-        let output_duplication = output1.DuplicateOutput(&d3d_device)?;
+    let _ = EnumDisplayMonitors(
+        None,
+        None,
+        Some(monitor_enum_proc),
+        LPARAM(&monitors as *const _ as isize),
+    );
 
-        log_info!("Desktop duplication initialized for monitor {}", monitor_index);
+    let monitors = monitors.into_inner().unwrap();
 
-        Ok(Self {
-            output_duplication,
-            _d3d_device: d3d_device,
-            _d3d_context: d3d_context,
-        })
+    if monitor_index >= monitors.len() {
+        anyhow::bail!("Monitor index {} out of range (found {} monitors)", monitor_index, monitors.len());
     }
 
-    unsafe fn acquire_next_frame(&mut self, timeout_ms: u32) -> Result<Option<ID3D11Texture2D>> {
-        let mut frame_info: DXGI_OUTDUPL_FRAME_INFO = std::mem::zeroed();
-        let mut desktop_resource: Option<IDXGIResource> = None;
+    Ok(monitors[monitor_index].clone())
+}
 
-        match self.output_duplication.AcquireNextFrame(timeout_ms, &mut frame_info, &mut desktop_resource) {
-            Ok(_) => {
-                if let Some(resource) = desktop_resource {
-                    let texture: ID3D11Texture2D = resource.cast()?;
-                    Ok(Some(texture))
-                } else {
-                    Ok(None)
-                }
-            }
-            Err(e) => {
-                if e.code() == DXGI_ERROR_WAIT_TIMEOUT {
-                    return Ok(None);
-                }
-                Err(anyhow::anyhow!("Failed to acquire frame: {:?}", e))
-            }
+#[cfg(windows)]
+unsafe extern "system" fn monitor_enum_proc(
+    hmonitor: HMONITOR,
+    _hdc: HDC,
+    _rect: *mut RECT,
+    lparam: LPARAM,
+) -> BOOL {
+    use std::sync::Mutex;
+
+    let monitors = &*(lparam.0 as *const Mutex<Vec<MonitorInfo>>);
+
+    let mut info: MONITORINFOEXW = std::mem::zeroed();
+    info.monitorInfo.cbSize = std::mem::size_of::<MONITORINFOEXW>() as u32;
+
+    if GetMonitorInfoW(hmonitor, &mut info as *mut _ as *mut _).as_bool() {
+        let rect = info.monitorInfo.rcMonitor;
+        let pos = (rect.left, rect.top);
+        let size = (rect.right - rect.left, rect.bottom - rect.top);
+
+        let device = String::from_utf16_lossy(&info.szDevice.iter().take_while(|&&c| c != 0).copied().collect::<Vec<_>>());
+        let device_string = chromabridge::monitor::device_string(&device).unwrap_or_default();
+
+        let refresh_rate = {
+            let mut dev_mode: DEVMODEW = std::mem::zeroed();
+            dev_mode.dmSize = std::mem::size_of::<DEVMODEW>() as u16;
+
+            let reported = EnumDisplaySettingsW(
+                PCWSTR(info.szDevice.as_ptr()),
+                ENUM_CURRENT_SETTINGS,
+                &mut dev_mode,
+            ).as_bool().then_some(dev_mode.dmDisplayFrequency);
+            chromabridge::monitor::refresh_rate_or_default(&device, reported, chromabridge::monitor::is_virtual_display(&device_string))
+        };
+
+        monitors.lock().unwrap().push(MonitorInfo {
+            pos,
+            size,
+            refresh_rate,
+            device,
+            device_string,
+        });
+    }
+
+    true.into()
+}
+
+/// The shader parameters for drawing `state`.
+///
+/// `capture_rotation` is in clockwise quarter turns, matching `capture_uv` in shaders.hlsl.
+/// `exclusions` are left transparent; past `MAX_EXCLUSION_RECTS` they are ignored.
+/// `use_lut` samples the tables from `RenderPipeline::bake_luts` instead of the shader math.
+#[cfg(windows)]
+fn spectrum_params(state: &OverlayState, show_debug_border: bool, screen_size: (u32, u32), history_valid: bool, capture_rotation: i32, exclusions: &[[f32; 4]], use_lut: bool) -> SpectrumParams {
+    let noise_size = state.noise_texture.as_ref().map_or((1, 1), |n| (n.width(), n.height()));
+    let exclusion_count = exclusions.len().min(MAX_EXCLUSION_RECTS);
+    let mut exclusion_rects = [[0.0; 4]; MAX_EXCLUSION_RECTS];
+    exclusion_rects[..exclusion_count].copy_from_slice(&exclusions[..exclusion_count]);
+    SpectrumParams {
+        strength: state.blend_weight(),
+        use_dual_spectrum: if state.spectrum_pair.has_dual_spectrum() { 1 } else { 0 },
+        use_noise_texture: if state.noise_texture.is_some() { 1 } else { 0 },
+        show_debug_border: if show_debug_border { 1 } else { 0 },
+        posterize_levels: state.posterize_levels.map_or(0, |l| l as i32),
+        // Must match the NOISE_* constants in shaders.hlsl
+        noise_mapping: match state.noise_mapping {
+            NoiseMapping::Stretch => 0,
+            NoiseMapping::Tile => 1,
+            NoiseMapping::PixelExact => 2,
+        },
+        noise_size: [noise_size.0 as f32, noise_size.1 as f32],
+        screen_size: [screen_size.0 as f32, screen_size.1 as f32],
+        temporal_smoothing: state.temporal_smoothing,
+        // The history holds the debug output, which smoothing would feed back into the frame
+        use_history: if history_valid && state.temporal_smoothing > 0.0 && state.debug_visibility == DebugVisibility::Normal { 1 } else { 0 },
+        capture_rotation,
+        use_hue_gate: if state.hue_gate.is_some() { 1 } else { 0 },
+        hue_gate_start: state.hue_gate.map_or(0.0, |g| g.start),
+        hue_gate_end: state.hue_gate.map_or(0.0, |g| g.end),
+        // 0 keeps the two-spectrum mask path
+        noise_spectra: match state.noise_texture {
+            Some(ref noise) if noise.kind() != NoiseKind::Mask => state.spectrum_pair.noise_spectrum_count(MAX_NOISE_INDICES) as i32,
+            _ => 0,
+        },
+        noise_intensity: state.noise_intensity.clamp(0.0, 1.0),
+        exclusion_count: exclusion_count as i32,
+        use_lut: if use_lut { 1 } else { 0 },
+        exclusion_rects,
+        bake_spectrum: 0,
+        bake_slice: 0.0,
+        use_target_rect: 0,
+        _padding: 0,
+        target_rect: [0.0; 4],
+        debug_mode: state.debug_visibility.shader_mode(),
+        // Must match the NOISE_TARGET_* constants in shaders.hlsl
+        noise_target: match state.noise_target {
+            NoiseTarget::BetweenSpectra => 0,
+            NoiseTarget::CorrectionVsOriginal => 1,
+        },
+        blend_ratio: state.blend_ratio.clamp(0.0, 1.0),
+        _tail_padding: 0,
+    }
+}
+
+/// Leaves everything outside `area` transparent; an empty rectangle suspends the correction.
+#[cfg(windows)]
+fn limit_params_to(params: &mut SpectrumParams, area: CorrectionArea) {
+    (params.use_target_rect, params.target_rect) = match area {
+        CorrectionArea::FullScreen => (0, [0.0; 4]),
+        CorrectionArea::Window(rect) => (1, rect),
+        CorrectionArea::Suspended => (1, [0.0; 4]),
+    };
+}
+
+/// The inputs baked into the `ColorMath::BakedLut` tables; the tables are rebaked when they change.
+#[cfg(windows)]
+#[derive(Debug, PartialEq)]
+struct LutKey {
+    spectrum_generation: u64,
+    strength: f32,
+    hue_gate: Option<HueGate>,
+}
+
+#[cfg(windows)]
+impl LutKey {
+    fn of(state: &OverlayState, spectrum_generation: u64) -> Self {
+        Self {
+            spectrum_generation,
+            strength: state.blend_weight(),
+            hue_gate: state.hue_gate,
         }
     }
+}
 
-    unsafe fn release_frame(&mut self) -> Result<()> {
-        self.output_duplication.ReleaseFrame()?;
-        Ok(())
+#[cfg(windows)]
+const HRESULT_NAMES: [(HRESULT, &str); 9] = [
+    (DXGI_ERROR_DEVICE_HUNG, "DXGI_ERROR_DEVICE_HUNG"),
+    (DXGI_ERROR_DEVICE_REMOVED, "DXGI_ERROR_DEVICE_REMOVED"),
+    (DXGI_ERROR_DEVICE_RESET, "DXGI_ERROR_DEVICE_RESET"),
+    (DXGI_ERROR_DRIVER_INTERNAL_ERROR, "DXGI_ERROR_DRIVER_INTERNAL_ERROR"),
+    (DXGI_ERROR_INVALID_CALL, "DXGI_ERROR_INVALID_CALL"),
+    (DXGI_ERROR_ACCESS_LOST, "DXGI_ERROR_ACCESS_LOST"),
+    (DXGI_ERROR_NOT_CURRENTLY_AVAILABLE, "DXGI_ERROR_NOT_CURRENTLY_AVAILABLE"),
+    (E_ACCESSDENIED, "E_ACCESSDENIED"),
+    (E_OUTOFMEMORY, "E_OUTOFMEMORY"),
+];
+
+#[cfg(windows)]
+fn hresult_name(hr: HRESULT) -> String {
+    match HRESULT_NAMES.iter().find(|(code, _)| *code == hr) {
+        Some((_, name)) => format!("{} (0x{:08X})", name, hr.0 as u32),
+        None => format!("0x{:08X}", hr.0 as u32),
     }
 }
 
+/// Adds the display and its driver to a duplication error on a virtual display, whose failures
+/// are usually down to the driver.
 #[cfg(windows)]
-struct DCompOverlay {
-    _hwnd: HWND,
-    d3d_device: ID3D11Device,
-    d3d_context: ID3D11DeviceContext,
-    swap_chain: IDXGISwapChain1,
-    _dcomp_device: IDCompositionDevice,
-    _dcomp_target: IDCompositionTarget,
-    _dcomp_visual: IDCompositionVisual,
+unsafe fn name_virtual_display(error: anyhow::Error, monitor_index: usize) -> anyhow::Error {
+    match get_monitor_info(monitor_index) {
+        Ok(info) if chromabridge::monitor::is_virtual_display(&info.device_string) => {
+            error.context(format!("Desktop duplication failed on virtual display {} ({})", info.device, info.device_string))
+        }
+        _ => error,
+    }
+}
 
-    vertex_shader: ID3D11VertexShader,
-    pixel_shader: ID3D11PixelShader,
-    input_layout: ID3D11InputLayout,
-    vertex_buffer: ID3D11Buffer,
-    sampler_state: ID3D11SamplerState,
-    spectrum_sampler: ID3D11SamplerState,
-    blend_state: ID3D11BlendState,
+/// The error code if starting desktop duplication failed because another app (or a secure
+/// desktop) holds the output, the cases Windows.Graphics.Capture can still capture.
+#[cfg(windows)]
+fn duplication_conflict(error: &anyhow::Error) -> Option<HRESULT> {
+    let code = error.downcast_ref::<windows::core::Error>()?.code();
+    (code == DXGI_ERROR_NOT_CURRENTLY_AVAILABLE || code == E_ACCESSDENIED).then_some(code)
+}
 
-    spectrum1_srv: ID3D11ShaderResourceView,
-    spectrum2_srv: Option<ID3D11ShaderResourceView>,
-    noise_srv: Option<ID3D11ShaderResourceView>,
-    constant_buffer: ID3D11Buffer,
+/// How long the render loop can wait for desktop content and still present before the refresh
+/// following the previous present.
+#[cfg(windows)]
+fn capture_timeout_ms(refresh_rate: f32, since_last_present: std::time::Duration) -> u32 {
+    let refresh_period = std::time::Duration::from_secs_f32(1.0 / refresh_rate.max(1.0));
+    refresh_period.saturating_sub(since_last_present).saturating_sub(CAPTURE_WAIT_MARGIN).as_millis() as u32
+}
 
-    capture_texture: Option<ID3D11Texture2D>,
-    capture_srv: Option<ID3D11ShaderResourceView>,
+/// How many refreshes each frame is meant to stay on screen, 2 when a 60Hz monitor is throttled to 30 FPS.
+#[cfg(windows)]
+fn refreshes_per_present(throttle: OverlayThrottle, refresh_rate: f32) -> u32 {
+    match throttle {
+        OverlayThrottle::Reduced(fps) if fps < refresh_rate => (refresh_rate / fps.max(1.0)).round() as u32,
+        _ => 1,
+    }
+}
 
-    desktop_duplication: Option<DesktopDuplicator>,
+/// Milliseconds since a QueryPerformanceCounter timestamp, such as a duplicated frame's present time.
+#[cfg(windows)]
+unsafe fn qpc_elapsed_ms(since: i64) -> Option<f32> {
+    let (mut now, mut frequency) = (0i64, 0i64);
+    QueryPerformanceCounter(&mut now).ok()?;
+    QueryPerformanceFrequency(&mut frequency).ok()?;
+    (frequency > 0 && now >= since).then(|| (now - since) as f32 * 1000.0 / frequency as f32)
+}
+
+#[cfg(windows)]
+/// Largest width and height of a 2D texture a device of feature `level` can create.
+fn max_texture_size(level: D3D_FEATURE_LEVEL) -> u32 {
+    if level.0 >= D3D_FEATURE_LEVEL_11_0.0 {
+        D3D11_REQ_TEXTURE2D_U_OR_V_DIMENSION
+    } else if level.0 >= D3D_FEATURE_LEVEL_10_0.0 {
+        8192
+    } else if level.0 >= D3D_FEATURE_LEVEL_9_3.0 {
+        4096
+    } else {
+        2048
+    }
+}
+
+pub fn feature_level_name(level: D3D_FEATURE_LEVEL) -> String {
+    // Encoded as 0xMm00, e.g. 0xb100 for 11_1
+    format!("{}_{}", (level.0 >> 12) & 0xf, (level.0 >> 8) & 0xf)
+}
+
+#[cfg(windows)]
+fn format_name(format: DXGI_FORMAT) -> String {
+    const NAMES: [(DXGI_FORMAT, &str); 6] = [
+        (DXGI_FORMAT_B8G8R8A8_UNORM, "B8G8R8A8_UNORM"),
+        (DXGI_FORMAT_R8G8B8A8_UNORM, "R8G8B8A8_UNORM"),
+        (DXGI_FORMAT_R10G10B10A2_UNORM, "R10G10B10A2_UNORM"),
+        (DXGI_FORMAT_R16G16B16A16_FLOAT, "R16G16B16A16_FLOAT"),
+        (DXGI_FORMAT_R32G32B32_FLOAT, "R32G32B32_FLOAT"),
+        (DXGI_FORMAT_R32G32B32A32_FLOAT, "R32G32B32A32_FLOAT"),
+    ];
+    match NAMES.iter().find(|(f, _)| *f == format) {
+        Some((_, name)) => name.to_string(),
+        None => format!("DXGI_FORMAT({})", format.0),
+    }
+}
+
+/// Swap chain format for captured frames of `captured`: 10-bit frames are presented at 10 bits,
+/// so the correction doesn't band them.
+///
+/// R10G10B10A2 leaves two bits for alpha. The correction only writes alpha 0 and 1, which stay
+/// exact; the half alpha debug view is rounded to a third or two.
+#[cfg(windows)]
+fn output_format_for(captured: DXGI_FORMAT) -> DXGI_FORMAT {
+    if captured == DXGI_FORMAT_R10G10B10A2_UNORM {
+        captured
+    } else {
+        DEFAULT_OUTPUT_FORMAT
+    }
+}
+
+/// How pixels of a capture texture in `format` are laid out for the CPU, `None` for formats the
+/// software cursor and the frame dump can't handle.
+#[cfg(windows)]
+fn pixel_layout(format: DXGI_FORMAT) -> Option<PixelLayout> {
+    match format {
+        DXGI_FORMAT_B8G8R8A8_UNORM => Some(PixelLayout::Bgra8),
+        DXGI_FORMAT_R10G10B10A2_UNORM => Some(PixelLayout::Rgb10A2),
+        _ => None,
+    }
+}
+
+/// Spectrum lookup texture formats, in order of preference.
+///
+/// Three-channel float textures can't be sampled on some GPUs (notably older Intel iGPUs),
+/// so the lookup table is padded to RGBA or converted to half floats there.
+#[cfg(windows)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SpectrumFormat {
+    Rgb32Float,
+    Rgba32Float,
+    Rgba16Float,
+}
+
+#[cfg(windows)]
+impl SpectrumFormat {
+    const FALLBACK_CHAIN: [SpectrumFormat; 3] = [SpectrumFormat::Rgb32Float, SpectrumFormat::Rgba32Float, SpectrumFormat::Rgba16Float];
+
+    fn dxgi_format(&self) -> DXGI_FORMAT {
+        match self {
+            SpectrumFormat::Rgb32Float => DXGI_FORMAT_R32G32B32_FLOAT,
+            SpectrumFormat::Rgba32Float => DXGI_FORMAT_R32G32B32A32_FLOAT,
+            SpectrumFormat::Rgba16Float => DXGI_FORMAT_R16G16B16A16_FLOAT,
+        }
+    }
+
+    fn bytes_per_texel(&self) -> u32 {
+        match self {
+            SpectrumFormat::Rgb32Float => 12,
+            SpectrumFormat::Rgba32Float => 16,
+            SpectrumFormat::Rgba16Float => 8,
+        }
+    }
+}
+
+/// Converts to IEEE half precision, rounding to nearest. Lookup values are within 0..1,
+/// so values too small for a normal half are flushed to zero.
+#[cfg(windows)]
+fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32 - 127 + 15;
+    let mantissa = bits & 0x7f_ffff;
+
+    if exponent <= 0 {
+        return sign;
+    }
+    if exponent >= 0x1f {
+        return sign | 0x7c00;
+    }
+
+    // A carry out of the mantissa correctly bumps the exponent
+    (sign | ((exponent as u16) << 10) | (mantissa >> 13) as u16) + ((mantissa >> 12) & 1) as u16
+}
+
+/// An overlay whose presents went nowhere, see `OCCLUSION_PROBE_INTERVAL`.
+#[cfg(windows)]
+struct Occlusion {
+    since: std::time::Instant,
+    last_probe: std::time::Instant,
+    /// Whether the swap chain was recreated during this occlusion already.
+    recreated: bool,
+}
+
+/// The image currently uploaded for a generated overlay source.
+#[cfg(windows)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum GeneratedImage {
+    Pattern(TestPattern),
+    Solid([u8; 3]),
+}
+
+#[cfg(windows)]
+struct DesktopDuplicator {
+    output_duplication: IDXGIOutputDuplication,
+    _d3d_device: ID3D11Device,
+    _d3d_context: ID3D11DeviceContext,
+    /// Duplicated frames keep the output's native orientation; portrait outputs report a rotation.
+    rotation: DXGI_MODE_ROTATION,
+    /// The desktop format reported by the duplication. Acquired frames are checked on their own,
+    /// see `copy_to_capture_texture`.
+    format: DXGI_FORMAT,
+    /// Top-left of the pointer shape on this output, `None` while hidden or on another output.
+    pointer_position: Option<(i32, i32)>,
+    pointer_shape: Option<CursorShape>,
+    /// QPC time the desktop presented the last acquired frame, `None` when only the pointer changed.
+    last_present_time: Option<i64>,
+}
+
+#[cfg(windows)]
+impl DesktopDuplicator {
+    unsafe fn new(d3d_device: ID3D11Device, d3d_context: ID3D11DeviceContext, monitor_index: usize) -> Result<Self> {
+        let dxgi_device: IDXGIDevice = d3d_device.cast()?;
+        let dxgi_adapter = dxgi_device.GetAdapter()?;
+
+        let output: IDXGIOutput = dxgi_adapter.EnumOutputs(monitor_index as u32)?;
+        let output_duplication = Self::duplicate_output(&output, &d3d_device)?;
+        let desc = output_duplication.GetDesc();
+
+        let duplicator = Self {
+            output_duplication,
+            _d3d_device: d3d_device,
+            _d3d_context: d3d_context,
+            rotation: desc.Rotation,
+            format: desc.ModeDesc.Format,
+            pointer_position: None,
+            pointer_shape: None,
+            last_present_time: None,
+        };
+        log_info!("Desktop duplication initialized for monitor {} ({}x{} native, {}, rotated {}°)",
+                 monitor_index, desc.ModeDesc.Width, desc.ModeDesc.Height, format_name(duplicator.format), duplicator.quarter_turns() * 90);
+
+        Ok(duplicator)
+    }
+
+    /// Duplicates `output` in one of `DUPLICATION_FORMATS`. `DuplicateOutput1` needs Windows 10
+    /// 1703 and a per-monitor DPI aware process; without it frames only come in 8 bits.
+    unsafe fn duplicate_output(output: &IDXGIOutput, d3d_device: &ID3D11Device) -> Result<IDXGIOutputDuplication> {
+        if let Ok(output5) = output.cast::<IDXGIOutput5>() {
+            match output5.DuplicateOutput1(d3d_device, 0, &DUPLICATION_FORMATS) {
+                Ok(duplication) => return Ok(duplication),
+                Err(e) => log_debug!("DuplicateOutput1 failed with {}, duplicating in 8 bits", hresult_name(e.code())),
+            }
+        }
+        let output1: IDXGIOutput1 = output.cast()?;
+        Ok(output1.DuplicateOutput(d3d_device)?)
+    }
+
+    /// Clockwise quarter turns from the duplicated frame to the desktop.
+    fn quarter_turns(&self) -> i32 {
+        match self.rotation {
+            DXGI_MODE_ROTATION_ROTATE90 => 1,
+            DXGI_MODE_ROTATION_ROTATE180 => 2,
+            DXGI_MODE_ROTATION_ROTATE270 => 3,
+            _ => 0,
+        }
+    }
+
+    unsafe fn acquire_next_frame(&mut self, timeout_ms: u32) -> Result<Option<ID3D11Texture2D>> {
+        let mut frame_info: DXGI_OUTDUPL_FRAME_INFO = std::mem::zeroed();
+        let mut desktop_resource: Option<IDXGIResource> = None;
+
+        match self.output_duplication.AcquireNextFrame(timeout_ms, &mut frame_info, &mut desktop_resource) {
+            Ok(_) => {
+                self.update_pointer(&frame_info);
+                self.last_present_time = (frame_info.LastPresentTime != 0).then_some(frame_info.LastPresentTime);
+                if let Some(resource) = desktop_resource {
+                    let texture: ID3D11Texture2D = resource.cast()?;
+                    Ok(Some(texture))
+                } else {
+                    Ok(None)
+                }
+            }
+            Err(e) => {
+                if e.code() == DXGI_ERROR_WAIT_TIMEOUT {
+                    return Ok(None);
+                }
+                Err(anyhow::Error::from(e).context("Failed to acquire frame"))
+            }
+        }
+    }
+
+    unsafe fn release_frame(&mut self) -> Result<()> {
+        self.output_duplication.ReleaseFrame()?;
+        Ok(())
+    }
+
+    /// Follows the pointer reported with each frame; the shape is only sent when it changes,
+    /// so it is tracked even while the software cursor is off.
+    unsafe fn update_pointer(&mut self, frame_info: &DXGI_OUTDUPL_FRAME_INFO) {
+        if frame_info.LastMouseUpdateTime == 0 {
+            return;
+        }
+
+        let pointer = frame_info.PointerPosition;
+        self.pointer_position = pointer.Visible.as_bool().then_some((pointer.Position.x, pointer.Position.y));
+
+        if frame_info.PointerShapeBufferSize == 0 {
+            return;
+        }
+
+        let mut buffer = vec![0u8; frame_info.PointerShapeBufferSize as usize];
+        let mut required_size = 0u32;
+        let mut shape_info = DXGI_OUTDUPL_POINTER_SHAPE_INFO::default();
+        if let Err(e) = self.output_duplication.GetFramePointerShape(
+            buffer.len() as u32,
+            buffer.as_mut_ptr() as *mut _,
+            &mut required_size,
+            &mut shape_info,
+        ) {
+            log_debug!("Failed to read pointer shape: {:?}", e);
+            return;
+        }
+
+        let kind = match shape_info.Type {
+            t if t == DXGI_OUTDUPL_POINTER_SHAPE_TYPE_MONOCHROME.0 as u32 => CursorShapeKind::Monochrome,
+            t if t == DXGI_OUTDUPL_POINTER_SHAPE_TYPE_COLOR.0 as u32 => CursorShapeKind::Color,
+            t if t == DXGI_OUTDUPL_POINTER_SHAPE_TYPE_MASKED_COLOR.0 as u32 => CursorShapeKind::MaskedColor,
+            other => {
+                log_debug!("Unknown pointer shape type {}", other);
+                return;
+            }
+        };
+
+        log_debug!("Pointer shape changed: {:?} {}x{}", kind, shape_info.Width, shape_info.Height);
+        self.pointer_shape = Some(CursorShape::new(kind, shape_info.Width, shape_info.Height, shape_info.Pitch, buffer));
+    }
+}
+
+#[cfg(windows)]
+struct DCompOverlay {
+    hwnd: HWND,
+    d3d_device: ID3D11Device,
+    d3d_context: ID3D11DeviceContext,
+    swap_chain: IDXGISwapChain1,
+    /// Format of the swap chain and the history texture, following the captured frames.
+    output_format: DXGI_FORMAT,
+    /// Set when `output_format` changed, until the diagnostics show it.
+    output_format_changed: bool,
+    dcomp_device: IDCompositionDevice,
+    _dcomp_target: IDCompositionTarget,
+    dcomp_visual: IDCompositionVisual,
+
+    pipeline: RenderPipeline,
+
+    /// Lookup textures in the order the noise texture indexes them, primary first.
+    spectrum_srvs: Vec<ID3D11ShaderResourceView>,
+    spectrum_generation: u64,
+    spectrum_format: SpectrumFormat,
+    noise_srv: Option<ID3D11ShaderResourceView>,
+    constant_buffer: ID3D11Buffer,
+    /// Baked corrections of `spectrum_srvs`, in the same order, while `ColorMath::BakedLut` is selected.
+    lut_srvs: Vec<ID3D11ShaderResourceView>,
+    /// What `lut_srvs` were baked from; `None` while there are none.
+    lut_key: Option<LutKey>,
+
+    capture_texture: Option<ID3D11Texture2D>,
+    capture_srv: Option<ID3D11ShaderResourceView>,
+    /// CPU-writable copy of the area under the software cursor, with its width and height.
+    cursor_staging: Option<(ID3D11Texture2D, u32, u32)>,
+    /// CPU-readable copy of the back buffer for the output preview, kept while it has the size
+    /// and format of the back buffer.
+    preview_staging: Option<ID3D11Texture2D>,
+    /// Set while a copy into `preview_staging` waits to be read on a later frame.
+    preview_pending: bool,
+
+    /// Previous output frame for temporal smoothing; only sampled while `history_valid`.
+    history_texture: ID3D11Texture2D,
+    history_srv: ID3D11ShaderResourceView,
+    history_valid: bool,
+
+    source: OverlaySource,
+    desktop_duplication: Option<DesktopDuplicator>,
+    /// Fallback capture while another app holds the desktop duplication.
+    graphics_capture: Option<GraphicsCapturer>,
+    /// Set when the last capture attempt failed because another app holds the desktop duplication.
+    capture_conflict: bool,
+    last_capture_fallback: Option<String>,
+    last_duplication_attempt: std::time::Instant,
+    /// QPC time the desktop presented the content of `capture_texture`, until the overlay presents it.
+    captured_present_time: Option<i64>,
+    /// What is currently in `capture_texture` for the generated sources.
+    generated_image: Option<GeneratedImage>,
+    monitor_index: usize,
+
+    overlay_state: Arc<RwLock<OverlayState>>,
+
+    width: u32,
+    height: u32,
+    /// Owned: closed on drop and when the swap chain is recreated.
+    frame_latency_waitable: HANDLE,
+    cap_to_monitor_refresh: bool,
+    monitor_refresh_rate: f32,
+    interactive_debug: bool,
+    topmost: crate::topmost::TopmostKeeper,
+    shell_exclusion: ShellExclusion,
+    /// Whether the window is excluded from capture as of the last `set_capture_exclusion`.
+    hidden_from_capture: bool,
+    target_window: TargetWindowTracker,
+    present_stats: PresentStats,
+}
+
+/// Releases what would otherwise outlive the overlay until its thread exits, or for good: the
+/// captures before the device they were created on, the waitable handle and the window.
+#[cfg(windows)]
+impl Drop for DCompOverlay {
+    fn drop(&mut self) {
+        self.desktop_duplication = None;
+        self.graphics_capture = None;
+        unsafe {
+            let _ = CloseHandle(self.frame_latency_waitable);
+            // The window class stays registered for the next start
+            let _ = DestroyWindow(self.hwnd);
+        }
+    }
+}
+
+/// The overlay window until `DCompOverlay::new` succeeds; destroyed if a later step fails, as it
+/// is already shown by then.
+#[cfg(windows)]
+struct WindowGuard(HWND);
+
+#[cfg(windows)]
+impl WindowGuard {
+    fn into_inner(self) -> HWND {
+        std::mem::ManuallyDrop::new(self).0
+    }
+}
+
+#[cfg(windows)]
+impl Drop for WindowGuard {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = DestroyWindow(self.0);
+        }
+    }
+}
+
+/// A handle closed on drop unless `into_inner` hands it on.
+#[cfg(windows)]
+struct HandleGuard(HANDLE);
+
+#[cfg(windows)]
+impl HandleGuard {
+    fn into_inner(self) -> HANDLE {
+        std::mem::ManuallyDrop::new(self).0
+    }
+}
+
+#[cfg(windows)]
+impl Drop for HandleGuard {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = CloseHandle(self.0);
+        }
+    }
+}
+
+#[cfg(windows)]
+impl DCompOverlay {
+    /// Ends the `device`, `resources` and `capture` steps of `startup_timer`.
+    unsafe fn new(state: Arc<RwLock<OverlayState>>, monitor_info: MonitorInfo, monitor_index: usize, source: OverlaySource, cap_to_monitor_refresh: bool, interactive_debug: bool, startup_timer: &mut StartupTimer) -> Result<Self> {
+        let (pos, size) = (monitor_info.pos, monitor_info.size);
+        let width = size.0 as u32;
+        let height = size.1 as u32;
+
+        let hide_from_capture = state.read().hide_from_capture;
+        let foreground = GetForegroundWindow();
+        // Declared before the composition target, so on failure the target goes first
+        let window = WindowGuard(Self::create_overlay_window(pos, size, interactive_debug, hide_from_capture)?);
+        let hwnd = window.0;
+        let (d3d_device, d3d_context) = create_d3d_device(D3D_DRIVER_TYPE_HARDWARE)?;
+        startup_timer.checkpoint("device");
+        let swap_chain = Self::create_swap_chain(&d3d_device, width, height, DEFAULT_OUTPUT_FORMAT)?;
+
+        // Get waitable handle and set max frame latency for proper frame pacing
+        let swap_chain2: IDXGISwapChain2 = swap_chain.cast()?;
+        swap_chain2.SetMaximumFrameLatency(1)?;
+        let frame_latency_waitable = HandleGuard(swap_chain2.GetFrameLatencyWaitableObject());
+        log_debug!("Frame latency waitable object initialized");
+
+        let dcomp_device: IDCompositionDevice = DCompositionCreateDevice(None)?;
+        let dcomp_target = dcomp_device.CreateTargetForHwnd(hwnd, true)?;
+        let dcomp_visual = dcomp_device.CreateVisual()?;
+        dcomp_visual.SetContent(&swap_chain)?;
+        dcomp_target.SetRoot(&dcomp_visual)?;
+        dcomp_device.Commit()?;
+        // Showing the window and creating its composition target are what could activate it
+        Self::warn_if_foreground_moved(foreground, hwnd);
+
+        log_info!("DirectComposition overlay initialized ({}x{} @ {},{}, {}Hz)",
+                 width, height, pos.0, pos.1, monitor_info.refresh_rate);
+
+        let pipeline = RenderPipeline::new(&d3d_device)?;
+
+        let spectrum_format = Self::select_spectrum_format(&d3d_device)?;
+        let (spectrum_srvs, noise_srv, constant_buffer) = Self::init_spectrum_textures(&d3d_device, &state, (width, height), spectrum_format)?;
+
+        let (history_texture, history_srv) = Self::create_history_texture(&d3d_device, width, height, DEFAULT_OUTPUT_FORMAT)?;
+        startup_timer.checkpoint("resources");
+
+        let mut overlay = Self {
+            hwnd: window.into_inner(),
+            d3d_device,
+            d3d_context,
+            swap_chain,
+            output_format: DEFAULT_OUTPUT_FORMAT,
+            output_format_changed: false,
+            dcomp_device,
+            _dcomp_target: dcomp_target,
+            dcomp_visual,
+            pipeline,
+            spectrum_srvs,
+            spectrum_generation: 0,
+            spectrum_format,
+            noise_srv,
+            constant_buffer,
+            lut_srvs: Vec::new(),
+            lut_key: None,
+            capture_texture: None,
+            capture_srv: None,
+            cursor_staging: None,
+            preview_staging: None,
+            preview_pending: false,
+            history_texture,
+            history_srv,
+            history_valid: false,
+            source,
+            desktop_duplication: None,
+            graphics_capture: None,
+            capture_conflict: false,
+            last_capture_fallback: None,
+            last_duplication_attempt: std::time::Instant::now(),
+            captured_present_time: None,
+            generated_image: None,
+            monitor_index,
+            overlay_state: state,
+            width,
+            height,
+            frame_latency_waitable: frame_latency_waitable.into_inner(),
+            cap_to_monitor_refresh,
+            monitor_refresh_rate: monitor_info.refresh_rate as f32,
+            interactive_debug,
+            topmost: crate::topmost::TopmostKeeper::new(hwnd),
+            shell_exclusion: ShellExclusion::new(hwnd),
+            hidden_from_capture: hide_from_capture,
+            target_window: TargetWindowTracker::new(hwnd),
+            present_stats: PresentStats::new(),
+        };
+
+        match source {
+            OverlaySource::DesktopCapture => {
+                if let Err(e) = overlay.try_acquire_capture() {
+                    log_error!("Desktop capture unavailable: {:#}. The overlay stays transparent until capture recovers.", e);
+                }
+            }
+            _ => log_info!("Overlay source: {}", source.label()),
+        }
+        startup_timer.checkpoint("capture");
+
+        Ok(overlay)
+    }
+
+    unsafe fn create_overlay_window(pos: (i32, i32), size: (i32, i32), interactive_debug: bool, hide_from_capture: bool) -> Result<HWND> {
+        let class_name = w!("ChromaBridgeOverlay");
+        let hinstance = windows::Win32::System::LibraryLoader::GetModuleHandleW(None)?;
+
+        let wc = WNDCLASSW {
+            lpfnWndProc: Some(Self::window_proc),
+            hInstance: hinstance.into(),
+            lpszClassName: class_name,
+            style: CS_HREDRAW | CS_VREDRAW,
+            ..Default::default()
+        };
+
+        RegisterClassW(&wc);
+
+        let ex_style = Self::overlay_ex_style(interactive_debug);
+
+        let hwnd = CreateWindowExW(
+            ex_style,
+            class_name,
+            w!("ChromaBridge Overlay"),
+            WS_POPUP,
+            pos.0, pos.1, size.0, size.1,
+            None, None,
+            Some(HINSTANCE(hinstance.0)),
+            None,
+        )?;
+
+        // Read by window_proc to decide whether hit-testing falls through
+        SetWindowLongPtrW(hwnd, GWLP_USERDATA, interactive_debug as isize);
+
+        if !Self::apply_ex_style(hwnd, ex_style) {
+            log_warn!("Failed to apply overlay window styles, retrying");
+            if !Self::apply_ex_style(hwnd, ex_style) {
+                log_error!("Failed to apply overlay window styles after retry");
+            }
+        }
+
+        if hide_from_capture {
+            Self::set_capture_exclusion(hwnd, true);
+        } else {
+            log_warn!("Overlay shown in captures - its own capture sees the correction and corrects it again");
+        }
+
+        // WS_EX_NOACTIVATE alone doesn't stop SW_SHOW from activating the window, which drops
+        // keystrokes meant for the focused app
+        let _ = ShowWindow(hwnd, SW_SHOWNOACTIVATE);
+
+        // ShowWindow can race with the style change above, so re-assert after showing
+        if !Self::apply_ex_style(hwnd, ex_style) {
+            log_error!("Failed to re-assert overlay window styles after ShowWindow");
+        }
+
+        if interactive_debug {
+            log_warn!("Interactive debug overlay enabled - click-through is DISABLED");
+        }
+
+        Ok(hwnd)
+    }
+
+    /// Keeps the overlay out of its own capture input, and so out of screenshots and screen
+    /// sharing too, or lets it back in. Only this window is excluded: the settings window must
+    /// stay captured, otherwise the overlay would paint over it with whatever is behind it.
+    /// Returns false if that failed twice.
+    unsafe fn set_capture_exclusion(hwnd: HWND, hide: bool) -> bool {
+        let affinity = if hide { WDA_EXCLUDEFROMCAPTURE } else { WDA_NONE };
+        if let Err(e) = SetWindowDisplayAffinity(hwnd, affinity) {
+            log_warn!("Failed to set window display affinity to {:?}: {:?}, retrying", affinity, e);
+            if let Err(e) = SetWindowDisplayAffinity(hwnd, affinity) {
+                log_error!("Failed to set window display affinity to {:?} after retry: {:?}", affinity, e);
+                return false;
+            }
+        }
+        log_debug!("Window {} capture", if hide { "excluded from" } else { "included in" });
+        true
+    }
+
+    /// Warns when the foreground window is no longer `before`, read just before the overlay window
+    /// `hwnd` was created. Other apps can take the foreground meanwhile, so this only logs.
+    unsafe fn warn_if_foreground_moved(before: HWND, hwnd: HWND) -> bool {
+        let after = GetForegroundWindow();
+        if after == before {
+            return false;
+        }
+        if after == hwnd {
+            log_warn!("Overlay window took the foreground while starting");
+        } else {
+            log_warn!("Foreground window changed while the overlay window was created ({:?} -> {:?})", before.0, after.0);
+        }
+        true
+    }
+
+    fn overlay_ex_style(interactive_debug: bool) -> WINDOW_EX_STYLE {
+        if interactive_debug {
+            WS_EX_LAYERED | WS_EX_TOPMOST
+        } else {
+            WS_EX_LAYERED | WS_EX_TRANSPARENT | WS_EX_TOPMOST | WS_EX_NOACTIVATE
+        }
+    }
+
+    /// Sets the extended window style and verifies it stuck.
+    unsafe fn apply_ex_style(hwnd: HWND, ex_style: WINDOW_EX_STYLE) -> bool {
+        // SetWindowLongW returns the previous value, so 0 is only an error if GetLastError says so
+        SetLastError(WIN32_ERROR(0));
+        let previous = SetWindowLongW(hwnd, GWL_EXSTYLE, ex_style.0 as i32);
+        if previous == 0 && GetLastError() != WIN32_ERROR(0) {
+            return false;
+        }
+
+        let applied = GetWindowLongW(hwnd, GWL_EXSTYLE) as u32;
+        applied & ex_style.0 == ex_style.0
+    }
+
+    unsafe extern "system" fn window_proc(
+        hwnd: HWND,
+        msg: u32,
+        wparam: WPARAM,
+        lparam: LPARAM,
+    ) -> LRESULT {
+        match msg {
+            WM_NCHITTEST if GetWindowLongPtrW(hwnd, GWLP_USERDATA) == 0 => {
+                LRESULT(HTTRANSPARENT as isize)
+            }
+            // Clicks fall through already; this covers anything that still reaches the window
+            WM_MOUSEACTIVATE if GetWindowLongPtrW(hwnd, GWLP_USERDATA) == 0 => {
+                LRESULT(MA_NOACTIVATE as isize)
+            }
+            WM_WINDOWPOSCHANGED => {
+                let pos = &*(lparam.0 as *const WINDOWPOS);
+                if !pos.flags.contains(SWP_NOZORDER) {
+                    crate::topmost::note_z_order_changed();
+                }
+                DefWindowProcW(hwnd, msg, wparam, lparam)
+            }
+            WM_DISPLAYCHANGE => {
+                DISPLAY_CHANGED.store(true, Ordering::Relaxed);
+                DefWindowProcW(hwnd, msg, wparam, lparam)
+            }
+            WM_DESTROY => {
+                PostQuitMessage(0);
+                LRESULT(0)
+            }
+            _ => DefWindowProcW(hwnd, msg, wparam, lparam),
+        }
+    }
+
+    unsafe fn create_swap_chain(device: &ID3D11Device, width: u32, height: u32, format: DXGI_FORMAT) -> Result<IDXGISwapChain1> {
+        let dxgi_device = device.cast::<IDXGIDevice>()?;
+        let dxgi_adapter = dxgi_device.GetAdapter()?;
+        let dxgi_factory: IDXGIFactory2 = dxgi_adapter.GetParent()?;
+
+        let swap_chain_desc = DXGI_SWAP_CHAIN_DESC1 {
+            Width: width,
+            Height: height,
+            Format: format,
+            SampleDesc: DXGI_SAMPLE_DESC { Count: 1, Quality: 0 },
+            BufferUsage: DXGI_USAGE_RENDER_TARGET_OUTPUT,
+            BufferCount: 2,
+            SwapEffect: DXGI_SWAP_EFFECT_FLIP_SEQUENTIAL,
+            AlphaMode: DXGI_ALPHA_MODE_PREMULTIPLIED,
+            Flags: DXGI_SWAP_CHAIN_FLAG_FRAME_LATENCY_WAITABLE_OBJECT.0 as u32,
+            ..Default::default()
+        };
+
+        let swap_chain = dxgi_factory.CreateSwapChainForComposition(device, &swap_chain_desc, None)?;
+
+        Ok(swap_chain)
+    }
+
+    fn run_message_loop(
+        &mut self,
+        lifecycle: &OverlayLifecycle,
+        frame_stats: &Arc<Mutex<Option<FrameStats>>>,
+        capture_healthy: &AtomicBool,
+        display_power: &DisplayPower,
+        diagnostics: &Mutex<OverlayDiagnostics>,
+        app_state: &StateManager,
+        frame_dump: &Arc<FrameDump>,
+        output_preview: &OutputPreview,
+        session: &mut SessionRecorder,
+        max_device_recoveries: u32,
+        mut startup_timer: Option<StartupTimer>,
+        restart_pending: &AtomicBool,
+    ) -> Result<()> {
+        #[cfg(windows)]
+        unsafe {
+            self.publish_diagnostics(diagnostics);
+            diagnostics.lock().occluded = false;
+            DISPLAY_CHANGED.store(false, Ordering::Relaxed);
+
+            let mut msg = MSG::default();
+            let mut last_error_log = std::time::Instant::now();
+            let mut error_count = 0u32;
+            let mut consecutive_errors = 0u32;
+            self.report_capture(capture_healthy, diagnostics);
+
+            // (render_ms, total_ms, latency_ms) per frame
+            let mut frame_times: Vec<(f32, f32, Option<f32>)> = Vec::with_capacity(60);
+            // The mode `frame_times` were rendered with; the averages start over when it changes
+            let mut frame_color_math = self.color_math();
+            let mut last_stats_update = std::time::Instant::now();
+
+            let mut last_frame_time = std::time::Instant::now();
+            let mut paused = false;
+            let mut suspended = false;
+            let mut occlusion: Option<Occlusion> = None;
+
+            loop {
+                if !lifecycle.keep_running() {
+                    log_info!("Overlay stop requested");
+                    break;
+                }
+
+                while PeekMessageW(&mut msg, None, 0, 0, PM_REMOVE).as_bool() {
+                    if msg.message == WM_QUIT {
+                        if error_count > 0 {
+                            log_warn!("Exiting with {} render errors encountered", error_count);
+                        }
+                        return Ok(());
+                    }
+                    let _ = TranslateMessage(&msg);
+                    DispatchMessageW(&msg);
+                }
+
+                if display_power.is_off() {
+                    if !suspended {
+                        log_info!("Display off - overlay rendering suspended");
+                        *frame_stats.lock() = Some(FrameStats::default());
+                        frame_times.clear();
+                        suspended = true;
+                    }
+                    // Wake periodically to pump messages and notice stop requests
+                    display_power.wait_while_off(std::time::Duration::from_millis(250));
+                    continue;
+                }
+
+                if DISPLAY_CHANGED.swap(false, Ordering::Relaxed) {
+                    if let Err(e) = self.handle_display_change() {
+                        log_error!("Failed to follow display change: {}", e);
+                    }
+                    self.report_capture(capture_healthy, diagnostics);
+                }
+
+                self.topmost.maintain(self.overlay_state.read().topmost_mode);
+                let shell_changed = self.shell_exclusion.update(self.overlay_state.read().exclude_shell);
+                let hide_from_capture = self.overlay_state.read().hide_from_capture;
+                if hide_from_capture != self.hidden_from_capture {
+                    // Applied to the existing window; the capture picks it up from its next frame
+                    if Self::set_capture_exclusion(self.hwnd, hide_from_capture) {
+                        log_info!("Overlay {} screenshots and screen sharing", if hide_from_capture { "hidden from" } else { "shown in" });
+                    }
+                    self.hidden_from_capture = hide_from_capture;
+                    self.history_valid = false;
+                }
+                let area_changed = self.target_window.update(self.overlay_state.read().target_window.as_ref());
+                if shell_changed || area_changed {
+                    // Uncovered pixels would otherwise blend with the transparent history
+                    self.history_valid = false;
+                }
+
+                if suspended {
+                    suspended = false;
+                    log_info!("Display on - overlay rendering resumed");
+                    // Duplication is usually invalidated by the display state change
+                    self.reacquire_capture("resume");
+                    self.report_capture(capture_healthy, diagnostics);
+                    consecutive_errors = 0;
+                    self.present_stats.reset();
+                    last_frame_time = std::time::Instant::now();
+                }
+
+                let throttle = self.overlay_state.read().throttle;
+                if throttle == OverlayThrottle::Paused {
+                    if !paused {
+                        // Leave a transparent frame behind rather than a stale correction
+                        if let Err(e) = self.present_clear() {
+                            log_warn!("Failed to clear overlay before pausing: {}", e);
+                        }
+                        *frame_stats.lock() = None;
+                        frame_times.clear();
+                        paused = true;
+                    }
+                    std::thread::sleep(std::time::Duration::from_millis(100));
+                    continue;
+                }
+                if paused {
+                    paused = false;
+                    self.history_valid = false;
+                    self.present_stats.reset();
+                    last_frame_time = std::time::Instant::now();
+                }
+
+                if let Some(ref mut occluded) = occlusion {
+                    let wait = OCCLUSION_PROBE_INTERVAL.saturating_sub(occluded.last_probe.elapsed());
+                    if !wait.is_zero() {
+                        // In short steps to keep pumping messages and noticing stop requests
+                        std::thread::sleep(wait.min(std::time::Duration::from_millis(100)));
+                        continue;
+                    }
+                    occluded.last_probe = std::time::Instant::now();
+                    // Tests whether a present would be shown, without presenting anything
+                    let probe = self.swap_chain.Present(0, DXGI_PRESENT_TEST);
+                    if probe == DXGI_STATUS_OCCLUDED {
+                        if !occluded.recreated && occluded.since.elapsed() >= OCCLUSION_RECREATE_AFTER {
+                            occluded.recreated = true;
+                            log_warn!("Overlay still occluded after {}s, recreating the swap chain", OCCLUSION_RECREATE_AFTER.as_secs());
+                            if let Err(e) = self.recreate_swap_chain() {
+                                log_error!("Failed to recreate the swap chain: {}", e);
+                            }
+                        }
+                        continue;
+                    }
+                    // A failed test, e.g. a removed device, shows up on the next regular present
+                    if let Err(e) = probe.ok() {
+                        log_debug!("Occlusion test failed: {}", e);
+                    }
+                    log_info!("Overlay no longer occluded after {:.1}s, rendering resumed", occluded.since.elapsed().as_secs_f32());
+                    occlusion = None;
+                    diagnostics.lock().occluded = false;
+                    self.history_valid = false;
+                    self.present_stats.reset();
+                    last_frame_time = std::time::Instant::now();
+                }
+
+                if !self.capture_available() && self.last_duplication_attempt.elapsed() >= DUPLICATION_RETRY_INTERVAL {
+                    match self.try_acquire_capture() {
+                        Ok(()) => log_info!("Desktop capture acquired"),
+                        Err(e) => log_debug!("Desktop capture still unavailable: {:#}", e),
+                    }
+                    self.report_capture(capture_healthy, diagnostics);
+                }
+
+                WaitForSingleObjectEx(self.frame_latency_waitable, FRAME_WAIT_TIMEOUT_MS, false);
+
+                // Once the swap chain is ready, wait for new desktop content up to just before the next
+                // refresh, so each present carries the newest frame; on timeout the last one is shown again
+                let capture_timeout_ms = capture_timeout_ms(self.monitor_refresh_rate, last_frame_time.elapsed());
+                let capture_result = self.capture_frame(capture_timeout_ms);
+                if std::mem::take(&mut self.output_format_changed) {
+                    self.report_capture(capture_healthy, diagnostics);
+                }
+
+                let frame_start = std::time::Instant::now();
+                let frame_result = capture_result.and_then(|()| self.prepare_frame());
+                let render_time_ms = frame_start.elapsed().as_secs_f32() * 1000.0;
+                // Before presenting, which leaves the back buffer undefined
+                if frame_result.is_ok() && frame_dump.take_armed() {
+                    self.dump_frame(frame_dump, app_state);
+                }
+                if self.preview_pending {
+                    self.finish_preview(output_preview);
+                } else if frame_result.is_ok() && output_preview.is_due() {
+                    if let Err(e) = self.start_preview() {
+                        output_preview.publish(Err(e));
+                    }
+                }
+                let present_result = self.present_frame();
+                let latency_ms = match present_result {
+                    Ok(true) => {
+                        self.present_stats.record_present(&self.swap_chain, refreshes_per_present(throttle, self.monitor_refresh_rate));
+                        self.captured_present_time.take().and_then(|time| qpc_elapsed_ms(time))
+                    }
+                    Ok(false) | Err(_) => None,
+                };
+                if present_result.is_ok() {
+                    if let Some(mut timer) = startup_timer.take() {
+                        timer.checkpoint("first_frame");
+                        log_info!("{}", timer.summary());
+                        app_state.record_timing(&timer);
+                        restart_pending.store(false, Ordering::Release);
+                    }
+                }
+
+                if let Ok(false) = present_result {
+                    // Nothing composites the frames until it clears, so stop rendering them
+                    log_info!("Overlay occluded, e.g. by a secure desktop; checking every {}ms instead of rendering", OCCLUSION_PROBE_INTERVAL.as_millis());
+                    let now = std::time::Instant::now();
+                    occlusion = Some(Occlusion { since: now, last_probe: now, recreated: false });
+                    diagnostics.lock().occluded = true;
+                    // The GUI shows the occlusion instead of frame rates nobody sees
+                    *frame_stats.lock() = None;
+                    frame_times.clear();
+                    continue;
+                }
+
+                if (frame_result.is_err() || present_result.is_err())
+                    && self.recover_if_device_removed(lifecycle, diagnostics, max_device_recoveries)?
+                {
+                    session.record_recovery();
+                    consecutive_errors = 0;
+                    self.report_capture(capture_healthy, diagnostics);
+                    frame_times.clear();
+                    last_frame_time = std::time::Instant::now();
+                    continue;
+                }
+
+                if let Err(e) = frame_result {
+                    error_count += 1;
+                    session.record_error();
+                    consecutive_errors += 1;
+                    // A single dropped frame is normal (e.g. UAC prompt); only flag sustained failure
+                    if consecutive_errors == CAPTURE_UNHEALTHY_AFTER {
+                        log_warn!("Screen capture failing for {} consecutive frames", consecutive_errors);
+                        capture_healthy.store(false, Ordering::Release);
+                    }
+
+                    if last_error_log.elapsed().as_secs() >= 1 {
+                        log_error!("Render error (count: {}): {}", error_count, e);
+                        last_error_log = std::time::Instant::now();
+                    }
+                } else if consecutive_errors > 0 {
+                    if consecutive_errors >= CAPTURE_UNHEALTHY_AFTER {
+                        log_info!("Screen capture recovered");
+                        self.history_valid = false;
+                    }
+                    consecutive_errors = 0;
+                    self.report_capture(capture_healthy, diagnostics);
+                }
+
+                // Apply FPS cap if enabled - use time since last frame to account for all overhead
+                let target_fps = match throttle {
+                    OverlayThrottle::Reduced(fps) => Some(fps.min(self.monitor_refresh_rate)),
+                    _ if self.cap_to_monitor_refresh => Some(self.monitor_refresh_rate),
+                    _ => None,
+                };
+                if let Some(target_fps) = target_fps {
+                    let target_frame_duration = std::time::Duration::from_secs_f32(1.0 / target_fps.max(1.0));
+                    let elapsed_since_last = last_frame_time.elapsed();
+
+                    // spin_sleep sleeps natively for most of the wait and only spins the last
+                    // stretch, so even a raised or MMCSS render thread leaves the CPU to others
+                    if elapsed_since_last < target_frame_duration {
+                        spin_sleep::sleep(target_frame_duration - elapsed_since_last);
+                    }
+                }
+                let now = std::time::Instant::now();
+                let total_frame_time_ms = now.duration_since(last_frame_time).as_secs_f32() * 1000.0;
+                last_frame_time = now;
+                if self.color_math() != frame_color_math {
+                    frame_color_math = self.color_math();
+                    frame_times.clear();
+                }
+                frame_times.push((render_time_ms, total_frame_time_ms, latency_ms));
+                session.record_frame(total_frame_time_ms);
+                if session.elapsed() >= SESSION_SLICE {
+                    if let Some(summary) = session.take_summary(app_state.read(|s| s.spectrum_name.clone())) {
+                        app_state.record_session(summary);
+                    }
+                }
+
+                if last_stats_update.elapsed().as_millis() >= 100 && !frame_times.is_empty() {
+                    let (sum_render, sum_total): (f32, f32) = frame_times.iter()
+                        .fold((0.0, 0.0), |(r, t), &(render, total, _)| (r + render, t + total));
+                    let avg_render_time = sum_render / frame_times.len() as f32;
+                    let avg_total_time = sum_total / frame_times.len() as f32;
+
+                    // Only frames that showed new desktop content have a latency
+                    let latencies: Vec<f32> = frame_times.iter().filter_map(|&(_, _, latency)| latency).collect();
+                    let avg_latency = (!latencies.is_empty()).then(|| latencies.iter().sum::<f32>() / latencies.len() as f32);
+
+                    // FPS from total frame time (including VSync)
+                    let fps = if avg_total_time > 0.0 {
+                        1000.0 / avg_total_time
+                    } else {
+                        0.0
+                    };
+                    *frame_stats.lock() = Some(FrameStats {
+                        fps,
+                        frame_time_ms: avg_render_time,
+                        latency_ms: avg_latency,
+                        color_math: frame_color_math,
+                        dropped_presents_per_second: self.present_stats.dropped_per_second(),
+                    });
+
+                    // Keep only last 60 frames for rolling average
+                    if frame_times.len() > 60 {
+                        frame_times.drain(0..frame_times.len() - 60);
+                    }
+
+                    last_stats_update = std::time::Instant::now();
+                }
+            }
+
+            Ok(())
+        }
+
+        #[cfg(not(windows))]
+        Ok(())
+    }
+
+    /// False while desktop capture is the source but neither capture backend is available.
+    #[cfg(windows)]
+    fn capture_available(&self) -> bool {
+        self.source != OverlaySource::DesktopCapture || self.desktop_duplication.is_some() || self.graphics_capture.is_some()
+    }
+
+    #[cfg(windows)]
+    fn capture_backend(&self) -> Option<CaptureBackend> {
+        if self.source != OverlaySource::DesktopCapture {
+            None
+        } else if self.desktop_duplication.is_some() {
+            Some(CaptureBackend::DesktopDuplication)
+        } else if self.graphics_capture.is_some() {
+            Some(CaptureBackend::GraphicsCapture)
+        } else {
+            None
+        }
+    }
+
+    /// Publishes whether capture works, and through which backend and in which format, to the
+    /// tray and the diagnostics panel.
+    #[cfg(windows)]
+    fn report_capture(&self, capture_healthy: &AtomicBool, diagnostics: &Mutex<OverlayDiagnostics>) {
+        capture_healthy.store(self.capture_available(), Ordering::Release);
+        let mut diagnostics = diagnostics.lock();
+        diagnostics.capture_backend = self.capture_backend();
+        diagnostics.desktop_format = match (diagnostics.capture_backend, &self.desktop_duplication) {
+            (Some(CaptureBackend::DesktopDuplication), Some(duplicator)) => Some(format_name(duplicator.format)),
+            _ => None,
+        };
+        if let Some(ref mut adapter) = diagnostics.adapter {
+            adapter.swap_chain_format = format_name(self.output_format);
+        }
+        diagnostics.capture_conflict = self.capture_conflict && !self.capture_available();
+        diagnostics.capture_rotation = self.capture_rotation() as u32 * 90;
+        if self.last_capture_fallback.is_some() {
+            diagnostics.last_capture_fallback = self.last_capture_fallback.clone();
+        }
+    }
+
+    /// Clockwise quarter turns from the capture texture to the screen; generated images are never rotated.
+    #[cfg(windows)]
+    fn capture_rotation(&self) -> i32 {
+        match self.desktop_duplication {
+            Some(ref duplicator) if self.source == OverlaySource::DesktopCapture => duplicator.quarter_turns(),
+            _ => 0,
+        }
+    }
+
+    /// Follows a resolution, orientation or layout change of the monitor: moves the window to the
+    /// new bounds, resizes the swap chain and recreates everything sized to the old mode.
+    #[cfg(windows)]
+    unsafe fn handle_display_change(&mut self) -> Result<()> {
+        let monitor_info = get_monitor_info(self.monitor_index)?;
+        let (pos, size) = (monitor_info.pos, monitor_info.size);
+        if let Err(unusable) = chromabridge::monitor::check_size(size.0, size.1) {
+            anyhow::bail!("Monitor {} {}", self.monitor_index + 1, unusable);
+        }
+        let (width, height) = (size.0 as u32, size.1 as u32);
+
+        log_info!("Display changed: {}x{} -> {}x{} @ {},{} ({}Hz)",
+                 self.width, self.height, width, height, pos.0, pos.1, monitor_info.refresh_rate);
+
+        SetWindowPos(self.hwnd, None, pos.0, pos.1, size.0, size.1, SWP_NOZORDER | SWP_NOACTIVATE)?;
+
+        if (width, height) != (self.width, self.height) {
+            // ResizeBuffers fails while anything still references the back buffers
+            self.d3d_context.OMSetRenderTargets(None, None);
+            self.d3d_context.ClearState();
+            self.d3d_context.Flush();
+            self.swap_chain.ResizeBuffers(
+                0,
+                width,
+                height,
+                DXGI_FORMAT_UNKNOWN,
+                DXGI_SWAP_CHAIN_FLAG_FRAME_LATENCY_WAITABLE_OBJECT,
+            )?;
+
+            let (history_texture, history_srv) = Self::create_history_texture(&self.d3d_device, width, height, self.output_format)?;
+            self.history_texture = history_texture;
+            self.history_srv = history_srv;
+            self.width = width;
+            self.height = height;
+        }
+
+        self.monitor_refresh_rate = monitor_info.refresh_rate as f32;
+        self.overlay_state.write().monitor_refresh_rate = self.monitor_refresh_rate;
+
+        // The capture texture follows the duplicated frame size and the generated images the
+        // screen size, so both are recreated on the next frame
+        self.capture_texture = None;
+        self.capture_srv = None;
+        self.cursor_staging = None;
+        self.generated_image = None;
+        self.history_valid = false;
+
+        // The old duplication is invalidated by the mode change
+        self.reacquire_capture("display change");
+        Ok(())
+    }
+
+    /// Starts desktop duplication, or Windows.Graphics.Capture when another app holds the duplication.
+    #[cfg(windows)]
+    unsafe fn try_acquire_capture(&mut self) -> Result<()> {
+        self.desktop_duplication = None;
+        self.graphics_capture = None;
+        self.capture_conflict = false;
+        self.history_valid = false;
+        self.last_duplication_attempt = std::time::Instant::now();
+
+        let duplication_error = match DesktopDuplicator::new(self.d3d_device.clone(), self.d3d_context.clone(), self.monitor_index) {
+            Ok(duplicator) => {
+                self.desktop_duplication = Some(duplicator);
+                return Ok(());
+            }
+            Err(e) => name_virtual_display(e, self.monitor_index),
+        };
+        let Some(code) = duplication_conflict(&duplication_error) else {
+            return Err(duplication_error);
+        };
+
+        let reason = hresult_name(code);
+        self.capture_conflict = true;
+        if !GraphicsCapturer::is_supported() {
+            return Err(duplication_error.context("Windows.Graphics.Capture is not supported on this system"));
+        }
+
+        let capture_cursor = self.overlay_state.read().correct_cursor;
+        match GraphicsCapturer::new(&self.d3d_device, self.monitor_index, capture_cursor) {
+            Ok(capturer) => {
+                log_warn!("Desktop duplication unavailable ({}), probably held by another app - using Windows.Graphics.Capture", reason);
+                self.graphics_capture = Some(capturer);
+                self.capture_conflict = false;
+                self.last_capture_fallback = Some(reason);
+                Ok(())
+            }
+            Err(e) => Err(duplication_error.context(format!("Windows.Graphics.Capture fallback failed: {:#}", e))),
+        }
+    }
+
+    #[cfg(windows)]
+    unsafe fn reacquire_capture(&mut self, reason: &str) {
+        if self.source != OverlaySource::DesktopCapture {
+            return;
+        }
+        if let Err(e) = self.try_acquire_capture() {
+            log_warn!("Failed to re-acquire desktop capture after {}: {:#}", reason, e);
+        }
+    }
+
+    /// Checks whether a failed frame was caused by the GPU device going away and, if so, rebuilds it.
+    ///
+    /// Returns `Ok(true)` once the device was recreated (or a stop was requested meanwhile),
+    /// `Ok(false)` for ordinary errors, and an error after `max_attempts` failed rebuilds.
+    #[cfg(windows)]
+    unsafe fn recover_if_device_removed(
+        &mut self,
+        lifecycle: &OverlayLifecycle,
+        diagnostics: &Mutex<OverlayDiagnostics>,
+        max_attempts: u32,
+    ) -> Result<bool> {
+        let Err(removed) = self.d3d_device.GetDeviceRemovedReason() else {
+            return Ok(false);
+        };
+
+        let reason = hresult_name(removed.code());
+        log_error!("GPU device removed: {} - {}", reason, removed.message());
+        diagnostics.lock().last_device_removed_reason = Some(reason.clone());
+
+        for attempt in 1..=max_attempts {
+            // The driver is usually still resetting right after removal
+            thread::sleep(std::time::Duration::from_millis(500 * attempt as u64));
+            if !lifecycle.keep_running() {
+                return Ok(true);
+            }
+
+            match self.rebuild_device() {
+                Ok(()) => {
+                    log_info!("GPU device recreated (attempt {}/{})", attempt, max_attempts);
+                    diagnostics.lock().device_removed_recoveries += 1;
+                    self.publish_diagnostics(diagnostics);
+                    return Ok(true);
+                }
+                Err(e) => log_warn!("GPU device rebuild attempt {}/{} failed: {}", attempt, max_attempts, e),
+            }
+        }
+
+        anyhow::bail!("GPU device removed ({}) and not recreated after {} attempts", reason, max_attempts)
+    }
+
+    /// Recreates the device and everything created from it. Only the window and the
+    /// DirectComposition tree survive, the visual just gets the new swap chain.
+    #[cfg(windows)]
+    unsafe fn rebuild_device(&mut self) -> Result<()> {
+        self.desktop_duplication = None;
+        self.graphics_capture = None;
+        self.capture_texture = None;
+        self.capture_srv = None;
+        self.cursor_staging = None;
+        self.preview_staging = None;
+        self.preview_pending = false;
+        self.generated_image = None;
+
+        let (d3d_device, d3d_context) = create_d3d_device(D3D_DRIVER_TYPE_HARDWARE)?;
+        let swap_chain = Self::create_swap_chain(&d3d_device, self.width, self.height, self.output_format)?;
+
+        let swap_chain2: IDXGISwapChain2 = swap_chain.cast()?;
+        swap_chain2.SetMaximumFrameLatency(1)?;
+        let frame_latency_waitable = swap_chain2.GetFrameLatencyWaitableObject();
+
+        let pipeline = RenderPipeline::new(&d3d_device)?;
+
+        let spectrum_generation = self.overlay_state.read().spectrum_generation;
+        let spectrum_format = Self::select_spectrum_format(&d3d_device)?;
+        let (spectrum_srvs, noise_srv, constant_buffer) = Self::init_spectrum_textures(&d3d_device, &self.overlay_state, (self.width, self.height), spectrum_format)?;
+        let (history_texture, history_srv) = Self::create_history_texture(&d3d_device, self.width, self.height, self.output_format)?;
+
+        self.dcomp_visual.SetContent(&swap_chain)?;
+        self.dcomp_device.Commit()?;
+
+        let _ = CloseHandle(self.frame_latency_waitable);
+
+        self.d3d_device = d3d_device;
+        self.d3d_context = d3d_context;
+        self.swap_chain = swap_chain;
+        self.present_stats.reset();
+        self.frame_latency_waitable = frame_latency_waitable;
+        self.pipeline = pipeline;
+        self.spectrum_srvs = spectrum_srvs;
+        self.spectrum_generation = spectrum_generation;
+        self.spectrum_format = spectrum_format;
+        self.noise_srv = noise_srv;
+        self.constant_buffer = constant_buffer;
+        self.lut_srvs.clear();
+        self.lut_key = None;
+        self.history_texture = history_texture;
+        self.history_srv = history_srv;
+        self.history_valid = false;
+
+        self.reacquire_capture("device rebuild");
+
+        Ok(())
+    }
+
+    #[cfg(windows)]
+    unsafe fn publish_diagnostics(&self, diagnostics: &Mutex<OverlayDiagnostics>) {
+        match self.adapter_diagnostics() {
+            Ok(adapter) => {
+                log_info!(
+                    "Rendering on {} (feature level {}, {} MB dedicated video memory)",
+                    adapter.description,
+                    adapter.feature_level,
+                    adapter.dedicated_video_memory / (1024 * 1024)
+                );
+                diagnostics.lock().adapter = Some(adapter);
+            }
+            Err(e) => log_warn!("Failed to read adapter diagnostics: {}", e),
+        }
+    }
+
+    #[cfg(windows)]
+    unsafe fn adapter_diagnostics(&self) -> Result<AdapterDiagnostics> {
+        let adapter = self.d3d_device.cast::<IDXGIDevice>()?.GetAdapter()?;
+        let desc = adapter.GetDesc()?;
+        let description_len = desc.Description.iter().position(|&c| c == 0).unwrap_or(desc.Description.len());
+        let swap_chain_desc = self.swap_chain.GetDesc1()?;
+
+        Ok(AdapterDiagnostics {
+            description: String::from_utf16_lossy(&desc.Description[..description_len]),
+            dedicated_video_memory: desc.DedicatedVideoMemory as u64,
+            dedicated_system_memory: desc.DedicatedSystemMemory as u64,
+            shared_system_memory: desc.SharedSystemMemory as u64,
+            feature_level: feature_level_name(self.d3d_device.GetFeatureLevel()),
+            swap_chain_format: format_name(swap_chain_desc.Format),
+            spectrum_format: format_name(self.spectrum_format.dxgi_format()),
+        })
+    }
+
+    /// Brings the capture texture up to date, waiting up to `timeout_ms` for new desktop content.
+    #[cfg(windows)]
+    unsafe fn capture_frame(&mut self, timeout_ms: u32) -> Result<()> {
+        match self.source {
+            OverlaySource::DesktopCapture => self.capture_desktop_frame(timeout_ms),
+            OverlaySource::TestPattern | OverlaySource::SolidColor => self.update_generated_image(),
+        }
+    }
+
+    #[cfg(windows)]
+    unsafe fn prepare_frame(&mut self) -> Result<()> {
+        self.reload_spectrum_if_changed()?;
+        self.update_luts()?;
+
+        let back_buffer: ID3D11Texture2D = self.swap_chain.GetBuffer(0)?;
+        let mut rtv: Option<ID3D11RenderTargetView> = None;
+        self.d3d_device.CreateRenderTargetView(&back_buffer, None, Some(&mut rtv))?;
+        let rtv = rtv.unwrap();
+
+        let clear_color = [0.0f32, 0.0, 0.0, 0.0];
+        self.d3d_context.ClearRenderTargetView(&rtv, &clear_color);
+
+        // Without a captured frame there is nothing to correct; present the transparent frame
+        if !self.capture_available() || self.capture_srv.is_none() {
+            self.history_valid = false;
+            return Ok(());
+        }
+
+        self.d3d_context.OMSetRenderTargets(Some(&[Some(rtv.clone())]), None);
+
+        // Update constant buffer with current strength every frame
+        self.update_constant_buffer()?;
+
+        let srvs = shader_resources(self.capture_srv.as_ref(), &self.spectrum_srvs, self.noise_srv.as_ref(), Some(&self.history_srv), &self.lut_srvs);
+        self.pipeline.draw(&self.d3d_context, &srvs, &self.constant_buffer, (self.width, self.height));
+        self.update_history(&back_buffer);
+
+        Ok(())
+    }
+
+    /// Copies the latest desktop frame into the capture texture. Desktop duplication waits up to
+    /// `timeout_ms` for one; Windows.Graphics.Capture only takes what has already arrived.
+    #[cfg(windows)]
+    unsafe fn capture_desktop_frame(&mut self, timeout_ms: u32) -> Result<()> {
+        if let Some(ref mut capturer) = self.graphics_capture {
+            capturer.set_cursor_capture(self.overlay_state.read().correct_cursor);
+            if let Some(frame) = capturer.latest_frame()? {
+                self.copy_to_capture_texture(&frame.texture)?;
+            }
+            return Ok(());
+        }
+
+        let Some(ref mut duplicator) = self.desktop_duplication else {
+            return Ok(());
+        };
+        let acquired_texture = match duplicator.acquire_next_frame(timeout_ms) {
+            Ok(Some(texture)) => texture,
+            Ok(None) => return Ok(()),
+            Err(e) if e.downcast_ref::<windows::core::Error>().is_some_and(|e| e.code() == DXGI_ERROR_ACCESS_LOST) => {
+                // E.g. another app took over the output; re-acquired on the next frame
+                log_warn!("Desktop duplication lost, re-acquiring");
+                self.desktop_duplication = None;
+                self.last_duplication_attempt = std::time::Instant::now() - DUPLICATION_RETRY_INTERVAL;
+                return Ok(());
+            }
+            Err(e) => return Err(e),
+        };
+        let present_time = duplicator.last_present_time;
+
+        let copied = self.copy_to_capture_texture(&acquired_texture);
+        if let Some(ref mut duplicator) = self.desktop_duplication {
+            duplicator.release_frame()?;
+        }
+        copied?;
+        if present_time.is_some() {
+            self.captured_present_time = present_time;
+        }
+
+        if self.overlay_state.read().correct_cursor {
+            self.draw_software_cursor()?;
+        }
+
+        Ok(())
+    }
+
+    /// Copies a captured frame into the capture texture, recreating it when the frame size or
+    /// format changed; `CopyResource` does nothing between textures that differ in either.
+    #[cfg(windows)]
+    unsafe fn copy_to_capture_texture(&mut self, acquired_texture: &ID3D11Texture2D) -> Result<()> {
+        // Sized like the captured frame; duplicated frames are in the output's native orientation
+        let mut acquired_desc = D3D11_TEXTURE2D_DESC::default();
+        acquired_texture.GetDesc(&mut acquired_desc);
+        let previous = self.capture_texture.as_ref().map(|texture| {
+            let mut desc = D3D11_TEXTURE2D_DESC::default();
+            texture.GetDesc(&mut desc);
+            desc
+        });
+        if let Some(desc) = previous {
+            if desc.Format != acquired_desc.Format {
+                log_info!("Captured frames changed from {} to {}", format_name(desc.Format), format_name(acquired_desc.Format));
+            } else if (desc.Width, desc.Height) != (acquired_desc.Width, acquired_desc.Height) {
+                log_debug!("Captured frame size changed to {}x{}", acquired_desc.Width, acquired_desc.Height);
+            } else {
+                return self.copy_capture(acquired_texture);
+            }
+            self.capture_texture = None;
+            self.capture_srv = None;
+            self.cursor_staging = None;
+        }
+
+        if self.capture_texture.is_none() {
+            self.set_output_format(output_format_for(acquired_desc.Format))?;
+
+            // Create a staging texture that can be used as a shader resource
+            let texture_desc = D3D11_TEXTURE2D_DESC {
+                Width: acquired_desc.Width,
+                Height: acquired_desc.Height,
+                MipLevels: 1,
+                ArraySize: 1,
+                // The shader samples normalized values either way, so 10-bit frames need no conversion
+                Format: acquired_desc.Format,
+                SampleDesc: DXGI_SAMPLE_DESC { Count: 1, Quality: 0 },
+                Usage: D3D11_USAGE_DEFAULT,
+                BindFlags: D3D11_BIND_SHADER_RESOURCE.0 as u32,
+                CPUAccessFlags: 0,
+                MiscFlags: 0,
+            };
+
+            let mut texture: Option<ID3D11Texture2D> = None;
+            self.d3d_device.CreateTexture2D(&texture_desc, None, Some(&mut texture))?;
+            let texture = texture.unwrap();
+
+            let mut srv: Option<ID3D11ShaderResourceView> = None;
+            self.d3d_device.CreateShaderResourceView(&texture, None, Some(&mut srv))?;
+
+            self.capture_texture = Some(texture);
+            self.capture_srv = Some(srv.unwrap());
+        }
+
+        self.copy_capture(acquired_texture)
+    }
+
+    #[cfg(windows)]
+    unsafe fn copy_capture(&self, acquired_texture: &ID3D11Texture2D) -> Result<()> {
+        if let Some(ref capture_texture) = self.capture_texture {
+            self.d3d_context.CopyResource(capture_texture, acquired_texture);
+        }
+        Ok(())
+    }
+
+    /// Switches the swap chain and the history texture to `format`. When the swap chain can't
+    /// take it the current format is kept, and the correction is presented at that precision.
+    #[cfg(windows)]
+    unsafe fn set_output_format(&mut self, format: DXGI_FORMAT) -> Result<()> {
+        if format == self.output_format {
+            return Ok(());
+        }
+
+        // ResizeBuffers fails while anything still references the back buffers
+        self.d3d_context.OMSetRenderTargets(None, None);
+        self.d3d_context.ClearState();
+        self.d3d_context.Flush();
+        if let Err(e) = self.swap_chain.ResizeBuffers(0, self.width, self.height, format, DXGI_SWAP_CHAIN_FLAG_FRAME_LATENCY_WAITABLE_OBJECT) {
+            log_warn!("Swap chain can't present {} ({}), staying at {}", format_name(format), hresult_name(e.code()), format_name(self.output_format));
+            return Ok(());
+        }
+
+        let (history_texture, history_srv) = Self::create_history_texture(&self.d3d_device, self.width, self.height, format)?;
+        self.history_texture = history_texture;
+        self.history_srv = history_srv;
+        self.history_valid = false;
+        log_info!("Presenting in {} to match the captured frames", format_name(format));
+        self.output_format = format;
+        self.output_format_changed = true;
+        Ok(())
+    }
+
+    /// Draws the pointer into the capture texture so the correction pass recolors it too;
+    /// duplicated frames never contain the hardware cursor.
+    #[cfg(windows)]
+    unsafe fn draw_software_cursor(&mut self) -> Result<()> {
+        let (Some(duplicator), Some(capture_texture)) = (self.desktop_duplication.as_ref(), self.capture_texture.as_ref()) else {
+            return Ok(());
+        };
+        let mut capture_desc = D3D11_TEXTURE2D_DESC::default();
+        capture_texture.GetDesc(&mut capture_desc);
+        let Some(layout) = pixel_layout(capture_desc.Format) else {
+            return Ok(());
+        };
+        let (Some((x, y)), Some(shape)) = (duplicator.pointer_position, duplicator.pointer_shape.as_ref()) else {
+            return Ok(());
+        };
+        // The pointer is reported in desktop coordinates but rotated outputs are captured unrotated
+        if self.capture_rotation() != 0 {
+            return Ok(());
+        }
+
+        // Cursor rectangle clipped to the output
+        let left = x.clamp(0, self.width as i32) as u32;
+        let top = y.clamp(0, self.height as i32) as u32;
+        let right = (x + shape.width as i32).clamp(0, self.width as i32) as u32;
+        let bottom = (y + shape.height as i32).clamp(0, self.height as i32) as u32;
+        if right <= left || bottom <= top {
+            return Ok(());
+        }
+        let (width, height) = (right - left, bottom - top);
+
+        let staging = match self.cursor_staging {
+            Some((ref texture, w, h)) if w >= width && h >= height => texture.clone(),
+            _ => {
+                let (w, h) = (shape.width.max(width), shape.height.max(height));
+                let texture_desc = D3D11_TEXTURE2D_DESC {
+                    Width: w,
+                    Height: h,
+                    MipLevels: 1,
+                    ArraySize: 1,
+                    Format: capture_desc.Format,
+                    SampleDesc: DXGI_SAMPLE_DESC { Count: 1, Quality: 0 },
+                    Usage: D3D11_USAGE_STAGING,
+                    BindFlags: 0,
+                    CPUAccessFlags: (D3D11_CPU_ACCESS_READ.0 | D3D11_CPU_ACCESS_WRITE.0) as u32,
+                    MiscFlags: 0,
+                };
+
+                let mut texture: Option<ID3D11Texture2D> = None;
+                self.d3d_device.CreateTexture2D(&texture_desc, None, Some(&mut texture))?;
+                let texture = texture.unwrap();
+                self.cursor_staging = Some((texture.clone(), w, h));
+                texture
+            }
+        };
+
+        let screen_box = D3D11_BOX { left, top, front: 0, right, bottom, back: 1 };
+        self.d3d_context.CopySubresourceRegion(&staging, 0, 0, 0, 0, capture_texture, 0, Some(&screen_box));
+
+        let mut mapped: D3D11_MAPPED_SUBRESOURCE = std::mem::zeroed();
+        self.d3d_context.Map(&staging, 0, D3D11_MAP_READ_WRITE, 0, Some(&mut mapped))?;
+        let pixels = std::slice::from_raw_parts_mut(mapped.pData as *mut u8, mapped.RowPitch as usize * height as usize);
+        shape.composite_onto(pixels, mapped.RowPitch as usize, (width, height), (x - left as i32, y - top as i32), layout);
+        self.d3d_context.Unmap(&staging, 0);
+
+        let staging_box = D3D11_BOX { left: 0, top: 0, front: 0, right: width, bottom: height, back: 1 };
+        self.d3d_context.CopySubresourceRegion(capture_texture, 0, left, top, 0, &staging, 0, Some(&staging_box));
+
+        Ok(())
+    }
+
+    /// Regenerates the capture texture when the selected test pattern or color changed.
+    #[cfg(windows)]
+    unsafe fn update_generated_image(&mut self) -> Result<()> {
+        let wanted = {
+            let state = self.overlay_state.read();
+            match self.source {
+                OverlaySource::SolidColor => GeneratedImage::Solid(state.solid_color),
+                _ => GeneratedImage::Pattern(state.test_pattern),
+            }
+        };
+        if self.generated_image == Some(wanted) {
+            return Ok(());
+        }
+
+        let pixels = match wanted {
+            GeneratedImage::Pattern(pattern) => pattern.generate_bgra(self.width, self.height),
+            GeneratedImage::Solid(color) => chromabridge::test_pattern::solid_bgra(color, self.width, self.height),
+        };
 
-    overlay_state: Arc<RwLock<OverlayState>>,
+        let (texture, srv) = create_image_texture(&self.d3d_device, &pixels, self.width, self.height)?;
+        self.capture_texture = Some(texture);
+        self.capture_srv = Some(srv);
+        self.generated_image = Some(wanted);
+        self.history_valid = false;
 
-    width: u32,
-    height: u32,
-    frame_latency_waitable: HANDLE,
-    cap_to_monitor_refresh: bool,
-    monitor_refresh_rate: f32,
-}
+        log_debug!("Generated {:?} for the overlay source", wanted);
+        Ok(())
+    }
 
-#[cfg(windows)]
-impl DCompOverlay {
-    unsafe fn new(state: Arc<RwLock<OverlayState>>, monitor_info: MonitorInfo, monitor_index: usize, cap_to_monitor_refresh: bool) -> Result<Self> {
-        let (pos, size) = (monitor_info.pos, monitor_info.size);
-        let width = size.0 as u32;
-        let height = size.1 as u32;
+    /// False if Windows reports the overlay occluded, in which case the frame went nowhere.
+    #[cfg(windows)]
+    unsafe fn present_frame(&mut self) -> Result<bool> {
+        // No vsync - immediate present
+        let status = self.swap_chain.Present(0, DXGI_PRESENT(0));
+        status.ok()?;
+        Ok(status != DXGI_STATUS_OCCLUDED)
+    }
 
-        let hwnd = Self::create_overlay_window(pos, size)?;
-        let (d3d_device, d3d_context) = Self::create_d3d_device()?;
-        let swap_chain = Self::create_swap_chain(&d3d_device, width, height)?;
+    /// Binds a new swap chain to the visual, for an occlusion that doesn't clear by itself.
+    #[cfg(windows)]
+    unsafe fn recreate_swap_chain(&mut self) -> Result<()> {
+        self.d3d_context.OMSetRenderTargets(None, None);
+        let swap_chain = Self::create_swap_chain(&self.d3d_device, self.width, self.height, self.output_format)?;
 
-        // Get waitable handle and set max frame latency for proper frame pacing
         let swap_chain2: IDXGISwapChain2 = swap_chain.cast()?;
         swap_chain2.SetMaximumFrameLatency(1)?;
         let frame_latency_waitable = swap_chain2.GetFrameLatencyWaitableObject();
-        log_info!("Frame latency waitable object initialized");
 
-        let dcomp_device: IDCompositionDevice = DCompositionCreateDevice(None)?;
-        let dcomp_target = dcomp_device.CreateTargetForHwnd(hwnd, true)?;
-        let dcomp_visual = dcomp_device.CreateVisual()?;
-        dcomp_visual.SetContent(&swap_chain)?;
-        dcomp_target.SetRoot(&dcomp_visual)?;
-        dcomp_device.Commit()?;
+        if let Err(e) = self.dcomp_visual.SetContent(&swap_chain).and_then(|()| self.dcomp_device.Commit()) {
+            let _ = CloseHandle(frame_latency_waitable);
+            return Err(e.into());
+        }
+        let _ = CloseHandle(self.frame_latency_waitable);
 
-        log_info!("DirectComposition overlay initialized ({}x{} @ {},{}, {}Hz)",
-                 width, height, pos.0, pos.1, monitor_info.refresh_rate);
+        self.swap_chain = swap_chain;
+        self.frame_latency_waitable = frame_latency_waitable;
+        self.present_stats.reset();
+        self.history_valid = false;
+        log_info!("Swap chain recreated ({}x{}, {})", self.width, self.height, format_name(self.output_format));
+        Ok(())
+    }
+
+    #[cfg(windows)]
+    unsafe fn reload_spectrum_if_changed(&mut self) -> Result<()> {
+        let state = self.overlay_state.read();
+        if state.spectrum_generation == self.spectrum_generation {
+            return Ok(());
+        }
 
-        let (vertex_shader, pixel_shader, input_layout, vertex_buffer) = Self::init_rendering_pipeline(&d3d_device)?;
-        let (sampler_state, spectrum_sampler, blend_state) = Self::create_render_states(&d3d_device)?;
+        self.spectrum_srvs = Self::create_spectrum_srvs(&self.d3d_device, &state, self.spectrum_format)?;
+        self.spectrum_generation = state.spectrum_generation;
+        self.history_valid = false;
 
-        let (spectrum1_srv, spectrum2_srv, noise_srv, constant_buffer) = Self::init_spectrum_textures(&d3d_device, &state)?;
+        log_info!("Spectrum textures reloaded (spectra: {})", self.spectrum_srvs.len());
+        Ok(())
+    }
 
-        let desktop_duplication = match DesktopDuplicator::new(d3d_device.clone(), d3d_context.clone(), monitor_index) {
-            Ok(dd) => Some(dd),
-            Err(e) => {
-                log_warn!("Failed to initialize desktop duplication: {}. Falling back to test pattern.", e);
-                None
+    /// Rebakes the correction tables when `ColorMath::BakedLut` is selected and the spectrum,
+    /// strength or hue gate changed since the last bake, and frees them when it is not.
+    #[cfg(windows)]
+    unsafe fn update_luts(&mut self) -> Result<()> {
+        let state = self.overlay_state.read();
+        if state.color_math != ColorMath::BakedLut {
+            if self.lut_key.take().is_some() {
+                self.lut_srvs.clear();
+                log_debug!("Baked LUTs released");
             }
-        };
+            return Ok(());
+        }
 
-        Ok(Self {
-            _hwnd: hwnd,
-            d3d_device,
-            d3d_context,
-            swap_chain,
-            _dcomp_device: dcomp_device,
-            _dcomp_target: dcomp_target,
-            _dcomp_visual: dcomp_visual,
-            vertex_shader,
-            pixel_shader,
-            input_layout,
-            vertex_buffer,
-            sampler_state,
-            spectrum_sampler,
-            blend_state,
-            spectrum1_srv,
-            spectrum2_srv,
-            noise_srv,
-            constant_buffer,
-            capture_texture: None,
-            capture_srv: None,
-            desktop_duplication,
-            overlay_state: state,
-            width,
-            height,
-            frame_latency_waitable,
-            cap_to_monitor_refresh,
-            monitor_refresh_rate: monitor_info.refresh_rate as f32,
-        })
+        let key = LutKey::of(&state, self.spectrum_generation);
+        if self.lut_key.as_ref() == Some(&key) {
+            return Ok(());
+        }
+
+        let started = Instant::now();
+        self.lut_srvs = self.pipeline.bake_luts(&self.d3d_device, &self.d3d_context, &state, &self.spectrum_srvs)?;
+        self.lut_key = Some(key);
+        log_debug!("Baked {} LUT(s) in {:.2}ms", self.lut_srvs.len(), started.elapsed().as_secs_f32() * 1000.0);
+        Ok(())
     }
 
-    unsafe fn create_overlay_window(pos: (i32, i32), size: (i32, i32)) -> Result<HWND> {
-        let class_name = w!("ChromaBridgeOverlay");
-        let hinstance = windows::Win32::System::LibraryLoader::GetModuleHandleW(None)?;
+    /// The mode the next frame is corrected with; the shader math until the tables are baked.
+    #[cfg(windows)]
+    fn color_math(&self) -> ColorMath {
+        if self.lut_srvs.is_empty() { ColorMath::Shader } else { ColorMath::BakedLut }
+    }
 
-        let wc = WNDCLASSW {
-            lpfnWndProc: Some(Self::window_proc),
-            hInstance: hinstance.into(),
-            lpszClassName: class_name,
-            style: CS_HREDRAW | CS_VREDRAW,
-            ..Default::default()
+    /// Keeps the frame just drawn as history for the next one, or drops the history when smoothing is off.
+    #[cfg(windows)]
+    unsafe fn update_history(&mut self, back_buffer: &ID3D11Texture2D) {
+        if self.overlay_state.read().temporal_smoothing > 0.0 {
+            self.d3d_context.CopyResource(&self.history_texture, back_buffer);
+            self.history_valid = true;
+        } else {
+            self.history_valid = false;
+        }
+    }
+
+    #[cfg(windows)]
+    unsafe fn create_history_texture(device: &ID3D11Device, width: u32, height: u32, format: DXGI_FORMAT) -> Result<(ID3D11Texture2D, ID3D11ShaderResourceView)> {
+        // Same format as the swap chain so the back buffer can be copied straight in
+        let texture_desc = D3D11_TEXTURE2D_DESC {
+            Width: width,
+            Height: height,
+            MipLevels: 1,
+            ArraySize: 1,
+            Format: format,
+            SampleDesc: DXGI_SAMPLE_DESC { Count: 1, Quality: 0 },
+            Usage: D3D11_USAGE_DEFAULT,
+            BindFlags: D3D11_BIND_SHADER_RESOURCE.0 as u32,
+            CPUAccessFlags: 0,
+            MiscFlags: 0,
         };
 
-        RegisterClassW(&wc);
+        let mut texture: Option<ID3D11Texture2D> = None;
+        device.CreateTexture2D(&texture_desc, None, Some(&mut texture))?;
+        let texture = texture.unwrap();
 
-        let hwnd = CreateWindowExW(
-            WS_EX_LAYERED | WS_EX_TRANSPARENT | WS_EX_TOPMOST | WS_EX_NOACTIVATE,
-            class_name,
-            w!("ChromaBridge Overlay"),
-            WS_POPUP,
-            pos.0, pos.1, size.0, size.1,
-            None, None,
-            Some(HINSTANCE(hinstance.0)),
-            None,
-        )?;
+        let mut srv: Option<ID3D11ShaderResourceView> = None;
+        device.CreateShaderResourceView(&texture, None, Some(&mut srv))?;
 
-        let ex_style = GetWindowLongW(hwnd, GWL_EXSTYLE);
-        SetWindowLongW(hwnd, GWL_EXSTYLE, ex_style | WS_EX_TRANSPARENT.0 as i32);
+        Ok((texture, srv.unwrap()))
+    }
 
-        if let Err(e) = SetWindowDisplayAffinity(hwnd, WDA_EXCLUDEFROMCAPTURE) {
-            log_warn!("Failed to exclude window from capture: {:?}", e);
-        } else {
-            log_info!("Window excluded from Desktop Duplication");
-        }
+    #[cfg(windows)]
+    unsafe fn present_clear(&mut self) -> Result<()> {
+        let back_buffer: ID3D11Texture2D = self.swap_chain.GetBuffer(0)?;
+        let mut rtv: Option<ID3D11RenderTargetView> = None;
+        self.d3d_device.CreateRenderTargetView(&back_buffer, None, Some(&mut rtv))?;
 
-        let _ = ShowWindow(hwnd, SW_SHOW);
+        let clear_color = [0.0f32, 0.0, 0.0, 0.0];
+        self.d3d_context.ClearRenderTargetView(&rtv.unwrap(), &clear_color);
+        self.present_frame().map(drop)
+    }
 
-        Ok(hwnd)
+    #[cfg(windows)]
+    unsafe fn update_constant_buffer(&mut self) -> Result<()> {
+        let params = self.current_params();
+        write_constant_buffer(&self.d3d_context, &self.constant_buffer, &params)
     }
 
-    unsafe extern "system" fn window_proc(
-        hwnd: HWND,
-        msg: u32,
-        wparam: WPARAM,
-        lparam: LPARAM,
-    ) -> LRESULT {
-        match msg {
-            WM_DESTROY => {
-                PostQuitMessage(0);
-                LRESULT(0)
-            }
-            _ => DefWindowProcW(hwnd, msg, wparam, lparam),
-        }
+    #[cfg(windows)]
+    fn current_params(&self) -> SpectrumParams {
+        let use_lut = !self.lut_srvs.is_empty();
+        let mut params = spectrum_params(&self.overlay_state.read(), self.interactive_debug, (self.width, self.height), self.history_valid, self.capture_rotation(), self.shell_exclusion.rects(), use_lut);
+        limit_params_to(&mut params, self.target_window.area());
+        params
     }
 
-    unsafe fn create_d3d_device() -> Result<(ID3D11Device, ID3D11DeviceContext)> {
-        let mut device: Option<ID3D11Device> = None;
-        let mut context: Option<ID3D11DeviceContext> = None;
+    /// Reads back the capture texture and the rendered back buffer and hands them to a thread
+    /// that writes them out, since encoding two PNGs takes several frames.
+    #[cfg(windows)]
+    unsafe fn dump_frame(&self, frame_dump: &Arc<FrameDump>, app_state: &StateManager) {
+        let images = (|| -> Result<(image::RgbaImage, image::RgbaImage)> {
+            let captured = self.capture_texture.as_ref().ok_or_else(|| anyhow::anyhow!("No captured frame to dump yet"))?;
+            let back_buffer: ID3D11Texture2D = self.swap_chain.GetBuffer(0)?;
+            Ok((
+                read_texture(&self.d3d_device, &self.d3d_context, captured)?,
+                read_texture(&self.d3d_device, &self.d3d_context, &back_buffer)?,
+            ))
+        })();
+
+        let info = app_state.read(|s| crate::frame_dump::FrameDumpInfo {
+            timestamp: chrono::Local::now().to_rfc3339(),
+            monitor: self.monitor_index,
+            source: self.source,
+            spectrum: s.spectrum_name.clone(),
+            strength: s.strength,
+            noise: s.noise_texture.clone(),
+            constant_buffer: serde_json::to_value(self.current_params()).unwrap_or_default(),
+        });
+        let dir = app_state.app_data_dir().join(crate::frame_dump::DUMP_DIR_NAME);
+        let frame_dump = Arc::clone(frame_dump);
+        let spawned = thread::Builder::new().name("frame-dump".into()).spawn(move || {
+            let result = images.and_then(|(captured, corrected)| crate::frame_dump::save(&dir, &captured, &corrected, &info));
+            frame_dump.finish(result);
+        });
+        if let Err(e) = spawned {
+            log_error!("Failed to spawn frame dump thread: {}", e);
+        }
+    }
 
-        let feature_levels = [
-            D3D_FEATURE_LEVEL_11_1,
-            D3D_FEATURE_LEVEL_11_0,
-            D3D_FEATURE_LEVEL_10_1,
-        ];
+    /// Copies the rendered back buffer for the developer preview window; `finish_preview` reads
+    /// the copy on a later frame, once the GPU got to it.
+    #[cfg(windows)]
+    unsafe fn start_preview(&mut self) -> Result<()> {
+        let back_buffer: ID3D11Texture2D = self.swap_chain.GetBuffer(0)?;
+        let mut desc = D3D11_TEXTURE2D_DESC::default();
+        back_buffer.GetDesc(&mut desc);
 
-        D3D11CreateDevice(
-            None,
-            D3D_DRIVER_TYPE_HARDWARE,
-            HMODULE::default(),
-            D3D11_CREATE_DEVICE_BGRA_SUPPORT,
-            Some(&feature_levels),
-            D3D11_SDK_VERSION,
-            Some(&mut device),
-            None,
-            Some(&mut context),
-        )?;
+        let reusable = self.preview_staging.as_ref().filter(|staging| {
+            let mut staging_desc = D3D11_TEXTURE2D_DESC::default();
+            staging.GetDesc(&mut staging_desc);
+            (staging_desc.Width, staging_desc.Height, staging_desc.Format) == (desc.Width, desc.Height, desc.Format)
+        });
+        let staging = match reusable {
+            Some(staging) => staging.clone(),
+            None => {
+                let staging = create_staging_texture(&self.d3d_device, &desc)?;
+                self.preview_staging = Some(staging.clone());
+                staging
+            }
+        };
+        self.d3d_context.CopyResource(&staging, &back_buffer);
+        self.preview_pending = true;
+        Ok(())
+    }
 
-        Ok((device.unwrap(), context.unwrap()))
+    /// Publishes the copy `start_preview` made, scaled down, unless the GPU hasn't made it yet;
+    /// then it is tried again next frame rather than waited for.
+    #[cfg(windows)]
+    unsafe fn finish_preview(&mut self, output_preview: &OutputPreview) {
+        let Some(staging) = self.preview_staging.clone() else {
+            self.preview_pending = false;
+            return;
+        };
+        let mut mapped: D3D11_MAPPED_SUBRESOURCE = std::mem::zeroed();
+        match self.d3d_context.Map(&staging, 0, D3D11_MAP_READ, D3D11_MAP_FLAG_DO_NOT_WAIT.0 as u32, Some(&mut mapped)) {
+            Err(e) if e.code() == DXGI_ERROR_WAS_STILL_DRAWING => return,
+            Err(e) => output_preview.publish(Err(e.into())),
+            Ok(()) => {
+                let mut desc = D3D11_TEXTURE2D_DESC::default();
+                staging.GetDesc(&mut desc);
+                output_preview.publish(mapped_image(&mapped, &desc, crate::output_preview::PREVIEW_WIDTH));
+                self.d3d_context.Unmap(&staging, 0);
+            }
+        }
+        self.preview_pending = false;
     }
 
-    unsafe fn create_swap_chain(device: &ID3D11Device, width: u32, height: u32) -> Result<IDXGISwapChain1> {
-        let dxgi_device = device.cast::<IDXGIDevice>()?;
-        let dxgi_adapter = dxgi_device.GetAdapter()?;
-        let dxgi_factory: IDXGIFactory2 = dxgi_adapter.GetParent()?;
+    /// Uploads the noise texture a band of rows at a time, so a large one is never held in memory
+    /// a second time in its GPU format.
+    unsafe fn create_noise_srv(device: &ID3D11Device, noise_texture: &NoiseTexture) -> Result<ID3D11ShaderResourceView> {
+        let (width, height) = (noise_texture.width(), noise_texture.height());
+        let max_size = max_texture_size(device.GetFeatureLevel());
+        anyhow::ensure!(
+            width <= max_size && height <= max_size,
+            "Noise texture is {}x{}, larger than the {}x{} textures this GPU supports",
+            width,
+            height,
+            max_size,
+            max_size
+        );
 
-        let swap_chain_desc = DXGI_SWAP_CHAIN_DESC1 {
+        // Masks stay white for the primary spectrum so linear filtering blends their edges as
+        // before; indices are spread over the full range and read back with point sampling
+        let top = MAX_NOISE_INDICES as u32 - 1;
+        let value = |index: u8| match noise_texture.kind() {
+            NoiseKind::Mask => if index == 0 { 255 } else { 0 },
+            NoiseKind::Channels | NoiseKind::Palette => ((index as u32).min(top) * 255 / top) as u8,
+        };
+
+        let noise_desc = D3D11_TEXTURE2D_DESC {
             Width: width,
             Height: height,
-            Format: DXGI_FORMAT_B8G8R8A8_UNORM,
+            MipLevels: 1,
+            ArraySize: 1,
+            Format: DXGI_FORMAT_R8_UNORM,
             SampleDesc: DXGI_SAMPLE_DESC { Count: 1, Quality: 0 },
-            BufferUsage: DXGI_USAGE_RENDER_TARGET_OUTPUT,
-            BufferCount: 2,
-            SwapEffect: DXGI_SWAP_EFFECT_FLIP_SEQUENTIAL,
-            AlphaMode: DXGI_ALPHA_MODE_PREMULTIPLIED,
-            Flags: DXGI_SWAP_CHAIN_FLAG_FRAME_LATENCY_WAITABLE_OBJECT.0 as u32,
-            ..Default::default()
+            Usage: D3D11_USAGE_DEFAULT,
+            BindFlags: D3D11_BIND_SHADER_RESOURCE.0 as u32,
+            CPUAccessFlags: 0,
+            MiscFlags: 0,
         };
 
-        let swap_chain = dxgi_factory.CreateSwapChainForComposition(device, &swap_chain_desc, None)?;
+        let mut texture: Option<ID3D11Texture2D> = None;
+        device.CreateTexture2D(&noise_desc, None, Some(&mut texture))?;
+        let texture = texture.unwrap();
+
+        let context = device.GetImmediateContext()?;
+        let band_size = width as usize * NOISE_UPLOAD_ROWS as usize;
+        let mut band = Vec::with_capacity(band_size.min(noise_texture.indices().len()));
+        for (i, indices) in noise_texture.indices().chunks(band_size).enumerate() {
+            band.clear();
+            band.extend(indices.iter().map(|&index| value(index)));
+
+            let first_row = i as u32 * NOISE_UPLOAD_ROWS;
+            let rows = (indices.len() / width as usize) as u32;
+            let area = D3D11_BOX { left: 0, top: first_row, front: 0, right: width, bottom: first_row + rows, back: 1 };
+            context.UpdateSubresource(&texture, 0, Some(&area), band.as_ptr() as *const _, width, 0);
+        }
 
-        Ok(swap_chain)
+        let mut srv: Option<ID3D11ShaderResourceView> = None;
+        device.CreateShaderResourceView(&texture, None, Some(&mut srv))?;
+        Ok(srv.unwrap())
     }
 
-    fn run_message_loop(&mut self, running_flag: &Arc<Mutex<bool>>, frame_stats: &Arc<Mutex<Option<(f32, f32)>>>) -> Result<()> {
-        #[cfg(windows)]
-        unsafe {
-            let mut msg = MSG::default();
-            let mut last_error_log = std::time::Instant::now();
-            let mut error_count = 0u32;
+    unsafe fn init_spectrum_textures(device: &ID3D11Device, state: &Arc<RwLock<OverlayState>>, screen_size: (u32, u32), spectrum_format: SpectrumFormat) -> Result<(Vec<ID3D11ShaderResourceView>, Option<ID3D11ShaderResourceView>, ID3D11Buffer)> {
+        let state_read = state.read();
 
-            let mut frame_times: Vec<(f32, f32)> = Vec::with_capacity(60);
-            let mut last_stats_update = std::time::Instant::now();
+        let spectrum_srvs = Self::create_spectrum_srvs(device, &state_read, spectrum_format)?;
 
-            let mut last_frame_time = std::time::Instant::now();
+        let noise_srv = match state_read.noise_texture {
+            Some(ref noise_texture) => Some(Self::create_noise_srv(device, noise_texture)?),
+            None => None,
+        };
 
-            loop {
-                if !*running_flag.lock() {
-                    log_info!("Overlay stop requested");
-                    break;
-                }
+        let params = spectrum_params(&state_read, false, screen_size, false, 0, &[], false);
 
-                WaitForSingleObjectEx(self.frame_latency_waitable, INFINITE, false);
+        let constant_buffer = create_constant_buffer(device, &params)?;
 
-                while PeekMessageW(&mut msg, None, 0, 0, PM_REMOVE).as_bool() {
-                    if msg.message == WM_QUIT {
-                        if error_count > 0 {
-                            log_warn!("Exiting with {} render errors encountered", error_count);
-                        }
-                        return Ok(());
-                    }
-                    let _ = TranslateMessage(&msg);
-                    DispatchMessageW(&msg);
-                }
+        log_info!("Spectrum textures initialized (spectra: {}, noise: {:?})",
+                 spectrum_srvs.len(),
+                 state_read.noise_texture.as_ref().map(|n| n.kind()));
 
-                let frame_start = std::time::Instant::now();
+        Ok((spectrum_srvs, noise_srv, constant_buffer))
+    }
 
-                if let Err(e) = self.prepare_frame() {
-                    error_count += 1;
+    unsafe fn create_spectrum_srvs(device: &ID3D11Device, state: &OverlayState, format: SpectrumFormat) -> Result<Vec<ID3D11ShaderResourceView>> {
+        state.spectrum_pair
+            .noise_spectra(state.noise_spectrum_limit())
+            .into_iter()
+            .map(|spectrum| Self::create_spectrum_srv(device, spectrum, format))
+            .collect()
+    }
 
-                    if last_error_log.elapsed().as_secs() >= 1 {
-                        log_error!("Render error (count: {}): {}", error_count, e);
-                        last_error_log = std::time::Instant::now();
-                    }
-                }
-                let render_time_ms = frame_start.elapsed().as_secs_f32() * 1000.0;
-                let _ = self.present_frame();
+    /// Picks the first format in the fallback chain the device can sample and actually create.
+    unsafe fn select_spectrum_format(device: &ID3D11Device) -> Result<SpectrumFormat> {
+        let required = (D3D11_FORMAT_SUPPORT_TEXTURE2D.0 | D3D11_FORMAT_SUPPORT_SHADER_SAMPLE.0) as u32;
 
-                // Apply FPS cap if enabled - use time since last frame to account for all overhead
-                if self.cap_to_monitor_refresh {
-                    let target_frame_duration = std::time::Duration::from_secs_f32(1.0 / self.monitor_refresh_rate);
-                    let elapsed_since_last = last_frame_time.elapsed();
+        for format in SpectrumFormat::FALLBACK_CHAIN {
+            let name = format_name(format.dxgi_format());
 
-                    if elapsed_since_last < target_frame_duration {
-                        spin_sleep::sleep(target_frame_duration - elapsed_since_last);
-                    }
+            let supported = device.CheckFormatSupport(format.dxgi_format()).map(|s| s & required == required).unwrap_or(false);
+            if !supported {
+                log_warn!("Spectrum texture format {} cannot be sampled on this GPU", name);
+                continue;
+            }
+
+            // Some drivers report support but still reject the texture, so probe with a real one
+            let probe = vec![0u8; (SPECTRUM_RESOLUTION as u32 * format.bytes_per_texel()) as usize];
+            match Self::create_lookup_srv(device, format, probe.as_ptr() as *const _) {
+                Ok(_) => {
+                    log_info!("Spectrum texture format: {}", name);
+                    return Ok(format);
                 }
-                let now = std::time::Instant::now();
-                let total_frame_time_ms = now.duration_since(last_frame_time).as_secs_f32() * 1000.0;
-                last_frame_time = now;
-                frame_times.push((render_time_ms, total_frame_time_ms));
+                Err(e) => log_warn!("Spectrum texture format {} rejected by the driver: {}", name, e),
+            }
+        }
 
-                if last_stats_update.elapsed().as_millis() >= 100 && !frame_times.is_empty() {
-                    let (sum_render, sum_total): (f32, f32) = frame_times.iter()
-                        .fold((0.0, 0.0), |(r, t), &(render, total)| (r + render, t + total));
-                    let avg_render_time = sum_render / frame_times.len() as f32;
-                    let avg_total_time = sum_total / frame_times.len() as f32;
+        anyhow::bail!("No supported spectrum texture format (tried R32G32B32_FLOAT, R32G32B32A32_FLOAT, R16G16B16A16_FLOAT)")
+    }
+
+    unsafe fn create_spectrum_srv(device: &ID3D11Device, spectrum: &chromabridge::Spectrum, format: SpectrumFormat) -> Result<ID3D11ShaderResourceView> {
+        match format {
+            SpectrumFormat::Rgb32Float => {
+                let table = spectrum.get_rgb_lookup_table(SPECTRUM_RESOLUTION)?;
+                Self::create_lookup_srv(device, format, table.as_ptr() as *const _)
+            }
+            SpectrumFormat::Rgba32Float => {
+                let table = spectrum.get_rgba_lookup_table(SPECTRUM_RESOLUTION)?;
+                Self::create_lookup_srv(device, format, table.as_ptr() as *const _)
+            }
+            SpectrumFormat::Rgba16Float => {
+                let table: Vec<u16> = spectrum.get_rgba_lookup_table(SPECTRUM_RESOLUTION)?.into_iter().map(f32_to_f16).collect();
+                Self::create_lookup_srv(device, format, table.as_ptr() as *const _)
+            }
+        }
+    }
+
+    /// `data` must point to `SPECTRUM_RESOLUTION` texels laid out for `format`.
+    unsafe fn create_lookup_srv(device: &ID3D11Device, format: SpectrumFormat, data: *const std::ffi::c_void) -> Result<ID3D11ShaderResourceView> {
+        let spectrum_desc = D3D11_TEXTURE2D_DESC {
+            Width: SPECTRUM_RESOLUTION as u32,
+            Height: 1,
+            MipLevels: 1,
+            ArraySize: 1,
+            Format: format.dxgi_format(),
+            SampleDesc: DXGI_SAMPLE_DESC { Count: 1, Quality: 0 },
+            Usage: D3D11_USAGE_DEFAULT,
+            BindFlags: D3D11_BIND_SHADER_RESOURCE.0 as u32,
+            CPUAccessFlags: 0,
+            MiscFlags: 0,
+        };
+
+        let init_data = D3D11_SUBRESOURCE_DATA {
+            pSysMem: data,
+            SysMemPitch: SPECTRUM_RESOLUTION as u32 * format.bytes_per_texel(),
+            SysMemSlicePitch: 0,
+        };
+
+        let mut texture: Option<ID3D11Texture2D> = None;
+        device.CreateTexture2D(&spectrum_desc, Some(&init_data), Some(&mut texture))?;
+
+        let mut srv: Option<ID3D11ShaderResourceView> = None;
+        device.CreateShaderResourceView(&texture.unwrap(), None, Some(&mut srv))?;
+        Ok(srv.unwrap())
+    }
+}
+
+#[cfg(windows)]
+unsafe fn create_d3d_device(driver_type: D3D_DRIVER_TYPE) -> Result<(ID3D11Device, ID3D11DeviceContext)> {
+    create_d3d_device_on(None, driver_type)
+}
+
+/// Creates the device the way the overlay does, on `adapter` if given. With an adapter,
+/// `driver_type` has to be `D3D_DRIVER_TYPE_UNKNOWN`.
+#[cfg(windows)]
+pub unsafe fn create_d3d_device_on(adapter: Option<&IDXGIAdapter>, driver_type: D3D_DRIVER_TYPE) -> Result<(ID3D11Device, ID3D11DeviceContext)> {
+    let mut device: Option<ID3D11Device> = None;
+    let mut context: Option<ID3D11DeviceContext> = None;
+
+    let feature_levels = [
+        D3D_FEATURE_LEVEL_11_1,
+        D3D_FEATURE_LEVEL_11_0,
+        D3D_FEATURE_LEVEL_10_1,
+    ];
+
+    D3D11CreateDevice(
+        adapter,
+        driver_type,
+        HMODULE::default(),
+        D3D11_CREATE_DEVICE_BGRA_SUPPORT,
+        Some(&feature_levels),
+        D3D11_SDK_VERSION,
+        Some(&mut device),
+        None,
+        Some(&mut context),
+    )?;
+
+    Ok((device.unwrap(), context.unwrap()))
+}
+
+/// Creates and drops the overlay's composition swap chain on `device`, for the self-check.
+#[cfg(windows)]
+pub unsafe fn probe_swap_chain(device: &ID3D11Device) -> Result<()> {
+    DCompOverlay::create_swap_chain(device, 64, 64, DEFAULT_OUTPUT_FORMAT).map(drop)
+}
 
-                    // FPS from total frame time (including VSync)
-                    let fps = if avg_total_time > 0.0 {
-                        1000.0 / avg_total_time
-                    } else {
-                        0.0
-                    };
-                    *frame_stats.lock() = Some((fps, avg_render_time));
+/// Times `check_focus` creates and destroys the overlay window.
+#[cfg(windows)]
+pub const FOCUS_CHECK_RUNS: usize = 10;
 
-                    // Keep only last 60 frames for rolling average
-                    if frame_times.len() > 60 {
-                        frame_times.drain(0..frame_times.len() - 60);
-                    }
+/// Creates the overlay window and its composition target on `monitor_index` the way starting the
+/// overlay does, destroys them again, `FOCUS_CHECK_RUNS` times, and returns in how many runs the
+/// foreground window changed. Anything but 0 means the overlay can steal focus, unless another app
+/// took it meanwhile; the log tells which.
+#[cfg(windows)]
+pub fn check_focus(monitor_index: usize) -> Result<usize> {
+    // Lets activation that showing the window queued arrive before the foreground is compared
+    const SETTLE: Duration = Duration::from_millis(50);
+
+    unsafe fn drain_messages() {
+        let mut msg = MSG::default();
+        while PeekMessageW(&mut msg, None, 0, 0, PM_REMOVE).as_bool() {
+            let _ = TranslateMessage(&msg);
+            DispatchMessageW(&msg);
+        }
+    }
 
-                    last_stats_update = std::time::Instant::now();
-                }
+    unsafe {
+        let monitor_info = get_monitor_info(monitor_index)?;
+        let mut moved = 0;
+        for _ in 0..FOCUS_CHECK_RUNS {
+            let foreground = GetForegroundWindow();
+            let hwnd = DCompOverlay::create_overlay_window(monitor_info.pos, monitor_info.size, false, true)?;
+            let composition = DCompositionCreateDevice::<_, IDCompositionDevice>(None).and_then(|device| {
+                let target = device.CreateTargetForHwnd(hwnd, true)?;
+                device.Commit()?;
+                Ok((device, target))
+            });
+            thread::sleep(SETTLE);
+            drain_messages();
+            if DCompOverlay::warn_if_foreground_moved(foreground, hwnd) {
+                moved += 1;
             }
 
-            Ok(())
+            // The target goes before its window
+            let composed = composition.map(drop);
+            let _ = DestroyWindow(hwnd);
+            // Also takes the WM_QUIT the window posts when destroyed
+            drain_messages();
+            composed?;
         }
-
-        #[cfg(not(windows))]
-        Ok(())
+        log_info!("Focus check: foreground moved in {} of {} runs", moved, FOCUS_CHECK_RUNS);
+        Ok(moved)
     }
+}
 
-    #[cfg(windows)]
-    unsafe fn prepare_frame(&mut self) -> Result<()> {
-        if let Some(ref mut duplicator) = self.desktop_duplication {
-            if let Some(acquired_texture) = duplicator.acquire_next_frame(0)? {
-                if self.capture_texture.is_none() {
-                    // Create a staging texture that can be used as a shader resource
-                    let texture_desc = D3D11_TEXTURE2D_DESC {
-                        Width: self.width,
-                        Height: self.height,
-                        MipLevels: 1,
-                        ArraySize: 1,
-                        Format: DXGI_FORMAT_B8G8R8A8_UNORM,
-                        SampleDesc: DXGI_SAMPLE_DESC { Count: 1, Quality: 0 },
-                        Usage: D3D11_USAGE_DEFAULT,
-                        BindFlags: D3D11_BIND_SHADER_RESOURCE.0 as u32,
-                        CPUAccessFlags: 0,
-                        MiscFlags: 0,
-                    };
+/// Kernel handles held by the process, shown in the diagnostics so leaks across overlay starts
+/// and stops are visible.
+#[cfg(windows)]
+pub fn process_handle_count() -> Option<u32> {
+    let mut count = 0;
+    unsafe { GetProcessHandleCount(GetCurrentProcess(), &mut count) }.ok().map(|_| count)
+}
 
-                    let mut texture: Option<ID3D11Texture2D> = None;
-                    self.d3d_device.CreateTexture2D(&texture_desc, None, Some(&mut texture))?;
-                    let texture = texture.unwrap();
+/// Compiles every entry point of the embedded shaders, for the self-check. Needs no device.
+#[cfg(windows)]
+pub fn compile_embedded_shaders() -> Result<()> {
+    for (entry_point, target) in RenderPipeline::ENTRY_POINTS {
+        unsafe { RenderPipeline::compile_shader(&RenderPipeline::shader_source(), entry_point, target) }
+            .map_err(|e| e.context(format!("{} failed to compile", entry_point)))?;
+    }
+    Ok(())
+}
 
-                    let mut srv: Option<ID3D11ShaderResourceView> = None;
-                    self.d3d_device.CreateShaderResourceView(&texture, None, Some(&mut srv))?;
+/// Shaders, full-screen quad, samplers and blend state of the correction pass: everything on the
+/// device that doesn't depend on the loaded assets.
+#[cfg(windows)]
+struct RenderPipeline {
+    vertex_shader: ID3D11VertexShader,
+    pixel_shader: ID3D11PixelShader,
+    /// Renders the correction tables for `ColorMath::BakedLut`.
+    bake_shader: ID3D11PixelShader,
+    input_layout: ID3D11InputLayout,
+    vertex_buffer: ID3D11Buffer,
+    sampler_state: ID3D11SamplerState,
+    spectrum_sampler: ID3D11SamplerState,
+    noise_point_sampler: ID3D11SamplerState,
+    blend_state: ID3D11BlendState,
+}
 
-                    self.capture_texture = Some(texture);
-                    self.capture_srv = Some(srv.unwrap());
-                }
+#[cfg(windows)]
+impl RenderPipeline {
+    /// Every entry point in shaders.hlsl with its shader model.
+    const ENTRY_POINTS: [(&'static str, &'static str); 3] = [("VS_Main", "vs_5_0"), ("PS_Main", "ps_5_0"), ("PS_BakeLut", "ps_5_0")];
 
-                if let Some(ref capture_texture) = self.capture_texture {
-                    self.d3d_context.CopyResource(capture_texture, &acquired_texture);
-                }
+    /// shaders.hlsl with the constant buffer declarations of shader_params.rs in place.
+    fn shader_source() -> String {
+        include_str!("shaders.hlsl").replacen(shader_params::CBUFFER_MARKER, shader_params::hlsl_declarations(), 1)
+    }
 
-                duplicator.release_frame()?;
-            }
-        } else if self.capture_texture.is_none() {
-            // Fallback: Create test pattern if desktop duplication is not available
-            let mut test_pixels = vec![0u8; (self.width * self.height * 4) as usize];
-            for y in 0..self.height {
-                for x in 0..self.width {
-                    let idx = ((y * self.width + x) * 4) as usize;
-                    let r = ((x as f32 / self.width as f32) * 255.0) as u8;
-                    let g = ((y as f32 / self.height as f32) * 255.0) as u8;
-                    let b = 128u8;
-                    test_pixels[idx] = b;
-                    test_pixels[idx + 1] = g;
-                    test_pixels[idx + 2] = r;
-                    test_pixels[idx + 3] = 255;
-                }
-            }
+    unsafe fn new(device: &ID3D11Device) -> Result<Self> {
+        let (vertex_shader, pixel_shader, input_layout, vertex_buffer) = Self::init_rendering_pipeline(device)?;
+        let bake_shader = Self::create_pixel_shader(device, "PS_BakeLut")?;
+        let (sampler_state, spectrum_sampler, noise_point_sampler, blend_state) = Self::create_render_states(device)?;
+        Ok(Self {
+            vertex_shader,
+            pixel_shader,
+            bake_shader,
+            input_layout,
+            vertex_buffer,
+            sampler_state,
+            spectrum_sampler,
+            noise_point_sampler,
+            blend_state,
+        })
+    }
 
-            let texture_desc = D3D11_TEXTURE2D_DESC {
-                Width: self.width,
-                Height: self.height,
-                MipLevels: 1,
-                ArraySize: 1,
-                Format: DXGI_FORMAT_B8G8R8A8_UNORM,
-                SampleDesc: DXGI_SAMPLE_DESC { Count: 1, Quality: 0 },
-                Usage: D3D11_USAGE_DYNAMIC,
-                BindFlags: D3D11_BIND_SHADER_RESOURCE.0 as u32,
-                CPUAccessFlags: D3D11_CPU_ACCESS_WRITE.0 as u32,
-                MiscFlags: 0,
-            };
+    /// Draws the correction pass into the bound render target; `srvs` come from `shader_resources`.
+    unsafe fn draw(&self, context: &ID3D11DeviceContext, srvs: &[Option<ID3D11ShaderResourceView>], constant_buffer: &ID3D11Buffer, size: (u32, u32)) {
+        self.draw_with(&self.pixel_shader, context, srvs, constant_buffer, size);
+    }
 
-            let texture_data = D3D11_SUBRESOURCE_DATA {
-                pSysMem: test_pixels.as_ptr() as *const _,
-                SysMemPitch: self.width * 4,
-                SysMemSlicePitch: 0,
-            };
+    /// Bakes the correction of each of `spectra` with the strength and hue gate of `state` into a
+    /// `LUT_SIZE`³ table, one blue slice per draw. The tables are in the order of `spectra`.
+    unsafe fn bake_luts(&self, device: &ID3D11Device, context: &ID3D11DeviceContext, state: &OverlayState, spectra: &[ID3D11ShaderResourceView]) -> Result<Vec<ID3D11ShaderResourceView>> {
+        let mut params = spectrum_params(state, false, (LUT_SIZE, LUT_SIZE), false, 0, &[], false);
+        let constant_buffer = create_constant_buffer(device, &params)?;
+        let srvs = shader_resources(None, spectra, None, None, &[]);
+
+        // Half floats keep the lattice well within 1/255 of the shader math
+        let texture_desc = D3D11_TEXTURE3D_DESC {
+            Width: LUT_SIZE,
+            Height: LUT_SIZE,
+            Depth: LUT_SIZE,
+            MipLevels: 1,
+            Format: DXGI_FORMAT_R16G16B16A16_FLOAT,
+            Usage: D3D11_USAGE_DEFAULT,
+            BindFlags: (D3D11_BIND_RENDER_TARGET.0 | D3D11_BIND_SHADER_RESOURCE.0) as u32,
+            CPUAccessFlags: 0,
+            MiscFlags: 0,
+        };
 
-            let mut texture: Option<ID3D11Texture2D> = None;
-            self.d3d_device.CreateTexture2D(&texture_desc, Some(&texture_data), Some(&mut texture))?;
+        let mut luts = Vec::with_capacity(spectra.len());
+        for index in 0..spectra.len() {
+            let mut texture: Option<ID3D11Texture3D> = None;
+            device.CreateTexture3D(&texture_desc, None, Some(&mut texture))?;
             let texture = texture.unwrap();
 
-            let mut srv: Option<ID3D11ShaderResourceView> = None;
-            self.d3d_device.CreateShaderResourceView(&texture, None, Some(&mut srv))?;
+            for slice in 0..LUT_SIZE {
+                let rtv_desc = D3D11_RENDER_TARGET_VIEW_DESC {
+                    Format: texture_desc.Format,
+                    ViewDimension: D3D11_RTV_DIMENSION_TEXTURE3D,
+                    Anonymous: D3D11_RENDER_TARGET_VIEW_DESC_0 {
+                        Texture3D: D3D11_TEX3D_RTV { MipSlice: 0, FirstWSlice: slice, WSize: 1 },
+                    },
+                };
+                let mut rtv: Option<ID3D11RenderTargetView> = None;
+                device.CreateRenderTargetView(&texture, Some(&rtv_desc), Some(&mut rtv))?;
+
+                params.bake_spectrum = index as i32;
+                params.bake_slice = slice as f32;
+                write_constant_buffer(context, &constant_buffer, &params)?;
+                context.OMSetRenderTargets(Some(&[rtv]), None);
+                self.draw_with(&self.bake_shader, context, &srvs, &constant_buffer, (LUT_SIZE, LUT_SIZE));
+            }
 
-            self.capture_texture = Some(texture);
-            self.capture_srv = Some(srv.unwrap());
+            let mut srv: Option<ID3D11ShaderResourceView> = None;
+            device.CreateShaderResourceView(&texture, None, Some(&mut srv))?;
+            luts.push(srv.unwrap());
         }
 
-        let back_buffer: ID3D11Texture2D = self.swap_chain.GetBuffer(0)?;
-        let mut rtv: Option<ID3D11RenderTargetView> = None;
-        self.d3d_device.CreateRenderTargetView(&back_buffer, None, Some(&mut rtv))?;
-        let rtv = rtv.unwrap();
-
-        let clear_color = [0.0f32, 0.0, 0.0, 0.0];
-        self.d3d_context.ClearRenderTargetView(&rtv, &clear_color);
-
-        self.d3d_context.OMSetRenderTargets(Some(&[Some(rtv.clone())]), None);
+        // Still bound as a render target, the last table would be unbound from the correction pass
+        context.OMSetRenderTargets(None, None);
+        Ok(luts)
+    }
 
+    unsafe fn draw_with(&self, pixel_shader: &ID3D11PixelShader, context: &ID3D11DeviceContext, srvs: &[Option<ID3D11ShaderResourceView>], constant_buffer: &ID3D11Buffer, size: (u32, u32)) {
         let viewport = D3D11_VIEWPORT {
             TopLeftX: 0.0,
             TopLeftY: 0.0,
-            Width: self.width as f32,
-            Height: self.height as f32,
+            Width: size.0 as f32,
+            Height: size.1 as f32,
             MinDepth: 0.0,
             MaxDepth: 1.0,
         };
-        self.d3d_context.RSSetViewports(Some(&[viewport]));
+        context.RSSetViewports(Some(&[viewport]));
 
-        self.d3d_context.VSSetShader(&self.vertex_shader, None);
-        self.d3d_context.PSSetShader(&self.pixel_shader, None);
-        self.d3d_context.IASetInputLayout(&self.input_layout);
-        self.d3d_context.IASetPrimitiveTopology(windows::Win32::Graphics::Direct3D::D3D_PRIMITIVE_TOPOLOGY_TRIANGLELIST);
+        context.VSSetShader(&self.vertex_shader, None);
+        context.PSSetShader(pixel_shader, None);
+        context.IASetInputLayout(&self.input_layout);
+        context.IASetPrimitiveTopology(D3D_PRIMITIVE_TOPOLOGY_TRIANGLELIST);
 
         let stride = std::mem::size_of::<f32>() as u32 * 4;
         let offset = 0u32;
-        self.d3d_context.IASetVertexBuffers(0, 1, Some(&Some(self.vertex_buffer.clone())), Some(&stride), Some(&offset));
-
-        let mut srvs: Vec<Option<ID3D11ShaderResourceView>> = vec![None; 4];
-        if let Some(ref srv) = self.capture_srv {
-            srvs[0] = Some(srv.clone());
-        }
-        srvs[1] = Some(self.spectrum1_srv.clone());
-        if let Some(ref srv) = self.spectrum2_srv {
-            srvs[2] = Some(srv.clone());
-        }
-        if let Some(ref srv) = self.noise_srv {
-            srvs[3] = Some(srv.clone());
-        }
+        context.IASetVertexBuffers(0, 1, Some(&Some(self.vertex_buffer.clone())), Some(&stride), Some(&offset));
 
-        self.d3d_context.PSSetShaderResources(0, Some(&srvs));
-        self.d3d_context.PSSetSamplers(0, Some(&[Some(self.sampler_state.clone())]));
-        self.d3d_context.PSSetSamplers(1, Some(&[Some(self.spectrum_sampler.clone())]));
-
-        // Update constant buffer with current strength every frame
-        self.update_constant_buffer()?;
-        self.d3d_context.PSSetConstantBuffers(0, Some(&[Some(self.constant_buffer.clone())]));
+        context.PSSetShaderResources(0, Some(srvs));
+        context.PSSetSamplers(0, Some(&[Some(self.sampler_state.clone())]));
+        context.PSSetSamplers(1, Some(&[Some(self.spectrum_sampler.clone())]));
+        context.PSSetSamplers(2, Some(&[Some(self.noise_point_sampler.clone())]));
+        context.PSSetConstantBuffers(0, Some(&[Some(constant_buffer.clone())]));
 
         let blend_factor = [1.0f32, 1.0, 1.0, 1.0];
-        self.d3d_context.OMSetBlendState(Some(&self.blend_state), Some(&blend_factor), 0xffffffff);
-
-        self.d3d_context.Draw(6, 0);
-
-        Ok(())
-    }
-
-    #[cfg(windows)]
-    unsafe fn present_frame(&mut self) -> Result<()> {
-        // No vsync - immediate present
-        self.swap_chain.Present(0, DXGI_PRESENT(0)).ok()?;
-        Ok(())
-    }
-
-    #[cfg(windows)]
-    unsafe fn update_constant_buffer(&mut self) -> Result<()> {
-        #[repr(C)]
-        struct SpectrumParams {
-            strength: f32,
-            use_dual_spectrum: i32,
-            use_noise_texture: i32,
-            padding: f32,
-        }
-
-        let state_read = self.overlay_state.read();
-        let params = SpectrumParams {
-            strength: state_read.hue_mapper.strength,
-            use_dual_spectrum: if state_read.spectrum_pair.has_dual_spectrum() { 1 } else { 0 },
-            use_noise_texture: if state_read.noise_texture.is_some() { 1 } else { 0 },
-            padding: 0.0,
-        };
-
-        let mut mapped: D3D11_MAPPED_SUBRESOURCE = std::mem::zeroed();
-        self.d3d_context.Map(
-            &self.constant_buffer,
-            0,
-            D3D11_MAP_WRITE_DISCARD,
-            0,
-            Some(&mut mapped),
-        )?;
+        context.OMSetBlendState(Some(&self.blend_state), Some(&blend_factor), 0xffffffff);
 
-        std::ptr::copy_nonoverlapping(
-            &params as *const _ as *const u8,
-            mapped.pData as *mut u8,
-            std::mem::size_of::<SpectrumParams>(),
-        );
-
-        self.d3d_context.Unmap(&self.constant_buffer, 0);
-        Ok(())
+        context.Draw(6, 0);
     }
 
     unsafe fn init_rendering_pipeline(device: &ID3D11Device) -> Result<(ID3D11VertexShader, ID3D11PixelShader, ID3D11InputLayout, ID3D11Buffer)> {
-        const SHADER_SOURCE: &str = include_str!("shaders.hlsl");
-
-        let vs_blob = Self::compile_shader(SHADER_SOURCE, "VS_Main", "vs_5_0")?;
+        let vs_blob = Self::compile_shader(&Self::shader_source(), "VS_Main", "vs_5_0")?;
         let mut vertex_shader: Option<ID3D11VertexShader> = None;
         device.CreateVertexShader(
             std::slice::from_raw_parts(
@@ -849,16 +3606,7 @@ impl DCompOverlay {
             Some(&mut vertex_shader),
         )?;
 
-        let ps_blob = Self::compile_shader(SHADER_SOURCE, "PS_Main", "ps_5_0")?;
-        let mut pixel_shader: Option<ID3D11PixelShader> = None;
-        device.CreatePixelShader(
-            std::slice::from_raw_parts(
-                ps_blob.GetBufferPointer() as *const u8,
-                ps_blob.GetBufferSize(),
-            ),
-            None,
-            Some(&mut pixel_shader),
-        )?;
+        let pixel_shader = Self::create_pixel_shader(device, "PS_Main")?;
 
         use windows::core::s;
         let input_elements = [
@@ -925,7 +3673,62 @@ impl DCompOverlay {
         let mut vertex_buffer: Option<ID3D11Buffer> = None;
         device.CreateBuffer(&buffer_desc, Some(&vertex_data), Some(&mut vertex_buffer))?;
 
-        Ok((vertex_shader.unwrap(), pixel_shader.unwrap(), input_layout.unwrap(), vertex_buffer.unwrap()))
+        Ok((vertex_shader.unwrap(), pixel_shader, input_layout.unwrap(), vertex_buffer.unwrap()))
+    }
+
+    unsafe fn create_pixel_shader(device: &ID3D11Device, entry_point: &str) -> Result<ID3D11PixelShader> {
+        let ps_blob = Self::compile_shader(&Self::shader_source(), entry_point, "ps_5_0")?;
+        Self::check_params_layout(&ps_blob).map_err(|e| e.context(format!("{} constant buffer", entry_point)))?;
+        let mut pixel_shader: Option<ID3D11PixelShader> = None;
+        device.CreatePixelShader(
+            std::slice::from_raw_parts(
+                ps_blob.GetBufferPointer() as *const u8,
+                ps_blob.GetBufferSize(),
+            ),
+            None,
+            Some(&mut pixel_shader),
+        )?;
+        Ok(pixel_shader.unwrap())
+    }
+
+    /// Compares the `SpectrumParams` layout the shader compiler chose for `blob` with the Rust
+    /// struct. The struct is checked against HLSL's packing rules at build time; this catches
+    /// whatever that check gets wrong before the overlay draws with garbage parameters.
+    unsafe fn check_params_layout(blob: &ID3DBlob) -> Result<()> {
+        use windows::Win32::Graphics::Direct3D::Fxc::D3DReflect;
+
+        let mut reflector = std::ptr::null_mut();
+        D3DReflect(blob.GetBufferPointer(), blob.GetBufferSize(), &ID3D11ShaderReflection::IID, &mut reflector)?;
+        let reflection = ID3D11ShaderReflection::from_raw(reflector);
+
+        let buffer = reflection.GetConstantBufferByName(s!("SpectrumParams"))
+            .ok_or_else(|| anyhow::anyhow!("SpectrumParams constant buffer not found"))?;
+        let mut buffer_desc = D3D11_SHADER_BUFFER_DESC::default();
+        buffer.GetDesc(&mut buffer_desc)?;
+        anyhow::ensure!(
+            buffer_desc.Size as usize == std::mem::size_of::<SpectrumParams>(),
+            "SpectrumParams is {} bytes in the shader but {} in Rust",
+            buffer_desc.Size,
+            std::mem::size_of::<SpectrumParams>()
+        );
+
+        for field in SpectrumParams::FIELDS {
+            let name = std::ffi::CString::new(field.name)?;
+            let variable = buffer.GetVariableByName(PCSTR(name.as_ptr() as *const u8))
+                .ok_or_else(|| anyhow::anyhow!("{} not found in the shader", field.name))?;
+            let mut variable_desc = D3D11_SHADER_VARIABLE_DESC::default();
+            variable.GetDesc(&mut variable_desc)?;
+            anyhow::ensure!(
+                (variable_desc.StartOffset as usize, variable_desc.Size as usize) == (field.offset, field.size),
+                "{} is at {}+{} bytes in the shader but {}+{} in Rust",
+                field.name,
+                variable_desc.StartOffset,
+                variable_desc.Size,
+                field.offset,
+                field.size
+            );
+        }
+        Ok(())
     }
 
     unsafe fn compile_shader(source: &str, entry_point: &str, target: &str) -> Result<windows::Win32::Graphics::Direct3D::ID3DBlob> {
@@ -968,7 +3771,7 @@ impl DCompOverlay {
         Ok(blob.unwrap())
     }
 
-    unsafe fn create_render_states(device: &ID3D11Device) -> Result<(ID3D11SamplerState, ID3D11SamplerState, ID3D11BlendState)> {
+    unsafe fn create_render_states(device: &ID3D11Device) -> Result<(ID3D11SamplerState, ID3D11SamplerState, ID3D11SamplerState, ID3D11BlendState)> {
         let sampler_desc = D3D11_SAMPLER_DESC {
             Filter: D3D11_FILTER_MIN_MAG_MIP_LINEAR,
             AddressU: D3D11_TEXTURE_ADDRESS_CLAMP,
@@ -988,6 +3791,17 @@ impl DCompOverlay {
         let mut spectrum_sampler: Option<ID3D11SamplerState> = None;
         device.CreateSamplerState(&sampler_desc, Some(&mut spectrum_sampler))?;
 
+        // Pixel-exact noise mapping must hit texel centers without blending neighbours
+        let noise_point_desc = D3D11_SAMPLER_DESC {
+            Filter: D3D11_FILTER_MIN_MAG_MIP_POINT,
+            AddressU: D3D11_TEXTURE_ADDRESS_WRAP,
+            AddressV: D3D11_TEXTURE_ADDRESS_WRAP,
+            AddressW: D3D11_TEXTURE_ADDRESS_WRAP,
+            ..sampler_desc
+        };
+        let mut noise_point_sampler: Option<ID3D11SamplerState> = None;
+        device.CreateSamplerState(&noise_point_desc, Some(&mut noise_point_sampler))?;
+
         let blend_desc = D3D11_BLEND_DESC {
             AlphaToCoverageEnable: false.into(),
             IndependentBlendEnable: false.into(),
@@ -1015,141 +3829,485 @@ impl DCompOverlay {
         let mut blend_state: Option<ID3D11BlendState> = None;
         device.CreateBlendState(&blend_desc, Some(&mut blend_state))?;
 
-        Ok((sampler_state.unwrap(), spectrum_sampler.unwrap(), blend_state.unwrap()))
+        Ok((sampler_state.unwrap(), spectrum_sampler.unwrap(), noise_point_sampler.unwrap(), blend_state.unwrap()))
     }
+}
 
-    unsafe fn init_spectrum_textures(device: &ID3D11Device, state: &Arc<RwLock<OverlayState>>) -> Result<(ID3D11ShaderResourceView, Option<ID3D11ShaderResourceView>, Option<ID3D11ShaderResourceView>, ID3D11Buffer)> {
-        const SPECTRUM_RESOLUTION: usize = 360;
+/// The correction pass inputs in their `register(tN)` slots from shaders.hlsl.
+#[cfg(windows)]
+fn shader_resources(
+    capture: Option<&ID3D11ShaderResourceView>,
+    spectra: &[ID3D11ShaderResourceView],
+    noise: Option<&ID3D11ShaderResourceView>,
+    history: Option<&ID3D11ShaderResourceView>,
+    luts: &[ID3D11ShaderResourceView],
+) -> Vec<Option<ID3D11ShaderResourceView>> {
+    let mut srvs: Vec<Option<ID3D11ShaderResourceView>> = vec![None; 11];
+    srvs[0] = capture.cloned();
+    // Spectra 1 and 2 sit in t1 and t2, 3 and 4 after the history in t5 and t6
+    for (slot, srv) in [1, 2, 5, 6].into_iter().zip(spectra) {
+        srvs[slot] = Some(srv.clone());
+    }
+    srvs[3] = noise.cloned();
+    srvs[4] = history.cloned();
+    // The baked tables follow in t7 to t10, in the order of the spectra
+    for (slot, srv) in (7..).zip(luts) {
+        srvs[slot] = Some(srv.clone());
+    }
+    srvs
+}
 
-        let state_read = state.read();
+/// A `SpectrumParams` buffer for the shaders of a `RenderPipeline`. Sized from the Rust struct;
+/// `RenderPipeline::new` already failed if the compiled shaders disagree, see
+/// `check_params_layout`, so create the pipeline before any of these.
+#[cfg(windows)]
+unsafe fn create_constant_buffer(device: &ID3D11Device, params: &SpectrumParams) -> Result<ID3D11Buffer> {
+    let cb_desc = D3D11_BUFFER_DESC {
+        ByteWidth: std::mem::size_of::<SpectrumParams>() as u32,
+        Usage: D3D11_USAGE_DYNAMIC,
+        BindFlags: D3D11_BIND_CONSTANT_BUFFER.0 as u32,
+        CPUAccessFlags: D3D11_CPU_ACCESS_WRITE.0 as u32,
+        MiscFlags: 0,
+        StructureByteStride: 0,
+    };
+
+    let cb_init_data = D3D11_SUBRESOURCE_DATA {
+        pSysMem: params as *const _ as *const _,
+        SysMemPitch: 0,
+        SysMemSlicePitch: 0,
+    };
+
+    let mut constant_buffer: Option<ID3D11Buffer> = None;
+    device.CreateBuffer(&cb_desc, Some(&cb_init_data), Some(&mut constant_buffer))?;
+    Ok(constant_buffer.unwrap())
+}
 
-        let spectrum1_data = state_read.spectrum_pair.spectrum1.get_rgb_lookup_table(SPECTRUM_RESOLUTION)?;
+#[cfg(windows)]
+unsafe fn write_constant_buffer(context: &ID3D11DeviceContext, constant_buffer: &ID3D11Buffer, params: &SpectrumParams) -> Result<()> {
+    let mut mapped: D3D11_MAPPED_SUBRESOURCE = std::mem::zeroed();
+    context.Map(
+        constant_buffer,
+        0,
+        D3D11_MAP_WRITE_DISCARD,
+        0,
+        Some(&mut mapped),
+    )?;
+
+    std::ptr::copy_nonoverlapping(
+        params as *const _ as *const u8,
+        mapped.pData as *mut u8,
+        std::mem::size_of::<SpectrumParams>(),
+    );
 
-        let spectrum_desc = D3D11_TEXTURE2D_DESC {
-            Width: SPECTRUM_RESOLUTION as u32,
-            Height: 1,
-            MipLevels: 1,
-            ArraySize: 1,
-            Format: DXGI_FORMAT_R32G32B32_FLOAT,
-            SampleDesc: DXGI_SAMPLE_DESC { Count: 1, Quality: 0 },
-            Usage: D3D11_USAGE_DEFAULT,
-            BindFlags: D3D11_BIND_SHADER_RESOURCE.0 as u32,
-            CPUAccessFlags: 0,
-            MiscFlags: 0,
-        };
+    context.Unmap(constant_buffer, 0);
+    Ok(())
+}
 
-        let spectrum1_init_data = D3D11_SUBRESOURCE_DATA {
-            pSysMem: spectrum1_data.as_ptr() as *const _,
-            SysMemPitch: (SPECTRUM_RESOLUTION * 3 * std::mem::size_of::<f32>()) as u32,
-            SysMemSlicePitch: 0,
-        };
+/// An immutable correction pass input from tightly packed BGRA rows.
+#[cfg(windows)]
+unsafe fn create_image_texture(device: &ID3D11Device, bgra: &[u8], width: u32, height: u32) -> Result<(ID3D11Texture2D, ID3D11ShaderResourceView)> {
+    let texture_desc = D3D11_TEXTURE2D_DESC {
+        Width: width,
+        Height: height,
+        MipLevels: 1,
+        ArraySize: 1,
+        Format: DXGI_FORMAT_B8G8R8A8_UNORM,
+        SampleDesc: DXGI_SAMPLE_DESC { Count: 1, Quality: 0 },
+        Usage: D3D11_USAGE_IMMUTABLE,
+        BindFlags: D3D11_BIND_SHADER_RESOURCE.0 as u32,
+        CPUAccessFlags: 0,
+        MiscFlags: 0,
+    };
+
+    let texture_data = D3D11_SUBRESOURCE_DATA {
+        pSysMem: bgra.as_ptr() as *const _,
+        SysMemPitch: width * 4,
+        SysMemSlicePitch: 0,
+    };
+
+    let mut texture: Option<ID3D11Texture2D> = None;
+    device.CreateTexture2D(&texture_desc, Some(&texture_data), Some(&mut texture))?;
+    let texture = texture.unwrap();
+
+    let mut srv: Option<ID3D11ShaderResourceView> = None;
+    device.CreateShaderResourceView(&texture, None, Some(&mut srv))?;
+    Ok((texture, srv.unwrap()))
+}
 
-        let mut spectrum1_texture: Option<ID3D11Texture2D> = None;
-        device.CreateTexture2D(&spectrum_desc, Some(&spectrum1_init_data), Some(&mut spectrum1_texture))?;
+/// The overlay state the tests render with.
+#[cfg(all(windows, test))]
+fn test_overlay_state(spectrum_pair: SpectrumPair, noise_texture: Option<NoiseTexture>, strength: f32, strength_curve: StrengthCurve, color_math: ColorMath) -> OverlayState {
+    OverlayState {
+        spectrum_pair,
+        spectrum_generation: 0,
+        noise_texture,
+        noise_mapping: NoiseMapping::Stretch,
+        noise_intensity: 1.0,
+        noise_target: NoiseTarget::BetweenSpectra,
+        blend_ratio: 0.0,
+        hue_mapper: HueMapper::new(strength),
+        strength_curve,
+        monitor_refresh_rate: 60.0,
+        throttle: OverlayThrottle::Full,
+        posterize_levels: None,
+        temporal_smoothing: 0.0,
+        hue_gate: None,
+        topmost_mode: TopmostMode::default(),
+        test_pattern: TestPattern::default(),
+        solid_color: [0; 3],
+        correct_cursor: false,
+        exclude_shell: false,
+        hide_from_capture: true,
+        target_window: None,
+        color_math,
+        debug_visibility: DebugVisibility::Normal,
+    }
+}
 
-        let mut spectrum1_srv: Option<ID3D11ShaderResourceView> = None;
-        device.CreateShaderResourceView(&spectrum1_texture.unwrap(), None, Some(&mut spectrum1_srv))?;
+/// Runs `input` once through the correction pass on the WARP software rasterizer, with the same
+/// pipeline, lookup textures and constant buffer setup as the overlay, and reads the result back.
+/// Needs no GPU, so the pipeline tests run on any Windows machine.
+#[cfg(all(windows, test))]
+unsafe fn render_offscreen(spectrum_pair: SpectrumPair, noise_texture: Option<NoiseTexture>, strength: f32, strength_curve: StrengthCurve, color_math: ColorMath, input: &image::RgbaImage) -> Result<image::RgbaImage> {
+    let (width, height) = input.dimensions();
+    let (device, context) = create_d3d_device(D3D_DRIVER_TYPE_WARP)?;
+    let pipeline = RenderPipeline::new(&device)?;
+
+    let state = Arc::new(RwLock::new(test_overlay_state(spectrum_pair, noise_texture, strength, strength_curve, color_math)));
+    let spectrum_format = DCompOverlay::select_spectrum_format(&device)?;
+    let (spectrum_srvs, noise_srv, constant_buffer) = DCompOverlay::init_spectrum_textures(&device, &state, (width, height), spectrum_format)?;
+    let lut_srvs = match color_math {
+        ColorMath::Shader => Vec::new(),
+        ColorMath::BakedLut => {
+            let luts = pipeline.bake_luts(&device, &context, &state.read(), &spectrum_srvs)?;
+            let params = spectrum_params(&state.read(), false, (width, height), false, 0, &[], true);
+            write_constant_buffer(&context, &constant_buffer, &params)?;
+            luts
+        }
+    };
+
+    let bgra: Vec<u8> = input.pixels().flat_map(|p| [p[2], p[1], p[0], p[3]]).collect();
+    let (_input_texture, input_srv) = create_image_texture(&device, &bgra, width, height)?;
+
+    let target_desc = D3D11_TEXTURE2D_DESC {
+        Width: width,
+        Height: height,
+        MipLevels: 1,
+        ArraySize: 1,
+        Format: DXGI_FORMAT_B8G8R8A8_UNORM,
+        SampleDesc: DXGI_SAMPLE_DESC { Count: 1, Quality: 0 },
+        Usage: D3D11_USAGE_DEFAULT,
+        BindFlags: D3D11_BIND_RENDER_TARGET.0 as u32,
+        CPUAccessFlags: 0,
+        MiscFlags: 0,
+    };
+    let mut target: Option<ID3D11Texture2D> = None;
+    device.CreateTexture2D(&target_desc, None, Some(&mut target))?;
+    let target = target.unwrap();
+
+    let mut rtv: Option<ID3D11RenderTargetView> = None;
+    device.CreateRenderTargetView(&target, None, Some(&mut rtv))?;
+    context.ClearRenderTargetView(rtv.as_ref().unwrap(), &[0.0f32; 4]);
+    context.OMSetRenderTargets(Some(&[rtv]), None);
+
+    let srvs = shader_resources(Some(&input_srv), &spectrum_srvs, noise_srv.as_ref(), None, &lut_srvs);
+    pipeline.draw(&context, &srvs, &constant_buffer, (width, height));
+
+    read_texture(&device, &context, &target)
+}
 
-        let spectrum2_srv = if let Some(ref spectrum2) = state_read.spectrum_pair.spectrum2 {
-            let spectrum2_data = spectrum2.get_rgb_lookup_table(SPECTRUM_RESOLUTION)?;
-            let spectrum2_init_data = D3D11_SUBRESOURCE_DATA {
-                pSysMem: spectrum2_data.as_ptr() as *const _,
-                SysMemPitch: (SPECTRUM_RESOLUTION * 3 * std::mem::size_of::<f32>()) as u32,
-                SysMemSlicePitch: 0,
-            };
+/// Copies a B8G8R8A8 or R10G10B10A2 texture back to the CPU through a staging texture, at 8
+/// bits per channel. Waits for the GPU to finish drawing it.
+#[cfg(windows)]
+unsafe fn read_texture(device: &ID3D11Device, context: &ID3D11DeviceContext, texture: &ID3D11Texture2D) -> Result<image::RgbaImage> {
+    let mut desc = D3D11_TEXTURE2D_DESC::default();
+    texture.GetDesc(&mut desc);
+    let staging = create_staging_texture(device, &desc)?;
+    context.CopyResource(&staging, texture);
+
+    let mut mapped: D3D11_MAPPED_SUBRESOURCE = std::mem::zeroed();
+    context.Map(&staging, 0, D3D11_MAP_READ, 0, Some(&mut mapped))?;
+    let image = mapped_image(&mapped, &desc, u32::MAX);
+    context.Unmap(&staging, 0);
+    image
+}
 
-            let mut spectrum2_texture: Option<ID3D11Texture2D> = None;
-            device.CreateTexture2D(&spectrum_desc, Some(&spectrum2_init_data), Some(&mut spectrum2_texture))?;
+/// A CPU-readable texture of the size and format of `desc`, to copy a texture into.
+#[cfg(windows)]
+unsafe fn create_staging_texture(device: &ID3D11Device, desc: &D3D11_TEXTURE2D_DESC) -> Result<ID3D11Texture2D> {
+    let staging_desc = D3D11_TEXTURE2D_DESC {
+        Usage: D3D11_USAGE_STAGING,
+        BindFlags: 0,
+        CPUAccessFlags: D3D11_CPU_ACCESS_READ.0 as u32,
+        MiscFlags: 0,
+        ..*desc
+    };
+    let mut staging: Option<ID3D11Texture2D> = None;
+    device.CreateTexture2D(&staging_desc, None, Some(&mut staging))?;
+    Ok(staging.unwrap())
+}
 
-            let mut srv: Option<ID3D11ShaderResourceView> = None;
-            device.CreateShaderResourceView(&spectrum2_texture.unwrap(), None, Some(&mut srv))?;
-            Some(srv.unwrap())
-        } else {
-            None
-        };
+/// The pixels of a mapped texture described by `desc`, at most `max_width` wide by keeping
+/// every nth pixel of every nth row. Only the kept pixels are converted, so it is cheap enough
+/// to run between frames.
+#[cfg(windows)]
+unsafe fn mapped_image(mapped: &D3D11_MAPPED_SUBRESOURCE, desc: &D3D11_TEXTURE2D_DESC, max_width: u32) -> Result<image::RgbaImage> {
+    let (width, height) = (desc.Width, desc.Height);
+    let layout = pixel_layout(desc.Format).ok_or_else(|| anyhow::anyhow!("Can't read back {} textures", format_name(desc.Format)))?;
+    let rows = std::slice::from_raw_parts(mapped.pData as *const u8, (mapped.RowPitch * height) as usize);
+    let step = width.div_ceil(max_width.max(1)).max(1);
+    let output = image::RgbaImage::from_fn(width.div_ceil(step), height.div_ceil(step), |x, y| {
+        let (x, y) = (x * step, y * step);
+        let idx = (y * mapped.RowPitch + x * 4) as usize;
+        let pixel = &rows[idx..idx + 4];
+        match layout {
+            PixelLayout::Bgra8 => image::Rgba([pixel[2], pixel[1], pixel[0], pixel[3]]),
+            PixelLayout::Rgb10A2 => {
+                let packed = u32::from_le_bytes([pixel[0], pixel[1], pixel[2], pixel[3]]);
+                let channel = |shift: u32| (((packed >> shift & 0x3FF) * 255 + 511) / 1023) as u8;
+                image::Rgba([channel(0), channel(10), channel(20), ((packed >> 30) * 85) as u8])
+            }
+        }
+    });
 
-        let noise_srv = if let Some(ref noise_texture) = state_read.noise_texture {
-            let noise_width = noise_texture.width();
-            let noise_height = noise_texture.height();
+    Ok(output)
+}
 
-            let mut noise_data: Vec<u8> = Vec::with_capacity((noise_width * noise_height) as usize);
-            for y in 0..noise_height {
-                for x in 0..noise_width {
-                    let value = if noise_texture.sample(x, y, noise_width, noise_height) {
-                        255u8
-                    } else {
-                        0u8
-                    };
-                    noise_data.push(value);
+#[cfg(all(test, windows))]
+mod tests {
+    use super::*;
+    use chromabridge::{SpectrumFile, SpectrumSelection};
+    use image::{DynamicImage, GrayImage, Luma, Rgba, RgbaImage};
+
+    // Linear blends between the primaries and secondaries keep the hue, so this maps every hue onto itself
+    const IDENTITY: &str = r##"{"nodes": [
+        {"color": "#FF0000", "position": 0.0},
+        {"color": "#FFFF00", "position": 0.16666667},
+        {"color": "#00FF00", "position": 0.33333334},
+        {"color": "#00FFFF", "position": 0.5},
+        {"color": "#0000FF", "position": 0.6666667},
+        {"color": "#FF00FF", "position": 0.8333333},
+        {"color": "#FF0000", "position": 1.0}
+    ]}"##;
+    const ALL_GREEN: &str = r##"{"nodes": [{"color": "#00FF00", "position": 0.0}]}"##;
+    const ALL_BLUE: &str = r##"{"nodes": [{"color": "#0000FF", "position": 0.0}]}"##;
+
+    const RED: Rgba<u8> = Rgba([255, 0, 0, 255]);
+    const GREEN: Rgba<u8> = Rgba([0, 255, 0, 255]);
+    const BLUE: Rgba<u8> = Rgba([0, 0, 255, 255]);
+
+    fn pair(spectra: &[&str]) -> SpectrumPair {
+        let file: SpectrumFile = serde_json::from_str(&format!(r#"{{"spectra": [{}]}}"#, spectra.join(","))).unwrap();
+        file.validate().unwrap();
+        SpectrumPair::from_file(file, SpectrumSelection::default())
+    }
+
+    fn swatches() -> RgbaImage {
+        let colors = [[255, 0, 0], [255, 128, 0], [128, 255, 64], [0, 200, 200], [40, 40, 220], [150, 60, 200], [128, 128, 128], [255, 190, 200]];
+        RgbaImage::from_fn(colors.len() as u32, 1, |x, _| {
+            let [r, g, b] = colors[x as usize];
+            Rgba([r, g, b, 255])
+        })
+    }
+
+    /// Fully saturated, fully bright colors around the hue circle. Only one channel falls between
+    /// lattice points there, along which the baked tables interpolate without error.
+    fn hue_ring() -> RgbaImage {
+        RgbaImage::from_fn(6 * 16, 1, |x, _| {
+            let ramp = (x % 16 * 17) as u8;
+            match x / 16 {
+                0 => Rgba([255, ramp, 0, 255]),
+                1 => Rgba([255 - ramp, 255, 0, 255]),
+                2 => Rgba([0, 255, ramp, 255]),
+                3 => Rgba([0, 255 - ramp, 255, 255]),
+                4 => Rgba([ramp, 0, 255, 255]),
+                _ => Rgba([255, 0, 255 - ramp, 255]),
+            }
+        })
+    }
+
+    fn render(spectrum_pair: SpectrumPair, noise_texture: Option<NoiseTexture>, strength: f32, input: &RgbaImage) -> RgbaImage {
+        render_with(spectrum_pair, noise_texture, strength, ColorMath::Shader, input)
+    }
+
+    fn render_with(spectrum_pair: SpectrumPair, noise_texture: Option<NoiseTexture>, strength: f32, color_math: ColorMath, input: &RgbaImage) -> RgbaImage {
+        render_curved(spectrum_pair, noise_texture, strength, StrengthCurve::Linear, color_math, input)
+    }
+
+    fn render_curved(spectrum_pair: SpectrumPair, noise_texture: Option<NoiseTexture>, strength: f32, strength_curve: StrengthCurve, color_math: ColorMath, input: &RgbaImage) -> RgbaImage {
+        unsafe { render_offscreen(spectrum_pair, noise_texture, strength, strength_curve, color_math, input) }.expect("offscreen render failed")
+    }
+
+    fn assert_close(actual: &RgbaImage, expected: &RgbaImage, tolerance: u8) {
+        for (x, y, pixel) in actual.enumerate_pixels() {
+            let wanted = expected.get_pixel(x, y);
+            let close = pixel.0.iter().zip(wanted.0).all(|(&a, b)| a.abs_diff(b) <= tolerance);
+            assert!(close, "pixel ({}, {}) is {:?}, expected {:?}", x, y, pixel.0, wanted.0);
+        }
+    }
+
+    #[test]
+    fn identity_spectrum_keeps_colors() {
+        let input = swatches();
+        // The lookup texture is sampled between hue entries, hence the tolerance
+        assert_close(&render(pair(&[IDENTITY]), None, 1.0, &input), &input, 4);
+    }
+
+    #[test]
+    fn zero_strength_passes_through() {
+        let input = swatches();
+        assert_close(&render(pair(&[ALL_GREEN]), None, 0.0, &input), &input, 1);
+    }
+
+    #[test]
+    fn red_maps_to_green() {
+        let input = RgbaImage::from_pixel(4, 4, RED);
+        assert_close(&render(pair(&[ALL_GREEN]), None, 1.0, &input), &RgbaImage::from_pixel(4, 4, GREEN), 2);
+    }
+
+    #[test]
+    fn noise_mask_selects_primary_and_secondary() {
+        let mask = GrayImage::from_fn(2, 1, |x, _| Luma([if x == 0 { 255 } else { 0 }]));
+        let noise = NoiseTexture::from_image(&DynamicImage::ImageLuma8(mask));
+        let input = RgbaImage::from_pixel(2, 1, RED);
+
+        let output = render(pair(&[ALL_GREEN, ALL_BLUE]), Some(noise), 1.0, &input);
+        assert_close(&output, &RgbaImage::from_fn(2, 1, |x, _| if x == 0 { GREEN } else { BLUE }), 2);
+    }
+
+    #[test]
+    fn baked_lut_matches_shader_math() {
+        let input = hue_ring();
+        for strength in [0.6, 1.0] {
+            let shader = render_with(pair(&[ALL_BLUE]), None, strength, ColorMath::Shader, &input);
+            let baked = render_with(pair(&[ALL_BLUE]), None, strength, ColorMath::BakedLut, &input);
+            assert_close(&baked, &shader, 1);
+        }
+    }
+
+    #[test]
+    fn strength_curve_sets_the_blend_weight() {
+        let input = RgbaImage::from_pixel(2, 2, RED);
+        for color_math in ColorMath::ALL {
+            for strength in [0.0, 0.25, 1.0] {
+                for curve in [StrengthCurve::Linear, StrengthCurve::Perceptual] {
+                    let curved = render_curved(pair(&[ALL_GREEN]), None, strength, curve, color_math, &input);
+                    let linear = render_with(pair(&[ALL_GREEN]), None, curve.apply(strength), color_math, &input);
+                    assert_close(&curved, &linear, 1);
                 }
             }
 
-            let noise_desc = D3D11_TEXTURE2D_DESC {
-                Width: noise_width,
-                Height: noise_height,
+            // A quarter of the slider shows clearly more correction on the perceptual curve
+            let linear = render_curved(pair(&[ALL_GREEN]), None, 0.25, StrengthCurve::Linear, color_math, &input);
+            let perceptual = render_curved(pair(&[ALL_GREEN]), None, 0.25, StrengthCurve::Perceptual, color_math, &input);
+            assert!(perceptual.get_pixel(0, 0)[1] > linear.get_pixel(0, 0)[1] + 40);
+        }
+    }
+
+    #[test]
+    fn baked_lut_follows_the_noise_mask() {
+        let mask = GrayImage::from_fn(2, 1, |x, _| Luma([if x == 0 { 255 } else { 0 }]));
+        let noise = NoiseTexture::from_image(&DynamicImage::ImageLuma8(mask));
+        let input = RgbaImage::from_pixel(2, 1, RED);
+
+        let output = render_with(pair(&[ALL_GREEN, ALL_BLUE]), Some(noise), 1.0, ColorMath::BakedLut, &input);
+        assert_close(&output, &RgbaImage::from_fn(2, 1, |x, _| if x == 0 { GREEN } else { BLUE }), 2);
+    }
+
+    #[test]
+    fn ten_bit_frames_are_presented_and_read_back() {
+        assert_eq!(output_format_for(DXGI_FORMAT_R10G10B10A2_UNORM), DXGI_FORMAT_R10G10B10A2_UNORM);
+        assert_eq!(output_format_for(DXGI_FORMAT_B8G8R8A8_UNORM), DEFAULT_OUTPUT_FORMAT);
+        assert_eq!(output_format_for(DXGI_FORMAT_R16G16B16A16_FLOAT), DEFAULT_OUTPUT_FORMAT);
+
+        unsafe {
+            let (device, context) = create_d3d_device(D3D_DRIVER_TYPE_WARP).unwrap();
+            // Red 1023, green 512, blue 0, opaque
+            let pixel: u32 = 0b11 << 30 | 512 << 10 | 1023;
+            let texture_desc = D3D11_TEXTURE2D_DESC {
+                Width: 1,
+                Height: 1,
                 MipLevels: 1,
                 ArraySize: 1,
-                Format: DXGI_FORMAT_R8_UNORM,
+                Format: DXGI_FORMAT_R10G10B10A2_UNORM,
                 SampleDesc: DXGI_SAMPLE_DESC { Count: 1, Quality: 0 },
                 Usage: D3D11_USAGE_DEFAULT,
                 BindFlags: D3D11_BIND_SHADER_RESOURCE.0 as u32,
                 CPUAccessFlags: 0,
                 MiscFlags: 0,
             };
+            let data = D3D11_SUBRESOURCE_DATA { pSysMem: &pixel as *const u32 as *const _, SysMemPitch: 4, SysMemSlicePitch: 0 };
+            let mut texture: Option<ID3D11Texture2D> = None;
+            device.CreateTexture2D(&texture_desc, Some(&data), Some(&mut texture)).unwrap();
 
-            let noise_init_data = D3D11_SUBRESOURCE_DATA {
-                pSysMem: noise_data.as_ptr() as *const _,
-                SysMemPitch: noise_width,
-                SysMemSlicePitch: 0,
-            };
+            let image = read_texture(&device, &context, &texture.unwrap()).unwrap();
+            assert_eq!(image.get_pixel(0, 0), &Rgba([255, 128, 0, 255]));
+        }
+    }
 
-            let mut noise_texture_d3d: Option<ID3D11Texture2D> = None;
-            device.CreateTexture2D(&noise_desc, Some(&noise_init_data), Some(&mut noise_texture_d3d))?;
+    #[test]
+    fn capture_wait_ends_before_the_next_refresh() {
+        use std::time::Duration;
+        // 240Hz leaves ~4.17ms per refresh, 1ms of which is kept for rendering
+        assert_eq!(capture_timeout_ms(240.0, Duration::ZERO), 3);
+        assert_eq!(capture_timeout_ms(60.0, Duration::from_millis(5)), 10);
+        // Already late: take whatever has arrived without waiting
+        assert_eq!(capture_timeout_ms(240.0, Duration::from_millis(5)), 0);
+        assert_eq!(capture_timeout_ms(0.0, Duration::ZERO), 999);
+    }
 
-            let mut srv: Option<ID3D11ShaderResourceView> = None;
-            device.CreateShaderResourceView(&noise_texture_d3d.unwrap(), None, Some(&mut srv))?;
-            Some(srv.unwrap())
-        } else {
-            None
-        };
+    /// Soak test for leaked handles and window objects: creates and drops the overlay on the
+    /// first monitor 200 times, which should leave the handle count flat. Needs a display, so it
+    /// is run by hand: `cargo test overlay_restarts_leak_no_handles -- --ignored`.
+    #[test]
+    #[ignore]
+    fn overlay_restarts_leak_no_handles() {
+        const RESTARTS: usize = 200;
+        // Drivers keep some handles open for good after the first devices
+        const WARMUP: usize = 10;
+        const SLACK: u32 = 32;
 
-        #[repr(C)]
-        struct SpectrumParams {
-            strength: f32,
-            use_dual_spectrum: i32,
-            use_noise_texture: i32,
-            padding: f32,
-        }
-
-        let params = SpectrumParams {
-            strength: state_read.hue_mapper.strength,
-            use_dual_spectrum: if state_read.spectrum_pair.has_dual_spectrum() { 1 } else { 0 },
-            use_noise_texture: if state_read.noise_texture.is_some() { 1 } else { 0 },
-            padding: 0.0,
-        };
+        unsafe {
+            let _ = CoInitializeEx(None, COINIT_MULTITHREADED);
+            let monitor_info = get_monitor_info(0).unwrap();
+            let state = Arc::new(RwLock::new(test_overlay_state(pair(&[IDENTITY]), None, 1.0, StrengthCurve::Linear, ColorMath::Shader)));
+
+            let mut baseline = None;
+            for restart in 0..RESTARTS {
+                if restart == WARMUP {
+                    baseline = process_handle_count();
+                }
+                let overlay = DCompOverlay::new(Arc::clone(&state), monitor_info.clone(), 0, OverlaySource::DesktopCapture, false, false, &mut StartupTimer::start("overlay")).unwrap();
+                drop(overlay);
 
-        let cb_desc = D3D11_BUFFER_DESC {
-            ByteWidth: std::mem::size_of::<SpectrumParams>() as u32,
-            Usage: D3D11_USAGE_DYNAMIC,
-            BindFlags: D3D11_BIND_CONSTANT_BUFFER.0 as u32,
-            CPUAccessFlags: D3D11_CPU_ACCESS_WRITE.0 as u32,
-            MiscFlags: 0,
-            StructureByteStride: 0,
-        };
+                // Fails creating the swap chain, after the window and device exist
+                let oversized = MonitorInfo { size: (chromabridge::monitor::MAX_MONITOR_SIZE + 1, monitor_info.size.1), ..monitor_info.clone() };
+                assert!(DCompOverlay::new(Arc::clone(&state), oversized, 0, OverlaySource::DesktopCapture, false, false, &mut StartupTimer::start("overlay")).is_err());
+                assert!(FindWindowW(w!("ChromaBridgeOverlay"), None).is_err(), "a failed start left its window behind");
+            }
 
-        let cb_init_data = D3D11_SUBRESOURCE_DATA {
-            pSysMem: &params as *const _ as *const _,
-            SysMemPitch: 0,
-            SysMemSlicePitch: 0,
-        };
+            let (before, after) = (baseline.unwrap(), process_handle_count().unwrap());
+            assert!(after <= before + SLACK, "handle count grew from {} to {} over {} restarts", before, after, RESTARTS - WARMUP);
+        }
+    }
+
+    #[test]
+    fn spectrum_changes_alone_skip_the_restart() {
+        let launched = LaunchSettings::of(&AppState {
+            spectrum_name: Some("deutan".into()),
+            ..AppState::default()
+        });
 
-        let mut constant_buffer: Option<ID3D11Buffer> = None;
-        device.CreateBuffer(&cb_desc, Some(&cb_init_data), Some(&mut constant_buffer))?;
+        let mut other_spectrum = launched.clone();
+        other_spectrum.spectrum_name = Some("protan".into());
+        assert!(other_spectrum.only_spectrum_differs(&launched));
 
-        log_info!("Spectrum textures initialized (dual: {}, noise: {})",
-                 state_read.spectrum_pair.has_dual_spectrum(),
-                 state_read.noise_texture.is_some());
+        let mut other_monitor = other_spectrum.clone();
+        other_monitor.monitor = 1;
+        assert!(!other_monitor.only_spectrum_differs(&launched));
 
-        Ok((spectrum1_srv.unwrap(), spectrum2_srv, noise_srv, constant_buffer.unwrap()))
+        let mut no_spectrum = launched.clone();
+        no_spectrum.spectrum_name = None;
+        assert!(!no_spectrum.only_spectrum_differs(&launched));
     }
 }
@@ -0,0 +1,81 @@
+use chromabridge::tr;
+use std::ffi::{c_char, CStr};
+use std::sync::OnceLock;
+use windows::{
+    core::{s, w, HSTRING},
+    Win32::Graphics::DirectComposition::{DCompositionCreateDevice, IDCompositionDevice},
+    Win32::System::LibraryLoader::{GetModuleHandleW, GetProcAddress},
+    Win32::UI::WindowsAndMessaging::{MessageBoxW, MB_ICONERROR, MB_OK},
+};
+
+/// Process exit code when startup stops because of an unsupported platform, apart from the 1 of
+/// a fatal error so launch scripts can tell them apart.
+pub const UNSUPPORTED_PLATFORM_EXIT_CODE: u8 = 3;
+
+/// Command line flag that starts the app even where `detect` finds it can't work.
+pub const FORCE_FLAG: &str = "--force";
+
+/// Why the overlay can't work in this environment.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Unsupported {
+    /// Running under Wine or Proton, whose desktop duplication and DirectComposition are stubs.
+    Wine { version: String },
+    /// DirectComposition, which the overlay window is built on, can't create a device.
+    NoDirectComposition { error: String },
+}
+
+impl Unsupported {
+    /// What the user is told, in the UI language.
+    pub fn message(&self) -> String {
+        match self {
+            Unsupported::Wine { version } => tr!("platform.wine", version = version),
+            Unsupported::NoDirectComposition { error } => tr!("platform.no_direct_composition", error = error),
+        }
+    }
+}
+
+impl std::fmt::Display for Unsupported {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Unsupported::Wine { version } => write!(f, "running under Wine {}", version),
+            Unsupported::NoDirectComposition { error } => write!(f, "DirectComposition unavailable: {}", error),
+        }
+    }
+}
+
+/// Checks once per process whether the overlay can work here; later calls return the same result.
+pub fn detect() -> Option<&'static Unsupported> {
+    static DETECTED: OnceLock<Option<Unsupported>> = OnceLock::new();
+    DETECTED
+        .get_or_init(|| unsafe {
+            if let Some(version) = wine_version() {
+                return Some(Unsupported::Wine { version });
+            }
+            DCompositionCreateDevice::<IDCompositionDevice>(None)
+                .err()
+                .map(|e| Unsupported::NoDirectComposition { error: e.to_string() })
+        })
+        .as_ref()
+}
+
+/// Tells the user why the app is about to exit, before the tray icon exists.
+pub fn show_message(unsupported: &Unsupported) {
+    let text = HSTRING::from(format!("{}\n\n{}", unsupported.message(), tr!("platform.force_hint", flag = FORCE_FLAG)));
+    let caption = HSTRING::from(tr!("platform.title"));
+    unsafe {
+        MessageBoxW(None, &text, &caption, MB_OK | MB_ICONERROR);
+    }
+}
+
+/// Wine's ntdll exports `wine_get_version`, Windows' doesn't; the usual way to tell them apart.
+unsafe fn wine_version() -> Option<String> {
+    let ntdll = GetModuleHandleW(w!("ntdll.dll")).ok()?;
+    let export = GetProcAddress(ntdll, s!("wine_get_version"))?;
+    let wine_get_version = std::mem::transmute::<unsafe extern "system" fn() -> isize, unsafe extern "C" fn() -> *const c_char>(export);
+
+    let version = wine_get_version();
+    if version.is_null() {
+        return Some("(unknown version)".to_string());
+    }
+    Some(CStr::from_ptr(version).to_string_lossy().into_owned())
+}
@@ -0,0 +1,174 @@
+use chromabridge::{log_debug, log_info};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+use windows::{
+    Win32::Graphics::Dxgi::{IDXGISwapChain1, DXGI_ERROR_FRAME_STATISTICS_DISJOINT, DXGI_FRAME_STATISTICS},
+    Win32::System::Performance::{QueryPerformanceCounter, QueryPerformanceFrequency},
+};
+
+/// How often the counts are summed up, published and logged.
+const REPORT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Submit times kept for the present-to-display latency, more than are ever queued.
+const SUBMIT_TIMES: usize = 8;
+
+/// What DWM did with the overlay's presents, read back from the swap chain's frame statistics.
+///
+/// The loop timing only shows how often the overlay presents. DWM shows at most one present per
+/// refresh and discards the others, and a present that misses its refresh leaves the previous
+/// frame up for another one; neither shows in the loop timing. Flip model swap chains such as the
+/// overlay's report both without any extra creation flags.
+pub struct PresentStats {
+    /// Statistics of the previous read; `None` before the first read and after a disjoint.
+    last: Option<DXGI_FRAME_STATISTICS>,
+    last_submitted: u32,
+    /// Present count and QPC time of the latest presents, oldest first.
+    submit_times: VecDeque<(u32, i64)>,
+    qpc_frequency: i64,
+
+    interval_start: Instant,
+    submitted: u32,
+    displayed: u32,
+    dropped: u32,
+    /// Frames that stayed on screen for more refreshes than meant, because the next one came late.
+    late: u32,
+    latency_sum_ms: f32,
+    latency_count: u32,
+
+    dropped_per_second: Option<f32>,
+    /// Set once the swap chain refused to report statistics at all.
+    unsupported: bool,
+}
+
+impl PresentStats {
+    pub fn new() -> Self {
+        let mut qpc_frequency = 0i64;
+        let _ = unsafe { QueryPerformanceFrequency(&mut qpc_frequency) };
+
+        Self {
+            last: None,
+            last_submitted: 0,
+            submit_times: VecDeque::with_capacity(SUBMIT_TIMES),
+            qpc_frequency,
+            interval_start: Instant::now(),
+            submitted: 0,
+            displayed: 0,
+            dropped: 0,
+            late: 0,
+            latency_sum_ms: 0.0,
+            latency_count: 0,
+            dropped_per_second: None,
+            unsupported: false,
+        }
+    }
+
+    /// Presents DWM discarded per second over the last full interval; `None` until one has
+    /// passed and when the swap chain keeps no statistics.
+    pub fn dropped_per_second(&self) -> Option<f32> {
+        self.dropped_per_second
+    }
+
+    /// Starts over, for a new swap chain or after a pause.
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+
+    /// Reads the statistics after a successful present. `refreshes_per_present` is how many
+    /// refreshes each frame is meant to stay up: 1 at the refresh rate, 2 at half of it.
+    pub unsafe fn record_present(&mut self, swap_chain: &IDXGISwapChain1, refreshes_per_present: u32) {
+        if self.unsupported {
+            return;
+        }
+        let Ok(submitted) = swap_chain.GetLastPresentCount() else {
+            return;
+        };
+
+        let mut now = 0i64;
+        if QueryPerformanceCounter(&mut now).is_ok() {
+            if self.submit_times.len() == SUBMIT_TIMES {
+                self.submit_times.pop_front();
+            }
+            self.submit_times.push_back((submitted, now));
+        }
+
+        let mut stats = DXGI_FRAME_STATISTICS::default();
+        match swap_chain.GetFrameStatistics(&mut stats) {
+            Ok(()) => self.count(stats, submitted, refreshes_per_present),
+            // Before the first frame was shown and after a mode change the counts don't follow on
+            // from the previous read, so comparing with it would report nonsense
+            Err(e) if e.code() == DXGI_ERROR_FRAME_STATISTICS_DISJOINT => {
+                self.last = None;
+                self.submit_times.clear();
+                self.restart_interval();
+            }
+            Err(e) => {
+                log_info!("Present statistics unavailable, dropped frames are not measured: {}", e);
+                self.unsupported = true;
+                self.dropped_per_second = None;
+                return;
+            }
+        }
+
+        if self.interval_start.elapsed() >= REPORT_INTERVAL {
+            self.report();
+        }
+    }
+
+    fn count(&mut self, stats: DXGI_FRAME_STATISTICS, submitted: u32, refreshes_per_present: u32) {
+        let previous_submitted = std::mem::replace(&mut self.last_submitted, submitted);
+        let Some(last) = self.last.replace(stats) else {
+            return;
+        };
+        self.submitted += submitted.wrapping_sub(previous_submitted);
+
+        let presents = stats.PresentCount.wrapping_sub(last.PresentCount);
+        if presents == 0 {
+            return;
+        }
+        let refreshes = stats.PresentRefreshCount.wrapping_sub(last.PresentRefreshCount);
+
+        // At most one present reaches the screen per refresh; the others were discarded
+        let shown = presents.min(refreshes);
+        self.displayed += shown;
+        self.dropped += presents - shown;
+        if refreshes > shown * refreshes_per_present.max(1) {
+            self.late += 1;
+        }
+
+        // SyncQPCTime is the time of the refresh the latest present was shown at when the two
+        // refresh counts match
+        if stats.SyncRefreshCount == stats.PresentRefreshCount && self.qpc_frequency > 0 {
+            let submitted_at = self.submit_times.iter().find(|&&(count, _)| count == stats.PresentCount);
+            if let Some(&(_, time)) = submitted_at.filter(|&&(_, time)| stats.SyncQPCTime >= time) {
+                self.latency_sum_ms += (stats.SyncQPCTime - time) as f32 * 1000.0 / self.qpc_frequency as f32;
+                self.latency_count += 1;
+            }
+        }
+    }
+
+    fn restart_interval(&mut self) {
+        self.interval_start = Instant::now();
+        self.submitted = 0;
+        self.displayed = 0;
+        self.dropped = 0;
+        self.late = 0;
+        self.latency_sum_ms = 0.0;
+        self.latency_count = 0;
+    }
+
+    fn report(&mut self) {
+        let seconds = self.interval_start.elapsed().as_secs_f32();
+        let latency = (self.latency_count > 0).then(|| self.latency_sum_ms / self.latency_count as f32);
+        log_debug!(
+            "Presents: {} submitted, {} displayed, {} dropped, {} late{}",
+            self.submitted,
+            self.displayed,
+            self.dropped,
+            self.late,
+            latency.map(|ms| format!(", {:.1}ms present to display", ms)).unwrap_or_default()
+        );
+
+        self.dropped_per_second = Some(self.dropped as f32 / seconds);
+        self.restart_interval();
+    }
+}
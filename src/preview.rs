@@ -0,0 +1,472 @@
+use crate::hue_mapper::{HueGate, HueMapper};
+use crate::noise::{NoiseKind, NoiseTexture, MAX_NOISE_INDICES};
+use crate::spectrum::{Spectrum, SpectrumPair};
+use crate::state::{NoiseMapping, NoiseTarget};
+use anyhow::Result;
+use image::imageops::FilterType;
+use image::{Rgba, RgbaImage};
+
+/// Widest screenshot preview; larger captures are scaled down first, which keeps a correction
+/// pass well under a second.
+pub const MAX_PREVIEW_WIDTH: u32 = 1280;
+
+/// Entries per spectrum table, one per degree like the overlay's spectrum textures.
+const HUE_STEPS: usize = 360;
+
+/// Degrees between the hues `distinct_hue` tries.
+const DISTINCT_HUE_STEP: usize = 10;
+
+const EPSILON: f32 = 0.0001;
+
+/// The interlace pattern a preview applies, as the overlay would lay it over the screen.
+pub struct NoisePattern {
+    pub texture: NoiseTexture,
+    pub mapping: NoiseMapping,
+    pub intensity: f32,
+    pub target: NoiseTarget,
+}
+
+/// The overlay's correction done on the CPU, for previewing settings on a screenshot.
+///
+/// Follows `PS_Main` in shaders.hlsl for the spectrum, strength, hue gate, noise pattern and
+/// posterizing; temporal smoothing is left out. Keep the two in sync.
+pub struct CpuCorrection {
+    /// HSV of each spectrum the noise pattern selects between, per degree of input hue; the
+    /// primary first.
+    spectra: Vec<Vec<[f32; 3]>>,
+    blend_weight: f32,
+    hue_gate: Option<HueGate>,
+    /// Only with a secondary spectrum or against the original, like the overlay.
+    noise: Option<NoisePattern>,
+    /// Share of the secondary spectrum mixed in everywhere, without a noise pattern.
+    blend_ratio: f32,
+    /// Hue levels the result is snapped to, 0 for off, as `posterizeLevels` in the shader.
+    posterize_levels: u32,
+}
+
+impl CpuCorrection {
+    /// `blend_weight` is the strength after the strength curve.
+    pub fn new(pair: &SpectrumPair, blend_weight: f32, hue_gate: Option<HueGate>, noise: Option<NoisePattern>) -> Result<Self> {
+        let noise = noise.filter(|noise| noise.target == NoiseTarget::CorrectionVsOriginal || pair.has_dual_spectrum());
+        let limit = match noise {
+            Some(ref noise) if noise.target == NoiseTarget::CorrectionVsOriginal => 1,
+            Some(ref noise) if noise.texture.kind() != NoiseKind::Mask => MAX_NOISE_INDICES,
+            _ => 2,
+        };
+        let spectra = pair.noise_spectra(limit).into_iter().map(hsv_table).collect::<Result<_>>()?;
+
+        Ok(Self {
+            spectra,
+            blend_weight,
+            hue_gate,
+            noise,
+            blend_ratio: 0.0,
+            posterize_levels: 0,
+        })
+    }
+
+    /// Mixes `ratio` of the secondary spectrum in evenly, as the overlay does for a dual-spectrum
+    /// file without a noise texture. No effect with a noise pattern or a single spectrum.
+    pub fn with_blend_ratio(mut self, ratio: f32) -> Self {
+        self.blend_ratio = ratio.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Snaps the result to `levels` hues like the overlay's posterize setting, also where the
+    /// correction leaves a pixel alone. `None` turns it off.
+    pub fn with_posterize(mut self, levels: Option<u32>) -> Self {
+        self.posterize_levels = levels.unwrap_or(0);
+        self
+    }
+
+    /// Whether a noise pattern switches between the spectra, or between the primary one and the
+    /// original; the former only with a secondary spectrum.
+    pub fn has_noise(&self) -> bool {
+        self.noise.is_some()
+    }
+
+    /// Corrects `image` in place. It shows a screen `screen_width` pixels wide, possibly scaled
+    /// down, which the tiled noise mappings need to lay their texels out at screen size.
+    pub fn apply(&self, image: &mut RgbaImage, screen_width: u32) {
+        let scale = screen_width.max(1) as f32 / image.width().max(1) as f32;
+        let view = ScreenView {
+            left: 0,
+            top: 0,
+            scale,
+            screen_width: screen_width.max(1),
+            screen_height: ((image.height() as f32 * scale) as u32).max(1),
+        };
+        self.apply_in_view(image, view);
+    }
+
+    /// Corrects `image` in place, a 1:1 crop of a `screen_width` by `screen_height` screen with
+    /// its top left corner at (`left`, `top`), so the noise pattern lands as on that screen.
+    pub fn apply_crop(&self, image: &mut RgbaImage, left: u32, top: u32, screen_width: u32, screen_height: u32) {
+        let view = ScreenView {
+            left,
+            top,
+            scale: 1.0,
+            screen_width: screen_width.max(1),
+            screen_height: screen_height.max(1),
+        };
+        self.apply_in_view(image, view);
+    }
+
+    fn apply_in_view(&self, image: &mut RgbaImage, view: ScreenView) {
+        let correct = self.blend_weight >= EPSILON;
+        if !correct && self.posterize_levels == 0 {
+            return;
+        }
+
+        for (x, y, pixel) in image.enumerate_pixels_mut() {
+            if correct {
+                self.correct_pixel(pixel, (x, y), view);
+            }
+            if self.posterize_levels > 0 {
+                let (h, s, v) = HueMapper::rgb_to_hsv(pixel[0], pixel[1], pixel[2]);
+                let (r, g, b) = HueMapper::hsv_to_rgb(HueMapper::posterize_hue(h, self.posterize_levels), s, v);
+                (pixel[0], pixel[1], pixel[2]) = (r, g, b);
+            }
+        }
+    }
+
+    /// Everything up to the posterize step for the pixel at (`x`, `y`) of the image.
+    fn correct_pixel(&self, pixel: &mut Rgba<u8>, (x, y): (u32, u32), view: ScreenView) {
+        let rgb = [pixel[0], pixel[1], pixel[2]].map(|c| c as f32 / 255.0);
+        let hsv = HueMapper::rgb_to_hsv(pixel[0], pixel[1], pixel[2]);
+        let weight = self.blend_weight * self.hue_gate.map_or(1.0, |gate| gate.weight(hsv.0));
+        if weight <= 0.0 {
+            return;
+        }
+
+        let hue_index = (hsv.0 as usize).min(HUE_STEPS - 1);
+        let mut corrected = correct_hsv(hsv, self.spectra[0][hue_index]);
+        if let Some(noise) = self.noise.as_ref().filter(|noise| noise.intensity > EPSILON) {
+            let index = self.noise_index(noise, view.screen_pixel(x, y), view);
+            match noise.target {
+                NoiseTarget::BetweenSpectra => {
+                    let noise_rgb = correct_hsv(hsv, self.spectra[index.min(self.spectra.len() - 1)][hue_index]);
+                    corrected = lerp(corrected, noise_rgb, noise.intensity.min(1.0));
+                }
+                NoiseTarget::CorrectionVsOriginal if index != 0 => corrected = lerp(corrected, rgb, noise.intensity.min(1.0)),
+                NoiseTarget::CorrectionVsOriginal => {}
+            }
+        } else if self.noise.is_none() && self.blend_ratio > EPSILON {
+            if let Some(secondary) = self.spectra.get(1) {
+                corrected = lerp(corrected, correct_hsv(hsv, secondary[hue_index]), self.blend_ratio);
+            }
+        }
+
+        let result = lerp(rgb, corrected, weight);
+        for (channel, value) in pixel.0.iter_mut().zip(result) {
+            *channel = (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+        }
+    }
+
+    /// Which spectrum the pattern selects at screen pixel (`x`, `y`), 0 being the primary. Not
+    /// clamped to the loaded spectra.
+    fn noise_index(&self, noise: &NoisePattern, (x, y): (u32, u32), view: ScreenView) -> usize {
+        let (noise_width, noise_height) = (noise.texture.width().max(1), noise.texture.height().max(1));
+        let (texel_x, texel_y) = match noise.mapping {
+            NoiseMapping::Stretch => (
+                (x as u64 * noise_width as u64 / view.screen_width as u64).min(noise_width as u64 - 1) as u32,
+                (y as u64 * noise_height as u64 / view.screen_height as u64).min(noise_height as u64 - 1) as u32,
+            ),
+            NoiseMapping::Tile | NoiseMapping::PixelExact => (x % noise_width, y % noise_height),
+        };
+        noise.texture.indices().get((texel_y * noise_width + texel_x) as usize).copied().unwrap_or(0) as usize
+    }
+}
+
+/// Where an image lies on the screen it shows, for laying out the noise pattern.
+#[derive(Debug, Clone, Copy)]
+struct ScreenView {
+    /// Screen position of the image's top left corner.
+    left: u32,
+    top: u32,
+    /// Screen pixels per image pixel.
+    scale: f32,
+    screen_width: u32,
+    screen_height: u32,
+}
+
+impl ScreenView {
+    /// The screen pixel under the center of image pixel (`x`, `y`).
+    fn screen_pixel(&self, x: u32, y: u32) -> (u32, u32) {
+        (
+            self.left + ((x as f32 + 0.5) * self.scale) as u32,
+            self.top + ((y as f32 + 0.5) * self.scale) as u32,
+        )
+    }
+}
+
+/// The hue whose color at `saturation` and `value` stays furthest in Oklab from every color in
+/// `avoid` once `correction` is applied to both, i.e. as someone looking through the overlay sees
+/// them. Ties go to the lower hue.
+pub fn distinct_hue(correction: &CpuCorrection, saturation: f32, value: f32, avoid: &[[u8; 3]]) -> f32 {
+    let hues: Vec<f32> = (0..360).step_by(DISTINCT_HUE_STEP).map(|h| h as f32).collect();
+    let candidates = hues.iter().map(|&h| {
+        let (r, g, b) = HueMapper::hsv_to_rgb(h, saturation, value);
+        [r, g, b]
+    });
+
+    let mut image = RgbaImage::new((hues.len() + avoid.len()) as u32, 1);
+    for (pixel, [r, g, b]) in image.pixels_mut().zip(candidates.chain(avoid.iter().copied())) {
+        *pixel = Rgba([r, g, b, 255]);
+    }
+    let width = image.width();
+    correction.apply(&mut image, width);
+
+    let oklab: Vec<(f32, f32, f32)> = image
+        .pixels()
+        .map(|pixel| {
+            let [r, g, b] = [pixel[0], pixel[1], pixel[2]].map(|c| HueMapper::srgb_to_linear(c as f32 / 255.0));
+            HueMapper::linear_to_oklab(r, g, b)
+        })
+        .collect();
+    let (corrected, avoided) = oklab.split_at(hues.len());
+    let distance = |a: &(f32, f32, f32), b: &(f32, f32, f32)| ((a.0 - b.0).powi(2) + (a.1 - b.1).powi(2) + (a.2 - b.2).powi(2)).sqrt();
+
+    let mut best = (hues[0], f32::NEG_INFINITY);
+    for (&hue, color) in hues.iter().zip(corrected) {
+        let closest = avoided.iter().map(|other| distance(color, other)).fold(f32::INFINITY, f32::min);
+        if closest > best.1 {
+            best = (hue, closest);
+        }
+    }
+    best.0
+}
+
+/// Scales `image` down to at most `MAX_PREVIEW_WIDTH` wide, keeping its aspect ratio.
+pub fn downscale(image: RgbaImage) -> RgbaImage {
+    let (width, height) = image.dimensions();
+    if width <= MAX_PREVIEW_WIDTH {
+        return image;
+    }
+    let scaled_height = (height as u64 * MAX_PREVIEW_WIDTH as u64 / width as u64).max(1) as u32;
+    image::imageops::resize(&image, MAX_PREVIEW_WIDTH, scaled_height, FilterType::Triangle)
+}
+
+fn hsv_table(spectrum: &Spectrum) -> Result<Vec<[f32; 3]>> {
+    let table = spectrum.get_rgb_lookup_table(HUE_STEPS)?;
+    Ok(table.chunks_exact(3).map(|rgb| rgb_to_hsv([rgb[0], rgb[1], rgb[2]])).collect())
+}
+
+/// Matches `correct_hsv` in shaders.hlsl: the spectrum's hue, with the input's brightness and
+/// saturation scaled by the spectrum's.
+fn correct_hsv(input: (f32, f32, f32), spectrum: [f32; 3]) -> [f32; 3] {
+    let (_, s, v) = input;
+    hsv_to_rgb([spectrum[0], s * spectrum[1], v * ((1.0 - s) + s * spectrum[2])])
+}
+
+fn lerp(a: [f32; 3], b: [f32; 3], t: f32) -> [f32; 3] {
+    [0, 1, 2].map(|c| a[c] + (b[c] - a[c]) * t)
+}
+
+/// Float versions of `HueMapper`'s conversions, for the spectrum tables' unrounded colors.
+fn rgb_to_hsv([r, g, b]: [f32; 3]) -> [f32; 3] {
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let delta = max - min;
+
+    let h = if delta <= EPSILON {
+        0.0
+    } else if max == r {
+        60.0 * (((g - b) / delta) % 6.0)
+    } else if max == g {
+        60.0 * (((b - r) / delta) + 2.0)
+    } else {
+        60.0 * (((r - g) / delta) + 4.0)
+    };
+    let h = if h < 0.0 { h + 360.0 } else { h };
+    let s = if max > EPSILON { delta / max } else { 0.0 };
+    [h, s, max]
+}
+
+fn hsv_to_rgb([h, s, v]: [f32; 3]) -> [f32; 3] {
+    let h = h.rem_euclid(360.0) / 60.0;
+    let c = v * s;
+    let x = c * (1.0 - (h % 2.0 - 1.0).abs());
+    let m = v - c;
+
+    let rgb = match h as u32 {
+        0 => [c, x, 0.0],
+        1 => [x, c, 0.0],
+        2 => [0.0, c, x],
+        3 => [0.0, x, c],
+        4 => [x, 0.0, c],
+        _ => [c, 0.0, x],
+    };
+    rgb.map(|channel| channel + m)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spectrum::{Interpolation, SpectrumFile, SpectrumSelection};
+    use image::{DynamicImage, GrayImage, Luma};
+
+    /// Primary maps every hue to blue, secondary to green.
+    fn blue_green_pair() -> SpectrumPair {
+        let solid = |name: &str, color: &str| Spectrum::builder().name(name).node(0.0, color).node(1.0, color).build().unwrap();
+        let file = SpectrumFile::new(vec![solid("Blue", "#0000FF"), solid("Green", "#00FF00")], Interpolation::Srgb).unwrap();
+        SpectrumPair::from_file(file, SpectrumSelection::default())
+    }
+
+    #[test]
+    fn strength_blends_towards_the_spectrum() {
+        let pair = blue_green_pair();
+        let red = RgbaImage::from_pixel(2, 2, Rgba([255, 0, 0, 255]));
+
+        let mut image = red.clone();
+        CpuCorrection::new(&pair, 0.0, None, None).unwrap().apply(&mut image, 2);
+        assert_eq!(image, red);
+
+        let mut image = red.clone();
+        CpuCorrection::new(&pair, 1.0, None, None).unwrap().apply(&mut image, 2);
+        assert_eq!(image.get_pixel(0, 0), &Rgba([0, 0, 255, 255]));
+
+        let mut image = red.clone();
+        CpuCorrection::new(&pair, 0.5, None, None).unwrap().apply(&mut image, 2);
+        assert_eq!(image.get_pixel(1, 1), &Rgba([128, 0, 128, 255]));
+
+        // Red is outside the gate
+        let mut image = red.clone();
+        let gate = HueGate { start: 90.0, end: 270.0 };
+        CpuCorrection::new(&pair, 1.0, Some(gate), None).unwrap().apply(&mut image, 2);
+        assert_eq!(image, red);
+    }
+
+    #[test]
+    fn noise_pattern_selects_the_secondary_spectrum() {
+        // Bright left half keeps the primary, dark right half selects the secondary
+        let mask = GrayImage::from_fn(2, 1, |x, _| Luma([if x == 0 { 255 } else { 0 }]));
+        let noise = |mapping| NoisePattern {
+            texture: NoiseTexture::from_image(&DynamicImage::ImageLuma8(mask.clone())),
+            mapping,
+            intensity: 1.0,
+            target: NoiseTarget::BetweenSpectra,
+        };
+        let correction = |mapping| CpuCorrection::new(&blue_green_pair(), 1.0, None, Some(noise(mapping))).unwrap();
+        let blue = Rgba([0, 0, 255, 255]);
+        let green = Rgba([0, 255, 0, 255]);
+
+        let mut image = RgbaImage::from_pixel(4, 1, Rgba([255, 0, 0, 255]));
+        correction(NoiseMapping::Stretch).apply(&mut image, 4);
+        assert_eq!(image.pixels().copied().collect::<Vec<_>>(), vec![blue, blue, green, green]);
+
+        // A preview at half size still lays the texels out per screen pixel
+        let mut image = RgbaImage::from_pixel(4, 1, Rgba([255, 0, 0, 255]));
+        correction(NoiseMapping::PixelExact).apply(&mut image, 8);
+        assert_eq!(image.pixels().copied().collect::<Vec<_>>(), vec![green, green, green, green]);
+
+        let mut image = RgbaImage::from_pixel(4, 1, Rgba([255, 0, 0, 255]));
+        correction(NoiseMapping::Tile).apply(&mut image, 4);
+        assert_eq!(image.pixels().copied().collect::<Vec<_>>(), vec![blue, green, blue, green]);
+
+        // A crop keeps the pattern where it lands on the screen
+        let mut crop = RgbaImage::from_pixel(2, 1, Rgba([255, 0, 0, 255]));
+        correction(NoiseMapping::Tile).apply_crop(&mut crop, 1, 0, 4, 1);
+        assert_eq!(crop.pixels().copied().collect::<Vec<_>>(), vec![green, blue]);
+
+        let mut crop = RgbaImage::from_pixel(2, 1, Rgba([255, 0, 0, 255]));
+        correction(NoiseMapping::Stretch).apply_crop(&mut crop, 2, 0, 4, 1);
+        assert_eq!(crop.pixels().copied().collect::<Vec<_>>(), vec![green, green]);
+    }
+
+    #[test]
+    fn noise_pattern_against_the_original_ignores_the_secondary_spectrum() {
+        let mask = GrayImage::from_fn(2, 1, |x, _| Luma([if x == 0 { 255 } else { 0 }]));
+        let noise = |intensity| NoisePattern {
+            texture: NoiseTexture::from_image(&DynamicImage::ImageLuma8(mask.clone())),
+            mapping: NoiseMapping::Tile,
+            intensity,
+            target: NoiseTarget::CorrectionVsOriginal,
+        };
+        let red = Rgba([255, 0, 0, 255]);
+        let blue = Rgba([0, 0, 255, 255]);
+
+        let mut image = RgbaImage::from_pixel(4, 1, red);
+        let correction = CpuCorrection::new(&blue_green_pair(), 1.0, None, Some(noise(1.0))).unwrap();
+        assert!(correction.has_noise());
+        correction.apply(&mut image, 4);
+        assert_eq!(image.pixels().copied().collect::<Vec<_>>(), vec![blue, red, blue, red]);
+
+        // Needs no secondary spectrum, and partial intensity only fades the original in
+        let blue_only = SpectrumPair::from_file(
+            SpectrumFile::new(vec![Spectrum::builder().name("Blue").node(0.0, "#0000FF").node(1.0, "#0000FF").build().unwrap()], Interpolation::Srgb).unwrap(),
+            SpectrumSelection::default(),
+        );
+        let mut image = RgbaImage::from_pixel(2, 1, red);
+        CpuCorrection::new(&blue_only, 1.0, None, Some(noise(0.5))).unwrap().apply(&mut image, 2);
+        assert_eq!(image.pixels().copied().collect::<Vec<_>>(), vec![blue, Rgba([128, 0, 128, 255])]);
+    }
+
+    #[test]
+    fn blend_ratio_mixes_the_secondary_spectrum_in_evenly() {
+        let red = Rgba([255, 0, 0, 255]);
+
+        let mut image = RgbaImage::from_pixel(2, 1, red);
+        CpuCorrection::new(&blue_green_pair(), 1.0, None, None).unwrap().with_blend_ratio(0.5).apply(&mut image, 2);
+        assert_eq!(image.pixels().copied().collect::<Vec<_>>(), vec![Rgba([0, 128, 128, 255]); 2]);
+
+        let mut image = RgbaImage::from_pixel(2, 1, red);
+        CpuCorrection::new(&blue_green_pair(), 1.0, None, None).unwrap().with_blend_ratio(1.0).apply(&mut image, 2);
+        assert_eq!(image.pixels().copied().collect::<Vec<_>>(), vec![Rgba([0, 255, 0, 255]); 2]);
+
+        // A noise pattern does the mixing instead
+        let mask = GrayImage::from_fn(2, 1, |_, _| Luma([255]));
+        let noise = NoisePattern {
+            texture: NoiseTexture::from_image(&DynamicImage::ImageLuma8(mask)),
+            mapping: NoiseMapping::Tile,
+            intensity: 1.0,
+            target: NoiseTarget::BetweenSpectra,
+        };
+        let mut image = RgbaImage::from_pixel(2, 1, red);
+        CpuCorrection::new(&blue_green_pair(), 1.0, None, Some(noise)).unwrap().with_blend_ratio(1.0).apply(&mut image, 2);
+        assert_eq!(image.pixels().copied().collect::<Vec<_>>(), vec![Rgba([0, 0, 255, 255]); 2]);
+    }
+
+    #[test]
+    fn posterize_snaps_hues_even_without_a_correction() {
+        let orange = Rgba([255, 85, 0, 255]);
+
+        let mut image = RgbaImage::from_pixel(2, 1, orange);
+        CpuCorrection::new(&blue_green_pair(), 0.0, None, None).unwrap().with_posterize(Some(6)).apply(&mut image, 2);
+        assert_eq!(image.pixels().copied().collect::<Vec<_>>(), vec![Rgba([255, 0, 0, 255]); 2]);
+
+        let mut image = RgbaImage::from_pixel(2, 1, orange);
+        CpuCorrection::new(&blue_green_pair(), 0.0, None, None).unwrap().with_posterize(None).apply(&mut image, 2);
+        assert_eq!(image.pixels().copied().collect::<Vec<_>>(), vec![orange; 2]);
+
+        // The corrected result is posterized too, (153, 51, 102) at 330° snaps back to red
+        let mut image = RgbaImage::from_pixel(2, 1, orange);
+        CpuCorrection::new(&blue_green_pair(), 0.4, None, None).unwrap().with_posterize(Some(6)).apply(&mut image, 2);
+        assert_eq!(image.pixels().copied().collect::<Vec<_>>(), vec![Rgba([153, 51, 51, 255]); 2]);
+    }
+
+    #[test]
+    fn distinct_hues_avoid_what_the_spectrum_maps_onto() {
+        let red = [255, 0, 0];
+
+        // Uncorrected, the hue furthest from red is a blue
+        let identity = CpuCorrection::new(&blue_green_pair(), 0.0, None, None).unwrap();
+        let hue = distinct_hue(&identity, 1.0, 1.0, &[red]);
+        assert!((180.0..=270.0).contains(&hue), "{}", hue);
+
+        // The first half of the hue circle turns red, so only the second half can stand out
+        let spectrum = Spectrum::builder().name("Half red").node(0.0, "#FF0000").node(0.5, "#FF0000").node(1.0, "#0000FF").build().unwrap();
+        let file = SpectrumFile::new(vec![spectrum], Interpolation::Srgb).unwrap();
+        let pair = SpectrumPair::from_file(file, SpectrumSelection::default());
+        let correction = CpuCorrection::new(&pair, 1.0, None, None).unwrap();
+        assert!(distinct_hue(&correction, 1.0, 1.0, &[red]) >= 180.0);
+    }
+
+    #[test]
+    fn only_wide_captures_are_scaled_down() {
+        let small = RgbaImage::new(800, 600);
+        assert_eq!(downscale(small).dimensions(), (800, 600));
+        let wide = RgbaImage::new(3840, 1600);
+        assert_eq!(downscale(wide).dimensions(), (1280, 533));
+    }
+}
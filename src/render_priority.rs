@@ -0,0 +1,98 @@
+use chromabridge::{log_info, log_warn, RenderThreadPriority};
+use windows::{
+    core::{w, PCWSTR},
+    Win32::Foundation::HANDLE,
+    Win32::System::Threading::{
+        AvRevertMmThreadCharacteristics, AvSetMmThreadCharacteristicsW, GetCurrentThread, GetThreadPriority,
+        SetThreadPriority, THREAD_PRIORITY, THREAD_PRIORITY_ABOVE_NORMAL,
+    },
+};
+
+/// MMCSS task the render thread joins; its defaults schedule it in the High category, above
+/// normal threads but below the audio and capture tasks.
+const MMCSS_TASK: PCWSTR = w!("DisplayPostProcessing");
+
+/// What the render thread got, which can be less than asked for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AchievedPriority {
+    Normal,
+    AboveNormal,
+    /// `task_index` identifies the thread's MMCSS registration.
+    Mmcss { task_index: u32 },
+}
+
+impl AchievedPriority {
+    pub fn label(&self) -> String {
+        match self {
+            AchievedPriority::Normal => "Normal".to_string(),
+            AchievedPriority::AboveNormal => "Above normal".to_string(),
+            AchievedPriority::Mmcss { task_index } => format!("MMCSS DisplayPostProcessing (task {})", task_index),
+        }
+    }
+}
+
+/// Raises the calling thread's priority for as long as it lives; dropping it on the same thread
+/// restores what the thread had before.
+pub struct PriorityGuard {
+    mmcss: Option<HANDLE>,
+    previous: Option<THREAD_PRIORITY>,
+    achieved: AchievedPriority,
+}
+
+impl PriorityGuard {
+    /// Applies `priority` to the calling thread, falling back from MMCSS to above normal when
+    /// the service refuses the registration.
+    pub fn raise(priority: RenderThreadPriority) -> Self {
+        let mut guard = Self {
+            mmcss: None,
+            previous: None,
+            achieved: AchievedPriority::Normal,
+        };
+
+        unsafe {
+            if priority == RenderThreadPriority::Mmcss {
+                let mut task_index = 0;
+                match AvSetMmThreadCharacteristicsW(MMCSS_TASK, &mut task_index) {
+                    Ok(handle) => {
+                        guard.mmcss = Some(handle);
+                        guard.achieved = AchievedPriority::Mmcss { task_index };
+                    }
+                    Err(e) => log_warn!("MMCSS registration failed, using above normal priority instead: {}", e),
+                }
+            }
+
+            if priority != RenderThreadPriority::Normal && guard.mmcss.is_none() {
+                let previous = THREAD_PRIORITY(GetThreadPriority(GetCurrentThread()));
+                match SetThreadPriority(GetCurrentThread(), THREAD_PRIORITY_ABOVE_NORMAL) {
+                    Ok(()) => {
+                        guard.previous = Some(previous);
+                        guard.achieved = AchievedPriority::AboveNormal;
+                    }
+                    Err(e) => log_warn!("Failed to raise the render thread priority: {}", e),
+                }
+            }
+        }
+
+        log_info!("Render thread priority: {}", guard.achieved.label());
+        guard
+    }
+
+    pub fn achieved(&self) -> AchievedPriority {
+        self.achieved
+    }
+}
+
+impl Drop for PriorityGuard {
+    fn drop(&mut self) {
+        unsafe {
+            if let Some(handle) = self.mmcss.take() {
+                if let Err(e) = AvRevertMmThreadCharacteristics(handle) {
+                    log_warn!("Failed to leave MMCSS: {}", e);
+                }
+            }
+            if let Some(previous) = self.previous.take() {
+                let _ = SetThreadPriority(GetCurrentThread(), previous);
+            }
+        }
+    }
+}
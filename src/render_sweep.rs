@@ -0,0 +1,229 @@
+use crate::theme;
+use anyhow::{Context, Result};
+use chromabridge::{log_info, log_warn, preview, sweep, tr, StateManager, SweepJob, SweepReport, TestPattern, SWEEP_STRENGTHS};
+use crossbeam_channel::{Receiver, TryRecvError};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Size the test patterns are rendered at.
+const PATTERN_SIZE: (u32, u32) = (1280, 720);
+
+/// A sweep on its worker thread.
+struct RunningSweep {
+    cancel: Arc<AtomicBool>,
+    done: Arc<AtomicUsize>,
+    steps: usize,
+    output_dir: PathBuf,
+    result: Receiver<Result<SweepReport>>,
+}
+
+/// Window that renders a test pattern or image through every installed spectrum at each of
+/// `SWEEP_STRENGTHS` and saves the results as PNGs, for documentation and comparing spectra.
+///
+/// Rendering runs on a worker thread with `SweepJob`; closing the window cancels it.
+pub struct RenderSweep {
+    /// `None` renders the image at `image_path` instead.
+    pattern: Option<TestPattern>,
+    image_path: String,
+    contact_sheet: bool,
+    running: Option<RunningSweep>,
+    /// The folder of the last sweep with what it wrote, or why it failed.
+    finished: Option<(PathBuf, Result<SweepReport, String>)>,
+}
+
+impl Default for RenderSweep {
+    fn default() -> Self {
+        Self {
+            pattern: Some(TestPattern::HueWheel),
+            image_path: String::new(),
+            contact_sheet: true,
+            running: None,
+            finished: None,
+        }
+    }
+}
+
+impl RenderSweep {
+    /// Draws the window; false once it was closed.
+    pub fn show(&mut self, ctx: &egui::Context, state: &StateManager) -> bool {
+        self.update(ctx);
+
+        let mut open = true;
+        let mut start = false;
+        egui::Window::new(tr!("sweep.title"))
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+            .show(ctx, |ui| {
+                let strengths: Vec<String> = SWEEP_STRENGTHS.iter().map(|s| format!("{}%", (s * 100.0).round())).collect();
+                ui.label(tr!("sweep.hint", strengths = strengths.join(", ")));
+                ui.add_space(5.0);
+
+                ui.add_enabled_ui(self.running.is_none(), |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label(tr!("sweep.input"));
+                        let selected = self.pattern.map_or_else(|| tr!("sweep.image_file"), |p| p.label().to_string());
+                        egui::ComboBox::from_id_salt("sweep_input").selected_text(selected).show_ui(ui, |ui| {
+                            for pattern in TestPattern::ALL {
+                                theme::selectable_value(ui, &mut self.pattern, Some(pattern), pattern.label());
+                            }
+                            theme::selectable_value(ui, &mut self.pattern, None, tr!("sweep.image_file"));
+                        });
+                    });
+                    if self.pattern.is_none() {
+                        ui.add(egui::TextEdit::singleline(&mut self.image_path).hint_text(tr!("sweep.image_path_hint")).desired_width(360.0));
+                    }
+                    ui.checkbox(&mut self.contact_sheet, tr!("sweep.contact_sheet")).on_hover_text(tr!("sweep.contact_sheet_hint"));
+                });
+                ui.add_space(5.0);
+
+                match self.running {
+                    Some(ref running) => {
+                        let done = running.done.load(Ordering::Relaxed);
+                        ui.horizontal(|ui| {
+                            let fraction = done as f32 / running.steps.max(1) as f32;
+                            ui.add(egui::ProgressBar::new(fraction).text(tr!("sweep.progress", done = done, total = running.steps)).desired_width(280.0));
+                            if ui.add_enabled(!running.cancel.load(Ordering::Relaxed), egui::Button::new(tr!("sweep.cancel"))).clicked() {
+                                running.cancel.store(true, Ordering::Relaxed);
+                            }
+                        });
+                    }
+                    None => start = ui.button(tr!("sweep.start")).clicked(),
+                }
+
+                if let Some((ref folder, ref result)) = self.finished {
+                    ui.add_space(5.0);
+                    match result {
+                        Ok(report) => {
+                            ui.label(if report.cancelled {
+                                tr!("sweep.cancelled", count = report.written, path = folder.display())
+                            } else {
+                                tr!("sweep.done", count = report.written, path = folder.display())
+                            });
+                            for (name, error) in &report.failed {
+                                ui.colored_label(theme::palette(ui).warning, tr!("sweep.spectrum_failed", name = name, error = error));
+                            }
+                            if ui.button(tr!("sweep.open_folder")).clicked() {
+                                open_folder(folder);
+                            }
+                        }
+                        Err(error) => {
+                            ui.colored_label(theme::palette(ui).error, tr!("sweep.failed", error = error));
+                        }
+                    }
+                }
+            });
+
+        if start {
+            self.start(ctx, state);
+        }
+        if !open {
+            if let Some(ref running) = self.running {
+                running.cancel.store(true, Ordering::Relaxed);
+            }
+        }
+        open
+    }
+
+    fn start(&mut self, ctx: &egui::Context, state: &StateManager) {
+        let output_dir = sweep::output_dir(state.app_data_dir());
+        let job = match self.job(state, output_dir.clone()) {
+            Ok(job) => job,
+            Err(e) => {
+                self.finished = Some((output_dir, Err(format!("{:#}", e))));
+                return;
+            }
+        };
+        log_info!("Rendering {} spectra into {}", job.spectra.len(), output_dir.display());
+
+        let (tx, rx) = crossbeam_channel::bounded(1);
+        let cancel = Arc::new(AtomicBool::new(false));
+        let done = Arc::new(AtomicUsize::new(0));
+        self.running = Some(RunningSweep {
+            cancel: Arc::clone(&cancel),
+            done: Arc::clone(&done),
+            steps: job.step_count(),
+            output_dir,
+            result: rx,
+        });
+        self.finished = None;
+
+        let repaint = ctx.clone();
+        std::thread::spawn(move || {
+            let result = job.run(&cancel, |steps| {
+                done.store(steps, Ordering::Relaxed);
+                repaint.request_repaint();
+            });
+            let _ = tx.send(result);
+            repaint.request_repaint();
+        });
+    }
+
+    /// Everything the sweep needs, read on the GUI thread.
+    fn job(&self, state: &StateManager, output_dir: PathBuf) -> Result<SweepJob> {
+        let input = match self.pattern {
+            Some(pattern) => pattern.generate_rgba(PATTERN_SIZE.0, PATTERN_SIZE.1),
+            None => {
+                // Paths copied from Explorer come quoted
+                let path = self.image_path.trim().trim_matches('"');
+                anyhow::ensure!(!path.is_empty(), tr!("sweep.no_image"));
+                let image = image::open(path).with_context(|| format!("Failed to open {}", path))?;
+                preview::downscale(image.to_rgba8())
+            }
+        };
+
+        let spectra: Vec<(String, PathBuf)> = state
+            .list_spectrum_files()?
+            .into_iter()
+            .filter_map(|name| Some((name.clone(), state.get_spectrum_path(&name).ok()?)))
+            .collect();
+        anyhow::ensure!(!spectra.is_empty(), tr!("sweep.no_spectra"));
+
+        Ok(SweepJob {
+            input,
+            spectra,
+            strength_curve: state.read(|s| s.strength_curve),
+            output_dir,
+            contact_sheet: self.contact_sheet,
+        })
+    }
+
+    /// Collects a finished sweep.
+    fn update(&mut self, ctx: &egui::Context) {
+        let Some(running) = self.running.take() else {
+            return;
+        };
+        let result = match running.result.try_recv() {
+            Ok(result) => result,
+            Err(TryRecvError::Disconnected) => Err(anyhow::anyhow!("The sweep stopped unexpectedly")),
+            Err(TryRecvError::Empty) => {
+                self.running = Some(running);
+                ctx.request_repaint_after(Duration::from_millis(50));
+                return;
+            }
+        };
+
+        match result {
+            Ok(ref report) => {
+                log_info!("Sweep wrote {} images to {}", report.written, running.output_dir.display());
+                for (name, error) in &report.failed {
+                    log_warn!("Sweep skipped {}: {}", name, error);
+                }
+            }
+            Err(ref e) => log_warn!("Sweep failed: {:#}", e),
+        }
+        self.finished = Some((running.output_dir, result.map_err(|e| format!("{:#}", e))));
+    }
+}
+
+fn open_folder(folder: &std::path::Path) {
+    #[cfg(windows)]
+    {
+        let _ = std::process::Command::new("explorer").arg(folder).spawn();
+    }
+    #[cfg(not(windows))]
+    let _ = folder;
+}
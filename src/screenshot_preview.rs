@@ -0,0 +1,369 @@
+use crate::gui::MonitorInfo;
+use crate::theme;
+use anyhow::{Context, Result};
+use chromabridge::preview::{self, CpuCorrection, NoisePattern};
+use chromabridge::{log_info, tr, HueGate, NoiseMapping, NoiseTarget, NoiseTexture, SpectrumPair, SpectrumSelection, StateManager};
+use crossbeam_channel::{Receiver, TryRecvError};
+use image::RgbaImage;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+#[cfg(windows)]
+use windows::Win32::Graphics::Gdi::{
+    BitBlt, CreateCompatibleBitmap, CreateCompatibleDC, DeleteDC, DeleteObject, GetDC, GetDIBits, ReleaseDC, SelectObject,
+    BITMAPINFO, BITMAPINFOHEADER, BI_RGB, CAPTUREBLT, DIB_RGB_COLORS, SRCCOPY,
+};
+
+/// How long the settings have to stay put before the preview is corrected again, so dragging a
+/// slider doesn't queue a pass per frame.
+const DEBOUNCE: Duration = Duration::from_millis(250);
+
+/// What the corrected preview depends on; any change queues a new pass.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PreviewSettings {
+    spectrum_name: Option<String>,
+    spectrum_selection: SpectrumSelection,
+    blend_weight: f32,
+    hue_gate: Option<HueGate>,
+    noise_texture: Option<String>,
+    noise_mapping: NoiseMapping,
+    noise_intensity: f32,
+    noise_target: NoiseTarget,
+    blend_ratio: f32,
+    posterize_levels: Option<u32>,
+    max_noise_size: u32,
+}
+
+impl PreviewSettings {
+    pub fn read(state: &StateManager) -> Self {
+        state.read(|s| Self {
+            spectrum_name: s.spectrum_name.clone(),
+            spectrum_selection: s.spectrum_selection,
+            blend_weight: s.strength_curve.apply(s.strength),
+            hue_gate: s.hue_gate(),
+            noise_texture: s.noise_texture.clone(),
+            noise_mapping: s.noise_mapping,
+            noise_intensity: s.noise_intensity,
+            noise_target: s.noise_target,
+            blend_ratio: s.blend_ratio,
+            posterize_levels: s.posterize_levels,
+            max_noise_size: s.max_noise_size,
+        })
+    }
+
+    /// The spectrum and noise files to correct with, `None` when no spectrum is selected.
+    pub fn files(&self, state: &StateManager) -> Result<Option<(PathBuf, Option<PathBuf>)>> {
+        let Some(ref spectrum_name) = self.spectrum_name else {
+            return Ok(None);
+        };
+        let spectrum_path = state.get_spectrum_path(spectrum_name)?;
+        Ok(Some((spectrum_path, self.noise_texture.as_deref().map(|name| state.get_noise_path(name)))))
+    }
+}
+
+/// A capture, scaled down to preview size.
+struct Screenshot {
+    image: Arc<RgbaImage>,
+    /// Width of the monitor it was taken of, for laying out tiled noise textures.
+    screen_width: u32,
+    texture: egui::TextureHandle,
+}
+
+/// Window showing a screenshot of the selected monitor before and after the correction, split by
+/// a divider that can be dragged across it.
+///
+/// The screenshot is taken once on the window's first frame and corrected on the CPU with
+/// `CpuCorrection`, so it works without the overlay running. The correction runs on a worker
+/// thread and again whenever the spectrum, strength or noise settings change.
+pub struct ScreenshotPreview {
+    monitor: MonitorInfo,
+    /// `None` until the first frame takes it.
+    screenshot: Option<Result<Screenshot, String>>,
+    corrected: Option<egui::TextureHandle>,
+    /// What `corrected` shows, or failed to; `None` until the first pass finished.
+    rendered: Option<PreviewSettings>,
+    /// The settings as last seen and when they changed to that.
+    seen: Option<(PreviewSettings, Instant)>,
+    pending: Option<(PreviewSettings, Receiver<Result<RgbaImage>>)>,
+    error: Option<String>,
+    /// Where the divider is, from 0 (all corrected) to 1 (all original).
+    divider: f32,
+}
+
+impl ScreenshotPreview {
+    pub fn new(monitor: MonitorInfo) -> Self {
+        Self {
+            monitor,
+            screenshot: None,
+            corrected: None,
+            rendered: None,
+            seen: None,
+            pending: None,
+            error: None,
+            divider: 0.5,
+        }
+    }
+
+    /// Draws the window; false once it was closed.
+    pub fn show(&mut self, ctx: &egui::Context, state: &StateManager) -> bool {
+        if self.screenshot.is_none() {
+            self.screenshot = Some(take_screenshot(ctx, &self.monitor));
+        }
+        self.update(ctx, state);
+
+        let mut open = true;
+        let mut retake = false;
+        egui::Window::new(tr!("preview.title"))
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+            .show(ctx, |ui| {
+                ui.weak(tr!("preview.monitor", name = self.monitor.name, width = self.monitor.width, height = self.monitor.height));
+                ui.add_space(5.0);
+
+                match self.screenshot {
+                    Some(Ok(ref screenshot)) => {
+                        show_comparison(ui, screenshot, self.corrected.as_ref(), &mut self.divider);
+                        ui.horizontal(|ui| {
+                            ui.weak(tr!("preview.before"));
+                            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                                ui.weak(tr!("preview.after"));
+                                if self.pending.is_some() {
+                                    ui.spinner();
+                                }
+                            });
+                        });
+                    }
+                    Some(Err(ref error)) => {
+                        ui.colored_label(theme::palette(ui).error, tr!("preview.capture_failed", error = error));
+                    }
+                    None => {}
+                }
+                if let Some(ref error) = self.error {
+                    ui.colored_label(theme::palette(ui).error, error);
+                }
+
+                ui.add_space(5.0);
+                ui.label(tr!("preview.hint"));
+                ui.horizontal(|ui| {
+                    retake = ui.button(tr!("preview.retake")).on_hover_text(tr!("preview.retake_hint")).clicked();
+                });
+            });
+
+        if retake {
+            // Keeps the divider where it was
+            *self = Self {
+                divider: self.divider,
+                ..Self::new(self.monitor.clone())
+            };
+        }
+        open
+    }
+
+    /// Collects a finished pass and starts the next one once the settings stopped changing.
+    fn update(&mut self, ctx: &egui::Context, state: &StateManager) {
+        let Some(Ok(ref screenshot)) = self.screenshot else {
+            return;
+        };
+
+        if let Some((settings, receiver)) = self.pending.take() {
+            match receiver.try_recv() {
+                Ok(Ok(image)) => {
+                    self.corrected = Some(load_texture(ctx, "preview_after", &image));
+                    self.error = None;
+                    self.rendered = Some(settings);
+                }
+                Ok(Err(e)) => {
+                    self.error = Some(tr!("preview.correction_failed", error = format!("{:#}", e)));
+                    self.rendered = Some(settings);
+                }
+                Err(TryRecvError::Disconnected) => {}
+                Err(TryRecvError::Empty) => {
+                    self.pending = Some((settings, receiver));
+                    ctx.request_repaint_after(Duration::from_millis(50));
+                    return;
+                }
+            }
+        }
+
+        let current = PreviewSettings::read(state);
+        if self.seen.as_ref().is_none_or(|(seen, _)| *seen != current) {
+            self.seen = Some((current.clone(), Instant::now()));
+        }
+        if self.rendered.as_ref() == Some(&current) {
+            return;
+        }
+        let since_change = self.seen.as_ref().map_or(DEBOUNCE, |(_, changed_at)| changed_at.elapsed());
+        if self.rendered.is_some() && since_change < DEBOUNCE {
+            ctx.request_repaint_after(DEBOUNCE - since_change);
+            return;
+        }
+
+        let (spectrum_path, noise_path) = match current.files(state) {
+            Ok(Some(files)) => files,
+            Ok(None) => {
+                self.corrected = None;
+                self.error = Some(tr!("preview.no_spectrum"));
+                self.rendered = Some(current);
+                return;
+            }
+            Err(e) => {
+                self.error = Some(tr!("preview.correction_failed", error = format!("{:#}", e)));
+                self.rendered = Some(current);
+                return;
+            }
+        };
+
+        let (tx, rx) = crossbeam_channel::bounded(1);
+        let job = (current.clone(), Arc::clone(&screenshot.image), screenshot.screen_width);
+        let repaint = ctx.clone();
+        std::thread::spawn(move || {
+            let (settings, image, screen_width) = job;
+            let _ = tx.send(correct(&settings, spectrum_path, noise_path, &image, screen_width));
+            repaint.request_repaint();
+        });
+        self.pending = Some((current, rx));
+    }
+}
+
+/// Original left of the divider, corrected right of it.
+fn show_comparison(ui: &mut egui::Ui, screenshot: &Screenshot, corrected: Option<&egui::TextureHandle>, divider: &mut f32) {
+    let [width, height] = screenshot.texture.size();
+    let display_width = ui.available_width().min(width as f32);
+    let size = egui::vec2(display_width, display_width * height as f32 / width as f32);
+    let (rect, response) = ui.allocate_exact_size(size, egui::Sense::click_and_drag());
+    let response = response.on_hover_cursor(egui::CursorIcon::ResizeHorizontal);
+    if let Some(pointer) = response.interact_pointer_pos() {
+        *divider = ((pointer.x - rect.left()) / rect.width()).clamp(0.0, 1.0);
+    }
+
+    let split = rect.left() + rect.width() * *divider;
+    let uv = |from: f32, to: f32| egui::Rect::from_min_max(egui::pos2(from, 0.0), egui::pos2(to, 1.0));
+    let painter = ui.painter_at(rect);
+    painter.image(
+        screenshot.texture.id(),
+        egui::Rect::from_min_max(rect.min, egui::pos2(split, rect.bottom())),
+        uv(0.0, *divider),
+        egui::Color32::WHITE,
+    );
+    // Until the first pass is done the original shows on both sides
+    let after = corrected.unwrap_or(&screenshot.texture);
+    painter.image(
+        after.id(),
+        egui::Rect::from_min_max(egui::pos2(split, rect.top()), rect.max),
+        uv(*divider, 1.0),
+        egui::Color32::WHITE,
+    );
+    painter.vline(split, rect.y_range(), egui::Stroke::new(2.0, egui::Color32::WHITE));
+}
+
+fn take_screenshot(ctx: &egui::Context, monitor: &MonitorInfo) -> Result<Screenshot, String> {
+    let captured = capture_monitor(monitor).and_then(|image| {
+        // Protected content and some exclusive fullscreen games come out black
+        anyhow::ensure!(image.pixels().any(|p| p[0] != 0 || p[1] != 0 || p[2] != 0), tr!("preview.capture_black"));
+        Ok(image)
+    });
+    match captured {
+        Ok(image) => {
+            let screen_width = image.width();
+            let image = preview::downscale(image);
+            log_info!("Captured monitor {} for the preview, showing it at {}x{}", monitor.index, image.width(), image.height());
+            Ok(Screenshot {
+                texture: load_texture(ctx, "preview_before", &image),
+                image: Arc::new(image),
+                screen_width,
+            })
+        }
+        Err(e) => Err(format!("{:#}", e)),
+    }
+}
+
+fn correct(
+    settings: &PreviewSettings,
+    spectrum_path: PathBuf,
+    noise_path: Option<PathBuf>,
+    image: &RgbaImage,
+    screen_width: u32,
+) -> Result<RgbaImage> {
+    let mut corrected = image.clone();
+    load_correction(settings, spectrum_path, noise_path)?.apply(&mut corrected, screen_width);
+    Ok(corrected)
+}
+
+/// Loads the files of `settings` into a correction. Slow for large noise textures, so call it
+/// off the GUI thread.
+pub fn load_correction(settings: &PreviewSettings, spectrum_path: PathBuf, noise_path: Option<PathBuf>) -> Result<CpuCorrection> {
+    let pair = SpectrumPair::load_with_selection(spectrum_path, settings.spectrum_selection)?;
+    let noise = match noise_path {
+        Some(path) => Some(NoisePattern {
+            texture: NoiseTexture::load_with_max_size(path, settings.max_noise_size)?,
+            mapping: settings.noise_mapping,
+            intensity: settings.noise_intensity,
+            target: settings.noise_target,
+        }),
+        None => None,
+    };
+    Ok(CpuCorrection::new(&pair, settings.blend_weight, settings.hue_gate, noise)?.with_blend_ratio(settings.blend_ratio).with_posterize(settings.posterize_levels))
+}
+
+fn load_texture(ctx: &egui::Context, name: &str, image: &RgbaImage) -> egui::TextureHandle {
+    let size = [image.width() as usize, image.height() as usize];
+    ctx.load_texture(name, egui::ColorImage::from_rgba_unmultiplied(size, image.as_raw()), egui::TextureOptions::LINEAR)
+}
+
+/// Copies what `monitor` shows with a single GDI blit of the desktop. Unlike the overlay's
+/// capture this needs no Direct3D device, and the overlay itself doesn't show up in it.
+#[cfg(windows)]
+fn capture_monitor(monitor: &MonitorInfo) -> Result<RgbaImage> {
+    let (width, height) = (monitor.width, monitor.height);
+    anyhow::ensure!(width > 0 && height > 0, "Monitor reports no size");
+
+    unsafe {
+        let screen_dc = GetDC(None);
+        anyhow::ensure!(!screen_dc.is_invalid(), "Failed to get the screen device context");
+        let memory_dc = CreateCompatibleDC(Some(screen_dc));
+        let bitmap = CreateCompatibleBitmap(screen_dc, width, height);
+        let previous = SelectObject(memory_dc, bitmap.into());
+
+        let copied = BitBlt(memory_dc, 0, 0, width, height, Some(screen_dc), monitor.left, monitor.top, SRCCOPY | CAPTUREBLT);
+        let mut info = BITMAPINFO {
+            bmiHeader: BITMAPINFOHEADER {
+                biSize: std::mem::size_of::<BITMAPINFOHEADER>() as u32,
+                biWidth: width,
+                // Negative for rows top to bottom
+                biHeight: -height,
+                biPlanes: 1,
+                biBitCount: 32,
+                biCompression: BI_RGB.0,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut pixels = vec![0u8; width as usize * height as usize * 4];
+        let rows = match copied {
+            Ok(()) => GetDIBits(memory_dc, bitmap, 0, height as u32, Some(pixels.as_mut_ptr() as *mut _), &mut info, DIB_RGB_COLORS),
+            Err(_) => 0,
+        };
+
+        SelectObject(memory_dc, previous);
+        let _ = DeleteObject(bitmap.into());
+        let _ = DeleteDC(memory_dc);
+        ReleaseDC(None, screen_dc);
+
+        copied.context("Failed to copy the screen")?;
+        anyhow::ensure!(rows == height, "Failed to read the captured screen");
+
+        // BGRX to RGBA
+        for pixel in pixels.chunks_exact_mut(4) {
+            pixel.swap(0, 2);
+            pixel[3] = 255;
+        }
+        RgbaImage::from_raw(width as u32, height as u32, pixels).context("Captured screen has an unexpected size")
+    }
+}
+
+#[cfg(not(windows))]
+fn capture_monitor(_monitor: &MonitorInfo) -> Result<RgbaImage> {
+    anyhow::bail!("Screen capture is only supported on Windows")
+}
@@ -0,0 +1,305 @@
+use crate::graphics_capture::GraphicsCapturer;
+use crate::overlay;
+use anyhow::{Context, Result};
+use chromabridge::{log_info, DataDirs, SpectrumFile, StateManager};
+use crossbeam_channel::RecvTimeoutError;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use windows::{
+    core::Interface,
+    Win32::Graphics::Direct3D::{D3D_DRIVER_TYPE_HARDWARE, D3D_DRIVER_TYPE_UNKNOWN},
+    Win32::Graphics::Direct3D11::ID3D11Device,
+    Win32::Graphics::Dxgi::{CreateDXGIFactory1, IDXGIAdapter, IDXGIFactory1, IDXGIOutput1, DXGI_ADAPTER_FLAG_SOFTWARE},
+    Win32::System::Com::{CoInitializeEx, COINIT_MULTITHREADED},
+    Win32::System::Console::{AttachConsole, GetStdHandle, ATTACH_PARENT_PROCESS, STD_OUTPUT_HANDLE},
+};
+
+/// Longest the whole self-check may take. Checks still running by then, e.g. on a hung driver,
+/// are reported as timed out and left to finish on their own.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Names the probe file written to check folder access; removed right away.
+const PROBE_FILE: &str = ".self-check.tmp";
+
+/// The outcome of one verification.
+#[derive(Debug, Clone)]
+pub struct CheckResult {
+    /// What was checked, e.g. "Desktop duplication: \\.\DISPLAY1".
+    pub name: String,
+    /// What was found, or the error.
+    pub outcome: std::result::Result<String, String>,
+}
+
+impl CheckResult {
+    fn new(name: impl Into<String>, outcome: Result<String>) -> Self {
+        Self {
+            name: name.into(),
+            outcome: outcome.map_err(|e| format!("{:#}", e)),
+        }
+    }
+
+    pub fn passed(&self) -> bool {
+        self.outcome.is_ok()
+    }
+}
+
+/// Checks run together on one thread; `name` stands for all of them if they time out.
+struct Check {
+    name: &'static str,
+    run: Box<dyn FnOnce() -> Vec<CheckResult> + Send>,
+}
+
+impl Check {
+    fn new(name: &'static str, run: impl FnOnce() -> Vec<CheckResult> + Send + 'static) -> Self {
+        Self { name, run: Box::new(run) }
+    }
+}
+
+/// Verifies the rendering stack and the app data in `dirs`, in parallel so failing probes can't
+/// add up past `TIMEOUT`.
+///
+/// Nothing is left behind: devices, swap chains and duplications are released as soon as they
+/// were created, the database is only read, and the overlay is never started.
+pub fn run(dirs: &DataDirs) -> Vec<CheckResult> {
+    let started = Instant::now();
+    let spectrums_dir = dirs.assets.join("spectrums");
+    let folders = [
+        dirs.local.clone(),
+        dirs.local.join("logs"),
+        spectrums_dir.clone(),
+        dirs.assets.join("noise"),
+    ];
+    let database_dir = dirs.local.clone();
+
+    let checks = vec![
+        Check::new("Direct3D 11 devices", || unsafe { check_devices() }),
+        Check::new("Composition swap chain", || vec![CheckResult::new("Composition swap chain", unsafe { check_swap_chain() })]),
+        Check::new("Shader compilation", || vec![CheckResult::new("Shader compilation", overlay::compile_embedded_shaders().map(|_| "Compiled".to_string()))]),
+        Check::new("Desktop duplication", || unsafe { check_outputs("Desktop duplication", check_duplication) }),
+        Check::new("Windows.Graphics.Capture", || unsafe { check_graphics_capture() }),
+        Check::new("Spectrum assets", move || vec![CheckResult::new("Spectrum assets", check_spectra(&spectrums_dir))]),
+        Check::new("Write access", move || folders.iter().map(|folder| CheckResult::new(format!("Write access: {}", folder.display()), check_write_access(folder))).collect()),
+        Check::new("State database", move || vec![CheckResult::new("State database", check_database(&database_dir))]),
+    ];
+
+    let pending: Vec<_> = checks
+        .into_iter()
+        .map(|check| {
+            let (tx, rx) = crossbeam_channel::bounded(1);
+            let run = check.run;
+            let spawned = std::thread::Builder::new().name("self-check".into()).spawn(move || {
+                // WinRT and DXGI calls need the thread in an apartment
+                unsafe {
+                    let _ = CoInitializeEx(None, COINIT_MULTITHREADED);
+                }
+                let _ = tx.send(run());
+            });
+            (check.name, spawned.map(|_| rx))
+        })
+        .collect();
+
+    let deadline = started + TIMEOUT;
+    let mut results = Vec::new();
+    for (name, rx) in pending {
+        let outcome = match rx {
+            Ok(rx) => rx.recv_deadline(deadline),
+            Err(e) => {
+                results.push(CheckResult::new(name, Err(anyhow::anyhow!("Failed to start: {}", e))));
+                continue;
+            }
+        };
+        match outcome {
+            Ok(group) => results.extend(group),
+            Err(RecvTimeoutError::Timeout) => results.push(CheckResult::new(name, Err(anyhow::anyhow!("Did not finish within {}s", TIMEOUT.as_secs())))),
+            Err(RecvTimeoutError::Disconnected) => results.push(CheckResult::new(name, Err(anyhow::anyhow!("Crashed")))),
+        }
+    }
+
+    let passed = results.iter().filter(|result| result.passed()).count();
+    log_info!("Self-check: {} of {} passed in {}ms", passed, results.len(), started.elapsed().as_millis());
+    for result in results.iter().filter(|result| !result.passed()) {
+        log_info!("Self-check failed - {}: {}", result.name, result.outcome.as_ref().unwrap_err());
+    }
+    results
+}
+
+/// A markdown block for pasting into an issue.
+pub fn report(results: &[CheckResult]) -> String {
+    // Keeps table cells on one line and their pipes from ending the cell
+    let cell = |text: &str| text.replace('|', "\\|").replace(['\r', '\n'], " ");
+
+    let passed = results.iter().filter(|result| result.passed()).count();
+    let mut report = format!(
+        "### ChromaBridge self-check\n\nVersion {}, {} of {} checks passed.\n\n| | Check | Result |\n|---|---|---|\n",
+        env!("CARGO_PKG_VERSION"),
+        passed,
+        results.len()
+    );
+    for result in results {
+        let (marker, detail) = match &result.outcome {
+            Ok(detail) => ("✅", detail),
+            Err(error) => ("❌", error),
+        };
+        report.push_str(&format!("| {} | {} | {} |\n", marker, cell(&result.name), cell(detail)));
+    }
+    report
+}
+
+/// `--self-check`: prints the results to the console it was started from and returns the exit
+/// code, 1 if anything failed.
+pub fn run_from_command_line() -> i32 {
+    unsafe {
+        // Release builds are GUI programs without a console of their own, unless output is redirected
+        if GetStdHandle(STD_OUTPUT_HANDLE).is_ok_and(|handle| handle.is_invalid()) {
+            let _ = AttachConsole(ATTACH_PARENT_PROCESS);
+        }
+    }
+
+    let results = match StateManager::default_data_dirs() {
+        Ok(dirs) => run(&dirs),
+        Err(e) => vec![CheckResult::new("App data folder", Err(e))],
+    };
+    for result in &results {
+        match &result.outcome {
+            Ok(detail) => println!("PASS  {}: {}", result.name, detail),
+            Err(error) => println!("FAIL  {}: {}", result.name, error),
+        }
+    }
+
+    let failed = results.iter().filter(|result| !result.passed()).count();
+    println!("{} of {} checks passed", results.len() - failed, results.len());
+    i32::from(failed > 0)
+}
+
+/// Hardware adapters, named by their description. The software rasterizer is left out, the
+/// overlay never renders on it.
+unsafe fn adapters() -> Result<Vec<(String, IDXGIAdapter)>> {
+    let factory: IDXGIFactory1 = CreateDXGIFactory1().context("Failed to create DXGI factory")?;
+    let mut adapters = Vec::new();
+    let mut index = 0;
+    while let Ok(adapter) = factory.EnumAdapters1(index) {
+        index += 1;
+        let desc = adapter.GetDesc1()?;
+        if desc.Flags & DXGI_ADAPTER_FLAG_SOFTWARE.0 as u32 != 0 {
+            continue;
+        }
+        let len = desc.Description.iter().position(|&c| c == 0).unwrap_or(desc.Description.len());
+        adapters.push((String::from_utf16_lossy(&desc.Description[..len]), adapter.cast()?));
+    }
+    anyhow::ensure!(!adapters.is_empty(), "No hardware adapter found");
+    Ok(adapters)
+}
+
+unsafe fn check_devices() -> Vec<CheckResult> {
+    let adapters = match adapters() {
+        Ok(adapters) => adapters,
+        Err(e) => return vec![CheckResult::new("Direct3D 11 device", Err(e))],
+    };
+    adapters
+        .iter()
+        .map(|(name, adapter)| {
+            let device = overlay::create_d3d_device_on(Some(adapter), D3D_DRIVER_TYPE_UNKNOWN);
+            let outcome = device.map(|(device, _)| format!("Feature level {}", overlay::feature_level_name(device.GetFeatureLevel())));
+            CheckResult::new(format!("Direct3D 11 device: {}", name), outcome)
+        })
+        .collect()
+}
+
+/// On the default adapter, like the overlay.
+unsafe fn check_swap_chain() -> Result<String> {
+    let (device, _) = overlay::create_d3d_device_on(None, D3D_DRIVER_TYPE_HARDWARE)?;
+    overlay::probe_swap_chain(&device)?;
+    Ok("Created".to_string())
+}
+
+/// Runs `check` on every monitor, with a device on the adapter it is connected to.
+unsafe fn check_outputs(kind: &str, check: unsafe fn(&ID3D11Device, &IDXGIAdapter, u32) -> Result<String>) -> Vec<CheckResult> {
+    let adapters = match adapters() {
+        Ok(adapters) => adapters,
+        Err(e) => return vec![CheckResult::new(kind, Err(e))],
+    };
+
+    let mut results = Vec::new();
+    for (name, adapter) in &adapters {
+        let mut index = 0;
+        while let Ok(output) = adapter.EnumOutputs(index) {
+            let display = output
+                .GetDesc()
+                .map(|desc| {
+                    let len = desc.DeviceName.iter().position(|&c| c == 0).unwrap_or(desc.DeviceName.len());
+                    String::from_utf16_lossy(&desc.DeviceName[..len])
+                })
+                .unwrap_or_else(|_| format!("{} output {}", name, index + 1));
+            let outcome = overlay::create_d3d_device_on(Some(adapter), D3D_DRIVER_TYPE_UNKNOWN)
+                .and_then(|(device, _)| check(&device, adapter, index));
+            results.push(CheckResult::new(format!("{}: {}", kind, display), outcome));
+            index += 1;
+        }
+    }
+    if results.is_empty() {
+        results.push(CheckResult::new(kind, Err(anyhow::anyhow!("No monitor is connected to a hardware adapter"))));
+    }
+    results
+}
+
+unsafe fn check_duplication(device: &ID3D11Device, adapter: &IDXGIAdapter, index: u32) -> Result<String> {
+    let output: IDXGIOutput1 = adapter.EnumOutputs(index)?.cast()?;
+    // Fails while other apps hold all duplications of the output, and on the overlay's own output
+    // while it runs, which then falls back to Windows.Graphics.Capture
+    let duplication = output.DuplicateOutput(device)?;
+    let desc = duplication.GetDesc();
+    Ok(format!("{}x{}", desc.ModeDesc.Width, desc.ModeDesc.Height))
+}
+
+unsafe fn check_graphics_capture() -> Vec<CheckResult> {
+    if !GraphicsCapturer::is_supported() {
+        return vec![CheckResult::new("Windows.Graphics.Capture", Err(anyhow::anyhow!("Not supported, needs Windows 10 1903 or later")))];
+    }
+    check_outputs("Windows.Graphics.Capture", check_capture)
+}
+
+unsafe fn check_capture(device: &ID3D11Device, _adapter: &IDXGIAdapter, index: u32) -> Result<String> {
+    GraphicsCapturer::probe(device, index as usize).map(|_| "Available".to_string())
+}
+
+/// Loads every spectrum file, including the ones the spectrum list leaves out for being invalid.
+fn check_spectra(spectrums_dir: &Path) -> Result<String> {
+    fn collect(dir: &Path, prefix: &str, files: &mut Vec<(String, std::path::PathBuf)>) {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            if path.is_dir() {
+                collect(&path, &format!("{}{}/", prefix, file_name), files);
+            } else if let Some(stem) = file_name.strip_suffix(".json") {
+                files.push((format!("{}{}", prefix, stem), path));
+            }
+        }
+    }
+
+    anyhow::ensure!(spectrums_dir.is_dir(), "{} does not exist", spectrums_dir.display());
+    let mut files = Vec::new();
+    collect(spectrums_dir, "", &mut files);
+    anyhow::ensure!(!files.is_empty(), "No spectrum files in {}", spectrums_dir.display());
+
+    let invalid: Vec<String> = files
+        .iter()
+        .filter_map(|(name, path)| SpectrumFile::load(path).err().map(|e| format!("{}: {:#}", name, anyhow::Error::from(e))))
+        .collect();
+    anyhow::ensure!(invalid.is_empty(), "{} of {} invalid - {}", invalid.len(), files.len(), invalid.join("; "));
+    Ok(format!("{} valid", files.len()))
+}
+
+fn check_write_access(folder: &Path) -> Result<String> {
+    anyhow::ensure!(folder.is_dir(), "Folder does not exist");
+    let probe = folder.join(PROBE_FILE);
+    std::fs::write(&probe, b"ChromaBridge").context("Failed to write")?;
+    std::fs::remove_file(&probe).context("Failed to remove the probe file")?;
+    Ok("Writable".to_string())
+}
+
+fn check_database(app_data_dir: &Path) -> Result<String> {
+    StateManager::check_database(app_data_dir).map(|version| format!("Healthy, schema version {}", version))
+}
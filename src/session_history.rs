@@ -0,0 +1,322 @@
+use chrono::{DateTime, Local, NaiveDate};
+use std::time::{Duration, Instant};
+
+/// How often the running overlay's active time is stored, bounding what a crash loses.
+pub const ACTIVE_TIME_SAVE_INTERVAL: Duration = Duration::from_secs(5 * 60);
+/// Longest gap between two counts still taken as active; counts come every loop of the main
+/// thread, so anything longer means the system was suspended.
+const MAX_COUNT_GAP: Duration = Duration::from_secs(60);
+
+/// Width of a frame time histogram bucket.
+const BUCKET_MS: f32 = 0.25;
+/// Buckets up to one second per frame; slower frames land in the last one.
+const BUCKETS: usize = 4000;
+
+/// One overlay run, or a slice of a long one, as stored in the performance history.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionSummary {
+    pub started_at: DateTime<Local>,
+    pub ended_at: DateTime<Local>,
+    pub monitor: usize,
+    pub spectrum: Option<String>,
+    pub average_fps: f32,
+    /// Frame rate over the slowest 1% of frames.
+    pub low_fps: f32,
+    pub max_frame_time_ms: f32,
+    pub error_count: u32,
+    /// GPU device rebuilds after the device was removed.
+    pub recovery_count: u32,
+}
+
+impl SessionSummary {
+    pub fn duration(&self) -> Duration {
+        (self.ended_at - self.started_at).to_std().unwrap_or_default()
+    }
+}
+
+/// Aggregates the frames of the running overlay into `SessionSummary`s. Frame times go into a
+/// fixed histogram, so long sessions cost no more memory than short ones.
+pub struct SessionRecorder {
+    monitor: usize,
+    started_at: DateTime<Local>,
+    started: Instant,
+    frames: u64,
+    total_ms: f64,
+    max_frame_time_ms: f32,
+    histogram: Vec<u32>,
+    error_count: u32,
+    recovery_count: u32,
+}
+
+impl SessionRecorder {
+    pub fn new(monitor: usize) -> Self {
+        Self {
+            monitor,
+            started_at: Local::now(),
+            started: Instant::now(),
+            frames: 0,
+            total_ms: 0.0,
+            max_frame_time_ms: 0.0,
+            histogram: vec![0; BUCKETS],
+            error_count: 0,
+            recovery_count: 0,
+        }
+    }
+
+    /// Time since the recorder was created or last summarized.
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Adds one presented frame, measured from the previous present.
+    pub fn record_frame(&mut self, frame_time_ms: f32) {
+        self.frames += 1;
+        self.total_ms += frame_time_ms as f64;
+        self.max_frame_time_ms = self.max_frame_time_ms.max(frame_time_ms);
+        let bucket = ((frame_time_ms / BUCKET_MS) as usize).min(BUCKETS - 1);
+        self.histogram[bucket] += 1;
+    }
+
+    pub fn record_error(&mut self) {
+        self.error_count += 1;
+    }
+
+    pub fn record_recovery(&mut self) {
+        self.recovery_count += 1;
+    }
+
+    /// Summarizes everything since the last summary and starts counting afresh.
+    /// `None` if no frame was presented in that time.
+    pub fn take_summary(&mut self, spectrum: Option<String>) -> Option<SessionSummary> {
+        let finished = std::mem::replace(self, Self::new(self.monitor));
+        if finished.frames == 0 || finished.total_ms <= 0.0 {
+            return None;
+        }
+
+        Some(SessionSummary {
+            started_at: finished.started_at,
+            ended_at: self.started_at,
+            monitor: finished.monitor,
+            spectrum,
+            average_fps: (finished.frames as f64 * 1000.0 / finished.total_ms) as f32,
+            low_fps: finished.low_fps(),
+            max_frame_time_ms: finished.max_frame_time_ms,
+            error_count: finished.error_count,
+            recovery_count: finished.recovery_count,
+        })
+    }
+
+    /// Average rate of the slowest 1% of frames, at least one frame.
+    fn low_fps(&self) -> f32 {
+        let wanted = self.frames.div_ceil(100);
+        let mut counted = 0u64;
+        let mut sum_ms = 0.0f64;
+
+        for (bucket, &count) in self.histogram.iter().enumerate().rev() {
+            let taken = (count as u64).min(wanted - counted);
+            // Bucket midpoint; the slowest bucket is open-ended, so the max stands in for it
+            let frame_ms = if bucket == BUCKETS - 1 {
+                self.max_frame_time_ms
+            } else {
+                (bucket as f32 + 0.5) * BUCKET_MS
+            };
+            sum_ms += taken as f64 * frame_ms as f64;
+            counted += taken;
+            if counted == wanted {
+                break;
+            }
+        }
+
+        if sum_ms > 0.0 { (counted as f64 * 1000.0 / sum_ms) as f32 } else { 0.0 }
+    }
+}
+
+/// How long the overlay has been running, and in total on the current calendar day.
+///
+/// Each count adds the smaller of the wall clock and monotonic time since the previous one, so
+/// neither a clock change nor a suspend counts as active time.
+#[derive(Debug)]
+pub struct ActiveTime {
+    day: NaiveDate,
+    /// The day's total as stored.
+    saved: Duration,
+    /// Counted since the last save.
+    unsaved: Duration,
+    /// When the overlay started, and where counting left off on both clocks; `None` while stopped.
+    running: Option<(Instant, Instant, DateTime<Local>)>,
+    last_save: Instant,
+}
+
+impl ActiveTime {
+    /// `saved` is what was stored for `day` so far.
+    pub fn new(day: NaiveDate, saved: Duration, now: Instant) -> Self {
+        Self {
+            day,
+            saved,
+            unsaved: Duration::ZERO,
+            running: None,
+            last_save: now,
+        }
+    }
+
+    pub fn start(&mut self, now: Instant, wall: DateTime<Local>) {
+        if self.running.is_none() {
+            self.running = Some((now, now, wall));
+        }
+    }
+
+    /// Time since `start`, `None` while stopped.
+    pub fn uptime(&self, now: Instant) -> Option<Duration> {
+        self.running.map(|(started, _, _)| now.saturating_duration_since(started))
+    }
+
+    /// The total of `day`, including what is not stored yet. Zero for a day not counted yet,
+    /// e.g. when the overlay was stopped since before midnight.
+    pub fn total(&self, day: NaiveDate) -> Duration {
+        if day == self.day { self.saved + self.unsaved } else { Duration::ZERO }
+    }
+
+    /// Forgets the total of `day`; the stored one has to be cleared separately.
+    pub fn reset(&mut self, day: NaiveDate) {
+        if day == self.day {
+            self.saved = Duration::ZERO;
+            self.unsaved = Duration::ZERO;
+        }
+    }
+
+    /// Counts the time since the last count. Returns what to store and for which day once the
+    /// day changed or `ACTIVE_TIME_SAVE_INTERVAL` passed since the last save.
+    pub fn count(&mut self, now: Instant, wall: DateTime<Local>) -> Option<(NaiveDate, Duration)> {
+        let (_, last_instant, last_wall) = self.running.as_mut()?;
+        let monotonic = now.saturating_duration_since(*last_instant);
+        let elapsed = monotonic.min((wall - *last_wall).to_std().unwrap_or_default());
+        *last_instant = now;
+        *last_wall = wall;
+
+        // Whatever crossed midnight goes to the new day; counts are far apart by seconds at most
+        let finished_day = if wall.date_naive() != self.day {
+            let finished = self.take_unsaved(now);
+            self.day = wall.date_naive();
+            self.saved = Duration::ZERO;
+            finished
+        } else {
+            None
+        };
+        if elapsed <= MAX_COUNT_GAP {
+            self.unsaved += elapsed;
+        }
+
+        if finished_day.is_some() {
+            return finished_day;
+        }
+        if now.saturating_duration_since(self.last_save) >= ACTIVE_TIME_SAVE_INTERVAL {
+            return self.take_unsaved(now);
+        }
+        None
+    }
+
+    /// Counts up to now and stops; returns what is left to store, per day.
+    pub fn stop(&mut self, now: Instant, wall: DateTime<Local>) -> Vec<(NaiveDate, Duration)> {
+        let finished_day = self.count(now, wall);
+        self.running = None;
+        finished_day.into_iter().chain(self.take_unsaved(now)).collect()
+    }
+
+    fn take_unsaved(&mut self, now: Instant) -> Option<(NaiveDate, Duration)> {
+        self.last_save = now;
+        if self.unsaved.is_zero() {
+            return None;
+        }
+        let unsaved = std::mem::take(&mut self.unsaved);
+        self.saved += unsaved;
+        Some((self.day, unsaved))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn summary_reports_average_and_one_percent_lows() {
+        let mut recorder = SessionRecorder::new(1);
+        for _ in 0..990 {
+            recorder.record_frame(4.0);
+        }
+        // The slowest 1%: ten 20ms hitches
+        for _ in 0..10 {
+            recorder.record_frame(20.0);
+        }
+        recorder.record_error();
+        recorder.record_recovery();
+
+        let summary = recorder.take_summary(Some("deutan".into())).unwrap();
+        assert_eq!(summary.monitor, 1);
+        assert_eq!(summary.spectrum.as_deref(), Some("deutan"));
+        assert!((summary.average_fps - 1000.0 / 4.16).abs() < 0.5, "{}", summary.average_fps);
+        assert!((summary.low_fps - 1000.0 / 20.125).abs() < 0.5, "{}", summary.low_fps);
+        assert_eq!(summary.max_frame_time_ms, 20.0);
+        assert_eq!((summary.error_count, summary.recovery_count), (1, 1));
+    }
+
+    #[test]
+    fn summaries_start_a_new_slice() {
+        let mut recorder = SessionRecorder::new(0);
+        assert!(recorder.take_summary(None).is_none());
+
+        recorder.record_frame(2000.0);
+        let summary = recorder.take_summary(None).unwrap();
+        // Beyond the histogram, the max stands in for the slowest frames
+        assert_eq!(summary.low_fps, 0.5);
+        assert!(recorder.take_summary(None).is_none());
+    }
+
+    #[test]
+    fn active_time_leaves_out_suspends_and_saves_periodically() {
+        let wall = Local.with_ymd_and_hms(2026, 3, 2, 12, 0, 0).unwrap();
+        let start = Instant::now();
+        let at = |minutes: u64, seconds: u64| {
+            let offset = Duration::from_secs(minutes * 60 + seconds);
+            (start + offset, wall + chrono::Duration::from_std(offset).unwrap())
+        };
+
+        let mut active = ActiveTime::new(wall.date_naive(), Duration::from_secs(600), start);
+        assert_eq!(active.uptime(start), None);
+        active.start(start, wall);
+        let (now, now_wall) = at(0, 30);
+        assert_eq!(active.count(now, now_wall), None);
+        assert_eq!(active.uptime(now), Some(Duration::from_secs(30)));
+        assert_eq!(active.total(wall.date_naive()), Duration::from_secs(630));
+
+        // An hour asleep between two counts adds nothing
+        let (now, now_wall) = at(60, 30);
+        assert_eq!(active.count(now, now_wall), Some((wall.date_naive(), Duration::from_secs(30))));
+        assert_eq!(active.total(wall.date_naive()), Duration::from_secs(630));
+
+        // Neither does the wall clock set back, nor jumping forward again beyond the monotonic time
+        let (now, _) = at(60, 40);
+        assert_eq!(active.count(now, wall), None);
+        let (now, now_wall) = at(60, 50);
+        assert_eq!(active.stop(now, now_wall), vec![(wall.date_naive(), Duration::from_secs(10))]);
+        assert_eq!(active.uptime(now), None);
+    }
+
+    #[test]
+    fn active_time_starts_over_at_midnight() {
+        let evening = Local.with_ymd_and_hms(2026, 3, 2, 23, 59, 50).unwrap();
+        let start = Instant::now();
+        let mut active = ActiveTime::new(evening.date_naive(), Duration::ZERO, start);
+        active.start(start, evening);
+        assert_eq!(active.count(start + Duration::from_secs(5), evening + chrono::Duration::seconds(5)), None);
+
+        let saved = active.stop(start + Duration::from_secs(20), evening + chrono::Duration::seconds(20));
+        let next_day = evening.date_naive().succ_opt().unwrap();
+        assert_eq!(saved, vec![(evening.date_naive(), Duration::from_secs(5)), (next_day, Duration::from_secs(15))]);
+        assert_eq!(active.total(next_day), Duration::from_secs(15));
+        assert_eq!(active.total(evening.date_naive()), Duration::ZERO);
+
+        active.reset(next_day);
+        assert_eq!(active.total(next_day), Duration::ZERO);
+    }
+}
@@ -0,0 +1,78 @@
+use crate::gui::SettingsGui;
+
+/// Where a setting is drawn in the settings window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SettingsSection {
+    /// The grid below the overlay button; rows draw their label in the first column.
+    Correction,
+    Advanced,
+    /// Only shown once developer mode is unlocked.
+    Developer,
+}
+
+/// One row of the settings window, declared in `SettingsGui::build_settings` so the search box
+/// filters rows by their data rather than by checks spread through the drawing code.
+pub struct Setting {
+    pub section: SettingsSection,
+    /// Sub-heading drawn above a run of rows sharing it, e.g. "System".
+    pub group: Option<String>,
+    pub label: String,
+    /// Extra space separated search terms, e.g. "battery fps" for the performance settings.
+    pub keywords: &'static str,
+    /// Draws the row given its label; correction rows end their own grid row.
+    pub show: Box<dyn Fn(&mut SettingsGui, &mut egui::Ui, &str)>,
+}
+
+impl Setting {
+    pub fn new(
+        section: SettingsSection,
+        label: impl Into<String>,
+        keywords: &'static str,
+        show: impl Fn(&mut SettingsGui, &mut egui::Ui, &str) + 'static,
+    ) -> Self {
+        Self {
+            section,
+            group: None,
+            label: label.into(),
+            keywords,
+            show: Box::new(show),
+        }
+    }
+
+    pub fn in_group(mut self, group: impl Into<String>) -> Self {
+        self.group = Some(group.into());
+        self
+    }
+
+    /// Case-insensitive; every word of `query` must appear in the label, group or keywords.
+    /// An empty query matches every row.
+    pub fn matches(&self, query: &str) -> bool {
+        let text = format!("{} {} {}", self.label, self.group.as_deref().unwrap_or_default(), self.keywords).to_lowercase();
+        query.to_lowercase().split_whitespace().all(|word| text.contains(word))
+    }
+}
+
+/// The rows of `section` matching `query`, in declaration order.
+pub fn matching<'a>(settings: &'a [Setting], section: SettingsSection, query: &'a str) -> impl Iterator<Item = &'a Setting> {
+    settings.iter().filter(move |setting| setting.section == section && setting.matches(query))
+}
+
+/// Draws the matching rows of a collapsible section: `spacing` between rows, except within a
+/// group, and each group's heading above its first matching row.
+pub fn show_rows(gui: &mut SettingsGui, ui: &mut egui::Ui, settings: &[Setting], section: SettingsSection, query: &str, spacing: f32) {
+    let mut previous_group: Option<Option<&str>> = None;
+
+    for setting in matching(settings, section, query) {
+        let group = setting.group.as_deref();
+        let same_group = group.is_some() && previous_group == Some(group);
+        if previous_group.is_some() && !same_group {
+            ui.add_space(spacing);
+        }
+        if let (Some(heading), false) = (group, same_group) {
+            ui.label(heading);
+        }
+        previous_group = Some(group);
+
+        (setting.show)(gui, ui, &setting.label);
+    }
+}
@@ -0,0 +1,244 @@
+//! The constant buffer of the correction shaders, defined once for Rust and HLSL.
+//!
+//! `shader_params!` declares the `#[repr(C)]` struct the overlay uploads together with the
+//! matching `cbuffer` declaration, which the overlay inserts into shaders.hlsl in place of
+//! `CBUFFER_MARKER` before compiling. A compile-time check holds each field to HLSL's packing
+//! rules, so a field that would land at a different offset on the GPU fails the build instead of
+//! showing up as garbage parameters on screen.
+
+use serde::Serialize;
+
+/// Most regions cut out of the correction at once, the length of `exclusion_rects`.
+pub const MAX_EXCLUSION_RECTS: usize = 8;
+
+/// The line in shaders.hlsl replaced by the generated `cbuffer` declarations.
+pub const CBUFFER_MARKER: &str = "// @cbuffers";
+
+/// Bytes in one HLSL constant register; no field may straddle two.
+const REGISTER_SIZE: usize = 16;
+
+/// One field of a constant buffer as HLSL sees it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CbufferField {
+    /// The HLSL name, e.g. `exclusionRects`.
+    pub name: &'static str,
+    pub offset: usize,
+    pub size: usize,
+}
+
+/// Declares a `#[repr(C)]` constant buffer struct and its HLSL declaration from one field list.
+///
+/// Each field gives its Rust type and its HLSL declaration, e.g. `strength: f32 => "float strength"`.
+/// Padding is declared like any other field, with a name starting with `_`; the check fails on
+/// padding that Rust or HLSL would add implicitly. Arrays must be of 16-byte elements, which is how
+/// HLSL lays out every array element.
+macro_rules! shader_params {
+    (
+        $(#[$meta:meta])*
+        pub struct $name:ident : $register:literal {
+            $(
+                $(#[$field_meta:meta])*
+                $field:ident : $ty:ty => $hlsl:literal
+            ),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        #[repr(C)]
+        #[derive(Debug, Clone, Copy, Serialize)]
+        pub struct $name {
+            $(
+                $(#[$field_meta])*
+                pub $field: $ty,
+            )*
+        }
+
+        impl $name {
+            /// The `cbuffer` declaration of this struct for shaders.hlsl.
+            pub const HLSL: &'static str = concat!(
+                "cbuffer ", stringify!($name), " : register(", $register, ") {\n",
+                $("    ", $hlsl, ";\n",)*
+                "};\n",
+            );
+
+            /// Every field in declaration order, with where Rust puts it.
+            pub const FIELDS: &'static [CbufferField] = &[
+                $(CbufferField {
+                    name: hlsl_name($hlsl),
+                    offset: std::mem::offset_of!($name, $field),
+                    size: std::mem::size_of::<$ty>(),
+                },)*
+            ];
+        }
+
+        const _: () = {
+            let mut end = 0;
+            $(
+                assert!(std::mem::offset_of!($name, $field) == end, concat!(stringify!($name), "::", stringify!($field), " follows implicit padding, declare it"));
+                end = check_packing(std::mem::offset_of!($name, $field), std::mem::size_of::<$ty>(), $hlsl);
+            )*
+            assert!(end == std::mem::size_of::<$name>(), concat!(stringify!($name), " ends in implicit padding, declare it"));
+            assert!(end % REGISTER_SIZE == 0, concat!(stringify!($name), " must fill whole registers, pad the last one"));
+        };
+    };
+}
+
+shader_params! {
+    /// Everything the correction pass and `PS_BakeLut` read besides their textures.
+    #[derive(Default)]
+    pub struct SpectrumParams : "b0" {
+        /// Blend weight with the strength curve already applied, see `StrengthCurve`.
+        strength: f32 => "float strength",
+        use_dual_spectrum: i32 => "int useDualSpectrum",
+        use_noise_texture: i32 => "int useNoiseTexture",
+        show_debug_border: i32 => "int showDebugBorder",
+        posterize_levels: i32 => "int posterizeLevels",
+        /// One of the NOISE_* modes in shaders.hlsl.
+        noise_mapping: i32 => "int noiseMapping",
+        noise_size: [f32; 2] => "float2 noiseSize",
+        screen_size: [f32; 2] => "float2 screenSize",
+        temporal_smoothing: f32 => "float temporalSmoothing",
+        use_history: i32 => "int useHistory",
+        /// Clockwise quarter turns, see `capture_uv` in shaders.hlsl.
+        capture_rotation: i32 => "int captureRotation",
+        use_hue_gate: i32 => "int useHueGate",
+        hue_gate_start: f32 => "float hueGateStart",
+        hue_gate_end: f32 => "float hueGateEnd",
+        noise_spectra: i32 => "int noiseSpectra",
+        noise_intensity: f32 => "float noiseIntensity",
+        exclusion_count: i32 => "int exclusionCount",
+        use_lut: i32 => "int useLut",
+        /// (left, top, right, bottom) in screen UV.
+        exclusion_rects: [[f32; 4]; MAX_EXCLUSION_RECTS] => "float4 exclusionRects[8]",
+        /// `PS_BakeLut` only: the spectrum being baked.
+        bake_spectrum: i32 => "int bakeSpectrum",
+        /// `PS_BakeLut` only: the blue lattice point of the slice.
+        bake_slice: f32 => "float bakeSlice",
+        /// Correct only inside `target_rect`; an empty rectangle while the window is hidden.
+        use_target_rect: i32 => "int useTargetRect",
+        #[serde(skip)]
+        _padding: i32 => "int _padding",
+        /// Screen UV, like `exclusion_rects`.
+        target_rect: [f32; 4] => "float4 targetRect",
+        /// One of the DEBUG_* modes, see `DebugVisibility`.
+        debug_mode: i32 => "int debugMode",
+        /// One of the NOISE_TARGET_* modes, see `NoiseTarget`.
+        noise_target: i32 => "int noiseTarget",
+        /// Share of the secondary spectrum mixed in evenly when dual without a noise texture.
+        blend_ratio: f32 => "float blendRatio",
+        /// Rounds the buffer up to whole registers.
+        #[serde(skip)]
+        _tail_padding: i32 => "int _tailPadding",
+    }
+}
+
+/// Every `cbuffer` declaration shaders.hlsl uses, for `CBUFFER_MARKER`.
+pub fn hlsl_declarations() -> &'static str {
+    SpectrumParams::HLSL
+}
+
+/// The name in an HLSL declaration such as `float4 exclusionRects[8]`.
+const fn hlsl_name(declaration: &'static str) -> &'static str {
+    let bytes = declaration.as_bytes();
+    let mut start = 0;
+    while bytes[start] != b' ' {
+        start += 1;
+    }
+    start += 1;
+    let mut end = start;
+    while end < bytes.len() && bytes[end] != b'[' {
+        end += 1;
+    }
+    let (_, rest) = bytes.split_at(start);
+    let (name, _) = rest.split_at(end - start);
+    match std::str::from_utf8(name) {
+        Ok(name) => name,
+        Err(_) => panic!("HLSL declarations are ASCII"),
+    }
+}
+
+/// Size in bytes of a scalar or vector type such as `float2`, or 0 for anything else.
+const fn hlsl_type_size(declaration: &[u8]) -> usize {
+    let components = match declaration {
+        [b'f', b'l', b'o', b'a', b't', rest @ ..] | [b'u', b'i', b'n', b't', rest @ ..] | [b'i', b'n', b't', rest @ ..] => rest,
+        _ => return 0,
+    };
+    match components {
+        [b' ', ..] => 4,
+        [n @ b'2'..=b'4', b' ', ..] => 4 * (*n - b'0') as usize,
+        _ => 0,
+    }
+}
+
+/// The element count of an array declaration such as `float4 exclusionRects[8]`, or `None`.
+const fn hlsl_array_len(declaration: &[u8]) -> Option<usize> {
+    let mut i = 0;
+    while i < declaration.len() && declaration[i] != b'[' {
+        i += 1;
+    }
+    if i == declaration.len() {
+        return None;
+    }
+    let mut len = 0;
+    i += 1;
+    while declaration[i] != b']' {
+        len = len * 10 + (declaration[i] - b'0') as usize;
+        i += 1;
+    }
+    Some(len)
+}
+
+/// Checks a field at `offset` of `size` bytes against its HLSL declaration and HLSL's packing:
+/// the types must be the same size, a vector or scalar must not cross a register boundary, and
+/// an array must start a register and have 16-byte elements. Returns where the field ends.
+const fn check_packing(offset: usize, size: usize, declaration: &'static str) -> usize {
+    let bytes = declaration.as_bytes();
+    let element = hlsl_type_size(bytes);
+    assert!(element != 0, "Constant buffer fields must be float, int or uint scalars or vectors");
+    match hlsl_array_len(bytes) {
+        Some(len) => {
+            assert!(element == REGISTER_SIZE, "Constant buffer arrays must have 16-byte elements, e.g. float4");
+            assert!(offset % REGISTER_SIZE == 0, "Constant buffer arrays start a new register, pad before it");
+            assert!(size == element * len, "Constant buffer array length or type differs between Rust and HLSL");
+        }
+        None => {
+            assert!(size == element, "Constant buffer field type differs in size between Rust and HLSL");
+            assert!(offset / REGISTER_SIZE == (offset + size - 1) / REGISTER_SIZE, "Constant buffer field crosses a register boundary, pad before it");
+        }
+    }
+    offset + size
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hlsl_matches_the_struct() {
+        let hlsl = SpectrumParams::HLSL;
+        assert!(hlsl.starts_with("cbuffer SpectrumParams : register(b0) {\n    float strength;\n"));
+        assert!(hlsl.ends_with("    int _tailPadding;\n};\n"));
+        assert!(hlsl.contains(&format!("float4 exclusionRects[{}];", MAX_EXCLUSION_RECTS)));
+
+        let fields = SpectrumParams::FIELDS;
+        assert_eq!(fields.len(), hlsl.lines().count() - 2);
+        let rects = fields.iter().find(|f| f.name == "exclusionRects").unwrap();
+        assert_eq!((rects.offset, rects.size), (80, 16 * MAX_EXCLUSION_RECTS));
+        let target = fields.iter().find(|f| f.name == "targetRect").unwrap();
+        assert_eq!(target.offset % 16, 0);
+        assert_eq!(std::mem::size_of::<SpectrumParams>(), 256);
+    }
+
+    #[test]
+    fn shaders_take_the_generated_declarations() {
+        let source = include_str!("shaders.hlsl");
+        assert_eq!(source.matches(CBUFFER_MARKER).count(), 1);
+        assert!(!source.contains("cbuffer "), "shaders.hlsl declares a cbuffer of its own");
+    }
+
+    #[test]
+    fn padding_stays_out_of_serialized_params() {
+        let json = serde_json::to_value(SpectrumParams::default()).unwrap();
+        assert!(json.get("strength").is_some());
+        assert!(json.get("_padding").is_none() && json.get("_tail_padding").is_none());
+    }
+}
@@ -12,18 +12,44 @@ Texture2D screenTexture : register(t0);
 Texture2D spectrum1Texture : register(t1);
 Texture2D spectrum2Texture : register(t2);
 Texture2D noiseTexture : register(t3);
+Texture2D historyTexture : register(t4);
+Texture2D spectrum3Texture : register(t5);
+Texture2D spectrum4Texture : register(t6);
+// Baked corrections of spectra 1 to 4, only bound while useLut is set
+Texture3D lut1Texture : register(t7);
+Texture3D lut2Texture : register(t8);
+Texture3D lut3Texture : register(t9);
+Texture3D lut4Texture : register(t10);
 SamplerState textureSampler : register(s0);
 SamplerState spectrumSampler : register(s1);
+SamplerState noisePointSampler : register(s2);
 
-cbuffer SpectrumParams : register(b0) {
-    float strength;
-    int useDualSpectrum;
-    int useNoiseTexture;
-    float padding;
-};
+// The SpectrumParams cbuffer, generated from src/shader_params.rs and inserted in place of this line
+// @cbuffers
 
 static const float EPSILON = 0.0001;
 static const float HUE_MAX = 360.0;
+static const float DEBUG_BORDER_PX = 6.0;
+// Keep in sync with HUE_GATE_FEATHER
+static const float HUE_GATE_FEATHER = 10.0;
+// Keep in sync with MAX_NOISE_INDICES
+static const int MAX_NOISE_INDICES = 4;
+// Keep in sync with MAX_EXCLUSION_RECTS
+static const int MAX_EXCLUSION_RECTS = 8;
+// Keep in sync with LUT_SIZE
+static const float LUT_SIZE = 33.0;
+
+// Must match spectrum_params in overlay.rs
+static const int NOISE_STRETCH = 0;
+static const int NOISE_TILE = 1;
+static const int NOISE_PIXEL_EXACT = 2;
+static const int NOISE_TARGET_SPECTRA = 0;
+static const int NOISE_TARGET_ORIGINAL = 1;
+
+// Must match DebugVisibility::shader_mode
+static const int DEBUG_NORMAL = 0;
+static const int DEBUG_HALF_ALPHA = 1;
+static const int DEBUG_MAGENTA_TINT = 2;
 
 PS_INPUT VS_Main(VS_INPUT input) {
     PS_INPUT output;
@@ -83,36 +109,236 @@ float3 lookup_spectrum_hsv(Texture2D spectrumTex, float hue) {
     return rgb_to_hsv(spectrum_rgb);
 }
 
-float4 PS_Main(PS_INPUT input) : SV_Target {
-    float4 color = screenTexture.Sample(textureSampler, input.tex);
-
-    if (strength < EPSILON) {
-        return color;
+// Snaps the hue to the nearest of posterizeLevels buckets; a no-op when disabled.
+// Keep in sync with HueMapper::posterize_hue.
+float3 posterize(float3 rgb) {
+    if (posterizeLevels <= 0) {
+        return rgb;
     }
 
-    float3 input_hsv = rgb_to_hsv(color.rgb);
+    float3 hsv = rgb_to_hsv(rgb);
+    float step = HUE_MAX / posterizeLevels;
+    hsv.x = fmod(round(hsv.x / step) * step, HUE_MAX);
+    return hsv_to_rgb(hsv);
+}
 
-    float3 spectrum_hsv;
-    if (useDualSpectrum && useNoiseTexture) {
-        float noise_value = noiseTexture.Sample(textureSampler, input.tex).r;
-        if (noise_value > 0.5) {
-            spectrum_hsv = lookup_spectrum_hsv(spectrum1Texture, input_hsv.x);
-        } else {
-            spectrum_hsv = lookup_spectrum_hsv(spectrum2Texture, input_hsv.x);
+// Maps screen coordinates to the capture texture, which rotated outputs deliver in their native
+// orientation. captureRotation is in clockwise quarter turns (DXGI_MODE_ROTATION_ROTATE90 = 1).
+float2 capture_uv(float2 uv) {
+    if (captureRotation == 1) {
+        return float2(uv.y, 1.0 - uv.x);
+    }
+    if (captureRotation == 2) {
+        return 1.0 - uv;
+    }
+    if (captureRotation == 3) {
+        return float2(1.0 - uv.y, uv.x);
+    }
+    return uv;
+}
+
+// True over a shell surface left uncorrected (taskbar, Start menu, ...) and outside the target
+// window. Uses screen coordinates, so it is checked before capture_uv.
+bool is_excluded(float2 uv) {
+    if (useTargetRect && !(uv.x >= targetRect.x && uv.y >= targetRect.y && uv.x < targetRect.z && uv.y < targetRect.w)) {
+        return true;
+    }
+    [loop]
+    for (int i = 0; i < min(exclusionCount, MAX_EXCLUSION_RECTS); i++) {
+        float4 rect = exclusionRects[i];
+        if (uv.x >= rect.x && uv.y >= rect.y && uv.x < rect.z && uv.y < rect.w) {
+            return true;
         }
-    } else {
-        spectrum_hsv = lookup_spectrum_hsv(spectrum1Texture, input_hsv.x);
     }
+    return false;
+}
+
+// 1 inside the hue gate, 0 outside, smoothly blended across the feather width at each edge.
+// Keep in sync with HueGate::weight.
+float hue_gate_weight(float hue) {
+    if (!useHueGate) {
+        return 1.0;
+    }
+
+    float width = fmod(hueGateEnd - hueGateStart + HUE_MAX, HUE_MAX);
+    if (width < EPSILON) {
+        return 1.0;
+    }
+
+    float offset = fmod(hue - hueGateStart + HUE_MAX, HUE_MAX);
+    float edge_distance = offset <= width
+        ? min(offset, width - offset)
+        : -min(offset - width, HUE_MAX - offset);
+    return smoothstep(0.0, 1.0, edge_distance / HUE_GATE_FEATHER + 0.5);
+}
+
+float sample_noise(PS_INPUT input) {
+    if (noiseMapping == NOISE_PIXEL_EXACT) {
+        // One texel per screen pixel, sampled at the texel center
+        float2 texel = fmod(floor(input.pos.xy), noiseSize);
+        return noiseTexture.Sample(noisePointSampler, (texel + 0.5) / noiseSize).r;
+    }
+
+    float2 uv = noiseMapping == NOISE_TILE ? frac(input.tex * screenSize / noiseSize) : input.tex;
+    // Blending two indices would land on an unrelated third one
+    if (noiseSpectra > 0) {
+        return noiseTexture.Sample(noisePointSampler, uv).r;
+    }
+    return noiseTexture.Sample(textureSampler, uv).r;
+}
+
+// Which bound spectrum the noise texture selects at this pixel, 0 being the primary.
+// Index textures store index / (MAX_NOISE_INDICES - 1); indices past the bound spectra use the last one.
+int noise_spectrum_index(PS_INPUT input) {
+    float noise_value = sample_noise(input);
+    if (noiseSpectra > 0) {
+        return min((int)round(noise_value * (MAX_NOISE_INDICES - 1)), noiseSpectra - 1);
+    }
+    return noise_value > 0.5 ? 0 : 1;
+}
+
+// How much of the captured pixel replaces the correction in NOISE_TARGET_ORIGINAL: none where the
+// texture selects the primary spectrum, noiseIntensity elsewhere. Indices are not clamped to the
+// bound spectra, so every non-primary index counts.
+float original_weight(PS_INPUT input) {
+    float noise_value = sample_noise(input);
+    bool primary = noiseSpectra > 0 ? round(noise_value * (MAX_NOISE_INDICES - 1)) < 0.5 : noise_value > 0.5;
+    return primary ? 0.0 : saturate(noiseIntensity);
+}
+
+float3 lookup_indexed_spectrum_hsv(int index, float hue) {
+    if (index == 0) {
+        return lookup_spectrum_hsv(spectrum1Texture, hue);
+    }
+    if (index == 1) {
+        return lookup_spectrum_hsv(spectrum2Texture, hue);
+    }
+    if (index == 2) {
+        return lookup_spectrum_hsv(spectrum3Texture, hue);
+    }
+    return lookup_spectrum_hsv(spectrum4Texture, hue);
+}
+
+// Spectrum the noise texture selects at this pixel.
+float3 lookup_noise_spectrum_hsv(PS_INPUT input, float hue) {
+    return lookup_indexed_spectrum_hsv(noise_spectrum_index(input), hue);
+}
+
+// The baked correction of spectrum `index` for `rgb`, trilinearly filtered between lattice points.
+float3 sample_lut(int index, float3 rgb) {
+    // 0 and 1 land on the centers of the first and last texels, so the clamp never kicks in
+    float3 uvw = rgb * ((LUT_SIZE - 1.0) / LUT_SIZE) + 0.5 / LUT_SIZE;
+    if (index == 0) {
+        return lut1Texture.SampleLevel(textureSampler, uvw, 0).rgb;
+    }
+    if (index == 1) {
+        return lut2Texture.SampleLevel(textureSampler, uvw, 0).rgb;
+    }
+    if (index == 2) {
+        return lut3Texture.SampleLevel(textureSampler, uvw, 0).rgb;
+    }
+    return lut4Texture.SampleLevel(textureSampler, uvw, 0).rgb;
+}
 
-    // Apply the full correction (preserving brightness and saturation)
+// Applies the full correction of a spectrum sample, preserving brightness and saturation.
+float3 correct_hsv(float3 input_hsv, float3 spectrum_hsv) {
     float corrected_hue = spectrum_hsv.x;
     float corrected_saturation = input_hsv.y * spectrum_hsv.y;
     float corrected_value = input_hsv.z * ((1.0 - input_hsv.y) + input_hsv.y * spectrum_hsv.z);
+    return hsv_to_rgb(float3(corrected_hue, corrected_saturation, corrected_value));
+}
 
-    float3 corrected_rgb = hsv_to_rgb(float3(corrected_hue, corrected_saturation, corrected_value));
+// Exponential moving average with the previous output frame to calm shimmering patterns.
+// Skipped entirely when disabled so the output is exactly the current frame.
+float3 temporal_smooth(float3 rgb, float4 pos) {
+    if (!useHistory) {
+        return rgb;
+    }
 
-    // Interpolate between original and corrected in RGB space
-    float3 final_rgb = lerp(color.rgb, corrected_rgb, strength);
+    float3 history = historyTexture.Load(int3(pos.xy, 0)).rgb;
+    return lerp(rgb, history, temporalSmoothing);
+}
+
+float4 correct_pixel(PS_INPUT input) {
+    if (showDebugBorder) {
+        float width, height;
+        screenTexture.GetDimensions(width, height);
+        float2 px = input.pos.xy;
+        if (px.x < DEBUG_BORDER_PX || px.y < DEBUG_BORDER_PX ||
+            px.x > width - DEBUG_BORDER_PX || px.y > height - DEBUG_BORDER_PX) {
+            return float4(1.0, 0.0, 1.0, 1.0);
+        }
+    }
+
+    // Fully transparent, so the live surface shows instead of a frame-old copy
+    if (is_excluded(input.tex)) {
+        return float4(0.0, 0.0, 0.0, 0.0);
+    }
+
+    float4 color = screenTexture.Sample(textureSampler, capture_uv(input.tex));
+
+    // Strength and the hue gate are baked in, so this replaces everything up to the posterize step
+    if (useLut) {
+        float3 baked_rgb = sample_lut(0, color.rgb);
+        if (useNoiseTexture && noiseTarget == NOISE_TARGET_ORIGINAL) {
+            baked_rgb = lerp(baked_rgb, color.rgb, original_weight(input));
+        } else if (useDualSpectrum && useNoiseTexture && noiseIntensity > EPSILON) {
+            baked_rgb = lerp(baked_rgb, sample_lut(noise_spectrum_index(input), color.rgb), noiseIntensity);
+        } else if (useDualSpectrum && !useNoiseTexture && blendRatio > EPSILON) {
+            baked_rgb = lerp(baked_rgb, sample_lut(1, color.rgb), blendRatio);
+        }
+        return float4(temporal_smooth(posterize(baked_rgb), input.pos), color.a);
+    }
+
+    if (strength < EPSILON) {
+        return float4(temporal_smooth(posterize(color.rgb), input.pos), color.a);
+    }
+
+    float3 input_hsv = rgb_to_hsv(color.rgb);
+
+    float3 corrected_rgb;
+    if (useNoiseTexture && noiseTarget == NOISE_TARGET_ORIGINAL) {
+        // Dark texels keep the captured pixel, dithering the correction in without a second spectrum
+        corrected_rgb = correct_hsv(input_hsv, lookup_spectrum_hsv(spectrum1Texture, input_hsv.x));
+        corrected_rgb = lerp(corrected_rgb, color.rgb, original_weight(input));
+    } else if (useDualSpectrum && useNoiseTexture && noiseIntensity >= 1.0) {
+        corrected_rgb = correct_hsv(input_hsv, lookup_noise_spectrum_hsv(input, input_hsv.x));
+    } else {
+        corrected_rgb = correct_hsv(input_hsv, lookup_spectrum_hsv(spectrum1Texture, input_hsv.x));
+        // Partial intensity fades the other spectra in over the primary one, in RGB since their hues may be far apart
+        if (useDualSpectrum && useNoiseTexture && noiseIntensity > EPSILON) {
+            float3 noise_rgb = correct_hsv(input_hsv, lookup_noise_spectrum_hsv(input, input_hsv.x));
+            corrected_rgb = lerp(corrected_rgb, noise_rgb, noiseIntensity);
+        } else if (useDualSpectrum && !useNoiseTexture && blendRatio > EPSILON) {
+            // Without a texture to pick per pixel, the secondary spectrum is mixed in everywhere
+            float3 secondary_rgb = correct_hsv(input_hsv, lookup_spectrum_hsv(spectrum2Texture, input_hsv.x));
+            corrected_rgb = lerp(corrected_rgb, secondary_rgb, blendRatio);
+        }
+    }
+
+    // Interpolate between original and corrected in RGB space, leaving hues outside the gate alone
+    float3 final_rgb = lerp(color.rgb, corrected_rgb, strength * hue_gate_weight(input_hsv.x));
+
+    return float4(temporal_smooth(posterize(final_rgb), input.pos), color.a);
+}
+
+float4 PS_Main(PS_INPUT input) : SV_Target {
+    float4 color = correct_pixel(input);
+    // The output is premultiplied, so the color fades with the alpha
+    if (debugMode == DEBUG_HALF_ALPHA) {
+        return color * 0.5;
+    }
+    if (debugMode == DEBUG_MAGENTA_TINT) {
+        return float4(lerp(color.rgb, float3(1.0, 0.0, 1.0) * color.a, 0.5), color.a);
+    }
+    return color;
+}
 
-    return float4(final_rgb, color.a);
+// Renders one blue slice of spectrum bakeSpectrum's LUT: the same correction as PS_Main, evaluated
+// at the lattice point given by the pixel (red, green) and bakeSlice (blue).
+float4 PS_BakeLut(PS_INPUT input) : SV_Target {
+    float3 rgb = float3(floor(input.pos.xy), bakeSlice) / (LUT_SIZE - 1.0);
+    float3 input_hsv = rgb_to_hsv(rgb);
+    float3 corrected_rgb = correct_hsv(input_hsv, lookup_indexed_spectrum_hsv(bakeSpectrum, input_hsv.x));
+    return float4(lerp(rgb, corrected_rgb, strength * hue_gate_weight(input_hsv.x)), 1.0);
 }
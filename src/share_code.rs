@@ -0,0 +1,143 @@
+use anyhow::{Context, Result};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use flate2::{Compression, Decompress, FlushDecompress, Status};
+use flate2::write::DeflateEncoder;
+use std::io::Write;
+use crate::spectrum::SpectrumFile;
+
+/// Version prefix; bump it if the payload format ever changes.
+pub const SHARE_CODE_PREFIX: &str = "CBS1:";
+
+/// Upper bound on the decompressed payload so a crafted code can't balloon memory.
+const MAX_DECODED_SIZE: usize = 1024 * 1024;
+
+/// Encodes a spectrum file as `CBS1:` + base64url(deflate(compact JSON)).
+pub fn encode(spectrum_file: &SpectrumFile) -> Result<String> {
+    let json = serde_json::to_vec(spectrum_file).context("Failed to serialize spectrum")?;
+
+    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::best());
+    encoder.write_all(&json)?;
+    let compressed = encoder.finish().context("Failed to compress spectrum")?;
+
+    Ok(format!("{}{}", SHARE_CODE_PREFIX, URL_SAFE_NO_PAD.encode(compressed)))
+}
+
+/// Decodes and fully validates a share code. Errors say which stage failed so users
+/// can tell a truncated paste apart from a code that decodes but isn't a valid spectrum.
+pub fn decode(code: &str) -> Result<SpectrumFile> {
+    // Chat clients like to wrap long lines, so ignore any whitespace inside the code
+    let code: String = code.chars().filter(|c| !c.is_whitespace()).collect();
+
+    let payload = code
+        .strip_prefix(SHARE_CODE_PREFIX)
+        .ok_or_else(|| anyhow::anyhow!("Not a spectrum share code (expected it to start with {})", SHARE_CODE_PREFIX))?;
+
+    let compressed = URL_SAFE_NO_PAD
+        .decode(payload.trim_end_matches('='))
+        .context("Share code could not be decoded - it may be incomplete or mistyped")?;
+
+    let json = inflate(&compressed).context("Share code could not be decompressed - it is probably truncated")?;
+
+    let mut spectrum_file: SpectrumFile = serde_json::from_slice(&json)
+        .context("Share code decompressed but does not contain a spectrum")?;
+
+    spectrum_file.validate().context("Share code contains an invalid spectrum")?;
+    // Codes copied from ChromaBridge are already in order; hand-made ones are fixed up as on load
+    spectrum_file.normalize();
+
+    Ok(spectrum_file)
+}
+
+/// Raw deflate that, unlike the `Read` adapters, reports a stream cut off before its end marker.
+fn inflate(compressed: &[u8]) -> Result<Vec<u8>> {
+    let mut decompress = Decompress::new(false);
+    let mut out = Vec::with_capacity((compressed.len() * 4).max(1024));
+
+    loop {
+        let consumed = decompress.total_in() as usize;
+        match decompress.decompress_vec(&compressed[consumed..], &mut out, FlushDecompress::Finish)? {
+            Status::StreamEnd => return Ok(out),
+            _ if out.len() == out.capacity() => {
+                if out.len() >= MAX_DECODED_SIZE {
+                    anyhow::bail!("decompressed data exceeds {} bytes", MAX_DECODED_SIZE);
+                }
+                out.reserve(out.len().max(1024));
+            }
+            _ => anyhow::bail!("compressed data ended early"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bundled_spectra() -> Vec<(String, SpectrumFile)> {
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("assets").join("spectrums");
+        std::fs::read_dir(dir)
+            .unwrap()
+            .flatten()
+            .map(|entry| {
+                let content = std::fs::read_to_string(entry.path()).unwrap();
+                (entry.file_name().to_string_lossy().into_owned(), serde_json::from_str(&content).unwrap())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn bundled_spectra_round_trip_losslessly() {
+        let spectra = bundled_spectra();
+        assert!(!spectra.is_empty());
+
+        for (name, spectrum_file) in spectra {
+            let code = encode(&spectrum_file).unwrap();
+            assert!(code.starts_with(SHARE_CODE_PREFIX), "{}", name);
+
+            let decoded = decode(&code).unwrap();
+            assert_eq!(
+                serde_json::to_value(&decoded).unwrap(),
+                serde_json::to_value(&spectrum_file).unwrap(),
+                "{} changed after round trip",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn truncated_code_fails_to_decompress() {
+        let (_, spectrum_file) = bundled_spectra().remove(0);
+        let code = encode(&spectrum_file).unwrap();
+        let compressed = URL_SAFE_NO_PAD.decode(&code[SHARE_CODE_PREFIX.len()..]).unwrap();
+        let truncated = format!("{}{}", SHARE_CODE_PREFIX, URL_SAFE_NO_PAD.encode(&compressed[..compressed.len() / 2]));
+
+        let err = decode(&truncated).unwrap_err();
+        assert!(format!("{:#}", err).contains("decompressed"), "{:#}", err);
+    }
+
+    #[test]
+    fn invalid_characters_fail_to_decode() {
+        let err = decode("CBS1:not*base64!").unwrap_err();
+        assert!(format!("{:#}", err).contains("decoded"), "{:#}", err);
+    }
+
+    #[test]
+    fn invalid_spectrum_fails_validation() {
+        let json = br##"{"spectra":[{"nodes":[{"position":2.0,"color":"#ff0000"}]}]}"##;
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::best());
+        encoder.write_all(json).unwrap();
+        let code = format!("{}{}", SHARE_CODE_PREFIX, URL_SAFE_NO_PAD.encode(encoder.finish().unwrap()));
+
+        let err = decode(&code).unwrap_err();
+        assert!(format!("{:#}", err).contains("invalid spectrum"), "{:#}", err);
+    }
+
+    #[test]
+    fn whitespace_from_line_wrapping_is_ignored() {
+        let (_, spectrum_file) = bundled_spectra().remove(0);
+        let code = encode(&spectrum_file).unwrap();
+        let (head, tail) = code.split_at(code.len() / 2);
+
+        assert!(decode(&format!("  {}\n{}  ", head, tail)).is_ok());
+    }
+}
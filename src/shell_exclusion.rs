@@ -0,0 +1,136 @@
+use chromabridge::log_debug;
+use std::time::{Duration, Instant};
+use windows::{
+    core::BOOL,
+    Win32::Foundation::{HWND, LPARAM, RECT},
+    Win32::Graphics::Dwm::{DwmGetWindowAttribute, DWMWA_CLOAKED},
+    Win32::UI::WindowsAndMessaging::{EnumWindows, GetClassNameW, GetWindowRect, IsWindowVisible},
+};
+
+pub use chromabridge::shader_params::MAX_EXCLUSION_RECTS;
+
+/// How often the shell windows are searched for; the ones already found are measured every frame.
+const SCAN_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Taskbars, the tray overflow and the XAML shell surfaces (Start, search, notification center, toasts).
+const SHELL_CLASSES: [&str; 5] = [
+    "Shell_TrayWnd",
+    "Shell_SecondaryTrayWnd",
+    "NotifyIconOverflowWindow",
+    "TopLevelWindowForOverflowXamlIsland",
+    "Windows.UI.Core.CoreWindow",
+];
+
+/// Finds the shell surfaces on the overlay's monitor so the correction leaves them alone.
+///
+/// The shell keeps most of these windows around while closed, cloaked by DWM, so they are searched
+/// for once a second and then only checked for visibility and position. That keeps auto-hide
+/// taskbars and the Start menu in step frame by frame.
+pub struct ShellExclusion {
+    overlay: HWND,
+    windows: Vec<HWND>,
+    last_scan: Option<Instant>,
+    /// Monitor UV rectangles as (left, top, right, bottom).
+    rects: Vec<[f32; 4]>,
+}
+
+impl ShellExclusion {
+    pub fn new(overlay: HWND) -> Self {
+        Self {
+            overlay,
+            windows: Vec::new(),
+            last_scan: None,
+            rects: Vec::new(),
+        }
+    }
+
+    /// Re-measures the shell windows. True when the excluded regions changed.
+    pub fn update(&mut self, enabled: bool) -> bool {
+        if !enabled {
+            self.windows.clear();
+            self.last_scan = None;
+            return !std::mem::take(&mut self.rects).is_empty();
+        }
+
+        if self.last_scan.is_none_or(|scan| scan.elapsed() >= SCAN_INTERVAL) {
+            self.last_scan = Some(Instant::now());
+            self.windows = unsafe { find_shell_windows() };
+        }
+
+        let rects = unsafe { self.measure() };
+        if rects == self.rects {
+            return false;
+        }
+        log_debug!("Shell exclusion: {} region(s)", rects.len());
+        self.rects = rects;
+        true
+    }
+
+    pub fn rects(&self) -> &[[f32; 4]] {
+        &self.rects
+    }
+
+    unsafe fn measure(&self) -> Vec<[f32; 4]> {
+        // The overlay window always covers its monitor exactly
+        let mut monitor = RECT::default();
+        if GetWindowRect(self.overlay, &mut monitor).is_err() {
+            return Vec::new();
+        }
+
+        self.windows
+            .iter()
+            .filter(|&&hwnd| IsWindowVisible(hwnd).as_bool() && !is_cloaked(hwnd))
+            .filter_map(|&hwnd| {
+                let mut rect = RECT::default();
+                GetWindowRect(hwnd, &mut rect).ok()?;
+                monitor_uv(rect, monitor)
+            })
+            .take(MAX_EXCLUSION_RECTS)
+            .collect()
+    }
+}
+
+/// The part of `window` on `monitor` in the monitor's UV space, `None` if they don't overlap.
+pub(crate) fn monitor_uv(window: RECT, monitor: RECT) -> Option<[f32; 4]> {
+    let left = window.left.max(monitor.left);
+    let top = window.top.max(monitor.top);
+    let right = window.right.min(monitor.right);
+    let bottom = window.bottom.min(monitor.bottom);
+    if right <= left || bottom <= top {
+        return None;
+    }
+
+    let width = (monitor.right - monitor.left) as f32;
+    let height = (monitor.bottom - monitor.top) as f32;
+    Some([
+        (left - monitor.left) as f32 / width,
+        (top - monitor.top) as f32 / height,
+        (right - monitor.left) as f32 / width,
+        (bottom - monitor.top) as f32 / height,
+    ])
+}
+
+pub(crate) unsafe fn is_cloaked(hwnd: HWND) -> bool {
+    let mut cloaked = 0u32;
+    DwmGetWindowAttribute(hwnd, DWMWA_CLOAKED, &mut cloaked as *mut _ as *mut _, std::mem::size_of::<u32>() as u32).is_ok() && cloaked != 0
+}
+
+unsafe fn find_shell_windows() -> Vec<HWND> {
+    let mut windows: Vec<HWND> = Vec::new();
+    // Fails with no error code when the callback stops early; this one never does
+    let _ = EnumWindows(Some(collect_shell_windows), LPARAM(&mut windows as *mut _ as isize));
+    windows
+}
+
+unsafe extern "system" fn collect_shell_windows(hwnd: HWND, lparam: LPARAM) -> BOOL {
+    let windows = &mut *(lparam.0 as *mut Vec<HWND>);
+
+    let mut class = [0u16; 64];
+    let len = GetClassNameW(hwnd, &mut class) as usize;
+    let class = String::from_utf16_lossy(&class[..len]);
+    if SHELL_CLASSES.contains(&class.as_str()) {
+        windows.push(hwnd);
+    }
+
+    true.into()
+}
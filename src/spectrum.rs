@@ -1,7 +1,15 @@
+use crate::atomic_file;
+use crate::error::SpectrumError;
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::Path;
+use std::time::Duration;
+
+/// A file that fails to load this soon after it changed may still be being copied or saved.
+const SETTLING_WINDOW: Duration = Duration::from_secs(2);
+/// Wait before loading such a file once more.
+const SETTLING_RETRY_DELAY: Duration = Duration::from_millis(250);
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SpectrumNode {
@@ -16,6 +24,60 @@ pub struct SpectrumNode {
 }
 
 impl SpectrumNode {
+    /// A node at `position` (0.0 to 1.0 along the hue circle) with a `#RRGGBB` color and no HSV overrides.
+    pub fn new(position: f32, color: impl Into<String>) -> Self {
+        Self {
+            position,
+            color: color.into(),
+            hue: None,
+            saturation: None,
+            value: None,
+        }
+    }
+
+    /// Overrides the hue (degrees, below 360.0) taken from `color`.
+    pub fn with_hue(mut self, hue: f32) -> Self {
+        self.hue = Some(hue);
+        self
+    }
+
+    /// Overrides the saturation (0.0 to 1.0) taken from `color`.
+    pub fn with_saturation(mut self, saturation: f32) -> Self {
+        self.saturation = Some(saturation);
+        self
+    }
+
+    /// Overrides the value (0.0 to 1.0) taken from `color`.
+    pub fn with_value(mut self, value: f32) -> Self {
+        self.value = Some(value);
+        self
+    }
+
+    /// Checks the color and the HSV overrides; the position is checked by the spectrum.
+    pub fn validate(&self) -> Result<(), SpectrumError> {
+        if let Err(e) = self.to_rgb() {
+            return Err(SpectrumError::invalid("color", format!("{:#}", e)));
+        }
+
+        if let Some(hue) = self.hue {
+            if !(0.0..360.0).contains(&hue) {
+                return Err(SpectrumError::invalid("hue", format!("Hue {} out of range [0.0, 360.0)", hue)));
+            }
+        }
+        if let Some(sat) = self.saturation {
+            if !(0.0..=1.0).contains(&sat) {
+                return Err(SpectrumError::invalid("saturation", format!("Saturation {} out of range [0.0, 1.0]", sat)));
+            }
+        }
+        if let Some(val) = self.value {
+            if !(0.0..=1.0).contains(&val) {
+                return Err(SpectrumError::invalid("value", format!("Value {} out of range [0.0, 1.0]", val)));
+            }
+        }
+
+        Ok(())
+    }
+
     pub fn to_rgb(&self) -> Result<(f32, f32, f32)> {
         let hex = self.color.trim_start_matches('#');
 
@@ -76,47 +138,182 @@ impl SpectrumNode {
     }
 }
 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Interpolation {
+    #[default]
+    Srgb,
+    Linear,
+    Oklab,
+}
+
+impl Interpolation {
+    /// Blends two sRGB-encoded colors in this space and returns the sRGB-encoded result.
+    pub fn blend(&self, c1: (f32, f32, f32), c2: (f32, f32, f32), t: f32) -> (f32, f32, f32) {
+        use crate::hue_mapper::HueMapper;
+
+        let lerp = |a: f32, b: f32| a + t * (b - a);
+
+        match self {
+            Interpolation::Srgb => (lerp(c1.0, c2.0), lerp(c1.1, c2.1), lerp(c1.2, c2.2)),
+            Interpolation::Linear => {
+                let r = lerp(HueMapper::srgb_to_linear(c1.0), HueMapper::srgb_to_linear(c2.0));
+                let g = lerp(HueMapper::srgb_to_linear(c1.1), HueMapper::srgb_to_linear(c2.1));
+                let b = lerp(HueMapper::srgb_to_linear(c1.2), HueMapper::srgb_to_linear(c2.2));
+                (HueMapper::linear_to_srgb(r), HueMapper::linear_to_srgb(g), HueMapper::linear_to_srgb(b))
+            }
+            Interpolation::Oklab => {
+                let lab1 = HueMapper::linear_to_oklab(
+                    HueMapper::srgb_to_linear(c1.0),
+                    HueMapper::srgb_to_linear(c1.1),
+                    HueMapper::srgb_to_linear(c1.2),
+                );
+                let lab2 = HueMapper::linear_to_oklab(
+                    HueMapper::srgb_to_linear(c2.0),
+                    HueMapper::srgb_to_linear(c2.1),
+                    HueMapper::srgb_to_linear(c2.2),
+                );
+                let (r, g, b) = HueMapper::oklab_to_linear(
+                    lerp(lab1.0, lab2.0),
+                    lerp(lab1.1, lab2.1),
+                    lerp(lab1.2, lab2.2),
+                );
+                (HueMapper::linear_to_srgb(r), HueMapper::linear_to_srgb(g), HueMapper::linear_to_srgb(b))
+            }
+        }
+    }
+}
+
+/// Where the secondary spectrum of a file comes from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Spectrum2Mode {
+    /// Picked from the file's spectra in the settings.
+    #[default]
+    Explicit,
+    /// The primary with the hue of every node turned by 180°.
+    Complement,
+    /// The primary with the value of every node inverted.
+    ValueInvert,
+}
+
+impl Spectrum2Mode {
+    pub const ALL: [Spectrum2Mode; 3] = [Spectrum2Mode::Explicit, Spectrum2Mode::Complement, Spectrum2Mode::ValueInvert];
+
+    pub fn is_explicit(&self) -> bool {
+        *self == Spectrum2Mode::Explicit
+    }
+
+    /// The secondary spectrum derived from `primary`, `None` when it is picked explicitly.
+    /// Node N of the result comes from node N of `primary`, so errors name the source node.
+    pub fn derive(&self, primary: &Spectrum) -> Result<Option<Spectrum>, SpectrumError> {
+        if self.is_explicit() {
+            return Ok(None);
+        }
+
+        let mut derived = primary.clone();
+        derived.description = None;
+        for (index, node) in derived.nodes.iter_mut().enumerate() {
+            let (h, _, v) = node.to_hsv().map_err(|e| SpectrumError::invalid("color", format!("{:#}", e)).at_node(index))?;
+            match self {
+                Spectrum2Mode::Complement => node.hue = Some((h + 180.0).rem_euclid(360.0)),
+                Spectrum2Mode::ValueInvert => node.value = Some((1.0 - v).clamp(0.0, 1.0)),
+                Spectrum2Mode::Explicit => {}
+            }
+        }
+        derived.validate()?;
+        Ok(Some(derived))
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Spectrum {
+    /// Optional label, e.g. "Left eye" or "Bright", shown in the spectrum sub-selector.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
     pub nodes: Vec<SpectrumNode>,
+    #[serde(skip)]
+    pub interpolation: Interpolation,
 }
 
 impl Spectrum {
-    pub fn validate(&self) -> Result<()> {
+    /// Starts a spectrum; see [`SpectrumBuilder`].
+    pub fn builder() -> SpectrumBuilder {
+        SpectrumBuilder::default()
+    }
+
+    /// The metadata name, or its 1-based position in the file for unnamed spectra.
+    pub fn label(&self, index: usize) -> String {
+        match self.name {
+            Some(ref name) => name.clone(),
+            None => format!("Spectrum {}", index + 1),
+        }
+    }
+
+    /// Rejects what can't be drawn: no nodes, positions outside 0.0 to 1.0 and invalid colors.
+    /// Node order is left to [`Spectrum::normalize`], see [`Spectrum::validate_strict`].
+    pub fn validate(&self) -> Result<(), SpectrumError> {
         if self.nodes.is_empty() {
-            anyhow::bail!("Spectrum must have at least one node");
+            return Err(SpectrumError::invalid("nodes", "Spectrum must have at least one node"));
         }
 
-        let mut last_pos = -0.1;
-        for node in &self.nodes {
-            if node.position < 0.0 || node.position > 1.0 {
-                anyhow::bail!("Node position {} out of range [0.0, 1.0]", node.position);
-            }
-            if node.position < last_pos {
-                anyhow::bail!("Nodes must be sorted by position");
+        for (index, node) in self.nodes.iter().enumerate() {
+            if !(0.0..=1.0).contains(&node.position) {
+                let reason = format!("Position {} out of range [0.0, 1.0]", node.position);
+                return Err(SpectrumError::invalid("position", reason).at_node(index));
             }
-            last_pos = node.position;
 
-            node.to_rgb()?;
+            node.validate().map_err(|e| e.at_node(index))?;
+        }
 
-            if let Some(hue) = node.hue {
-                if !(0.0..360.0).contains(&hue) {
-                    anyhow::bail!("Node hue {} out of range [0.0, 360.0)", hue);
-                }
+        Ok(())
+    }
+
+    /// [`Spectrum::validate`] plus nodes in position order with no two at the same position,
+    /// what ChromaBridge itself writes.
+    pub fn validate_strict(&self) -> Result<(), SpectrumError> {
+        self.validate()?;
+
+        for (index, pair) in self.nodes.windows(2).enumerate() {
+            if pair[1].position < pair[0].position {
+                return Err(SpectrumError::invalid("position", "Nodes must be sorted by position").at_node(index + 1));
             }
-            if let Some(sat) = node.saturation {
-                if !(0.0..=1.0).contains(&sat) {
-                    anyhow::bail!("Node saturation {} out of range [0.0, 1.0]", sat);
-                }
+            if pair[1].position == pair[0].position {
+                let reason = format!("Position {} is already taken by node {}", pair[1].position, index + 1);
+                return Err(SpectrumError::invalid("position", reason).at_node(index + 1));
             }
-            if let Some(val) = node.value {
-                if !(0.0..=1.0).contains(&val) {
-                    anyhow::bail!("Node value {} out of range [0.0, 1.0]", val);
+        }
+
+        Ok(())
+    }
+
+    /// Sorts the nodes by position and, of nodes sharing a position, keeps the last one written.
+    /// The sort is stable, so nodes keep their written order up to the duplicates. Returns a
+    /// warning per change; node numbers are 1-based, in the order the nodes were written.
+    ///
+    /// The spectrum must pass [`Spectrum::validate`], which rules out NaN positions.
+    pub fn normalize(&mut self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        if self.nodes.windows(2).any(|pair| pair[1].position < pair[0].position) {
+            warnings.push("Nodes were not sorted by position and have been sorted".to_string());
+        }
+
+        let mut written: Vec<(usize, SpectrumNode)> = self.nodes.drain(..).enumerate().collect();
+        written.sort_by(|(_, a), (_, b)| a.position.partial_cmp(&b.position).unwrap_or(std::cmp::Ordering::Equal));
+
+        for (index, node) in written {
+            match self.nodes.last_mut() {
+                Some(kept) if kept.position == node.position => {
+                    warnings.push(format!("Node {} has the same position {} as an earlier node, which it replaces", index + 1, node.position));
+                    *kept = node;
                 }
+                _ => self.nodes.push(node),
             }
         }
 
-        Ok(())
+        warnings
     }
 
     pub fn map_hue_to_rgb(&self, input_hue: f32) -> Result<(f32, f32, f32)> {
@@ -151,19 +348,10 @@ impl Spectrum {
                 let (r1_u8, g1_u8, b1_u8) = HueMapper::hsv_to_rgb(h1, s1, v1);
                 let (r2_u8, g2_u8, b2_u8) = HueMapper::hsv_to_rgb(h2, s2, v2);
 
-                let r1 = r1_u8 as f32 / 255.0;
-                let g1 = g1_u8 as f32 / 255.0;
-                let b1 = b1_u8 as f32 / 255.0;
-
-                let r2 = r2_u8 as f32 / 255.0;
-                let g2 = g2_u8 as f32 / 255.0;
-                let b2 = b2_u8 as f32 / 255.0;
+                let c1 = (r1_u8 as f32 / 255.0, g1_u8 as f32 / 255.0, b1_u8 as f32 / 255.0);
+                let c2 = (r2_u8 as f32 / 255.0, g2_u8 as f32 / 255.0, b2_u8 as f32 / 255.0);
 
-                let r = r1 + t * (r2 - r1);
-                let g = g1 + t * (g2 - g1);
-                let b = b1 + t * (b2 - b1);
-
-                return Ok((r, g, b));
+                return Ok(self.interpolation.blend(c1, c2, t));
             }
         }
 
@@ -179,7 +367,17 @@ impl Spectrum {
     }
 
     pub fn get_rgb_lookup_table(&self, resolution: usize) -> Result<Vec<f32>> {
-        let mut table = Vec::with_capacity(resolution * 3);
+        self.lookup_table(resolution, false)
+    }
+
+    /// Same as `get_rgb_lookup_table` with alpha = 1 after each entry, for four-channel texture formats.
+    pub fn get_rgba_lookup_table(&self, resolution: usize) -> Result<Vec<f32>> {
+        self.lookup_table(resolution, true)
+    }
+
+    fn lookup_table(&self, resolution: usize, with_alpha: bool) -> Result<Vec<f32>> {
+        let channels = if with_alpha { 4 } else { 3 };
+        let mut table = Vec::with_capacity(resolution * channels);
 
         for i in 0..resolution {
             let hue = (i as f32 / resolution as f32) * 360.0;
@@ -187,51 +385,605 @@ impl Spectrum {
             table.push(r);
             table.push(g);
             table.push(b);
+            if with_alpha {
+                table.push(1.0);
+            }
         }
 
         Ok(table)
     }
 }
 
+/// Builds a validated [`Spectrum`].
+///
+/// ```
+/// use chromabridge::Spectrum;
+///
+/// let spectrum = Spectrum::builder()
+///     .name("Left eye")
+///     .node(0.0, "#ff0000")
+///     .node(1.0, "#00ff00")
+///     .build()?;
+/// assert_eq!(spectrum.nodes.len(), 2);
+///
+/// // Positions must be in order and within 0.0 to 1.0
+/// assert!(Spectrum::builder().node(0.5, "#ff0000").node(0.2, "#00ff00").build().is_err());
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct SpectrumBuilder {
+    name: Option<String>,
+    description: Option<String>,
+    nodes: Vec<SpectrumNode>,
+}
+
+impl SpectrumBuilder {
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    /// Appends a node with a `#RRGGBB` color; nodes must be added in position order.
+    pub fn node(self, position: f32, color: impl Into<String>) -> Self {
+        self.push(SpectrumNode::new(position, color))
+    }
+
+    /// Appends a prepared node, e.g. one with HSV overrides.
+    pub fn push(mut self, node: SpectrumNode) -> Self {
+        self.nodes.push(node);
+        self
+    }
+
+    /// Checks the spectrum with the strict rules of saving a file, see [`Spectrum::validate_strict`].
+    pub fn build(self) -> Result<Spectrum, SpectrumError> {
+        let spectrum = Spectrum {
+            name: self.name,
+            description: self.description,
+            nodes: self.nodes,
+            interpolation: Interpolation::default(),
+        };
+        spectrum.validate_strict()?;
+        Ok(spectrum)
+    }
+}
+
+/// The color vision deficiency a spectrum file is made for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Deficiency {
+    Protan,
+    Deutan,
+    /// Made for protans and deutans alike.
+    RedGreen,
+    Tritan,
+    Achromat,
+    /// Anything else, including values only newer versions know.
+    #[serde(other)]
+    Other,
+}
+
+/// What a spectrum file is for, written by its author; every field is optional.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SpectrumMetadata {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deficiency: Option<Deficiency>,
+    /// Strength (0.0 to 1.0) the file is tuned for.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recommended_strength: Option<f32>,
+}
+
+impl SpectrumMetadata {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// The recommended strength, clamped to 0.0 to 1.0 like the strength slider.
+    pub fn recommended_strength(&self) -> Option<f32> {
+        self.recommended_strength.map(|strength| strength.clamp(0.0, 1.0))
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SpectrumFile {
+    /// Top-level fields next to `spectra`; older files without them load with none set.
+    #[serde(flatten)]
+    pub metadata: SpectrumMetadata,
     pub spectra: Vec<Spectrum>,
+    #[serde(default)]
+    pub interpolation: Interpolation,
+    /// Derives the secondary spectrum from the primary instead of picking one of `spectra`.
+    #[serde(default, skip_serializing_if = "Spectrum2Mode::is_explicit")]
+    pub spectrum2_mode: Spectrum2Mode,
+}
+
+impl SpectrumFile {
+    /// A validated file of `spectra`, blended with `interpolation`.
+    ///
+    /// ```
+    /// use chromabridge::{Interpolation, Spectrum, SpectrumFile};
+    ///
+    /// let spectrum = Spectrum::builder().node(0.0, "#ff0000").node(1.0, "#0000ff").build()?;
+    /// let file = SpectrumFile::new(vec![spectrum], Interpolation::Oklab)?;
+    ///
+    /// let path = std::env::temp_dir().join("chromabridge-doctest.json");
+    /// file.save_to_file(&path)?;
+    /// assert_eq!(SpectrumFile::load(&path)?.interpolation, Interpolation::Oklab);
+    /// # std::fs::remove_file(&path)?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn new(spectra: Vec<Spectrum>, interpolation: Interpolation) -> Result<Self, SpectrumError> {
+        let mut spectrum_file = Self {
+            metadata: SpectrumMetadata::default(),
+            spectra,
+            interpolation,
+            spectrum2_mode: Spectrum2Mode::Explicit,
+        };
+        spectrum_file.validate_strict()?;
+        spectrum_file.apply_interpolation();
+        Ok(spectrum_file)
+    }
+
+    /// Checks every spectrum in the file; shared by file loading and share code import. Node order
+    /// isn't checked, see [`SpectrumFile::normalize`].
+    pub fn validate(&self) -> Result<(), SpectrumError> {
+        if self.spectra.is_empty() {
+            return Err(SpectrumError::invalid("spectra", "Spectrum file must contain at least one spectrum"));
+        }
+
+        for (index, spectrum) in self.spectra.iter().enumerate() {
+            spectrum.validate().map_err(|e| e.in_spectrum(index))?;
+            // Any spectrum can be the primary the secondary is derived from
+            self.spectrum2_mode.derive(spectrum).map_err(|e| e.in_spectrum(index))?;
+        }
+
+        Ok(())
+    }
+
+    /// [`SpectrumFile::validate`] with every spectrum checked by [`Spectrum::validate_strict`], for
+    /// files about to be written.
+    pub fn validate_strict(&self) -> Result<(), SpectrumError> {
+        self.validate()?;
+        for (index, spectrum) in self.spectra.iter().enumerate() {
+            spectrum.validate_strict().map_err(|e| e.in_spectrum(index))?;
+        }
+        Ok(())
+    }
+
+    /// Puts the nodes of every spectrum in order, see [`Spectrum::normalize`], and returns what
+    /// was changed, naming the spectrum.
+    pub fn normalize(&mut self) -> Vec<String> {
+        self.spectra
+            .iter_mut()
+            .enumerate()
+            .flat_map(|(index, spectrum)| {
+                let label = spectrum.label(index);
+                spectrum.normalize().into_iter().map(move |warning| format!("{}: {}", label, warning))
+            })
+            .collect()
+    }
+
+    /// Problems that don't stop the file from loading, like a recommended strength outside 0.0
+    /// to 1.0, which is clamped where it is used.
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        if let Some(strength) = self.metadata.recommended_strength.filter(|s| !(0.0..=1.0).contains(s)) {
+            warnings.push(format!("Recommended strength {} out of range [0.0, 1.0], clamped", strength));
+        }
+        warnings
+    }
+
+    /// Reads, parses and validates a spectrum file, applying the file-wide interpolation to each spectrum.
+    ///
+    /// A file modified within the last `SETTLING_WINDOW` that fails is read once more after
+    /// `SETTLING_RETRY_DELAY`, since another program may still be writing it.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, SpectrumError> {
+        Self::load_with_warnings(path).map(|(spectrum_file, _)| spectrum_file)
+    }
+
+    /// [`SpectrumFile::load`] that also returns the warnings it logs: nodes it sorted or dropped
+    /// as duplicates, see [`SpectrumFile::normalize`], and the ones of [`SpectrumFile::warnings`].
+    pub fn load_with_warnings<P: AsRef<Path>>(path: P) -> Result<(Self, Vec<String>), SpectrumError> {
+        let path = path.as_ref();
+        let mut spectrum_file = match Self::read_validated(path) {
+            Err(_) if atomic_file::modified_within(path, SETTLING_WINDOW) => {
+                std::thread::sleep(SETTLING_RETRY_DELAY);
+                Self::read_validated(path)?
+            }
+            result => result?,
+        };
+
+        let mut warnings = spectrum_file.normalize();
+        warnings.extend(spectrum_file.warnings());
+        spectrum_file.apply_interpolation();
+        for warning in &warnings {
+            crate::log_warn!("{}: {}", path.display(), warning);
+        }
+
+        Ok((spectrum_file, warnings))
+    }
+
+    fn read_validated(path: &Path) -> Result<Self, SpectrumError> {
+        let content = fs::read_to_string(path).map_err(|source| SpectrumError::Io { path: path.to_path_buf(), source })?;
+
+        let spectrum_file: SpectrumFile = serde_json::from_str(&content)
+            .map_err(|source| SpectrumError::Parse { path: path.to_path_buf(), source })?;
+
+        spectrum_file.validate()?;
+        Ok(spectrum_file)
+    }
+
+    /// The file as ChromaBridge writes it: pretty-printed JSON with fields in a fixed order,
+    /// so saving the same spectra always produces the same text.
+    pub fn to_json(&self) -> Result<String> {
+        let mut json = serde_json::to_string_pretty(self).context("Failed to serialize spectrum file")?;
+        json.push('\n');
+        Ok(json)
+    }
+
+    /// Validates strictly and writes the file in the format of [`SpectrumFile::to_json`]. The file is
+    /// replaced in one step, so it is never seen half written, see [`atomic_file::write`].
+    pub fn save_to_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        self.validate_strict()?;
+        atomic_file::write(path.as_ref(), self.to_json()?)
+            .with_context(|| format!("Failed to write spectrum file {}", path.as_ref().display()))
+    }
+
+    /// Spectra are blended with the file-wide interpolation, which is only stored once in the file.
+    fn apply_interpolation(&mut self) {
+        for spectrum in &mut self.spectra {
+            spectrum.interpolation = self.interpolation;
+        }
+    }
+}
+
+/// Which spectra of a file the overlay uses. Indices past the end of the file are ignored,
+/// so the default (first two) also covers single-spectrum files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SpectrumSelection {
+    pub primary: usize,
+    pub secondary: Option<usize>,
+}
+
+impl Default for SpectrumSelection {
+    fn default() -> Self {
+        Self {
+            primary: 0,
+            secondary: Some(1),
+        }
+    }
+}
+
+impl SpectrumSelection {
+    /// Clamps the selection to a file with `count` spectra.
+    pub fn resolve(&self, count: usize) -> Self {
+        let primary = if self.primary < count { self.primary } else { 0 };
+        Self {
+            primary,
+            secondary: self.secondary.filter(|&i| i < count && i != primary),
+        }
+    }
 }
 
+/// A selection of one or two spectra over a loaded spectrum file.
 #[derive(Debug, Clone)]
 pub struct SpectrumPair {
-    pub spectrum1: Spectrum,
-    pub spectrum2: Option<Spectrum>,
+    pub file: SpectrumFile,
+    pub selection: SpectrumSelection,
+    /// The secondary spectrum when the file derives it; `selection.secondary` is ignored then.
+    pub derived: Option<Spectrum>,
+    /// What loading corrected or found questionable, see [`SpectrumFile::load_with_warnings`].
+    pub warnings: Vec<String>,
 }
 
 impl SpectrumPair {
-    pub fn load_from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
-        let content = fs::read_to_string(path.as_ref()).context("Failed to read spectrum file")?;
+    pub fn load_from_file<P: AsRef<Path>>(path: P) -> Result<Self, SpectrumError> {
+        Self::load_with_selection(path, SpectrumSelection::default())
+    }
 
-        let spectrum_file: SpectrumFile = serde_json::from_str(&content)
-            .context("Failed to parse spectrum file")?;
+    pub fn load_with_selection<P: AsRef<Path>>(path: P, selection: SpectrumSelection) -> Result<Self, SpectrumError> {
+        let (file, warnings) = SpectrumFile::load_with_warnings(path)?;
+        Ok(Self { warnings, ..Self::from_file(file, selection) })
+    }
 
-        if spectrum_file.spectra.is_empty() {
-            anyhow::bail!("Spectrum file must contain at least one spectrum");
+    /// `file` must already be validated; the selection is clamped to the spectra it contains.
+    /// A derived secondary spectrum is computed here, so the GPU only ever sees node lists.
+    pub fn from_file(file: SpectrumFile, selection: SpectrumSelection) -> Self {
+        let selection = selection.resolve(file.spectra.len());
+        // Can't fail on a validated file, which checked the derivation from every spectrum
+        let derived = file.spectrum2_mode.derive(&file.spectra[selection.primary]).ok().flatten();
+        Self { file, selection, derived, warnings: Vec::new() }
+    }
+
+    pub fn spectrum1(&self) -> &Spectrum {
+        &self.file.spectra[self.selection.primary]
+    }
+
+    pub fn spectrum2(&self) -> Option<&Spectrum> {
+        match self.derived {
+            Some(ref derived) => Some(derived),
+            None => self.selection.secondary.map(|i| &self.file.spectra[i]),
         }
+    }
+
+    pub fn has_dual_spectrum(&self) -> bool {
+        self.spectrum2().is_some()
+    }
 
-        for spectrum in &spectrum_file.spectra {
-            spectrum.validate()?;
+    /// Spectra a noise texture selects between by index: the primary, the secondary, then the
+    /// file's remaining spectra in order, at most `limit`. Just the primary without a secondary.
+    pub fn noise_spectra(&self, limit: usize) -> Vec<&Spectrum> {
+        let Some(secondary) = self.spectrum2() else {
+            return vec![self.spectrum1()];
+        };
+
+        let picked = self.selection.secondary.filter(|_| self.derived.is_none());
+        let rest = (0..self.file.spectra.len())
+            .filter(|&i| i != self.selection.primary && Some(i) != picked)
+            .map(|i| &self.file.spectra[i]);
+        [self.spectrum1(), secondary]
+            .into_iter()
+            .chain(rest)
+            .take(limit)
+            .collect()
+    }
+
+    /// Length of `noise_spectra(limit)`, without collecting them.
+    pub fn noise_spectrum_count(&self, limit: usize) -> usize {
+        match (&self.derived, self.selection.secondary) {
+            // The derived one comes on top of the file's spectra
+            (Some(_), _) => (self.file.spectra.len() + 1).min(limit),
+            (None, Some(_)) => self.file.spectra.len().min(limit),
+            (None, None) => 1,
         }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+
+    #[test]
+    fn built_files_round_trip_through_disk() {
+        let spectrum = Spectrum::builder()
+            .name("Left eye")
+            .description("Shifts greens towards blue")
+            .node(0.0, "#FF0000")
+            .push(SpectrumNode::new(0.4, "#00FF00").with_hue(200.0).with_saturation(0.5))
+            .node(1.0, "#FF0000")
+            .build()
+            .unwrap();
+        let file = SpectrumFile::new(vec![spectrum], Interpolation::Oklab).unwrap();
+        assert_eq!(file.spectra[0].interpolation, Interpolation::Oklab);
+
+        let dir = temp_dir("round-trip");
+        let path = dir.join("round-trip.json");
+        file.save_to_file(&path).unwrap();
+        let loaded = SpectrumFile::load(&path).unwrap();
+        let _ = fs::remove_dir_all(&dir);
+
+        assert_eq!(loaded.to_json().unwrap(), file.to_json().unwrap());
+        assert_eq!(loaded.spectra[0].interpolation, Interpolation::Oklab);
+        assert_eq!(loaded.spectra[0].nodes[1].hue, Some(200.0));
+        assert_eq!(loaded.spectra[0].nodes[1].value, None);
+    }
+
+    #[test]
+    fn secondary_spectrum_can_be_derived() {
+        let json = r##"{"spectra": [
+            {"nodes": [{"color": "#FF0000", "position": 0.0}, {"color": "#00FF00", "position": 1.0, "value": 0.25}]},
+            {"nodes": [{"color": "#0000FF", "position": 0.0}]}
+        ], "spectrum2_mode": "complement"}"##;
+        let mut file: SpectrumFile = serde_json::from_str(json).unwrap();
+        file.validate().unwrap();
+        let selection = SpectrumSelection { primary: 0, secondary: None };
+
+        let pair = SpectrumPair::from_file(file.clone(), selection);
+        assert!(pair.has_dual_spectrum());
+        let complement = pair.spectrum2().unwrap();
+        assert_eq!(complement.nodes[0].to_hsv().unwrap().0, 180.0);
+        assert_eq!(complement.nodes[1].to_hsv().unwrap(), (300.0, 1.0, 0.25));
+        // The derived secondary comes before the file's other spectra
+        assert_eq!(pair.noise_spectrum_count(4), 3);
+        assert_eq!(pair.noise_spectra(4)[2].nodes[0].color, "#0000FF");
+
+        file.spectrum2_mode = Spectrum2Mode::ValueInvert;
+        let inverted = SpectrumPair::from_file(file.clone(), selection);
+        assert_eq!(inverted.spectrum2().unwrap().nodes[1].to_hsv().unwrap(), (120.0, 1.0, 0.75));
+
+        // Explicit files don't write the mode, so they read as before
+        file.spectrum2_mode = Spectrum2Mode::Explicit;
+        assert!(!file.to_json().unwrap().contains("spectrum2_mode"));
+        assert!(!SpectrumPair::from_file(file, selection).has_dual_spectrum());
+    }
 
-        match spectrum_file.spectra.len() {
-            1 => Ok(Self {
-                spectrum1: spectrum_file.spectra[0].clone(),
-                spectrum2: None,
-            }),
-            _ => Ok(Self {
-                spectrum1: spectrum_file.spectra[0].clone(),
-                spectrum2: Some(spectrum_file.spectra[1].clone()),
-            }),
+    #[test]
+    fn metadata_is_optional_and_only_warns() {
+        let plain = r##"{"spectra": [{"nodes": [{"color": "#FF0000", "position": 0.0}]}]}"##;
+        let file: SpectrumFile = serde_json::from_str(plain).unwrap();
+        assert!(file.metadata.is_empty());
+        assert!(!file.to_json().unwrap().contains("author"));
+
+        let described = r##"{
+            "name": "Deutan strong", "author": "Someone", "deficiency": "deutan", "recommended_strength": 1.4,
+            "spectra": [{"nodes": [{"color": "#FF0000", "position": 0.0}]}]
+        }"##;
+        let file: SpectrumFile = serde_json::from_str(described).unwrap();
+        file.validate().unwrap();
+        assert_eq!(file.metadata.deficiency, Some(Deficiency::Deutan));
+        assert_eq!(file.metadata.recommended_strength(), Some(1.0));
+        assert_eq!(file.warnings().len(), 1);
+
+        let reparsed: SpectrumFile = serde_json::from_str(&file.to_json().unwrap()).unwrap();
+        assert_eq!(reparsed.metadata, file.metadata);
+
+        let unknown = described.replace("\"deutan\"", "\"tetrachromat\"");
+        let file: SpectrumFile = serde_json::from_str(&unknown).unwrap();
+        assert_eq!(file.metadata.deficiency, Some(Deficiency::Other));
+    }
+
+    #[test]
+    fn builders_reject_what_loading_rejects() {
+        assert!(Spectrum::builder().build().is_err());
+        assert!(Spectrum::builder().node(0.6, "#FF0000").node(0.3, "#00FF00").build().is_err());
+        assert!(Spectrum::builder().node(1.5, "#FF0000").build().is_err());
+        assert!(Spectrum::builder().node(0.0, "red").build().is_err());
+        assert!(Spectrum::builder().push(SpectrumNode::new(0.0, "#FF0000").with_value(2.0)).build().is_err());
+        assert!(SpectrumFile::new(Vec::new(), Interpolation::Srgb).is_err());
+    }
+
+    #[test]
+    fn unsorted_and_duplicate_nodes_are_fixed_on_load() {
+        let dir = temp_dir("normalize");
+        let path = dir.join("normalize.json");
+        let json = r##"{"spectra": [
+            {"nodes": [{"color": "#0000FF", "position": 1.0}, {"color": "#FF0000", "position": 0.0}, {"color": "#00FF00", "position": 0.5}]},
+            {"name": "Twice", "nodes": [
+                {"color": "#FF0000", "position": 0.0}, {"color": "#00FF00", "position": 0.5},
+                {"color": "#0000FF", "position": 0.5}, {"color": "#FFFFFF", "position": 1.0}
+            ]}
+        ]}"##;
+        fs::write(&path, json).unwrap();
+        let pair = SpectrumPair::load_with_selection(&path, SpectrumSelection::default());
+        let _ = fs::remove_file(&path);
+        let pair = pair.unwrap();
+
+        let colors = |spectrum: &Spectrum| spectrum.nodes.iter().map(|n| n.color.clone()).collect::<Vec<_>>();
+        assert_eq!(colors(pair.spectrum1()), ["#FF0000", "#00FF00", "#0000FF"]);
+        // Of the two at 0.5, the one written later wins
+        assert_eq!(colors(pair.spectrum2().unwrap()), ["#FF0000", "#0000FF", "#FFFFFF"]);
+        assert_eq!(pair.warnings.len(), 2);
+        assert!(pair.warnings[0].starts_with("Spectrum 1: "), "{}", pair.warnings[0]);
+        assert!(pair.warnings[1].starts_with("Twice: Node 3 "), "{}", pair.warnings[1]);
+
+        // What loading fixes, saving refuses
+        assert!(pair.file.validate_strict().is_ok());
+        let mut unsorted: SpectrumFile = serde_json::from_str(json).unwrap();
+        assert!(unsorted.validate().is_ok());
+        assert!(unsorted.save_to_file(&path).is_err());
+        assert!(!path.exists());
+        unsorted.normalize();
+        assert!(unsorted.validate_strict().is_ok());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn boundary_positions_are_kept_and_out_of_range_ones_rejected() {
+        let mut spectrum = Spectrum::builder().node(0.0, "#FF0000").node(1.0, "#0000FF").build().unwrap();
+        assert!(spectrum.normalize().is_empty());
+        assert_eq!(spectrum.nodes.len(), 2);
+
+        let duplicate = Spectrum::builder().node(0.0, "#FF0000").node(0.0, "#00FF00").build().err().unwrap();
+        assert_eq!(duplicate.to_string(), "Node 2: Position 0 is already taken by node 1");
+
+        for position in [-0.001, 1.001, f32::NAN] {
+            let mut spectrum = Spectrum::builder().node(0.0, "#FF0000").build().unwrap();
+            spectrum.nodes.push(SpectrumNode::new(position, "#00FF00"));
+            assert!(matches!(spectrum.validate(), Err(SpectrumError::Validation { node_index: Some(1), field: "position", .. })), "{}", position);
         }
     }
 
-    pub fn has_dual_spectrum(&self) -> bool {
-        self.spectrum2.is_some()
+    #[test]
+    fn files_still_being_written_get_a_second_read() {
+        let dir = temp_dir("settling");
+        let path = dir.join("settling.json");
+        let file = SpectrumFile::new(vec![Spectrum::builder().name("Blue").node(0.0, "#0000FF").node(1.0, "#0000FF").build().unwrap()], Interpolation::Srgb).unwrap();
+        let json = file.to_json().unwrap();
+        let truncated = &json[..json.len() / 2];
+
+        // Long unchanged, a truncated file is invalid right away
+        fs::write(&path, truncated).unwrap();
+        let an_hour_ago = std::time::SystemTime::now() - Duration::from_secs(3600);
+        fs::File::options().write(true).open(&path).unwrap().set_modified(an_hour_ago).unwrap();
+        assert!(matches!(SpectrumFile::load(&path), Err(SpectrumError::Parse { .. })));
+
+        // Just written, it is read again once the rest has arrived
+        fs::write(&path, truncated).unwrap();
+        let finisher = {
+            let (path, file) = (path.clone(), file.clone());
+            std::thread::spawn(move || {
+                std::thread::sleep(SETTLING_RETRY_DELAY / 5);
+                file.save_to_file(&path).unwrap();
+            })
+        };
+        let loaded = SpectrumFile::load(&path);
+        finisher.join().unwrap();
+        let _ = fs::remove_dir_all(&dir);
+        assert_eq!(loaded.unwrap().to_json().unwrap(), json);
+    }
+
+    #[test]
+    fn load_errors_say_what_went_wrong() {
+        let dir = temp_dir("load-errors");
+        let path = dir.join("load-errors.json");
+        let load = |json: Option<&str>| {
+            if let Some(json) = json {
+                fs::write(&path, json).unwrap();
+            }
+            SpectrumFile::load(&path).err().unwrap()
+        };
+
+        match load(None) {
+            SpectrumError::Io { source, .. } => assert_eq!(source.kind(), std::io::ErrorKind::NotFound),
+            other => panic!("expected an I/O error, got {:?}", other),
+        }
+        assert!(matches!(load(Some("{\"spectra\": [")), SpectrumError::Parse { .. }));
+
+        let out_of_range = r##"{"spectra": [
+            {"nodes": [{"color": "#FF0000", "position": 0.0}]},
+            {"nodes": [{"color": "#FF0000", "position": 0.0}, {"color": "#00FF00", "position": 0.5, "hue": 400.0}]}
+        ]}"##;
+        match load(Some(out_of_range)) {
+            SpectrumError::Validation { spectrum_index, node_index, field, .. } => {
+                assert_eq!((spectrum_index, node_index, field), (Some(1), Some(1), "hue"));
+            }
+            other => panic!("expected a validation error, got {:?}", other),
+        }
+        let _ = fs::remove_dir_all(&dir);
+
+        let unsorted = Spectrum::builder().node(0.6, "#FF0000").node(0.3, "#00FF00").build().err().unwrap();
+        assert!(matches!(unsorted, SpectrumError::Validation { spectrum_index: None, node_index: Some(1), field: "position", .. }));
+        assert_eq!(unsorted.to_string(), "Node 2: Nodes must be sorted by position");
+        let bad_color = Spectrum::builder().node(0.0, "red").build().err().unwrap();
+        assert!(matches!(bad_color, SpectrumError::Validation { node_index: Some(0), field: "color", .. }));
+        let empty = SpectrumFile::new(Vec::new(), Interpolation::Srgb).err().unwrap();
+        assert!(matches!(empty, SpectrumError::Validation { spectrum_index: None, node_index: None, field: "spectra", .. }));
+    }
+
+    #[test]
+    fn red_to_green_midpoint_is_brighter_outside_srgb() {
+        use crate::hue_mapper::HueMapper;
+
+        let (red, green) = ((1.0, 0.0, 0.0), (0.0, 1.0, 0.0));
+        let luminance = |(r, g, b): (f32, f32, f32)| {
+            0.2126 * HueMapper::srgb_to_linear(r) + 0.7152 * HueMapper::srgb_to_linear(g) + 0.0722 * HueMapper::srgb_to_linear(b)
+        };
+        let close = |a: (f32, f32, f32), b: (f32, f32, f32)| (a.0 - b.0).abs() < 0.01 && (a.1 - b.1).abs() < 0.01 && (a.2 - b.2).abs() < 0.01;
+
+        let srgb = Interpolation::Srgb.blend(red, green, 0.5);
+        assert!(close(srgb, (0.5, 0.5, 0.0)), "{:?}", srgb);
+        let linear = Interpolation::Linear.blend(red, green, 0.5);
+        assert!(close(linear, (0.735, 0.735, 0.0)), "{:?}", linear);
+        let oklab = Interpolation::Oklab.blend(red, green, 0.5);
+        assert!(close(oklab, (0.816, 0.660, 0.0)), "{:?}", oklab);
+
+        assert!(luminance(linear) > luminance(srgb) && luminance(oklab) > luminance(srgb));
+
+        // The ends stay put in every space
+        for interpolation in [Interpolation::Srgb, Interpolation::Linear, Interpolation::Oklab] {
+            assert!(close(interpolation.blend(red, green, 0.0), red));
+            assert!(close(interpolation.blend(red, green, 1.0), green));
+        }
     }
 }
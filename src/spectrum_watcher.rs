@@ -0,0 +1,101 @@
+use crate::StateManager;
+use crate::overlay::OverlayManager;
+use chromabridge::{log_info, log_warn, SpectrumPair};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Polls the active spectrum file's mtime and hot-swaps it into the running overlay.
+///
+/// Runs on its own thread because the main loop is blocked while the settings window is open.
+pub struct SpectrumWatcher {
+    shutdown: Arc<AtomicBool>,
+    thread: parking_lot::Mutex<Option<thread::JoinHandle<()>>>,
+}
+
+struct WatchedFile {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+}
+
+impl SpectrumWatcher {
+    pub fn spawn(state: Arc<StateManager>, overlay_manager: Arc<OverlayManager>) -> Self {
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let shutdown_flag = Arc::clone(&shutdown);
+
+        let thread = thread::spawn(move || {
+            let mut watched: Option<WatchedFile> = None;
+            while !shutdown_flag.load(Ordering::Acquire) {
+                Self::poll(&state, &overlay_manager, &mut watched);
+                thread::sleep(POLL_INTERVAL);
+            }
+        });
+
+        Self {
+            shutdown,
+            thread: parking_lot::Mutex::new(Some(thread)),
+        }
+    }
+
+    pub fn stop(&self) {
+        self.shutdown.store(true, Ordering::Release);
+        if let Some(handle) = self.thread.lock().take() {
+            let _ = handle.join();
+        }
+    }
+
+    fn poll(state: &StateManager, overlay_manager: &OverlayManager, watched: &mut Option<WatchedFile>) {
+        let (enabled, spectrum_name, selection) =
+            state.read(|s| (s.watch_spectrum_file, s.spectrum_name.clone(), s.spectrum_selection));
+
+        let Some(name) = spectrum_name.filter(|_| enabled && overlay_manager.is_running()) else {
+            *watched = None;
+            return;
+        };
+
+        let Ok(path) = state.get_spectrum_path(&name) else {
+            *watched = None;
+            return;
+        };
+        // Editors often save by writing a temp file and renaming it over the original,
+        // so a missing file is treated as "not changed yet" rather than an error
+        let modified = std::fs::metadata(&path).and_then(|m| m.modified()).ok();
+
+        if watched.as_ref().map(|f| &f.path) != Some(&path) {
+            // New spectrum or overlay just started: take a baseline, the overlay already loaded it
+            *watched = Some(WatchedFile { path, modified });
+            return;
+        }
+        let Some(file) = watched.as_mut() else {
+            return;
+        };
+
+        if modified.is_none() || modified == file.modified {
+            return;
+        }
+        file.modified = modified;
+
+        match SpectrumPair::load_with_selection(&file.path, selection) {
+            Ok(pair) => {
+                log_info!("Spectrum '{}' changed on disk - reloading", name);
+                overlay_manager.update_spectrum(pair);
+                overlay_manager.set_spectrum_status(format!("Reloaded spectrum: {}", name));
+            }
+            Err(e) => {
+                let e = anyhow::Error::from(e);
+                log_warn!("Spectrum '{}' changed but failed to load, keeping current: {:#}", name, e);
+                overlay_manager.set_spectrum_status(format!("Spectrum '{}' not reloaded: {:#}", name, e));
+            }
+        }
+    }
+}
+
+impl Drop for SpectrumWatcher {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
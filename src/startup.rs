@@ -0,0 +1,188 @@
+use anyhow::Result;
+use chromabridge::{log_debug, log_info, log_warn};
+use std::path::{Path, PathBuf};
+
+#[cfg(windows)]
+use windows::{
+    core::HSTRING,
+    Win32::Foundation::ERROR_FILE_NOT_FOUND,
+    Win32::System::Registry::{
+        RegCloseKey, RegDeleteValueW, RegOpenKeyExW, RegQueryValueExW, RegSetValueExW, HKEY,
+        HKEY_CURRENT_USER, KEY_READ, KEY_WRITE, REG_SZ, REG_VALUE_TYPE,
+    },
+};
+
+#[cfg(windows)]
+const RUN_KEY: &str = "Software\\Microsoft\\Windows\\CurrentVersion\\Run";
+#[cfg(windows)]
+const VALUE_NAME: &str = "ChromaBridge";
+
+/// Result of checking the Run entry against the running executable.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StartupCheck {
+    Disabled,
+    Valid,
+    /// The entry pointed somewhere else (moved or updated install) and was rewritten.
+    Repaired { previous_path: PathBuf },
+}
+
+/// Makes sure the Run entry, if present, launches the executable that is running now.
+///
+/// The registry is the source of truth for "run at startup"; callers sync `run_at_startup` from the result.
+pub fn verify_startup_entry() -> Result<StartupCheck> {
+    let Some(command) = read_startup_command()? else {
+        return Ok(StartupCheck::Disabled);
+    };
+
+    let exe_path = std::env::current_exe()?;
+    let (registered_path, args) = split_command(&command);
+    let expected = startup_command(&exe_path, args);
+
+    if command == expected {
+        log_debug!("Startup entry OK: {}", command);
+        return Ok(StartupCheck::Valid);
+    }
+
+    if same_path(&registered_path, &exe_path) {
+        // Same target, just written by an older version without quotes
+        set_startup_registry(true, &exe_path, args)?;
+        log_info!("Startup entry re-quoted: {}", expected);
+        return Ok(StartupCheck::Valid);
+    }
+
+    log_warn!(
+        "Startup entry pointed at {} - repairing to {}",
+        registered_path.display(),
+        exe_path.display()
+    );
+    set_startup_registry(true, &exe_path, args)?;
+    Ok(StartupCheck::Repaired { previous_path: registered_path })
+}
+
+/// Quoted so install directories with spaces work; extra arguments follow the path.
+fn startup_command(exe_path: &Path, args: &str) -> String {
+    if args.is_empty() {
+        format!("\"{}\"", exe_path.display())
+    } else {
+        format!("\"{}\" {}", exe_path.display(), args)
+    }
+}
+
+/// Splits a Run command into its executable and any arguments after it.
+fn split_command(command: &str) -> (PathBuf, &str) {
+    let command = command.trim();
+    if let Some(rest) = command.strip_prefix('"') {
+        if let Some((path, args)) = rest.split_once('"') {
+            return (PathBuf::from(path), args.trim());
+        }
+    }
+    // Older versions wrote the bare path, which may itself contain spaces
+    (PathBuf::from(command), "")
+}
+
+fn same_path(a: &Path, b: &Path) -> bool {
+    // NTFS paths are case-insensitive
+    a.to_string_lossy().eq_ignore_ascii_case(&b.to_string_lossy())
+}
+
+/// Enables or disables the Run entry, keeping any arguments of the existing entry.
+pub fn set_startup_enabled(enabled: bool) -> Result<()> {
+    let exe_path = std::env::current_exe()?;
+    let existing = read_startup_command()?;
+    let args = existing.as_deref().map(|c| split_command(c).1).unwrap_or("");
+    set_startup_registry(enabled, &exe_path, args)
+}
+
+#[cfg(windows)]
+fn read_startup_command() -> Result<Option<String>> {
+    unsafe {
+        let subkey = HSTRING::from(RUN_KEY);
+        let value_name = HSTRING::from(VALUE_NAME);
+        let mut hkey = HKEY::default();
+
+        let open_result = RegOpenKeyExW(HKEY_CURRENT_USER, &subkey, None, KEY_READ, &mut hkey);
+
+        if open_result == ERROR_FILE_NOT_FOUND {
+            return Ok(None);
+        }
+
+        if open_result.is_err() {
+            return Err(anyhow::anyhow!("Failed to open registry key: {:?}", open_result));
+        }
+
+        let mut buffer = [0u16; 1024];
+        let mut buffer_size = (buffer.len() * 2) as u32;
+        let mut value_type = REG_VALUE_TYPE::default();
+
+        let query_result = RegQueryValueExW(
+            hkey,
+            &value_name,
+            None,
+            Some(&mut value_type),
+            Some(buffer.as_mut_ptr() as *mut u8),
+            Some(&mut buffer_size),
+        );
+
+        let _ = RegCloseKey(hkey);
+
+        if query_result.is_err() {
+            return Ok(None);
+        }
+
+        let len = (buffer_size as usize / 2).min(buffer.len());
+        let value = String::from_utf16_lossy(&buffer[..len]);
+        Ok(Some(value.trim_end_matches('\0').to_string()))
+    }
+}
+
+#[cfg(windows)]
+fn set_startup_registry(enabled: bool, exe_path: &Path, args: &str) -> Result<()> {
+    unsafe {
+        let subkey = HSTRING::from(RUN_KEY);
+        let value_name = HSTRING::from(VALUE_NAME);
+        let mut hkey = HKEY::default();
+
+        let open_result = RegOpenKeyExW(HKEY_CURRENT_USER, &subkey, None, KEY_WRITE, &mut hkey);
+
+        if open_result.is_err() {
+            return Err(anyhow::anyhow!("Failed to open registry key for write: {:?}", open_result));
+        }
+
+        let result = if enabled {
+            let command = startup_command(exe_path, args);
+            let command_wide: Vec<u16> = command.encode_utf16().chain(std::iter::once(0)).collect();
+            let bytes: &[u8] = std::slice::from_raw_parts(
+                command_wide.as_ptr() as *const u8,
+                command_wide.len() * 2
+            );
+
+            RegSetValueExW(
+                hkey,
+                &value_name,
+                None,
+                REG_SZ,
+                Some(bytes),
+            )
+        } else {
+            RegDeleteValueW(hkey, &value_name)
+        };
+
+        let _ = RegCloseKey(hkey);
+
+        if result.is_err() {
+            return Err(anyhow::anyhow!("Failed to set/delete registry value: {:?}", result));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(not(windows))]
+fn read_startup_command() -> Result<Option<String>> {
+    Ok(None)
+}
+
+#[cfg(not(windows))]
+fn set_startup_registry(_enabled: bool, _exe_path: &Path, _args: &str) -> Result<()> {
+    Ok(())
+}
@@ -0,0 +1,77 @@
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+/// Named checkpoints of a startup sequence, for telling which step makes launching or starting
+/// the overlay slow.
+///
+/// Each checkpoint ends a step that took the time since the previous one. Recording is an
+/// `Instant::now()` and a push, so checkpoints can stay in release builds.
+pub struct StartupTimer {
+    /// Leads the summary and names where it is stored, e.g. `startup` or `overlay`.
+    name: &'static str,
+    started: Instant,
+    last: Instant,
+    steps: Vec<(&'static str, Duration)>,
+}
+
+impl StartupTimer {
+    pub fn start(name: &'static str) -> Self {
+        Self::start_at(name, Instant::now())
+    }
+
+    fn start_at(name: &'static str, now: Instant) -> Self {
+        Self {
+            name,
+            started: now,
+            last: now,
+            steps: Vec::with_capacity(8),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Ends `step` now.
+    pub fn checkpoint(&mut self, step: &'static str) {
+        self.checkpoint_at(step, Instant::now());
+    }
+
+    fn checkpoint_at(&mut self, step: &'static str, now: Instant) {
+        self.steps.push((step, now.saturating_duration_since(self.last)));
+        self.last = now;
+    }
+
+    /// From the start to the last checkpoint.
+    pub fn total(&self) -> Duration {
+        self.last.saturating_duration_since(self.started)
+    }
+
+    /// One line like `startup: state=120ms logger=5ms tray=80ms total=240ms`.
+    pub fn summary(&self) -> String {
+        let mut summary = format!("{}:", self.name);
+        for (step, duration) in &self.steps {
+            let _ = write!(summary, " {}={}ms", step, duration.as_millis());
+        }
+        let _ = write!(summary, " total={}ms", self.total().as_millis());
+        summary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn steps_take_the_time_since_the_previous_checkpoint() {
+        let start = Instant::now();
+        let mut timer = StartupTimer::start_at("startup", start);
+        assert_eq!(timer.summary(), "startup: total=0ms");
+
+        timer.checkpoint_at("state", start + Duration::from_millis(120));
+        timer.checkpoint_at("logger", start + Duration::from_millis(125));
+        timer.checkpoint_at("tray", start + Duration::from_millis(205));
+        assert_eq!(timer.total(), Duration::from_millis(205));
+        assert_eq!(timer.summary(), "startup: state=120ms logger=5ms tray=80ms total=205ms");
+    }
+}
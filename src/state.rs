@@ -1,18 +1,37 @@
 use anyhow::{Context, Result};
-use parking_lot::RwLock;
-use rusqlite::{Connection, params};
+use parking_lot::{Mutex, RwLock};
+use rusqlite::{Connection, OpenFlags, OptionalExtension, params};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::thread;
-use crossbeam_channel::{Sender, Receiver, unbounded};
+use std::time::{Duration, Instant};
+use crate::data_dirs::{DataDirLayout, DataDirs, Environment, RoamingCopy};
+use crate::error::ConfigError;
+use crate::hue_mapper::HueGate;
+use crate::i18n::Language;
+use crate::logger::{LogFormat, LogLevel};
+use crate::migration::{self, MigrationReport};
+use crate::session_history::SessionSummary;
+use crate::spectrum::SpectrumSelection;
+use crate::startup_timer::StartupTimer;
+use crate::test_pattern::TestPattern;
+use crate::update_check::{self, Release, UpdateCheck};
+use crossbeam_channel::{Sender, Receiver, RecvTimeoutError, SendError, unbounded};
 
 const SCHEMA_VERSION: i32 = 1;
+/// Minimum spacing between persisted writes; updates arriving faster are coalesced.
+const WRITE_INTERVAL: Duration = Duration::from_millis(250);
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct AppState {
     pub last_monitor: Option<usize>,
     pub spectrum_name: Option<String>,
+    /// Which spectra of a multi-spectrum file are used; reset when switching files.
+    #[serde(default)]
+    pub spectrum_selection: SpectrumSelection,
     pub strength: f32,
     pub noise_texture: Option<String>,
     pub overlay_enabled: bool,
@@ -31,21 +50,510 @@ pub struct AppState {
 
     #[serde(default = "default_cap_to_monitor_refresh")]
     pub cap_to_monitor_refresh: bool,
+
+    #[serde(default = "default_per_spectrum_settings")]
+    pub per_spectrum_settings: bool,
+    #[serde(default)]
+    pub spectrum_settings: HashMap<String, SpectrumSettings>,
+    /// Starred spectra, listed first in this order.
+    #[serde(default)]
+    pub favorite_spectrums: Vec<String>,
+
+    #[serde(default)]
+    pub control_server_enabled: bool,
+    #[serde(default = "default_control_server_port")]
+    pub control_server_port: u16,
+    #[serde(default)]
+    pub control_server_token: String,
+
+    #[serde(default)]
+    pub fullscreen_behavior: FullscreenBehavior,
+    #[serde(default = "default_fullscreen_target_fps")]
+    pub fullscreen_target_fps: u32,
+
+    /// Look for Night Light, f.lux and similar color filters while the overlay runs.
+    #[serde(default = "default_conflict_detection")]
+    pub conflict_detection: bool,
+    #[serde(default)]
+    pub conflict_behavior: ConflictBehavior,
+    /// Executable names of color filter apps, e.g. "flux.exe"; matched ignoring case.
+    #[serde(default = "default_conflict_processes")]
+    pub conflict_processes: Vec<String>,
+
+    #[serde(default)]
+    pub vision_setup_complete: bool,
+
+    #[serde(default)]
+    pub posterize_levels: Option<u32>,
+    #[serde(default)]
+    pub color_math: ColorMath,
+
+    #[serde(default)]
+    pub watch_spectrum_file: bool,
+
+    #[serde(default)]
+    pub log_level: LogLevel,
+    /// Format of the log file, from the next launch on.
+    #[serde(default)]
+    pub log_format: LogFormat,
+
+    #[serde(default)]
+    pub noise_mapping: NoiseMapping,
+    /// How strongly the noise texture selects its other spectra (0 = primary spectrum only, 1 = as drawn).
+    #[serde(default = "default_noise_intensity")]
+    pub noise_intensity: f32,
+    #[serde(default)]
+    pub noise_target: NoiseTarget,
+    /// How much of the secondary spectrum is mixed in evenly when a dual-spectrum file is used
+    /// without a noise texture (0 = primary only, 1 = secondary only).
+    #[serde(default)]
+    pub blend_ratio: f32,
+    /// Mapping and intensity last used with each noise texture, keyed by its name.
+    #[serde(default)]
+    pub noise_settings: HashMap<String, NoiseSettings>,
+
+    /// Weight of the previous frame in the output (0 = off).
+    #[serde(default)]
+    pub temporal_smoothing: f32,
+
+    /// Only correct hues between `hue_gate_start` and `hue_gate_end` (degrees, may wrap past 360).
+    #[serde(default)]
+    pub hue_gate_enabled: bool,
+    #[serde(default = "default_hue_gate_start")]
+    pub hue_gate_start: f32,
+    #[serde(default = "default_hue_gate_end")]
+    pub hue_gate_end: f32,
+
+    #[serde(default)]
+    pub strength_curve: StrengthCurve,
+
+    /// Rebuild attempts after the GPU device is removed before the overlay gives up (0 = never rebuild).
+    #[serde(default = "default_max_device_recoveries")]
+    pub max_device_recoveries: u32,
+
+    #[serde(default)]
+    pub topmost_mode: TopmostMode,
+
+    #[serde(default)]
+    pub overlay_source: OverlaySource,
+    #[serde(default)]
+    pub test_pattern: TestPattern,
+    #[serde(default = "default_solid_color")]
+    pub solid_color: [u8; 3],
+    /// Draw the pointer into captured frames so it is corrected too.
+    #[serde(default)]
+    pub correct_cursor: bool,
+    /// Leave the taskbars, Start menu and notifications uncorrected.
+    #[serde(default)]
+    pub exclude_shell: bool,
+    /// Keep the overlay out of screenshots and screen sharing. Off shows the correction to
+    /// viewers, but the overlay's own capture then sees it too.
+    #[serde(default = "default_hide_from_capture")]
+    pub hide_from_capture: bool,
+    /// The window picked for window-only correction, kept while that is switched off.
+    #[serde(default)]
+    pub target_window: Option<TargetWindow>,
+    /// Correct only inside `target_window` instead of the whole monitor.
+    #[serde(default)]
+    pub target_window_only: bool,
+
+    /// Show a notification when the overlay starts or stops.
+    #[serde(default)]
+    pub notify_start_stop: bool,
+    /// Show a notification when the overlay pauses for a fullscreen app or another color filter, and resumes.
+    #[serde(default)]
+    pub notify_pause: bool,
+    /// Show a notification when the overlay fails or hits a capture error, and recovers.
+    #[serde(default)]
+    pub notify_errors: bool,
+
+    /// Look for a newer release at launch and daily.
+    #[serde(default)]
+    pub check_for_updates: bool,
+    /// Where the latest release is looked up: the GitHub releases API or a static JSON file, see
+    /// `Release::from_manifest`.
+    #[serde(default = "default_update_manifest_url")]
+    pub update_manifest_url: String,
+    /// The last successful check, so launches within a day (or offline) don't check again.
+    #[serde(default)]
+    pub last_update_check: Option<UpdateCheck>,
+    /// The release whose banner was dismissed; a newer one shows again.
+    #[serde(default)]
+    pub dismissed_update: Option<String>,
+
+    #[serde(default)]
+    pub language: Language,
+    #[serde(default)]
+    pub gui_theme: GuiTheme,
+    /// Tint the settings window's accent with a hue the active spectrum keeps distinct.
+    #[serde(default)]
+    pub spectrum_accent: bool,
+
+    /// Set while battery saver is on, holding the user's own values of the settings it overrides.
+    #[serde(default)]
+    pub battery_saver: Option<PowerSettings>,
+    /// Turn battery saver on when the system switches to battery power, and off again on AC.
+    #[serde(default)]
+    pub auto_battery_saver: bool,
+
+    /// Overlay session summaries kept in the performance history; older ones are deleted on insert.
+    #[serde(default = "default_session_history_limit")]
+    pub session_history_limit: usize,
+
+    /// Largest width and height of a noise texture in pixels; bigger files are listed as invalid.
+    #[serde(default = "default_max_noise_size")]
+    pub max_noise_size: u32,
+
+    #[serde(default)]
+    pub render_thread_priority: RenderThreadPriority,
+}
+
+/// Frame rate cap while battery saver is on.
+pub const BATTERY_SAVER_FPS: u32 = 30;
+
+/// The settings battery saver overrides.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PowerSettings {
+    pub fullscreen_behavior: FullscreenBehavior,
+    pub temporal_smoothing: f32,
+}
+
+impl PowerSettings {
+    /// The low-impact configuration: suspended behind fullscreen apps, no history copy per frame.
+    const BATTERY_SAVER: PowerSettings = PowerSettings {
+        fullscreen_behavior: FullscreenBehavior::Pause,
+        temporal_smoothing: 0.0,
+    };
+}
+
+/// What the overlay does while another app is fullscreen on the foreground.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FullscreenBehavior {
+    #[default]
+    Ignore,
+    Reduce,
+    Pause,
+}
+
+/// What the overlay does while another color filter (Night Light, f.lux, ...) is detected.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictBehavior {
+    /// Only shows the warning.
+    #[default]
+    Notify,
+    /// Halves the correction strength.
+    Reduce,
+    Pause,
+}
+
+/// How the strength slider maps to the share of the correction that is blended in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StrengthCurve {
+    /// The slider value is the blend weight.
+    #[default]
+    Linear,
+    /// The slider value is raised to 1/2.2 first, the inverse of the display gamma. A linear blend
+    /// barely shows over the bottom third of the slider and changes little near the top; this
+    /// spreads the visible change evenly over its travel.
+    Perceptual,
+}
+
+impl StrengthCurve {
+    const PERCEPTUAL_GAMMA: f32 = 2.2;
+
+    /// The blend weight for slider value `strength` (0 to 1). Both curves keep 0 and 1 in place.
+    pub fn apply(&self, strength: f32) -> f32 {
+        let strength = strength.clamp(0.0, 1.0);
+        match self {
+            StrengthCurve::Linear => strength,
+            StrengthCurve::Perceptual => strength.powf(1.0 / Self::PERCEPTUAL_GAMMA),
+        }
+    }
+}
+
+/// How the interlace pattern is laid over the screen.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NoiseMapping {
+    /// Scaled across the whole screen.
+    #[default]
+    Stretch,
+    /// Repeated at its native size, filtered.
+    Tile,
+    /// Repeated so one texel covers exactly one screen pixel.
+    PixelExact,
+}
+
+impl NoiseMapping {
+    pub const ALL: [NoiseMapping; 3] = [NoiseMapping::Stretch, NoiseMapping::Tile, NoiseMapping::PixelExact];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            NoiseMapping::Stretch => "Stretch",
+            NoiseMapping::Tile => "Tile",
+            NoiseMapping::PixelExact => "Pixel-exact",
+        }
+    }
+}
+
+/// What the interlace pattern switches between.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NoiseTarget {
+    /// White selects the primary spectrum, black the secondary (or the spectrum an index texture names).
+    #[default]
+    BetweenSpectra,
+    /// White selects the primary spectrum, black leaves the pixel uncorrected; other spectra are ignored.
+    CorrectionVsOriginal,
+}
+
+impl NoiseTarget {
+    pub const ALL: [NoiseTarget; 2] = [NoiseTarget::BetweenSpectra, NoiseTarget::CorrectionVsOriginal];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            NoiseTarget::BetweenSpectra => "Between spectra",
+            NoiseTarget::CorrectionVsOriginal => "Correction vs. original",
+        }
+    }
+}
+
+/// What the overlay corrects: the real desktop, or a generated image for checking a spectrum.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OverlaySource {
+    #[default]
+    DesktopCapture,
+    TestPattern,
+    SolidColor,
+}
+
+impl OverlaySource {
+    pub const ALL: [OverlaySource; 3] = [OverlaySource::DesktopCapture, OverlaySource::TestPattern, OverlaySource::SolidColor];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            OverlaySource::DesktopCapture => "Desktop capture",
+            OverlaySource::TestPattern => "Test pattern",
+            OverlaySource::SolidColor => "Solid color",
+        }
+    }
+}
+
+/// How the overlay evaluates the correction for each pixel.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ColorMath {
+    /// Converts to HSV and looks the hue up in the spectrum, per pixel.
+    #[default]
+    Shader,
+    /// Bakes the correction of each spectrum into a 33x33x33 RGB table on the GPU and samples
+    /// that instead; rebaked whenever the spectrum, strength or hue gate changes.
+    BakedLut,
+}
+
+impl ColorMath {
+    pub const ALL: [ColorMath; 2] = [ColorMath::Shader, ColorMath::BakedLut];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ColorMath::Shader => "Shader math",
+            ColorMath::BakedLut => "Baked LUT",
+        }
+    }
+}
+
+/// How the overlay's render thread asks the scheduler for CPU time, so a loading game doesn't
+/// starve it. Applied when the overlay starts.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RenderThreadPriority {
+    /// Normal priority, like any other thread.
+    Normal,
+    #[default]
+    AboveNormal,
+    /// Registered with the Multimedia Class Scheduler Service as display post-processing, which
+    /// boosts it further while it renders; falls back to above normal where that fails.
+    Mmcss,
+}
+
+impl RenderThreadPriority {
+    pub const ALL: [RenderThreadPriority; 3] = [RenderThreadPriority::Normal, RenderThreadPriority::AboveNormal, RenderThreadPriority::Mmcss];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            RenderThreadPriority::Normal => "Normal",
+            RenderThreadPriority::AboveNormal => "Above normal",
+            RenderThreadPriority::Mmcss => "MMCSS",
+        }
+    }
+}
+
+/// How the overlay keeps itself above other always-on-top windows.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TopmostMode {
+    /// Only re-asserts when another topmost window has moved above the overlay.
+    #[default]
+    Polite,
+    /// Re-asserts on every check, regardless of what is above it.
+    Aggressive,
+}
+
+impl TopmostMode {
+    pub const ALL: [TopmostMode; 2] = [TopmostMode::Polite, TopmostMode::Aggressive];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            TopmostMode::Polite => "Polite",
+            TopmostMode::Aggressive => "Aggressive",
+        }
+    }
+}
+
+/// Colors of the settings window.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GuiTheme {
+    /// Light or dark, following Windows.
+    #[default]
+    Auto,
+    Light,
+    Dark,
+    /// Black and white with thick outlines.
+    HighContrast,
+}
+
+impl GuiTheme {
+    pub const ALL: [GuiTheme; 4] = [GuiTheme::Auto, GuiTheme::Light, GuiTheme::Dark, GuiTheme::HighContrast];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            GuiTheme::Auto => "Auto",
+            GuiTheme::Light => "Light",
+            GuiTheme::Dark => "Dark",
+            GuiTheme::HighContrast => "High contrast",
+        }
+    }
+}
+
+/// A window to limit the correction to. Window handles don't outlive the window, so it is found
+/// again by its process and title, e.g. after the game restarts.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TargetWindow {
+    /// Executable file name, e.g. "game.exe".
+    pub process_name: String,
+    /// Part of the window title; empty matches any window of the process.
+    #[serde(default)]
+    pub title_pattern: String,
+}
+
+impl TargetWindow {
+    /// Both compared case-insensitively.
+    pub fn matches(&self, process_name: &str, title: &str) -> bool {
+        self.process_name.eq_ignore_ascii_case(process_name)
+            && title.to_lowercase().contains(&self.title_pattern.to_lowercase())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpectrumSettings {
+    pub strength: f32,
+    pub noise_texture: Option<String>,
+    #[serde(default)]
+    pub hue_gate_enabled: bool,
+    #[serde(default = "default_hue_gate_start")]
+    pub hue_gate_start: f32,
+    #[serde(default = "default_hue_gate_end")]
+    pub hue_gate_end: f32,
+    #[serde(default = "default_noise_intensity")]
+    pub noise_intensity: f32,
+    #[serde(default)]
+    pub blend_ratio: f32,
+}
+
+/// What is remembered per noise texture, since each pattern has its own best mapping.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct NoiseSettings {
+    #[serde(default)]
+    pub mapping: NoiseMapping,
+    #[serde(default = "default_noise_intensity")]
+    pub intensity: f32,
+}
+
+fn default_noise_intensity() -> f32 {
+    1.0
+}
+
+fn default_hue_gate_start() -> f32 {
+    60.0
+}
+
+fn default_hue_gate_end() -> f32 {
+    180.0
+}
+
+fn default_fullscreen_target_fps() -> u32 {
+    30
+}
+
+fn default_conflict_detection() -> bool {
+    true
+}
+
+fn default_hide_from_capture() -> bool {
+    true
+}
+
+fn default_update_manifest_url() -> String {
+    update_check::DEFAULT_MANIFEST_URL.to_string()
+}
+
+fn default_conflict_processes() -> Vec<String> {
+    ["flux.exe", "redshift.exe", "Iris.exe", "CareUEyes.exe", "LightBulb.exe"].map(String::from).to_vec()
+}
+
+fn default_control_server_port() -> u16 {
+    47810
+}
+
+fn default_per_spectrum_settings() -> bool {
+    true
 }
 
 fn default_cap_to_monitor_refresh() -> bool {
     true
 }
 
+fn default_max_device_recoveries() -> u32 {
+    3
+}
+
+fn default_solid_color() -> [u8; 3] {
+    [255, 0, 0]
+}
+
 fn default_open_gui_on_launch() -> bool {
     true
 }
 
+fn default_session_history_limit() -> usize {
+    500
+}
+
+fn default_max_noise_size() -> u32 {
+    crate::noise::DEFAULT_MAX_NOISE_SIZE
+}
+
 impl Default for AppState {
     fn default() -> Self {
         Self {
             last_monitor: None,
             spectrum_name: None,
+            spectrum_selection: SpectrumSelection::default(),
             strength: 1.0,
             noise_texture: None,
             overlay_enabled: false,
@@ -60,59 +568,658 @@ impl Default for AppState {
             last_overlay_enabled: false,
 
             cap_to_monitor_refresh: true,
+
+            per_spectrum_settings: true,
+            spectrum_settings: HashMap::new(),
+            favorite_spectrums: Vec::new(),
+
+            control_server_enabled: false,
+            control_server_port: default_control_server_port(),
+            control_server_token: String::new(),
+
+            fullscreen_behavior: FullscreenBehavior::Ignore,
+            fullscreen_target_fps: default_fullscreen_target_fps(),
+            conflict_detection: default_conflict_detection(),
+            conflict_behavior: ConflictBehavior::Notify,
+            conflict_processes: default_conflict_processes(),
+
+            vision_setup_complete: false,
+
+            posterize_levels: None,
+            color_math: ColorMath::Shader,
+
+            watch_spectrum_file: false,
+
+            log_level: LogLevel::Info,
+            log_format: LogFormat::Text,
+
+            noise_mapping: NoiseMapping::Stretch,
+            noise_intensity: default_noise_intensity(),
+            noise_target: NoiseTarget::BetweenSpectra,
+            blend_ratio: 0.0,
+            noise_settings: HashMap::new(),
+
+            temporal_smoothing: 0.0,
+            hue_gate_enabled: false,
+            hue_gate_start: default_hue_gate_start(),
+            hue_gate_end: default_hue_gate_end(),
+            strength_curve: StrengthCurve::Linear,
+            max_device_recoveries: default_max_device_recoveries(),
+            topmost_mode: TopmostMode::Polite,
+            overlay_source: OverlaySource::DesktopCapture,
+            test_pattern: TestPattern::Gradient,
+            solid_color: default_solid_color(),
+            correct_cursor: false,
+            exclude_shell: false,
+            hide_from_capture: true,
+            target_window: None,
+            target_window_only: false,
+
+            notify_start_stop: false,
+            notify_pause: false,
+            notify_errors: false,
+
+            check_for_updates: false,
+            update_manifest_url: default_update_manifest_url(),
+            last_update_check: None,
+            dismissed_update: None,
+
+            language: Language::Auto,
+            gui_theme: GuiTheme::Auto,
+            spectrum_accent: false,
+
+            battery_saver: None,
+            auto_battery_saver: false,
+
+            session_history_limit: default_session_history_limit(),
+            max_noise_size: default_max_noise_size(),
+            render_thread_priority: RenderThreadPriority::AboveNormal,
+        }
+    }
+}
+
+impl AppState {
+    /// Applies or reverts the battery saver configuration. Turning it off restores the values
+    /// from before it was turned on, so it never overwrites the user's own settings.
+    pub fn set_battery_saver(&mut self, on: bool) {
+        match (on, self.battery_saver) {
+            (true, None) => {
+                self.battery_saver = Some(self.power_settings());
+                self.apply_power_settings(PowerSettings::BATTERY_SAVER);
+            }
+            (false, Some(saved)) => {
+                self.apply_power_settings(saved);
+                self.battery_saver = None;
+            }
+            _ => {}
+        }
+    }
+
+    pub fn battery_saver_active(&self) -> bool {
+        self.battery_saver.is_some()
+    }
+
+    /// The release the last update check found newer than this build, while checking is on.
+    pub fn available_update(&self) -> Option<&Release> {
+        self.last_update_check.as_ref().filter(|_| self.check_for_updates).and_then(UpdateCheck::update)
+    }
+
+    /// `available_update` unless its banner was dismissed.
+    pub fn update_banner(&self) -> Option<&Release> {
+        self.available_update().filter(|release| self.dismissed_update.as_ref() != Some(&release.version))
+    }
+
+    fn power_settings(&self) -> PowerSettings {
+        PowerSettings {
+            fullscreen_behavior: self.fullscreen_behavior,
+            temporal_smoothing: self.temporal_smoothing,
+        }
+    }
+
+    fn apply_power_settings(&mut self, settings: PowerSettings) {
+        self.fullscreen_behavior = settings.fullscreen_behavior;
+        self.temporal_smoothing = settings.temporal_smoothing;
+    }
+
+    /// Restores the defaults of the settings window's Advanced section. The control server token
+    /// is kept, so clients set up with it still work once the server is enabled again.
+    pub fn reset_advanced_settings(&mut self) {
+        let defaults = AppState::default();
+        self.hue_gate_enabled = defaults.hue_gate_enabled;
+        self.hue_gate_start = defaults.hue_gate_start;
+        self.hue_gate_end = defaults.hue_gate_end;
+        self.strength_curve = defaults.strength_curve;
+        self.run_at_startup = defaults.run_at_startup;
+        self.open_gui_on_launch = defaults.open_gui_on_launch;
+        self.keep_running_in_tray = defaults.keep_running_in_tray;
+        self.language = defaults.language;
+        self.gui_theme = defaults.gui_theme;
+        self.spectrum_accent = defaults.spectrum_accent;
+        self.battery_saver = defaults.battery_saver;
+        self.auto_battery_saver = defaults.auto_battery_saver;
+        self.fullscreen_behavior = defaults.fullscreen_behavior;
+        self.fullscreen_target_fps = defaults.fullscreen_target_fps;
+        self.conflict_detection = defaults.conflict_detection;
+        self.conflict_behavior = defaults.conflict_behavior;
+        self.conflict_processes = defaults.conflict_processes;
+        self.topmost_mode = defaults.topmost_mode;
+        self.correct_cursor = defaults.correct_cursor;
+        self.exclude_shell = defaults.exclude_shell;
+        self.hide_from_capture = defaults.hide_from_capture;
+        self.target_window_only = defaults.target_window_only;
+        self.notify_start_stop = defaults.notify_start_stop;
+        self.notify_pause = defaults.notify_pause;
+        self.notify_errors = defaults.notify_errors;
+        self.check_for_updates = defaults.check_for_updates;
+        self.update_manifest_url = defaults.update_manifest_url;
+        self.control_server_enabled = defaults.control_server_enabled;
+        self.control_server_port = defaults.control_server_port;
+        self.remember_spectrum_settings();
+    }
+
+    /// Restores the defaults of the settings window's Developer section.
+    pub fn reset_developer_settings(&mut self) {
+        let defaults = AppState::default();
+        self.cap_to_monitor_refresh = defaults.cap_to_monitor_refresh;
+        self.debug_overlay = defaults.debug_overlay;
+        self.watch_spectrum_file = defaults.watch_spectrum_file;
+        self.posterize_levels = defaults.posterize_levels;
+        self.color_math = defaults.color_math;
+        self.overlay_source = defaults.overlay_source;
+        self.test_pattern = defaults.test_pattern;
+        self.solid_color = defaults.solid_color;
+        self.log_level = defaults.log_level;
+        self.log_format = defaults.log_format;
+        self.max_device_recoveries = defaults.max_device_recoveries;
+        self.session_history_limit = defaults.session_history_limit;
+        self.max_noise_size = defaults.max_noise_size;
+        self.render_thread_priority = defaults.render_thread_priority;
+    }
+
+    /// The window the correction is limited to, if window-only correction is on.
+    pub fn correction_target(&self) -> Option<&TargetWindow> {
+        self.target_window.as_ref().filter(|_| self.target_window_only)
+    }
+
+    /// The active hue gate, if enabled.
+    pub fn hue_gate(&self) -> Option<HueGate> {
+        self.hue_gate_enabled.then_some(HueGate {
+            start: self.hue_gate_start,
+            end: self.hue_gate_end,
+        })
+    }
+
+    /// Stores the current strength and noise selection for the active spectrum.
+    pub fn remember_spectrum_settings(&mut self) {
+        if !self.per_spectrum_settings {
+            return;
+        }
+        if let Some(ref name) = self.spectrum_name {
+            self.spectrum_settings.insert(name.clone(), SpectrumSettings {
+                strength: self.strength,
+                noise_texture: self.noise_texture.clone(),
+                hue_gate_enabled: self.hue_gate_enabled,
+                hue_gate_start: self.hue_gate_start,
+                hue_gate_end: self.hue_gate_end,
+                noise_intensity: self.noise_intensity,
+                blend_ratio: self.blend_ratio,
+            });
+        }
+    }
+
+    /// Switches to `name`, restoring its remembered settings when per-spectrum settings are enabled.
+    pub fn select_spectrum(&mut self, name: String) {
+        if self.per_spectrum_settings {
+            if let Some(settings) = self.spectrum_settings.get(&name) {
+                self.strength = settings.strength;
+                self.noise_texture = settings.noise_texture.clone();
+                self.hue_gate_enabled = settings.hue_gate_enabled;
+                self.hue_gate_start = settings.hue_gate_start;
+                self.hue_gate_end = settings.hue_gate_end;
+                self.noise_intensity = settings.noise_intensity;
+                self.blend_ratio = settings.blend_ratio;
+            }
+            self.recall_noise_settings();
+        }
+        if self.spectrum_name.as_deref() != Some(name.as_str()) {
+            self.spectrum_selection = SpectrumSelection::default();
+        }
+        self.spectrum_name = Some(name);
+        self.remember_spectrum_settings();
+    }
+
+    /// Stores the current mapping and intensity for the active noise texture.
+    pub fn remember_noise_settings(&mut self) {
+        if let Some(ref name) = self.noise_texture {
+            self.noise_settings.insert(name.clone(), NoiseSettings {
+                mapping: self.noise_mapping,
+                intensity: self.noise_intensity,
+            });
+        }
+    }
+
+    /// Switches the noise texture, restoring the mapping and intensity last used with it.
+    pub fn select_noise(&mut self, name: Option<String>) {
+        self.noise_texture = name;
+        self.recall_noise_settings();
+        self.remember_noise_settings();
+        self.remember_spectrum_settings();
+    }
+
+    fn recall_noise_settings(&mut self) {
+        if let Some(settings) = self.noise_texture.as_ref().and_then(|name| self.noise_settings.get(name)) {
+            self.noise_mapping = settings.mapping;
+            self.noise_intensity = settings.intensity;
+        }
+    }
+
+    pub fn is_favorite_spectrum(&self, name: &str) -> bool {
+        self.favorite_spectrums.iter().any(|f| f == name)
+    }
+
+    /// Stars `name`, adding it at the end of the favorites, or unstars it.
+    pub fn toggle_favorite_spectrum(&mut self, name: &str) {
+        if self.is_favorite_spectrum(name) {
+            self.favorite_spectrums.retain(|f| f != name);
+        } else {
+            self.favorite_spectrums.push(name.to_string());
+        }
+    }
+
+    /// Moves the favorite at `from` to `to`, shifting the ones in between.
+    pub fn move_favorite_spectrum(&mut self, from: usize, to: usize) {
+        if from < self.favorite_spectrums.len() && to < self.favorite_spectrums.len() {
+            let name = self.favorite_spectrums.remove(from);
+            self.favorite_spectrums.insert(to, name);
         }
     }
 }
 
+/// Splits a spectrum name into its folder and file name, e.g. "gaming/deutan-strong" into
+/// ("gaming", "deutan-strong"). Spectra at the top of the spectrum folder have an empty folder.
+pub fn split_spectrum_name(name: &str) -> (&str, &str) {
+    name.rsplit_once('/').unwrap_or(("", name))
+}
+
+/// A selected asset looked up in a freshly listed asset folder.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AssetSelection {
+    None,
+    /// Its position in the list.
+    Found(usize),
+    /// The selected name, whose file is gone.
+    Missing(String),
+}
+
+impl AssetSelection {
+    pub fn index(&self) -> Option<usize> {
+        match self {
+            Self::Found(index) => Some(*index),
+            _ => None,
+        }
+    }
+
+    pub fn missing(&self) -> Option<&str> {
+        match self {
+            Self::Missing(name) => Some(name),
+            _ => None,
+        }
+    }
+}
+
+/// Finds the selected asset `name` in `files`. Selections are kept by name and looked up again
+/// whenever the list is reloaded, as any file sorting before it moves its position.
+pub fn resolve_asset(files: &[String], name: Option<&str>) -> AssetSelection {
+    match name {
+        Some(name) => files
+            .iter()
+            .position(|file| file == name)
+            .map_or_else(|| AssetSelection::Missing(name.to_string()), AssetSelection::Found),
+        None => AssetSelection::None,
+    }
+}
+
+/// Asset names are file stems relative to their asset folder, with '/' between subfolders.
+/// Anything that could point outside the folder (empty, "." or ".." parts, drive letters,
+/// backslashes) is rejected; so are hidden files and folders.
+pub(crate) fn check_asset_name(name: &str) -> Result<()> {
+    let valid = name
+        .split('/')
+        .all(|part| !part.trim().is_empty() && !part.starts_with('.') && !part.contains(['\\', ':']));
+    anyhow::ensure!(valid, "Invalid asset name '{}'", name);
+    Ok(())
+}
+
+/// How deep subfolders of the spectrum folder are searched.
+pub const MAX_SPECTRUM_FOLDER_DEPTH: usize = 4;
+
+/// Which groups of settings one update changed, delivered to `StateManager::subscribe` listeners.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StateChanges {
+    /// `overlay_enabled` / `last_overlay_enabled`.
+    pub overlay: bool,
+    /// Active spectrum file, its spectrum selection or the noise texture.
+    pub spectrum: bool,
+    pub strength: bool,
+    /// Settings the running overlay applies without a restart.
+    pub live_rendering: bool,
+    /// UI language, which relabels the tray menu.
+    pub language: bool,
+    /// Anything outside the groups above.
+    pub other: bool,
+}
+
+/// Compares each group's fields between `$before` and `$after` into a bool named after the
+/// group, and copies them into `$rest`, so each field is listed once and `other` sees the rest.
+macro_rules! diff_groups {
+    ($before:ident, $after:ident, $rest:ident, $($group:ident: [$($field:ident),+ $(,)?]),+ $(,)?) => {
+        $(
+            let $group = false $(|| $before.$field != $after.$field)+;
+            $($rest.$field = Clone::clone(&$after.$field);)+
+        )+
+    };
+}
+
+impl StateChanges {
+    pub fn between(before: &AppState, after: &AppState) -> Self {
+        let mut rest = before.clone();
+        diff_groups!(before, after, rest,
+            overlay: [overlay_enabled, last_overlay_enabled],
+            spectrum: [spectrum_name, spectrum_selection, noise_texture],
+            strength: [strength],
+            live_rendering: [
+                noise_mapping, noise_intensity, noise_target, blend_ratio, temporal_smoothing,
+                hue_gate_enabled, hue_gate_start, hue_gate_end, strength_curve, posterize_levels,
+                color_math, topmost_mode, test_pattern, solid_color, correct_cursor, exclude_shell,
+                hide_from_capture, target_window, target_window_only,
+            ],
+            language: [language],
+        );
+        let other = rest != *after;
+
+        Self { overlay, spectrum, strength, live_rendering, language, other }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl std::ops::BitOrAssign for StateChanges {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.overlay |= rhs.overlay;
+        self.spectrum |= rhs.spectrum;
+        self.strength |= rhs.strength;
+        self.live_rendering |= rhs.live_rendering;
+        self.language |= rhs.language;
+        self.other |= rhs.other;
+    }
+}
+
+/// Row of the `state` table holding the last summary of a `StartupTimer`.
+fn timing_key(name: &str) -> String {
+    format!("timing.{}", name)
+}
+
 enum WriteCommand {
     Update(AppState),
+    /// A finished overlay session, and how many sessions to keep.
+    RecordSession(SessionSummary, usize),
+    /// Overlay running time to add to a day's total.
+    AddActiveTime(chrono::NaiveDate, Duration),
+    ResetActiveTime(chrono::NaiveDate),
+    /// The summary line of a `StartupTimer`, stored under its name.
+    RecordTiming(&'static str, String),
+    Flush,
     Shutdown,
 }
 
+/// Keeps only the latest pending state and decides when it is due to be written.
+struct WriteCoalescer {
+    pending: Option<AppState>,
+    last_write: Option<Instant>,
+}
+
+impl WriteCoalescer {
+    fn new() -> Self {
+        Self {
+            pending: None,
+            last_write: None,
+        }
+    }
+
+    fn push(&mut self, state: AppState) {
+        self.pending = Some(state);
+    }
+
+    /// Time left until the pending state is due, or `None` when nothing is pending.
+    fn timeout(&self, now: Instant) -> Option<Duration> {
+        self.pending.as_ref()?;
+        Some(match self.last_write {
+            Some(last) => WRITE_INTERVAL.saturating_sub(now.duration_since(last)),
+            None => Duration::ZERO,
+        })
+    }
+
+    fn take_due(&mut self, now: Instant) -> Option<AppState> {
+        if self.timeout(now)? > Duration::ZERO {
+            return None;
+        }
+        self.take(now)
+    }
+
+    fn take(&mut self, now: Instant) -> Option<AppState> {
+        let state = self.pending.take()?;
+        self.last_write = Some(now);
+        Some(state)
+    }
+}
+
 pub struct StateManager {
-    app_data_dir: PathBuf,
+    dirs: DataDirs,
+    db_path: PathBuf,
     state: Arc<RwLock<AppState>>,
-    write_sender: Sender<WriteCommand>,
-    _write_thread: Option<thread::JoinHandle<()>>,
+    write_sender: Mutex<Sender<WriteCommand>>,
+    write_thread: Mutex<Option<thread::JoinHandle<()>>>,
+    worker_respawned: AtomicBool,
+    shutting_down: AtomicBool,
+    /// Where the corrupt database was moved to, until the GUI has shown the reset notice.
+    reset_notice: Mutex<Option<PathBuf>>,
+    /// What was imported from an old ColorInterlacer folder on this launch, until the GUI has shown it.
+    migration_report: Mutex<Option<MigrationReport>>,
+    /// What was copied from the roaming folder of an older version on this launch.
+    roaming_copy: Option<RoamingCopy>,
+    subscribers: Mutex<Vec<Sender<StateChanges>>>,
 }
 
-impl StateManager {
-    pub fn new() -> Result<Self> {
-        let app_data = std::env::var("APPDATA")
-            .context("Failed to get APPDATA environment variable")?;
+impl StateManager {
+    pub fn new() -> Result<Self> {
+        let dirs = Self::default_data_dirs()?;
+        let legacy_dir = dirs.roaming.as_ref().and_then(|roaming| roaming.parent()).map(|app_data| app_data.join(migration::LEGACY_DIR_NAME));
+        Self::open_with_legacy(dirs, legacy_dir.as_deref())
+    }
+
+    /// The folders `new` opens, see `DataDirs::resolve`.
+    pub fn default_data_dirs() -> Result<DataDirs> {
+        DataDirs::resolve(&Environment::current())
+    }
+
+    /// Opens the state with everything in `app_data_dir`.
+    pub fn open(app_data_dir: PathBuf) -> Result<Self> {
+        Self::open_with_legacy(DataDirs::single(app_data_dir, DataDirLayout::Portable), None)
+    }
+
+    /// Opens the state in `dirs`, copying the database of an older version from the roaming
+    /// folder or else importing `legacy_dir` first if the database is new.
+    pub fn open_with_legacy(dirs: DataDirs, legacy_dir: Option<&Path>) -> Result<Self> {
+        let roaming_copy = dirs.copy_from_roaming();
+        let db_path = dirs.local.join("state.db");
+        let fresh = !db_path.exists();
+
+        std::fs::create_dir_all(&dirs.local)
+            .context("Failed to create app data directory")?;
+        std::fs::create_dir_all(dirs.assets.join("spectrums"))
+            .context("Failed to create spectrums directory")?;
+        std::fs::create_dir_all(dirs.assets.join("noise"))
+            .context("Failed to create noise directory")?;
+
+        let migration = legacy_dir
+            .filter(|legacy| migration::should_import(legacy, &dirs.local, fresh))
+            .map(|legacy| migration::import_legacy(legacy, &dirs));
+
+        let (mut initial_state, reset_notice) = match Self::open_database(&db_path) {
+            Ok(state) => (state, None),
+            Err(e) => {
+                let e = anyhow::Error::from(e);
+                // Logger isn't up yet; main logs the reset once it is
+                let moved_to = Self::move_corrupt_database(&db_path)
+                    .with_context(|| format!("Database unusable ({:#}) and could not be moved aside", e))?;
+                let state = Self::open_database(&db_path).context("Failed to recreate database")?;
+                (state, Some(moved_to))
+            }
+        };
+        let (migration_report, migrated_state) = match migration {
+            Some((report, state)) => (Some(report), state),
+            None => (None, None),
+        };
+        let migrated = migrated_state.is_some();
+        if let Some(state) = migrated_state {
+            initial_state = state;
+        }
+
+        // Seed the active spectrum with the previous global values so upgrading loses nothing
+        if let Some(ref name) = initial_state.spectrum_name {
+            if !initial_state.spectrum_settings.contains_key(name) {
+                initial_state.remember_spectrum_settings();
+            }
+        }
+        if let Some(ref name) = initial_state.noise_texture {
+            if !initial_state.noise_settings.contains_key(name) {
+                initial_state.remember_noise_settings();
+            }
+        }
+        let state = Arc::new(RwLock::new(initial_state));
+
+        let (write_sender, write_thread) = Self::spawn_write_worker(db_path.clone());
+        if migrated {
+            let _ = write_sender.send(WriteCommand::Update(state.read().clone()));
+        }
+
+        Ok(Self {
+            dirs,
+            db_path,
+            state,
+            write_sender: Mutex::new(write_sender),
+            write_thread: Mutex::new(Some(write_thread)),
+            worker_respawned: AtomicBool::new(false),
+            shutting_down: AtomicBool::new(false),
+            reset_notice: Mutex::new(reset_notice),
+            migration_report: Mutex::new(migration_report),
+            roaming_copy,
+            subscribers: Mutex::new(Vec::new()),
+        })
+    }
+
+    /// Checks the database in `app_data_dir` without touching it: SQLite's own consistency check,
+    /// then whether the saved settings still load. Returns the schema version.
+    pub fn check_database(app_data_dir: &Path) -> Result<i32, ConfigError> {
+        let db_path = app_data_dir.join("state.db");
+        if !db_path.exists() {
+            return Err(ConfigError::Missing(db_path));
+        }
+
+        let conn = Connection::open_with_flags(&db_path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+            .map_err(ConfigError::database("open database"))?;
+        let check: String = conn
+            .query_row("PRAGMA quick_check", [], |row| row.get(0))
+            .map_err(ConfigError::database("read database"))?;
+        if check != "ok" {
+            return Err(ConfigError::Damaged(check));
+        }
+
+        let version = conn
+            .query_row("SELECT version FROM schema_version LIMIT 1", [], |row| row.get(0))
+            .map_err(ConfigError::database("read schema version"))?;
+        Self::load_state(&conn)?;
+        Ok(version)
+    }
+
+    fn open_database(db_path: &Path) -> Result<AppState, ConfigError> {
+        let conn = Connection::open(db_path).map_err(ConfigError::database("open database"))?;
+        Self::init_database(&conn).map_err(ConfigError::database("initialize database"))?;
+        Self::load_state(&conn)
+    }
+
+    /// Renames an unreadable database (and its WAL files) to `state.db.corrupt-<timestamp>`.
+    fn move_corrupt_database(db_path: &Path) -> Result<PathBuf> {
+        let timestamp = chrono::Local::now().format("%Y%m%d_%H%M%S");
+        let file_name = db_path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        let moved_to = db_path.with_file_name(format!("{}.corrupt-{}", file_name, timestamp));
+
+        std::fs::rename(db_path, &moved_to).context("Failed to rename corrupt database")?;
+        for suffix in ["-wal", "-shm"] {
+            let sidecar = db_path.with_file_name(format!("{}{}", file_name, suffix));
+            if sidecar.exists() {
+                let _ = std::fs::rename(&sidecar, format!("{}{}", moved_to.display(), suffix));
+            }
+        }
+
+        Ok(moved_to)
+    }
+
+    fn spawn_write_worker(db_path: PathBuf) -> (Sender<WriteCommand>, thread::JoinHandle<()>) {
+        let (write_sender, write_receiver): (Sender<WriteCommand>, Receiver<WriteCommand>) = unbounded();
+        let write_thread = thread::spawn(move || {
+            Self::write_worker(db_path, write_receiver);
+        });
+        (write_sender, write_thread)
+    }
+
+    /// Sends to the write worker, restarting it once if it has died.
+    fn send(&self, cmd: WriteCommand) {
+        let mut sender = self.write_sender.lock();
+        let Err(SendError(cmd)) = sender.send(cmd) else {
+            return;
+        };
 
-        let app_data_dir = PathBuf::from(app_data).join("ChromaBridge");
-        let db_path = app_data_dir.join("state.db");
+        if self.shutting_down.load(Ordering::Acquire) || self.worker_respawned.swap(true, Ordering::AcqRel) {
+            return;
+        }
 
-        std::fs::create_dir_all(&app_data_dir)
-            .context("Failed to create app data directory")?;
-        std::fs::create_dir_all(app_data_dir.join("assets").join("spectrums"))
-            .context("Failed to create spectrums directory")?;
-        std::fs::create_dir_all(app_data_dir.join("assets").join("noise"))
-            .context("Failed to create noise directory")?;
+        crate::log_error!("State write worker stopped unexpectedly - restarting it");
+        let (new_sender, new_thread) = Self::spawn_write_worker(self.db_path.clone());
+        *sender = new_sender;
+        *self.write_thread.lock() = Some(new_thread);
+        if sender.send(cmd).is_err() {
+            crate::log_error!("Failed to restart state write worker - settings will not be saved");
+        }
+    }
 
-        let conn = Connection::open(&db_path).context("Failed to open database")?;
-        Self::init_database(&conn)?;
+    /// Returns where the corrupt database was moved if settings were reset this session.
+    /// Only returns it once so the GUI notice shows on first open only.
+    pub fn take_reset_notice(&self) -> Option<PathBuf> {
+        self.reset_notice.lock().take()
+    }
 
-        let initial_state = Self::load_state(&conn)?;
-        let state = Arc::new(RwLock::new(initial_state));
+    pub fn corrupt_database_backup(&self) -> Option<PathBuf> {
+        self.reset_notice.lock().clone()
+    }
 
-        let (write_sender, write_receiver): (Sender<WriteCommand>, Receiver<WriteCommand>) = unbounded();
+    /// What was imported from the ColorInterlacer folder on this launch.
+    /// Only returned once so the GUI status line shows on first open only.
+    pub fn take_migration_report(&self) -> Option<MigrationReport> {
+        self.migration_report.lock().take()
+    }
 
-        let db_path_clone = db_path.clone();
-        let write_thread = thread::spawn(move || {
-            Self::write_worker(db_path_clone, write_receiver);
-        });
+    pub fn migration_report(&self) -> Option<MigrationReport> {
+        self.migration_report.lock().clone()
+    }
 
-        Ok(Self {
-            app_data_dir,
-            state,
-            write_sender,
-            _write_thread: Some(write_thread),
-        })
+    pub fn roaming_copy(&self) -> Option<&RoamingCopy> {
+        self.roaming_copy.as_ref()
     }
 
-    fn init_database(conn: &Connection) -> Result<()> {
+    fn init_database(conn: &Connection) -> rusqlite::Result<()> {
         conn.pragma_update(None, "journal_mode", "WAL")?;
         conn.pragma_update(None, "synchronous", "NORMAL")?;
 
@@ -129,6 +1236,30 @@ impl StateManager {
             [],
         )?;
 
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS sessions (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                started_at TEXT NOT NULL,
+                ended_at TEXT NOT NULL,
+                monitor INTEGER NOT NULL,
+                spectrum TEXT,
+                average_fps REAL NOT NULL,
+                low_fps REAL NOT NULL,
+                max_frame_time_ms REAL NOT NULL,
+                error_count INTEGER NOT NULL,
+                recovery_count INTEGER NOT NULL
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS active_time (
+                day TEXT PRIMARY KEY,
+                milliseconds INTEGER NOT NULL
+            )",
+            [],
+        )?;
+
         let current_version: Option<i32> = conn
             .query_row("SELECT version FROM schema_version LIMIT 1", [], |row| row.get(0))
             .ok();
@@ -140,14 +1271,14 @@ impl StateManager {
         Ok(())
     }
 
-    fn load_state(conn: &Connection) -> Result<AppState> {
+    pub(crate) fn load_state(conn: &Connection) -> Result<AppState, ConfigError> {
         let json_str: Option<String> = conn
             .query_row("SELECT value FROM state WHERE key = 'app_state'", [], |row| row.get(0))
             .ok();
 
         match json_str {
             Some(json) => {
-                serde_json::from_str(&json).context("Failed to parse state JSON")
+                serde_json::from_str(&json).map_err(|source| ConfigError::Parse { source })
             }
             None => Ok(AppState::default()),
         }
@@ -165,47 +1296,236 @@ impl StateManager {
         let _ = conn.pragma_update(None, "journal_mode", "WAL");
         let _ = conn.pragma_update(None, "synchronous", "NORMAL");
 
-        while let Ok(cmd) = receiver.recv() {
+        let mut coalescer = WriteCoalescer::new();
+
+        loop {
+            let cmd = match coalescer.timeout(Instant::now()) {
+                Some(timeout) => receiver.recv_timeout(timeout),
+                None => receiver.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            };
+
             match cmd {
-                WriteCommand::Update(state) => {
-                    if let Ok(json) = serde_json::to_string(&state) {
-                        if let Err(e) = conn.execute(
-                            "INSERT OR REPLACE INTO state (key, value) VALUES ('app_state', ?1)",
-                            params![json],
-                        ) {
-                            crate::log_error!("Failed to write state: {}", e);
-                        }
+                Ok(WriteCommand::Update(state)) => coalescer.push(state),
+                Ok(WriteCommand::RecordSession(summary, keep)) => Self::write_session(&conn, &summary, keep),
+                Ok(WriteCommand::AddActiveTime(day, time)) => Self::write_active_time(&conn, day, Some(time)),
+                Ok(WriteCommand::ResetActiveTime(day)) => Self::write_active_time(&conn, day, None),
+                Ok(WriteCommand::RecordTiming(name, summary)) => Self::write_timing(&conn, name, &summary),
+                Ok(WriteCommand::Flush) => {
+                    if let Some(state) = coalescer.take(Instant::now()) {
+                        Self::write_state(&conn, &state);
                     }
                 }
-                WriteCommand::Shutdown => {
+                Ok(WriteCommand::Shutdown) | Err(RecvTimeoutError::Disconnected) => {
+                    if let Some(state) = coalescer.take(Instant::now()) {
+                        Self::write_state(&conn, &state);
+                    }
                     break;
                 }
+                Err(RecvTimeoutError::Timeout) => {}
+            }
+
+            if let Some(state) = coalescer.take_due(Instant::now()) {
+                Self::write_state(&conn, &state);
             }
         }
 
         let _ = conn.pragma_update(None, "wal_checkpoint", "TRUNCATE");
     }
 
+    fn write_state(conn: &Connection, state: &AppState) {
+        if let Ok(json) = serde_json::to_string(state) {
+            if let Err(e) = conn.execute(
+                "INSERT OR REPLACE INTO state (key, value) VALUES ('app_state', ?1)",
+                params![json],
+            ) {
+                crate::log_error!("Failed to write state: {}", e);
+            }
+        }
+    }
+
+    fn write_session(conn: &Connection, summary: &SessionSummary, keep: usize) {
+        let result = conn
+            .execute(
+                "INSERT INTO sessions (started_at, ended_at, monitor, spectrum, average_fps, low_fps, max_frame_time_ms, error_count, recovery_count)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                params![
+                    summary.started_at.to_rfc3339(),
+                    summary.ended_at.to_rfc3339(),
+                    summary.monitor as i64,
+                    summary.spectrum,
+                    summary.average_fps,
+                    summary.low_fps,
+                    summary.max_frame_time_ms,
+                    summary.error_count,
+                    summary.recovery_count,
+                ],
+            )
+            .and_then(|_| {
+                conn.execute(
+                    "DELETE FROM sessions WHERE id NOT IN (SELECT id FROM sessions ORDER BY id DESC LIMIT ?1)",
+                    params![keep as i64],
+                )
+            });
+        if let Err(e) = result {
+            crate::log_error!("Failed to record overlay session: {}", e);
+        }
+    }
+
+    /// Adds `time` to the total of `day`, or clears it with `None`.
+    fn write_active_time(conn: &Connection, day: chrono::NaiveDate, time: Option<Duration>) {
+        let day = day.to_string();
+        let result = match time {
+            Some(time) => conn.execute(
+                "INSERT INTO active_time (day, milliseconds) VALUES (?1, ?2)
+                 ON CONFLICT(day) DO UPDATE SET milliseconds = milliseconds + excluded.milliseconds",
+                params![day, time.as_millis() as i64],
+            ),
+            None => conn.execute("DELETE FROM active_time WHERE day = ?1", params![day]),
+        };
+        if let Err(e) = result {
+            crate::log_error!("Failed to record active time: {}", e);
+        }
+    }
+
+    fn write_timing(conn: &Connection, name: &str, summary: &str) {
+        if let Err(e) = conn.execute(
+            "INSERT OR REPLACE INTO state (key, value) VALUES (?1, ?2)",
+            params![timing_key(name), summary],
+        ) {
+            crate::log_error!("Failed to record {} timing: {}", name, e);
+        }
+    }
+
+    fn load_sessions(conn: &Connection, limit: usize) -> Result<Vec<SessionSummary>> {
+        let parse_time = |text: String| {
+            chrono::DateTime::parse_from_rfc3339(&text)
+                .map(|time| time.with_timezone(&chrono::Local))
+                .map_err(|e| rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, Box::new(e)))
+        };
+
+        let mut statement = conn.prepare(
+            "SELECT started_at, ended_at, monitor, spectrum, average_fps, low_fps, max_frame_time_ms, error_count, recovery_count
+             FROM sessions ORDER BY id DESC LIMIT ?1",
+        )?;
+        let sessions = statement
+            .query_map(params![limit as i64], |row| {
+                Ok(SessionSummary {
+                    started_at: parse_time(row.get(0)?)?,
+                    ended_at: parse_time(row.get(1)?)?,
+                    monitor: row.get::<_, i64>(2)? as usize,
+                    spectrum: row.get(3)?,
+                    average_fps: row.get(4)?,
+                    low_fps: row.get(5)?,
+                    max_frame_time_ms: row.get(6)?,
+                    error_count: row.get(7)?,
+                    recovery_count: row.get(8)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(sessions)
+    }
+
+    /// Stores an overlay session summary, keeping the newest `session_history_limit` ones.
+    pub fn record_session(&self, summary: SessionSummary) {
+        let keep = self.read(|s| s.session_history_limit);
+        self.send(WriteCommand::RecordSession(summary, keep));
+    }
+
+    /// Up to `limit` stored session summaries, newest first.
+    pub fn recent_sessions(&self, limit: usize) -> Result<Vec<SessionSummary>> {
+        let conn = Connection::open(&self.db_path).context("Failed to open database")?;
+        Self::load_sessions(&conn, limit).context("Failed to read performance history")
+    }
+
+    /// Adds overlay running time to the stored total of `day`.
+    pub fn record_active_time(&self, day: chrono::NaiveDate, time: Duration) {
+        self.send(WriteCommand::AddActiveTime(day, time));
+    }
+
+    pub fn reset_active_time(&self, day: chrono::NaiveDate) {
+        self.send(WriteCommand::ResetActiveTime(day));
+    }
+
+    /// Keeps the summary of `timer` as the last one of its name, replacing the previous.
+    pub fn record_timing(&self, timer: &StartupTimer) {
+        self.send(WriteCommand::RecordTiming(timer.name(), timer.summary()));
+    }
+
+    /// The last stored summary of the `StartupTimer` called `name`.
+    pub fn last_timing(&self, name: &str) -> Result<Option<String>> {
+        let conn = Connection::open(&self.db_path).context("Failed to open database")?;
+        conn.query_row("SELECT value FROM state WHERE key = ?1", params![timing_key(name)], |row| row.get(0))
+            .optional()
+            .context("Failed to read startup timing")
+    }
+
+    /// The stored overlay running time of `day`.
+    pub fn active_time(&self, day: chrono::NaiveDate) -> Result<Duration> {
+        let conn = Connection::open(&self.db_path).context("Failed to open database")?;
+        let milliseconds: Option<i64> = conn
+            .query_row("SELECT milliseconds FROM active_time WHERE day = ?1", params![day.to_string()], |row| row.get(0))
+            .optional()
+            .context("Failed to read active time")?;
+        Ok(Duration::from_millis(milliseconds.unwrap_or(0).max(0) as u64))
+    }
+
+    /// Persists the latest state and waits for the write worker to exit.
+    /// Returns false if the worker did not finish within `timeout`.
+    pub fn flush_and_join(&self, timeout: Duration) -> bool {
+        self.shutting_down.store(true, Ordering::Release);
+        let state = self.state.read().clone();
+        self.send(WriteCommand::Update(state));
+        self.send(WriteCommand::Shutdown);
+
+        let Some(handle) = self.write_thread.lock().take() else {
+            return true;
+        };
+
+        let deadline = Instant::now() + timeout;
+        while !handle.is_finished() {
+            if Instant::now() >= deadline {
+                return false;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        let _ = handle.join();
+        true
+    }
+
+    /// The folder of the database, logs, crash reports and debug output.
     pub fn app_data_dir(&self) -> &PathBuf {
-        &self.app_data_dir
+        &self.dirs.local
+    }
+
+    pub fn data_dirs(&self) -> &DataDirs {
+        &self.dirs
+    }
+
+    pub fn assets_dir(&self) -> PathBuf {
+        self.dirs.assets.clone()
     }
 
     pub fn spectrums_dir(&self) -> PathBuf {
-        self.app_data_dir.join("assets").join("spectrums")
+        self.assets_dir().join("spectrums")
     }
 
     pub fn noise_dir(&self) -> PathBuf {
-        self.app_data_dir.join("assets").join("noise")
+        self.assets_dir().join("noise")
     }
 
-    pub fn get_spectrum_path(&self, name: &str) -> PathBuf {
-        self.spectrums_dir().join(format!("{}.json", name))
+    /// The file of spectrum `name`, e.g. "deutan" or "gaming/deutan-strong". Fails for names that
+    /// would leave the spectrum folder.
+    pub fn get_spectrum_path(&self, name: &str) -> Result<PathBuf> {
+        check_asset_name(name)?;
+        Ok(self.spectrums_dir().join(format!("{}.json", name)))
     }
 
     pub fn get_noise_path(&self, name: &str) -> PathBuf {
         self.noise_dir().join(format!("{}.png", name))
     }
 
+    /// Runs `f` under the read lock, so everything it reads belongs to the same state. Separate
+    /// calls can see an update land in between; read related fields in one call or use `snapshot`.
     pub fn read<F, R>(&self, f: F) -> R
     where
         F: FnOnce(&AppState) -> R,
@@ -213,42 +1533,111 @@ impl StateManager {
         f(&self.state.read())
     }
 
+    /// A copy of the whole state as of one moment, for code that reads many fields or reads them
+    /// over time without holding the lock, such as the overlay start.
+    pub fn snapshot(&self) -> AppState {
+        self.state.read().clone()
+    }
+
+    /// Applies `f` as one transaction: readers see the state from before or after all of it,
+    /// and it is persisted and reported to subscribers as one change. Changes that only make
+    /// sense together, like a spectrum and the strength picked for it, belong in one call.
     pub fn update<F>(&self, f: F)
     where
         F: FnOnce(&mut AppState),
     {
-        let mut state = self.state.write();
-        f(&mut state);
-        let _ = self.write_sender.send(WriteCommand::Update(state.clone()));
+        let (before, after) = {
+            let mut state = self.state.write();
+            let before = state.clone();
+            f(&mut state);
+            self.send(WriteCommand::Update(state.clone()));
+            (before, state.clone())
+        };
+        self.notify(StateChanges::between(&before, &after));
+    }
+
+    /// Updates the in-memory state without persisting it; for high-frequency UI changes
+    /// such as slider drags. Call `flush` once the interaction ends. Atomic like `update`.
+    pub fn update_ephemeral<F>(&self, f: F)
+    where
+        F: FnOnce(&mut AppState),
+    {
+        let (before, after) = {
+            let mut state = self.state.write();
+            let before = state.clone();
+            f(&mut state);
+            (before, state.clone())
+        };
+        self.notify(StateChanges::between(&before, &after));
+    }
+
+    /// Returns a channel that receives a summary after every update that changed something.
+    /// Dropping the receiver unsubscribes.
+    pub fn subscribe(&self) -> Receiver<StateChanges> {
+        let (sender, receiver) = unbounded();
+        self.subscribers.lock().push(sender);
+        receiver
     }
 
+    /// Called once the state lock is released, so listeners may read the state right away.
+    fn notify(&self, changes: StateChanges) {
+        if changes.is_empty() {
+            return;
+        }
+        self.subscribers.lock().retain(|subscriber| subscriber.send(changes).is_ok());
+    }
+
+    /// Persists the current state immediately, bypassing write coalescing.
+    pub fn flush(&self) {
+        let state = self.state.read().clone();
+        self.send(WriteCommand::Update(state));
+        self.send(WriteCommand::Flush);
+    }
+
+    /// Names of the loadable spectra, including those in subfolders as "folder/name". Sorted by
+    /// folder, top-level spectra first.
     pub fn list_spectrum_files(&self) -> Result<Vec<String>> {
-        use crate::SpectrumPair;
         let mut files = Vec::new();
+        self.collect_spectrum_files(&self.spectrums_dir(), "", 0, &mut files);
 
-        if let Ok(entries) = std::fs::read_dir(self.spectrums_dir()) {
-            for entry in entries.flatten() {
-                if let Some(ext) = entry.path().extension() {
-                    if ext == "json" {
-                        if let Some(name) = entry.path().file_stem() {
-                            let name_str = name.to_string_lossy().to_string();
-                            let path = self.get_spectrum_path(&name_str);
-                            if SpectrumPair::load_from_file(path).is_ok() {
-                                files.push(name_str);
-                            }
-                        }
-                    }
+        files.sort_by(|a, b| split_spectrum_name(a).cmp(&split_spectrum_name(b)));
+        Ok(files)
+    }
+
+    fn collect_spectrum_files(&self, dir: &Path, prefix: &str, depth: usize, files: &mut Vec<String>) {
+        use crate::SpectrumPair;
+
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                if depth < MAX_SPECTRUM_FOLDER_DEPTH {
+                    let folder = format!("{}{}/", prefix, entry.file_name().to_string_lossy());
+                    self.collect_spectrum_files(&path, &folder, depth + 1, files);
                 }
+                continue;
             }
-        }
+            if path.extension().is_none_or(|ext| ext != "json") {
+                continue;
+            }
+            let Some(stem) = path.file_stem() else {
+                continue;
+            };
 
-        files.sort();
-        Ok(files)
+            let name = format!("{}{}", prefix, stem.to_string_lossy());
+            // Also skips files in hidden or otherwise unnameable folders
+            if check_asset_name(&name).is_ok() && SpectrumPair::load_from_file(&path).is_ok() {
+                files.push(name);
+            }
+        }
     }
 
     pub fn list_noise_files(&self) -> Result<Vec<String>> {
         use crate::NoiseTexture;
         let mut files = Vec::new();
+        let max_size = self.read(|s| s.max_noise_size);
 
         if let Ok(entries) = std::fs::read_dir(self.noise_dir()) {
             for entry in entries.flatten() {
@@ -257,7 +1646,8 @@ impl StateManager {
                         if let Some(name) = entry.path().file_stem() {
                             let name_str = name.to_string_lossy().to_string();
                             let path = self.get_noise_path(&name_str);
-                            if NoiseTexture::load_from_file(path).is_ok() {
+                            // Only the header is read; the overlay reports files that fail to decode
+                            if NoiseTexture::probe_with_max_size(path, max_size).is_ok() {
                                 files.push(name_str);
                             }
                         }
@@ -273,6 +1663,567 @@ impl StateManager {
 
 impl Drop for StateManager {
     fn drop(&mut self) {
-        let _ = self.write_sender.send(WriteCommand::Shutdown);
+        self.shutting_down.store(true, Ordering::Release);
+        self.send(WriteCommand::Shutdown);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+
+    #[test]
+    fn strength_curves_keep_the_endpoints() {
+        for curve in [StrengthCurve::Linear, StrengthCurve::Perceptual] {
+            assert_eq!(curve.apply(0.0), 0.0);
+            assert_eq!(curve.apply(1.0), 1.0);
+        }
+        assert_eq!(StrengthCurve::Linear.apply(0.25), 0.25);
+        // The weak end is lifted
+        assert!(StrengthCurve::Perceptual.apply(0.25) > 0.5);
+        assert!(StrengthCurve::Perceptual.apply(0.9) > 0.9);
+    }
+
+    fn state_with_strength(strength: f32) -> AppState {
+        AppState {
+            strength,
+            ..AppState::default()
+        }
+    }
+
+    fn has_corrupt_backup(dir: &Path) -> bool {
+        std::fs::read_dir(dir)
+            .unwrap()
+            .flatten()
+            .any(|e| e.file_name().to_string_lossy().starts_with("state.db.corrupt-"))
+    }
+
+    #[test]
+    fn garbage_database_is_moved_aside_and_reset() {
+        let dir = temp_dir("garbage-db");
+        std::fs::write(dir.join("state.db"), vec![0xAB; 4096]).unwrap();
+
+        let manager = StateManager::open(dir.clone()).unwrap();
+        assert!(manager.take_reset_notice().is_some());
+        assert!(manager.take_reset_notice().is_none());
+        assert_eq!(manager.read(|s| s.strength), AppState::default().strength);
+        assert!(has_corrupt_backup(&dir));
+
+        assert!(manager.flush_and_join(Duration::from_secs(2)));
+        drop(manager);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn damaged_pages_are_reported_by_the_check() {
+        let dir = temp_dir("damaged-db");
+        assert!(matches!(StateManager::check_database(&dir), Err(ConfigError::Missing(_))));
+
+        let db_path = dir.join("state.db");
+        let (root_page, page_size) = {
+            let conn = Connection::open(&db_path).unwrap();
+            StateManager::init_database(&conn).unwrap();
+            conn.execute("INSERT OR REPLACE INTO state (key, value) VALUES ('app_state', '{}')", []).unwrap();
+            let root_page: u64 = conn.query_row("SELECT rootpage FROM sqlite_master WHERE tbl_name = 'state' AND type = 'index'", [], |row| row.get(0)).unwrap();
+            let page_size: u64 = conn.query_row("PRAGMA page_size", [], |row| row.get(0)).unwrap();
+            (root_page, page_size)
+        };
+        assert!(StateManager::check_database(&dir).is_ok());
+
+        // Points the first cell of the settings key index past the end of its page
+        let mut bytes = std::fs::read(&db_path).unwrap();
+        let cell_pointer = ((root_page - 1) * page_size + 8) as usize;
+        bytes[cell_pointer..cell_pointer + 2].copy_from_slice(&[0xFF, 0xFF]);
+        std::fs::write(&db_path, bytes).unwrap();
+        assert!(matches!(StateManager::check_database(&dir), Err(ConfigError::Damaged(_))));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn unparseable_state_json_is_reset() {
+        let dir = temp_dir("bad-json");
+        {
+            let conn = Connection::open(dir.join("state.db")).unwrap();
+            StateManager::init_database(&conn).unwrap();
+            conn.execute(
+                "INSERT OR REPLACE INTO state (key, value) VALUES ('app_state', ?1)",
+                params!["{not json"],
+            ).unwrap();
+            assert!(matches!(StateManager::load_state(&conn), Err(ConfigError::Parse { .. })));
+        }
+
+        let manager = StateManager::open(dir.clone()).unwrap();
+        assert!(manager.corrupt_database_backup().is_some());
+        assert!(has_corrupt_backup(&dir));
+
+        assert!(manager.flush_and_join(Duration::from_secs(2)));
+        drop(manager);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn healthy_database_is_not_reset() {
+        let dir = temp_dir("healthy-db");
+        {
+            let manager = StateManager::open(dir.clone()).unwrap();
+            manager.update(|s| s.strength = 0.25);
+            assert!(manager.flush_and_join(Duration::from_secs(2)));
+        }
+
+        let manager = StateManager::open(dir.clone()).unwrap();
+        assert!(manager.corrupt_database_backup().is_none());
+        assert_eq!(manager.read(|s| s.strength), 0.25);
+
+        assert!(manager.flush_and_join(Duration::from_secs(2)));
+        drop(manager);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn database_check_changes_nothing() {
+        let dir = temp_dir("check-db");
+        assert!(matches!(StateManager::check_database(&dir), Err(ConfigError::Missing(_))));
+        assert!(!dir.join("state.db").exists());
+
+        std::fs::write(dir.join("state.db"), vec![0xAB; 4096]).unwrap();
+        assert!(matches!(StateManager::check_database(&dir), Err(ConfigError::Database { .. })));
+        assert!(!has_corrupt_backup(&dir));
+        std::fs::remove_file(dir.join("state.db")).unwrap();
+
+        {
+            let manager = StateManager::open(dir.clone()).unwrap();
+            assert!(manager.flush_and_join(Duration::from_secs(2)));
+        }
+        assert_eq!(StateManager::check_database(&dir).unwrap(), SCHEMA_VERSION);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn battery_saver_restores_previous_settings() {
+        let mut state = AppState {
+            fullscreen_behavior: FullscreenBehavior::Reduce,
+            temporal_smoothing: 0.4,
+            ..AppState::default()
+        };
+
+        state.set_battery_saver(true);
+        assert!(state.battery_saver_active());
+        assert_eq!(state.fullscreen_behavior, FullscreenBehavior::Pause);
+        assert_eq!(state.temporal_smoothing, 0.0);
+
+        // Turning it on twice must not save the battery saver values as the user's
+        state.set_battery_saver(true);
+        state.set_battery_saver(false);
+        assert!(!state.battery_saver_active());
+        assert_eq!(state.fullscreen_behavior, FullscreenBehavior::Reduce);
+        assert_eq!(state.temporal_smoothing, 0.4);
+    }
+
+    #[test]
+    fn update_banner_shows_newer_undismissed_releases() {
+        let check = |version: &str| UpdateCheck::new(
+            update_check::DEFAULT_MANIFEST_URL,
+            Release { version: version.into(), url: "https://example.com".into() },
+            std::time::SystemTime::now(),
+        );
+        let mut state = AppState { check_for_updates: true, last_update_check: Some(check("v999.0.0")), ..AppState::default() };
+        assert_eq!(state.update_banner().map(|r| r.version.as_str()), Some("v999.0.0"));
+
+        state.dismissed_update = Some("v999.0.0".into());
+        assert!(state.update_banner().is_none());
+        assert!(state.available_update().is_some());
+        state.last_update_check = Some(check("v999.0.1"));
+        assert!(state.update_banner().is_some());
+
+        state.last_update_check = Some(check(update_check::CURRENT_VERSION));
+        assert!(state.available_update().is_none());
+        state.last_update_check = Some(check("v999.0.1"));
+        state.check_for_updates = false;
+        assert!(state.available_update().is_none());
+    }
+
+    #[test]
+    fn hue_gate_is_remembered_per_spectrum() {
+        let mut state = AppState::default();
+        state.select_spectrum("deutan".into());
+        state.hue_gate_enabled = true;
+        state.hue_gate_start = 330.0;
+        state.hue_gate_end = 30.0;
+        state.remember_spectrum_settings();
+
+        state.select_spectrum("tritan".into());
+        state.hue_gate_enabled = false;
+        state.remember_spectrum_settings();
+
+        state.select_spectrum("deutan".into());
+        assert_eq!(state.hue_gate(), Some(HueGate { start: 330.0, end: 30.0 }));
+    }
+
+    #[test]
+    fn noise_settings_follow_the_noise_texture() {
+        let mut state = AppState::default();
+        state.select_noise(Some("lines".into()));
+        state.noise_mapping = NoiseMapping::PixelExact;
+        state.noise_intensity = 0.5;
+        state.remember_noise_settings();
+
+        state.select_noise(Some("blobs".into()));
+        assert_eq!(state.noise_mapping, NoiseMapping::PixelExact);
+        state.noise_mapping = NoiseMapping::Stretch;
+        state.noise_intensity = 1.0;
+        state.remember_noise_settings();
+
+        state.select_noise(Some("lines".into()));
+        assert_eq!((state.noise_mapping, state.noise_intensity), (NoiseMapping::PixelExact, 0.5));
+
+        // Configs from before the map load with it empty
+        let mut json = serde_json::to_value(AppState::default()).unwrap();
+        json.as_object_mut().unwrap().remove("noise_settings");
+        assert!(serde_json::from_value::<AppState>(json).unwrap().noise_settings.is_empty());
+    }
+
+    #[test]
+    fn section_resets_keep_the_correction() {
+        let mut state = AppState::default();
+        state.select_spectrum("deutan".into());
+        state.strength = 0.6;
+        state.hue_gate_enabled = true;
+        state.topmost_mode = TopmostMode::Aggressive;
+        state.control_server_token = "token".into();
+        state.overlay_source = OverlaySource::SolidColor;
+        state.max_noise_size = 8192;
+        state.render_thread_priority = RenderThreadPriority::Mmcss;
+        state.target_window = Some(TargetWindow { process_name: "game.exe".into(), title_pattern: String::new() });
+        state.target_window_only = true;
+        state.gui_theme = GuiTheme::HighContrast;
+        state.remember_spectrum_settings();
+
+        state.reset_advanced_settings();
+        assert_eq!(state.hue_gate(), None);
+        assert_eq!(state.topmost_mode, TopmostMode::Polite);
+        assert_eq!(state.gui_theme, GuiTheme::Auto);
+        assert_eq!(state.control_server_token, "token");
+        assert_eq!(state.overlay_source, OverlaySource::SolidColor);
+        // Back to full screen, but the picked window is kept for turning it on again
+        assert_eq!(state.correction_target(), None);
+        assert!(state.target_window.is_some());
+        assert_eq!(state.spectrum_name.as_deref(), Some("deutan"));
+        assert_eq!(state.strength, 0.6);
+        // The remembered per-spectrum gate is reset too, so switching back doesn't restore it
+        assert!(!state.spectrum_settings["deutan"].hue_gate_enabled);
+
+        state.reset_developer_settings();
+        assert_eq!(state.overlay_source, OverlaySource::DesktopCapture);
+        assert_eq!(state.max_noise_size, crate::noise::DEFAULT_MAX_NOISE_SIZE);
+        assert_eq!(state.render_thread_priority, RenderThreadPriority::AboveNormal);
+        assert_eq!(state.strength, 0.6);
+    }
+
+    #[test]
+    fn session_history_keeps_the_newest_rows() {
+        let dir = temp_dir("sessions");
+        let manager = StateManager::open(dir.clone()).unwrap();
+        manager.update(|s| s.session_history_limit = 2);
+
+        for fps in [30.0, 60.0, 120.0] {
+            let now = chrono::Local::now();
+            manager.record_session(SessionSummary {
+                started_at: now - chrono::Duration::seconds(5),
+                ended_at: now,
+                monitor: 1,
+                spectrum: Some("deutan".into()),
+                average_fps: fps,
+                low_fps: fps / 2.0,
+                max_frame_time_ms: 40.0,
+                error_count: 0,
+                recovery_count: 0,
+            });
+        }
+        assert!(manager.flush_and_join(Duration::from_secs(2)));
+
+        let sessions = manager.recent_sessions(50).unwrap();
+        assert_eq!(sessions.iter().map(|s| s.average_fps).collect::<Vec<_>>(), vec![120.0, 60.0]);
+        assert_eq!(sessions[0].spectrum.as_deref(), Some("deutan"));
+        assert_eq!(sessions[0].duration(), Duration::from_secs(5));
+
+        drop(manager);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn active_time_adds_up_per_day() {
+        let dir = temp_dir("active_time");
+        let manager = StateManager::open(dir.clone()).unwrap();
+        let day = chrono::NaiveDate::from_ymd_opt(2026, 3, 2).unwrap();
+        let other_day = day.succ_opt().unwrap();
+
+        manager.record_active_time(day, Duration::from_secs(300));
+        manager.record_active_time(day, Duration::from_millis(1500));
+        manager.record_active_time(other_day, Duration::from_secs(60));
+        manager.reset_active_time(other_day);
+        assert!(manager.flush_and_join(Duration::from_secs(2)));
+
+        assert_eq!(manager.active_time(day).unwrap(), Duration::from_millis(301_500));
+        assert_eq!(manager.active_time(other_day).unwrap(), Duration::ZERO);
+
+        drop(manager);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn only_the_last_timing_is_kept() {
+        let dir = temp_dir("timing");
+        let manager = StateManager::open(dir.clone()).unwrap();
+
+        let mut first = StartupTimer::start("startup");
+        first.checkpoint("state");
+        manager.record_timing(&first);
+        let mut second = StartupTimer::start("startup");
+        second.checkpoint("tray");
+        manager.record_timing(&second);
+        assert!(manager.flush_and_join(Duration::from_secs(2)));
+
+        assert_eq!(manager.last_timing("startup").unwrap(), Some(second.summary()));
+        assert_eq!(manager.last_timing("overlay").unwrap(), None);
+
+        drop(manager);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn changes_are_grouped() {
+        let before = AppState::default();
+
+        let after = state_with_strength(before.strength + 0.1);
+        assert_eq!(StateChanges::between(&before, &after), StateChanges { strength: true, ..Default::default() });
+
+        let after = AppState { overlay_enabled: true, test_pattern: TestPattern::GreyRamp, ..AppState::default() };
+        assert_eq!(
+            StateChanges::between(&before, &after),
+            StateChanges { overlay: true, live_rendering: true, ..Default::default() }
+        );
+
+        let after = AppState { language: Language::German, ..AppState::default() };
+        assert_eq!(StateChanges::between(&before, &after), StateChanges { language: true, ..Default::default() });
+
+        let after = AppState { log_retention_count: before.log_retention_count + 1, ..AppState::default() };
+        assert_eq!(StateChanges::between(&before, &after), StateChanges { other: true, ..Default::default() });
+
+        assert!(StateChanges::between(&before, &before.clone()).is_empty());
+    }
+
+    #[test]
+    fn subscribers_are_notified_after_the_lock_is_released() {
+        let dir = temp_dir("subscribe");
+        let manager = StateManager::open(dir.clone()).unwrap();
+        let changes = manager.subscribe();
+
+        manager.update(|s| s.strength = 0.25);
+        // Reading from the listener side must not deadlock
+        assert_eq!(changes.try_recv().unwrap(), StateChanges { strength: true, ..Default::default() });
+        assert_eq!(manager.read(|s| s.strength), 0.25);
+
+        // Updates that change nothing are not reported
+        manager.update(|s| s.strength = 0.25);
+        assert!(changes.try_recv().is_err());
+
+        drop(changes);
+        manager.update_ephemeral(|s| s.strength = 0.5);
+        assert!(manager.subscribers.lock().is_empty());
+
+        assert!(manager.flush_and_join(Duration::from_secs(2)));
+        drop(manager);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn snapshots_never_show_half_an_update() {
+        let dir = temp_dir("snapshot");
+        let manager = StateManager::open(dir.clone()).unwrap();
+        manager.update(|s| {
+            s.last_monitor = Some(0);
+            s.strength = 0.0;
+        });
+
+        let done = AtomicBool::new(false);
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                for i in 1..=2000usize {
+                    manager.update(|s| {
+                        s.last_monitor = Some(i);
+                        s.strength = i as f32;
+                    });
+                }
+                done.store(true, Ordering::Release);
+            });
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    while !done.load(Ordering::Acquire) {
+                        let s = manager.snapshot();
+                        assert_eq!(s.last_monitor.unwrap() as f32, s.strength);
+                    }
+                });
+            }
+        });
+        assert_eq!(manager.read(|s| (s.last_monitor, s.strength)), (Some(2000), 2000.0));
+
+        assert!(manager.flush_and_join(Duration::from_secs(2)));
+        drop(manager);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn first_update_is_written_immediately() {
+        let start = Instant::now();
+        let mut coalescer = WriteCoalescer::new();
+
+        coalescer.push(state_with_strength(0.5));
+        assert_eq!(coalescer.take_due(start).map(|s| s.strength), Some(0.5));
+        assert!(coalescer.timeout(start).is_none());
+    }
+
+    #[test]
+    fn rapid_updates_are_coalesced_to_latest() {
+        let start = Instant::now();
+        let mut coalescer = WriteCoalescer::new();
+        let mut writes = Vec::new();
+
+        // Simulate a 1 second slider drag at ~100 updates per second
+        for i in 0..100 {
+            let now = start + Duration::from_millis(i * 10);
+            coalescer.push(state_with_strength(i as f32 / 100.0));
+            if let Some(state) = coalescer.take_due(now) {
+                writes.push(state.strength);
+            }
+        }
+
+        assert!(writes.len() <= 5, "expected at most 5 writes, got {}", writes.len());
+
+        // The final value is still pending and gets flushed on shutdown
+        let last = coalescer.take(start + Duration::from_millis(1000)).map(|s| s.strength);
+        assert_eq!(last, Some(0.99));
+    }
+
+    #[test]
+    fn pending_state_becomes_due_after_interval() {
+        let start = Instant::now();
+        let mut coalescer = WriteCoalescer::new();
+
+        coalescer.push(state_with_strength(0.1));
+        assert!(coalescer.take_due(start).is_some());
+
+        coalescer.push(state_with_strength(0.2));
+        let soon = start + Duration::from_millis(100);
+        assert!(coalescer.take_due(soon).is_none());
+        assert_eq!(coalescer.timeout(soon), Some(Duration::from_millis(150)));
+
+        let later = start + WRITE_INTERVAL;
+        assert_eq!(coalescer.take_due(later).map(|s| s.strength), Some(0.2));
+    }
+
+    #[test]
+    fn spectrum_names_cannot_leave_the_spectrum_folder() {
+        for name in ["deutan", "gaming/deutan-strong", "a/b/c"] {
+            assert!(check_asset_name(name).is_ok(), "{}", name);
+        }
+        for name in ["", "../secret", "gaming/../../x", "/etc/passwd", "C:/x", "gaming\\x", "gaming//x", ".hidden", "gaming/"] {
+            assert!(check_asset_name(name).is_err(), "{}", name);
+        }
+        assert_eq!(split_spectrum_name("gaming/deutan-strong"), ("gaming", "deutan-strong"));
+        assert_eq!(split_spectrum_name("deutan"), ("", "deutan"));
+    }
+
+    #[test]
+    fn spectra_in_subfolders_are_listed_after_top_level_ones() {
+        let dir = temp_dir("spectrum-folders");
+        let manager = StateManager::open(dir.clone()).unwrap();
+        let spectrum = r##"{"spectra": [{"nodes": [{"color": "#0000FF", "position": 0.0}]}]}"##;
+        let spectrums = manager.spectrums_dir();
+        std::fs::create_dir_all(spectrums.join("gaming")).unwrap();
+        std::fs::create_dir_all(spectrums.join(".backup")).unwrap();
+        std::fs::write(spectrums.join("tritan.json"), spectrum).unwrap();
+        std::fs::write(spectrums.join("gaming").join("deutan-strong.json"), spectrum).unwrap();
+        std::fs::write(spectrums.join("gaming").join("broken.json"), "{").unwrap();
+        std::fs::write(spectrums.join(".backup").join("old.json"), spectrum).unwrap();
+
+        assert_eq!(manager.list_spectrum_files().unwrap(), ["tritan", "gaming/deutan-strong"]);
+        // Flat names resolve as before
+        assert_eq!(manager.get_spectrum_path("tritan").unwrap(), spectrums.join("tritan.json"));
+        assert!(manager.get_spectrum_path("gaming/deutan-strong").unwrap().exists());
+
+        assert!(manager.flush_and_join(Duration::from_secs(2)));
+        drop(manager);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn selections_follow_their_name_when_the_list_changes() {
+        let files = |names: &[&str]| names.iter().map(|name| name.to_string()).collect::<Vec<_>>();
+
+        let before = files(&["deutan", "protan", "tritan"]);
+        assert_eq!(resolve_asset(&before, Some("protan")), AssetSelection::Found(1));
+        assert_eq!(resolve_asset(&before, None), AssetSelection::None);
+
+        // A file sorting before it was added or removed
+        assert_eq!(resolve_asset(&files(&["achromat", "deutan", "protan", "tritan"]), Some("protan")), AssetSelection::Found(2));
+        assert_eq!(resolve_asset(&files(&["protan", "tritan"]), Some("protan")), AssetSelection::Found(0));
+        // Its neighbour was renamed
+        assert_eq!(resolve_asset(&files(&["deutan-strong", "protan", "tritan"]), Some("protan")), AssetSelection::Found(1));
+
+        // It was renamed or removed itself, and nothing else takes its place
+        let renamed = resolve_asset(&files(&["deutan", "protan-old", "tritan"]), Some("protan"));
+        assert_eq!(renamed, AssetSelection::Missing("protan".into()));
+        assert_eq!((renamed.index(), renamed.missing()), (None, Some("protan")));
+        assert_eq!(resolve_asset(&files(&["deutan", "tritan"]), Some("protan")).index(), None);
+        assert_eq!(resolve_asset(&[], Some("protan")).missing(), Some("protan"));
+    }
+
+    #[test]
+    fn target_windows_match_by_process_and_title() {
+        let target = TargetWindow {
+            process_name: "Game.exe".into(),
+            title_pattern: "world".into(),
+        };
+        assert!(target.matches("game.exe", "Hello World - DX12"));
+        assert!(!target.matches("game.exe", "Launcher"));
+        assert!(!target.matches("other.exe", "Hello World"));
+
+        let any_title = TargetWindow {
+            process_name: "game.exe".into(),
+            title_pattern: String::new(),
+        };
+        assert!(any_title.matches("GAME.EXE", ""));
+
+        let mut state = AppState::default();
+        state.target_window = Some(any_title.clone());
+        assert_eq!(state.correction_target(), None);
+        let mut after = state.clone();
+        after.target_window_only = true;
+        assert_eq!(after.correction_target(), Some(&any_title));
+        assert_eq!(StateChanges::between(&state, &after), StateChanges { live_rendering: true, ..Default::default() });
+
+        // Changing the target itself is only a live change too
+        let mut retargeted = after.clone();
+        retargeted.target_window = Some(TargetWindow { process_name: "other.exe".into(), title_pattern: String::new() });
+        assert_eq!(StateChanges::between(&after, &retargeted), StateChanges { live_rendering: true, ..Default::default() });
+    }
+
+    #[test]
+    fn favorite_spectrums_can_be_reordered() {
+        let mut state = AppState::default();
+        state.toggle_favorite_spectrum("deutan");
+        state.toggle_favorite_spectrum("gaming/protan");
+        state.toggle_favorite_spectrum("tritan");
+        state.move_favorite_spectrum(2, 0);
+        assert_eq!(state.favorite_spectrums, ["tritan", "deutan", "gaming/protan"]);
+
+        state.toggle_favorite_spectrum("deutan");
+        assert!(!state.is_favorite_spectrum("deutan"));
+        // Out of range moves are ignored
+        state.move_favorite_spectrum(0, 5);
+        assert_eq!(state.favorite_spectrums, ["tritan", "gaming/protan"]);
     }
 }
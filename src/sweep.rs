@@ -0,0 +1,363 @@
+use crate::preview::CpuCorrection;
+use crate::spectrum::SpectrumPair;
+use crate::state::StrengthCurve;
+use anyhow::{Context, Result};
+use image::imageops::FilterType;
+use image::{Rgba, RgbaImage};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Strength slider positions every spectrum is rendered at.
+pub const SWEEP_STRENGTHS: [f32; 4] = [0.25, 0.5, 0.75, 1.0];
+
+/// Width of each image on the contact sheet.
+const SHEET_CELL_WIDTH: u32 = 240;
+/// Space around and between the images on the contact sheet.
+const SHEET_PADDING: u32 = 8;
+/// Each font pixel is drawn as a square this many pixels wide.
+const LABEL_SCALE: u32 = 2;
+const SHEET_BACKGROUND: Rgba<u8> = Rgba([32, 32, 32, 255]);
+const LABEL_COLOR: Rgba<u8> = Rgba([235, 235, 235, 255]);
+
+const GLYPH_WIDTH: u32 = 5;
+const GLYPH_HEIGHT: u32 = 7;
+
+/// A 5x7 raster font for the contact sheet labels, one row per byte with the leftmost pixel in
+/// bit 4. Lowercase is drawn as uppercase and anything missing as '?'.
+const GLYPHS: [(char, [u8; 7]); 48] = [
+    ('A', [0b01110, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001]),
+    ('B', [0b11110, 0b10001, 0b10001, 0b11110, 0b10001, 0b10001, 0b11110]),
+    ('C', [0b01110, 0b10001, 0b10000, 0b10000, 0b10000, 0b10001, 0b01110]),
+    ('D', [0b11110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b11110]),
+    ('E', [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b11111]),
+    ('F', [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b10000]),
+    ('G', [0b01110, 0b10001, 0b10000, 0b10111, 0b10001, 0b10001, 0b01111]),
+    ('H', [0b10001, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001]),
+    ('I', [0b01110, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110]),
+    ('J', [0b00111, 0b00010, 0b00010, 0b00010, 0b00010, 0b10010, 0b01100]),
+    ('K', [0b10001, 0b10010, 0b10100, 0b11000, 0b10100, 0b10010, 0b10001]),
+    ('L', [0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b11111]),
+    ('M', [0b10001, 0b11011, 0b10101, 0b10101, 0b10001, 0b10001, 0b10001]),
+    ('N', [0b10001, 0b10001, 0b11001, 0b10101, 0b10011, 0b10001, 0b10001]),
+    ('O', [0b01110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110]),
+    ('P', [0b11110, 0b10001, 0b10001, 0b11110, 0b10000, 0b10000, 0b10000]),
+    ('Q', [0b01110, 0b10001, 0b10001, 0b10001, 0b10101, 0b10010, 0b01101]),
+    ('R', [0b11110, 0b10001, 0b10001, 0b11110, 0b10100, 0b10010, 0b10001]),
+    ('S', [0b01111, 0b10000, 0b10000, 0b01110, 0b00001, 0b00001, 0b11110]),
+    ('T', [0b11111, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100]),
+    ('U', [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110]),
+    ('V', [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01010, 0b00100]),
+    ('W', [0b10001, 0b10001, 0b10001, 0b10101, 0b10101, 0b10101, 0b01010]),
+    ('X', [0b10001, 0b10001, 0b01010, 0b00100, 0b01010, 0b10001, 0b10001]),
+    ('Y', [0b10001, 0b10001, 0b10001, 0b01010, 0b00100, 0b00100, 0b00100]),
+    ('Z', [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b11111]),
+    ('0', [0b01110, 0b10001, 0b10011, 0b10101, 0b11001, 0b10001, 0b01110]),
+    ('1', [0b00100, 0b01100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110]),
+    ('2', [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b01000, 0b11111]),
+    ('3', [0b11111, 0b00010, 0b00100, 0b00010, 0b00001, 0b10001, 0b01110]),
+    ('4', [0b00010, 0b00110, 0b01010, 0b10010, 0b11111, 0b00010, 0b00010]),
+    ('5', [0b11111, 0b10000, 0b11110, 0b00001, 0b00001, 0b10001, 0b01110]),
+    ('6', [0b00110, 0b01000, 0b10000, 0b11110, 0b10001, 0b10001, 0b01110]),
+    ('7', [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b01000, 0b01000]),
+    ('8', [0b01110, 0b10001, 0b10001, 0b01110, 0b10001, 0b10001, 0b01110]),
+    ('9', [0b01110, 0b10001, 0b10001, 0b01111, 0b00001, 0b00010, 0b01100]),
+    (' ', [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000]),
+    ('-', [0b00000, 0b00000, 0b00000, 0b11111, 0b00000, 0b00000, 0b00000]),
+    ('_', [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b11111]),
+    ('.', [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b01100]),
+    ('/', [0b00000, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b00000]),
+    ('%', [0b11000, 0b11001, 0b00010, 0b00100, 0b01000, 0b10011, 0b00011]),
+    ('(', [0b00010, 0b00100, 0b01000, 0b01000, 0b01000, 0b00100, 0b00010]),
+    (')', [0b01000, 0b00100, 0b00010, 0b00010, 0b00010, 0b00100, 0b01000]),
+    (':', [0b00000, 0b01100, 0b01100, 0b00000, 0b01100, 0b01100, 0b00000]),
+    ('+', [0b00000, 0b00100, 0b00100, 0b11111, 0b00100, 0b00100, 0b00000]),
+    (',', [0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b00100, 0b01000]),
+    ('?', [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b00000, 0b00100]),
+];
+
+/// Renders an image through every spectrum at each of `SWEEP_STRENGTHS`, for comparison images
+/// in documentation and bug reports.
+///
+/// Uses the screenshot preview's `CpuCorrection`, so nothing needs the GPU or the overlay. The
+/// hue gate and noise pattern are left out: each image shows the spectrum alone.
+pub struct SweepJob {
+    pub input: RgbaImage,
+    /// Name and file of each spectrum to render, names as in the spectrum list.
+    pub spectra: Vec<(String, PathBuf)>,
+    /// Turns each strength into the blend weight, as for the overlay.
+    pub strength_curve: StrengthCurve,
+    pub output_dir: PathBuf,
+    /// Also write `contact_sheet.png`, every render side by side with labels.
+    pub contact_sheet: bool,
+}
+
+/// What a sweep wrote.
+#[derive(Debug, Default)]
+pub struct SweepReport {
+    /// Images written, the original and contact sheet included.
+    pub written: usize,
+    /// Spectra that couldn't be rendered, with why.
+    pub failed: Vec<(String, String)>,
+    pub cancelled: bool,
+}
+
+impl SweepJob {
+    /// Steps `run` reports progress in: the original, each render and the contact sheet.
+    pub fn step_count(&self) -> usize {
+        1 + self.spectra.len() * SWEEP_STRENGTHS.len() + usize::from(self.contact_sheet)
+    }
+
+    /// Writes the images into `output_dir`, calling `progress` with the steps done so far after
+    /// each. Stops between images once `cancel` is set, keeping what was written.
+    pub fn run(&self, cancel: &AtomicBool, mut progress: impl FnMut(usize)) -> Result<SweepReport> {
+        std::fs::create_dir_all(&self.output_dir).with_context(|| format!("Failed to create {}", self.output_dir.display()))?;
+        let mut report = SweepReport::default();
+        let mut done = 0;
+
+        self.save(&self.input, "original")?;
+        report.written += 1;
+        done += 1;
+        progress(done);
+
+        let names: Vec<&str> = self.spectra.iter().map(|(name, _)| name.as_str()).collect();
+        let mut rows = Vec::new();
+        for ((name, path), stem) in self.spectra.iter().zip(unique_file_stems(&names)) {
+            let corrections = SpectrumPair::load_from_file(path).map_err(anyhow::Error::from).and_then(|pair| {
+                SWEEP_STRENGTHS.iter().map(|&strength| CpuCorrection::new(&pair, self.strength_curve.apply(strength), None, None)).collect::<Result<Vec<_>>>()
+            });
+            let corrections = match corrections {
+                Ok(corrections) => corrections,
+                Err(e) => {
+                    report.failed.push((name.clone(), format!("{:#}", e)));
+                    done += SWEEP_STRENGTHS.len();
+                    progress(done);
+                    continue;
+                }
+            };
+
+            let mut renders = Vec::new();
+            for (correction, strength) in corrections.iter().zip(SWEEP_STRENGTHS) {
+                if cancel.load(Ordering::Relaxed) {
+                    report.cancelled = true;
+                    return Ok(report);
+                }
+                let mut image = self.input.clone();
+                correction.apply(&mut image, image.width());
+                self.save(&image, &format!("{}_{:03}", stem, (strength * 100.0).round() as u32))?;
+                report.written += 1;
+                done += 1;
+                progress(done);
+                if self.contact_sheet {
+                    renders.push(thumbnail(&image));
+                }
+            }
+            rows.push((name.as_str(), renders));
+        }
+
+        if self.contact_sheet && !rows.is_empty() {
+            if cancel.load(Ordering::Relaxed) {
+                report.cancelled = true;
+                return Ok(report);
+            }
+            self.save(&contact_sheet(&thumbnail(&self.input), &rows), "contact_sheet")?;
+            report.written += 1;
+            progress(done + 1);
+        }
+        Ok(report)
+    }
+
+    fn save(&self, image: &RgbaImage, stem: &str) -> Result<()> {
+        let path = self.output_dir.join(format!("{}.png", stem));
+        image.save(&path).with_context(|| format!("Failed to write {}", path.display()))
+    }
+}
+
+/// `name` as a file name that is safe everywhere the images get uploaded to: ASCII letters,
+/// digits, '-' and '_', with folders joined by '_'.
+pub fn file_stem(name: &str) -> String {
+    let stem: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c.to_ascii_lowercase() } else { '_' })
+        .collect();
+    if stem.is_empty() {
+        "spectrum".to_string()
+    } else {
+        stem
+    }
+}
+
+/// `file_stem` of each name, with "-2", "-3", ... appended where two would be the same.
+fn unique_file_stems(names: &[&str]) -> Vec<String> {
+    let mut taken = HashSet::new();
+    names
+        .iter()
+        .map(|name| {
+            let stem = file_stem(name);
+            let unique = (1..).map(|n| if n == 1 { stem.clone() } else { format!("{}-{}", stem, n) }).find(|s| !taken.contains(s)).unwrap();
+            taken.insert(unique.clone());
+            unique
+        })
+        .collect()
+}
+
+fn thumbnail(image: &RgbaImage) -> RgbaImage {
+    let (width, height) = image.dimensions();
+    let scaled_height = (height as u64 * SHEET_CELL_WIDTH as u64 / width.max(1) as u64).max(1) as u32;
+    image::imageops::resize(image, SHEET_CELL_WIDTH, scaled_height, FilterType::Triangle)
+}
+
+/// A header row naming the columns, then one row per spectrum: its name above the original and
+/// each strength.
+fn contact_sheet(original: &RgbaImage, rows: &[(&str, Vec<RgbaImage>)]) -> RgbaImage {
+    let columns = 1 + SWEEP_STRENGTHS.len() as u32;
+    let cell_height = original.height();
+    let label_height = GLYPH_HEIGHT * LABEL_SCALE + SHEET_PADDING;
+    let width = SHEET_PADDING + columns * (SHEET_CELL_WIDTH + SHEET_PADDING);
+    let height = SHEET_PADDING + label_height + rows.len() as u32 * (label_height + cell_height + SHEET_PADDING);
+    let mut sheet = RgbaImage::from_pixel(width, height, SHEET_BACKGROUND);
+
+    let column_x = |column: u32| SHEET_PADDING + column * (SHEET_CELL_WIDTH + SHEET_PADDING);
+    let headers = std::iter::once("Original".to_string()).chain(SWEEP_STRENGTHS.iter().map(|s| format!("{}%", (s * 100.0).round())));
+    for (column, header) in headers.enumerate() {
+        draw_label(&mut sheet, &header, column_x(column as u32), SHEET_PADDING, SHEET_CELL_WIDTH);
+    }
+
+    let mut y = SHEET_PADDING + label_height;
+    for (name, renders) in rows {
+        draw_label(&mut sheet, name, SHEET_PADDING, y, width - 2 * SHEET_PADDING);
+        y += label_height;
+        for (column, image) in std::iter::once(original).chain(renders).enumerate() {
+            image::imageops::overlay(&mut sheet, image, column_x(column as u32) as i64, y as i64);
+        }
+        y += cell_height + SHEET_PADDING;
+    }
+    sheet
+}
+
+/// Draws `text` with its top left at (`x`, `y`), dropping the characters that don't fit in
+/// `max_width`.
+fn draw_label(image: &mut RgbaImage, text: &str, x: u32, y: u32, max_width: u32) {
+    let advance = (GLYPH_WIDTH + 1) * LABEL_SCALE;
+    let fits = (max_width / advance) as usize;
+    for (i, c) in text.chars().take(fits).enumerate() {
+        let rows = glyph(c);
+        let left = x + i as u32 * advance;
+        for (row, bits) in rows.iter().enumerate() {
+            for column in 0..GLYPH_WIDTH {
+                if bits & (1 << (GLYPH_WIDTH - 1 - column)) == 0 {
+                    continue;
+                }
+                for dy in 0..LABEL_SCALE {
+                    for dx in 0..LABEL_SCALE {
+                        let (px, py) = (left + column * LABEL_SCALE + dx, y + row as u32 * LABEL_SCALE + dy);
+                        if px < image.width() && py < image.height() {
+                            image.put_pixel(px, py, LABEL_COLOR);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn glyph(c: char) -> [u8; 7] {
+    let c = c.to_ascii_uppercase();
+    let find = |c: char| GLYPHS.iter().find(|(glyph, _)| *glyph == c).map(|(_, rows)| *rows);
+    find(c).or_else(|| find('?')).unwrap_or_default()
+}
+
+/// Where the sweeps of the app data folder `app_data_dir` go, one timestamped folder each.
+pub fn output_dir(app_data_dir: &Path) -> PathBuf {
+    app_data_dir.join("sweeps").join(chrono::Local::now().format("%Y%m%d-%H%M%S").to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+
+    fn job(dir: &Path, contact_sheet: bool) -> SweepJob {
+        let spectrum = r##"{"spectra": [{"nodes": [{"color": "#0000FF", "position": 0.0}]}]}"##;
+        std::fs::write(dir.join("blue.json"), spectrum).unwrap();
+        std::fs::write(dir.join("broken.json"), "{").unwrap();
+        SweepJob {
+            input: RgbaImage::from_pixel(64, 32, Rgba([200, 40, 40, 255])),
+            spectra: vec![
+                ("gaming/Deutan strong".to_string(), dir.join("blue.json")),
+                ("broken".to_string(), dir.join("broken.json")),
+            ],
+            strength_curve: StrengthCurve::Linear,
+            output_dir: dir.join("out"),
+            contact_sheet,
+        }
+    }
+
+    #[test]
+    fn file_names_are_safe_and_unique() {
+        assert_eq!(file_stem("gaming/Deutan strong"), "gaming_deutan_strong");
+        assert_eq!(file_stem("..\\CON"), "___con");
+        assert_eq!(file_stem("protan-v2_final"), "protan-v2_final");
+        assert_eq!(file_stem("élan"), "_lan");
+        assert_eq!(file_stem(""), "spectrum");
+        assert_eq!(unique_file_stems(&["a b", "a_b", "A B", "c"]), ["a_b", "a_b-2", "a_b-3", "c"]);
+    }
+
+    #[test]
+    fn every_spectrum_is_rendered_at_every_strength() {
+        let dir = temp_dir("render");
+        let job = job(&dir, true);
+        let mut steps = Vec::new();
+        let report = job.run(&AtomicBool::new(false), |done| steps.push(done)).unwrap();
+
+        assert_eq!(report.written, 1 + SWEEP_STRENGTHS.len() + 1);
+        assert!(!report.cancelled);
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].0, "broken");
+        assert_eq!(steps.last(), Some(&job.step_count()));
+
+        let out = &job.output_dir;
+        let original = image::open(out.join("original.png")).unwrap().to_rgba8();
+        assert_eq!(original, job.input);
+        let quarter = image::open(out.join("gaming_deutan_strong_025.png")).unwrap().to_rgba8();
+        let full = image::open(out.join("gaming_deutan_strong_100.png")).unwrap().to_rgba8();
+        assert_ne!(quarter, original);
+        // Full strength moves red all the way to the spectrum's blue
+        assert!(full.get_pixel(0, 0)[2] > quarter.get_pixel(0, 0)[2]);
+        assert!(out.join("gaming_deutan_strong_050.png").exists());
+        assert!(out.join("gaming_deutan_strong_075.png").exists());
+        assert!(out.join("contact_sheet.png").exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn cancelling_keeps_what_was_written() {
+        let dir = temp_dir("cancel");
+        let job = job(&dir, true);
+        let report = job.run(&AtomicBool::new(true), |_| {}).unwrap();
+
+        assert!(report.cancelled);
+        assert_eq!(report.written, 1);
+        assert!(job.output_dir.join("original.png").exists());
+        assert!(!job.output_dir.join("contact_sheet.png").exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn labels_are_drawn_in_the_raster_font() {
+        let mut image = RgbaImage::from_pixel(40, 20, SHEET_BACKGROUND);
+        draw_label(&mut image, "t", 0, 0, 40);
+        // The top bar of the T, then its stem
+        for x in 0..GLYPH_WIDTH * LABEL_SCALE {
+            assert_eq!(*image.get_pixel(x, 0), LABEL_COLOR);
+        }
+        assert_eq!(*image.get_pixel(2 * LABEL_SCALE, 6 * LABEL_SCALE), LABEL_COLOR);
+        assert_eq!(*image.get_pixel(0, 6 * LABEL_SCALE), SHEET_BACKGROUND);
+
+        // Unknown characters are drawn as '?', and what doesn't fit is left out
+        assert_eq!(glyph('☃'), glyph('?'));
+        let mut narrow = RgbaImage::from_pixel(40, 20, SHEET_BACKGROUND);
+        draw_label(&mut narrow, "TT", 0, 0, 15);
+        assert_eq!(*narrow.get_pixel(12, 0), SHEET_BACKGROUND);
+    }
+}
@@ -0,0 +1,198 @@
+use crate::fullscreen::process_name;
+use crate::shell_exclusion::{is_cloaked, monitor_uv};
+use chromabridge::{log_debug, log_info, TargetWindow};
+use std::time::{Duration, Instant};
+use windows::{
+    core::BOOL,
+    Win32::Foundation::{HWND, LPARAM, RECT},
+    Win32::Graphics::Dwm::{DwmGetWindowAttribute, DWMWA_EXTENDED_FRAME_BOUNDS},
+    Win32::System::Threading::GetCurrentProcessId,
+    Win32::UI::WindowsAndMessaging::{
+        EnumWindows, GetWindow, GetWindowLongW, GetWindowRect, GetWindowTextW, GetWindowThreadProcessId, IsIconic,
+        IsWindow, IsWindowVisible, GWL_EXSTYLE, GW_OWNER, WS_EX_TOOLWINDOW,
+    },
+};
+
+/// How often a target window that isn't open is searched for.
+const SCAN_INTERVAL: Duration = Duration::from_millis(500);
+
+/// A window offered by the target window picker.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WindowChoice {
+    pub title: String,
+    pub process_name: String,
+}
+
+impl WindowChoice {
+    pub fn label(&self) -> String {
+        format!("{} — {}", self.title, self.process_name)
+    }
+
+    /// Finds this window again by its process and full title.
+    pub fn target(&self) -> TargetWindow {
+        TargetWindow {
+            process_name: self.process_name.clone(),
+            title_pattern: self.title.clone(),
+        }
+    }
+}
+
+/// The visible, titled top-level windows of other processes, front to back.
+pub fn list_windows() -> Vec<WindowChoice> {
+    unsafe { candidates() }.into_iter().map(|(_, choice)| choice).collect()
+}
+
+/// Where the overlay corrects.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CorrectionArea {
+    /// The whole monitor, when no target window is set.
+    FullScreen,
+    /// Only the target window, in monitor UV as (left, top, right, bottom).
+    Window([f32; 4]),
+    /// The target window is closed, minimized or on another monitor, so nothing is corrected.
+    Suspended,
+}
+
+/// Follows the target window on the overlay's monitor.
+///
+/// The window is looked up by process and title, then kept by handle for as long as it lives, so
+/// a title that changes while it runs doesn't lose it. Its frame is measured every frame, which
+/// keeps the corrected area at most one frame behind a window being moved or resized.
+pub struct TargetWindowTracker {
+    overlay: HWND,
+    target: Option<TargetWindow>,
+    window: Option<HWND>,
+    last_scan: Option<Instant>,
+    area: CorrectionArea,
+}
+
+impl TargetWindowTracker {
+    pub fn new(overlay: HWND) -> Self {
+        Self {
+            overlay,
+            target: None,
+            window: None,
+            last_scan: None,
+            area: CorrectionArea::FullScreen,
+        }
+    }
+
+    /// Re-measures the window of `target`, `None` for full screen correction. True when the
+    /// corrected area changed.
+    pub fn update(&mut self, target: Option<&TargetWindow>) -> bool {
+        if self.target.as_ref() != target {
+            self.target = target.cloned();
+            self.window = None;
+            self.last_scan = None;
+        }
+
+        let area = match self.target.clone() {
+            Some(target) => unsafe { self.locate(&target) },
+            None => CorrectionArea::FullScreen,
+        };
+        if area == self.area {
+            return false;
+        }
+        log_debug!("Correction area: {:?}", area);
+        self.area = area;
+        true
+    }
+
+    pub fn area(&self) -> CorrectionArea {
+        self.area
+    }
+
+    unsafe fn locate(&mut self, target: &TargetWindow) -> CorrectionArea {
+        if self.window.is_some_and(|hwnd| !IsWindow(Some(hwnd)).as_bool()) {
+            log_info!("Target window {} closed, correction suspended", target.process_name);
+            // Search right away, the app may have replaced it with a new window
+            self.window = None;
+            self.last_scan = None;
+        }
+
+        if self.window.is_none() && self.last_scan.is_none_or(|scan| scan.elapsed() >= SCAN_INTERVAL) {
+            self.last_scan = Some(Instant::now());
+            self.window = find_window(target);
+            if self.window.is_some() {
+                log_info!("Found target window of {}", target.process_name);
+            }
+        }
+
+        let Some(hwnd) = self.window else {
+            return CorrectionArea::Suspended;
+        };
+        if IsIconic(hwnd).as_bool() || !IsWindowVisible(hwnd).as_bool() || is_cloaked(hwnd) {
+            return CorrectionArea::Suspended;
+        }
+
+        // The overlay window always covers its monitor exactly
+        let mut monitor = RECT::default();
+        if GetWindowRect(self.overlay, &mut monitor).is_err() {
+            return CorrectionArea::Suspended;
+        }
+        monitor_uv(frame_bounds(hwnd), monitor).map_or(CorrectionArea::Suspended, CorrectionArea::Window)
+    }
+}
+
+/// The visible frame of `hwnd`. `GetWindowRect` includes the invisible resize borders around
+/// windows with the standard frame, so DWM is asked first.
+unsafe fn frame_bounds(hwnd: HWND) -> RECT {
+    let mut rect = RECT::default();
+    let measured = DwmGetWindowAttribute(hwnd, DWMWA_EXTENDED_FRAME_BOUNDS, &mut rect as *mut _ as *mut _, std::mem::size_of::<RECT>() as u32);
+    if measured.is_err() {
+        let _ = GetWindowRect(hwnd, &mut rect);
+    }
+    rect
+}
+
+unsafe fn find_window(target: &TargetWindow) -> Option<HWND> {
+    candidates()
+        .into_iter()
+        .find(|(_, choice)| target.matches(&choice.process_name, &choice.title))
+        .map(|(hwnd, _)| hwnd)
+}
+
+unsafe fn candidates() -> Vec<(HWND, WindowChoice)> {
+    let mut windows: Vec<HWND> = Vec::new();
+    // Fails with no error code when the callback stops early; this one never does
+    let _ = EnumWindows(Some(collect_windows), LPARAM(&mut windows as *mut _ as isize));
+    windows.into_iter().filter_map(|hwnd| Some((hwnd, choice(hwnd)?))).collect()
+}
+
+/// What the picker shows for `hwnd`, `None` for windows it leaves out: hidden, owned, tool and
+/// untitled windows, and those of this app.
+unsafe fn choice(hwnd: HWND) -> Option<WindowChoice> {
+    if !IsWindowVisible(hwnd).as_bool() || is_cloaked(hwnd) {
+        return None;
+    }
+    if GetWindow(hwnd, GW_OWNER).is_ok_and(|owner| !owner.is_invalid()) {
+        return None;
+    }
+    if GetWindowLongW(hwnd, GWL_EXSTYLE) as u32 & WS_EX_TOOLWINDOW.0 != 0 {
+        return None;
+    }
+
+    let mut title = [0u16; 256];
+    let len = GetWindowTextW(hwnd, &mut title) as usize;
+    let title = String::from_utf16_lossy(&title[..len]);
+    if title.trim().is_empty() {
+        return None;
+    }
+
+    let mut pid = 0u32;
+    GetWindowThreadProcessId(hwnd, Some(&mut pid));
+    if pid == 0 || pid == GetCurrentProcessId() {
+        return None;
+    }
+
+    Some(WindowChoice {
+        title,
+        process_name: process_name(pid)?,
+    })
+}
+
+unsafe extern "system" fn collect_windows(hwnd: HWND, lparam: LPARAM) -> BOOL {
+    let windows = &mut *(lparam.0 as *mut Vec<HWND>);
+    windows.push(hwnd);
+    true.into()
+}
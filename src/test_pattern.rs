@@ -0,0 +1,194 @@
+use crate::hue_mapper::HueMapper;
+use image::{Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+
+/// Number of patches in the grey ramp, matching the common 18-step photographic grey scale.
+pub const GREY_RAMP_STEPS: u32 = 18;
+/// Hue bands in the saturation ramp pattern, 30 degrees apart.
+pub const SATURATION_RAMP_BANDS: u32 = 12;
+/// Spacing of the coverage pattern's grid lines, and the size of its corner markers, in pixels.
+pub const COVERAGE_GRID_PX: u32 = 100;
+
+/// Procedural images fed to the overlay instead of the desktop, for checking a spectrum by eye.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TestPattern {
+    /// Red increasing to the right, green downwards; the original fallback image.
+    #[default]
+    Gradient,
+    /// Hue around the center, saturation increasing outwards.
+    HueWheel,
+    /// One band per hue, saturation increasing to the right.
+    SaturationRamps,
+    /// Black to white in equal steps.
+    GreyRamp,
+    /// Edge border, pixel grid and a differently colored marker in each corner, for checking that
+    /// the overlay covers the whole monitor in the right orientation.
+    Coverage,
+}
+
+impl TestPattern {
+    pub const ALL: [TestPattern; 5] = [
+        TestPattern::Gradient,
+        TestPattern::HueWheel,
+        TestPattern::SaturationRamps,
+        TestPattern::GreyRamp,
+        TestPattern::Coverage,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            TestPattern::Gradient => "Gradient",
+            TestPattern::HueWheel => "Hue wheel",
+            TestPattern::SaturationRamps => "Saturation ramps",
+            TestPattern::GreyRamp => "18-step grey ramp",
+            TestPattern::Coverage => "Coverage check",
+        }
+    }
+
+    /// Renders the pattern as tightly packed BGRA rows, the layout of the capture texture.
+    pub fn generate_bgra(&self, width: u32, height: u32) -> Vec<u8> {
+        let mut pixels = Vec::with_capacity((width * height * 4) as usize);
+        for y in 0..height {
+            for x in 0..width {
+                let (r, g, b) = self.pixel(x, y, width, height);
+                pixels.extend_from_slice(&[b, g, r, 255]);
+            }
+        }
+        pixels
+    }
+
+    /// Renders the pattern as an image, for previews on the CPU.
+    pub fn generate_rgba(&self, width: u32, height: u32) -> RgbaImage {
+        RgbaImage::from_fn(width, height, |x, y| {
+            let (r, g, b) = self.pixel(x, y, width, height);
+            Rgba([r, g, b, 255])
+        })
+    }
+
+    fn pixel(&self, x: u32, y: u32, width: u32, height: u32) -> (u8, u8, u8) {
+        // Sample at pixel centers so patterns are symmetric
+        let u = (x as f32 + 0.5) / width as f32;
+        let v = (y as f32 + 0.5) / height as f32;
+
+        match self {
+            TestPattern::Gradient => ((u * 255.0) as u8, (v * 255.0) as u8, 128),
+            TestPattern::HueWheel => {
+                let dx = x as f32 + 0.5 - width as f32 / 2.0;
+                let dy = y as f32 + 0.5 - height as f32 / 2.0;
+                let radius = width.min(height) as f32 / 2.0;
+                let distance = (dx * dx + dy * dy).sqrt();
+                if distance > radius {
+                    return (0, 0, 0);
+                }
+                let hue = (dy.atan2(dx).to_degrees() + 360.0) % 360.0;
+                HueMapper::hsv_to_rgb(hue, distance / radius, 1.0)
+            }
+            TestPattern::SaturationRamps => {
+                let band = ((v * SATURATION_RAMP_BANDS as f32) as u32).min(SATURATION_RAMP_BANDS - 1);
+                let hue = band as f32 * 360.0 / SATURATION_RAMP_BANDS as f32;
+                HueMapper::hsv_to_rgb(hue, u, 1.0)
+            }
+            TestPattern::GreyRamp => {
+                let step = ((u * GREY_RAMP_STEPS as f32) as u32).min(GREY_RAMP_STEPS - 1);
+                let grey = (step as f32 / (GREY_RAMP_STEPS - 1) as f32 * 255.0).round() as u8;
+                (grey, grey, grey)
+            }
+            TestPattern::Coverage => {
+                let (right, bottom) = (x + 1 == width, y + 1 == height);
+                if x == 0 || y == 0 || right || bottom {
+                    return (255, 255, 255);
+                }
+                let (left_half, top_half) = (x < COVERAGE_GRID_PX, y < COVERAGE_GRID_PX);
+                let (right_half, bottom_half) = (x >= width.saturating_sub(COVERAGE_GRID_PX), y >= height.saturating_sub(COVERAGE_GRID_PX));
+                match (left_half, right_half, top_half, bottom_half) {
+                    (true, _, true, _) => return (255, 0, 0),
+                    (_, true, true, _) => return (0, 255, 0),
+                    (true, _, _, true) => return (0, 0, 255),
+                    (_, true, _, true) => return (255, 255, 0),
+                    _ => {}
+                }
+                if x % COVERAGE_GRID_PX == 0 || y % COVERAGE_GRID_PX == 0 {
+                    (96, 96, 96)
+                } else {
+                    (0, 0, 0)
+                }
+            }
+        }
+    }
+}
+
+/// A single opaque color as BGRA rows.
+pub fn solid_bgra(color: [u8; 3], width: u32, height: u32) -> Vec<u8> {
+    [color[2], color[1], color[0], 255].repeat((width * height) as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rgb_at(pixels: &[u8], width: u32, x: u32, y: u32) -> (u8, u8, u8) {
+        let idx = ((y * width + x) * 4) as usize;
+        (pixels[idx + 2], pixels[idx + 1], pixels[idx])
+    }
+
+    #[test]
+    fn grey_ramp_has_equal_steps_from_black_to_white() {
+        let width = GREY_RAMP_STEPS * 10;
+        let pixels = TestPattern::GreyRamp.generate_bgra(width, 4);
+
+        let greys: Vec<u8> = (0..GREY_RAMP_STEPS).map(|step| rgb_at(&pixels, width, step * 10 + 5, 2).0).collect();
+        assert_eq!(greys.first(), Some(&0));
+        assert_eq!(greys.last(), Some(&255));
+        assert!(greys.windows(2).all(|w| w[1] > w[0]), "{:?}", greys);
+    }
+
+    #[test]
+    fn saturation_ramps_start_white_and_end_saturated() {
+        let (width, height) = (100, SATURATION_RAMP_BANDS * 10);
+        let pixels = TestPattern::SaturationRamps.generate_bgra(width, height);
+
+        let (r, g, b) = rgb_at(&pixels, width, 0, 5);
+        assert!(r > 250 && g > 250 && b > 250, "{:?}", (r, g, b));
+
+        // First band is red
+        let (r, g, b) = rgb_at(&pixels, width, width - 1, 5);
+        assert!(r == 255 && g < 10 && b < 10, "{:?}", (r, g, b));
+    }
+
+    #[test]
+    fn hue_wheel_center_is_white_and_corners_black() {
+        let pixels = TestPattern::HueWheel.generate_bgra(64, 64);
+        let (r, g, b) = rgb_at(&pixels, 64, 32, 32);
+        assert!(r > 240 && g > 240 && b > 240, "{:?}", (r, g, b));
+        assert_eq!(rgb_at(&pixels, 64, 0, 0), (0, 0, 0));
+    }
+
+    #[test]
+    fn coverage_marks_edges_and_corners() {
+        let (width, height) = (320, 240);
+        let pixels = TestPattern::Coverage.generate_bgra(width, height);
+
+        for (x, y) in [(0, 120), (319, 120), (160, 0), (160, 239)] {
+            assert_eq!(rgb_at(&pixels, width, x, y), (255, 255, 255), "edge at {:?}", (x, y));
+        }
+        assert_eq!(rgb_at(&pixels, width, 5, 5), (255, 0, 0));
+        assert_eq!(rgb_at(&pixels, width, 314, 5), (0, 255, 0));
+        assert_eq!(rgb_at(&pixels, width, 5, 234), (0, 0, 255));
+        assert_eq!(rgb_at(&pixels, width, 314, 234), (255, 255, 0));
+        assert_eq!(rgb_at(&pixels, width, 150, 150), (0, 0, 0));
+    }
+
+    #[test]
+    fn generated_size_matches_texture() {
+        for pattern in TestPattern::ALL {
+            assert_eq!(pattern.generate_bgra(7, 5).len(), 7 * 5 * 4);
+        }
+        assert_eq!(solid_bgra([1, 2, 3], 2, 1), vec![3, 2, 1, 255, 3, 2, 1, 255]);
+
+        let bgra = TestPattern::HueWheel.generate_bgra(7, 5);
+        let rgba = TestPattern::HueWheel.generate_rgba(7, 5);
+        let [r, g, b, _] = rgba.get_pixel(1, 1).0;
+        assert_eq!((r, g, b), rgb_at(&bgra, 7, 1, 1));
+    }
+}
@@ -0,0 +1,16 @@
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::UNIX_EPOCH;
+
+/// Creates an empty directory for a test under the system temp dir. The process id, start time
+/// and a counter keep it apart from other tests, also ones running in parallel or with the same
+/// `name`.
+pub(crate) fn temp_dir(name: &str) -> PathBuf {
+    static COUNTER: AtomicU32 = AtomicU32::new(0);
+
+    let nanos = std::time::SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos();
+    let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+    let dir = std::env::temp_dir().join(format!("chromabridge-{}-{}-{}-{}", name, std::process::id(), nanos, count));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
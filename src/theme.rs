@@ -0,0 +1,245 @@
+use chromabridge::preview::{self, CpuCorrection};
+use chromabridge::{GuiTheme, HueMapper, SpectrumSelection, StateManager};
+use egui::{Color32, Stroke};
+
+/// Where `apply` leaves the palettes for `palette`.
+const PALETTES_ID: &str = "chromabridge_palettes";
+
+/// Tint of the debug overlay's border, fixed so the hint names the color it really shows.
+/// Matches the magenta in shaders.hlsl.
+pub const DEBUG_OVERLAY: Color32 = Color32::from_rgb(255, 0, 255);
+
+/// Every color the settings window uses.
+///
+/// Status colors are taken from the Okabe-Ito set, which stays apart for the common forms of
+/// color blindness. Nothing is told apart by hue alone: selected entries are also bold and
+/// underlined, and hovered or pressed controls get a thicker outline.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Palette {
+    pub dark: bool,
+    /// Panels and windows.
+    pub background: Color32,
+    /// Buttons and other controls.
+    pub surface: Color32,
+    /// Text fields and scroll tracks.
+    pub extreme: Color32,
+    pub text: Color32,
+    /// The title and pressed controls.
+    pub strong_text: Color32,
+    /// Hints, the version and other secondary text.
+    pub weak_text: Color32,
+    /// Control outlines and separators.
+    pub border: Color32,
+    /// Hovered outlines and links.
+    pub accent: Color32,
+    /// Behind selected entries and text.
+    pub selection: Color32,
+    pub warning: Color32,
+    pub error: Color32,
+    pub success: Color32,
+    pub warning_fill: Color32,
+    pub error_fill: Color32,
+    /// Width of control outlines.
+    pub stroke_width: f32,
+}
+
+pub const DARK: Palette = Palette {
+    dark: true,
+    background: Color32::from_rgb(27, 27, 27),
+    surface: Color32::from_rgb(50, 50, 50),
+    extreme: Color32::from_rgb(12, 12, 12),
+    text: Color32::from_rgb(215, 215, 215),
+    strong_text: Color32::from_rgb(240, 240, 240),
+    weak_text: Color32::from_rgb(150, 150, 150),
+    border: Color32::from_rgb(95, 95, 95),
+    accent: Color32::from_rgb(86, 180, 233),
+    selection: Color32::from_rgb(0, 84, 130),
+    warning: Color32::from_rgb(240, 200, 60),
+    error: Color32::from_rgb(240, 120, 60),
+    success: Color32::from_rgb(0, 180, 130),
+    warning_fill: Color32::from_rgb(70, 60, 20),
+    error_fill: Color32::from_rgb(80, 35, 20),
+    stroke_width: 1.0,
+};
+
+pub const LIGHT: Palette = Palette {
+    dark: false,
+    background: Color32::from_rgb(246, 246, 246),
+    surface: Color32::from_rgb(224, 224, 224),
+    extreme: Color32::WHITE,
+    text: Color32::from_rgb(25, 25, 25),
+    strong_text: Color32::BLACK,
+    weak_text: Color32::from_rgb(90, 90, 90),
+    border: Color32::from_rgb(120, 120, 120),
+    accent: Color32::from_rgb(0, 114, 178),
+    selection: Color32::from_rgb(170, 215, 240),
+    warning: Color32::from_rgb(150, 100, 0),
+    error: Color32::from_rgb(190, 70, 0),
+    success: Color32::from_rgb(0, 125, 90),
+    warning_fill: Color32::from_rgb(255, 238, 185),
+    error_fill: Color32::from_rgb(255, 218, 200),
+    stroke_width: 1.0,
+};
+
+pub const HIGH_CONTRAST: Palette = Palette {
+    dark: true,
+    background: Color32::BLACK,
+    surface: Color32::from_rgb(20, 20, 20),
+    extreme: Color32::BLACK,
+    text: Color32::WHITE,
+    strong_text: Color32::WHITE,
+    weak_text: Color32::from_rgb(200, 200, 200),
+    border: Color32::WHITE,
+    accent: Color32::from_rgb(0, 220, 255),
+    selection: Color32::from_rgb(0, 70, 110),
+    warning: Color32::from_rgb(255, 225, 0),
+    error: Color32::from_rgb(255, 110, 70),
+    success: Color32::from_rgb(0, 230, 150),
+    warning_fill: Color32::from_rgb(60, 50, 0),
+    error_fill: Color32::from_rgb(70, 20, 0),
+    stroke_width: 2.0,
+};
+
+impl Palette {
+    /// Turns the accent and selection to the hue that `correction` keeps furthest from the
+    /// other colors, keeping their saturation and brightness.
+    fn follow_spectrum(self, correction: &CpuCorrection) -> Self {
+        let (_, saturation, value) = hsv(self.accent);
+        let avoid = [self.background, self.text, self.warning, self.error, self.success].map(|c| [c.r(), c.g(), c.b()]);
+        let hue = preview::distinct_hue(correction, saturation, value, &avoid);
+        Self {
+            accent: with_hue(self.accent, hue),
+            selection: with_hue(self.selection, hue),
+            ..self
+        }
+    }
+
+    fn visuals(&self) -> egui::Visuals {
+        let mut visuals = if self.dark { egui::Visuals::dark() } else { egui::Visuals::light() };
+        visuals.panel_fill = self.background;
+        visuals.window_fill = self.background;
+        visuals.window_stroke = Stroke::new(self.stroke_width, self.border);
+        visuals.extreme_bg_color = self.extreme;
+        visuals.faint_bg_color = self.surface.linear_multiply(0.5);
+        visuals.code_bg_color = self.surface;
+        visuals.hyperlink_color = self.accent;
+        visuals.warn_fg_color = self.warning;
+        visuals.error_fg_color = self.error;
+        visuals.selection.bg_fill = self.selection;
+        visuals.selection.stroke = Stroke::new(self.stroke_width, self.strong_text);
+
+        let widgets = &mut visuals.widgets;
+        widgets.noninteractive.bg_fill = self.background;
+        widgets.noninteractive.weak_bg_fill = self.background;
+        widgets.noninteractive.bg_stroke = Stroke::new(1.0, self.border);
+        widgets.noninteractive.fg_stroke = Stroke::new(1.0, self.text);
+
+        widgets.inactive.bg_fill = self.surface;
+        widgets.inactive.weak_bg_fill = self.surface;
+        widgets.inactive.bg_stroke = Stroke::new(self.stroke_width, self.border);
+        widgets.inactive.fg_stroke = Stroke::new(1.0, self.text);
+
+        // Hover and press are marked by the outline's width as well as its color
+        widgets.hovered.bg_fill = self.surface;
+        widgets.hovered.weak_bg_fill = self.surface;
+        widgets.hovered.bg_stroke = Stroke::new(self.stroke_width + 1.0, self.accent);
+        widgets.hovered.fg_stroke = Stroke::new(1.5, self.strong_text);
+
+        widgets.active.bg_fill = self.selection;
+        widgets.active.weak_bg_fill = self.selection;
+        widgets.active.bg_stroke = Stroke::new(self.stroke_width + 1.0, self.strong_text);
+        widgets.active.fg_stroke = Stroke::new(2.0, self.strong_text);
+
+        widgets.open.bg_fill = self.surface;
+        widgets.open.weak_bg_fill = self.surface;
+        widgets.open.bg_stroke = Stroke::new(self.stroke_width, self.accent);
+        widgets.open.fg_stroke = Stroke::new(1.0, self.strong_text);
+        visuals
+    }
+}
+
+/// What the applied visuals were built from; they are rebuilt when it changes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ThemeSettings {
+    pub theme: GuiTheme,
+    /// The spectrum the accent follows, with its selection and blend weight.
+    pub accent: Option<(String, SpectrumSelection, f32)>,
+}
+
+impl ThemeSettings {
+    pub fn read(state: &StateManager) -> Self {
+        state.read(|s| Self {
+            theme: s.gui_theme,
+            accent: s
+                .spectrum_name
+                .clone()
+                .filter(|_| s.spectrum_accent)
+                .map(|name| (name, s.spectrum_selection, s.strength_curve.apply(s.strength))),
+        })
+    }
+}
+
+/// Sets the visuals for `theme`, with the accent following `correction` when given. Auto leaves
+/// the choice between the light and dark palette to Windows.
+pub fn apply(ctx: &egui::Context, theme: GuiTheme, correction: Option<&CpuCorrection>) {
+    let dark = if theme == GuiTheme::HighContrast { HIGH_CONTRAST } else { DARK };
+    let palettes = [dark, LIGHT].map(|palette| match correction {
+        Some(correction) => palette.follow_spectrum(correction),
+        None => palette,
+    });
+
+    ctx.set_visuals_of(egui::Theme::Dark, palettes[0].visuals());
+    ctx.set_visuals_of(egui::Theme::Light, palettes[1].visuals());
+    ctx.set_theme(match theme {
+        GuiTheme::Auto => egui::ThemePreference::System,
+        GuiTheme::Light => egui::ThemePreference::Light,
+        GuiTheme::Dark | GuiTheme::HighContrast => egui::ThemePreference::Dark,
+    });
+    ctx.data_mut(|data| data.insert_temp(egui::Id::new(PALETTES_ID), palettes));
+}
+
+/// The palette `ui` is drawn with.
+pub fn palette(ui: &egui::Ui) -> Palette {
+    let dark = ui.visuals().dark_mode;
+    let palettes = ui.ctx().data(|data| data.get_temp::<[Palette; 2]>(egui::Id::new(PALETTES_ID)));
+    match (palettes, dark) {
+        (Some([palette, _]), true) | (Some([_, palette]), false) => palette,
+        (None, true) => DARK,
+        (None, false) => LIGHT,
+    }
+}
+
+/// `text` in bold and underlined when `selected`, so the selection shows without its color.
+fn marked(text: impl Into<egui::WidgetText>, selected: bool) -> egui::WidgetText {
+    let text = text.into();
+    if selected {
+        text.strong().underline()
+    } else {
+        text
+    }
+}
+
+/// `Ui::selectable_label` with the selection marked beyond color.
+pub fn selectable_label(ui: &mut egui::Ui, selected: bool, text: impl Into<egui::WidgetText>) -> egui::Response {
+    ui.add(egui::SelectableLabel::new(selected, marked(text, selected)))
+}
+
+/// `Ui::selectable_value` with the selection marked beyond color.
+pub fn selectable_value<V: PartialEq>(ui: &mut egui::Ui, current: &mut V, value: V, text: impl Into<egui::WidgetText>) -> egui::Response {
+    let mut response = selectable_label(ui, *current == value, text);
+    if response.clicked() && *current != value {
+        *current = value;
+        response.mark_changed();
+    }
+    response
+}
+
+fn hsv(color: Color32) -> (f32, f32, f32) {
+    HueMapper::rgb_to_hsv(color.r(), color.g(), color.b())
+}
+
+fn with_hue(color: Color32, hue: f32) -> Color32 {
+    let (_, saturation, value) = hsv(color);
+    let (r, g, b) = HueMapper::hsv_to_rgb(hue, saturation, value);
+    Color32::from_rgb(r, g, b)
+}
@@ -0,0 +1,149 @@
+use chromabridge::{log_debug, TopmostMode};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use windows::{
+    core::BOOL,
+    Win32::Foundation::{HWND, LPARAM, RECT},
+    Win32::UI::WindowsAndMessaging::{
+        EnumWindows, GetWindowDisplayAffinity, GetWindowLongW, GetWindowRect, IsWindowVisible, SetWindowPos,
+        GWL_EXSTYLE, HWND_TOPMOST, SWP_NOACTIVATE, SWP_NOMOVE, SWP_NOSIZE, WDA_NONE, WS_EX_TOPMOST,
+    },
+};
+
+/// How often the Z-order is checked when no change notification arrived.
+const CHECK_INTERVAL: Duration = Duration::from_secs(2);
+
+// Set from the overlay window procedure; there is only ever one overlay window
+static Z_ORDER_CHANGED: AtomicBool = AtomicBool::new(false);
+// SetWindowPos sends WM_WINDOWPOSCHANGED synchronously, so our own re-asserts are filtered out with this
+static ASSERTING: AtomicBool = AtomicBool::new(false);
+
+/// Called by the overlay window procedure on WM_WINDOWPOSCHANGED when the Z-order changed.
+pub fn note_z_order_changed() {
+    if !ASSERTING.load(Ordering::Relaxed) {
+        Z_ORDER_CHANGED.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Keeps the overlay above other always-on-top windows (OSDs, crosshairs, PowerToys, ...).
+///
+/// Windows excluded from capture (WDA_EXCLUDEFROMCAPTURE) are missing from the duplicated frame, so
+/// drawing over them would paint what is behind them instead. In polite mode the overlay therefore
+/// stays directly below such windows rather than covering them; they show uncorrected but visible.
+pub struct TopmostKeeper {
+    hwnd: HWND,
+    last_check: Instant,
+}
+
+/// A topmost window above the overlay that overlaps it.
+struct WindowAbove {
+    hwnd: HWND,
+    excluded_from_capture: bool,
+}
+
+struct ZOrderScan {
+    overlay: HWND,
+    overlay_rect: RECT,
+    found_overlay: bool,
+    above: Vec<WindowAbove>,
+}
+
+impl TopmostKeeper {
+    pub fn new(hwnd: HWND) -> Self {
+        Z_ORDER_CHANGED.store(false, Ordering::Relaxed);
+        Self {
+            hwnd,
+            last_check: Instant::now(),
+        }
+    }
+
+    /// Re-asserts the overlay's position when the check interval elapsed or its Z-order changed.
+    pub fn maintain(&mut self, mode: TopmostMode) {
+        let changed = Z_ORDER_CHANGED.swap(false, Ordering::Relaxed);
+        if !changed && self.last_check.elapsed() < CHECK_INTERVAL {
+            return;
+        }
+        self.last_check = Instant::now();
+
+        let insert_after = match mode {
+            TopmostMode::Aggressive => HWND_TOPMOST,
+            TopmostMode::Polite => match unsafe { self.polite_insert_after() } {
+                Some(insert_after) => insert_after,
+                None => return,
+            },
+        };
+
+        ASSERTING.store(true, Ordering::Relaxed);
+        let result = unsafe {
+            SetWindowPos(self.hwnd, Some(insert_after), 0, 0, 0, 0, SWP_NOACTIVATE | SWP_NOMOVE | SWP_NOSIZE)
+        };
+        ASSERTING.store(false, Ordering::Relaxed);
+
+        if let Err(e) = result {
+            log_debug!("Failed to re-assert overlay Z-order: {:?}", e);
+        }
+    }
+
+    /// Where to move the overlay, or `None` if no capture-visible topmost window covers it.
+    unsafe fn polite_insert_after(&self) -> Option<HWND> {
+        let mut scan = ZOrderScan {
+            overlay: self.hwnd,
+            overlay_rect: RECT::default(),
+            found_overlay: false,
+            above: Vec::new(),
+        };
+        GetWindowRect(self.hwnd, &mut scan.overlay_rect).ok()?;
+
+        // Fails with no error code when the callback stops early, so check the scan instead
+        let _ = EnumWindows(Some(collect_windows_above), LPARAM(&mut scan as *mut _ as isize));
+        if !scan.found_overlay {
+            return None;
+        }
+
+        // Windows are listed top to bottom; only those below the lowest capture-excluded one count
+        let lowest_excluded = scan.above.iter().rposition(|w| w.excluded_from_capture);
+        let covering = match lowest_excluded {
+            Some(index) => scan.above.len() - index - 1,
+            None => scan.above.len(),
+        };
+        if covering == 0 {
+            return None;
+        }
+
+        log_debug!("{} topmost window(s) above the overlay - re-asserting Z-order", covering);
+        Some(match lowest_excluded {
+            Some(index) => scan.above[index].hwnd,
+            None => HWND_TOPMOST,
+        })
+    }
+}
+
+unsafe extern "system" fn collect_windows_above(hwnd: HWND, lparam: LPARAM) -> BOOL {
+    let scan = &mut *(lparam.0 as *mut ZOrderScan);
+
+    // EnumWindows goes top to bottom, so everything after the overlay is below it
+    if hwnd == scan.overlay {
+        scan.found_overlay = true;
+        return false.into();
+    }
+
+    let topmost = GetWindowLongW(hwnd, GWL_EXSTYLE) as u32 & WS_EX_TOPMOST.0 != 0;
+    if !topmost || !IsWindowVisible(hwnd).as_bool() {
+        return true.into();
+    }
+
+    let mut rect = RECT::default();
+    let overlaps = GetWindowRect(hwnd, &mut rect).is_ok()
+        && rect.left < scan.overlay_rect.right
+        && rect.right > scan.overlay_rect.left
+        && rect.top < scan.overlay_rect.bottom
+        && rect.bottom > scan.overlay_rect.top;
+
+    if overlaps {
+        let mut affinity = 0u32;
+        let excluded_from_capture = GetWindowDisplayAffinity(hwnd, &mut affinity).is_ok() && affinity != WDA_NONE.0;
+        scan.above.push(WindowAbove { hwnd, excluded_from_capture });
+    }
+
+    true.into()
+}
@@ -0,0 +1,269 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local, NaiveDateTime};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use crate::state::{check_asset_name, split_spectrum_name};
+
+/// Folder in the assets folder that deleted assets are moved into. Hidden, so the spectrum
+/// listing never picks it up.
+pub const TRASH_DIR_NAME: &str = ".trash";
+
+/// How long deleted assets are offered for restore; older ones are purged at startup.
+pub const RETENTION: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+const TIMESTAMP_FORMAT: &str = "%Y%m%d-%H%M%S";
+
+/// Between the original name and the deletion time in the trash, e.g. "deutan~20261015-153000.json".
+const SEPARATOR: char = '~';
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AssetKind {
+    Spectrum,
+    Noise,
+}
+
+impl AssetKind {
+    pub const ALL: [AssetKind; 2] = [AssetKind::Spectrum, AssetKind::Noise];
+
+    /// Subfolder of both the assets folder and the trash.
+    fn folder(&self) -> &'static str {
+        match self {
+            AssetKind::Spectrum => "spectrums",
+            AssetKind::Noise => "noise",
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self {
+            AssetKind::Spectrum => "json",
+            AssetKind::Noise => "png",
+        }
+    }
+
+    /// The file of asset `name` in `assets_dir`. Spectra can be in subfolders, noise textures can't.
+    pub fn path(&self, assets_dir: &Path, name: &str) -> Result<PathBuf> {
+        anyhow::ensure!(*self == AssetKind::Spectrum || !name.contains('/'), "Invalid noise texture name '{}'", name);
+        check_asset_name(name)?;
+        Ok(assets_dir.join(self.folder()).join(format!("{}.{}", name, self.extension())))
+    }
+}
+
+/// A deleted spectrum or noise texture waiting in the trash.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrashedAsset {
+    pub kind: AssetKind,
+    /// The name it had, e.g. "gaming/deutan-strong".
+    pub name: String,
+    pub deleted_at: DateTime<Local>,
+    /// Where the file is kept in the trash.
+    pub path: PathBuf,
+}
+
+impl TrashedAsset {
+    /// Whether it is still offered for restore at `now`.
+    pub fn restorable(&self, now: DateTime<Local>) -> bool {
+        // A deletion time in the future (clock changed) counts as recent
+        (now - self.deleted_at).to_std().map_or(true, |age| age < RETENTION)
+    }
+}
+
+pub fn trash_dir(assets_dir: &Path) -> PathBuf {
+    assets_dir.join(TRASH_DIR_NAME)
+}
+
+/// Moves asset `name` into the trash, named after it and `now`. Files deleted within the same
+/// second get a counter, so nothing in the trash is ever overwritten.
+pub fn move_to_trash(assets_dir: &Path, kind: AssetKind, name: &str, now: DateTime<Local>) -> Result<TrashedAsset> {
+    let source = kind.path(assets_dir, name)?;
+    let (folder, file_name) = split_spectrum_name(name);
+    let mut trash_folder = trash_dir(assets_dir).join(kind.folder());
+    if !folder.is_empty() {
+        trash_folder = trash_folder.join(folder);
+    }
+    std::fs::create_dir_all(&trash_folder)
+        .with_context(|| format!("Failed to create folder {}", trash_folder.display()))?;
+
+    let stamp = now.format(TIMESTAMP_FORMAT).to_string();
+    let path = (1..)
+        .map(|n| match n {
+            1 => stamp.clone(),
+            n => format!("{}-{}", stamp, n),
+        })
+        .map(|suffix| trash_folder.join(format!("{}{}{}.{}", file_name, SEPARATOR, suffix, kind.extension())))
+        .find(|path| !path.exists())
+        .unwrap_or_default();
+
+    std::fs::rename(&source, &path).with_context(|| format!("Failed to move {} to the trash", source.display()))?;
+
+    Ok(TrashedAsset {
+        kind,
+        name: name.to_string(),
+        deleted_at: now,
+        path,
+    })
+}
+
+/// Everything in the trash, most recently deleted first. Files that don't look like they were
+/// put there by `move_to_trash` are left out.
+pub fn list(assets_dir: &Path) -> Vec<TrashedAsset> {
+    let mut items = Vec::new();
+    for kind in AssetKind::ALL {
+        collect(&trash_dir(assets_dir).join(kind.folder()), kind, "", &mut items);
+    }
+    items.sort_by(|a, b| b.deleted_at.cmp(&a.deleted_at));
+    items
+}
+
+fn collect(dir: &Path, kind: AssetKind, prefix: &str, items: &mut Vec<TrashedAsset>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            if kind == AssetKind::Spectrum {
+                let folder = format!("{}{}/", prefix, entry.file_name().to_string_lossy());
+                collect(&path, kind, &folder, items);
+            }
+            continue;
+        }
+        if path.extension().is_none_or(|ext| ext != kind.extension()) {
+            continue;
+        }
+
+        let stem = path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+        let Some((file_name, suffix)) = stem.rsplit_once(SEPARATOR) else {
+            continue;
+        };
+        let deleted_at = suffix
+            .get(..15)
+            .and_then(|stamp| NaiveDateTime::parse_from_str(stamp, TIMESTAMP_FORMAT).ok())
+            .and_then(|time| time.and_local_timezone(Local).earliest());
+        if let Some(deleted_at) = deleted_at {
+            items.push(TrashedAsset {
+                kind,
+                name: format!("{}{}", prefix, file_name),
+                deleted_at,
+                path,
+            });
+        }
+    }
+}
+
+/// Moves `item` back to its folder and returns the name it got: its old name, or "name-2",
+/// "name-3", ... if another asset took that name since.
+pub fn restore(assets_dir: &Path, item: &TrashedAsset) -> Result<String> {
+    let (name, target) = (1..)
+        .map(|n| match n {
+            1 => item.name.clone(),
+            n => format!("{}-{}", item.name, n),
+        })
+        .map(|name| item.kind.path(assets_dir, &name).map(|path| (name, path)))
+        .find(|candidate| !matches!(candidate, Ok((_, path)) if path.exists()))
+        .unwrap_or_else(|| Err(anyhow::anyhow!("No free name for '{}'", item.name)))?;
+
+    if let Some(folder) = target.parent() {
+        std::fs::create_dir_all(folder).with_context(|| format!("Failed to create folder {}", folder.display()))?;
+    }
+    std::fs::rename(&item.path, &target).with_context(|| format!("Failed to restore {}", item.path.display()))?;
+    Ok(name)
+}
+
+/// Removes what is no longer restorable at `now`, returning how many files went.
+pub fn purge_expired(assets_dir: &Path, now: DateTime<Local>) -> usize {
+    list(assets_dir)
+        .into_iter()
+        .filter(|item| !item.restorable(now))
+        .filter(|item| std::fs::remove_file(&item.path).is_ok())
+        .count()
+}
+
+/// Empties the trash for good.
+pub fn purge_all(assets_dir: &Path) -> Result<()> {
+    match std::fs::remove_dir_all(trash_dir(assets_dir)) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e).context("Failed to empty the trash"),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    /// A temp dir with the asset folders the trash moves files out of.
+    fn temp_assets_dir(name: &str) -> PathBuf {
+        let dir = crate::test_util::temp_dir(name);
+        std::fs::create_dir_all(dir.join("spectrums").join("gaming")).unwrap();
+        std::fs::create_dir_all(dir.join("noise")).unwrap();
+        dir
+    }
+
+    #[test]
+    fn deleting_twice_in_a_second_keeps_both_files() {
+        let assets = temp_assets_dir("trash-collision");
+        let now = Local.with_ymd_and_hms(2026, 10, 15, 15, 30, 0).unwrap();
+        let spectrum = assets.join("spectrums").join("gaming").join("deutan.json");
+
+        std::fs::write(&spectrum, "first").unwrap();
+        let first = move_to_trash(&assets, AssetKind::Spectrum, "gaming/deutan", now).unwrap();
+        std::fs::write(&spectrum, "second").unwrap();
+        let second = move_to_trash(&assets, AssetKind::Spectrum, "gaming/deutan", now).unwrap();
+
+        assert!(!spectrum.exists());
+        assert_ne!(first.path, second.path);
+        let items = list(&assets);
+        assert_eq!(items.len(), 2);
+        assert!(items.iter().all(|item| item.name == "gaming/deutan" && item.deleted_at == now));
+
+        let _ = std::fs::remove_dir_all(&assets);
+    }
+
+    #[test]
+    fn restore_picks_a_free_name() {
+        let assets = temp_assets_dir("trash-restore");
+        let now = Local::now();
+        std::fs::write(assets.join("noise").join("dots.png"), "old").unwrap();
+        let item = move_to_trash(&assets, AssetKind::Noise, "dots", now).unwrap();
+        // A new file took the name in the meantime
+        std::fs::write(assets.join("noise").join("dots.png"), "new").unwrap();
+
+        assert_eq!(restore(&assets, &item).unwrap(), "dots-2");
+        assert_eq!(std::fs::read_to_string(assets.join("noise").join("dots-2.png")).unwrap(), "old");
+        assert!(list(&assets).is_empty());
+
+        let _ = std::fs::remove_dir_all(&assets);
+    }
+
+    #[test]
+    fn old_items_expire() {
+        let assets = temp_assets_dir("trash-expiry");
+        let deleted = Local.with_ymd_and_hms(2026, 9, 1, 12, 0, 0).unwrap();
+        std::fs::write(assets.join("spectrums").join("tritan.json"), "{}").unwrap();
+        std::fs::write(assets.join("spectrums").join("protan.json"), "{}").unwrap();
+        move_to_trash(&assets, AssetKind::Spectrum, "tritan", deleted).unwrap();
+        move_to_trash(&assets, AssetKind::Spectrum, "protan", deleted + chrono::Duration::days(20)).unwrap();
+
+        let now = deleted + chrono::Duration::days(31);
+        let items = list(&assets);
+        assert_eq!(items.iter().filter(|item| item.restorable(now)).count(), 1);
+        assert_eq!(purge_expired(&assets, now), 1);
+        assert_eq!(list(&assets)[0].name, "protan");
+
+        purge_all(&assets).unwrap();
+        assert!(list(&assets).is_empty());
+        // Nothing to purge is fine too
+        purge_all(&assets).unwrap();
+
+        let _ = std::fs::remove_dir_all(&assets);
+    }
+
+    #[test]
+    fn names_outside_the_asset_folders_are_refused() {
+        let assets = temp_assets_dir("trash-names");
+        assert!(move_to_trash(&assets, AssetKind::Spectrum, "../state", Local::now()).is_err());
+        assert!(move_to_trash(&assets, AssetKind::Noise, "gaming/dots", Local::now()).is_err());
+
+        let _ = std::fs::remove_dir_all(&assets);
+    }
+}
@@ -0,0 +1,470 @@
+use crate::StateManager;
+use crate::overlay::{OverlayManager, OverlayStatus};
+use anyhow::Result;
+use chromabridge::{log_info, log_warn, tr};
+use std::cell::{Cell, RefCell};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use tray_icon::menu::{CheckMenuItem, Menu, MenuId, MenuItem, PredefinedMenuItem, Submenu};
+use tray_icon::{Icon, TrayIcon, TrayIconBuilder};
+use windows::{
+    core::{w, PCWSTR},
+    Win32::Foundation::{HINSTANCE, HWND, LPARAM, LRESULT, WPARAM},
+    Win32::System::LibraryLoader::GetModuleHandleW,
+    Win32::UI::Shell::{
+        Shell_NotifyIconW, NIF_ICON, NIF_INFO, NIF_MESSAGE, NIF_TIP, NIIF_INFO, NIIF_RESPECT_QUIET_TIME,
+        NIM_ADD, NIM_DELETE, NIN_BALLOONHIDE, NIN_BALLOONTIMEOUT, NIN_BALLOONUSERCLICK, NOTIFYICONDATAW,
+    },
+    Win32::UI::WindowsAndMessaging::{
+        ChangeWindowMessageFilterEx, CreateWindowExW, DefWindowProcW, DestroyWindow, LoadIconW, RegisterClassW,
+        RegisterWindowMessageW, IDI_APPLICATION, MSGFLT_ALLOW, WINDOW_EX_STYLE, WINDOW_STYLE, WM_APP, WNDCLASSW,
+    },
+};
+
+const ICON_SIZE: u32 = 32;
+const FALLBACK_SIZE: u32 = 16;
+const FALLBACK_COLOR: [u8; 4] = [100, 150, 255, 255];
+const ERROR_BADGE_COLOR: [u8; 3] = [220, 40, 40];
+const BADGE_OUTLINE_COLOR: [u8; 3] = [30, 30, 30];
+
+/// Change of the "Strength +10%" and "Strength −10%" entries.
+const STRENGTH_STEP: f32 = 0.1;
+/// Strength presets in the tray's strength submenu, in percent.
+const STRENGTH_PRESETS: [u32; 4] = [25, 50, 75, 100];
+/// Id of the icon a balloon notification is shown from, on the taskbar watcher's window.
+const BALLOON_ICON_ID: u32 = 1;
+/// Sent to the taskbar watcher's window about the balloon icon, e.g. when the balloon closes.
+const BALLOON_CALLBACK_MESSAGE: u32 = WM_APP + 1;
+
+/// What the tray icon is currently showing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrayStatus {
+    Active,
+    Inactive,
+    Error,
+}
+
+impl TrayStatus {
+    pub fn of(overlay_manager: &OverlayManager) -> Self {
+        match overlay_manager.state() {
+            // Expected while a dock connects at logon, so not worth the error badge
+            OverlayStatus::WaitingForDisplay => TrayStatus::Inactive,
+            _ if overlay_manager.has_error() => TrayStatus::Error,
+            OverlayStatus::Starting | OverlayStatus::Restarting | OverlayStatus::Running => TrayStatus::Active,
+            OverlayStatus::Stopped | OverlayStatus::Failed => TrayStatus::Inactive,
+        }
+    }
+}
+
+/// Icon variants generated once from the loaded RGBA buffer.
+struct TrayIcons {
+    active: Icon,
+    inactive: Icon,
+    error: Icon,
+}
+
+impl TrayIcons {
+    fn new(rgba: Vec<u8>, width: u32, height: u32) -> Result<Self> {
+        let make = |rgba: Vec<u8>| {
+            Icon::from_rgba(rgba, width, height).map_err(|e| anyhow::anyhow!("Failed to create tray icon: {}", e))
+        };
+
+        Ok(Self {
+            inactive: make(greyed_out(&rgba))?,
+            error: make(with_badge(&rgba, width, height, ERROR_BADGE_COLOR))?,
+            active: make(rgba)?,
+        })
+    }
+
+    fn get(&self, status: TrayStatus) -> &Icon {
+        match status {
+            TrayStatus::Active => &self.active,
+            TrayStatus::Inactive => &self.inactive,
+            TrayStatus::Error => &self.error,
+        }
+    }
+}
+
+/// What one of the tray's strength entries does to the strength.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StrengthChange {
+    By(f32),
+    To(f32),
+}
+
+impl StrengthChange {
+    /// The strength after the change, clamped to 0-1 and rounded to whole percent so repeated
+    /// steps land on round values.
+    pub fn apply(&self, strength: f32) -> f32 {
+        let strength = match *self {
+            StrengthChange::By(step) => strength + step,
+            StrengthChange::To(value) => value,
+        };
+        ((strength * 100.0).round() / 100.0).clamp(0.0, 1.0)
+    }
+}
+
+/// `strength` (0 to 1) in whole percent, as the menu and tooltip show it.
+pub fn strength_percent(strength: f32) -> u32 {
+    (strength.clamp(0.0, 1.0) * 100.0).round() as u32
+}
+
+/// The tray menu entries, labeled in the current UI language.
+pub struct TrayMenuItems {
+    pub open_settings: MenuItem,
+    pub overlay: CheckMenuItem,
+    /// Only enabled while the overlay runs.
+    pub restart: MenuItem,
+    /// Titled with the current strength; holds the steps and presets.
+    pub strength: Submenu,
+    strength_up: MenuItem,
+    strength_down: MenuItem,
+    strength_presets: Vec<(u32, CheckMenuItem)>,
+    pub exit: MenuItem,
+}
+
+impl TrayMenuItems {
+    pub fn new(overlay_checked: bool, strength: f32) -> Result<Self> {
+        let percent = strength_percent(strength);
+        let items = Self {
+            open_settings: MenuItem::new(tr!("tray.open_settings"), true, None),
+            overlay: CheckMenuItem::new(tr!("tray.enable_overlay"), true, overlay_checked, None),
+            restart: MenuItem::new(tr!("tray.restart_overlay"), overlay_checked, None),
+            strength: Submenu::new(tr!("tray.strength", percent = percent), true),
+            strength_up: MenuItem::new(tr!("tray.strength_up"), true, None),
+            strength_down: MenuItem::new(tr!("tray.strength_down"), true, None),
+            strength_presets: STRENGTH_PRESETS
+                .iter()
+                .map(|&preset| (preset, CheckMenuItem::new(tr!("tray.strength_preset", percent = preset), true, preset == percent, None)))
+                .collect(),
+            exit: MenuItem::new(tr!("tray.exit"), true, None),
+        };
+
+        items.strength.append_items(&[&items.strength_up, &items.strength_down, &PredefinedMenuItem::separator()])?;
+        for (_, preset) in &items.strength_presets {
+            items.strength.append(preset)?;
+        }
+        Ok(items)
+    }
+
+    /// The strength entries' ids with what each does, for the menu event handler.
+    pub fn strength_ids(&self) -> Vec<(MenuId, StrengthChange)> {
+        let mut ids = vec![
+            (self.strength_up.id().clone(), StrengthChange::By(STRENGTH_STEP)),
+            (self.strength_down.id().clone(), StrengthChange::By(-STRENGTH_STEP)),
+        ];
+        ids.extend(
+            self.strength_presets
+                .iter()
+                .map(|(preset, item)| (item.id().clone(), StrengthChange::To(*preset as f32 / 100.0))),
+        );
+        ids
+    }
+
+    /// Shows `strength` in the submenu title and checks the preset it matches, if any. Also
+    /// undoes the check a click on a preset toggles by itself.
+    fn show_strength(&self, strength: f32) {
+        let percent = strength_percent(strength);
+        let title = tr!("tray.strength", percent = percent);
+        if self.strength.text() != title {
+            self.strength.set_text(title);
+        }
+        for (preset, item) in &self.strength_presets {
+            set_checked(item, *preset == percent);
+        }
+    }
+
+    fn relabel(&self) {
+        self.open_settings.set_text(tr!("tray.open_settings"));
+        self.overlay.set_text(tr!("tray.enable_overlay"));
+        self.restart.set_text(tr!("tray.restart_overlay"));
+        self.strength_up.set_text(tr!("tray.strength_up"));
+        self.strength_down.set_text(tr!("tray.strength_down"));
+        self.exit.set_text(tr!("tray.exit"));
+    }
+}
+
+/// Keeps the tray icon, tooltip and "Enable Overlay" checkbox in sync with the overlay, the
+/// strength submenu with the strength, and the menu labels in sync with the UI language.
+///
+/// Shared between the main loop and the settings window, both of which run on the main thread.
+/// Refreshing is cheap when nothing changed: only what differs from what is shown is set, since
+/// some shells redraw an open tooltip on every update even with the same text.
+pub struct TrayIndicator {
+    tray_icon: TrayIcon,
+    /// Kept to rebuild the icon with the same menu, and so the same menu ids, after Explorer restarts.
+    menu: Menu,
+    menu_items: TrayMenuItems,
+    icons: TrayIcons,
+    shown: Cell<Option<TrayStatus>>,
+    shown_tooltip: RefCell<Option<String>>,
+    labeled_locale: Cell<&'static str>,
+}
+
+impl TrayIndicator {
+    pub fn new(menu: Menu, menu_items: TrayMenuItems, icons_rgba: (Vec<u8>, u32, u32), tooltip: &str) -> Result<Self> {
+        let (rgba, width, height) = icons_rgba;
+        let icons = TrayIcons::new(rgba, width, height)?;
+        Ok(Self {
+            tray_icon: build_tray_icon(&menu, icons.get(TrayStatus::Active).clone(), tooltip)?,
+            menu,
+            menu_items,
+            icons,
+            shown: Cell::new(None),
+            shown_tooltip: RefCell::new(None),
+            labeled_locale: Cell::new(chromabridge::i18n::current_locale()),
+        })
+    }
+
+    /// Adds the icon to the notification area again, for when a restarted Explorer came up
+    /// without it. The menu is reused, so the ids the menu event handler matches stay valid.
+    pub fn recreate(&mut self, state: &StateManager, overlay_manager: &OverlayManager) -> Result<()> {
+        let status = TrayStatus::of(overlay_manager);
+        let tooltip = crate::tray_tooltip(&state.snapshot(), overlay_manager);
+        let tray_icon = build_tray_icon(&self.menu, self.icons.get(status).clone(), &tooltip)?;
+        // Dropping the old icon removes whatever is left of it before the new one takes its place
+        self.tray_icon = tray_icon;
+        self.shown.set(Some(status));
+        *self.shown_tooltip.borrow_mut() = Some(tooltip);
+        Ok(())
+    }
+
+    pub fn refresh(&self, state: &StateManager, overlay_manager: &OverlayManager) {
+        let settings = state.snapshot();
+        let tooltip = crate::tray_tooltip(&settings, overlay_manager);
+        if self.shown_tooltip.borrow().as_deref() != Some(tooltip.as_str()) {
+            self.tray_icon.set_tooltip(Some(&tooltip)).ok();
+            *self.shown_tooltip.borrow_mut() = Some(tooltip);
+        }
+        let overlay_status = overlay_manager.state();
+        let overlay_on = matches!(overlay_status, OverlayStatus::Starting | OverlayStatus::Restarting | OverlayStatus::Running);
+        set_checked(&self.menu_items.overlay, overlay_on);
+        let restartable = overlay_status == OverlayStatus::Running;
+        if self.menu_items.restart.is_enabled() != restartable {
+            self.menu_items.restart.set_enabled(restartable);
+        }
+        self.menu_items.show_strength(settings.strength);
+
+        let locale = chromabridge::i18n::current_locale();
+        if self.labeled_locale.get() != locale {
+            self.menu_items.relabel();
+            self.labeled_locale.set(locale);
+        }
+
+        let status = TrayStatus::of(overlay_manager);
+        if self.shown.get() != Some(status) {
+            // set_icon recreates the native icon handle, so only call it on actual changes
+            self.tray_icon.set_icon(Some(self.icons.get(status).clone())).ok();
+            self.shown.set(Some(status));
+        }
+    }
+}
+
+fn build_tray_icon(menu: &Menu, icon: Icon, tooltip: &str) -> Result<TrayIcon> {
+    Ok(TrayIconBuilder::new()
+        .with_menu(Box::new(menu.clone()))
+        .with_menu_on_left_click(false)
+        .with_tooltip(tooltip)
+        .with_icon(icon)
+        .build()?)
+}
+
+// The window procedure has no context pointer of its own; 0 until registered
+static TASKBAR_CREATED_MESSAGE: AtomicU32 = AtomicU32::new(0);
+static TASKBAR_CREATED: AtomicBool = AtomicBool::new(false);
+
+/// True once after Explorer recreated the taskbar since the last call, e.g. after it crashed or
+/// was restarted, which drops every notification area icon.
+pub fn take_taskbar_created() -> bool {
+    TASKBAR_CREATED.swap(false, Ordering::AcqRel)
+}
+
+/// Hidden window that receives the "TaskbarCreated" broadcast Explorer sends to top-level windows
+/// when it starts, noting it for `take_taskbar_created`. Also owns the icon balloon notifications
+/// are shown from where toasts aren't available.
+///
+/// Lives on the main thread, so its messages are dispatched by the main loop or by the settings window's event loop.
+pub struct TaskbarWatcher {
+    hwnd: HWND,
+}
+
+impl TaskbarWatcher {
+    pub fn register() -> Result<Self> {
+        unsafe {
+            let message = RegisterWindowMessageW(w!("TaskbarCreated"));
+            anyhow::ensure!(message != 0, "Failed to register the TaskbarCreated message");
+            TASKBAR_CREATED_MESSAGE.store(message, Ordering::Release);
+
+            let class_name = w!("ChromaBridgeTaskbarWatcher");
+            let hinstance = GetModuleHandleW(None)?;
+
+            let wc = WNDCLASSW {
+                lpfnWndProc: Some(window_proc),
+                hInstance: hinstance.into(),
+                lpszClassName: class_name,
+                ..Default::default()
+            };
+            RegisterClassW(&wc);
+
+            // Never shown; a message-only window would not receive broadcasts
+            let hwnd = CreateWindowExW(
+                WINDOW_EX_STYLE::default(),
+                class_name,
+                w!("ChromaBridge Taskbar Watcher"),
+                WINDOW_STYLE::default(),
+                0, 0, 0, 0,
+                None, None,
+                Some(HINSTANCE(hinstance.0)),
+                None,
+            )?;
+
+            // Explorer runs unelevated; without this an elevated app never hears from it
+            if let Err(e) = ChangeWindowMessageFilterEx(hwnd, message, MSGFLT_ALLOW, None) {
+                log_warn!("Failed to allow TaskbarCreated through the message filter: {}", e);
+            }
+
+            Ok(Self { hwnd })
+        }
+    }
+
+    /// Shows a balloon from an icon of its own next to the tray icon, which goes away with the
+    /// balloon. tray-icon offers no balloons, and its icon's window and id aren't reachable.
+    /// Balloons never take focus, and stay quiet during quiet hours and fullscreen apps.
+    pub fn show_balloon(&self, title: &str, text: &str) -> Result<()> {
+        unsafe {
+            let hinstance = GetModuleHandleW(None)?;
+            // The app icon is the first icon resource
+            let icon = LoadIconW(Some(HINSTANCE(hinstance.0)), PCWSTR(1 as *const u16))
+                .or_else(|_| LoadIconW(None, IDI_APPLICATION))?;
+
+            let mut data = NOTIFYICONDATAW {
+                cbSize: std::mem::size_of::<NOTIFYICONDATAW>() as u32,
+                hWnd: self.hwnd,
+                uID: BALLOON_ICON_ID,
+                uFlags: NIF_ICON | NIF_INFO | NIF_MESSAGE | NIF_TIP,
+                uCallbackMessage: BALLOON_CALLBACK_MESSAGE,
+                hIcon: icon,
+                dwInfoFlags: NIIF_INFO | NIIF_RESPECT_QUIET_TIME,
+                ..Default::default()
+            };
+            copy_truncated(&mut data.szTip, "ChromaBridge");
+            copy_truncated(&mut data.szInfoTitle, title);
+            copy_truncated(&mut data.szInfo, text);
+
+            // A balloon still up is replaced rather than queued
+            let _ = Shell_NotifyIconW(NIM_DELETE, &data);
+            anyhow::ensure!(Shell_NotifyIconW(NIM_ADD, &data).as_bool(), "Failed to add the balloon icon");
+            Ok(())
+        }
+    }
+}
+
+impl Drop for TaskbarWatcher {
+    fn drop(&mut self) {
+        unsafe {
+            remove_balloon_icon(self.hwnd);
+            let _ = DestroyWindow(self.hwnd);
+        }
+    }
+}
+
+/// Copies `text` into a fixed-size, nul-terminated wide string field, cutting it off if too long.
+fn copy_truncated(field: &mut [u16], text: &str) {
+    let len = field.len() - 1;
+    for (slot, unit) in field.iter_mut().zip(text.encode_utf16().take(len).chain(std::iter::once(0))) {
+        *slot = unit;
+    }
+}
+
+unsafe fn remove_balloon_icon(hwnd: HWND) {
+    let data = NOTIFYICONDATAW {
+        cbSize: std::mem::size_of::<NOTIFYICONDATAW>() as u32,
+        hWnd: hwnd,
+        uID: BALLOON_ICON_ID,
+        ..Default::default()
+    };
+    let _ = Shell_NotifyIconW(NIM_DELETE, &data);
+}
+
+unsafe extern "system" fn window_proc(hwnd: HWND, msg: u32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    let taskbar_created = TASKBAR_CREATED_MESSAGE.load(Ordering::Acquire);
+    if taskbar_created != 0 && msg == taskbar_created {
+        TASKBAR_CREATED.store(true, Ordering::Release);
+    }
+    if msg == BALLOON_CALLBACK_MESSAGE && matches!(lparam.0 as u32 & 0xFFFF, NIN_BALLOONTIMEOUT | NIN_BALLOONHIDE | NIN_BALLOONUSERCLICK) {
+        remove_balloon_icon(hwnd);
+    }
+
+    DefWindowProcW(hwnd, msg, wparam, lparam)
+}
+
+/// Compares with the item's own state rather than a cached one, since clicking a check item
+/// toggles it without going through here.
+fn set_checked(item: &CheckMenuItem, checked: bool) {
+    if item.is_checked() != checked {
+        item.set_checked(checked);
+    }
+}
+
+/// Loads `icon.ico` next to the executable as raw RGBA, falling back to a plain blue square.
+pub fn load_icon_rgba() -> (Vec<u8>, u32, u32) {
+    let icon_path = std::env::current_exe()
+        .ok()
+        .and_then(|p| p.parent().map(|p| p.join("icon.ico")));
+
+    match icon_path {
+        Some(path) if path.exists() => match image::open(&path) {
+            Ok(image) => {
+                log_info!("Loaded icon from {:?}", path);
+                let rgba = image
+                    .resize_exact(ICON_SIZE, ICON_SIZE, image::imageops::FilterType::Lanczos3)
+                    .to_rgba8();
+                return (rgba.into_raw(), ICON_SIZE, ICON_SIZE);
+            }
+            Err(e) => log_warn!("Failed to load icon from {:?}: {}. Using fallback.", path, e),
+        },
+        Some(path) => log_warn!("Icon file not found at {:?}. Using fallback.", path),
+        None => log_warn!("Could not determine executable directory. Using fallback icon."),
+    }
+
+    let rgba = FALLBACK_COLOR.repeat((FALLBACK_SIZE * FALLBACK_SIZE) as usize);
+    (rgba, FALLBACK_SIZE, FALLBACK_SIZE)
+}
+
+/// Desaturates and fades the icon so "off" reads differently even at 16px.
+fn greyed_out(rgba: &[u8]) -> Vec<u8> {
+    rgba.chunks_exact(4)
+        .flat_map(|px| {
+            let luma = (0.299 * px[0] as f32 + 0.587 * px[1] as f32 + 0.114 * px[2] as f32) as u8;
+            [luma, luma, luma, (px[3] as f32 * 0.6) as u8]
+        })
+        .collect()
+}
+
+/// Draws a filled circle with a dark outline in the bottom-right corner.
+fn with_badge(rgba: &[u8], width: u32, height: u32, color: [u8; 3]) -> Vec<u8> {
+    let mut out = rgba.to_vec();
+    let radius = (width.min(height) as f32 / 4.0).max(3.0);
+    let center_x = width as f32 - radius - 1.0;
+    let center_y = height as f32 - radius - 1.0;
+
+    for y in 0..height {
+        for x in 0..width {
+            let dx = x as f32 + 0.5 - center_x;
+            let dy = y as f32 + 0.5 - center_y;
+            let distance = (dx * dx + dy * dy).sqrt();
+
+            let fill = if distance <= radius {
+                color
+            } else if distance <= radius + 1.0 {
+                BADGE_OUTLINE_COLOR
+            } else {
+                continue;
+            };
+
+            let idx = ((y * width + x) * 4) as usize;
+            out[idx..idx + 4].copy_from_slice(&[fill[0], fill[1], fill[2], 255]);
+        }
+    }
+
+    out
+}
@@ -0,0 +1,236 @@
+//! Telling whether a newer release is out, from a small JSON manifest. Only detection: fetching
+//! the manifest is the app's job, and updating is left to the user.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The GitHub releases API entry of the latest release.
+pub const DEFAULT_MANIFEST_URL: &str = "https://api.github.com/repos/99oblivius/ChromaBridge/releases/latest";
+
+/// A successful check is good for this long, also across launches.
+pub const CHECK_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// The version of this build.
+pub const CURRENT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// A semantic version, compared by semver precedence: build metadata is ignored and a
+/// pre-release sorts before its release. Missing minor or patch numbers count as 0, so tags
+/// like `v1.2` work too.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Version {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+    pre: Vec<PreRelease>,
+}
+
+/// One dot-separated part of a pre-release, e.g. `beta` and `2` of `1.0.0-beta.2`. Numeric parts
+/// sort before alphanumeric ones, as the variant order makes the derive do.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum PreRelease {
+    Numeric(u64),
+    Alphanumeric(String),
+}
+
+impl Version {
+    /// Parses `1.2.3`, `v1.2.3-rc.1+build` and the like.
+    pub fn parse(text: &str) -> Option<Version> {
+        let text = text.trim();
+        let text = text.strip_prefix(['v', 'V']).unwrap_or(text);
+        let text = text.split_once('+').map_or(text, |(version, _build)| version);
+        let (core, pre) = match text.split_once('-') {
+            Some((core, pre)) => (core, Some(pre)),
+            None => (text, None),
+        };
+
+        let mut numbers = core.split('.').map(parse_number);
+        let major = numbers.next()??;
+        let minor = numbers.next().unwrap_or(Some(0))?;
+        let patch = numbers.next().unwrap_or(Some(0))?;
+        if numbers.next().is_some() {
+            return None;
+        }
+
+        let pre = match pre {
+            Some(pre) => pre
+                .split('.')
+                .map(|part| match parse_number(part) {
+                    Some(n) => Some(PreRelease::Numeric(n)),
+                    None if !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') => {
+                        Some(PreRelease::Alphanumeric(part.to_string()))
+                    }
+                    None => None,
+                })
+                .collect::<Option<Vec<_>>>()?,
+            None => Vec::new(),
+        };
+        Some(Version { major, minor, patch, pre })
+    }
+
+    pub fn is_prerelease(&self) -> bool {
+        !self.pre.is_empty()
+    }
+}
+
+/// Digits only, so signs and spaces don't pass as numbers.
+fn parse_number(text: &str) -> Option<u64> {
+    if text.is_empty() || !text.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    text.parse().ok()
+}
+
+impl Ord for Version {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.major, self.minor, self.patch)
+            .cmp(&(other.major, other.minor, other.patch))
+            .then_with(|| match (self.pre.is_empty(), other.pre.is_empty()) {
+                (true, true) => Ordering::Equal,
+                (true, false) => Ordering::Greater,
+                (false, true) => Ordering::Less,
+                (false, false) => self.pre.cmp(&other.pre),
+            })
+    }
+}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// The latest release a manifest names.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Release {
+    /// As the manifest gives it, e.g. `v0.2025.40`.
+    pub version: String,
+    /// The release page, opened in the browser for the download.
+    pub url: String,
+}
+
+impl Release {
+    /// Reads either a GitHub releases API entry (`tag_name`, `html_url`) or a static file of the
+    /// form `{"version": "0.2025.40", "url": "https://..."}`.
+    pub fn from_manifest(json: &str) -> Result<Release> {
+        let manifest: serde_json::Value = serde_json::from_str(json).context("Update manifest is not JSON")?;
+        let field = |names: [&str; 2]| names.iter().find_map(|name| manifest.get(*name).and_then(|v| v.as_str()));
+
+        let version = field(["tag_name", "version"]).context("Update manifest names no version")?;
+        if Version::parse(version).is_none() {
+            anyhow::bail!("Update manifest version \"{}\" is not a semantic version", version);
+        }
+        let url = field(["html_url", "url"]).context("Update manifest has no release page")?;
+        // It is handed to the browser, so nothing but web pages
+        if !url.starts_with("https://") {
+            anyhow::bail!("Update manifest release page \"{}\" is not an https address", url);
+        }
+        Ok(Release { version: version.to_string(), url: url.to_string() })
+    }
+
+    /// True if this release is newer than `current`; false for versions that don't parse.
+    pub fn is_newer_than(&self, current: &str) -> bool {
+        match (Version::parse(&self.version), Version::parse(current)) {
+            (Some(latest), Some(current)) => latest > current,
+            _ => false,
+        }
+    }
+}
+
+/// The outcome of the last successful update check, kept in the app state.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UpdateCheck {
+    /// Seconds since the Unix epoch.
+    pub checked_at: u64,
+    /// The manifest address the check used; a changed address makes the next check due.
+    pub manifest_url: String,
+    pub latest: Release,
+}
+
+impl UpdateCheck {
+    pub fn new(manifest_url: &str, latest: Release, now: SystemTime) -> Self {
+        Self { checked_at: unix_seconds(now), manifest_url: manifest_url.to_string(), latest }
+    }
+
+    /// Whether `last` is missing, older than `CHECK_INTERVAL`, from another manifest, or dated in
+    /// the future, as after the clock was set back.
+    pub fn is_due(last: Option<&UpdateCheck>, manifest_url: &str, now: SystemTime) -> bool {
+        let Some(last) = last else {
+            return true;
+        };
+        let now = unix_seconds(now);
+        last.manifest_url != manifest_url || last.checked_at > now || now - last.checked_at >= CHECK_INTERVAL.as_secs()
+    }
+
+    /// The latest release if it is newer than this build.
+    pub fn update(&self) -> Option<&Release> {
+        self.latest.is_newer_than(CURRENT_VERSION).then_some(&self.latest)
+    }
+}
+
+fn unix_seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).map_or(0, |since| since.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(text: &str) -> Version {
+        Version::parse(text).unwrap_or_else(|| panic!("{} should parse", text))
+    }
+
+    #[test]
+    fn versions_follow_semver_precedence() {
+        let ordered = [
+            "0.9.9", "1.0.0-alpha", "1.0.0-alpha.1", "1.0.0-alpha.beta", "1.0.0-beta", "1.0.0-beta.2",
+            "1.0.0-beta.11", "1.0.0-rc.1", "1.0.0", "1.0.1", "1.2.0", "1.10.0", "2.0.0",
+        ];
+        for pair in ordered.windows(2) {
+            assert!(version(pair[0]) < version(pair[1]), "{} < {}", pair[0], pair[1]);
+        }
+        assert_eq!(version("v1.2.3+build.7"), version("1.2.3"));
+        assert_eq!(version("V1.2"), version("1.2.0"));
+        assert_eq!(version("0.2025.36").cmp(&version("0.2025.4")), Ordering::Greater);
+        assert!(version("1.0.0-rc.1").is_prerelease());
+    }
+
+    #[test]
+    fn rejects_what_is_not_a_version() {
+        for text in ["", "v", "latest", "1.2.3.4", "1..3", "1.-2.3", "+1.2.3", "1.2.3-", "1.2.3-beta..1", "1.2.3-be ta"] {
+            assert_eq!(Version::parse(text), None, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn reads_github_and_static_manifests() {
+        let github = r#"{"tag_name": "v0.2026.1", "html_url": "https://github.com/99oblivius/ChromaBridge/releases/tag/v0.2026.1", "draft": false}"#;
+        let release = Release::from_manifest(github).unwrap();
+        assert_eq!(release.version, "v0.2026.1");
+        assert!(release.url.ends_with("/v0.2026.1"));
+        assert!(release.is_newer_than("0.2025.36"));
+        assert!(!release.is_newer_than("0.2026.1"));
+
+        let file = r#"{"version": "0.2025.1", "url": "https://example.com/chromabridge"}"#;
+        assert!(!Release::from_manifest(file).unwrap().is_newer_than("0.2025.36"));
+
+        assert!(Release::from_manifest("<html>").is_err());
+        assert!(Release::from_manifest(r#"{"version": "soon", "url": "https://example.com"}"#).is_err());
+        assert!(Release::from_manifest(r#"{"version": "1.0.0", "url": "file:///C:/Windows/System32/calc.exe"}"#).is_err());
+        assert!(Release::from_manifest(r#"{"version": "1.0.0"}"#).is_err());
+    }
+
+    #[test]
+    fn checks_are_due_daily() {
+        let release = Release { version: "1.0.0".into(), url: "https://example.com".into() };
+        let then = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let check = UpdateCheck::new(DEFAULT_MANIFEST_URL, release, then);
+
+        assert!(UpdateCheck::is_due(None, DEFAULT_MANIFEST_URL, then));
+        assert!(!UpdateCheck::is_due(Some(&check), DEFAULT_MANIFEST_URL, then + Duration::from_secs(60 * 60)));
+        assert!(UpdateCheck::is_due(Some(&check), DEFAULT_MANIFEST_URL, then + CHECK_INTERVAL));
+        assert!(UpdateCheck::is_due(Some(&check), "https://example.com/latest.json", then));
+        assert!(UpdateCheck::is_due(Some(&check), DEFAULT_MANIFEST_URL, then - Duration::from_secs(60)));
+    }
+}
@@ -0,0 +1,125 @@
+//! Looks for a newer release in the background when update checks are on, see `chromabridge::update_check`.
+
+use crate::StateManager;
+use anyhow::{Context, Result};
+use chromabridge::update_check::{Release, UpdateCheck, CURRENT_VERSION};
+use chromabridge::{log_debug, log_info};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+use windows::{
+    core::{h, HSTRING},
+    Foundation::{AsyncStatus, Uri},
+    Web::Http::HttpClient,
+    Win32::System::Com::{CoInitializeEx, COINIT_MULTITHREADED},
+};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// After a failed check, e.g. while offline, the next attempt waits this long.
+const RETRY_INTERVAL: Duration = Duration::from_secs(60 * 60);
+/// A request taking longer is cancelled and counts as failed.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Fetches the update manifest at launch and whenever the last check is a day old, and stores
+/// the result in the app state, where the settings window and tray pick it up.
+///
+/// Never shows anything itself, and failures are only logged at debug level: being offline is
+/// no reason to bother anyone.
+pub struct UpdateChecker {
+    shutdown: Arc<AtomicBool>,
+    thread: parking_lot::Mutex<Option<thread::JoinHandle<()>>>,
+}
+
+/// The last failed attempt and the manifest it was for; a changed address is tried right away.
+struct Failure {
+    at: Instant,
+    manifest_url: String,
+}
+
+impl UpdateChecker {
+    pub fn spawn(state: Arc<StateManager>) -> Self {
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let shutdown_flag = Arc::clone(&shutdown);
+
+        let thread = thread::spawn(move || {
+            // Windows.Web.Http is a WinRT API
+            unsafe {
+                let _ = CoInitializeEx(None, COINIT_MULTITHREADED);
+            }
+            let mut failure: Option<Failure> = None;
+            while !shutdown_flag.load(Ordering::Acquire) {
+                Self::poll(&state, &shutdown_flag, &mut failure);
+                thread::sleep(POLL_INTERVAL);
+            }
+        });
+
+        Self {
+            shutdown,
+            thread: parking_lot::Mutex::new(Some(thread)),
+        }
+    }
+
+    /// Cancels a request in flight and waits for the thread.
+    pub fn stop(&self) {
+        self.shutdown.store(true, Ordering::Release);
+        if let Some(handle) = self.thread.lock().take() {
+            let _ = handle.join();
+        }
+    }
+
+    fn poll(state: &StateManager, shutdown: &AtomicBool, failure: &mut Option<Failure>) {
+        let (enabled, manifest_url, last_check) =
+            state.read(|s| (s.check_for_updates, s.update_manifest_url.clone(), s.last_update_check.clone()));
+        if !enabled || !UpdateCheck::is_due(last_check.as_ref(), &manifest_url, SystemTime::now()) {
+            return;
+        }
+        if failure.as_ref().is_some_and(|f| f.manifest_url == manifest_url && f.at.elapsed() < RETRY_INTERVAL) {
+            return;
+        }
+
+        match fetch(&manifest_url, shutdown).and_then(|json| Release::from_manifest(&json)) {
+            Ok(latest) => {
+                *failure = None;
+                if latest.is_newer_than(CURRENT_VERSION) {
+                    log_info!("Update available: {} (running {}), {}", latest.version, CURRENT_VERSION, latest.url);
+                } else {
+                    log_debug!("No update available, the latest release is {}", latest.version);
+                }
+                let check = UpdateCheck::new(&manifest_url, latest, SystemTime::now());
+                state.update(|s| s.last_update_check = Some(check));
+            }
+            Err(_) if shutdown.load(Ordering::Acquire) => {}
+            Err(e) => {
+                log_debug!("Update check failed, retrying in {} minutes: {:#}", RETRY_INTERVAL.as_secs() / 60, e);
+                *failure = Some(Failure { at: Instant::now(), manifest_url });
+            }
+        }
+    }
+}
+
+/// The body of `url`, giving up after `REQUEST_TIMEOUT` or once `shutdown` is set.
+fn fetch(url: &str, shutdown: &AtomicBool) -> Result<String> {
+    let uri = Uri::CreateUri(&HSTRING::from(url)).with_context(|| format!("Invalid update manifest address \"{}\"", url))?;
+    let client = HttpClient::new()?;
+    let headers = client.DefaultRequestHeaders()?;
+    // The GitHub API turns away requests without one
+    headers.UserAgent()?.TryParseAdd(&HSTRING::from(format!("ChromaBridge/{}", CURRENT_VERSION)))?;
+    headers.Accept()?.TryParseAdd(h!("application/json"))?;
+
+    let request = client.GetStringAsync(&uri)?;
+    let deadline = Instant::now() + REQUEST_TIMEOUT;
+    while request.Status()? == AsyncStatus::Started {
+        if shutdown.load(Ordering::Acquire) {
+            let _ = request.Cancel();
+            anyhow::bail!("Request to {} cancelled on exit", url);
+        }
+        if Instant::now() >= deadline {
+            let _ = request.Cancel();
+            anyhow::bail!("Request to {} timed out after {}s", url, REQUEST_TIMEOUT.as_secs());
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+    let body = request.GetResults().with_context(|| format!("Request to {} failed", url))?;
+    Ok(body.to_string_lossy())
+}
@@ -0,0 +1,137 @@
+/// Broad kind of color vision a test answer points towards.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VisionType {
+    Typical,
+    ProtanDeutan,
+    Tritan,
+}
+
+/// A pair of swatches; the pair the user finds hardest to tell apart hints at their vision type.
+pub struct TestPanel {
+    pub colors: [(u8, u8, u8); 2],
+    pub indicates: VisionType,
+}
+
+pub struct TestScreen {
+    pub prompt: &'static str,
+    pub panels: &'static [TestPanel],
+}
+
+/// Each screen has one control pair (a slight lightness difference everyone can see)
+/// and pairs along the red-green and blue-yellow confusion lines.
+pub const TEST_SCREENS: &[TestScreen] = &[
+    TestScreen {
+        prompt: "Which pair of colors looks the most alike?",
+        panels: &[
+            TestPanel { colors: [(196, 84, 60), (128, 128, 52)], indicates: VisionType::ProtanDeutan },
+            TestPanel { colors: [(120, 120, 120), (142, 142, 142)], indicates: VisionType::Typical },
+            TestPanel { colors: [(84, 150, 168), (104, 128, 206)], indicates: VisionType::Tritan },
+        ],
+    },
+    TestScreen {
+        prompt: "Which pair of colors looks the most alike?",
+        panels: &[
+            TestPanel { colors: [(222, 196, 96), (226, 160, 168)], indicates: VisionType::Tritan },
+            TestPanel { colors: [(214, 120, 40), (150, 160, 48)], indicates: VisionType::ProtanDeutan },
+            TestPanel { colors: [(70, 110, 180), (84, 124, 196)], indicates: VisionType::Typical },
+        ],
+    },
+    TestScreen {
+        prompt: "Which pair of colors looks the most alike?",
+        panels: &[
+            TestPanel { colors: [(168, 90, 130), (110, 110, 160)], indicates: VisionType::ProtanDeutan },
+            TestPanel { colors: [(96, 170, 96), (70, 150, 150)], indicates: VisionType::Tritan },
+            TestPanel { colors: [(200, 90, 80), (214, 108, 96)], indicates: VisionType::Typical },
+        ],
+    },
+    TestScreen {
+        prompt: "Which pair of colors looks the most alike?",
+        panels: &[
+            TestPanel { colors: [(140, 190, 90), (200, 170, 80)], indicates: VisionType::ProtanDeutan },
+            TestPanel { colors: [(180, 140, 60), (196, 156, 76)], indicates: VisionType::Typical },
+            TestPanel { colors: [(130, 100, 190), (120, 130, 120)], indicates: VisionType::Tritan },
+        ],
+    },
+];
+
+pub struct Recommendation {
+    pub vision: VisionType,
+    /// Minimum number of answers pointing at `vision` for this entry to apply.
+    pub min_votes: usize,
+    pub description: &'static str,
+    pub spectrum: &'static str,
+    pub noise: Option<&'static str>,
+    pub strength: f32,
+}
+
+/// Checked top to bottom; the first entry matching the tallied answers wins.
+pub const RECOMMENDATIONS: &[Recommendation] = &[
+    Recommendation {
+        vision: VisionType::ProtanDeutan,
+        min_votes: 3,
+        description: "Strong red-green color blindness (protan/deutan)",
+        spectrum: "deutan-protan-contrast-interlaced",
+        noise: Some("clustered"),
+        strength: 1.0,
+    },
+    Recommendation {
+        vision: VisionType::ProtanDeutan,
+        min_votes: 1,
+        description: "Mild red-green color blindness (protan/deutan)",
+        spectrum: "deutan-protan-true-interlaced",
+        noise: Some("clustered"),
+        strength: 0.6,
+    },
+    Recommendation {
+        vision: VisionType::Tritan,
+        min_votes: 3,
+        description: "Strong blue-yellow color blindness (tritan)",
+        spectrum: "tritan-contrast-interlaced",
+        noise: Some("clustered"),
+        strength: 1.0,
+    },
+    Recommendation {
+        vision: VisionType::Tritan,
+        min_votes: 1,
+        description: "Mild blue-yellow color blindness (tritan)",
+        spectrum: "tritan-true-interlaced",
+        noise: Some("clustered"),
+        strength: 0.6,
+    },
+    Recommendation {
+        vision: VisionType::Typical,
+        min_votes: 0,
+        description: "No color blindness detected",
+        spectrum: "normal",
+        noise: None,
+        strength: 1.0,
+    },
+];
+
+/// Picks a recommendation from the panel chosen on each screen (`None` = "they all look different").
+pub fn recommend(answers: &[Option<usize>]) -> &'static Recommendation {
+    let votes = |vision: VisionType| {
+        answers
+            .iter()
+            .zip(TEST_SCREENS)
+            .filter(|(answer, screen)| {
+                answer.and_then(|i| screen.panels.get(i)).map(|p| p.indicates) == Some(vision)
+            })
+            .count()
+    };
+
+    let protan_deutan = votes(VisionType::ProtanDeutan);
+    let tritan = votes(VisionType::Tritan);
+    let (vision, count) = if protan_deutan == 0 && tritan == 0 {
+        (VisionType::Typical, 0)
+    } else if protan_deutan >= tritan {
+        (VisionType::ProtanDeutan, protan_deutan)
+    } else {
+        (VisionType::Tritan, tritan)
+    };
+
+    RECOMMENDATIONS
+        .iter()
+        .find(|r| r.vision == vision && count >= r.min_votes)
+        .unwrap_or(&RECOMMENDATIONS[RECOMMENDATIONS.len() - 1])
+}